@@ -1,9 +1,10 @@
+import sys
 import json
-import redis
 
 
 COMMAND_TABLE_FILE = './docs/command_table.json'
 MARKDOWN_TABLE_FILE = './docs/command_table.md'
+RUST_TABLE_FILE = './src/proxy/command_table.rs'
 
 
 def get_existing_command_table():
@@ -18,9 +19,36 @@ def get_existing_command_table():
 def get_commands_from_redis():
     ''' Use COMMAND to get all the commands from Redis
     '''
+    import redis
     client = redis.StrictRedis()
-    commands = client.execute_command("COMMAND")
-    return [cmd[0] for cmd in commands]
+    return client.execute_command("COMMAND")
+
+
+def get_commands_from_file(path):
+    ''' Load the output of `redis-cli --json COMMAND`
+    '''
+    with open(path, 'r') as f:
+        return json.loads(f.read())
+
+
+def to_str(s):
+    if isinstance(s, bytes):
+        return s.decode('utf-8')
+    return s
+
+
+def parse_key_spec(cmd):
+    ''' COMMAND replies [name, arity, flags, first_key, last_key, step, ...]
+    '''
+    flags = [to_str(flag) for flag in cmd[2]]
+    return {
+        'first_key': cmd[3],
+        'last_key': cmd[4],
+        'step': cmd[5],
+        'movable_keys': 'movablekeys' in flags,
+        'readonly': 'readonly' in flags,
+        'write': 'write' in flags,
+    }
 
 
 def generate_markdown(table):
@@ -36,16 +64,52 @@ def generate_markdown(table):
     return '\n'.join(headers + lines)
 
 
-# Need to run a Redis locally to retrieve the commands.
+def generate_rust(table):
+    lines = []
+    lines.append('// Generated by docs/generate_command_table.py. Do not edit.')
+    lines.append('use super::key_spec::KeySpec;')
+    lines.append('')
+    lines.append('// Sorted by the lowercase command names.')
+    lines.append('pub const COMMAND_KEY_SPECS: &[(&str, KeySpec)] = &[')
+    for cmd in sorted(table.keys()):
+        fields = table[cmd]
+        if 'first_key' not in fields:
+            continue
+        lines.append('    (')
+        lines.append('        "{}",'.format(cmd))
+        lines.append('        KeySpec {')
+        lines.append('            first_key: {},'.format(fields['first_key']))
+        lines.append('            last_key: {},'.format(fields['last_key']))
+        lines.append('            step: {},'.format(fields['step']))
+        lines.append('            movable_keys: {},'.format(str(fields['movable_keys']).lower()))
+        lines.append('            readonly: {},'.format(str(fields['readonly']).lower()))
+        lines.append('            write: {},'.format(str(fields['write']).lower()))
+        lines.append('        },')
+        lines.append('    ),')
+    lines.append('];')
+    return '\n'.join(lines) + '\n'
+
+
+# Need to run a Redis locally to retrieve the commands,
+# or pass the output of `redis-cli --json COMMAND` as the first argument.
+# Use `--offline` to only regenerate the files from the existing table.
 if __name__ == '__main__':
     table = get_existing_command_table()
-    for cmd in get_commands_from_redis():
-        if cmd in table:
-            continue
-        table[cmd] = {
-            'supported': False,
-            'desc': ''
-        }
+    if len(sys.argv) > 1 and sys.argv[1] == '--offline':
+        commands = []
+    elif len(sys.argv) > 1:
+        commands = get_commands_from_file(sys.argv[1])
+    else:
+        commands = get_commands_from_redis()
+
+    for cmd in commands:
+        name = to_str(cmd[0])
+        if name not in table:
+            table[name] = {
+                'supported': False,
+                'desc': ''
+            }
+        table[name].update(parse_key_spec(cmd))
 
     content = json.dumps(table, indent=4, sort_keys=True)
     with open(COMMAND_TABLE_FILE, 'w') as f:
@@ -53,3 +117,6 @@ if __name__ == '__main__':
 
     with open(MARKDOWN_TABLE_FILE, 'w') as f:
         f.write(generate_markdown(table))
+
+    with open(RUST_TABLE_FILE, 'w') as f:
+        f.write(generate_rust(table))
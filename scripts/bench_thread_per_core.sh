@@ -0,0 +1,39 @@
+#!/usr/bin/env bash
+# Compares the throughput of the default mode and the thread-per-core mode.
+# Requires redis-server, redis-cli and redis-benchmark in PATH.
+# Run `make release` before this.
+set -e
+
+PROXY_BIN=${PROXY_BIN:-target/release/server_proxy}
+PROXY_PORT=${PROXY_PORT:-5299}
+REDIS_PORT=${REDIS_PORT:-6379}
+THREADS=${THREADS:-4}
+CLIENTS=${CLIENTS:-200}
+REQUESTS=${REQUESTS:-1000000}
+PIPELINE=${PIPELINE:-1}
+
+redis-server --port "${REDIS_PORT}" --save '' --daemonize yes
+trap 'redis-cli -p "${REDIS_PORT}" shutdown nosave > /dev/null 2>&1 || true' EXIT
+
+run_bench() {
+    mode_name=$1
+    thread_per_core=$2
+
+    UNDERMOON_ADDRESS="127.0.0.1:${PROXY_PORT}" \
+    UNDERMOON_ANNOUNCE_ADDRESS="127.0.0.1:${PROXY_PORT}" \
+    UNDERMOON_THREAD_NUMBER="${THREADS}" \
+    UNDERMOON_THREAD_PER_CORE="${thread_per_core}" \
+        "${PROXY_BIN}" conf/server-proxy.toml > /dev/null 2>&1 &
+    proxy_pid=$!
+    sleep 1
+
+    redis-cli -p "${PROXY_PORT}" UMCTL SETCLUSTER 1 NOFLAGS mydb "127.0.0.1:${REDIS_PORT}" 1 0-16383 > /dev/null
+    echo "###### ${mode_name}"
+    redis-benchmark -p "${PROXY_PORT}" -c "${CLIENTS}" -n "${REQUESTS}" -P "${PIPELINE}" -r 100000 -t set,get -q
+
+    kill "${proxy_pid}"
+    wait "${proxy_pid}" || true
+}
+
+run_bench "default mode" false
+run_bench "thread-per-core mode" true
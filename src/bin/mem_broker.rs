@@ -3,70 +3,93 @@ extern crate undermoon;
 #[macro_use]
 extern crate log;
 extern crate config;
-extern crate env_logger;
 use actix_web::{middleware, App, HttpServer};
-use arc_swap::ArcSwap;
 use futures_timer::Delay;
+use std::collections::HashMap;
 use std::env;
-use std::num::NonZeroU64;
 use std::sync::Arc;
 use std::time::Duration;
 use undermoon::broker::{
-    configure_app, JsonFileStorage, JsonMetaReplicator, MemBrokerConfig, MemBrokerService,
-    MetaStorage, MetaStoreError, MetaSyncError,
+    configure_app, gen_broker_openapi, AlertEvent, DeclarativeSpec, JsonFileStorage,
+    JsonMetaReplicator, MemBrokerConfig, MemBrokerConfigFile, MemBrokerService, MetaStorage,
+    MetaStoreError, MetaSyncError, CLUSTER_STATS_RESOLUTION,
 };
+use undermoon::common::cli::{init_logger, CliArgs, CliParser, HELP_FLAG, VERSION_FLAG};
+use undermoon::common::config_file::{load_config, ConfigFileError};
 
-fn gen_conf() -> MemBrokerConfig {
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+const CONFIG_FLAG: &str = "--config";
+const LOG_LEVEL_FLAG: &str = "--log-level";
+const APPLY_FLAG: &str = "--apply";
+// Exits with non-zero code if the config is invalid without starting the broker.
+const VALIDATE_CONFIG_FLAG: &str = "--validate-config";
+const DUMP_OPENAPI_FLAG: &str = "--dump-openapi";
+// (flag, config field) overriding the config file and the env vars.
+const CLI_CONFIG_FIELDS: [(&str, &str); 2] = [
+    ("--address", "address"),
+    ("--meta-filename", "meta_filename"),
+];
+
+fn gen_cli_parser() -> CliParser {
+    CliParser::new("mem_broker")
+        .positional("config_file")
+        .option(
+            CONFIG_FLAG,
+            "config_file",
+            "The config file in TOML, YAML or JSON",
+        )
+        .option("--address", "address", "The address of the HTTP API")
+        .option("--meta-filename", "path", "The file to store the metadata")
+        .option(
+            LOG_LEVEL_FLAG,
+            "filters",
+            "The log filters like RUST_LOG, e.g. info or undermoon=debug",
+        )
+        .option(APPLY_FLAG, "spec_file", "Applies the declarative spec file")
+        .flag(
+            VALIDATE_CONFIG_FLAG,
+            "Only validates the config and the spec file",
+        )
+        .flag(
+            DUMP_OPENAPI_FLAG,
+            "Prints the OpenAPI document of the HTTP API",
+        )
+}
+
+fn gen_cli_config(args: &CliArgs) -> HashMap<String, String> {
+    CLI_CONFIG_FIELDS
+        .iter()
+        .filter_map(|(flag, field)| args.get(flag).map(|v| (field.to_string(), v.to_string())))
+        .collect()
+}
+
+fn invalid_input<E>(err: E) -> std::io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, err)
+}
+
+// The format is decided by the file extension, e.g. spec.yaml, spec.toml or spec.json.
+fn load_spec(spec_file_path: &str) -> Result<DeclarativeSpec, config::ConfigError> {
     let mut s = config::Config::new();
-    if let Some(conf_file_path) = env::args().nth(1) {
-        s.merge(config::File::with_name(&conf_file_path))
-            .map(|_| ())
-            .unwrap_or_else(|e| warn!("failed to read config file: {:?}", e));
-    }
-    // e.g. UNDERMOON_ADDRESS='127.0.0.1:7799'
-    s.merge(config::Environment::with_prefix("undermoon"))
-        .map(|_| ())
-        .unwrap_or_else(|e| warn!("failed to read config from env vars {:?}", e));
-
-    let replica_addresses = s
-        .get::<Vec<String>>("replica_addresses")
-        .unwrap_or_else(|_| {
-            s.get::<String>("replica_addresses")
-                .unwrap_or_else(|_| String::new())
-                .split_terminator(',')
-                .map(|s| s.to_string())
-                .collect()
-        });
-    let replica_addresses = Arc::new(ArcSwap::new(Arc::new(replica_addresses)));
-
-    let debug = s.get::<bool>("debug").unwrap_or(false);
-
-    MemBrokerConfig {
-        address: s
-            .get::<String>("address")
-            .unwrap_or_else(|_| "127.0.0.1:7799".to_string()),
-        failure_ttl: s.get::<u64>("failure_ttl").unwrap_or_else(|_| 60),
-        failure_quorum: s.get::<u64>("failure_quorum").unwrap_or_else(|_| 1),
-        migration_limit: s.get::<u64>("migration_limit").unwrap_or_else(|_| 1),
-        recover_from_meta_file: s
-            .get::<bool>("recover_from_meta_file")
-            .unwrap_or_else(|_| false),
-        meta_filename: s
-            .get::<String>("meta_filename")
-            .unwrap_or_else(|_| "metadata".to_string()),
-        auto_update_meta_file: s
-            .get::<bool>("auto_update_meta_file")
-            .unwrap_or_else(|_| false),
-        update_meta_file_interval: NonZeroU64::new(
-            s.get::<u64>("update_meta_file_interval")
-                .unwrap_or_else(|_| 0),
-        ),
-        replica_addresses,
-        sync_meta_interval: NonZeroU64::new(
-            s.get::<u64>("sync_meta_interval").unwrap_or_else(|_| 0),
-        ),
-        debug,
-    }
+    s.merge(config::File::with_name(spec_file_path))?;
+    s.try_into()
+}
+
+fn gen_conf(
+    conf_file_path: Option<&str>,
+    cli_config: &HashMap<String, String>,
+) -> Result<MemBrokerConfig, ConfigFileError> {
+    let config_file: MemBrokerConfigFile = load_config(conf_file_path, None, cli_config)?;
+    config_file.into_config()
 }
 
 fn meta_sync_error_to_io_err(err: MetaSyncError) -> std::io::Error {
@@ -109,14 +132,115 @@ async fn sync_meta_to_replicas(service: Arc<MemBrokerService>, interval: Duratio
     }
 }
 
+async fn send_alert_webhook(client: &reqwest::Client, webhook: &str, event: &AlertEvent) {
+    match client.post(webhook).json(event).send().await {
+        Ok(response) if response.status().is_success() => (),
+        Ok(response) => error!(
+            "failed to send alert to {}: status code {:?}",
+            webhook,
+            response.status()
+        ),
+        Err(err) => error!("failed to send alert to {}: {}", webhook, err),
+    }
+}
+
+async fn check_alerts(service: Arc<MemBrokerService>, interval: Duration) {
+    let client = reqwest::Client::new();
+    loop {
+        Delay::new(interval).await;
+        trace!("periodically check alerts");
+        for (event, webhook) in service.check_alerts().into_iter() {
+            warn!("alert fired: {:?}", event);
+            if let Some(webhook) = webhook {
+                send_alert_webhook(&client, &webhook, &event).await;
+            }
+        }
+    }
+}
+
+async fn auto_balance(service: Arc<MemBrokerService>, interval: Duration) {
+    loop {
+        Delay::new(interval).await;
+        trace!("periodically check the balance of clusters");
+        match service.auto_balance().await {
+            Ok(cluster_names) => {
+                for cluster_name in cluster_names.into_iter() {
+                    info!("start migration to balance cluster {}", cluster_name);
+                }
+            }
+            Err(err) => error!("failed to balance clusters: {}", err),
+        }
+    }
+}
+
+async fn auto_failback(service: Arc<MemBrokerService>, interval: Duration) {
+    loop {
+        Delay::new(interval).await;
+        trace!("periodically check the failback of clusters");
+        match service.auto_failback().await {
+            Ok(cluster_names) => {
+                for cluster_name in cluster_names.into_iter() {
+                    info!("moved the masters back for cluster {}", cluster_name);
+                }
+            }
+            Err(err) => error!("failed to fail back clusters: {}", err),
+        }
+    }
+}
+
+async fn record_cluster_stats(service: Arc<MemBrokerService>) {
+    let interval = Duration::from_secs(CLUSTER_STATS_RESOLUTION as u64);
+    loop {
+        Delay::new(interval).await;
+        trace!("periodically record cluster stats");
+        service.record_cluster_stats();
+    }
+}
+
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init();
+    let parser = gen_cli_parser();
+    let args = parser.parse(env::args().skip(1)).map_err(|err| {
+        eprintln!("{}\n\n{}", err, parser.usage());
+        invalid_input(err)
+    })?;
+    if args.has_flag(HELP_FLAG) {
+        println!("{}", parser.usage());
+        return Ok(());
+    }
+    if args.has_flag(VERSION_FLAG) {
+        println!("{}", parser.version());
+        return Ok(());
+    }
+    if args.has_flag(DUMP_OPENAPI_FLAG) {
+        let doc = gen_broker_openapi();
+        println!("{}", serde_json::to_string_pretty(&doc).unwrap_or_default());
+        return Ok(());
+    }
 
-    let config = gen_conf();
+    init_logger(args.get(LOG_LEVEL_FLAG));
+    let conf_file_path = args.get(CONFIG_FLAG).or_else(|| args.get_positional());
+    let spec_file_path = args.get(APPLY_FLAG).map(|s| s.to_string());
+    let config = gen_conf(conf_file_path, &gen_cli_config(&args)).map_err(|err| {
+        error!("invalid config: {}", err);
+        invalid_input(err)
+    })?;
+    if args.has_flag(VALIDATE_CONFIG_FLAG) {
+        if let Some(spec_file_path) = spec_file_path.as_ref() {
+            load_spec(spec_file_path).map_err(|err| {
+                error!("invalid spec file {}: {}", spec_file_path, err);
+                invalid_input(err)
+            })?;
+        }
+        println!("config is valid");
+        return Ok(());
+    }
     let address = config.address.clone();
     let update_file_interval = config.update_meta_file_interval;
     let sync_meta_interval = config.sync_meta_interval;
+    let check_alert_interval = config.check_alert_interval;
+    let auto_balance_interval = config.auto_balance_interval;
+    let auto_failback_interval = config.auto_failback_interval;
 
     let meta_storage = Arc::new(JsonFileStorage::new(config.meta_filename.clone()));
     let meta_store = if config.recover_from_meta_file {
@@ -136,6 +260,20 @@ async fn main() -> std::io::Result<()> {
         .map_err(meta_error_to_io_error)?;
     let service = Arc::new(service);
 
+    if let Some(spec_file_path) = spec_file_path {
+        let spec = load_spec(&spec_file_path).map_err(|err| {
+            error!("failed to read spec file {}: {:?}", spec_file_path, err);
+            invalid_input(err)
+        })?;
+        let plan = service
+            .apply_spec(&spec, false, true)
+            .map_err(meta_error_to_io_error)?;
+        info!("applied spec file {}: {:?}", spec_file_path, plan.steps);
+        for skipped in plan.skipped.iter() {
+            warn!("not applied: {}", skipped);
+        }
+    }
+
     if let Some(interval) = update_file_interval {
         info!("start periodically updating meta file");
         let interval = Duration::from_secs(interval.get());
@@ -148,6 +286,26 @@ async fn main() -> std::io::Result<()> {
         actix_rt::spawn(sync_meta_to_replicas(service.clone(), interval));
     }
 
+    actix_rt::spawn(record_cluster_stats(service.clone()));
+
+    if let Some(interval) = check_alert_interval {
+        info!("start periodically checking alerts");
+        let interval = Duration::from_secs(interval.get());
+        actix_rt::spawn(check_alerts(service.clone(), interval));
+    }
+
+    if let Some(interval) = auto_balance_interval {
+        info!("start periodically balancing clusters");
+        let interval = Duration::from_secs(interval.get());
+        actix_rt::spawn(auto_balance(service.clone(), interval));
+    }
+
+    if let Some(interval) = auto_failback_interval {
+        info!("start periodically failing back clusters");
+        let interval = Duration::from_secs(interval.get());
+        actix_rt::spawn(auto_failback(service.clone(), interval));
+    }
+
     HttpServer::new(move || {
         let service = service.clone();
         App::new()
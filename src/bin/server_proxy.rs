@@ -2,171 +2,187 @@ extern crate tokio;
 extern crate undermoon;
 #[macro_use]
 extern crate log;
-extern crate arc_swap;
 extern crate config;
-extern crate env_logger;
 
-use arc_swap::ArcSwap;
-use std::cmp::min;
+use std::collections::HashMap;
 use std::env;
 use std::error::Error;
-use std::num::NonZeroUsize;
-use std::sync::atomic::{AtomicI64, AtomicU64};
 use std::sync::Arc;
-use std::time::Duration;
-use string_error::into_err;
+use std::thread;
+use undermoon::common::cli::{init_logger, CliArgs, CliParser, HELP_FLAG, VERSION_FLAG};
 use undermoon::common::config::ClusterConfig;
-use undermoon::common::track::TrackedFutureRegistry;
-use undermoon::protocol::SimpleRedisClientFactory;
-use undermoon::proxy::backend::DefaultConnFactory;
-use undermoon::proxy::executor::SharedForwardHandler;
-use undermoon::proxy::manager::MetaMap;
-use undermoon::proxy::service::{ServerProxyConfig, ServerProxyService};
-use undermoon::proxy::slowlog::SlowRequestLogger;
-use undermoon::MAX_REDIRECTIONS;
-
-fn gen_conf() -> Result<(ServerProxyConfig, ClusterConfig), &'static str> {
-    let mut s = config::Config::new();
-    // If config file is specified, load it.
-    if let Some(conf_file_path) = env::args().nth(1) {
-        s.merge(config::File::with_name(&conf_file_path))
-            .map(|_| ())
-            .unwrap_or_else(|e| warn!("failed to read config file: {:?}", e));
-    }
-    // e.g. UNDERMOON_ADDRESS='127.0.0.1:5299'
-    s.merge(config::Environment::with_prefix("undermoon"))
-        .map(|_| ())
-        .unwrap_or_else(|e| warn!("failed to read config from env vars: {:?}", e));
+use undermoon::common::config_file::{config_from_map, load_config, ConfigFileError};
+use undermoon::proxy::broker_config::{
+    fetch_proxy_config, watch_proxy_config, BROKER_CONFIG_INTERVAL,
+};
+use undermoon::proxy::builder::ProxyBuilder;
+use undermoon::proxy::config_file::ServerProxyConfigFile;
+use undermoon::proxy::key_spec::dump_command_table;
+use undermoon::proxy::service::ServerProxyConfig;
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+const CONFIG_FLAG: &str = "--config";
+const LOG_LEVEL_FLAG: &str = "--log-level";
+// Fetches the config from the broker and keeps applying its changes.
+const BROKER_ADDRESS_FLAG: &str = "--broker-address";
+// Exits with non-zero code if the config is invalid without starting the proxy.
+const VALIDATE_CONFIG_FLAG: &str = "--validate-config";
+// Prints `<command> <first_key> <last_key> <step> [movablekeys] [readonly] [write]` for each command.
+const DUMP_COMMAND_TABLE_FLAG: &str = "--dump-command-table";
+// (flag, config field) overriding the config file and the env vars.
+const CLI_CONFIG_FIELDS: [(&str, &str); 2] = [
+    ("--address", "address"),
+    ("--announce-address", "announce_address"),
+];
+
+fn gen_cli_parser() -> CliParser {
+    CliParser::new("server_proxy")
+        .positional("config_file")
+        .option(
+            CONFIG_FLAG,
+            "config_file",
+            "The config file in TOML, YAML or JSON",
+        )
+        .option("--address", "address", "The address to listen on")
+        .option(
+            "--announce-address",
+            "address",
+            "The address registered in the broker",
+        )
+        .option(
+            LOG_LEVEL_FLAG,
+            "filters",
+            "The log filters like RUST_LOG, e.g. info or undermoon=debug",
+        )
+        .option(
+            BROKER_ADDRESS_FLAG,
+            "address",
+            "Fetches the config from the memory broker",
+        )
+        .flag(VALIDATE_CONFIG_FLAG, "Only validates the config")
+        .flag(DUMP_COMMAND_TABLE_FLAG, "Prints the command table")
+}
 
-    let address = s
-        .get::<String>("address")
-        .unwrap_or_else(|_| "127.0.0.1:5299".to_string());
+fn gen_cli_config(args: &CliArgs) -> HashMap<String, String> {
+    CLI_CONFIG_FIELDS
+        .iter()
+        .filter_map(|(flag, field)| args.get(flag).map(|v| (field.to_string(), v.to_string())))
+        .collect()
+}
 
-    let slowlog_len = NonZeroUsize::new(s.get::<usize>("slowlog_len").unwrap_or_else(|_| 1024))
-        .ok_or_else(|| "slowlog_len")?;
-    let thread_number = NonZeroUsize::new(s.get::<usize>("thread_number").unwrap_or_else(|_| 2))
-        .ok_or_else(|| "thread_number")?;
-    let backend_conn_num =
-        NonZeroUsize::new(s.get::<usize>("backend_conn_num").unwrap_or_else(|_| 2))
-            .ok_or_else(|| "backend_conn_num")?;
-    let backend_batch_buf =
-        NonZeroUsize::new(s.get::<usize>("backend_batch_buf").unwrap_or_else(|_| 10))
-            .ok_or_else(|| "backend_batch_buf")?;
-    let session_batch_buf =
-        NonZeroUsize::new(s.get::<usize>("session_batch_buf").unwrap_or_else(|_| 10))
-            .ok_or_else(|| "session_batch_buf")?;
+fn fetch_broker_config(broker_address: &str) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut runtime = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()?;
+    let client = reqwest::Client::new();
+    let broker_config = runtime.block_on(fetch_proxy_config(&client, broker_address))?;
+    Ok(broker_config)
+}
 
-    let mut max_redirections = s.get::<usize>("max_redirections").unwrap_or_else(|_| 0);
-    if max_redirections != 0 {
-        max_redirections = min(MAX_REDIRECTIONS, max_redirections);
-    }
-    let max_redirections = NonZeroUsize::new(max_redirections);
+fn spawn_broker_config_watcher(
+    config: Arc<ServerProxyConfig>,
+    broker_address: String,
+    broker_config: HashMap<String, String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut runtime = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()?;
+    thread::Builder::new()
+        .name("broker-config-watcher".to_string())
+        .spawn(move || {
+            let client = reqwest::Client::new();
+            runtime.block_on(watch_proxy_config(
+                config,
+                client,
+                broker_address,
+                broker_config,
+                BROKER_CONFIG_INTERVAL,
+            ))
+        })?;
+    Ok(())
+}
 
-    let config = ServerProxyConfig {
-        address: address.clone(),
-        announce_address: s
-            .get::<String>("announce_address")
-            .unwrap_or_else(|_| address),
-        auto_select_cluster: s
-            .get::<bool>("auto_select_cluster")
-            .unwrap_or_else(|_| true),
-        slowlog_len,
-        slowlog_log_slower_than: AtomicI64::new(
-            s.get::<i64>("slowlog_log_slower_than")
-                .unwrap_or_else(|_| 50000),
-        ),
-        slowlog_sample_rate: AtomicU64::new(
-            s.get::<u64>("slowlog_sample_rate").unwrap_or_else(|_| 1000),
-        ),
-        thread_number,
-        session_channel_size: s
-            .get::<usize>("session_channel_size")
-            .unwrap_or_else(|_| 4096),
-        backend_channel_size: s
-            .get::<usize>("backend_channel_size")
-            .unwrap_or_else(|_| 4096),
-        backend_conn_num,
-        backend_batch_min_time: s
-            .get::<usize>("backend_batch_min_time")
-            .unwrap_or_else(|_| 20000),
-        backend_batch_max_time: s
-            .get::<usize>("backend_batch_max_time")
-            .unwrap_or_else(|_| 400_000),
-        backend_batch_buf,
-        session_batch_min_time: s
-            .get::<usize>("session_batch_min_time")
-            .unwrap_or_else(|_| 20000),
-        session_batch_max_time: s
-            .get::<usize>("session_batch_max_time")
-            .unwrap_or_else(|_| 400_000),
-        session_batch_buf,
-        active_redirection: s
-            .get::<bool>("active_redirection")
-            .unwrap_or_else(|_| false),
-        max_redirections,
+// The config from the broker overrides the config file but not the env vars.
+fn gen_conf(
+    conf_file_path: Option<&str>,
+    broker_config: Option<&HashMap<String, String>>,
+    cli_config: &HashMap<String, String>,
+) -> Result<(ServerProxyConfig, ClusterConfig), ConfigFileError> {
+    let broker_source = match broker_config {
+        Some(broker_config) => Some(config_from_map(broker_config)?),
+        None => None,
     };
-
-    let mut cluster_config = ClusterConfig::default();
-    let cluster_fields = [
-        "compression_strategy",
-        "migration_max_migration_time",
-        "migration_max_blocking_time",
-        "migration_scan_interval",
-        "migration_scan_count",
-    ];
-    for field in cluster_fields.iter() {
-        if let Ok(value) = s.get::<String>(*field) {
-            if cluster_config.set_field(*field, value.as_str()).is_err() {
-                return Err(*field);
-            }
-        }
-    }
-
-    Ok((config, cluster_config))
+    let config_file: ServerProxyConfigFile =
+        load_config(conf_file_path, broker_source, cli_config)?;
+    config_file.into_config()
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::init();
-    let (config, cluster_config) = gen_conf().map_err(|field| {
-        let err_msg = format!("invalid field {}", field);
-        into_err(err_msg)
+    let parser = gen_cli_parser();
+    let args = parser.parse(env::args().skip(1)).map_err(|err| {
+        eprintln!("{}\n\n{}", err, parser.usage());
+        err
+    })?;
+    if args.has_flag(HELP_FLAG) {
+        println!("{}", parser.usage());
+        return Ok(());
+    }
+    if args.has_flag(VERSION_FLAG) {
+        println!("{}", parser.version());
+        return Ok(());
+    }
+    if args.has_flag(DUMP_COMMAND_TABLE_FLAG) {
+        println!("{}", dump_command_table());
+        return Ok(());
+    }
+
+    init_logger(args.get(LOG_LEVEL_FLAG));
+    let conf_file_path = args.get(CONFIG_FLAG).or_else(|| args.get_positional());
+    let broker_address = args.get(BROKER_ADDRESS_FLAG).map(|s| s.to_string());
+    let broker_config = match broker_address.as_ref() {
+        Some(broker_address) => {
+            let broker_config = fetch_broker_config(broker_address).map_err(|err| {
+                error!(
+                    "failed to fetch config from broker {}: {}",
+                    broker_address, err
+                );
+                err
+            })?;
+            Some(broker_config)
+        }
+        None => None,
+    };
+    let (config, cluster_config) = gen_conf(
+        conf_file_path,
+        broker_config.as_ref(),
+        &gen_cli_config(&args),
+    )
+    .map_err(|err| {
+        error!("invalid config: {}", err);
+        err
     })?;
 
     info!("config: {:?}", config);
     info!("cluster default config: {:?}", cluster_config);
+    if args.has_flag(VALIDATE_CONFIG_FLAG) {
+        println!("config is valid");
+        return Ok(());
+    }
 
-    let config = Arc::new(config);
-
-    let timeout = Duration::new(1, 0);
-    let client_factory = SimpleRedisClientFactory::new(timeout);
-
-    let slow_request_logger = Arc::new(SlowRequestLogger::new(config.clone()));
-    let meta_map = Arc::new(ArcSwap::new(Arc::new(MetaMap::empty())));
-    let future_registry = Arc::new(TrackedFutureRegistry::default());
-
-    let forward_handler = SharedForwardHandler::new(
-        config.clone(),
-        cluster_config,
-        Arc::new(client_factory),
-        slow_request_logger.clone(),
-        meta_map,
-        Arc::new(DefaultConnFactory::default()),
-        future_registry.clone(),
-    );
-    let server = ServerProxyService::new(
-        config.clone(),
-        forward_handler,
-        slow_request_logger,
-        future_registry,
-    );
-
-    let mut runtime = tokio::runtime::Builder::new()
-        .threaded_scheduler()
-        .core_threads(config.thread_number.get())
-        .enable_all()
-        .build()?;
-
-    if let Err(err) = runtime.block_on(server.run()) {
+    let builder = ProxyBuilder::new(config, cluster_config);
+    if let (Some(broker_address), Some(broker_config)) = (broker_address, broker_config) {
+        spawn_broker_config_watcher(builder.get_config(), broker_address, broker_config)?;
+    }
+    if let Err(err) = builder.run() {
         error!("tokio runtime failed: {}", err);
         return Err(err);
     }
@@ -0,0 +1,205 @@
+use super::store::{MetaStore, MetaStoreError};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    ProxyQps,
+    ProxyErrorRate,
+    // In seconds
+    ReplicationLag,
+    // In seconds
+    MigrationElapsed,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AlertRule {
+    pub metric: AlertMetric,
+    // Fires when the value is larger than the threshold.
+    pub threshold: f64,
+    // The events will be posted to this url in addition to the logs.
+    #[serde(default)]
+    pub webhook: Option<String>,
+}
+
+impl AlertRule {
+    pub fn validate(&self) -> Result<(), MetaStoreError> {
+        if self.threshold.is_finite() && self.threshold >= 0.0 {
+            return Ok(());
+        }
+        Err(MetaStoreError::InvalidConfig {
+            key: "threshold".to_string(),
+            value: self.threshold.to_string(),
+            error: "should be a non-negative number".to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AlertEvent {
+    pub rule_name: String,
+    pub metric: AlertMetric,
+    // Proxy address, or cluster name and slots for the migration.
+    pub target: String,
+    pub value: f64,
+    pub threshold: f64,
+    // Unix timestamp in seconds of the first time it fires.
+    pub time: i64,
+}
+
+impl AlertEvent {
+    pub fn get_key(&self) -> (String, String) {
+        (self.rule_name.clone(), self.target.clone())
+    }
+}
+
+pub struct AlertChecker<'a> {
+    store: &'a MetaStore,
+}
+
+impl<'a> AlertChecker<'a> {
+    pub fn new(store: &'a MetaStore) -> Self {
+        Self { store }
+    }
+
+    pub fn check(&self, now: i64) -> Vec<AlertEvent> {
+        let mut events = vec![];
+        for (rule_name, rule) in self.store.alert_rules.iter() {
+            for (target, value) in self.get_values(rule.metric, now) {
+                if value > rule.threshold {
+                    events.push(AlertEvent {
+                        rule_name: rule_name.clone(),
+                        metric: rule.metric,
+                        target,
+                        value,
+                        threshold: rule.threshold,
+                        time: now,
+                    });
+                }
+            }
+        }
+        events
+    }
+
+    fn get_values(&self, metric: AlertMetric, now: i64) -> Vec<(String, f64)> {
+        let proxy_stats = self.store.proxy_stats.iter();
+        match metric {
+            AlertMetric::ProxyQps => proxy_stats
+                .map(|(address, sample)| (address.clone(), sample.qps))
+                .collect(),
+            AlertMetric::ProxyErrorRate => proxy_stats
+                .map(|(address, sample)| (address.clone(), sample.error_rate))
+                .collect(),
+            AlertMetric::ReplicationLag => proxy_stats
+                .map(|(address, sample)| (address.clone(), sample.stats.replication_lag as f64))
+                .collect(),
+            AlertMetric::MigrationElapsed => self
+                .store
+                .get_migration_tasks(now)
+                .into_iter()
+                .filter_map(|task| {
+                    let target = format!(
+                        "{} {}",
+                        task.cluster_name,
+                        task.range_list.to_strings().join(" ")
+                    );
+                    task.elapsed.map(|elapsed| (target, elapsed as f64))
+                })
+                .collect(),
+        }
+    }
+}
+
+// Only returns the events which are not firing in the last check.
+pub fn update_active_alerts(
+    active_alerts: &mut HashMap<(String, String), AlertEvent>,
+    events: Vec<AlertEvent>,
+) -> Vec<AlertEvent> {
+    let mut new_active_alerts = HashMap::new();
+    let mut new_events = vec![];
+    for mut event in events.into_iter() {
+        let key = event.get_key();
+        match active_alerts.remove(&key) {
+            Some(last) => event.time = last.time,
+            None => new_events.push(event.clone()),
+        }
+        new_active_alerts.insert(key, event);
+    }
+    for (rule_name, target) in active_alerts.keys() {
+        info!("alert {} of {} is resolved", rule_name, target);
+    }
+    *active_alerts = new_active_alerts;
+    new_events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::ProxyRuntimeStats;
+
+    #[test]
+    fn test_alert_rule_validate() {
+        let mut rule = AlertRule {
+            metric: AlertMetric::ProxyQps,
+            threshold: 1000.0,
+            webhook: None,
+        };
+        assert!(rule.validate().is_ok());
+        rule.threshold = -1.0;
+        assert!(rule.validate().is_err());
+        rule.threshold = f64::NAN;
+        assert!(rule.validate().is_err());
+    }
+
+    #[test]
+    fn test_check_alerts() {
+        let mut store = MetaStore::default();
+        store
+            .add_proxy(
+                "127.0.0.1:7001".to_string(),
+                ["127.0.0.1:6001".to_string(), "127.0.0.1:6002".to_string()],
+                None,
+                None,
+            )
+            .unwrap();
+        let stats = ProxyRuntimeStats {
+            replication_lag: 5,
+            ..Default::default()
+        };
+        store
+            .report_proxy_stats("127.0.0.1:7001".to_string(), stats, 100)
+            .unwrap();
+
+        let rule = AlertRule {
+            metric: AlertMetric::ReplicationLag,
+            threshold: 3.0,
+            webhook: None,
+        };
+        store.set_alert_rule("lag".to_string(), rule).unwrap();
+        let rule = AlertRule {
+            metric: AlertMetric::ProxyQps,
+            threshold: 1000.0,
+            webhook: None,
+        };
+        store.set_alert_rule("qps".to_string(), rule).unwrap();
+
+        let events = AlertChecker::new(&store).check(100);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule_name, "lag");
+        assert_eq!(events[0].target, "127.0.0.1:7001");
+        assert_eq!(events[0].value, 5.0);
+
+        let mut active_alerts = HashMap::new();
+        let new_events = update_active_alerts(&mut active_alerts, events);
+        assert_eq!(new_events.len(), 1);
+        // The firing alerts are not reported again.
+        let events = AlertChecker::new(&store).check(110);
+        assert!(update_active_alerts(&mut active_alerts, events).is_empty());
+        assert_eq!(active_alerts.values().next().unwrap().time, 100);
+
+        store.remove_alert_rule("lag").unwrap();
+        let events = AlertChecker::new(&store).check(120);
+        assert!(update_active_alerts(&mut active_alerts, events).is_empty());
+        assert!(active_alerts.is_empty());
+    }
+}
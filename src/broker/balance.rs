@@ -0,0 +1,454 @@
+use super::history::CLUSTER_STATS_RESOLUTION;
+use super::store::{ClusterStore, MigrationSlotRangeStore, ProxyStatsSample, CHUNK_PARTS};
+use crate::common::cluster::{Range, SLOT_LOAD_BUCKETS, SLOT_LOAD_BUCKET_SIZE};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+// A daily time window in UTC, e.g. "02:00-04:00".
+// It wraps around midnight when the end is earlier than the start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaintenanceWindow {
+    // minutes of the day
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidMaintenanceWindow;
+
+fn parse_minutes(s: &str) -> Result<u32, InvalidMaintenanceWindow> {
+    let mut parts = s.trim().splitn(2, ':');
+    let (hour, minute) = match (parts.next(), parts.next()) {
+        (Some(hour), Some(minute)) => (hour, minute),
+        _ => return Err(InvalidMaintenanceWindow),
+    };
+    let hour = u32::from_str(hour).map_err(|_| InvalidMaintenanceWindow)?;
+    let minute = u32::from_str(minute).map_err(|_| InvalidMaintenanceWindow)?;
+    if hour >= 24 || minute >= 60 {
+        return Err(InvalidMaintenanceWindow);
+    }
+    Ok(hour * 60 + minute)
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = InvalidMaintenanceWindow;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '-');
+        match (parts.next(), parts.next()) {
+            (Some(start), Some(end)) => Ok(Self {
+                start: parse_minutes(start)?,
+                end: parse_minutes(end)?,
+            }),
+            _ => Err(InvalidMaintenanceWindow),
+        }
+    }
+}
+
+impl MaintenanceWindow {
+    // `now` is the unix timestamp in seconds.
+    pub fn contains(&self, now: i64) -> bool {
+        let minute = ((now / 60).rem_euclid(i64::from(MINUTES_PER_DAY))) as u32;
+        if self.start <= self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            self.start <= minute || minute < self.end
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BalancePolicy {
+    // Balances the cluster when (max - min) / average of the master loads exceeds it.
+    pub threshold: f64,
+    // The limit of the migrations started in each run across all the clusters.
+    pub max_migrations: usize,
+    // Always allowed when it's None.
+    pub window: Option<MaintenanceWindow>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceMigration {
+    pub src_chunk_index: usize,
+    pub src_chunk_part: usize,
+    pub dst_chunk_index: usize,
+    pub dst_chunk_part: usize,
+    pub slot_num: usize,
+}
+
+struct MasterLoad {
+    chunk_index: usize,
+    chunk_part: usize,
+    slots: usize,
+    load: f64,
+    used: bool,
+    // The loads of the slots in the order of the slot ranges
+    // when all the proxies report the qps of the slot buckets.
+    slot_loads: Option<Vec<f64>>,
+}
+
+// The load of a master is the qps of the slot buckets of its proxy shared by the slots
+// so that the hot slots could be spread across the masters.
+// Falls back to the qps of its proxy shared by the slots for the proxies of the older versions,
+// and then the slot number when some proxies have no recent stats.
+fn get_master_loads(
+    cluster: &ClusterStore,
+    proxy_stats: &HashMap<String, ProxyStatsSample>,
+    now: i64,
+) -> Option<Vec<MasterLoad>> {
+    let mut masters = vec![];
+    let mut proxy_slots: HashMap<&str, usize> = HashMap::new();
+    for (chunk_index, chunk) in cluster.chunks.iter().enumerate() {
+        for chunk_part in 0..CHUNK_PARTS {
+            // Leave the empty chunks to the scaling out.
+            let range_list = chunk.stable_slots[chunk_part]
+                .as_ref()
+                .map(|slot_range| slot_range.get_range_list())?;
+            let slots = range_list.get_slots_num();
+            if slots == 0 {
+                return None;
+            }
+            let proxy_index =
+                MigrationSlotRangeStore::chunk_part_to_proxy_index(chunk_part, chunk.role_position);
+            let proxy_address = chunk.proxy_addresses[proxy_index].as_str();
+            *proxy_slots.entry(proxy_address).or_insert(0) += slots;
+            masters.push((
+                proxy_address,
+                range_list.get_ranges(),
+                MasterLoad {
+                    chunk_index,
+                    chunk_part,
+                    slots,
+                    load: slots as f64,
+                    used: false,
+                    slot_loads: None,
+                },
+            ));
+        }
+    }
+
+    let recent_sample = |proxy_address: &str| {
+        proxy_stats
+            .get(proxy_address)
+            .filter(|sample| now - sample.time <= CLUSTER_STATS_RESOLUTION)
+    };
+    let recent_qps = |proxy_address: &str| recent_sample(proxy_address).map(|sample| sample.qps);
+    let qps: Option<Vec<f64>> = masters
+        .iter()
+        .map(|(proxy_address, _, _)| recent_qps(proxy_address))
+        .collect();
+    let use_qps = match qps {
+        Some(qps) => qps.iter().sum::<f64>() > 0.0,
+        None => false,
+    };
+    let slot_qps: Option<HashMap<&str, &[f64]>> = masters
+        .iter()
+        .map(|(proxy_address, _, _)| {
+            recent_sample(proxy_address)
+                .map(|sample| sample.slot_qps.as_slice())
+                .filter(|slot_qps| slot_qps.len() == SLOT_LOAD_BUCKETS)
+                .map(|slot_qps| (*proxy_address, slot_qps))
+        })
+        .collect();
+    let slot_qps = slot_qps.filter(|slot_qps| {
+        slot_qps
+            .values()
+            .map(|qps| qps.iter().sum::<f64>())
+            .sum::<f64>()
+            > 0.0
+    });
+
+    if let Some(slot_qps) = slot_qps {
+        // The load of a bucket is shared by the slots of the proxy in it.
+        let mut proxy_bucket_slots: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (proxy_address, ranges, _) in masters.iter() {
+            let bucket_slots = proxy_bucket_slots
+                .entry(proxy_address)
+                .or_insert_with(|| vec![0; SLOT_LOAD_BUCKETS]);
+            for slot in iter_slots(ranges) {
+                bucket_slots[slot / SLOT_LOAD_BUCKET_SIZE] += 1;
+            }
+        }
+        let masters = masters
+            .into_iter()
+            .map(|(proxy_address, ranges, mut master)| {
+                let bucket_qps = slot_qps[proxy_address];
+                let bucket_slots = &proxy_bucket_slots[proxy_address];
+                let slot_loads: Vec<f64> = iter_slots(ranges)
+                    .map(|slot| {
+                        let bucket = slot / SLOT_LOAD_BUCKET_SIZE;
+                        bucket_qps[bucket] / bucket_slots[bucket] as f64
+                    })
+                    .collect();
+                master.load = slot_loads.iter().sum();
+                master.slot_loads = Some(slot_loads);
+                master
+            })
+            .collect();
+        return Some(masters);
+    }
+
+    let masters = masters
+        .into_iter()
+        .map(|(proxy_address, _, mut master)| {
+            if use_qps {
+                let qps = recent_qps(proxy_address).unwrap_or(0.0);
+                let slots = proxy_slots.get(proxy_address).cloned().unwrap_or(1);
+                master.load = qps * master.slots as f64 / slots as f64;
+            }
+            master
+        })
+        .collect();
+    Some(masters)
+}
+
+fn iter_slots(ranges: &[Range]) -> impl Iterator<Item = usize> + '_ {
+    ranges.iter().flat_map(|range| range.start()..=range.end())
+}
+
+// The slots are taken from the end of the ranges
+// until the moved load is closest to the target.
+fn get_slots_to_move(slot_loads: &[f64], target: f64) -> (usize, f64) {
+    let mut slot_num = 0;
+    let mut moved_load = 0.0;
+    // Keep at least one slot.
+    for load in slot_loads.iter().skip(1).rev() {
+        let next = moved_load + load;
+        if next <= target {
+            slot_num += 1;
+            moved_load = next;
+            continue;
+        }
+        if next - target < target - moved_load {
+            slot_num += 1;
+            moved_load = next;
+        }
+        break;
+    }
+    (slot_num, moved_load)
+}
+
+fn get_imbalance(masters: &[MasterLoad]) -> f64 {
+    let total: f64 = masters.iter().map(|master| master.load).sum();
+    if masters.is_empty() || total <= 0.0 {
+        return 0.0;
+    }
+    let average = total / masters.len() as f64;
+    let max = masters.iter().map(|m| m.load).fold(f64::MIN, f64::max);
+    let min = masters.iter().map(|m| m.load).fold(f64::MAX, f64::min);
+    (max - min) / average
+}
+
+// Each master is in at most one of the migrations.
+// The loads are assumed to move along with the slots.
+pub fn plan_balance(
+    cluster: &ClusterStore,
+    proxy_stats: &HashMap<String, ProxyStatsSample>,
+    now: i64,
+    policy: &BalancePolicy,
+) -> Vec<BalanceMigration> {
+    let mut masters = match get_master_loads(cluster, proxy_stats, now) {
+        Some(masters) => masters,
+        None => return vec![],
+    };
+
+    let mut migrations = vec![];
+    while migrations.len() < policy.max_migrations {
+        if get_imbalance(&masters) <= policy.threshold {
+            break;
+        }
+        let unused = masters
+            .iter()
+            .enumerate()
+            .filter(|(_, master)| !master.used);
+        let src = unused
+            .clone()
+            .max_by(|(_, a), (_, b)| a.load.partial_cmp(&b.load).expect("plan_balance"));
+        let dst =
+            unused.min_by(|(_, a), (_, b)| a.load.partial_cmp(&b.load).expect("plan_balance"));
+        let (src, dst) = match (src, dst) {
+            (Some((src, _)), Some((dst, _))) if src != dst => (src, dst),
+            _ => break,
+        };
+
+        let (src_slots, src_load) = (masters[src].slots, masters[src].load);
+        let dst_load = masters[dst].load;
+        if src_load <= 0.0 {
+            break;
+        }
+        let (slot_num, moved_load) = match masters[src].slot_loads.as_ref() {
+            Some(slot_loads) => get_slots_to_move(slot_loads, (src_load - dst_load) / 2.0),
+            None => {
+                let slot_num =
+                    ((src_slots as f64) * (src_load - dst_load) / (2.0 * src_load)) as usize;
+                let slot_num = slot_num.min(src_slots - 1);
+                (slot_num, src_load * slot_num as f64 / src_slots as f64)
+            }
+        };
+        if slot_num == 0 {
+            break;
+        }
+
+        if let Some(slot_loads) = masters[src].slot_loads.as_mut() {
+            let moved = slot_loads.split_off(slot_loads.len() - slot_num);
+            if let Some(dst_slot_loads) = masters[dst].slot_loads.as_mut() {
+                dst_slot_loads.extend(moved);
+            }
+        }
+        masters[src].slots -= slot_num;
+        masters[src].load -= moved_load;
+        masters[src].used = true;
+        masters[dst].slots += slot_num;
+        masters[dst].load += moved_load;
+        masters[dst].used = true;
+        migrations.push(BalanceMigration {
+            src_chunk_index: masters[src].chunk_index,
+            src_chunk_part: masters[src].chunk_part,
+            dst_chunk_index: masters[dst].chunk_index,
+            dst_chunk_part: masters[dst].chunk_part,
+            slot_num,
+        });
+    }
+    migrations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::store::MetaStore;
+    use super::*;
+    use crate::common::cluster::{ClusterName, ProxyRuntimeStats};
+    use std::convert::TryFrom;
+
+    fn gen_store_with_qps(qps: &[f64]) -> MetaStore {
+        let mut store = MetaStore::default();
+        for i in 1..=qps.len() {
+            let proxy_address = format!("127.0.0.{}:7000", i);
+            let node_addresses = [format!("127.0.0.{}:6000", i), format!("127.0.0.{}:6001", i)];
+            store
+                .add_proxy(proxy_address, node_addresses, None, None)
+                .unwrap();
+        }
+        store.add_cluster("mycluster".to_string(), 8).unwrap();
+        for (i, qps) in qps.iter().enumerate() {
+            let sample = ProxyStatsSample {
+                time: 100,
+                stats: ProxyRuntimeStats::default(),
+                qps: *qps,
+                error_rate: 0.0,
+                slot_qps: vec![],
+            };
+            store
+                .proxy_stats
+                .insert(format!("127.0.0.{}:7000", i + 1), sample);
+        }
+        store
+    }
+
+    #[test]
+    fn test_plan_balance() {
+        let policy = BalancePolicy {
+            threshold: 0.2,
+            max_migrations: 2,
+            window: None,
+        };
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+
+        let store = gen_store_with_qps(&[1000.0, 1000.0, 1000.0, 1000.0]);
+        let cluster = store.clusters.get(&cluster_name).unwrap();
+        assert!(plan_balance(cluster, &store.proxy_stats, 100, &policy).is_empty());
+
+        let store = gen_store_with_qps(&[1000.0, 1000.0, 3000.0, 1000.0]);
+        let cluster = store.clusters.get(&cluster_name).unwrap();
+        let migrations = plan_balance(cluster, &store.proxy_stats, 100, &policy);
+        assert_eq!(migrations.len(), 1);
+        let migration = &migrations[0];
+        let src = &cluster.chunks[migration.src_chunk_index];
+        let proxy_index = MigrationSlotRangeStore::chunk_part_to_proxy_index(
+            migration.src_chunk_part,
+            src.role_position,
+        );
+        assert_eq!(src.proxy_addresses[proxy_index], "127.0.0.3:7000");
+        assert_eq!(migration.slot_num, 4096 / 3);
+
+        // The stats are outdated so the slots are already balanced.
+        let migrations = plan_balance(cluster, &store.proxy_stats, 1000, &policy);
+        assert!(migrations.is_empty());
+    }
+
+    #[test]
+    fn test_plan_balance_by_slot_load() {
+        let policy = BalancePolicy {
+            threshold: 0.2,
+            max_migrations: 1,
+            window: None,
+        };
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let hot_proxy = "127.0.0.3:7000";
+
+        let mut store = gen_store_with_qps(&[64.0, 64.0, 64.0, 64.0]);
+        for sample in store.proxy_stats.values_mut() {
+            sample.slot_qps = vec![1.0; SLOT_LOAD_BUCKETS];
+        }
+        let cluster = store.clusters.get(&cluster_name).unwrap();
+        assert!(plan_balance(cluster, &store.proxy_stats, 100, &policy).is_empty());
+
+        // The last bucket of a master in the hot proxy is hot.
+        let (chunk_index, chunk_part) = (0..cluster.chunks.len())
+            .flat_map(|i| (0..CHUNK_PARTS).map(move |part| (i, part)))
+            .find(|(i, part)| {
+                let chunk = &cluster.chunks[*i];
+                let proxy_index =
+                    MigrationSlotRangeStore::chunk_part_to_proxy_index(*part, chunk.role_position);
+                chunk.proxy_addresses[proxy_index] == hot_proxy
+            })
+            .unwrap();
+        let last_slot = cluster.chunks[chunk_index].stable_slots[chunk_part]
+            .as_ref()
+            .unwrap()
+            .get_range_list()
+            .get_ranges()
+            .last()
+            .unwrap()
+            .end();
+        store.proxy_stats.get_mut(hot_proxy).unwrap().slot_qps[last_slot / SLOT_LOAD_BUCKET_SIZE] =
+            1000.0;
+
+        let cluster = store.clusters.get(&cluster_name).unwrap();
+        let migrations = plan_balance(cluster, &store.proxy_stats, 100, &policy);
+        assert_eq!(migrations.len(), 1);
+        let migration = &migrations[0];
+        assert_eq!(migration.src_chunk_index, chunk_index);
+        assert_eq!(migration.src_chunk_part, chunk_part);
+        // Only half of the hot bucket is moved.
+        assert_eq!(migration.slot_num, SLOT_LOAD_BUCKET_SIZE / 2);
+    }
+
+    #[test]
+    fn test_get_slots_to_move() {
+        assert_eq!(get_slots_to_move(&[1.0, 1.0, 1.0, 1.0], 2.0), (2, 2.0));
+        assert_eq!(get_slots_to_move(&[1.0, 1.0, 1.0, 10.0], 4.0), (0, 0.0));
+        assert_eq!(get_slots_to_move(&[1.0, 1.0, 1.0, 10.0], 6.0), (1, 10.0));
+        assert_eq!(get_slots_to_move(&[1.0, 0.0, 0.0, 10.0], 100.0), (3, 10.0));
+    }
+
+    #[test]
+    fn test_maintenance_window() {
+        let window = MaintenanceWindow::from_str("02:00-04:30").unwrap();
+        assert!(!window.contains(3600));
+        assert!(window.contains(2 * 3600));
+        assert!(window.contains(4 * 3600 + 29 * 60));
+        assert!(!window.contains(4 * 3600 + 30 * 60));
+        assert!(window.contains(24 * 3600 + 3 * 3600));
+
+        let window = MaintenanceWindow::from_str("23:00-01:00").unwrap();
+        assert!(window.contains(23 * 3600 + 60));
+        assert!(window.contains(60));
+        assert!(!window.contains(12 * 3600));
+
+        assert!(MaintenanceWindow::from_str("24:00-01:00").is_err());
+        assert!(MaintenanceWindow::from_str("02:00").is_err());
+        assert!(MaintenanceWindow::from_str("a:00-b:00").is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use super::service::{
+    AddProxyResponse, CreateClusterPayload, ProxyResourcePayload, MEM_BROKER_API_VERSION,
+};
+use crate::common::cluster::{
+    Cluster, ClusterName, MigrationTaskEvents, MigrationTaskMeta, Proxy, ProxyRuntimeStats,
+};
+use crate::coordinator::http_mani_broker::{MigrationEventsPayload, ReplaceProxyResponse};
+use crate::coordinator::http_meta_broker::{
+    ClusterNamesPayload, ClusterPayload, FailedProxiesPayload, FailuresPayload,
+    ProxyAddressesPayload, ProxyPayload, RebuildingProxiesPayload,
+};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::fmt;
+
+// A typed client of the HTTP API of the memory broker.
+// The routes are listed in `BROKER_API_ROUTES` and `GET /openapi.json`.
+#[derive(Clone)]
+pub struct BrokerClient {
+    client: reqwest::Client,
+    address: String,
+    api_token: Option<String>,
+}
+
+impl BrokerClient {
+    pub fn new(client: reqwest::Client, address: String) -> Self {
+        Self {
+            client,
+            address,
+            api_token: None,
+        }
+    }
+
+    // Required when the broker is configured with `api_tokens`.
+    pub fn with_api_token(mut self, api_token: String) -> Self {
+        self.api_token = Some(api_token);
+        self
+    }
+
+    pub fn get_address(&self) -> &str {
+        &self.address
+    }
+
+    fn gen_url(&self, path: &str) -> String {
+        format!("http://{}{}{}", self.address, MEM_BROKER_API_VERSION, path)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let builder = self.client.request(method, &self.gen_url(path));
+        match self.api_token.as_ref() {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    async fn send(builder: RequestBuilder) -> Result<Response, BrokerClientError> {
+        let response = builder
+            .send()
+            .await
+            .map_err(BrokerClientError::RequestFailed)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(BrokerClientError::InvalidReply)?;
+        Err(BrokerClientError::Failed(status, body))
+    }
+
+    async fn send_json<T: DeserializeOwned>(
+        builder: RequestBuilder,
+    ) -> Result<T, BrokerClientError> {
+        Self::send(builder)
+            .await?
+            .json()
+            .await
+            .map_err(BrokerClientError::InvalidReply)
+    }
+
+    pub async fn get_version(&self) -> Result<String, BrokerClientError> {
+        Self::send(self.request(Method::GET, "/version"))
+            .await?
+            .text()
+            .await
+            .map_err(BrokerClientError::InvalidReply)
+    }
+
+    pub async fn get_openapi(&self) -> Result<serde_json::Value, BrokerClientError> {
+        Self::send_json(self.request(Method::GET, "/openapi.json")).await
+    }
+
+    pub async fn get_cluster_names(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<ClusterName>, BrokerClientError> {
+        let builder = self
+            .request(Method::GET, "/clusters/names")
+            .query(&[("offset", offset), ("limit", limit)]);
+        let ClusterNamesPayload { names } = Self::send_json(builder).await?;
+        Ok(names)
+    }
+
+    pub async fn get_cluster(
+        &self,
+        cluster_name: &ClusterName,
+    ) -> Result<Option<Cluster>, BrokerClientError> {
+        let path = format!("/clusters/meta/{}", cluster_name);
+        let ClusterPayload { cluster } = Self::send_json(self.request(Method::GET, &path)).await?;
+        Ok(cluster)
+    }
+
+    pub async fn get_proxy_addresses(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<String>, BrokerClientError> {
+        let builder = self
+            .request(Method::GET, "/proxies/addresses")
+            .query(&[("offset", offset), ("limit", limit)]);
+        let ProxyAddressesPayload { addresses } = Self::send_json(builder).await?;
+        Ok(addresses)
+    }
+
+    pub async fn get_proxy(&self, address: &str) -> Result<Option<Proxy>, BrokerClientError> {
+        let path = format!("/proxies/meta/{}", address);
+        let ProxyPayload { proxy } = Self::send_json(self.request(Method::GET, &path)).await?;
+        Ok(proxy)
+    }
+
+    pub async fn get_failures(&self) -> Result<Vec<String>, BrokerClientError> {
+        let FailuresPayload { addresses } =
+            Self::send_json(self.request(Method::GET, "/failures")).await?;
+        Ok(addresses)
+    }
+
+    pub async fn add_failure(
+        &self,
+        address: &str,
+        reporter_id: &str,
+    ) -> Result<(), BrokerClientError> {
+        let path = format!("/failures/{}/{}", address, reporter_id);
+        Self::send(self.request(Method::POST, &path)).await?;
+        Ok(())
+    }
+
+    pub async fn get_failed_proxies(&self) -> Result<Vec<String>, BrokerClientError> {
+        let builder = self.request(Method::GET, "/proxies/failed/addresses");
+        let FailedProxiesPayload { addresses } = Self::send_json(builder).await?;
+        Ok(addresses)
+    }
+
+    pub async fn get_rebuilding_proxies(&self) -> Result<Vec<String>, BrokerClientError> {
+        let builder = self.request(Method::GET, "/proxies/rebuilding/addresses");
+        let RebuildingProxiesPayload { addresses } = Self::send_json(builder).await?;
+        Ok(addresses)
+    }
+
+    pub async fn replace_failed_proxy(
+        &self,
+        address: &str,
+    ) -> Result<Option<Proxy>, BrokerClientError> {
+        let path = format!("/proxies/failover/{}", address);
+        let ReplaceProxyResponse { proxy } =
+            Self::send_json(self.request(Method::POST, &path)).await?;
+        Ok(proxy)
+    }
+
+    pub async fn commit_migration(
+        &self,
+        meta: &MigrationTaskMeta,
+    ) -> Result<(), BrokerClientError> {
+        let builder = self.request(Method::PUT, "/clusters/migrations").json(meta);
+        Self::send(builder).await?;
+        Ok(())
+    }
+
+    pub async fn finish_proxy_rebuilding(&self, address: &str) -> Result<(), BrokerClientError> {
+        let path = format!("/proxies/rebuilding/{}", address);
+        Self::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    pub async fn report_proxy_stats(
+        &self,
+        address: &str,
+        stats: &ProxyRuntimeStats,
+    ) -> Result<(), BrokerClientError> {
+        let path = format!("/proxies/stats/{}", address);
+        Self::send(self.request(Method::PUT, &path).json(stats)).await?;
+        Ok(())
+    }
+
+    pub async fn report_migration_events(
+        &self,
+        address: &str,
+        events: Vec<MigrationTaskEvents>,
+    ) -> Result<(), BrokerClientError> {
+        let path = format!("/proxies/migration_events/{}", address);
+        let payload = MigrationEventsPayload { tasks: events };
+        Self::send(self.request(Method::PUT, &path).json(&payload)).await?;
+        Ok(())
+    }
+
+    pub async fn add_cluster(
+        &self,
+        cluster_name: &ClusterName,
+        payload: &CreateClusterPayload,
+    ) -> Result<(), BrokerClientError> {
+        let path = format!("/clusters/meta/{}", cluster_name);
+        Self::send(self.request(Method::POST, &path).json(payload)).await?;
+        Ok(())
+    }
+
+    pub async fn remove_cluster(
+        &self,
+        cluster_name: &ClusterName,
+    ) -> Result<(), BrokerClientError> {
+        let path = format!("/clusters/meta/{}", cluster_name);
+        Self::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    // Returns the proxy id.
+    pub async fn add_proxy(
+        &self,
+        payload: &ProxyResourcePayload,
+    ) -> Result<String, BrokerClientError> {
+        let builder = self.request(Method::POST, "/proxies/meta").json(payload);
+        let AddProxyResponse { proxy_id } = Self::send_json(builder).await?;
+        Ok(proxy_id)
+    }
+
+    pub async fn remove_proxy(&self, address: &str) -> Result<(), BrokerClientError> {
+        let path = format!("/proxies/meta/{}", address);
+        Self::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum BrokerClientError {
+    RequestFailed(reqwest::Error),
+    InvalidReply(reqwest::Error),
+    // The status code and the body of the error reply.
+    Failed(StatusCode, String),
+}
+
+impl BrokerClientError {
+    // The cluster or the proxy could have been removed.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Self::Failed(status, _) => *status == StatusCode::NOT_FOUND,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for BrokerClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::RequestFailed(err) => write!(f, "request failed: {}", err),
+            Self::InvalidReply(err) => write!(f, "invalid reply: {}", err),
+            Self::Failed(status, body) => write!(f, "status {}: {}", status, body),
+        }
+    }
+}
+
+impl Error for BrokerClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::RequestFailed(err) | Self::InvalidReply(err) => Some(err),
+            Self::Failed(..) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let client = BrokerClient::new(reqwest::Client::new(), "127.0.0.1:7799".to_string());
+        let request = client.request(Method::GET, "/version").build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "http://127.0.0.1:7799/api/v2/version"
+        );
+        assert!(request.headers().get("Authorization").is_none());
+
+        let client = client.with_api_token("mytoken".to_string());
+        let request = client.request(Method::DELETE, "/proxies/meta/127.0.0.1:5299");
+        let request = request.build().unwrap();
+        assert_eq!(request.method(), Method::DELETE);
+        assert_eq!(
+            request.headers().get("Authorization").unwrap(),
+            "Bearer mytoken"
+        );
+    }
+
+    #[test]
+    fn test_is_not_found() {
+        let err = BrokerClientError::Failed(StatusCode::NOT_FOUND, "".to_string());
+        assert!(err.is_not_found());
+        let err = BrokerClientError::Failed(StatusCode::CONFLICT, "".to_string());
+        assert!(!err.is_not_found());
+    }
+}
@@ -0,0 +1,180 @@
+use super::balance::{BalancePolicy, MaintenanceWindow};
+use super::namespace::NamespaceTokens;
+use super::service::MemBrokerConfig;
+use crate::common::config_file::{ConfigFileError, StringList};
+use arc_swap::ArcSwap;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// The fields of `conf/mem-broker.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemBrokerConfigFile {
+    pub address: String,
+    pub failure_ttl: u64,
+    pub failure_quorum: u64,
+    pub migration_limit: u64,
+    pub recover_from_meta_file: bool,
+    pub meta_filename: String,
+    pub auto_update_meta_file: bool,
+    pub update_meta_file_interval: u64,
+    pub replica_addresses: StringList,
+    pub sync_meta_interval: u64,
+    pub check_alert_interval: u64,
+    pub persist_cluster_stats: bool,
+    pub persist_migration_events: bool,
+    // e.g. ["namespace1:token1", "namespace2:token2"]
+    pub namespace_tokens: StringList,
+    pub auto_balance_interval: u64,
+    pub auto_balance_threshold: f64,
+    pub auto_balance_max_migrations: usize,
+    // e.g. "02:00-04:00" in UTC
+    pub auto_balance_window: String,
+    pub auto_failback_interval: u64,
+    // e.g. "02:00-04:00" in UTC
+    pub auto_failback_window: String,
+    pub idempotency_key_ttl: u64,
+    pub debug: bool,
+}
+
+impl Default for MemBrokerConfigFile {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:7799".to_string(),
+            failure_ttl: 60,
+            failure_quorum: 1,
+            migration_limit: 1,
+            recover_from_meta_file: false,
+            meta_filename: "metadata".to_string(),
+            auto_update_meta_file: false,
+            update_meta_file_interval: 0,
+            replica_addresses: StringList::default(),
+            sync_meta_interval: 0,
+            check_alert_interval: 0,
+            persist_cluster_stats: false,
+            persist_migration_events: false,
+            namespace_tokens: StringList::default(),
+            auto_balance_interval: 0,
+            auto_balance_threshold: 0.2,
+            auto_balance_max_migrations: 1,
+            auto_balance_window: String::new(),
+            auto_failback_interval: 0,
+            auto_failback_window: String::new(),
+            idempotency_key_ttl: 86400,
+            debug: false,
+        }
+    }
+}
+
+impl MemBrokerConfigFile {
+    pub fn into_config(self) -> Result<MemBrokerConfig, ConfigFileError> {
+        let mut namespace_tokens = HashMap::new();
+        for s in self.namespace_tokens.into_vec().into_iter() {
+            let mut parts = s.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(namespace), Some(token)) if !token.is_empty() => {
+                    namespace_tokens.insert(token.to_string(), namespace.to_string());
+                }
+                _ => {
+                    return Err(ConfigFileError::invalid_field(
+                        "namespace_tokens",
+                        "expected <namespace>:<token>",
+                    ))
+                }
+            }
+        }
+
+        let window = parse_window("auto_balance_window", &self.auto_balance_window)?;
+        let failback_window = parse_window("auto_failback_window", &self.auto_failback_window)?;
+        let balance_policy = BalancePolicy {
+            threshold: self.auto_balance_threshold,
+            max_migrations: self.auto_balance_max_migrations,
+            window,
+        };
+
+        let replica_addresses = self.replica_addresses.into_vec();
+        Ok(MemBrokerConfig {
+            address: self.address,
+            failure_ttl: self.failure_ttl,
+            failure_quorum: self.failure_quorum,
+            migration_limit: self.migration_limit,
+            recover_from_meta_file: self.recover_from_meta_file,
+            meta_filename: self.meta_filename,
+            auto_update_meta_file: self.auto_update_meta_file,
+            update_meta_file_interval: NonZeroU64::new(self.update_meta_file_interval),
+            replica_addresses: Arc::new(ArcSwap::new(Arc::new(replica_addresses))),
+            sync_meta_interval: NonZeroU64::new(self.sync_meta_interval),
+            check_alert_interval: NonZeroU64::new(self.check_alert_interval),
+            persist_cluster_stats: self.persist_cluster_stats,
+            persist_migration_events: self.persist_migration_events,
+            namespace_tokens: NamespaceTokens::new(namespace_tokens),
+            auto_balance_interval: NonZeroU64::new(self.auto_balance_interval),
+            balance_policy,
+            auto_failback_interval: NonZeroU64::new(self.auto_failback_interval),
+            failback_window,
+            idempotency_key_ttl: self.idempotency_key_ttl,
+            debug: self.debug,
+        })
+    }
+}
+
+fn parse_window(field: &str, s: &str) -> Result<Option<MaintenanceWindow>, ConfigFileError> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    let window = MaintenanceWindow::from_str(s)
+        .map_err(|_| ConfigFileError::invalid_field(field, "expected HH:MM-HH:MM"))?;
+    Ok(Some(window))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::config_file::parse_config;
+
+    #[test]
+    fn test_mem_broker_config_file() {
+        let mut s = config::Config::new();
+        s.merge(config::File::with_name("conf/mem-broker.toml"))
+            .unwrap();
+        let config_file: MemBrokerConfigFile = parse_config(s).unwrap();
+        let config = config_file.into_config().unwrap();
+        assert_eq!(config.address, "127.0.0.1:7799");
+        assert_eq!(config.migration_limit, 2);
+        assert!(config.replica_addresses.lease().is_empty());
+        assert!(config.balance_policy.window.is_none());
+        assert!(config.failback_window.is_none());
+
+        let mut s = config::Config::new();
+        s.set("replica_addresses", "127.0.0.1:7799,127.0.0.1:8899")
+            .unwrap();
+        s.set("auto_balance_window", "02:00-04:00").unwrap();
+        s.set("auto_failback_window", "03:00-04:00").unwrap();
+        let config_file: MemBrokerConfigFile = parse_config(s).unwrap();
+        let config = config_file.into_config().unwrap();
+        assert_eq!(config.replica_addresses.lease().len(), 2);
+        assert!(config.balance_policy.window.is_some());
+        assert!(config.failback_window.is_some());
+
+        let mut s = config::Config::new();
+        s.set("auto_balance_window", "2am").unwrap();
+        let config_file: MemBrokerConfigFile = parse_config(s).unwrap();
+        assert!(config_file.into_config().is_err());
+
+        let mut s = config::Config::new();
+        s.set("auto_failback_window", "3am").unwrap();
+        let config_file: MemBrokerConfigFile = parse_config(s).unwrap();
+        assert!(config_file.into_config().is_err());
+
+        let mut s = config::Config::new();
+        s.set("namespace_tokens", "team_a").unwrap();
+        let config_file: MemBrokerConfigFile = parse_config(s).unwrap();
+        assert!(config_file.into_config().is_err());
+
+        let mut s = config::Config::new();
+        s.set("failure_tll", 60).unwrap();
+        assert!(parse_config::<MemBrokerConfigFile>(s).is_err());
+    }
+}
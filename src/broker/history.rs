@@ -0,0 +1,298 @@
+use super::store::ProxyStatsSample;
+use crate::common::cluster::{ClusterName, MigrationTaskEvents};
+use std::collections::{HashMap, VecDeque};
+
+// 24 hours at 1-minute resolution.
+pub const CLUSTER_STATS_RESOLUTION: i64 = 60;
+pub const CLUSTER_STATS_CAPACITY: usize = 24 * 60;
+// The tasks of both the migrating and the importing proxies.
+pub const MIGRATION_EVENTS_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ClusterStatsSample {
+    // Unix timestamp in seconds
+    pub time: i64,
+    pub qps: f64,
+    pub error_rate: f64,
+    pub connected_sessions: u64,
+    pub used_memory_sessions: u64,
+    pub replication_lag: u64,
+}
+
+impl ClusterStatsSample {
+    // Aggregates the latest samples of the proxies of the cluster.
+    fn from_proxy_samples<'a, I>(time: i64, samples: I) -> Option<Self>
+    where
+        I: Iterator<Item = &'a ProxyStatsSample>,
+    {
+        let mut cluster_sample = Self {
+            time,
+            ..Default::default()
+        };
+        let mut found = false;
+        let mut errors = 0.0;
+        for sample in samples {
+            found = true;
+            cluster_sample.qps += sample.qps;
+            errors += sample.qps * sample.error_rate;
+            cluster_sample.connected_sessions += sample.stats.connected_sessions;
+            cluster_sample.used_memory_sessions += sample.stats.used_memory_sessions;
+            cluster_sample.replication_lag = cluster_sample
+                .replication_lag
+                .max(sample.stats.replication_lag);
+        }
+        if cluster_sample.qps > 0.0 {
+            cluster_sample.error_rate = errors / cluster_sample.qps;
+        }
+        if found {
+            Some(cluster_sample)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ClusterStatsHistory {
+    // Set from the broker config and not saved.
+    #[serde(skip)]
+    persisted: bool,
+    samples: HashMap<ClusterName, VecDeque<ClusterStatsSample>>,
+}
+
+impl ClusterStatsHistory {
+    pub fn set_persisted(&mut self, persisted: bool) {
+        self.persisted = persisted;
+    }
+
+    pub fn is_transient(&self) -> bool {
+        !self.persisted
+    }
+
+    // Only keeps the first sample in each minute.
+    pub fn record<'a, I>(&mut self, cluster_name: ClusterName, now: i64, proxy_samples: I)
+    where
+        I: Iterator<Item = &'a ProxyStatsSample>,
+    {
+        let recent = proxy_samples.filter(|s| now - s.time <= CLUSTER_STATS_RESOLUTION);
+        let sample = match ClusterStatsSample::from_proxy_samples(now, recent) {
+            Some(sample) => sample,
+            None => return,
+        };
+        let samples = self.samples.entry(cluster_name).or_default();
+        if let Some(last) = samples.back() {
+            if last.time / CLUSTER_STATS_RESOLUTION == now / CLUSTER_STATS_RESOLUTION {
+                return;
+            }
+        }
+        samples.push_back(sample);
+        while samples.len() > CLUSTER_STATS_CAPACITY {
+            samples.pop_front();
+        }
+    }
+
+    pub fn get(&self, cluster_name: &ClusterName, since: i64) -> Vec<ClusterStatsSample> {
+        self.samples
+            .get(cluster_name)
+            .map(|samples| {
+                samples
+                    .iter()
+                    .filter(|sample| sample.time >= since)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&mut self, cluster_name: &ClusterName) {
+        self.samples.remove(cluster_name);
+    }
+
+    pub fn rename(&mut self, cluster_name: &ClusterName, new_cluster_name: ClusterName) {
+        if let Some(samples) = self.samples.remove(cluster_name) {
+            self.samples.insert(new_cluster_name, samples);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MigrationEventHistory {
+    // Set from the broker config and not saved.
+    #[serde(skip)]
+    persisted: bool,
+    tasks: HashMap<ClusterName, VecDeque<MigrationTaskEvents>>,
+}
+
+impl MigrationEventHistory {
+    pub fn set_persisted(&mut self, persisted: bool) {
+        self.persisted = persisted;
+    }
+
+    pub fn is_transient(&self) -> bool {
+        !self.persisted
+    }
+
+    // The proxies keep reporting the recent tasks so the old records are replaced.
+    pub fn record(&mut self, cluster_name: ClusterName, task_events: MigrationTaskEvents) {
+        let tasks = self.tasks.entry(cluster_name).or_default();
+        let existing = tasks
+            .iter_mut()
+            .find(|t| t.task == task_events.task && t.proxy_address == task_events.proxy_address);
+        if let Some(existing) = existing {
+            *existing = task_events;
+            return;
+        }
+        tasks.push_back(task_events);
+        while tasks.len() > MIGRATION_EVENTS_CAPACITY {
+            tasks.pop_front();
+        }
+    }
+
+    pub fn find(
+        &self,
+        cluster_name: &ClusterName,
+        proxy_address: &str,
+        task: &str,
+    ) -> Option<&MigrationTaskEvents> {
+        self.tasks
+            .get(cluster_name)?
+            .iter()
+            .find(|t| t.task == task && t.proxy_address == proxy_address)
+    }
+
+    pub fn get(&self, cluster_name: &ClusterName) -> Vec<MigrationTaskEvents> {
+        self.tasks
+            .get(cluster_name)
+            .map(|tasks| tasks.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&mut self, cluster_name: &ClusterName) {
+        self.tasks.remove(cluster_name);
+    }
+
+    pub fn rename(&mut self, cluster_name: &ClusterName, new_cluster_name: ClusterName) {
+        if let Some(tasks) = self.tasks.remove(cluster_name) {
+            self.tasks.insert(new_cluster_name, tasks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::{MigrationEvent, MigrationEventKind, ProxyRuntimeStats};
+    use std::convert::TryFrom;
+
+    fn gen_proxy_sample(time: i64, qps: f64, error_rate: f64, lag: u64) -> ProxyStatsSample {
+        ProxyStatsSample {
+            time,
+            stats: ProxyRuntimeStats {
+                connected_sessions: 10,
+                replication_lag: lag,
+                ..Default::default()
+            },
+            qps,
+            error_rate,
+            slot_qps: vec![],
+        }
+    }
+
+    #[test]
+    fn test_cluster_stats_history() {
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let mut history = ClusterStatsHistory::default();
+        let proxy_samples = [
+            gen_proxy_sample(100, 100.0, 0.0, 1),
+            gen_proxy_sample(100, 300.0, 0.1, 3),
+            // Outdated
+            gen_proxy_sample(10, 1000.0, 0.0, 0),
+        ];
+
+        history.record(cluster_name.clone(), 120, proxy_samples.iter());
+        let samples = history.get(&cluster_name, 0);
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].qps, 400.0);
+        assert!((samples[0].error_rate - 0.075).abs() < 1e-9);
+        assert_eq!(samples[0].connected_sessions, 20);
+        assert_eq!(samples[0].replication_lag, 3);
+
+        // In the same minute.
+        history.record(cluster_name.clone(), 150, proxy_samples.iter());
+        assert_eq!(history.get(&cluster_name, 0).len(), 1);
+        // No recent sample.
+        history.record(cluster_name.clone(), 400, proxy_samples.iter());
+        assert_eq!(history.get(&cluster_name, 0).len(), 1);
+
+        for i in 0..CLUSTER_STATS_CAPACITY as i64 {
+            let now = 1000 + i * CLUSTER_STATS_RESOLUTION;
+            let proxy_samples = [gen_proxy_sample(now, 1.0, 0.0, 0)];
+            history.record(cluster_name.clone(), now, proxy_samples.iter());
+        }
+        let samples = history.get(&cluster_name, 0);
+        assert_eq!(samples.len(), CLUSTER_STATS_CAPACITY);
+        assert_eq!(samples[0].time, 1000);
+        assert_eq!(
+            history.get(&cluster_name, 1000 + 60).len(),
+            CLUSTER_STATS_CAPACITY - 1
+        );
+
+        history.remove(&cluster_name);
+        assert!(history.get(&cluster_name, 0).is_empty());
+    }
+
+    fn gen_task_events(task: &str, proxy_address: &str, count: u64) -> MigrationTaskEvents {
+        MigrationTaskEvents {
+            cluster_name: "mycluster".to_string(),
+            task: task.to_string(),
+            proxy_address: proxy_address.to_string(),
+            finished: false,
+            events: vec![MigrationEvent {
+                time: 1,
+                last_time: 1,
+                kind: MigrationEventKind::Retry,
+                detail: "pre_check not ready".to_string(),
+                count,
+            }],
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_migration_event_history() {
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let mut history = MigrationEventHistory::default();
+        history.record(
+            cluster_name.clone(),
+            gen_task_events("task1", "127.0.0.1:7000", 1),
+        );
+        history.record(
+            cluster_name.clone(),
+            gen_task_events("task1", "127.0.0.1:7001", 1),
+        );
+        history.record(
+            cluster_name.clone(),
+            gen_task_events("task1", "127.0.0.1:7000", 2),
+        );
+        let tasks = history.get(&cluster_name);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].events[0].count, 2);
+
+        for i in 0..MIGRATION_EVENTS_CAPACITY {
+            let task = format!("task{}", i + 2);
+            history.record(
+                cluster_name.clone(),
+                gen_task_events(&task, "127.0.0.1:7000", 1),
+            );
+        }
+        let tasks = history.get(&cluster_name);
+        assert_eq!(tasks.len(), MIGRATION_EVENTS_CAPACITY);
+        assert_eq!(tasks[0].task, "task2");
+
+        let new_cluster_name = ClusterName::try_from("newcluster").unwrap();
+        history.rename(&cluster_name, new_cluster_name.clone());
+        assert!(history.get(&cluster_name).is_empty());
+        history.remove(&new_cluster_name);
+        assert!(history.get(&new_cluster_name).is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// The reply of a request with an `Idempotency-Key`,
+// which is sent again for the retries with the same key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    pub status: u16,
+    pub json: bool,
+    pub body: String,
+}
+
+enum Entry {
+    // The first request is still being processed.
+    Pending(Instant),
+    Done(Instant, CachedResponse),
+}
+
+impl Entry {
+    fn get_time(&self) -> Instant {
+        match self {
+            Self::Pending(t) | Self::Done(t, _) => *t,
+        }
+    }
+}
+
+pub enum IdempotencyState {
+    New(IdempotencyGuard),
+    Pending,
+    Done(CachedResponse),
+}
+
+// Keeps the outcomes of the mutating API calls for `ttl`
+// so that the retries after a timeout won't apply the same change twice.
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn begin(&self, key: String, now: Instant) -> IdempotencyState {
+        let mut entries = self.entries.lock().expect("IdempotencyCache::begin");
+        let ttl = self.ttl;
+        entries.retain(|_, entry| now.saturating_duration_since(entry.get_time()) < ttl);
+
+        match entries.get(&key) {
+            Some(Entry::Pending(_)) => return IdempotencyState::Pending,
+            Some(Entry::Done(_, response)) => return IdempotencyState::Done(response.clone()),
+            None => (),
+        }
+        entries.insert(key.clone(), Entry::Pending(now));
+        IdempotencyState::New(IdempotencyGuard {
+            key,
+            entries: self.entries.clone(),
+            finished: false,
+        })
+    }
+}
+
+// Removes the pending entry when the request is dropped before it finishes
+// so that the client could retry it.
+pub struct IdempotencyGuard {
+    key: String,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    finished: bool,
+}
+
+impl IdempotencyGuard {
+    pub fn finish(mut self, response: CachedResponse, now: Instant) {
+        self.finished = true;
+        let mut entries = self.entries.lock().expect("IdempotencyGuard::finish");
+        entries.insert(self.key.clone(), Entry::Done(now, response));
+    }
+}
+
+impl Drop for IdempotencyGuard {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_response(body: &str) -> CachedResponse {
+        CachedResponse {
+            status: 200,
+            json: false,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_idempotency_cache() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let guard = match cache.begin("key1".to_string(), now) {
+            IdempotencyState::New(guard) => guard,
+            _ => panic!(),
+        };
+        assert!(matches!(
+            cache.begin("key1".to_string(), now),
+            IdempotencyState::Pending
+        ));
+        guard.finish(gen_response("done"), now);
+        match cache.begin("key1".to_string(), now) {
+            IdempotencyState::Done(response) => assert_eq!(response, gen_response("done")),
+            _ => panic!(),
+        }
+
+        // Expired
+        let later = now + Duration::from_secs(60);
+        assert!(matches!(
+            cache.begin("key1".to_string(), later),
+            IdempotencyState::New(_)
+        ));
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dropped_request() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        match cache.begin("key1".to_string(), now) {
+            IdempotencyState::New(guard) => drop(guard),
+            _ => panic!(),
+        }
+        assert!(cache.entries.lock().unwrap().is_empty());
+        assert!(matches!(
+            cache.begin("key1".to_string(), now),
+            IdempotencyState::New(_)
+        ));
+    }
+}
@@ -1,10 +1,15 @@
+use super::balance::{plan_balance, BalanceMigration, BalancePolicy};
 use super::store::{
-    ClusterStore, MetaStore, MetaStoreError, MigrationMetaStore, MigrationSlotRangeStore,
-    MigrationSlots, CHUNK_NODE_NUM,
+    ClusterStore, ForceMigrationAction, MetaStore, MetaStoreError, MigrationMetaStore,
+    MigrationSlotRangeStore, MigrationSlots, CHUNK_NODE_NUM,
 };
 use crate::common::cluster::ClusterName;
-use crate::common::cluster::{MigrationTaskMeta, Range, RangeList, SlotRange, SlotRangeTag};
+use crate::common::cluster::{
+    MigrationTaskMeta, Range, RangeList, RestorePolicy, SlotRange, SlotRangeTag,
+};
+use crate::common::config::RoutingMode;
 use crate::common::utils::SLOT_NUM;
+use chrono::Utc;
 use std::cmp::min;
 use std::convert::TryFrom;
 
@@ -17,7 +22,11 @@ impl<'a> MetaStoreMigrate<'a> {
         Self { store }
     }
 
-    pub fn migrate_slots(&mut self, cluster_name: String) -> Result<(), MetaStoreError> {
+    pub fn migrate_slots(
+        &mut self,
+        cluster_name: String,
+        restore_policy: RestorePolicy,
+    ) -> Result<(), MetaStoreError> {
         let cluster_name = ClusterName::try_from(cluster_name.as_str())
             .map_err(|_| MetaStoreError::InvalidClusterName)?;
         let new_epoch = self.store.bump_global_epoch();
@@ -40,13 +49,145 @@ impl<'a> MetaStoreMigrate<'a> {
         }
 
         let migration_slots = Self::remove_slots_from_src(cluster, new_epoch);
-        Self::assign_dst_slots(cluster, migration_slots.clone());
+        Self::assign_dst_slots(cluster, migration_slots.clone(), restore_policy);
         cluster.set_epoch(new_epoch);
 
         Self::print_migration_slot(cluster, &migration_slots);
+        self.commit_migrations_without_data(&cluster_name)
+    }
+
+    // The clusters in the consistent hashing mode are caches
+    // so the slots are moved at once without migrating the data.
+    fn commit_migrations_without_data(
+        &mut self,
+        cluster_name: &ClusterName,
+    ) -> Result<(), MetaStoreError> {
+        let tasks: Vec<MigrationTaskMeta> = match self.store.clusters.get(cluster_name) {
+            Some(cluster) if cluster.config.routing_mode == RoutingMode::ConsistentHash => cluster
+                .chunks
+                .iter()
+                .flat_map(|chunk| chunk.migrating_slots.iter().flatten())
+                .filter(|slot_range_store| slot_range_store.is_migrating)
+                .map(|slot_range_store| MigrationTaskMeta {
+                    cluster_name: cluster_name.clone(),
+                    slot_range: slot_range_store.to_slot_range(&cluster.chunks),
+                })
+                .collect(),
+            _ => return Ok(()),
+        };
+        for task in tasks.into_iter() {
+            self.commit_migration(task)?;
+        }
         Ok(())
     }
 
+    // Returns the clusters starting the migrations.
+    pub fn balance_slots(&mut self, policy: &BalancePolicy, now: i64) -> Vec<ClusterName> {
+        let mut cluster_names: Vec<ClusterName> = self.store.clusters.keys().cloned().collect();
+        cluster_names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        let mut balanced = vec![];
+        let mut remaining = policy.max_migrations;
+        for cluster_name in cluster_names.into_iter() {
+            if remaining == 0 {
+                break;
+            }
+            let failed_proxies = &self.store.failed_proxies;
+            let cluster = match self.store.clusters.get(&cluster_name) {
+                Some(cluster) => cluster,
+                None => continue,
+            };
+            let has_failed_proxy = cluster.chunks.iter().any(|chunk| {
+                chunk
+                    .proxy_addresses
+                    .iter()
+                    .any(|address| failed_proxies.contains(address))
+            });
+            let running_migration = cluster
+                .chunks
+                .iter()
+                .any(|chunk| chunk.migrating_slots.iter().any(|slots| !slots.is_empty()));
+            if has_failed_proxy || running_migration {
+                continue;
+            }
+
+            let policy = BalancePolicy {
+                max_migrations: remaining,
+                ..policy.clone()
+            };
+            let migrations = plan_balance(cluster, &self.store.proxy_stats, now, &policy);
+            if migrations.is_empty() {
+                continue;
+            }
+
+            remaining -= migrations.len();
+            let new_epoch = self.store.bump_global_epoch();
+            let cluster = self
+                .store
+                .clusters
+                .get_mut(&cluster_name)
+                .expect("balance_slots");
+            let migration_slots = Self::remove_slots_to_balance(cluster, migrations, new_epoch);
+            Self::assign_dst_slots(cluster, migration_slots.clone(), RestorePolicy::default());
+            cluster.set_epoch(new_epoch);
+            Self::print_migration_slot(cluster, &migration_slots);
+            if let Err(err) = self.commit_migrations_without_data(&cluster_name) {
+                error!("failed to commit the slots of {}: {}", cluster_name, err);
+            }
+            balanced.push(cluster_name);
+        }
+        balanced
+    }
+
+    // Takes the slots from the end of the source masters.
+    fn remove_slots_to_balance(
+        cluster: &mut ClusterStore,
+        migrations: Vec<BalanceMigration>,
+        epoch: u64,
+    ) -> Vec<MigrationSlots> {
+        let mut migration_slots = vec![];
+        for migration in migrations.into_iter() {
+            let slot_range = match cluster.chunks[migration.src_chunk_index].stable_slots
+                [migration.src_chunk_part]
+                .as_mut()
+            {
+                Some(slot_range) => slot_range,
+                None => continue,
+            };
+            let mut ranges = vec![];
+            let mut remaining = migration.slot_num;
+            while remaining > 0 {
+                let ranges_mut = slot_range.get_mut_range_list().get_mut_ranges();
+                let range = match ranges_mut.last_mut() {
+                    Some(range) => range,
+                    None => break,
+                };
+                let num = range.end() - range.start() + 1;
+                if num <= remaining {
+                    remaining -= num;
+                    ranges.push(ranges_mut.pop().expect("remove_slots_to_balance"));
+                } else {
+                    let end = range.end();
+                    *range.end_mut() -= remaining;
+                    ranges.push(Range(end - remaining + 1, end));
+                    remaining = 0;
+                }
+            }
+            migration_slots.push(MigrationSlots {
+                meta: MigrationMetaStore {
+                    epoch,
+                    src_chunk_index: migration.src_chunk_index,
+                    src_chunk_part: migration.src_chunk_part,
+                    dst_chunk_index: migration.dst_chunk_index,
+                    dst_chunk_part: migration.dst_chunk_part,
+                    restore_policy: RestorePolicy::default(),
+                },
+                ranges,
+            });
+        }
+        migration_slots
+    }
+
     fn remove_slots_from_src(cluster: &mut ClusterStore, epoch: u64) -> Vec<MigrationSlots> {
         let dst_chunk_num = cluster
             .chunks
@@ -124,6 +265,7 @@ impl<'a> MetaStoreMigrate<'a> {
                                     src_chunk_part,
                                     dst_chunk_index: src_chunk_num + (curr_dst_master_index / 2),
                                     dst_chunk_part: curr_dst_master_index % 2,
+                                    restore_policy: RestorePolicy::default(),
                                 },
                                 ranges: curr_dst_slots.drain(..).collect(),
                             });
@@ -229,9 +371,15 @@ impl<'a> MetaStoreMigrate<'a> {
         }
     }
 
-    fn assign_dst_slots(cluster: &mut ClusterStore, migration_slots: Vec<MigrationSlots>) {
+    fn assign_dst_slots(
+        cluster: &mut ClusterStore,
+        migration_slots: Vec<MigrationSlots>,
+        restore_policy: RestorePolicy,
+    ) {
+        let start_time = Utc::now().timestamp();
         for migration_slot_range in migration_slots.into_iter() {
-            let MigrationSlots { ranges, meta } = migration_slot_range;
+            let MigrationSlots { ranges, mut meta } = migration_slot_range;
+            meta.restore_policy = restore_policy;
 
             {
                 let src_chunk = cluster
@@ -246,6 +394,7 @@ impl<'a> MetaStoreMigrate<'a> {
                     range_list: RangeList::new(ranges.clone()),
                     is_migrating: true,
                     meta: meta.clone(),
+                    start_time,
                 };
                 migrating_slots.push(slot_range);
             }
@@ -262,6 +411,7 @@ impl<'a> MetaStoreMigrate<'a> {
                     range_list: RangeList::new(ranges.clone()),
                     is_migrating: false,
                     meta,
+                    start_time,
                 };
                 migrating_slots.push(slot_range);
             }
@@ -289,6 +439,7 @@ impl<'a> MetaStoreMigrate<'a> {
         &mut self,
         cluster_name: String,
         new_node_num: usize,
+        restore_policy: RestorePolicy,
     ) -> Result<(), MetaStoreError> {
         let cluster_name = ClusterName::try_from(cluster_name.as_str())
             .map_err(|_| MetaStoreError::InvalidClusterName)?;
@@ -321,11 +472,11 @@ impl<'a> MetaStoreMigrate<'a> {
         let new_chunk_num = new_node_num / 4;
         let migration_slots =
             Self::remove_slots_from_src_to_scale_down(cluster, new_epoch, new_chunk_num);
-        Self::assign_dst_slots(cluster, migration_slots.clone());
+        Self::assign_dst_slots(cluster, migration_slots.clone(), restore_policy);
         cluster.set_epoch(new_epoch);
 
         Self::print_migration_slot(cluster, &migration_slots);
-        Ok(())
+        self.commit_migrations_without_data(&cluster_name)
     }
 
     fn remove_slots_from_src_to_scale_down(
@@ -416,6 +567,7 @@ impl<'a> MetaStoreMigrate<'a> {
                                     src_chunk_part,
                                     dst_chunk_index: curr_dst_master_index / 2,
                                     dst_chunk_part: curr_dst_master_index % 2,
+                                    restore_policy: RestorePolicy::default(),
                                 },
                                 ranges: curr_dst_slots.drain(..).collect(),
                             });
@@ -451,7 +603,7 @@ impl<'a> MetaStoreMigrate<'a> {
             SlotRangeTag::Importing(meta) => meta.epoch,
         };
 
-        let (src_chunk_index, src_chunk_part) = cluster
+        let (src_chunk_index, src_chunk_part, restore_policy) = cluster
             .chunks
             .iter()
             .enumerate()
@@ -472,7 +624,7 @@ impl<'a> MetaStoreMigrate<'a> {
                     && slot_range_store.meta.epoch == task_epoch
                     && slot_range_store.is_migrating
             })
-            .map(|(i, j, _)| (i, j))
+            .map(|(i, j, slot_range_store)| (i, j, slot_range_store.meta.restore_policy))
             .ok_or_else(|| MetaStoreError::MigrationTaskNotFound)?;
 
         let (dst_chunk_index, dst_chunk_part) = cluster
@@ -505,6 +657,7 @@ impl<'a> MetaStoreMigrate<'a> {
             src_chunk_part,
             dst_chunk_index,
             dst_chunk_part,
+            restore_policy,
         };
 
         for chunk in cluster.chunks.iter_mut() {
@@ -533,21 +686,9 @@ impl<'a> MetaStoreMigrate<'a> {
                             })
                             .map(|index| (j, migrating_slots.remove(index).range_list))
                     });
-            if let Some((j, mut range_list)) = removed_slots {
-                match chunk.stable_slots.get_mut(j).expect("commit_migration") {
-                    Some(stable_slots) => {
-                        stable_slots
-                            .get_mut_range_list()
-                            .merge_another(&mut range_list);
-                    }
-                    stable_slots => {
-                        let slot_range = SlotRange {
-                            range_list,
-                            tag: SlotRangeTag::None,
-                        };
-                        *stable_slots = Some(slot_range);
-                    }
-                }
+            if let Some((j, range_list)) = removed_slots {
+                let stable_slots = chunk.stable_slots.get_mut(j).expect("commit_migration");
+                Self::merge_stable_slots(stable_slots, range_list);
                 break;
             }
         }
@@ -559,6 +700,100 @@ impl<'a> MetaStoreMigrate<'a> {
         Ok(())
     }
 
+    fn merge_stable_slots(stable_slots: &mut Option<SlotRange>, mut range_list: RangeList) {
+        match stable_slots {
+            Some(stable_slots) => {
+                stable_slots
+                    .get_mut_range_list()
+                    .merge_another(&mut range_list);
+            }
+            stable_slots => {
+                let slot_range = SlotRange {
+                    range_list,
+                    tag: SlotRangeTag::None,
+                };
+                *stable_slots = Some(slot_range);
+            }
+        }
+    }
+
+    // Used by the administrators to handle the stuck migration tasks.
+    pub fn force_migration(
+        &mut self,
+        cluster_name: String,
+        epoch: u64,
+        range_list: RangeList,
+        action: ForceMigrationAction,
+    ) -> Result<(), MetaStoreError> {
+        let cluster_name = ClusterName::try_from(cluster_name.as_str())
+            .map_err(|_| MetaStoreError::InvalidClusterName)?;
+        let (slot_range, meta) = {
+            let cluster = self
+                .store
+                .clusters
+                .get(&cluster_name)
+                .ok_or(MetaStoreError::ClusterNotFound)?;
+            let slot_range_store = cluster
+                .chunks
+                .iter()
+                .flat_map(|chunk| chunk.migrating_slots.iter().flatten())
+                .find(|slot_range_store| {
+                    slot_range_store.is_migrating
+                        && slot_range_store.meta.epoch == epoch
+                        && slot_range_store.range_list == range_list
+                })
+                .ok_or(MetaStoreError::MigrationTaskNotFound)?;
+            (
+                slot_range_store.to_slot_range(&cluster.chunks),
+                slot_range_store.meta.clone(),
+            )
+        };
+
+        match action {
+            ForceMigrationAction::Complete => self.commit_migration(MigrationTaskMeta {
+                cluster_name,
+                slot_range,
+            }),
+            ForceMigrationAction::Rollback => {
+                self.rollback_migration(cluster_name, meta, range_list)
+            }
+        }
+    }
+
+    fn rollback_migration(
+        &mut self,
+        cluster_name: ClusterName,
+        meta: MigrationMetaStore,
+        range_list: RangeList,
+    ) -> Result<(), MetaStoreError> {
+        let new_epoch = self.store.bump_global_epoch();
+        let cluster = self
+            .store
+            .clusters
+            .get_mut(&cluster_name)
+            .ok_or(MetaStoreError::ClusterNotFound)?;
+
+        // Remove both the migrating and the importing side.
+        for chunk in cluster.chunks.iter_mut() {
+            for migrating_slots in chunk.migrating_slots.iter_mut() {
+                migrating_slots.retain(|slot_range_store| {
+                    !(slot_range_store.meta == meta && slot_range_store.range_list == range_list)
+                })
+            }
+        }
+
+        let stable_slots = cluster
+            .chunks
+            .get_mut(meta.src_chunk_index)
+            .and_then(|chunk| chunk.stable_slots.get_mut(meta.src_chunk_part))
+            .ok_or(MetaStoreError::InvalidMigrationTask)?;
+        Self::merge_stable_slots(stable_slots, range_list);
+
+        Self::compact_slots(cluster);
+        cluster.set_epoch(new_epoch);
+        Ok(())
+    }
+
     fn check_running_tasks(cluster: &mut ClusterStore) -> Result<(), MetaStoreError> {
         let running_migration = cluster
             .chunks
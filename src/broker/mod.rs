@@ -1,16 +1,43 @@
+#[allow(non_local_definitions)]
+mod alert;
+mod balance;
+mod client;
+#[allow(non_local_definitions)]
+mod config_file;
+#[allow(non_local_definitions)]
+mod history;
+mod idempotency;
 mod migrate;
+#[allow(non_local_definitions)]
+mod namespace;
+mod openapi;
 mod persistence;
 mod query;
 mod recovery;
 mod replication;
+#[allow(non_local_definitions)]
 mod resource;
+// The impls generated by the derive macros of the pinned serde_derive are non-local.
+#[allow(non_local_definitions)]
 mod service;
+#[allow(non_local_definitions)]
+mod spec;
+#[allow(non_local_definitions)]
 mod store;
 mod update;
 
+pub use self::alert::AlertEvent;
+pub use self::balance::{BalancePolicy, MaintenanceWindow};
+pub use self::client::{BrokerClient, BrokerClientError};
+pub use self::config_file::MemBrokerConfigFile;
+pub use self::history::CLUSTER_STATS_RESOLUTION;
+pub use self::namespace::NamespaceTokens;
+pub use self::openapi::{gen_broker_openapi, ApiRoute};
 pub use self::persistence::{JsonFileStorage, MetaStorage, MetaSyncError};
 pub use self::replication::{JsonMetaReplicator, MetaReplicator};
 pub use self::service::{
-    configure_app, MemBrokerConfig, MemBrokerService, ReplicaAddresses, MEM_BROKER_API_VERSION,
+    configure_app, AddProxyResponse, CreateClusterPayload, MemBrokerConfig, MemBrokerService,
+    ProxyResourcePayload, ReplicaAddresses, BROKER_API_ROUTES, MEM_BROKER_API_VERSION,
 };
+pub use self::spec::{ClusterSpec, DeclarativeSpec, ProxySpec, SpecPlan, SpecStep};
 pub use self::store::MetaStoreError;
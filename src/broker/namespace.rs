@@ -0,0 +1,285 @@
+use super::store::{MetaStore, MetaStoreError};
+use crate::common::cluster::ClusterName;
+use crate::common::config::ClusterConfig;
+use actix_web::http;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+const MAX_NAMESPACE_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct NamespaceQuota {
+    #[serde(default)]
+    pub max_clusters: Option<usize>,
+    #[serde(default)]
+    pub max_nodes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Namespace {
+    #[serde(default)]
+    pub quota: NamespaceQuota,
+    // Cluster config fields applied to all the clusters in the namespace.
+    #[serde(default)]
+    pub policy: HashMap<String, String>,
+}
+
+impl Namespace {
+    pub fn validate(&self) -> Result<(), MetaStoreError> {
+        self.apply_policy(&mut ClusterConfig::default())
+    }
+
+    pub fn apply_policy(&self, config: &mut ClusterConfig) -> Result<(), MetaStoreError> {
+        for (k, v) in self.policy.iter() {
+            config
+                .set_field(k, v)
+                .map_err(|err| MetaStoreError::InvalidConfig {
+                    key: k.clone(),
+                    value: v.clone(),
+                    error: err.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NamespaceInfo {
+    pub namespace: Namespace,
+    pub cluster_names: Vec<String>,
+    pub node_number: usize,
+}
+
+// token => namespace
+#[derive(Clone, Default)]
+pub struct NamespaceTokens(HashMap<String, String>);
+
+impl NamespaceTokens {
+    pub fn new(tokens: HashMap<String, String>) -> Self {
+        Self(tokens)
+    }
+
+    pub fn get_namespace(&self, token: &str) -> Option<&str> {
+        self.0.get(token).map(|namespace| namespace.as_str())
+    }
+}
+
+// Avoid logging the tokens.
+impl fmt::Debug for NamespaceTokens {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut namespaces: Vec<&String> = self.0.values().collect();
+        namespaces.sort();
+        write!(f, "NamespaceTokens({:?})", namespaces)
+    }
+}
+
+// Set in the request extensions for the requests with a namespace token.
+pub struct NamespaceScope(pub String);
+
+pub fn check_namespace_name(namespace: &str) -> Result<(), MetaStoreError> {
+    let valid = !namespace.is_empty()
+        && namespace.len() <= MAX_NAMESPACE_LENGTH
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(MetaStoreError::InvalidNamespace)
+    }
+}
+
+// Returns the cluster number and the node number of the namespace.
+pub fn get_namespace_usage(store: &MetaStore, namespace: &str) -> (usize, usize) {
+    store
+        .clusters
+        .values()
+        .filter(|cluster| cluster.namespace.as_deref() == Some(namespace))
+        .fold((0, 0), |(cluster_num, node_num), cluster| {
+            (cluster_num + 1, node_num + cluster.chunks.len() * 4)
+        })
+}
+
+pub fn check_namespace_quota(
+    store: &MetaStore,
+    namespace: &str,
+    added_clusters: usize,
+    added_nodes: usize,
+) -> Result<(), MetaStoreError> {
+    let quota = &store
+        .namespaces
+        .get(namespace)
+        .ok_or(MetaStoreError::NamespaceNotFound)?
+        .quota;
+    let (cluster_num, node_num) = get_namespace_usage(store, namespace);
+    if let Some(max_clusters) = quota.max_clusters {
+        if cluster_num + added_clusters > max_clusters {
+            return Err(MetaStoreError::QuotaExceeded);
+        }
+    }
+    if let Some(max_nodes) = quota.max_nodes {
+        if node_num + added_nodes > max_nodes {
+            return Err(MetaStoreError::QuotaExceeded);
+        }
+    }
+    Ok(())
+}
+
+// The tokens scoped to a namespace can only access the APIs of the clusters in it.
+// `path` should not include the api version.
+pub fn check_namespace_access(
+    store: &MetaStore,
+    namespace: &str,
+    method: &http::Method,
+    path: &str,
+) -> bool {
+    let in_namespace = |cluster_name: &str| {
+        ClusterName::try_from(cluster_name)
+            .ok()
+            .and_then(|cluster_name| store.clusters.get(&cluster_name))
+            .and_then(|cluster| cluster.namespace.as_deref())
+            == Some(namespace)
+    };
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["version"] => true,
+        ["namespaces", name] => *name == namespace && method == http::Method::GET,
+        ["clusters", "migrations", "shrink", cluster_name, _]
+        | ["clusters", "migrations", "expand", cluster_name] => in_namespace(cluster_name),
+        ["clusters", "migrations", ..] => false,
+        // Listing all the keys is only for the admin.
+        ["clusters", "keys", _] => false,
+        // The new cluster is always created in the namespace of the token.
+        ["clusters", "meta", _] if method == http::Method::POST => true,
+        ["clusters", "rename", cluster_name, ..] => in_namespace(cluster_name),
+        ["clusters", _, cluster_name] => in_namespace(cluster_name),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_validate() {
+        assert!(check_namespace_name("team-a_1").is_ok());
+        assert!(check_namespace_name("").is_err());
+        assert!(check_namespace_name("team/a").is_err());
+
+        let mut namespace = Namespace::default();
+        namespace
+            .policy
+            .insert("compression_strategy".to_string(), "disabled".to_string());
+        assert!(namespace.validate().is_ok());
+        namespace
+            .policy
+            .insert("unknown_field".to_string(), "1".to_string());
+        assert!(namespace.validate().is_err());
+    }
+
+    fn add_testing_proxies(store: &mut MetaStore, proxy_num: usize) {
+        for i in 1..=proxy_num {
+            let proxy_address = format!("127.0.0.{}:7000", i);
+            let node_addresses = [format!("127.0.0.{}:6000", i), format!("127.0.0.{}:6001", i)];
+            store
+                .add_proxy(proxy_address, node_addresses, None, None)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_namespace_quota_and_access() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 8);
+
+        let res = store.add_cluster_in_namespace("cluster1".to_string(), 4, "team".to_string());
+        assert_eq!(res.unwrap_err(), MetaStoreError::NamespaceNotFound);
+
+        let mut namespace = Namespace::default();
+        namespace.quota.max_clusters = Some(1);
+        namespace.quota.max_nodes = Some(8);
+        namespace.policy.insert(
+            "compression_strategy".to_string(),
+            "set_get_only".to_string(),
+        );
+        store.set_namespace("team".to_string(), namespace).unwrap();
+
+        store
+            .add_cluster_in_namespace("cluster1".to_string(), 4, "team".to_string())
+            .unwrap();
+        store.add_cluster("cluster2".to_string(), 4).unwrap();
+        let res = store.add_cluster_in_namespace("cluster3".to_string(), 4, "team".to_string());
+        assert_eq!(res.unwrap_err(), MetaStoreError::QuotaExceeded);
+        store.auto_add_nodes("cluster1".to_string(), 4).unwrap();
+        let res = store.auto_add_nodes("cluster1".to_string(), 4);
+        assert_eq!(res.unwrap_err(), MetaStoreError::QuotaExceeded);
+
+        let cluster = store.get_cluster_by_name("cluster1", 1).unwrap();
+        assert_eq!(
+            cluster.get_config().compression_strategy.to_str(),
+            "set_get_only"
+        );
+        let info = store.get_namespace("team").unwrap();
+        assert_eq!(info.cluster_names, vec!["cluster1".to_string()]);
+        assert_eq!(info.node_number, 8);
+        assert_eq!(store.remove_namespace("team"), Err(MetaStoreError::InUse));
+
+        let get = http::Method::GET;
+        assert!(check_namespace_access(
+            &store,
+            "team",
+            &get,
+            "/clusters/meta/cluster1"
+        ));
+        assert!(!check_namespace_access(
+            &store,
+            "team",
+            &get,
+            "/clusters/meta/cluster2"
+        ));
+        assert!(!check_namespace_access(
+            &store,
+            "team",
+            &get,
+            "/clusters/names"
+        ));
+        assert!(!check_namespace_access(
+            &store,
+            "team",
+            &get,
+            "/proxies/addresses"
+        ));
+        assert!(!check_namespace_access(
+            &store,
+            "team",
+            &get,
+            "/clusters/keys/cluster1"
+        ));
+        assert!(check_namespace_access(
+            &store,
+            "team",
+            &get,
+            "/namespaces/team"
+        ));
+        assert!(!check_namespace_access(
+            &store,
+            "team",
+            &http::Method::PUT,
+            "/namespaces/team"
+        ));
+        assert!(check_namespace_access(
+            &store,
+            "team",
+            &http::Method::POST,
+            "/clusters/migrations/expand/cluster1"
+        ));
+        assert!(!check_namespace_access(
+            &store,
+            "team",
+            &http::Method::PUT,
+            "/clusters/migrations"
+        ));
+    }
+}
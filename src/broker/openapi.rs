@@ -0,0 +1,120 @@
+use super::service::{BROKER_API_ROUTES, MEM_BROKER_API_VERSION};
+use crate::common::version::UNDERMOON_VERSION;
+use serde_json::{json, Map, Value};
+
+// Generated by the `broker_routes!` in `broker::service`.
+#[derive(Debug, Clone, Copy)]
+pub struct ApiRoute {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub handler: &'static str,
+    pub summary: &'static str,
+}
+
+impl ApiRoute {
+    pub fn get_path_params(&self) -> Vec<&'static str> {
+        self.path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+            .collect()
+    }
+}
+
+pub fn gen_broker_openapi() -> Value {
+    gen_openapi(BROKER_API_ROUTES)
+}
+
+// The request and response bodies are documented in `docs/memory_broker_api.md`.
+pub fn gen_openapi(routes: &[ApiRoute]) -> Value {
+    let mut paths = Map::new();
+    for route in routes.iter() {
+        let params: Vec<Value> = route
+            .get_path_params()
+            .into_iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": {"type": "string"},
+                })
+            })
+            .collect();
+        let operation = json!({
+            "operationId": route.handler,
+            "summary": route.summary,
+            "parameters": params,
+            "responses": {
+                "200": {"description": "Success"},
+                "default": {"description": "Error"},
+            },
+        });
+        let path = format!("{}{}", MEM_BROKER_API_VERSION, route.path);
+        if let Value::Object(operations) = paths.entry(path).or_insert_with(|| json!({})) {
+            operations.insert(route.method.to_string(), operation);
+        }
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Undermoon Memory Broker API",
+            "version": UNDERMOON_VERSION,
+        },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearerAuth": {"type": "http", "scheme": "bearer"},
+            },
+        },
+        // The token is only required when `api_tokens` is configured.
+        "security": [{}, {"bearerAuth": []}],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_gen_openapi() {
+        let routes = [
+            ApiRoute {
+                method: "get",
+                path: "/clusters/meta/{cluster_name}",
+                handler: "get_cluster_by_name",
+                summary: "Get a cluster",
+            },
+            ApiRoute {
+                method: "delete",
+                path: "/clusters/meta/{cluster_name}",
+                handler: "remove_cluster",
+                summary: "Delete a cluster",
+            },
+        ];
+        let doc = gen_openapi(&routes);
+        let path = &doc["paths"]["/api/v2/clusters/meta/{cluster_name}"];
+        assert_eq!(path["get"]["operationId"], "get_cluster_by_name");
+        assert_eq!(path["delete"]["summary"], "Delete a cluster");
+        assert_eq!(path["get"]["parameters"][0]["name"], "cluster_name");
+        assert_eq!(path["get"]["parameters"][0]["in"], "path");
+    }
+
+    #[test]
+    fn test_broker_api_routes() {
+        let mut routes = HashSet::new();
+        for route in BROKER_API_ROUTES.iter() {
+            assert!(routes.insert((route.method, route.path)), "{:?}", route);
+        }
+        let doc = gen_broker_openapi();
+        assert_eq!(
+            doc["paths"]["/api/v2/failures/{server_proxy_address}/{reporter_id}"]["post"]
+                ["parameters"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(doc["paths"]["/api/v2/openapi.json"]["get"].is_object());
+    }
+}
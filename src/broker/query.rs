@@ -1,7 +1,10 @@
 use super::store::{
-    ChunkRolePosition, ClusterStore, HostProxy, MetaStore, CHUNK_HALF_NODE_NUM, CHUNK_NODE_NUM,
+    ChunkRolePosition, ClusterStore, HostMaintenance, HostProxy, MetaStore, MetaStoreError,
+    MigrationTaskInfo, CHUNK_HALF_NODE_NUM, CHUNK_NODE_NUM,
+};
+use crate::common::cluster::{
+    Cluster, MigrationTaskMeta, Node, PeerProxy, Proxy, ReplMeta, ReplPeer,
 };
-use crate::common::cluster::{Cluster, Node, PeerProxy, Proxy, ReplMeta, ReplPeer};
 use crate::common::cluster::{ClusterName, Role};
 use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
@@ -56,22 +59,33 @@ impl<'a> MetaStoreQuery<'a> {
         let cluster = match cluster_opt {
             Some(cluster_store) => Self::cluster_store_to_cluster(&cluster_store),
             None => {
-                return Some(Proxy::new(
+                let mut proxy = Proxy::new(
                     address.to_string(),
                     self.store.global_epoch,
                     vec![],
                     proxy_resource.node_addresses.to_vec(),
                     vec![],
                     HashMap::new(),
-                ));
+                );
+                proxy.set_force_resync(proxy_resource.force_resync);
+                proxy.set_tls(proxy_resource.tls.clone());
+                proxy.set_proxy_id(proxy_resource.proxy_id.clone());
+                return Some(proxy);
             }
         };
 
         let cluster_name = cluster.get_name().clone();
+        let config = cluster.get_config();
         // Both global epoch and cluster epoch should work.
         // But cluster epoch avoid updating the meta of this proxy
         // if only other clusters are changing.
-        let epoch = cluster.get_epoch();
+        // The linked clusters could change without bumping the cluster epoch
+        // so the global epoch is used instead.
+        let epoch = if !config.get_linked_clusters().is_empty() {
+            self.store.global_epoch
+        } else {
+            cluster.get_epoch()
+        };
         let nodes: Vec<Node> = cluster
             .get_nodes()
             .iter()
@@ -83,30 +97,24 @@ impl<'a> MetaStoreQuery<'a> {
             let free_nodes = proxy_resource.node_addresses.to_vec();
             (vec![], free_nodes)
         } else {
-            let peers = cluster
-                .get_nodes()
-                .iter()
-                .filter(|n| n.get_role() == Role::Master && n.get_proxy_address() != address)
-                .cloned()
-                .group_by(|node| node.get_proxy_address().to_string())
-                .into_iter()
-                .map(|(proxy_address, nodes)| {
-                    // Collect all slots from masters.
-                    let slots = nodes.map(Node::into_slots).flatten().collect();
-                    PeerProxy {
-                        proxy_address,
-                        cluster_name: cluster_name.clone(),
-                        slots,
-                    }
-                })
-                .collect();
+            let mut peers = Self::get_peer_proxies(&cluster, address);
+            // The proxy needs to know where the slots of the canary
+            // and double write clusters are.
+            for linked_cluster_name in config.get_linked_clusters() {
+                if let Some(linked_cluster_store) =
+                    Self::get_cluster_store(clusters, linked_cluster_name, migration_limit)
+                {
+                    let linked_cluster = Self::cluster_store_to_cluster(&linked_cluster_store);
+                    peers.extend(Self::get_peer_proxies(&linked_cluster, address));
+                }
+            }
             (peers, vec![])
         };
 
         let mut cluster_config = HashMap::new();
-        cluster_config.insert(cluster_name, cluster.get_config());
+        cluster_config.insert(cluster_name, config);
 
-        let proxy = Proxy::new(
+        let mut proxy = Proxy::new(
             address.to_string(),
             epoch,
             nodes,
@@ -114,9 +122,93 @@ impl<'a> MetaStoreQuery<'a> {
             peers,
             cluster_config,
         );
+        proxy.set_force_resync(proxy_resource.force_resync);
+        proxy.set_tls(proxy_resource.tls.clone());
+        proxy.set_proxy_id(proxy_resource.proxy_id.clone());
         Some(proxy)
     }
 
+    fn get_peer_proxies(cluster: &Cluster, address: &str) -> Vec<PeerProxy> {
+        let cluster_name = cluster.get_name().clone();
+        cluster
+            .get_nodes()
+            .iter()
+            .filter(|n| n.get_role() == Role::Master && n.get_proxy_address() != address)
+            .cloned()
+            .group_by(|node| node.get_proxy_address().to_string())
+            .into_iter()
+            .map(|(proxy_address, nodes)| {
+                // Collect all slots from masters.
+                let slots = nodes.map(Node::into_slots).flatten().collect();
+                PeerProxy {
+                    proxy_address,
+                    cluster_name: cluster_name.clone(),
+                    slots,
+                }
+            })
+            .collect()
+    }
+
+    // Only the migrating side is listed since the importing side has the same metadata.
+    pub fn get_migration_tasks(&self, now: i64) -> Vec<MigrationTaskInfo> {
+        let mut tasks = vec![];
+        for (cluster_name, cluster_store) in self.store.clusters.iter() {
+            for chunk in cluster_store.chunks.iter() {
+                for slot_range_store in chunk.migrating_slots.iter().flatten() {
+                    if !slot_range_store.is_migrating {
+                        continue;
+                    }
+                    let slot_range = slot_range_store.to_slot_range(&cluster_store.chunks);
+                    let meta = match slot_range.tag.get_migration_meta() {
+                        Some(meta) => meta,
+                        None => continue,
+                    };
+                    let start_time = slot_range_store.start_time;
+                    let elapsed = if start_time == 0 {
+                        None
+                    } else {
+                        Some(now - start_time)
+                    };
+                    let task = MigrationTaskMeta {
+                        cluster_name: cluster_name.clone(),
+                        slot_range: slot_range.clone(),
+                    }
+                    .into_strings()
+                    .join(" ");
+                    let progress = self
+                        .store
+                        .migration_events
+                        .find(cluster_name, &meta.src_proxy_address, &task)
+                        .and_then(|task_events| task_events.progress.clone());
+                    let percentage = progress
+                        .as_ref()
+                        .and_then(|progress| progress.get_percentage());
+                    let eta = match (elapsed, percentage) {
+                        (Some(elapsed), Some(percentage)) if percentage > 0.0 => {
+                            Some((elapsed as f64 * (100.0 - percentage) / percentage) as i64)
+                        }
+                        _ => None,
+                    };
+                    tasks.push(MigrationTaskInfo {
+                        cluster_name: cluster_name.clone(),
+                        epoch: meta.epoch,
+                        range_list: slot_range_store.range_list.clone(),
+                        src_proxy_address: meta.src_proxy_address.clone(),
+                        src_node_address: meta.src_node_address.clone(),
+                        dst_proxy_address: meta.dst_proxy_address.clone(),
+                        dst_node_address: meta.dst_node_address.clone(),
+                        start_time,
+                        elapsed,
+                        progress,
+                        percentage,
+                        eta,
+                    });
+                }
+            }
+        }
+        tasks
+    }
+
     pub fn get_cluster_names(&self) -> Vec<ClusterName> {
         self.store.clusters.keys().cloned().collect()
     }
@@ -263,6 +355,9 @@ impl<'a> MetaStoreQuery<'a> {
             if failures.contains_key(proxy_address) {
                 continue;
             }
+            if self.store.maintenance_hosts.contains(&proxy_resource.host) {
+                continue;
+            }
             free_proxies.push(HostProxy {
                 host: proxy_resource.host.clone(),
                 proxy_address: proxy_address.clone(),
@@ -271,10 +366,54 @@ impl<'a> MetaStoreQuery<'a> {
         free_proxies
     }
 
+    pub fn get_rebuilding_proxies(&self) -> Vec<String> {
+        self.store
+            .all_proxies
+            .values()
+            .filter(|proxy| proxy.rebuilding && proxy.cluster.is_some())
+            .map(|proxy| proxy.proxy_address.clone())
+            .collect()
+    }
+
+    // The host is safe to reboot after all its masters are promoted away.
+    pub fn get_host_maintenance(&self, host: &str) -> Result<HostMaintenance, MetaStoreError> {
+        let in_maintenance = self.store.maintenance_hosts.contains(host);
+        if !in_maintenance && !self.store.all_proxies.values().any(|p| p.host == host) {
+            return Err(MetaStoreError::HostNotFound);
+        }
+
+        let mut masters = vec![];
+        for cluster in self.store.clusters.values() {
+            for chunk in cluster.chunks.iter() {
+                let master_indexes = match chunk.role_position {
+                    ChunkRolePosition::Normal => [0, 2],
+                    ChunkRolePosition::FirstChunkMaster => [0, 1],
+                    ChunkRolePosition::SecondChunkMaster => [2, 3],
+                };
+                for i in master_indexes.iter() {
+                    if chunk.hosts[i / 2] == host {
+                        masters.push(chunk.node_addresses[*i].clone());
+                    }
+                }
+            }
+        }
+
+        Ok(HostMaintenance {
+            host: host.to_string(),
+            in_maintenance,
+            safe_to_reboot: in_maintenance && masters.is_empty(),
+            masters,
+        })
+    }
+
     pub fn check_metadata(&self) -> bool {
         let mut data_correct = true;
 
         for (cluster_name, cluster) in self.store.clusters.iter() {
+            if let Err(err) = cluster.check_slot_ranges() {
+                error!("invalid slot ranges in cluster {}: {}", cluster_name, err);
+                data_correct = false;
+            }
             let mut proxy_address_set = HashSet::new();
             for chunk in cluster.chunks.iter() {
                 for (i, proxy_address) in chunk.proxy_addresses.iter().enumerate() {
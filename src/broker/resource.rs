@@ -1,5 +1,6 @@
 use super::store::{MetaStore, MetaStoreError};
-use std::collections::HashMap;
+use crate::common::cluster::RangeList;
+use std::collections::{BTreeSet, HashMap};
 
 pub struct ResourceChecker {
     store: MetaStore,
@@ -56,6 +57,125 @@ impl ResourceChecker {
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct FailoverPlan {
+    pub failed_proxy_address: String,
+    pub cluster_name: String,
+    // None if there's no free proxy to replace it.
+    pub new_proxy_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct UnservedSlots {
+    pub cluster_name: String,
+    pub range_list: RangeList,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct FailureSimulation {
+    pub host: String,
+    pub failover_plan: Vec<FailoverPlan>,
+    // The clusters with masters left without replicas.
+    pub clusters_losing_redundancy: Vec<String>,
+    // Both proxies of the chunks holding these slots are down.
+    pub unserved_slots: Vec<UnservedSlots>,
+}
+
+impl ResourceChecker {
+    // Runs the failover on a copy of the metadata as if all the proxies on the host failed.
+    pub fn simulate_host_failure(
+        &self,
+        host: &str,
+        migration_limit: u64,
+    ) -> Result<FailureSimulation, MetaStoreError> {
+        let mut proxy_addresses: Vec<(String, Option<String>)> = self
+            .store
+            .all_proxies
+            .values()
+            .filter(|proxy| proxy.host == host)
+            .map(|proxy| {
+                let cluster_name = proxy.cluster.as_ref().map(|name| name.to_string());
+                (proxy.proxy_address.clone(), cluster_name)
+            })
+            .collect();
+        if proxy_addresses.is_empty() {
+            return Err(MetaStoreError::HostNotFound);
+        }
+        proxy_addresses.sort();
+
+        let unserved_slots = self.get_unserved_slots(host);
+
+        let mut store = self.store.clone();
+        let mut failover_plan = vec![];
+        let mut clusters_losing_redundancy = BTreeSet::new();
+        for (proxy_address, cluster_name) in proxy_addresses.into_iter() {
+            let new_proxy = match store.replace_failed_proxy(proxy_address.clone(), migration_limit)
+            {
+                Ok(new_proxy) => new_proxy,
+                Err(MetaStoreError::NoAvailableResource) => None,
+                Err(err) => {
+                    error!("ResourceChecker failed to simulate failure: {}", err);
+                    return Err(err);
+                }
+            };
+            let cluster_name = match cluster_name {
+                Some(cluster_name) => cluster_name,
+                None => continue,
+            };
+            if new_proxy.is_none() {
+                clusters_losing_redundancy.insert(cluster_name.clone());
+            }
+            failover_plan.push(FailoverPlan {
+                failed_proxy_address: proxy_address,
+                cluster_name,
+                new_proxy_address: new_proxy.map(|proxy| proxy.get_address().to_string()),
+            });
+        }
+
+        Ok(FailureSimulation {
+            host: host.to_string(),
+            failover_plan,
+            clusters_losing_redundancy: clusters_losing_redundancy.into_iter().collect(),
+            unserved_slots,
+        })
+    }
+
+    fn get_unserved_slots(&self, host: &str) -> Vec<UnservedSlots> {
+        let store = &self.store;
+        let is_down = |proxy_address: &str| -> bool {
+            store.failed_proxies.contains(proxy_address)
+                || store
+                    .all_proxies
+                    .get(proxy_address)
+                    .map(|proxy| proxy.host == host)
+                    .unwrap_or(false)
+        };
+
+        let mut unserved_slots = vec![];
+        for (cluster_name, cluster) in store.clusters.iter() {
+            for chunk in cluster.chunks.iter() {
+                if !chunk.proxy_addresses.iter().all(|addr| is_down(addr)) {
+                    continue;
+                }
+                let stable = chunk.stable_slots.iter().flatten().map(|s| &s.range_list);
+                let migrating = chunk
+                    .migrating_slots
+                    .iter()
+                    .flatten()
+                    .filter(|s| s.is_migrating)
+                    .map(|s| &s.range_list);
+                for range_list in stable.chain(migrating) {
+                    unserved_slots.push(UnservedSlots {
+                        cluster_name: cluster_name.to_string(),
+                        range_list: range_list.clone(),
+                    });
+                }
+            }
+        }
+        unserved_slots
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,7 +189,7 @@ mod tests {
                     format!("127.0.0.{}:60{:02}", host_index, i * 2 + 1),
                 ];
                 store
-                    .add_proxy(proxy_address, node_addresses, None)
+                    .add_proxy(proxy_address, node_addresses, None, None)
                     .unwrap();
             }
         }
@@ -109,4 +229,52 @@ mod tests {
         let proxies = res.unwrap();
         assert!(!proxies.is_empty());
     }
+
+    #[test]
+    fn test_simulate_host_failure() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 3, 2);
+        store.add_cluster("test_cluster".to_string(), 8).unwrap();
+        let host = store.clusters.values().next().unwrap().chunks[0].hosts[0].clone();
+
+        let checker = ResourceChecker::new(store.clone());
+        assert_eq!(
+            checker.simulate_host_failure("127.0.0.9", 2),
+            Err(MetaStoreError::HostNotFound)
+        );
+
+        let simulation = checker.simulate_host_failure(&host, 2).unwrap();
+        assert_eq!(simulation.host, host);
+        assert!(!simulation.failover_plan.is_empty());
+        assert!(simulation
+            .failover_plan
+            .iter()
+            .all(|plan| plan.cluster_name == "test_cluster"));
+        assert!(simulation.unserved_slots.is_empty());
+        // The real metadata is not changed.
+        assert!(store.get_failed_proxies().is_empty());
+    }
+
+    #[test]
+    fn test_simulate_unserved_slots() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 2, 1);
+        store.add_cluster("test_cluster".to_string(), 4).unwrap();
+        store
+            .replace_failed_proxy("127.0.0.2:7001".to_string(), 2)
+            .unwrap_err();
+
+        let checker = ResourceChecker::new(store);
+        let simulation = checker.simulate_host_failure("127.0.0.1", 2).unwrap();
+        assert_eq!(
+            simulation.failover_plan,
+            vec![FailoverPlan {
+                failed_proxy_address: "127.0.0.1:7001".to_string(),
+                cluster_name: "test_cluster".to_string(),
+                new_proxy_address: None,
+            }]
+        );
+        assert_eq!(simulation.clusters_losing_redundancy, vec!["test_cluster"]);
+        assert_eq!(simulation.unserved_slots.len(), 2);
+    }
 }
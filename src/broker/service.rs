@@ -1,116 +1,197 @@
+use super::alert::{update_active_alerts, AlertChecker, AlertEvent, AlertRule};
+use super::balance::{BalancePolicy, MaintenanceWindow};
+use super::history::ClusterStatsSample;
+use super::idempotency::{CachedResponse, IdempotencyCache, IdempotencyState};
+use super::namespace::{
+    check_namespace_access, Namespace, NamespaceInfo, NamespaceScope, NamespaceTokens,
+};
+use super::openapi::{gen_broker_openapi, ApiRoute};
 use super::persistence::{MetaStorage, MetaSyncError};
 use super::replication::MetaReplicator;
-use super::resource::ResourceChecker;
-use super::store::{MetaStore, MetaStoreError, CHUNK_HALF_NODE_NUM};
+use super::resource::{FailureSimulation, ResourceChecker};
+use super::spec::{apply_spec, plan_spec, DeclarativeSpec, SpecPlan};
+use super::store::{
+    AuditEntry, ForceMigrationAction, HostMaintenance, MetaStore, MetaStoreError,
+    MigrationTaskInfo, ProxyStatsSample, CHUNK_HALF_NODE_NUM,
+};
 use crate::broker::recovery::{fetch_largest_epoch, EpochFetchResult};
-use crate::common::cluster::{Cluster, ClusterName, MigrationTaskMeta, Node, Proxy};
+use crate::common::cluster::{
+    Cluster, ClusterName, MigrationTaskEvents, MigrationTaskMeta, Node, Proxy, ProxyRuntimeStats,
+    ProxyTlsMeta, Range, RangeList, RestorePolicy, Role,
+};
+use crate::common::utils::SLOT_NUM;
 use crate::common::version::UNDERMOON_VERSION;
 use crate::coordinator::http_mani_broker::ReplaceProxyResponse;
 use crate::coordinator::http_meta_broker::{
     ClusterNamesPayload, ClusterPayload, FailedProxiesPayload, FailuresPayload,
-    ProxyAddressesPayload, ProxyPayload,
+    ProxyAddressesPayload, ProxyPayload, RebuildingProxiesPayload,
+};
+use crate::migration::diff::{
+    self, KeyspaceDiffConfig, KeyspaceDiffReport, DEFAULT_DIFF_KEYS_PER_SEC, DEFAULT_MAX_DIFFS,
 };
+use crate::migration::list_keys::{
+    self, ListKeysConfig, DEFAULT_LIST_KEYS_PER_SEC, DEFAULT_LIST_KEYS_SCAN_COUNT,
+};
+use crate::protocol::{BinSafeStr, PooledRedisClientFactory, RedisClientError, RedisClientFactory};
 use actix_http::ResponseBuilder;
 use actix_web::dev::Service;
-use actix_web::{error, http, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{error, http, web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use arc_swap::ArcSwap;
+use bytes::Bytes;
+use futures::{Future, Stream, StreamExt};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::num::NonZeroU64;
+use std::str::FromStr;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 pub const MEM_BROKER_API_VERSION: &str = "/api/v2";
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+// Each route is only defined here so that the OpenAPI document is generated from the same routes.
+macro_rules! broker_routes {
+    ($($method:ident $path:literal => $handler:ident, $summary:literal;)*) => {
+        fn gen_scope() -> actix_web::Scope {
+            web::scope(MEM_BROKER_API_VERSION)$(.route($path, web::$method().to($handler)))*
+        }
+
+        pub const BROKER_API_ROUTES: &[ApiRoute] = &[$(ApiRoute {
+            method: stringify!($method),
+            path: $path,
+            handler: stringify!($handler),
+            summary: $summary,
+        }),*];
+    };
+}
+
+broker_routes! {
+    get "/version" => get_version, "Get the version of undermoon";
+    get "/metadata" => get_all_metadata, "Get the inner metadata";
+    put "/metadata" => restore_metadata, "Restore the metadata";
+    // Broker api
+    get "/clusters/names" => get_cluster_names, "Get the cluster names";
+    get "/clusters/meta/{cluster_name}" => get_cluster_by_name, "Get a cluster";
+    get "/proxies/addresses" => get_proxy_addresses, "Get the proxy addresses";
+    get "/proxies/meta/{address}" => get_proxy_by_address, "Get a proxy";
+    get "/failures" => get_failures, "Get the reported failures";
+    post "/failures/{server_proxy_address}/{reporter_id}" => add_failure, "Report a failed proxy";
+    post "/proxies/failover/{address}" => replace_failed_node, "Replace a failed proxy";
+    put "/clusters/migrations" => commit_migration, "Commit a migration task";
+    get "/clusters/migrations/tasks" => get_migration_tasks, "Get the migration tasks";
+    post "/clusters/migrations/tasks/force" => force_migration, "Force a migration task";
+    post "/clusters/keyspace_diff/{cluster_name}" => diff_keyspace, "Diff the keyspace of a cluster";
+    get "/clusters/keys/{cluster_name}" => list_cluster_keys, "List the keys of a cluster";
+    get "/proxies/failed/addresses" => get_failed_proxies, "Get the failed proxies";
+    get "/proxies/rebuilding/addresses" => get_rebuilding_proxies, "Get the rebuilding proxies";
+    delete "/proxies/rebuilding/{proxy_address}" => finish_proxy_rebuilding, "Finish rebuilding a proxy";
+    put "/proxies/stats/{proxy_address}" => report_proxy_stats, "Report the stats of a proxy";
+    put "/proxies/migration_events/{proxy_address}" => report_migration_events, "Report the migration events of a proxy";
+    get "/proxies/config" => get_proxy_config, "Get the proxy config";
+    // Additional api
+    post "/clusters/meta/{cluster_name}" => add_cluster, "Create a cluster";
+    delete "/clusters/meta/{cluster_name}" => remove_cluster, "Delete a cluster";
+    post "/clusters/rename/{cluster_name}/{new_cluster_name}" => rename_cluster, "Rename a cluster";
+    delete "/clusters/rename/{cluster_name}" => finish_cluster_renaming, "Finish renaming a cluster";
+    patch "/clusters/nodes/{cluster_name}" => auto_add_nodes, "Add nodes to a cluster";
+    put "/clusters/nodes/{cluster_name}" => auto_scale_up_nodes, "Add nodes to a cluster if needed";
+    delete "/clusters/free_nodes/{cluster_name}" => audo_delete_free_nodes, "Delete the unused nodes of a cluster";
+    post "/clusters/migrations/shrink/{cluster_name}/{node_number}" => migrate_slots_to_scale_down, "Start migration for scaling down";
+    post "/clusters/migrations/expand/{cluster_name}" => migrate_slots, "Start migration for scaling out";
+    patch "/clusters/config/{cluster_name}" => change_config, "Change the config of a cluster";
+    put "/clusters/balance/{cluster_name}" => balance_masters, "Balance the masters of a cluster";
+    get "/clusters/stats/{cluster_name}" => get_cluster_stats, "Get the stats of a cluster";
+    get "/clusters/migration_events/{cluster_name}" => get_migration_events, "Get the migration events of a cluster";
+    post "/proxies/meta" => add_proxy, "Add a proxy";
+    get "/proxies/tls/{proxy_address}" => get_proxy_tls, "Get the TLS config of a proxy";
+    put "/proxies/tls/{proxy_address}" => set_proxy_tls, "Rotate the TLS config of a proxy";
+    delete "/proxies/tls/{proxy_address}" => remove_proxy_tls, "Remove the TLS config of a proxy";
+    post "/proxies/resync/{proxy_address}" => force_resync_proxy, "Force a proxy to resync the metadata";
+    delete "/proxies/resync/{proxy_address}" => clear_proxy_resync, "Stop forcing a proxy to resync the metadata";
+    get "/proxies/stats" => get_all_proxy_stats, "Get the stats of all the proxies";
+    get "/proxies/stats/{proxy_address}" => get_proxy_stats, "Get the stats of a proxy";
+    get "/hosts/maintenance" => get_maintenance_hosts, "Get the hosts in maintenance";
+    get "/hosts/maintenance/{host}" => get_host_maintenance, "Get the maintenance of a host";
+    post "/hosts/maintenance/{host}" => start_host_maintenance, "Start the maintenance of a host";
+    delete "/hosts/maintenance/{host}" => end_host_maintenance, "End the maintenance of a host";
+    get "/audit" => get_audit_log, "Get the audit log";
+    get "/alerts" => get_active_alerts, "Get the active alerts";
+    get "/alerts/rules" => get_alert_rules, "Get the alert rules";
+    put "/alerts/rules/{rule_name}" => set_alert_rule, "Set an alert rule";
+    delete "/alerts/rules/{rule_name}" => remove_alert_rule, "Remove an alert rule";
+    get "/namespaces" => get_namespaces, "Get the namespaces";
+    get "/namespaces/{namespace}" => get_namespace, "Get a namespace";
+    put "/namespaces/{namespace}" => set_namespace, "Set a namespace";
+    delete "/namespaces/{namespace}" => remove_namespace, "Remove a namespace";
+    delete "/proxies/meta/{proxy_address}" => remove_proxy, "Delete a proxy";
+    post "/resources/failures/check" => check_resource_for_failures, "Check the resources for failures";
+    get "/resources/failures/simulate/{host}" => simulate_host_failure, "Simulate the failure of a host";
+    put "/config" => change_broker_config, "Change the broker config";
+    put "/proxies/config" => set_proxy_config, "Set the proxy config";
+    post "/spec" => apply_declarative_spec, "Apply a declarative spec";
+    put "/epoch/recovery" => recover_epoch, "Recover the epoch from the proxies";
+    put "/epoch/{new_epoch}" => bump_epoch, "Bump the global epoch";
+    get "/openapi.json" => get_openapi, "Get the OpenAPI document of this API";
+}
 
 pub fn configure_app(cfg: &mut web::ServiceConfig, service: Arc<MemBrokerService>) {
     let service2 = service.clone();
-    cfg.data(service).service(
-        web::scope(MEM_BROKER_API_VERSION)
-            .wrap_fn(move |req, srv| {
-                let method = req.method().clone();
-                let peer_addr = match req.peer_addr() {
-                    None => "".to_string(),
-                    Some(address) => format!("{:?}", address),
-                };
-                let req_str = format!("{} {} {} {:?} {}", req.method(), req.path(), req.query_string(), req.version(), peer_addr);
-                let fut = srv.call(req);
-
-                let service = if service2.config.debug {
-                    Some(service2.clone())
-                } else {
-                    None
+    cfg.data(service)
+        .service(gen_scope().wrap_fn(move |req, srv| {
+            let method = req.method().clone();
+            let peer_addr = match req.peer_addr() {
+                None => "".to_string(),
+                Some(address) => format!("{:?}", address),
+            };
+            let req_str = format!(
+                "{} {} {} {:?} {}",
+                req.method(),
+                req.path(),
+                req.query_string(),
+                req.version(),
+                peer_addr
+            );
+            let token = req
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            let fut = match service2.check_api_token(token, req.method(), req.path()) {
+                Ok(scope) => {
+                    if let Some(namespace) = scope {
+                        req.extensions_mut().insert(NamespaceScope(namespace));
+                    }
+                    Ok(srv.call(req))
+                }
+                Err(err) => Err(err),
+            };
+
+            let service = if service2.config.debug {
+                Some(service2.clone())
+            } else {
+                None
+            };
+
+            async move {
+                let res = match fut {
+                    Ok(fut) => fut.await,
+                    Err(err) => Err(err.into()),
                 };
-
-                async move {
-                    let res = fut.await;
-                    // The GET APIs are accessed too frequently so we don't log them.
-                    if method != http::Method::GET {
-                        match &res {
-                            Ok(response) => info!("{} status {}", req_str, response.status()),
-                            Err(err) => info!("{} err {}", req_str, err)
-                        }
-                    } else if let Some(service) = service {
-                        if let Err(invalid_meta_store) = service.check_metadata() {
-                            error!("Invalid meta store: {:?}", invalid_meta_store);
-                        }
+                // The GET APIs are accessed too frequently so we don't log them.
+                if method != http::Method::GET {
+                    match &res {
+                        Ok(response) => info!("{} status {}", req_str, response.status()),
+                        Err(err) => info!("{} err {}", req_str, err),
+                    }
+                } else if let Some(service) = service {
+                    if let Err(invalid_meta_store) = service.check_metadata() {
+                        error!("Invalid meta store: {:?}", invalid_meta_store);
                     }
-                    res
                 }
-            })
-            .route("/version", web::get().to(get_version))
-            .route("/metadata", web::get().to(get_all_metadata))
-            .route("/metadata", web::put().to(restore_metadata))
-            // Broker api
-            .route("/clusters/names", web::get().to(get_cluster_names))
-            .route(
-                "/clusters/meta/{cluster_name}",
-                web::get().to(get_cluster_by_name),
-            )
-            .route("/proxies/addresses", web::get().to(get_proxy_addresses))
-            .route(
-                "/proxies/meta/{address}",
-                web::get().to(get_proxy_by_address),
-            )
-            .route("/failures", web::get().to(get_failures))
-            .route(
-                "/failures/{server_proxy_address}/{reporter_id}",
-                web::post().to(add_failure),
-            )
-            .route(
-                "/proxies/failover/{address}",
-                web::post().to(replace_failed_node),
-            )
-            .route("/clusters/migrations", web::put().to(commit_migration))
-            .route("/proxies/failed/addresses", web::get().to(get_failed_proxies))
-
-            // Additional api
-            .route("/clusters/meta/{cluster_name}", web::post().to(add_cluster))
-            .route("/clusters/meta/{cluster_name}", web::delete().to(remove_cluster))
-            .route(
-                "/clusters/nodes/{cluster_name}",
-                web::patch().to(auto_add_nodes),
-            )
-            .route(
-                "/clusters/nodes/{cluster_name}",
-                web::put().to(auto_scale_up_nodes),
-            )
-            .route("/clusters/free_nodes/{cluster_name}", web::delete().to(audo_delete_free_nodes))
-            .route(
-                "/clusters/migrations/shrink/{cluster_name}/{node_number}",
-                web::post().to(migrate_slots_to_scale_down),
-            )
-            .route("/clusters/migrations/expand/{cluster_name}", web::post().to(migrate_slots))
-            .route("/clusters/config/{cluster_name}", web::patch().to(change_config))
-            .route("/clusters/balance/{cluster_name}", web::put().to(balance_masters))
-
-            .route("/proxies/meta", web::post().to(add_proxy))
-            .route(
-                "/proxies/meta/{proxy_address}",
-                web::delete().to(remove_proxy),
-            )
-            .route("/resources/failures/check", web::post().to(check_resource_for_failures))
-            .route("/config", web::put().to(change_broker_config))
-            .route("/epoch/recovery", web::put().to(recover_epoch))
-            .route("/epoch/{new_epoch}", web::put().to(bump_epoch)),
-    );
+                res
+            }
+        }));
 }
 
 pub type ReplicaAddresses = Arc<ArcSwap<Vec<String>>>;
@@ -127,6 +208,21 @@ pub struct MemBrokerConfig {
     pub update_meta_file_interval: Option<NonZeroU64>,
     pub replica_addresses: ReplicaAddresses,
     pub sync_meta_interval: Option<NonZeroU64>,
+    pub check_alert_interval: Option<NonZeroU64>,
+    // Saves the cluster statistics history in the meta file and the replicas.
+    pub persist_cluster_stats: bool,
+    // Saves the migration events in the meta file and the replicas.
+    pub persist_migration_events: bool,
+    pub namespace_tokens: NamespaceTokens,
+    // Periodically starts the migrations for the unbalanced clusters.
+    pub auto_balance_interval: Option<NonZeroU64>,
+    pub balance_policy: BalancePolicy,
+    // Periodically moves the masters back for the clusters with the `auto` failback policy.
+    pub auto_failback_interval: Option<NonZeroU64>,
+    // Always allowed when it's None.
+    pub failback_window: Option<MaintenanceWindow>,
+    // Keeps the outcomes of the requests with `Idempotency-Key` for this long.
+    pub idempotency_key_ttl: u64, // in seconds
     pub debug: bool,
 }
 
@@ -148,6 +244,9 @@ pub struct MemBrokerService {
     store: Arc<RwLock<MetaStore>>,
     meta_storage: Arc<dyn MetaStorage + Send + Sync + 'static>,
     meta_replicator: Arc<dyn MetaReplicator + Send + Sync + 'static>,
+    // (rule_name, target) => event
+    active_alerts: RwLock<HashMap<(String, String), AlertEvent>>,
+    idempotency_cache: IdempotencyCache,
 }
 
 impl MemBrokerService {
@@ -163,12 +262,22 @@ impl MemBrokerService {
             info!("restore metadata");
             meta_store.restore(last)?;
         }
-
+        meta_store
+            .cluster_stats
+            .set_persisted(config.persist_cluster_stats);
+        meta_store
+            .migration_events
+            .set_persisted(config.persist_migration_events);
+
+        let idempotency_cache =
+            IdempotencyCache::new(Duration::from_secs(config.idempotency_key_ttl));
         let service = Self {
             config,
             store: Arc::new(RwLock::new(meta_store)),
             meta_storage,
             meta_replicator,
+            active_alerts: RwLock::new(HashMap::new()),
+            idempotency_cache,
         };
         Ok(service)
     }
@@ -208,6 +317,25 @@ impl MemBrokerService {
             .restore(meta_store)
     }
 
+    // Only returns the plan without `execute`.
+    // Nothing is changed if any step fails.
+    pub fn apply_spec(
+        &self,
+        spec: &DeclarativeSpec,
+        prune: bool,
+        execute: bool,
+    ) -> Result<SpecPlan, MetaStoreError> {
+        if !execute {
+            let store = self.store.read().expect("MemBrokerService::apply_spec");
+            return plan_spec(&store, spec, prune);
+        }
+        let mut store = self.store.write().expect("MemBrokerService::apply_spec");
+        let mut new_store = store.clone();
+        let plan = apply_spec(&mut new_store, spec, prune)?;
+        *store = new_store;
+        Ok(plan)
+    }
+
     pub fn get_proxy_addresses(&self, offset: Option<usize>, limit: Option<usize>) -> Vec<String> {
         self.store
             .read()
@@ -242,23 +370,33 @@ impl MemBrokerService {
             .get_cluster_by_name(name, migration_limit)
     }
 
-    pub fn add_proxy(&self, proxy_resource: ProxyResourcePayload) -> Result<(), MetaStoreError> {
+    pub fn add_proxy(
+        &self,
+        proxy_resource: ProxyResourcePayload,
+    ) -> Result<String, MetaStoreError> {
         let ProxyResourcePayload {
             proxy_address,
             nodes,
             host,
+            proxy_id,
         } = proxy_resource;
         self.store
             .write()
             .expect("MemBrokerService::add_proxy")
-            .add_proxy(proxy_address, nodes, host)
+            .add_proxy(proxy_address, nodes, host, proxy_id)
     }
 
-    pub fn add_cluster(&self, cluster_name: String, node_num: usize) -> Result<(), MetaStoreError> {
-        self.store
-            .write()
-            .expect("MemBrokerService::add_cluster")
-            .add_cluster(cluster_name, node_num)
+    pub fn add_cluster(
+        &self,
+        cluster_name: String,
+        node_num: usize,
+        namespace: Option<String>,
+    ) -> Result<(), MetaStoreError> {
+        let mut store = self.store.write().expect("MemBrokerService::add_cluster");
+        match namespace {
+            Some(namespace) => store.add_cluster_in_namespace(cluster_name, node_num, namespace),
+            None => store.add_cluster(cluster_name, node_num),
+        }
     }
 
     pub fn remove_cluster(&self, cluster_name: String) -> Result<(), MetaStoreError> {
@@ -268,6 +406,24 @@ impl MemBrokerService {
             .remove_cluster(cluster_name)
     }
 
+    pub fn rename_cluster(
+        &self,
+        cluster_name: String,
+        new_cluster_name: String,
+    ) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::rename_cluster")
+            .rename_cluster(cluster_name, new_cluster_name)
+    }
+
+    pub fn finish_cluster_renaming(&self, cluster_name: String) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::finish_cluster_renaming")
+            .finish_cluster_renaming(cluster_name)
+    }
+
     pub fn auto_add_node(
         &self,
         cluster_name: String,
@@ -322,6 +478,87 @@ impl MemBrokerService {
             .remove_proxy(proxy_address)
     }
 
+    pub fn get_proxy_tls(
+        &self,
+        proxy_address: &str,
+    ) -> Result<Option<ProxyTlsMeta>, MetaStoreError> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_proxy_tls")
+            .get_proxy_tls(proxy_address)
+    }
+
+    pub fn set_proxy_tls(
+        &self,
+        proxy_address: String,
+        payload: ProxyTlsPayload,
+    ) -> Result<ProxyTlsMeta, MetaStoreError> {
+        let ProxyTlsPayload {
+            pkcs12_file,
+            password,
+        } = payload;
+        self.store
+            .write()
+            .expect("MemBrokerService::set_proxy_tls")
+            .set_proxy_tls(proxy_address, pkcs12_file, password)
+    }
+
+    pub fn remove_proxy_tls(&self, proxy_address: String) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::remove_proxy_tls")
+            .remove_proxy_tls(proxy_address)
+    }
+
+    pub fn set_proxy_force_resync(
+        &self,
+        proxy_address: String,
+        force_resync: bool,
+    ) -> Result<(), MetaStoreError> {
+        let now = chrono::Utc::now().timestamp();
+        self.store
+            .write()
+            .expect("MemBrokerService::set_proxy_force_resync")
+            .set_proxy_force_resync(proxy_address, force_resync, now)
+    }
+
+    pub fn start_host_maintenance(&self, host: String) -> Result<(), MetaStoreError> {
+        let now = chrono::Utc::now().timestamp();
+        self.store
+            .write()
+            .expect("MemBrokerService::start_host_maintenance")
+            .start_host_maintenance(host, now)
+    }
+
+    pub fn end_host_maintenance(&self, host: String) -> Result<(), MetaStoreError> {
+        let now = chrono::Utc::now().timestamp();
+        self.store
+            .write()
+            .expect("MemBrokerService::end_host_maintenance")
+            .end_host_maintenance(host, now)
+    }
+
+    pub fn get_maintenance_hosts(&self) -> Vec<String> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_maintenance_hosts")
+            .get_maintenance_hosts()
+    }
+
+    pub fn get_host_maintenance(&self, host: &str) -> Result<HostMaintenance, MetaStoreError> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_host_maintenance")
+            .get_host_maintenance(host)
+    }
+
+    pub fn get_audit_log(&self) -> Vec<AuditEntry> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_audit_log")
+            .get_audit_log()
+    }
+
     pub fn check_resource_for_failures(&self) -> Result<Vec<String>, MetaStoreError> {
         let migration_limit = self.config.migration_limit;
         let store_copy = self
@@ -333,6 +570,17 @@ impl MemBrokerService {
         checker.check_failure_tolerance(migration_limit)
     }
 
+    pub fn simulate_host_failure(&self, host: &str) -> Result<FailureSimulation, MetaStoreError> {
+        let migration_limit = self.config.migration_limit;
+        let store_copy = self
+            .store
+            .read()
+            .expect("MemBrokerService::simulate_host_failure")
+            .clone();
+        let checker = ResourceChecker::new(store_copy);
+        checker.simulate_host_failure(host, migration_limit)
+    }
+
     pub fn change_broker_config(
         &self,
         config_payload: MemBrokerConfigPayload,
@@ -341,22 +589,27 @@ impl MemBrokerService {
         Ok(())
     }
 
-    pub fn migrate_slots(&self, cluster_name: String) -> Result<(), MetaStoreError> {
+    pub fn migrate_slots(
+        &self,
+        cluster_name: String,
+        restore_policy: RestorePolicy,
+    ) -> Result<(), MetaStoreError> {
         self.store
             .write()
             .expect("MemBrokerService::migrate_slots")
-            .migrate_slots(cluster_name)
+            .migrate_slots(cluster_name, restore_policy)
     }
 
     pub fn migrate_slots_to_scale_down(
         &self,
         cluster_name: String,
         new_node_num: usize,
+        restore_policy: RestorePolicy,
     ) -> Result<(), MetaStoreError> {
         self.store
             .write()
             .expect("MemBrokerService::migrate_slots_to_scale_down")
-            .migrate_slots_to_scale_down(cluster_name, new_node_num)
+            .migrate_slots_to_scale_down(cluster_name, new_node_num, restore_policy)
     }
 
     pub fn get_failures(&self) -> Vec<String> {
@@ -382,6 +635,41 @@ impl MemBrokerService {
             .commit_migration(task)
     }
 
+    pub fn get_migration_tasks(&self, min_elapsed: Option<i64>) -> Vec<MigrationTaskInfo> {
+        let now = chrono::Utc::now().timestamp();
+        let tasks = self
+            .store
+            .read()
+            .expect("MemBrokerService::get_migration_tasks")
+            .get_migration_tasks(now);
+        match min_elapsed {
+            None => tasks,
+            Some(min_elapsed) => tasks
+                .into_iter()
+                .filter(|task| !matches!(task.elapsed, Some(elapsed) if elapsed < min_elapsed))
+                .collect(),
+        }
+    }
+
+    pub fn force_migration(&self, payload: ForceMigrationPayload) -> Result<(), MetaStoreError> {
+        let ForceMigrationPayload {
+            cluster_name,
+            epoch,
+            range_list,
+            action,
+            confirm,
+        } = payload;
+        if !confirm {
+            return Err(MetaStoreError::NotConfirmed);
+        }
+        let action = ForceMigrationAction::from_str(&action)
+            .map_err(|()| MetaStoreError::InvalidMigrationTask)?;
+        self.store
+            .write()
+            .expect("MemBrokerService::force_migration")
+            .force_migration(cluster_name, epoch, range_list, action)
+    }
+
     pub fn replace_failed_proxy(
         &self,
         failed_proxy_address: String,
@@ -400,6 +688,256 @@ impl MemBrokerService {
             .get_failed_proxies()
     }
 
+    pub fn get_rebuilding_proxies(&self) -> Vec<String> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_rebuilding_proxies")
+            .get_rebuilding_proxies()
+    }
+
+    pub fn finish_proxy_rebuilding(&self, proxy_address: String) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::finish_proxy_rebuilding")
+            .finish_proxy_rebuilding(proxy_address)
+    }
+
+    pub fn report_proxy_stats(
+        &self,
+        proxy_address: String,
+        stats: ProxyRuntimeStats,
+    ) -> Result<(), MetaStoreError> {
+        let now = chrono::Utc::now().timestamp();
+        self.store
+            .write()
+            .expect("MemBrokerService::report_proxy_stats")
+            .report_proxy_stats(proxy_address, stats, now)
+    }
+
+    pub fn get_proxy_stats(&self, proxy_address: &str) -> Result<ProxyStatsSample, MetaStoreError> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_proxy_stats")
+            .get_proxy_stats(proxy_address)
+    }
+
+    pub fn get_all_proxy_stats(&self) -> HashMap<String, ProxyStatsSample> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_all_proxy_stats")
+            .get_all_proxy_stats()
+    }
+
+    pub fn record_cluster_stats(&self) {
+        let now = chrono::Utc::now().timestamp();
+        self.store
+            .write()
+            .expect("MemBrokerService::record_cluster_stats")
+            .record_cluster_stats(now)
+    }
+
+    pub fn get_cluster_stats(
+        &self,
+        cluster_name: &str,
+        since: i64,
+    ) -> Result<Vec<ClusterStatsSample>, MetaStoreError> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_cluster_stats")
+            .get_cluster_stats(cluster_name, since)
+    }
+
+    pub fn report_migration_events(
+        &self,
+        proxy_address: String,
+        events: Vec<MigrationTaskEvents>,
+    ) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::report_migration_events")
+            .report_migration_events(proxy_address, events)
+    }
+
+    pub fn get_migration_events(
+        &self,
+        cluster_name: &str,
+    ) -> Result<Vec<MigrationTaskEvents>, MetaStoreError> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_migration_events")
+            .get_migration_events(cluster_name)
+    }
+
+    pub fn get_proxy_config(&self) -> HashMap<String, String> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_proxy_config")
+            .get_proxy_config()
+    }
+
+    pub fn set_proxy_config(
+        &self,
+        proxy_config: HashMap<String, String>,
+    ) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::set_proxy_config")
+            .set_proxy_config(proxy_config)
+    }
+
+    pub fn get_alert_rules(&self) -> HashMap<String, AlertRule> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_alert_rules")
+            .get_alert_rules()
+    }
+
+    pub fn set_alert_rule(&self, name: String, rule: AlertRule) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::set_alert_rule")
+            .set_alert_rule(name, rule)
+    }
+
+    pub fn remove_alert_rule(&self, name: &str) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::remove_alert_rule")
+            .remove_alert_rule(name)
+    }
+
+    pub fn get_namespaces(&self) -> HashMap<String, NamespaceInfo> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_namespaces")
+            .get_namespaces()
+    }
+
+    pub fn get_namespace(&self, name: &str) -> Result<NamespaceInfo, MetaStoreError> {
+        self.store
+            .read()
+            .expect("MemBrokerService::get_namespace")
+            .get_namespace(name)
+    }
+
+    pub fn set_namespace(&self, name: String, namespace: Namespace) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::set_namespace")
+            .set_namespace(name, namespace)
+    }
+
+    pub fn remove_namespace(&self, name: &str) -> Result<(), MetaStoreError> {
+        self.store
+            .write()
+            .expect("MemBrokerService::remove_namespace")
+            .remove_namespace(name)
+    }
+
+    // Returns the namespace which the token is scoped to.
+    // The requests without a token are not restricted.
+    pub fn check_api_token(
+        &self,
+        token: Option<&str>,
+        method: &http::Method,
+        path: &str,
+    ) -> Result<Option<String>, MetaStoreError> {
+        let token = match token {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        let namespace = self
+            .config
+            .namespace_tokens
+            .get_namespace(token)
+            .ok_or(MetaStoreError::Forbidden)?;
+        let path = path.strip_prefix(MEM_BROKER_API_VERSION).unwrap_or(path);
+        let store = self
+            .store
+            .read()
+            .expect("MemBrokerService::check_api_token");
+        if check_namespace_access(&store, namespace, method, path) {
+            Ok(Some(namespace.to_string()))
+        } else {
+            Err(MetaStoreError::Forbidden)
+        }
+    }
+
+    pub fn get_active_alerts(&self) -> Vec<AlertEvent> {
+        self.active_alerts
+            .read()
+            .expect("MemBrokerService::get_active_alerts")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    // Returns the newly fired events and the webhooks of their rules.
+    pub fn check_alerts(&self) -> Vec<(AlertEvent, Option<String>)> {
+        let now = chrono::Utc::now().timestamp();
+        let store = self.store.read().expect("MemBrokerService::check_alerts");
+        let events = AlertChecker::new(&store).check(now);
+        let mut active_alerts = self
+            .active_alerts
+            .write()
+            .expect("MemBrokerService::check_alerts");
+        update_active_alerts(&mut active_alerts, events)
+            .into_iter()
+            .map(|event| {
+                let webhook = store
+                    .alert_rules
+                    .get(&event.rule_name)
+                    .and_then(|rule| rule.webhook.clone());
+                (event, webhook)
+            })
+            .collect()
+    }
+
+    // Returns the clusters starting the migrations.
+    pub async fn auto_balance(&self) -> Result<Vec<String>, MetaStoreError> {
+        let now = chrono::Utc::now().timestamp();
+        let policy = &self.config.balance_policy;
+        if let Some(window) = policy.window.as_ref() {
+            if !window.contains(now) {
+                return Ok(vec![]);
+            }
+        }
+        let cluster_names = self
+            .store
+            .write()
+            .expect("MemBrokerService::auto_balance")
+            .balance_slots(policy, now);
+        if !cluster_names.is_empty() {
+            self.trigger_update().await?;
+        }
+        Ok(cluster_names
+            .into_iter()
+            .map(|cluster_name| cluster_name.to_string())
+            .collect())
+    }
+
+    // Returns the clusters whose masters are moved back.
+    pub async fn auto_failback(&self) -> Result<Vec<String>, MetaStoreError> {
+        let now = chrono::Utc::now().timestamp();
+        if let Some(window) = self.config.failback_window.as_ref() {
+            if !window.contains(now) {
+                return Ok(vec![]);
+            }
+        }
+        let cluster_names = self
+            .store
+            .write()
+            .expect("MemBrokerService::auto_failback")
+            .failback_masters(now);
+        if !cluster_names.is_empty() {
+            self.trigger_update().await?;
+        }
+        Ok(cluster_names
+            .into_iter()
+            .map(|cluster_name| cluster_name.to_string())
+            .collect())
+    }
+
     pub fn force_bump_all_epoch(&self, new_epoch: u64) -> Result<(), MetaStoreError> {
         self.store
             .write()
@@ -428,6 +966,99 @@ impl MemBrokerService {
         Ok(failed_addresses)
     }
 
+    // Both nodes should be in the cluster.
+    pub async fn diff_keyspace(
+        &self,
+        cluster_name: String,
+        payload: KeyspaceDiffPayload,
+    ) -> Result<KeyspaceDiffReport, MetaStoreError> {
+        let cluster = self
+            .get_cluster_by_name(&cluster_name)
+            .ok_or(MetaStoreError::ClusterNotFound)?;
+        let in_cluster = |address: &str| {
+            cluster
+                .get_nodes()
+                .iter()
+                .any(|node| node.get_address() == address)
+        };
+        if !in_cluster(&payload.source) || !in_cluster(&payload.target) {
+            return Err(MetaStoreError::NodeNotFound);
+        }
+
+        let config = KeyspaceDiffConfig {
+            slot_ranges: payload
+                .range_list
+                .unwrap_or_else(|| RangeList::from_single_range(Range(0, SLOT_NUM - 1))),
+            compare_value: payload.compare_value,
+            max_keys_per_sec: payload
+                .max_keys_per_sec
+                .unwrap_or(DEFAULT_DIFF_KEYS_PER_SEC),
+            max_diffs: payload.max_diffs.unwrap_or(DEFAULT_MAX_DIFFS),
+        };
+        let to_diff_error = |err: RedisClientError| MetaStoreError::KeyspaceDiffFailed {
+            error: err.to_string(),
+        };
+        let client_factory = PooledRedisClientFactory::new(1, Duration::from_secs(10));
+        let mut source = client_factory
+            .create_client(payload.source.clone())
+            .await
+            .map_err(to_diff_error)?;
+        let mut target = client_factory
+            .create_client(payload.target.clone())
+            .await
+            .map_err(to_diff_error)?;
+        let report = diff::diff_keyspace(&mut source, &mut target, &config)
+            .await
+            .map_err(to_diff_error)?;
+        info!(
+            "keyspace diff of {} {} => {}: {} keys {} diffs",
+            cluster_name,
+            payload.source,
+            payload.target,
+            report.scanned_keys,
+            report.diffs.len()
+        );
+        Ok(report)
+    }
+
+    // Scans the masters of the cluster one by one.
+    // Each line of the result is a json object of either the keys of one SCAN or the error.
+    pub fn list_keys(
+        &self,
+        cluster_name: &str,
+        max_keys_per_sec: Option<u64>,
+    ) -> Result<impl Stream<Item = Bytes>, MetaStoreError> {
+        let cluster = self
+            .get_cluster_by_name(cluster_name)
+            .ok_or(MetaStoreError::ClusterNotFound)?;
+        let nodes: Vec<String> = cluster
+            .get_nodes()
+            .iter()
+            .filter(|node| node.get_role() == Role::Master)
+            .map(|node| node.get_address().to_string())
+            .collect();
+        let config = ListKeysConfig {
+            scan_count: DEFAULT_LIST_KEYS_SCAN_COUNT,
+            max_keys_per_sec: max_keys_per_sec.unwrap_or(DEFAULT_LIST_KEYS_PER_SEC),
+        };
+        info!("start listing keys of {}", cluster_name);
+        let client_factory = Arc::new(PooledRedisClientFactory::new(1, Duration::from_secs(10)));
+        let cluster_name = cluster_name.to_string();
+        let s = list_keys::list_keys(client_factory, nodes, config).map(move |res| {
+            let line = match res {
+                Ok(keys) => KeysChunk::Keys(lossy_keys(keys)),
+                Err(err) => {
+                    error!("failed to list keys of {}: {}", cluster_name, err);
+                    KeysChunk::Error(err.to_string())
+                }
+            };
+            let mut line = serde_json::to_vec(&line).unwrap_or_default();
+            line.push(b'\n');
+            Bytes::from(line)
+        });
+        Ok(s)
+    }
+
     pub fn check_metadata(&self) -> Result<(), MetaStore> {
         self.store
             .read()
@@ -438,10 +1069,113 @@ impl MemBrokerService {
 
 type ServiceState = web::Data<Arc<MemBrokerService>>;
 
+trait IdempotentReply {
+    fn into_cached(self) -> CachedResponse;
+}
+
+impl IdempotentReply for &'static str {
+    fn into_cached(self) -> CachedResponse {
+        CachedResponse {
+            status: http::StatusCode::OK.as_u16(),
+            json: false,
+            body: self.to_string(),
+        }
+    }
+}
+
+impl<T: Serialize> IdempotentReply for web::Json<T> {
+    fn into_cached(self) -> CachedResponse {
+        match serde_json::to_string(&self.into_inner()) {
+            Ok(body) => CachedResponse {
+                status: http::StatusCode::OK.as_u16(),
+                json: true,
+                body,
+            },
+            Err(err) => CachedResponse {
+                status: http::StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                json: false,
+                body: err.to_string(),
+            },
+        }
+    }
+}
+
+fn gen_cached_response<R: IdempotentReply>(res: Result<R, MetaStoreError>) -> CachedResponse {
+    match res {
+        Ok(reply) => reply.into_cached(),
+        Err(err) => CachedResponse {
+            status: error::ResponseError::status_code(&err).as_u16(),
+            json: true,
+            body: serde_json::to_string(&err).unwrap_or_else(|_| format!("{:?}", err)),
+        },
+    }
+}
+
+fn gen_http_response(response: CachedResponse, replayed: bool) -> HttpResponse {
+    let CachedResponse { status, json, body } = response;
+    let status =
+        http::StatusCode::from_u16(status).unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR);
+    let content_type = if json {
+        "application/json"
+    } else {
+        "text/plain; charset=utf-8"
+    };
+    let mut builder = HttpResponse::build(status);
+    builder.content_type(content_type);
+    if replayed {
+        builder.header(IDEMPOTENT_REPLAYED_HEADER, "true");
+    }
+    builder.body(body)
+}
+
+// The retries with the same `Idempotency-Key` get the outcome of the first request
+// instead of applying the change again.
+// The keys are separated by the namespaces, the methods and the paths.
+async fn call_idempotent<R, F>(req: &HttpRequest, state: &ServiceState, fut: F) -> HttpResponse
+where
+    R: IdempotentReply,
+    F: Future<Output = Result<R, MetaStoreError>>,
+{
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        None => return gen_http_response(gen_cached_response(fut.await), false),
+        Some(key) => match key.to_str() {
+            Ok(key) if !key.is_empty() => key.to_string(),
+            _ => {
+                return HttpResponse::BadRequest()
+                    .body(format!("invalid {} header", IDEMPOTENCY_KEY_HEADER))
+            }
+        },
+    };
+    let namespace = req
+        .extensions()
+        .get::<NamespaceScope>()
+        .map(|scope| scope.0.clone())
+        .unwrap_or_default();
+    let cache_key = format!("{} {} {} {}", namespace, req.method(), req.path(), key);
+
+    let guard = match state.idempotency_cache.begin(cache_key, Instant::now()) {
+        IdempotencyState::New(guard) => guard,
+        IdempotencyState::Pending => {
+            return HttpResponse::Conflict().body(format!(
+                "the request with the same {} is still in progress",
+                IDEMPOTENCY_KEY_HEADER
+            ))
+        }
+        IdempotencyState::Done(response) => return gen_http_response(response, true),
+    };
+    let response = gen_cached_response(fut.await);
+    guard.finish(response.clone(), Instant::now());
+    gen_http_response(response, false)
+}
+
 async fn get_version(_req: HttpRequest) -> &'static str {
     UNDERMOON_VERSION
 }
 
+async fn get_openapi(_req: HttpRequest) -> impl Responder {
+    web::Json(gen_broker_openapi())
+}
+
 async fn get_all_metadata(state: ServiceState) -> impl Responder {
     let metadata = state.get_all_data();
     web::Json(metadata)
@@ -498,36 +1232,66 @@ async fn get_failures(state: ServiceState) -> impl Responder {
 
 #[derive(Deserialize, Serialize)]
 pub struct ProxyResourcePayload {
-    proxy_address: String,
-    nodes: [String; CHUNK_HALF_NODE_NUM],
-    host: Option<String>,
+    pub proxy_address: String,
+    pub nodes: [String; CHUNK_HALF_NODE_NUM],
+    pub host: Option<String>,
+    // The id returned by the previous registration.
+    #[serde(default)]
+    pub proxy_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AddProxyResponse {
+    pub proxy_id: String,
 }
 
 async fn add_proxy(
     (proxy_resource, state): (web::Json<ProxyResourcePayload>, ServiceState),
-) -> Result<&'static str, MetaStoreError> {
-    let res = state.add_proxy(proxy_resource.into_inner()).map(|()| "")?;
+) -> Result<web::Json<AddProxyResponse>, MetaStoreError> {
+    let proxy_id = state.add_proxy(proxy_resource.into_inner())?;
     state.trigger_update().await?;
-    Ok(res)
+    Ok(web::Json(AddProxyResponse { proxy_id }))
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct CreateClusterPayload {
-    node_number: usize,
+    pub node_number: usize,
+    #[serde(default)]
+    pub namespace: Option<String>,
 }
 
 async fn add_cluster(
-    (path, payload, state): (
+    (req, path, payload, state): (
+        HttpRequest,
         web::Path<(String,)>,
         web::Json<CreateClusterPayload>,
         ServiceState,
     ),
-) -> Result<&'static str, MetaStoreError> {
-    let cluster_name = path.into_inner().0;
-    let CreateClusterPayload { node_number } = payload.into_inner();
-    let res = state.add_cluster(cluster_name, node_number).map(|()| "")?;
-    state.trigger_update().await?;
-    Ok(res)
+) -> HttpResponse {
+    let fut = async {
+        let cluster_name = path.into_inner().0;
+        let CreateClusterPayload {
+            node_number,
+            namespace,
+        } = payload.into_inner();
+        let scope = req
+            .extensions()
+            .get::<NamespaceScope>()
+            .map(|scope| scope.0.clone());
+        let namespace = match (scope, namespace) {
+            (Some(scope), Some(namespace)) if scope != namespace => {
+                return Err(MetaStoreError::Forbidden)
+            }
+            (Some(scope), _) => Some(scope),
+            (None, namespace) => namespace,
+        };
+        let res = state
+            .add_cluster(cluster_name, node_number, namespace)
+            .map(|()| "")?;
+        state.trigger_update().await?;
+        Ok(res)
+    };
+    call_idempotent(&req, &state, fut).await
 }
 
 async fn remove_cluster(
@@ -539,25 +1303,49 @@ async fn remove_cluster(
     Ok(res)
 }
 
+async fn rename_cluster(
+    (path, state): (web::Path<(String, String)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (cluster_name, new_cluster_name) = path.into_inner();
+    let res = state
+        .rename_cluster(cluster_name, new_cluster_name)
+        .map(|()| "")?;
+    state.trigger_update().await?;
+    Ok(res)
+}
+
+async fn finish_cluster_renaming(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let cluster_name = path.into_inner().0;
+    let res = state.finish_cluster_renaming(cluster_name).map(|()| "")?;
+    state.trigger_update().await?;
+    Ok(res)
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct AutoScaleUpNodesPayload {
     cluster_node_number: usize,
 }
 
 async fn auto_scale_up_nodes(
-    (path, payload, state): (
+    (req, path, payload, state): (
+        HttpRequest,
         web::Path<(String,)>,
         web::Json<AutoScaleUpNodesPayload>,
         ServiceState,
     ),
-) -> Result<web::Json<Vec<Node>>, MetaStoreError> {
-    let cluster_name = path.into_inner().0;
-    let node_num = payload.into_inner().cluster_node_number;
-    let res = state
-        .auto_scale_up_nodes(cluster_name, node_num)
-        .map(web::Json)?;
-    state.trigger_update().await?;
-    Ok(res)
+) -> HttpResponse {
+    let fut = async {
+        let cluster_name = path.into_inner().0;
+        let node_num = payload.into_inner().cluster_node_number;
+        let res = state
+            .auto_scale_up_nodes(cluster_name, node_num)
+            .map(web::Json)?;
+        state.trigger_update().await?;
+        Ok(res)
+    };
+    call_idempotent(&req, &state, fut).await
 }
 
 #[derive(Deserialize, Serialize)]
@@ -566,17 +1354,21 @@ pub struct AutoAddNodesPayload {
 }
 
 async fn auto_add_nodes(
-    (path, payload, state): (
+    (req, path, payload, state): (
+        HttpRequest,
         web::Path<(String,)>,
         web::Json<AutoAddNodesPayload>,
         ServiceState,
     ),
-) -> Result<web::Json<Vec<Node>>, MetaStoreError> {
-    let cluster_name = path.into_inner().0;
-    let node_num = payload.into_inner().node_number;
-    let res = state.auto_add_node(cluster_name, node_num).map(web::Json)?;
-    state.trigger_update().await?;
-    Ok(res)
+) -> HttpResponse {
+    let fut = async {
+        let cluster_name = path.into_inner().0;
+        let node_num = payload.into_inner().node_number;
+        let res = state.auto_add_node(cluster_name, node_num).map(web::Json)?;
+        state.trigger_update().await?;
+        Ok(res)
+    };
+    call_idempotent(&req, &state, fut).await
 }
 
 async fn audo_delete_free_nodes(
@@ -632,6 +1424,125 @@ async fn remove_proxy(
     Ok(res)
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct ProxyTlsPayload {
+    pkcs12_file: String,
+    password: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ProxyTlsResponse {
+    tls: Option<ProxyTlsMeta>,
+}
+
+impl ProxyTlsResponse {
+    // The password is only sent to the coordinator in the proxy metadata.
+    fn redacted(tls: Option<ProxyTlsMeta>) -> Self {
+        let tls = tls.map(|tls| ProxyTlsMeta {
+            password: REDACTED_PASSWORD.to_string(),
+            ..tls
+        });
+        Self { tls }
+    }
+}
+
+const REDACTED_PASSWORD: &str = "(redacted)";
+
+async fn get_proxy_tls(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<web::Json<ProxyTlsResponse>, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    let tls = state.get_proxy_tls(&proxy_address)?;
+    Ok(web::Json(ProxyTlsResponse::redacted(tls)))
+}
+
+async fn set_proxy_tls(
+    (path, payload, state): (
+        web::Path<(String,)>,
+        web::Json<ProxyTlsPayload>,
+        ServiceState,
+    ),
+) -> Result<web::Json<ProxyTlsResponse>, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    let tls = state.set_proxy_tls(proxy_address, payload.into_inner())?;
+    state.trigger_update().await?;
+    Ok(web::Json(ProxyTlsResponse::redacted(Some(tls))))
+}
+
+async fn remove_proxy_tls(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    state.remove_proxy_tls(proxy_address)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+async fn force_resync_proxy(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    state.set_proxy_force_resync(proxy_address, true)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+async fn clear_proxy_resync(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    state.set_proxy_force_resync(proxy_address, false)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AuditLogPayload {
+    pub entries: Vec<AuditEntry>,
+}
+
+async fn get_audit_log(state: ServiceState) -> impl Responder {
+    let entries = state.get_audit_log();
+    web::Json(AuditLogPayload { entries })
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MaintenanceHostsPayload {
+    pub hosts: Vec<String>,
+}
+
+async fn get_maintenance_hosts(state: ServiceState) -> impl Responder {
+    let hosts = state.get_maintenance_hosts();
+    web::Json(MaintenanceHostsPayload { hosts })
+}
+
+async fn get_host_maintenance(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<web::Json<HostMaintenance>, MetaStoreError> {
+    let (host,) = path.into_inner();
+    let maintenance = state.get_host_maintenance(&host)?;
+    Ok(web::Json(maintenance))
+}
+
+async fn start_host_maintenance(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<web::Json<HostMaintenance>, MetaStoreError> {
+    let (host,) = path.into_inner();
+    state.start_host_maintenance(host.clone())?;
+    state.trigger_update().await?;
+    let maintenance = state.get_host_maintenance(&host)?;
+    Ok(web::Json(maintenance))
+}
+
+async fn end_host_maintenance(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (host,) = path.into_inner();
+    state.end_host_maintenance(host)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct ResourceFailureCheckPayload {
     hosts_cannot_fail: Vec<String>,
@@ -644,6 +1555,14 @@ async fn check_resource_for_failures(
     Ok(web::Json(ResourceFailureCheckPayload { hosts_cannot_fail }))
 }
 
+async fn simulate_host_failure(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<web::Json<FailureSimulation>, MetaStoreError> {
+    let (host,) = path.into_inner();
+    let simulation = state.simulate_host_failure(&host)?;
+    Ok(web::Json(simulation))
+}
+
 async fn change_broker_config(
     (state, config_payload): (ServiceState, web::Json<MemBrokerConfigPayload>),
 ) -> Result<&'static str, MetaStoreError> {
@@ -651,24 +1570,48 @@ async fn change_broker_config(
     Ok("")
 }
 
+#[derive(Deserialize)]
+struct MigrationQuery {
+    #[serde(default)]
+    restore_policy: RestorePolicy,
+}
+
 async fn migrate_slots(
-    (path, state): (web::Path<(String,)>, ServiceState),
-) -> Result<&'static str, MetaStoreError> {
-    let (cluster_name,) = path.into_inner();
-    let res = state.migrate_slots(cluster_name).map(|()| "")?;
-    state.trigger_update().await?;
-    Ok(res)
+    (req, path, web::Query(query), state): (
+        HttpRequest,
+        web::Path<(String,)>,
+        web::Query<MigrationQuery>,
+        ServiceState,
+    ),
+) -> HttpResponse {
+    let fut = async {
+        let (cluster_name,) = path.into_inner();
+        let res = state
+            .migrate_slots(cluster_name, query.restore_policy)
+            .map(|()| "")?;
+        state.trigger_update().await?;
+        Ok(res)
+    };
+    call_idempotent(&req, &state, fut).await
 }
 
 async fn migrate_slots_to_scale_down(
-    (path, state): (web::Path<(String, usize)>, ServiceState),
-) -> Result<&'static str, MetaStoreError> {
-    let (cluster_name, new_node_num) = path.into_inner();
-    let res = state
-        .migrate_slots_to_scale_down(cluster_name, new_node_num)
-        .map(|()| "")?;
-    state.trigger_update().await?;
-    Ok(res)
+    (req, path, web::Query(query), state): (
+        HttpRequest,
+        web::Path<(String, usize)>,
+        web::Query<MigrationQuery>,
+        ServiceState,
+    ),
+) -> HttpResponse {
+    let fut = async {
+        let (cluster_name, new_node_num) = path.into_inner();
+        let res = state
+            .migrate_slots_to_scale_down(cluster_name, new_node_num, query.restore_policy)
+            .map(|()| "")?;
+        state.trigger_update().await?;
+        Ok(res)
+    };
+    call_idempotent(&req, &state, fut).await
 }
 
 async fn add_failure(
@@ -688,25 +1631,333 @@ async fn commit_migration(
     Ok(res)
 }
 
-async fn replace_failed_node(
-    (path, state): (web::Path<(String,)>, ServiceState),
-) -> Result<web::Json<ReplaceProxyResponse>, MetaStoreError> {
-    let (proxy_address,) = path.into_inner();
-    let res = state
-        .replace_failed_proxy(proxy_address)
-        .map(|proxy| ReplaceProxyResponse { proxy })
-        .map(web::Json);
-    let sync_res = state.trigger_update().await;
-    let res = res?;
-    sync_res?;
+#[derive(Deserialize)]
+struct SpecQuery {
+    #[serde(default)]
+    execute: bool,
+    #[serde(default)]
+    prune: bool,
+}
+
+async fn apply_declarative_spec(
+    (web::Query(query), payload, state): (
+        web::Query<SpecQuery>,
+        web::Json<DeclarativeSpec>,
+        ServiceState,
+    ),
+) -> Result<web::Json<SpecPlan>, MetaStoreError> {
+    let plan = state.apply_spec(&payload.into_inner(), query.prune, query.execute)?;
+    if query.execute {
+        state.trigger_update().await?;
+    }
+    Ok(web::Json(plan))
+}
+
+#[derive(Deserialize)]
+struct MigrationTasksQuery {
+    min_elapsed: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MigrationTasksPayload {
+    pub tasks: Vec<MigrationTaskInfo>,
+}
+
+async fn get_migration_tasks(
+    (web::Query(query), state): (web::Query<MigrationTasksQuery>, ServiceState),
+) -> impl Responder {
+    let tasks = state.get_migration_tasks(query.min_elapsed);
+    web::Json(MigrationTasksPayload { tasks })
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ForceMigrationPayload {
+    pub cluster_name: String,
+    pub epoch: u64,
+    pub range_list: RangeList,
+    // "complete" or "rollback"
+    pub action: String,
+    // Must be true since the data could be lost.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+async fn force_migration(
+    (payload, state): (web::Json<ForceMigrationPayload>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let res = state.force_migration(payload.into_inner()).map(|()| "")?;
+    state.trigger_update().await?;
     Ok(res)
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct KeyspaceDiffPayload {
+    // node addresses
+    pub source: String,
+    pub target: String,
+    // All the slots by default.
+    #[serde(default)]
+    pub range_list: Option<RangeList>,
+    #[serde(default)]
+    pub compare_value: bool,
+    #[serde(default)]
+    pub max_keys_per_sec: Option<u64>,
+    #[serde(default)]
+    pub max_diffs: Option<usize>,
+}
+
+async fn diff_keyspace(
+    (path, payload, state): (
+        web::Path<(String,)>,
+        web::Json<KeyspaceDiffPayload>,
+        ServiceState,
+    ),
+) -> Result<web::Json<KeyspaceDiffReport>, MetaStoreError> {
+    let report = state
+        .diff_keyspace(path.into_inner().0, payload.into_inner())
+        .await?;
+    Ok(web::Json(report))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum KeysChunk {
+    Keys(Vec<String>),
+    Error(String),
+}
+
+fn lossy_keys(keys: Vec<BinSafeStr>) -> Vec<String> {
+    keys.iter()
+        .map(|key| String::from_utf8_lossy(key).to_string())
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct ListKeysQuery {
+    #[serde(default)]
+    max_keys_per_sec: Option<u64>,
+}
+
+async fn list_cluster_keys(
+    (path, web::Query(query), state): (
+        web::Path<(String,)>,
+        web::Query<ListKeysQuery>,
+        ServiceState,
+    ),
+) -> Result<HttpResponse, MetaStoreError> {
+    let (cluster_name,) = path.into_inner();
+    let s = state.list_keys(&cluster_name, query.max_keys_per_sec)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(Box::pin(s.map(Ok::<_, actix_web::Error>))))
+}
+
+async fn replace_failed_node(
+    (req, path, state): (HttpRequest, web::Path<(String,)>, ServiceState),
+) -> HttpResponse {
+    let fut = async {
+        let (proxy_address,) = path.into_inner();
+        let res = state
+            .replace_failed_proxy(proxy_address)
+            .map(|proxy| ReplaceProxyResponse { proxy })
+            .map(web::Json);
+        let sync_res = state.trigger_update().await;
+        let res = res?;
+        sync_res?;
+        Ok(res)
+    };
+    call_idempotent(&req, &state, fut).await
+}
+
 async fn get_failed_proxies(state: ServiceState) -> impl Responder {
     let addresses = state.get_failed_proxies();
     web::Json(FailedProxiesPayload { addresses })
 }
 
+async fn get_rebuilding_proxies(state: ServiceState) -> impl Responder {
+    let addresses = state.get_rebuilding_proxies();
+    web::Json(RebuildingProxiesPayload { addresses })
+}
+
+async fn finish_proxy_rebuilding(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    state.finish_proxy_rebuilding(proxy_address)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+// Reported frequently so the metadata file is not updated here.
+async fn report_proxy_stats(
+    (path, stats, state): (
+        web::Path<(String,)>,
+        web::Json<ProxyRuntimeStats>,
+        ServiceState,
+    ),
+) -> Result<&'static str, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    state.report_proxy_stats(proxy_address, stats.into_inner())?;
+    Ok("")
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ProxyStatsPayload {
+    pub stats: HashMap<String, ProxyStatsSample>,
+}
+
+async fn get_all_proxy_stats(state: ServiceState) -> impl Responder {
+    let stats = state.get_all_proxy_stats();
+    web::Json(ProxyStatsPayload { stats })
+}
+
+async fn get_proxy_stats(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<web::Json<ProxyStatsSample>, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    let sample = state.get_proxy_stats(&proxy_address)?;
+    Ok(web::Json(sample))
+}
+
+#[derive(Deserialize)]
+struct ClusterStatsQuery {
+    since: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ClusterStatsPayload {
+    pub samples: Vec<ClusterStatsSample>,
+}
+
+async fn get_cluster_stats(
+    (path, web::Query(query), state): (
+        web::Path<(String,)>,
+        web::Query<ClusterStatsQuery>,
+        ServiceState,
+    ),
+) -> Result<web::Json<ClusterStatsPayload>, MetaStoreError> {
+    let (cluster_name,) = path.into_inner();
+    let samples = state.get_cluster_stats(&cluster_name, query.since.unwrap_or(0))?;
+    Ok(web::Json(ClusterStatsPayload { samples }))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MigrationEventsPayload {
+    pub tasks: Vec<MigrationTaskEvents>,
+}
+
+// Reported frequently so the metadata file is not updated here.
+async fn report_migration_events(
+    (path, payload, state): (
+        web::Path<(String,)>,
+        web::Json<MigrationEventsPayload>,
+        ServiceState,
+    ),
+) -> Result<&'static str, MetaStoreError> {
+    let (proxy_address,) = path.into_inner();
+    state.report_migration_events(proxy_address, payload.into_inner().tasks)?;
+    Ok("")
+}
+
+async fn get_migration_events(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<web::Json<MigrationEventsPayload>, MetaStoreError> {
+    let (cluster_name,) = path.into_inner();
+    let tasks = state.get_migration_events(&cluster_name)?;
+    Ok(web::Json(MigrationEventsPayload { tasks }))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AlertRulesPayload {
+    pub rules: HashMap<String, AlertRule>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ProxyConfigPayload {
+    pub config: HashMap<String, String>,
+}
+
+async fn get_proxy_config(state: ServiceState) -> impl Responder {
+    let config = state.get_proxy_config();
+    web::Json(ProxyConfigPayload { config })
+}
+
+async fn set_proxy_config(
+    (payload, state): (web::Json<ProxyConfigPayload>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    state.set_proxy_config(payload.into_inner().config)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+async fn get_alert_rules(state: ServiceState) -> impl Responder {
+    let rules = state.get_alert_rules();
+    web::Json(AlertRulesPayload { rules })
+}
+
+async fn set_alert_rule(
+    (path, rule, state): (web::Path<(String,)>, web::Json<AlertRule>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (rule_name,) = path.into_inner();
+    state.set_alert_rule(rule_name, rule.into_inner())?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+async fn remove_alert_rule(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (rule_name,) = path.into_inner();
+    state.remove_alert_rule(&rule_name)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct NamespacesPayload {
+    pub namespaces: HashMap<String, NamespaceInfo>,
+}
+
+async fn get_namespaces(state: ServiceState) -> impl Responder {
+    let namespaces = state.get_namespaces();
+    web::Json(NamespacesPayload { namespaces })
+}
+
+async fn get_namespace(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<web::Json<NamespaceInfo>, MetaStoreError> {
+    let (name,) = path.into_inner();
+    state.get_namespace(&name).map(web::Json)
+}
+
+async fn set_namespace(
+    (path, namespace, state): (web::Path<(String,)>, web::Json<Namespace>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (name,) = path.into_inner();
+    state.set_namespace(name, namespace.into_inner())?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+async fn remove_namespace(
+    (path, state): (web::Path<(String,)>, ServiceState),
+) -> Result<&'static str, MetaStoreError> {
+    let (name,) = path.into_inner();
+    state.remove_namespace(&name)?;
+    state.trigger_update().await?;
+    Ok("")
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ActiveAlertsPayload {
+    pub alerts: Vec<AlertEvent>,
+}
+
+async fn get_active_alerts(state: ServiceState) -> impl Responder {
+    let alerts = state.get_active_alerts();
+    web::Json(ActiveAlertsPayload { alerts })
+}
+
 #[derive(Deserialize, Serialize)]
 struct RecoverEpochResult {
     failed_addresses: Vec<String>,
@@ -744,6 +1995,17 @@ impl error::ResponseError for MetaStoreError {
             MetaStoreError::SyncError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
             MetaStoreError::InvalidMetaVersion => http::StatusCode::CONFLICT,
             MetaStoreError::SmallEpoch => http::StatusCode::CONFLICT,
+            MetaStoreError::NotConfirmed => http::StatusCode::BAD_REQUEST,
+            MetaStoreError::HostNotFound => http::StatusCode::NOT_FOUND,
+            MetaStoreError::AlertRuleNotFound => http::StatusCode::NOT_FOUND,
+            MetaStoreError::ClusterRenaming => http::StatusCode::CONFLICT,
+            MetaStoreError::InvalidNamespace => http::StatusCode::BAD_REQUEST,
+            MetaStoreError::NamespaceNotFound => http::StatusCode::NOT_FOUND,
+            MetaStoreError::QuotaExceeded => http::StatusCode::CONFLICT,
+            MetaStoreError::Forbidden => http::StatusCode::FORBIDDEN,
+            MetaStoreError::InvalidSlotRanges { .. } => http::StatusCode::BAD_REQUEST,
+            MetaStoreError::NodeNotFound => http::StatusCode::NOT_FOUND,
+            MetaStoreError::KeyspaceDiffFailed { .. } => http::StatusCode::BAD_GATEWAY,
         }
     }
 
@@ -0,0 +1,487 @@
+use super::store::{ClusterStore, MetaStore, MetaStoreError, NODES_PER_PROXY};
+use crate::common::cluster::{ClusterName, RestorePolicy};
+use crate::common::config::ClusterConfig;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+// Every chunk has two masters and two replicas.
+pub const SPEC_REPLICAS: usize = 1;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProxySpec {
+    pub address: String,
+    pub nodes: [String; NODES_PER_PROXY],
+    #[serde(default)]
+    pub host: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClusterSpec {
+    pub name: String,
+    // Including the replicas
+    pub node_number: usize,
+    #[serde(default = "default_replicas")]
+    pub replicas: usize,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub config: HashMap<String, String>,
+}
+
+fn default_replicas() -> usize {
+    SPEC_REPLICAS
+}
+
+// The desired resources and clusters for `mem_broker --apply <spec_file>`
+// and the spec API.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DeclarativeSpec {
+    #[serde(default)]
+    pub proxies: Vec<ProxySpec>,
+    #[serde(default)]
+    pub clusters: Vec<ClusterSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SpecStep {
+    AddProxy {
+        proxy_address: String,
+        nodes: [String; NODES_PER_PROXY],
+        host: Option<String>,
+    },
+    CreateCluster {
+        cluster_name: String,
+        node_number: usize,
+        namespace: Option<String>,
+    },
+    // Adds the missing nodes and starts the migration.
+    ScaleUp {
+        cluster_name: String,
+        node_number: usize,
+    },
+    // Only starts the migration. The free nodes are deleted in the later plans.
+    ScaleDown {
+        cluster_name: String,
+        node_number: usize,
+    },
+    DeleteFreeNodes {
+        cluster_name: String,
+    },
+    ChangeConfig {
+        cluster_name: String,
+        config: HashMap<String, String>,
+    },
+    DeleteCluster {
+        cluster_name: String,
+    },
+    RemoveProxy {
+        proxy_address: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SpecPlan {
+    pub steps: Vec<SpecStep>,
+    // The differences which are not converged by this plan.
+    pub skipped: Vec<String>,
+}
+
+impl DeclarativeSpec {
+    fn validate(&self) -> Result<(), MetaStoreError> {
+        let mut names = HashSet::new();
+        for cluster in self.clusters.iter() {
+            ClusterName::try_from(cluster.name.as_str())
+                .map_err(|_| MetaStoreError::InvalidClusterName)?;
+            if !names.insert(cluster.name.as_str()) {
+                return Err(MetaStoreError::AlreadyExisted);
+            }
+            if cluster.replicas != SPEC_REPLICAS || cluster.node_number % 4 != 0 {
+                return Err(MetaStoreError::InvalidNodeNum);
+            }
+        }
+        let mut addresses = HashSet::new();
+        for proxy in self.proxies.iter() {
+            if !addresses.insert(proxy.address.as_str()) {
+                return Err(MetaStoreError::AlreadyExisted);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_migrating(cluster_store: &ClusterStore) -> bool {
+    cluster_store
+        .chunks
+        .iter()
+        .any(|chunk| chunk.migrating_slots.iter().any(|slots| !slots.is_empty()))
+}
+
+fn get_free_node_num(cluster_store: &ClusterStore) -> usize {
+    let free_chunks = cluster_store
+        .chunks
+        .iter()
+        .filter(|chunk| {
+            chunk.stable_slots.iter().all(|slots| slots.is_none())
+                && chunk.migrating_slots.iter().all(|slots| slots.is_empty())
+        })
+        .count();
+    free_chunks * 4
+}
+
+// Returns the fields changing the current config.
+fn diff_config(
+    current: &ClusterConfig,
+    config: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, MetaStoreError> {
+    let mut changed = HashMap::new();
+    let mut new_config = current.clone();
+    for (k, v) in config.iter() {
+        let last_config = new_config.clone();
+        new_config
+            .set_field(k, v)
+            .map_err(|err| MetaStoreError::InvalidConfig {
+                key: k.clone(),
+                value: v.clone(),
+                error: err.to_string(),
+            })?;
+        if new_config != last_config {
+            changed.insert(k.clone(), v.clone());
+        }
+    }
+    Ok(changed)
+}
+
+// Without `prune`, the plan never removes anything or scales down the clusters
+// since it could lose data by mistake.
+// Planning the same spec again after executing the plan
+// only continues the steps depending on the finished migrations.
+pub fn plan_spec(
+    store: &MetaStore,
+    spec: &DeclarativeSpec,
+    prune: bool,
+) -> Result<SpecPlan, MetaStoreError> {
+    spec.validate()?;
+    let mut plan = SpecPlan::default();
+
+    for proxy in spec.proxies.iter() {
+        if !store.all_proxies.contains_key(&proxy.address) {
+            plan.steps.push(SpecStep::AddProxy {
+                proxy_address: proxy.address.clone(),
+                nodes: proxy.nodes.clone(),
+                host: proxy.host.clone(),
+            });
+        }
+    }
+
+    for cluster in spec.clusters.iter() {
+        let cluster_name = ClusterName::try_from(cluster.name.as_str())
+            .map_err(|_| MetaStoreError::InvalidClusterName)?;
+        let cluster_store = match store.clusters.get(&cluster_name) {
+            Some(cluster_store) => cluster_store,
+            None => {
+                plan.steps.push(SpecStep::CreateCluster {
+                    cluster_name: cluster.name.clone(),
+                    node_number: cluster.node_number,
+                    namespace: cluster.namespace.clone(),
+                });
+                let config = diff_config(&ClusterConfig::default(), &cluster.config)?;
+                if !config.is_empty() {
+                    plan.steps.push(SpecStep::ChangeConfig {
+                        cluster_name: cluster.name.clone(),
+                        config,
+                    });
+                }
+                continue;
+            }
+        };
+
+        let config = diff_config(&cluster_store.config, &cluster.config)?;
+        if !config.is_empty() {
+            plan.steps.push(SpecStep::ChangeConfig {
+                cluster_name: cluster.name.clone(),
+                config,
+            });
+        }
+
+        if is_migrating(cluster_store) {
+            plan.skipped
+                .push(format!("cluster {} is migrating", cluster.name));
+            continue;
+        }
+        let free_node_num = get_free_node_num(cluster_store);
+        let node_num = cluster_store.chunks.len() * 4 - free_node_num;
+        if node_num < cluster.node_number {
+            plan.steps.push(SpecStep::ScaleUp {
+                cluster_name: cluster.name.clone(),
+                node_number: cluster.node_number,
+            });
+        } else if node_num > cluster.node_number {
+            if prune {
+                plan.steps.push(SpecStep::ScaleDown {
+                    cluster_name: cluster.name.clone(),
+                    node_number: cluster.node_number,
+                });
+            } else {
+                plan.skipped.push(format!(
+                    "cluster {} has {} nodes which is more than {}",
+                    cluster.name, node_num, cluster.node_number
+                ));
+            }
+        } else if free_node_num > 0 {
+            plan.steps.push(SpecStep::DeleteFreeNodes {
+                cluster_name: cluster.name.clone(),
+            });
+        }
+    }
+
+    let spec_clusters: HashSet<&str> = spec.clusters.iter().map(|c| c.name.as_str()).collect();
+    let mut cluster_names = store.get_cluster_names();
+    cluster_names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    for cluster_name in cluster_names.into_iter() {
+        if spec_clusters.contains(cluster_name.as_str()) {
+            continue;
+        }
+        if prune {
+            plan.steps.push(SpecStep::DeleteCluster {
+                cluster_name: cluster_name.to_string(),
+            });
+        } else {
+            plan.skipped
+                .push(format!("cluster {} is not in the spec", cluster_name));
+        }
+    }
+
+    // The proxies of the deleted clusters are removed in the later plans.
+    if prune {
+        let spec_proxies: HashSet<&str> = spec.proxies.iter().map(|p| p.address.as_str()).collect();
+        let mut addresses: Vec<&String> = store
+            .all_proxies
+            .iter()
+            .filter(|(address, proxy)| {
+                proxy.cluster.is_none() && !spec_proxies.contains(address.as_str())
+            })
+            .map(|(address, _)| address)
+            .collect();
+        addresses.sort();
+        for address in addresses.into_iter() {
+            plan.steps.push(SpecStep::RemoveProxy {
+                proxy_address: address.clone(),
+            });
+        }
+    }
+    Ok(plan)
+}
+
+pub fn execute_plan(store: &mut MetaStore, plan: &SpecPlan) -> Result<(), MetaStoreError> {
+    for step in plan.steps.iter() {
+        match step.clone() {
+            SpecStep::AddProxy {
+                proxy_address,
+                nodes,
+                host,
+            } => {
+                store.add_proxy(proxy_address, nodes, host, None)?;
+            }
+            SpecStep::CreateCluster {
+                cluster_name,
+                node_number,
+                namespace,
+            } => match namespace {
+                Some(namespace) => {
+                    store.add_cluster_in_namespace(cluster_name, node_number, namespace)?
+                }
+                None => store.add_cluster(cluster_name, node_number)?,
+            },
+            SpecStep::ScaleUp {
+                cluster_name,
+                node_number,
+            } => {
+                let name = ClusterName::try_from(cluster_name.as_str())
+                    .map_err(|_| MetaStoreError::InvalidClusterName)?;
+                let existing_node_num = store
+                    .clusters
+                    .get(&name)
+                    .map(|cluster_store| cluster_store.chunks.len() * 4)
+                    .ok_or(MetaStoreError::ClusterNotFound)?;
+                // The free nodes could be already added.
+                if existing_node_num < node_number {
+                    store.auto_scale_up_nodes(cluster_name.clone(), node_number)?;
+                }
+                store.migrate_slots(cluster_name, RestorePolicy::default())?;
+            }
+            SpecStep::ScaleDown {
+                cluster_name,
+                node_number,
+            } => {
+                store.migrate_slots_to_scale_down(
+                    cluster_name,
+                    node_number,
+                    RestorePolicy::default(),
+                )?;
+            }
+            SpecStep::DeleteFreeNodes { cluster_name } => {
+                store.audo_delete_free_nodes(cluster_name)?;
+            }
+            SpecStep::ChangeConfig {
+                cluster_name,
+                config,
+            } => {
+                store.change_config(cluster_name, config)?;
+            }
+            SpecStep::DeleteCluster { cluster_name } => {
+                store.remove_cluster(cluster_name)?;
+            }
+            SpecStep::RemoveProxy { proxy_address } => {
+                store.remove_proxy(proxy_address)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Returns the executed plan.
+pub fn apply_spec(
+    store: &mut MetaStore,
+    spec: &DeclarativeSpec,
+    prune: bool,
+) -> Result<SpecPlan, MetaStoreError> {
+    let plan = plan_spec(store, spec, prune)?;
+    execute_plan(store, &plan)?;
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::store::CHUNK_HALF_NODE_NUM;
+    use crate::common::cluster::MigrationTaskMeta;
+
+    fn gen_spec(node_number: usize) -> DeclarativeSpec {
+        let proxies = (1..=4)
+            .map(|i| ProxySpec {
+                address: format!("127.0.0.{}:7001", i),
+                nodes: [format!("127.0.0.{}:6001", i), format!("127.0.0.{}:6002", i)],
+                host: None,
+            })
+            .collect();
+        let mut config = HashMap::new();
+        config.insert("max_key_size".to_string(), "1024".to_string());
+        DeclarativeSpec {
+            proxies,
+            clusters: vec![ClusterSpec {
+                name: "mycluster".to_string(),
+                node_number,
+                replicas: SPEC_REPLICAS,
+                namespace: None,
+                config,
+            }],
+        }
+    }
+
+    fn finish_migrations(store: &mut MetaStore) {
+        let cluster = store.get_cluster_by_name("mycluster", 0).unwrap();
+        let slot_ranges: Vec<_> = cluster
+            .get_nodes()
+            .iter()
+            .flat_map(|node| node.get_slots().iter())
+            .filter(|slot_range| slot_range.tag.is_migrating())
+            .cloned()
+            .collect();
+        assert!(!slot_ranges.is_empty());
+        for slot_range in slot_ranges.into_iter() {
+            let task_meta = MigrationTaskMeta {
+                cluster_name: ClusterName::try_from("mycluster").unwrap(),
+                slot_range,
+            };
+            store.commit_migration(task_meta).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_apply_spec() {
+        let mut store = MetaStore::default();
+        let plan = apply_spec(&mut store, &gen_spec(4), false).unwrap();
+        assert_eq!(plan.steps.len(), 6);
+        let cluster = store.get_cluster_by_name("mycluster", 1).unwrap();
+        assert_eq!(cluster.get_nodes().len(), 4);
+        assert_eq!(cluster.get_config().max_key_size, 1024);
+
+        // Applying the same spec again changes nothing.
+        let plan = apply_spec(&mut store, &gen_spec(4), false).unwrap();
+        assert_eq!(plan, SpecPlan::default());
+
+        let plan = apply_spec(&mut store, &gen_spec(8), false).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![SpecStep::ScaleUp {
+                cluster_name: "mycluster".to_string(),
+                node_number: 8,
+            }]
+        );
+        let cluster = store.get_cluster_by_name("mycluster", 1).unwrap();
+        assert_eq!(cluster.get_nodes().len(), 8);
+        let plan = plan_spec(&store, &gen_spec(8), false).unwrap();
+        assert!(plan.steps.is_empty());
+        assert_eq!(plan.skipped.len(), 1);
+        finish_migrations(&mut store);
+        assert_eq!(
+            plan_spec(&store, &gen_spec(8), false).unwrap(),
+            SpecPlan::default()
+        );
+
+        let plan = apply_spec(&mut store, &gen_spec(4), false).unwrap();
+        assert!(plan.steps.is_empty());
+        assert_eq!(plan.skipped.len(), 1);
+        let cluster = store.get_cluster_by_name("mycluster", 1).unwrap();
+        assert_eq!(cluster.get_nodes().len(), 8);
+    }
+
+    #[test]
+    fn test_prune_spec() {
+        let mut store = MetaStore::default();
+        apply_spec(&mut store, &gen_spec(8), false).unwrap();
+
+        let plan = apply_spec(&mut store, &gen_spec(4), true).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![SpecStep::ScaleDown {
+                cluster_name: "mycluster".to_string(),
+                node_number: 4,
+            }]
+        );
+        finish_migrations(&mut store);
+        let plan = apply_spec(&mut store, &gen_spec(4), true).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![SpecStep::DeleteFreeNodes {
+                cluster_name: "mycluster".to_string(),
+            }]
+        );
+        let cluster = store.get_cluster_by_name("mycluster", 1).unwrap();
+        assert_eq!(cluster.get_nodes().len(), 4);
+        assert_eq!(store.get_free_proxies().len(), CHUNK_HALF_NODE_NUM);
+
+        let spec = DeclarativeSpec::default();
+        let plan = apply_spec(&mut store, &spec, true).unwrap();
+        assert_eq!(plan.steps.len(), 3);
+        let plan = apply_spec(&mut store, &spec, true).unwrap();
+        assert_eq!(plan.steps.len(), 2);
+        assert!(store.all_proxies.is_empty());
+        assert!(store.clusters.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_spec() {
+        let store = MetaStore::default();
+        assert!(plan_spec(&store, &gen_spec(3), false).is_err());
+        let mut spec = gen_spec(4);
+        spec.clusters[0].replicas = 2;
+        assert!(plan_spec(&store, &spec, false).is_err());
+        let mut spec = gen_spec(4);
+        spec.clusters[0]
+            .config
+            .insert("max_key_size".to_string(), "big".to_string());
+        assert!(plan_spec(&store, &spec, false).is_err());
+    }
+}
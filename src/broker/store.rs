@@ -1,19 +1,26 @@
+use super::alert::AlertRule;
+use super::balance::BalancePolicy;
+use super::history::{ClusterStatsHistory, ClusterStatsSample, MigrationEventHistory};
 use super::migrate::MetaStoreMigrate;
+use super::namespace::{check_namespace_name, get_namespace_usage, Namespace, NamespaceInfo};
 use super::persistence::MetaSyncError;
 use super::query::MetaStoreQuery;
 use super::update::MetaStoreUpdate;
 use crate::common::cluster::ClusterName;
 use crate::common::cluster::{
-    Cluster, MigrationMeta, MigrationTaskMeta, Node, Proxy, Range, RangeList, SlotRange,
-    SlotRangeTag,
+    check_slot_owners, Cluster, MigrationMeta, MigrationProgress, MigrationTaskEvents,
+    MigrationTaskMeta, Node, Proxy, ProxyRuntimeStats, ProxyTlsMeta, Range, RangeList,
+    RestorePolicy, SlotRange, SlotRangeError, SlotRangeTag,
 };
 use crate::common::config::ClusterConfig;
 use crate::common::version::UNDERMOON_MEM_BROKER_META_VERSION;
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 use std::cmp::max;
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
 
 pub const NODES_PER_PROXY: usize = 2;
 pub const CHUNK_PARTS: usize = 2;
@@ -22,10 +29,22 @@ pub const CHUNK_NODE_NUM: usize = 4;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ProxyResource {
+    // Assigned by the broker on registration and kept when the address changes.
+    // Empty for the metadata of the old versions.
+    #[serde(default)]
+    pub proxy_id: String,
     pub proxy_address: String,
     pub node_addresses: [String; NODES_PER_PROXY],
     pub host: String,
     pub cluster: Option<ClusterName>,
+    #[serde(default)]
+    pub tls: Option<ProxyTlsMeta>,
+    // Set by the operators to recover the proxy with a corrupted or future epoch.
+    #[serde(default)]
+    pub force_resync: bool,
+    // Set after replacing a failed proxy until its replicas finish the initial sync.
+    #[serde(default)]
+    pub rebuilding: bool,
 }
 
 pub struct HostProxy {
@@ -33,6 +52,15 @@ pub struct HostProxy {
     pub proxy_address: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct HostMaintenance {
+    pub host: String,
+    pub in_maintenance: bool,
+    // The master nodes still on this host.
+    pub masters: Vec<String>,
+    pub safe_to_reboot: bool,
+}
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
 pub enum ChunkRolePosition {
     Normal,
@@ -45,6 +73,9 @@ pub struct MigrationSlotRangeStore {
     pub range_list: RangeList,
     pub is_migrating: bool, // migrating or importing
     pub meta: MigrationMetaStore,
+    // Unix timestamp in seconds. Zero for the metadata of the old versions.
+    #[serde(default)]
+    pub start_time: i64,
 }
 
 impl MigrationSlotRangeStore {
@@ -87,6 +118,7 @@ impl MigrationSlotRangeStore {
             src_node_address,
             dst_proxy_address,
             dst_node_address,
+            restore_policy: self.meta.restore_policy,
         };
         if self.is_migrating {
             SlotRange {
@@ -101,7 +133,7 @@ impl MigrationSlotRangeStore {
         }
     }
 
-    fn chunk_part_to_proxy_index(chunk_part: usize, role_position: ChunkRolePosition) -> usize {
+    pub fn chunk_part_to_proxy_index(chunk_part: usize, role_position: ChunkRolePosition) -> usize {
         match (chunk_part, role_position) {
             (0, ChunkRolePosition::SecondChunkMaster) => 1,
             (1, ChunkRolePosition::FirstChunkMaster) => 0,
@@ -125,6 +157,8 @@ pub struct MigrationMetaStore {
     pub src_chunk_part: usize,
     pub dst_chunk_index: usize,
     pub dst_chunk_part: usize,
+    #[serde(default)]
+    pub restore_policy: RestorePolicy,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -143,6 +177,8 @@ pub struct ClusterStore {
     pub name: ClusterName,
     pub chunks: Vec<ChunkStore>,
     pub config: ClusterConfig,
+    #[serde(default)]
+    pub namespace: Option<String>,
 }
 
 impl ClusterStore {
@@ -150,6 +186,25 @@ impl ClusterStore {
         self.epoch = new_epoch;
     }
 
+    // The importing slots are also in the migrating slots of the source chunk parts.
+    pub fn check_slot_ranges(&self) -> Result<(), SlotRangeError> {
+        let mut owners = vec![];
+        for chunk in self.chunks.iter() {
+            for i in 0..CHUNK_PARTS {
+                let owner = chunk.proxy_addresses[i].as_str();
+                if let Some(slot_range) = chunk.stable_slots[i].as_ref() {
+                    owners.push((owner, &slot_range.range_list));
+                }
+                for slot_range_store in chunk.migrating_slots[i].iter() {
+                    if slot_range_store.is_migrating {
+                        owners.push((owner, &slot_range_store.range_list));
+                    }
+                }
+            }
+        }
+        check_slot_owners(owners.into_iter())
+    }
+
     // LimitMigration reduces the concurrent running migration.
     // This implementation is a bit tricky. The stored data do not allow some of shards
     // are migrating while others not. They are all set with the migration metadata.
@@ -236,6 +291,7 @@ impl ClusterStore {
             name: self.name.clone(),
             chunks,
             config: self.config.clone(),
+            namespace: self.namespace.clone(),
         }
     }
 }
@@ -246,6 +302,77 @@ pub struct MigrationSlots {
     pub meta: MigrationMetaStore,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MigrationTaskInfo {
+    pub cluster_name: ClusterName,
+    pub epoch: u64,
+    pub range_list: RangeList,
+    pub src_proxy_address: String,
+    pub src_node_address: String,
+    pub dst_proxy_address: String,
+    pub dst_node_address: String,
+    pub start_time: i64,
+    // In seconds. None if the start time is unknown.
+    pub elapsed: Option<i64>,
+    // Reported by the source proxy.
+    pub progress: Option<MigrationProgress>,
+    pub percentage: Option<f64>,
+    // The estimated remaining seconds.
+    pub eta: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForceMigrationAction {
+    // Moves the slots to the destination as if the migration is committed.
+    Complete,
+    // Moves the slots back to the source.
+    Rollback,
+}
+
+impl FromStr for ForceMigrationAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "complete" => Ok(Self::Complete),
+            "rollback" => Ok(Self::Rollback),
+            _ => Err(()),
+        }
+    }
+}
+
+pub const MAX_AUDIT_LOG_LEN: usize = 100;
+// The proxy config fields which are different in each proxy.
+const PROXY_LOCAL_CONFIG_FIELDS: [&str; 4] = [
+    "address",
+    "announce_address",
+    "tls_pkcs12_file",
+    "tls_pkcs12_password",
+];
+
+// The latest runtime statistics reported for a proxy.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ProxyStatsSample {
+    // Unix timestamp in seconds
+    pub time: i64,
+    pub stats: ProxyRuntimeStats,
+    // Calculated from the previous sample.
+    pub qps: f64,
+    pub error_rate: f64,
+    // The qps of each slot bucket. Empty when the proxy doesn't report them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slot_qps: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AuditEntry {
+    // Unix timestamp in seconds
+    pub time: i64,
+    pub action: String,
+    pub target: String,
+    pub epoch: u64,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MetaStore {
     pub version: String,
@@ -257,6 +384,27 @@ pub struct MetaStore {
     pub failed_proxies: HashSet<String>,
     // failed_proxy_address => reporter_id => time,
     pub failures: HashMap<String, HashMap<String, i64>>,
+    // The latest manual operations bypassing the normal epoch checks.
+    #[serde(default)]
+    pub audit_log: Vec<AuditEntry>,
+    // No new nodes will be placed on these hosts.
+    #[serde(default)]
+    pub maintenance_hosts: HashSet<String>,
+    // proxy_address => the latest sample
+    #[serde(default)]
+    pub proxy_stats: HashMap<String, ProxyStatsSample>,
+    // rule_name => rule
+    #[serde(default)]
+    pub alert_rules: HashMap<String, AlertRule>,
+    #[serde(default, skip_serializing_if = "ClusterStatsHistory::is_transient")]
+    pub cluster_stats: ClusterStatsHistory,
+    #[serde(default, skip_serializing_if = "MigrationEventHistory::is_transient")]
+    pub migration_events: MigrationEventHistory,
+    #[serde(default)]
+    pub namespaces: HashMap<String, Namespace>,
+    // The config fetched by the proxies started with `--broker-address`.
+    #[serde(default)]
+    pub proxy_config: HashMap<String, String>,
 }
 
 impl Default for MetaStore {
@@ -268,6 +416,14 @@ impl Default for MetaStore {
             all_proxies: HashMap::new(),
             failed_proxies: HashSet::new(),
             failures: HashMap::new(),
+            audit_log: Vec::new(),
+            maintenance_hosts: HashSet::new(),
+            proxy_stats: HashMap::new(),
+            alert_rules: HashMap::new(),
+            cluster_stats: ClusterStatsHistory::default(),
+            migration_events: MigrationEventHistory::default(),
+            namespaces: HashMap::new(),
+            proxy_config: HashMap::new(),
         }
     }
 }
@@ -280,7 +436,18 @@ impl MetaStore {
         if self.global_epoch > other.global_epoch {
             return Err(MetaStoreError::SmallEpoch);
         }
+        for (cluster_name, cluster) in other.clusters.iter() {
+            cluster
+                .check_slot_ranges()
+                .map_err(|err| MetaStoreError::InvalidSlotRanges {
+                    error: format!("cluster {}: {}", cluster_name, err),
+                })?;
+        }
+        let persisted = !self.cluster_stats.is_transient();
+        let events_persisted = !self.migration_events.is_transient();
         *self = other;
+        self.cluster_stats.set_persisted(persisted);
+        self.migration_events.set_persisted(events_persisted);
         Ok(())
     }
 
@@ -342,8 +509,9 @@ impl MetaStore {
         proxy_address: String,
         nodes: [String; NODES_PER_PROXY],
         host: Option<String>,
-    ) -> Result<(), MetaStoreError> {
-        MetaStoreUpdate::new(self).add_proxy(proxy_address, nodes, host)
+        proxy_id: Option<String>,
+    ) -> Result<String, MetaStoreError> {
+        MetaStoreUpdate::new(self).add_proxy(proxy_address, nodes, host, proxy_id)
     }
 
     pub fn add_cluster(
@@ -351,13 +519,34 @@ impl MetaStore {
         cluster_name: String,
         node_num: usize,
     ) -> Result<(), MetaStoreError> {
-        MetaStoreUpdate::new(self).add_cluster(cluster_name, node_num)
+        MetaStoreUpdate::new(self).add_cluster(cluster_name, node_num, None)
+    }
+
+    pub fn add_cluster_in_namespace(
+        &mut self,
+        cluster_name: String,
+        node_num: usize,
+        namespace: String,
+    ) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).add_cluster(cluster_name, node_num, Some(namespace))
     }
 
     pub fn remove_cluster(&mut self, cluster_name: String) -> Result<(), MetaStoreError> {
         MetaStoreUpdate::new(self).remove_cluster(cluster_name)
     }
 
+    pub fn rename_cluster(
+        &mut self,
+        cluster_name: String,
+        new_cluster_name: String,
+    ) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).rename_cluster(cluster_name, new_cluster_name)
+    }
+
+    pub fn finish_cluster_renaming(&mut self, cluster_name: String) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).finish_cluster_renaming(cluster_name)
+    }
+
     pub fn auto_scale_up_nodes(
         &mut self,
         cluster_name: String,
@@ -382,22 +571,49 @@ impl MetaStore {
         MetaStoreUpdate::new(self).remove_proxy(proxy_address)
     }
 
-    pub fn migrate_slots(&mut self, cluster_name: String) -> Result<(), MetaStoreError> {
-        MetaStoreMigrate::new(self).migrate_slots(cluster_name)
+    pub fn migrate_slots(
+        &mut self,
+        cluster_name: String,
+        restore_policy: RestorePolicy,
+    ) -> Result<(), MetaStoreError> {
+        MetaStoreMigrate::new(self).migrate_slots(cluster_name, restore_policy)
     }
 
     pub fn migrate_slots_to_scale_down(
         &mut self,
         cluster_name: String,
         new_node_num: usize,
+        restore_policy: RestorePolicy,
     ) -> Result<(), MetaStoreError> {
-        MetaStoreMigrate::new(self).migrate_slots_to_scale_down(cluster_name, new_node_num)
+        MetaStoreMigrate::new(self).migrate_slots_to_scale_down(
+            cluster_name,
+            new_node_num,
+            restore_policy,
+        )
+    }
+
+    pub fn balance_slots(&mut self, policy: &BalancePolicy, now: i64) -> Vec<ClusterName> {
+        MetaStoreMigrate::new(self).balance_slots(policy, now)
     }
 
     pub fn commit_migration(&mut self, task: MigrationTaskMeta) -> Result<(), MetaStoreError> {
         MetaStoreMigrate::new(self).commit_migration(task)
     }
 
+    pub fn get_migration_tasks(&self, now: i64) -> Vec<MigrationTaskInfo> {
+        MetaStoreQuery::new(self).get_migration_tasks(now)
+    }
+
+    pub fn force_migration(
+        &mut self,
+        cluster_name: String,
+        epoch: u64,
+        range_list: RangeList,
+        action: ForceMigrationAction,
+    ) -> Result<(), MetaStoreError> {
+        MetaStoreMigrate::new(self).force_migration(cluster_name, epoch, range_list, action)
+    }
+
     pub fn get_free_proxies(&self) -> Vec<HostProxy> {
         MetaStoreQuery::new(&self).get_free_proxies()
     }
@@ -422,10 +638,283 @@ impl MetaStore {
         MetaStoreUpdate::new(self).balance_masters(cluster_name)
     }
 
+    pub fn failback_masters(&mut self, now: i64) -> Vec<ClusterName> {
+        MetaStoreUpdate::new(self).failback_masters(now)
+    }
+
+    pub fn start_host_maintenance(&mut self, host: String, now: i64) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).start_host_maintenance(host, now)
+    }
+
+    pub fn end_host_maintenance(&mut self, host: String, now: i64) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).end_host_maintenance(host, now)
+    }
+
+    pub fn get_maintenance_hosts(&self) -> Vec<String> {
+        self.maintenance_hosts.iter().cloned().collect()
+    }
+
+    pub fn get_host_maintenance(&self, host: &str) -> Result<HostMaintenance, MetaStoreError> {
+        MetaStoreQuery::new(self).get_host_maintenance(host)
+    }
+
+    pub fn get_rebuilding_proxies(&self) -> Vec<String> {
+        MetaStoreQuery::new(self).get_rebuilding_proxies()
+    }
+
+    pub fn finish_proxy_rebuilding(&mut self, proxy_address: String) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).finish_proxy_rebuilding(proxy_address)
+    }
+
+    pub fn report_proxy_stats(
+        &mut self,
+        proxy_address: String,
+        stats: ProxyRuntimeStats,
+        now: i64,
+    ) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).report_proxy_stats(proxy_address, stats, now)
+    }
+
+    pub fn get_proxy_stats(&self, proxy_address: &str) -> Result<ProxyStatsSample, MetaStoreError> {
+        self.proxy_stats
+            .get(proxy_address)
+            .cloned()
+            .ok_or(MetaStoreError::ProxyNotFound)
+    }
+
+    pub fn get_all_proxy_stats(&self) -> HashMap<String, ProxyStatsSample> {
+        self.proxy_stats.clone()
+    }
+
+    pub fn record_cluster_stats(&mut self, now: i64) {
+        let cluster_names: Vec<ClusterName> = self.clusters.keys().cloned().collect();
+        for cluster_name in cluster_names.into_iter() {
+            let all_proxies = &self.all_proxies;
+            let proxy_samples = self.proxy_stats.iter().filter_map(|(address, sample)| {
+                all_proxies
+                    .get(address)
+                    .filter(|proxy| proxy.cluster.as_ref() == Some(&cluster_name))
+                    .map(|_| sample)
+            });
+            self.cluster_stats
+                .record(cluster_name.clone(), now, proxy_samples);
+        }
+    }
+
+    pub fn get_cluster_stats(
+        &self,
+        cluster_name: &str,
+        since: i64,
+    ) -> Result<Vec<ClusterStatsSample>, MetaStoreError> {
+        let cluster_name =
+            ClusterName::try_from(cluster_name).map_err(|_| MetaStoreError::InvalidClusterName)?;
+        if !self.clusters.contains_key(&cluster_name) {
+            return Err(MetaStoreError::ClusterNotFound);
+        }
+        Ok(self.cluster_stats.get(&cluster_name, since))
+    }
+
+    // The events of the unknown clusters are dropped.
+    pub fn report_migration_events(
+        &mut self,
+        proxy_address: String,
+        events: Vec<MigrationTaskEvents>,
+    ) -> Result<(), MetaStoreError> {
+        if !self.all_proxies.contains_key(&proxy_address) {
+            return Err(MetaStoreError::ProxyNotFound);
+        }
+        for mut task_events in events.into_iter() {
+            let cluster_name = match ClusterName::try_from(task_events.cluster_name.as_str()) {
+                Ok(cluster_name) if self.clusters.contains_key(&cluster_name) => cluster_name,
+                _ => continue,
+            };
+            task_events.proxy_address = proxy_address.clone();
+            self.migration_events.record(cluster_name, task_events);
+        }
+        Ok(())
+    }
+
+    pub fn get_migration_events(
+        &self,
+        cluster_name: &str,
+    ) -> Result<Vec<MigrationTaskEvents>, MetaStoreError> {
+        let cluster_name =
+            ClusterName::try_from(cluster_name).map_err(|_| MetaStoreError::InvalidClusterName)?;
+        if !self.clusters.contains_key(&cluster_name) {
+            return Err(MetaStoreError::ClusterNotFound);
+        }
+        Ok(self.migration_events.get(&cluster_name))
+    }
+
+    pub fn get_alert_rules(&self) -> HashMap<String, AlertRule> {
+        self.alert_rules.clone()
+    }
+
+    pub fn set_alert_rule(&mut self, name: String, rule: AlertRule) -> Result<(), MetaStoreError> {
+        rule.validate()?;
+        self.alert_rules.insert(name, rule);
+        Ok(())
+    }
+
+    pub fn remove_alert_rule(&mut self, name: &str) -> Result<(), MetaStoreError> {
+        self.alert_rules
+            .remove(name)
+            .map(|_| ())
+            .ok_or(MetaStoreError::AlertRuleNotFound)
+    }
+
+    pub fn get_proxy_config(&self) -> HashMap<String, String> {
+        self.proxy_config.clone()
+    }
+
+    // Replaces the whole config.
+    pub fn set_proxy_config(
+        &mut self,
+        proxy_config: HashMap<String, String>,
+    ) -> Result<(), MetaStoreError> {
+        for (key, value) in proxy_config.iter() {
+            if PROXY_LOCAL_CONFIG_FIELDS.contains(&key.to_lowercase().as_str()) {
+                return Err(MetaStoreError::InvalidConfig {
+                    key: key.clone(),
+                    value: value.clone(),
+                    error: "only configurable in each proxy".to_string(),
+                });
+            }
+        }
+        self.proxy_config = proxy_config;
+        Ok(())
+    }
+
+    pub fn get_namespaces(&self) -> HashMap<String, NamespaceInfo> {
+        self.namespaces
+            .keys()
+            .filter_map(|name| {
+                self.get_namespace(name)
+                    .ok()
+                    .map(|info| (name.clone(), info))
+            })
+            .collect()
+    }
+
+    pub fn get_namespace(&self, name: &str) -> Result<NamespaceInfo, MetaStoreError> {
+        let namespace = self
+            .namespaces
+            .get(name)
+            .ok_or(MetaStoreError::NamespaceNotFound)?
+            .clone();
+        let mut cluster_names: Vec<String> = self
+            .clusters
+            .values()
+            .filter(|cluster| cluster.namespace.as_deref() == Some(name))
+            .map(|cluster| cluster.name.to_string())
+            .collect();
+        cluster_names.sort();
+        let (_, node_number) = get_namespace_usage(self, name);
+        Ok(NamespaceInfo {
+            namespace,
+            cluster_names,
+            node_number,
+        })
+    }
+
+    // The policy is applied to the existing clusters in the namespace.
+    // The quota only limits the later changes.
+    pub fn set_namespace(
+        &mut self,
+        name: String,
+        namespace: Namespace,
+    ) -> Result<(), MetaStoreError> {
+        check_namespace_name(&name)?;
+        namespace.validate()?;
+        let mut configs = vec![];
+        for cluster in self.clusters.values() {
+            if cluster.namespace.as_ref() == Some(&name) {
+                let mut config = cluster.config.clone();
+                namespace.apply_policy(&mut config)?;
+                configs.push((cluster.name.clone(), config));
+            }
+        }
+        self.namespaces.insert(name, namespace);
+        if configs.is_empty() {
+            return Ok(());
+        }
+        let epoch = self.bump_global_epoch();
+        for (cluster_name, config) in configs.into_iter() {
+            if let Some(cluster) = self.clusters.get_mut(&cluster_name) {
+                cluster.config = config;
+                cluster.set_epoch(epoch);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_namespace(&mut self, name: &str) -> Result<(), MetaStoreError> {
+        if !self.namespaces.contains_key(name) {
+            return Err(MetaStoreError::NamespaceNotFound);
+        }
+        let (cluster_num, _) = get_namespace_usage(self, name);
+        if cluster_num != 0 {
+            return Err(MetaStoreError::InUse);
+        }
+        self.namespaces.remove(name);
+        Ok(())
+    }
+
     pub fn get_failed_proxies(&self) -> Vec<String> {
         self.failed_proxies.iter().cloned().collect()
     }
 
+    pub fn get_proxy_tls(
+        &self,
+        proxy_address: &str,
+    ) -> Result<Option<ProxyTlsMeta>, MetaStoreError> {
+        self.all_proxies
+            .get(proxy_address)
+            .map(|proxy| proxy.tls.clone())
+            .ok_or(MetaStoreError::ProxyNotFound)
+    }
+
+    pub fn set_proxy_tls(
+        &mut self,
+        proxy_address: String,
+        pkcs12_file: String,
+        password: String,
+    ) -> Result<ProxyTlsMeta, MetaStoreError> {
+        MetaStoreUpdate::new(self).set_proxy_tls(proxy_address, pkcs12_file, password)
+    }
+
+    pub fn remove_proxy_tls(&mut self, proxy_address: String) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).remove_proxy_tls(proxy_address)
+    }
+
+    pub fn set_proxy_force_resync(
+        &mut self,
+        proxy_address: String,
+        force_resync: bool,
+        now: i64,
+    ) -> Result<(), MetaStoreError> {
+        MetaStoreUpdate::new(self).set_proxy_force_resync(proxy_address, force_resync, now)
+    }
+
+    pub fn get_audit_log(&self) -> Vec<AuditEntry> {
+        self.audit_log.clone()
+    }
+
+    pub fn add_audit_entry(&mut self, action: &str, target: String, now: i64) {
+        let entry = AuditEntry {
+            time: now,
+            action: action.to_string(),
+            target,
+            epoch: self.global_epoch,
+        };
+        info!("audit: {:?}", entry);
+        self.audit_log.push(entry);
+        if self.audit_log.len() > MAX_AUDIT_LOG_LEN {
+            let n = self.audit_log.len() - MAX_AUDIT_LOG_LEN;
+            self.audit_log.drain(..n);
+        }
+    }
+
     pub fn force_bump_all_epoch(&mut self, new_epoch: u64) -> Result<(), MetaStoreError> {
         if new_epoch <= self.global_epoch {
             return Err(MetaStoreError::SmallEpoch);
@@ -483,6 +972,21 @@ pub enum MetaStoreError {
     SyncError(MetaSyncError),
     InvalidMetaVersion,
     SmallEpoch,
+    NotConfirmed,
+    HostNotFound,
+    AlertRuleNotFound,
+    ClusterRenaming,
+    InvalidNamespace,
+    NamespaceNotFound,
+    QuotaExceeded,
+    Forbidden,
+    InvalidSlotRanges {
+        error: String,
+    },
+    NodeNotFound,
+    KeyspaceDiffFailed {
+        error: String,
+    },
 }
 
 impl MetaStoreError {
@@ -509,6 +1013,17 @@ impl MetaStoreError {
             Self::SyncError(err) => err.to_code(),
             Self::InvalidMetaVersion => "INVALID_META_VERSION",
             Self::SmallEpoch => "EPOCH_SMALLER_THAN_CURRENT",
+            Self::NotConfirmed => "NOT_CONFIRMED",
+            Self::HostNotFound => "HOST_NOT_FOUND",
+            Self::AlertRuleNotFound => "ALERT_RULE_NOT_FOUND",
+            Self::ClusterRenaming => "CLUSTER_RENAMING",
+            Self::InvalidNamespace => "INVALID_NAMESPACE",
+            Self::NamespaceNotFound => "NAMESPACE_NOT_FOUND",
+            Self::QuotaExceeded => "QUOTA_EXCEEDED",
+            Self::Forbidden => "FORBIDDEN",
+            Self::InvalidSlotRanges { .. } => "INVALID_SLOT_RANGES",
+            Self::NodeNotFound => "NODE_NOT_FOUND",
+            Self::KeyspaceDiffFailed { .. } => "KEYSPACE_DIFF_FAILED",
         }
     }
 }
@@ -546,6 +1061,12 @@ impl Serialize for MetaStoreError {
                 state.serialize_field("message", &error)?;
                 state.end()
             }
+            Self::InvalidSlotRanges { error } | Self::KeyspaceDiffFailed { error } => {
+                let mut state = serializer.serialize_struct("MetaStoreError", 2)?;
+                state.serialize_field("error", &error_code)?;
+                state.serialize_field("message", &error)?;
+                state.end()
+            }
             _ => {
                 let mut state = serializer.serialize_struct("MetaStoreError", 1)?;
                 state.serialize_field("error", &error_code)?;
@@ -558,7 +1079,7 @@ impl Serialize for MetaStoreError {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::cluster::Role;
+    use crate::common::cluster::{Role, SLOT_LOAD_BUCKETS};
     use crate::common::config::CompressionStrategy;
     use crate::common::utils::SLOT_NUM;
     use std::convert::TryFrom;
@@ -572,7 +1093,7 @@ mod tests {
                     format!("127.0.0.{}:60{:02}", host_index, i * 2 + 1),
                 ];
                 store
-                    .add_proxy(proxy_address, node_addresses, None)
+                    .add_proxy(proxy_address, node_addresses, None, None)
                     .unwrap();
             }
         }
@@ -587,11 +1108,11 @@ mod tests {
         let nodes = ["127.0.0.1:6000".to_string(), "127.0.0.1:6001".to_string()];
 
         assert!(store
-            .add_proxy("127.0.0.1".to_string(), nodes.clone(), None)
+            .add_proxy("127.0.0.1".to_string(), nodes.clone(), None, None)
             .is_err());
 
         store
-            .add_proxy(proxy_address.to_string(), nodes.clone(), None)
+            .add_proxy(proxy_address.to_string(), nodes.clone(), None, None)
             .unwrap();
         assert_eq!(store.get_global_epoch(), 1);
         assert_eq!(store.all_proxies.len(), 1);
@@ -621,7 +1142,7 @@ mod tests {
         {
             let mut store = MetaStore::default();
             store
-                .add_proxy(proxy_address.to_string(), nodes.clone(), None)
+                .add_proxy(proxy_address.to_string(), nodes.clone(), None, None)
                 .unwrap();
             let proxies = store.get_free_proxies();
             let proxy = proxies.get(0).unwrap();
@@ -635,6 +1156,7 @@ mod tests {
                     proxy_address.to_string(),
                     nodes.clone(),
                     Some("localhost".to_string()),
+                    None,
                 )
                 .unwrap();
             let proxies = store.get_free_proxies();
@@ -644,6 +1166,82 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_reattach_proxy_by_id() {
+        let migration_limit = 0;
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 4, 3);
+        store.add_cluster("testcluster".to_string(), 4).unwrap();
+
+        let cluster_name = ClusterName::try_from("testcluster").unwrap();
+        let old_address =
+            store.clusters.get(&cluster_name).unwrap().chunks[0].proxy_addresses[0].clone();
+        let proxy_id = store
+            .all_proxies
+            .get(&old_address)
+            .unwrap()
+            .proxy_id
+            .clone();
+        assert_eq!(proxy_id.len(), 36);
+        assert!(store
+            .all_proxies
+            .values()
+            .all(|proxy| proxy.proxy_address == old_address || proxy.proxy_id != proxy_id));
+
+        // Heartbeat
+        let epoch = store.get_global_epoch();
+        let nodes = store
+            .all_proxies
+            .get(&old_address)
+            .unwrap()
+            .node_addresses
+            .clone();
+        let id = store
+            .add_proxy(old_address.clone(), nodes, None, Some(proxy_id.clone()))
+            .unwrap();
+        assert_eq!(id, proxy_id);
+        assert_eq!(store.get_global_epoch(), epoch);
+
+        let err = store
+            .add_proxy(
+                "127.0.0.9:7000".to_string(),
+                ["127.0.0.9:6000".to_string(), "127.0.0.9:6001".to_string()],
+                None,
+                Some("unknown".to_string()),
+            )
+            .unwrap_err();
+        assert_eq!(err, MetaStoreError::ProxyNotFound);
+
+        let new_address = "127.0.0.9:7000".to_string();
+        let new_nodes = ["127.0.0.9:6000".to_string(), "127.0.0.9:6001".to_string()];
+        let id = store
+            .add_proxy(
+                new_address.clone(),
+                new_nodes.clone(),
+                None,
+                Some(proxy_id.clone()),
+            )
+            .unwrap();
+        assert_eq!(id, proxy_id);
+        assert!(store.get_global_epoch() > epoch);
+        assert!(!store.all_proxies.contains_key(&old_address));
+        let resource = store.all_proxies.get(&new_address).unwrap();
+        assert_eq!(resource.proxy_id, proxy_id);
+        assert_eq!(resource.cluster, Some(cluster_name.clone()));
+        assert_eq!(resource.host, "127.0.0.9");
+
+        let chunk = &store.clusters.get(&cluster_name).unwrap().chunks[0];
+        assert_eq!(chunk.proxy_addresses[0], new_address);
+        assert_eq!(chunk.node_addresses[0..2], new_nodes);
+        check_cluster_and_proxy(&store);
+
+        let proxy = store
+            .get_proxy_by_address(&new_address, migration_limit)
+            .unwrap();
+        assert_eq!(proxy.get_proxy_id(), proxy_id);
+        assert_eq!(proxy.get_nodes().len(), 2);
+    }
+
     fn check_cluster_and_proxy(store: &MetaStore) {
         for cluster in store.clusters.values() {
             for chunk in cluster.chunks.iter() {
@@ -790,6 +1388,111 @@ mod tests {
         assert_eq!(free_node_num, original_free_node_num);
     }
 
+    #[test]
+    fn test_rename_cluster() {
+        let migration_limit = 0;
+
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 4, 3);
+        let cluster_name = "testcluster".to_string();
+        let new_cluster_name = "newcluster".to_string();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        store.add_cluster("another".to_string(), 4).unwrap();
+
+        let res = store.rename_cluster(cluster_name.clone(), "another".to_string());
+        assert_eq!(res.unwrap_err(), MetaStoreError::AlreadyExisted);
+        let res = store.rename_cluster("notfound".to_string(), new_cluster_name.clone());
+        assert_eq!(res.unwrap_err(), MetaStoreError::ClusterNotFound);
+
+        let epoch = store.get_global_epoch();
+        store
+            .rename_cluster(cluster_name.clone(), new_cluster_name.clone())
+            .unwrap();
+        assert!(store.get_global_epoch() > epoch);
+        assert!(store
+            .get_cluster_by_name(&cluster_name, migration_limit)
+            .is_none());
+        let cluster = store
+            .get_cluster_by_name(&new_cluster_name, migration_limit)
+            .unwrap();
+        assert_eq!(cluster.get_config().renamed_from.to_string(), cluster_name);
+        for node in cluster.get_nodes() {
+            assert_eq!(node.get_cluster_name().to_string(), new_cluster_name);
+        }
+        check_cluster_and_proxy(&store);
+
+        // The old name can't be reused during the renaming.
+        let res = store.add_cluster(cluster_name.clone(), 4);
+        assert_eq!(res.unwrap_err(), MetaStoreError::AlreadyExisted);
+        let res = store.rename_cluster(new_cluster_name.clone(), "othername".to_string());
+        assert_eq!(res.unwrap_err(), MetaStoreError::ClusterRenaming);
+
+        let epoch = store.get_global_epoch();
+        store
+            .finish_cluster_renaming(new_cluster_name.clone())
+            .unwrap();
+        assert!(store.get_global_epoch() > epoch);
+        let cluster = store
+            .get_cluster_by_name(&new_cluster_name, migration_limit)
+            .unwrap();
+        assert!(!cluster.get_config().is_renaming());
+        store.add_cluster(cluster_name, 4).unwrap();
+    }
+
+    #[test]
+    fn test_balance_slots() {
+        let migration_limit = 0;
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 4, 1);
+        let cluster_name = "testcluster".to_string();
+        store.add_cluster(cluster_name.clone(), 8).unwrap();
+
+        let hot_proxy = store.get_proxies()[0].clone();
+        for proxy_address in store.get_proxies().into_iter() {
+            let qps = if proxy_address == hot_proxy {
+                3000.0
+            } else {
+                1000.0
+            };
+            let sample = ProxyStatsSample {
+                time: 100,
+                stats: ProxyRuntimeStats::default(),
+                qps,
+                error_rate: 0.0,
+                slot_qps: vec![],
+            };
+            store.proxy_stats.insert(proxy_address, sample);
+        }
+
+        let policy = BalancePolicy {
+            threshold: 0.2,
+            max_migrations: 1,
+            window: None,
+        };
+        let epoch = store.get_global_epoch();
+        let balanced = store.balance_slots(&policy, 100);
+        assert_eq!(balanced.len(), 1);
+        assert!(store.get_global_epoch() > epoch);
+
+        let cluster = store
+            .get_cluster_by_name(&cluster_name, migration_limit)
+            .unwrap();
+        let migrating: Vec<_> = cluster
+            .get_nodes()
+            .iter()
+            .flat_map(|node| node.get_slots().iter())
+            .filter(|slot_range| slot_range.tag.is_migrating())
+            .collect();
+        assert_eq!(migrating.len(), 1);
+        assert_eq!(migrating[0].get_range_list().get_slots_num(), 4096 / 3);
+        let meta = migrating[0].tag.get_migration_meta().unwrap();
+        assert_eq!(meta.src_proxy_address, hot_proxy);
+
+        // Skipped during the migration.
+        assert!(store.balance_slots(&policy, 100).is_empty());
+        check_cluster_and_proxy(&store);
+    }
+
     #[test]
     fn test_allocation_distribution() {
         let migration_limit = 0;
@@ -961,7 +1664,7 @@ mod tests {
             .node_addresses
             .clone();
         let err = store
-            .add_proxy(failed_proxy_address.clone(), nodes, None)
+            .add_proxy(failed_proxy_address.clone(), nodes, None, None)
             .unwrap_err();
         assert_eq!(err, MetaStoreError::AlreadyExisted);
         assert_eq!(
@@ -1118,7 +1821,9 @@ mod tests {
             all_proxy_num - start_node_num / 2 - added_node_num / 2
         );
 
-        store.migrate_slots(cluster_name.clone()).unwrap();
+        store
+            .migrate_slots(cluster_name.clone(), RestorePolicy::default())
+            .unwrap();
         let epoch3 = store.get_global_epoch();
         assert!(epoch2 < epoch3);
 
@@ -1280,15 +1985,158 @@ mod tests {
     }
 
     #[test]
-    fn test_multiple_migration() {
-        const MAX_HOST_NUM: usize = 6;
-        const MAX_PROXY_PER_HOST: usize = 6;
-        assert_eq!(MAX_HOST_NUM * MAX_PROXY_PER_HOST % 2, 0);
-        const MAX_MIGRATION_LIMIT: u64 = 1;
+    fn test_force_migration() {
+        let migration_limit = 0;
+        let mut store = init_migration_test_store(4, 3, 4, migration_limit);
+        let cluster_name = CLUSTER_NAME.to_string();
+        store.auto_add_nodes(cluster_name.clone(), 4).unwrap();
+        store
+            .migrate_slots(cluster_name.clone(), RestorePolicy::default())
+            .unwrap();
 
-        for host_num in 2..=MAX_HOST_NUM {
-            for proxy_per_host in 1..=MAX_PROXY_PER_HOST {
-                for migration_limit in 0..=MAX_MIGRATION_LIMIT {
+        let now = chrono::Utc::now().timestamp();
+        let tasks = store.get_migration_tasks(now);
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks.iter().all(|task| task.elapsed == Some(0)));
+        assert!(tasks.iter().all(|task| task.percentage.is_none()));
+
+        let task = tasks[0].clone();
+        let slot_range = store
+            .get_cluster_by_name(&cluster_name, migration_limit)
+            .unwrap()
+            .get_nodes()
+            .iter()
+            .flat_map(|node| node.get_slots().to_vec())
+            .find(|slot_range| {
+                slot_range.tag.get_migration_meta().map(|meta| meta.epoch) == Some(task.epoch)
+                    && slot_range.tag.is_migrating()
+            })
+            .unwrap();
+        let task_events = MigrationTaskEvents {
+            cluster_name: cluster_name.clone(),
+            task: MigrationTaskMeta {
+                cluster_name: task.cluster_name.clone(),
+                slot_range,
+            }
+            .into_strings()
+            .join(" "),
+            proxy_address: String::new(),
+            finished: false,
+            events: vec![],
+            progress: Some(MigrationProgress {
+                estimated_keys: Some(1000),
+                estimated_bytes: None,
+                migrated_keys: 250,
+                migrated_bytes: 0,
+            }),
+        };
+        store
+            .report_migration_events(task.src_proxy_address.clone(), vec![task_events])
+            .unwrap();
+        let reported = store
+            .get_migration_tasks(now + 60)
+            .into_iter()
+            .find(|t| t.epoch == task.epoch && t.range_list == task.range_list)
+            .unwrap();
+        assert_eq!(reported.percentage, Some(25.0));
+        assert_eq!(reported.eta, Some(180));
+        assert_eq!(
+            store.force_migration(
+                cluster_name.clone(),
+                task.epoch + 1,
+                task.range_list.clone(),
+                ForceMigrationAction::Rollback,
+            ),
+            Err(MetaStoreError::MigrationTaskNotFound)
+        );
+        let epoch = store.get_global_epoch();
+        store
+            .force_migration(
+                cluster_name.clone(),
+                task.epoch,
+                task.range_list.clone(),
+                ForceMigrationAction::Rollback,
+            )
+            .unwrap();
+        assert!(store.get_global_epoch() > epoch);
+        assert_eq!(store.get_migration_tasks(now).len(), 1);
+
+        let task = store.get_migration_tasks(now).pop().unwrap();
+        store
+            .force_migration(
+                cluster_name.clone(),
+                task.epoch,
+                task.range_list,
+                ForceMigrationAction::Complete,
+            )
+            .unwrap();
+        assert!(store.get_migration_tasks(now).is_empty());
+
+        let cluster = store
+            .get_cluster_by_name(&cluster_name, migration_limit)
+            .unwrap();
+        let slot_num: usize = cluster
+            .get_nodes()
+            .iter()
+            .filter(|node| node.get_role() == Role::Master)
+            .flat_map(|node| node.get_slots().iter())
+            .map(|slot_range| {
+                assert!(slot_range.tag.is_stable());
+                slot_range.get_range_list().get_slots_num()
+            })
+            .sum();
+        assert_eq!(slot_num, SLOT_NUM);
+    }
+
+    #[test]
+    fn test_consistent_hash_scaling() {
+        let migration_limit = 0;
+        let mut store = init_migration_test_store(4, 3, 4, migration_limit);
+        let cluster_name = CLUSTER_NAME.to_string();
+        let mut config = HashMap::new();
+        config.insert("routing_mode".to_string(), "consistent_hash".to_string());
+        store.change_config(cluster_name.clone(), config).unwrap();
+
+        store.auto_add_nodes(cluster_name.clone(), 4).unwrap();
+        store
+            .migrate_slots(cluster_name.clone(), RestorePolicy::default())
+            .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        assert!(store.get_migration_tasks(now).is_empty());
+
+        let cluster = store
+            .get_cluster_by_name(&cluster_name, migration_limit)
+            .unwrap();
+        let masters: Vec<&Node> = cluster
+            .get_nodes()
+            .iter()
+            .filter(|node| node.get_role() == Role::Master)
+            .collect();
+        assert_eq!(masters.len(), 4);
+        for master in masters.into_iter() {
+            assert!(master
+                .get_slots()
+                .iter()
+                .all(|slot_range| slot_range.tag.is_stable()));
+            let slot_num: usize = master
+                .get_slots()
+                .iter()
+                .map(|slot_range| slot_range.get_range_list().get_slots_num())
+                .sum();
+            assert_eq!(slot_num, SLOT_NUM / 4);
+        }
+    }
+
+    #[test]
+    fn test_multiple_migration() {
+        const MAX_HOST_NUM: usize = 6;
+        const MAX_PROXY_PER_HOST: usize = 6;
+        assert_eq!(MAX_HOST_NUM * MAX_PROXY_PER_HOST % 2, 0);
+        const MAX_MIGRATION_LIMIT: u64 = 1;
+
+        for host_num in 2..=MAX_HOST_NUM {
+            for proxy_per_host in 1..=MAX_PROXY_PER_HOST {
+                for migration_limit in 0..=MAX_MIGRATION_LIMIT {
                     let sum_node_num = host_num * proxy_per_host * 2;
                     let mut store =
                         init_migration_test_store(host_num, proxy_per_host, 4, migration_limit);
@@ -1413,7 +2261,11 @@ mod tests {
 
         let epoch1 = store.get_global_epoch();
         store
-            .migrate_slots_to_scale_down(cluster_name.clone(), start_node_num - removed_node_num)
+            .migrate_slots_to_scale_down(
+                cluster_name.clone(),
+                start_node_num - removed_node_num,
+                RestorePolicy::default(),
+            )
             .unwrap();
         let epoch2 = store.get_global_epoch();
         assert!(epoch1 < epoch2);
@@ -1693,6 +2545,121 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_canary_config() {
+        let migration_limit = 0;
+
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 4, 3);
+
+        let cluster_name = CLUSTER_NAME.to_string();
+        let canary_cluster_name = "canarycluster".to_string();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        store.add_cluster(canary_cluster_name.clone(), 4).unwrap();
+
+        let mut config = HashMap::new();
+        config.insert("canary_cluster".to_string(), canary_cluster_name.clone());
+        config.insert("canary_percentage".to_string(), "10".to_string());
+        store.change_config(cluster_name.clone(), config).unwrap();
+
+        let cluster = store
+            .get_cluster_by_name(&cluster_name, migration_limit)
+            .unwrap();
+        assert_eq!(cluster.get_config().canary.percentage, 10);
+        let proxy_address = cluster.get_nodes()[0].get_proxy_address().to_string();
+        let proxy = store
+            .get_proxy_by_address(&proxy_address, migration_limit)
+            .unwrap();
+        assert_eq!(proxy.get_epoch(), store.get_global_epoch());
+        let canary_peers: Vec<_> = proxy
+            .get_peers()
+            .iter()
+            .filter(|peer| peer.cluster_name.as_str() == canary_cluster_name)
+            .collect();
+        assert_eq!(canary_peers.len(), 2);
+    }
+
+    #[test]
+    fn test_proxy_tls() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 1, 1);
+        let proxy_address = "127.0.0.1:7001".to_string();
+
+        assert_eq!(store.get_proxy_tls(&proxy_address).unwrap(), None);
+        assert_eq!(
+            store.get_proxy_tls("127.0.0.1:9999"),
+            Err(MetaStoreError::ProxyNotFound)
+        );
+
+        let tls = store
+            .set_proxy_tls(
+                proxy_address.clone(),
+                "/path/to/identity.p12".to_string(),
+                "pwd".to_string(),
+            )
+            .unwrap();
+        assert_eq!(tls.version, 1);
+        let tls = store
+            .set_proxy_tls(
+                proxy_address.clone(),
+                "/path/to/new_identity.p12".to_string(),
+                "pwd".to_string(),
+            )
+            .unwrap();
+        assert_eq!(tls.version, 2);
+        assert_eq!(
+            store.get_proxy_tls(&proxy_address).unwrap(),
+            Some(tls.clone())
+        );
+        // Pushed to the proxy by the coordinator.
+        let proxy = store.get_proxy_by_address(&proxy_address, 1).unwrap();
+        assert_eq!(proxy.get_tls(), Some(&tls));
+
+        store.remove_proxy_tls(proxy_address.clone()).unwrap();
+        assert_eq!(store.get_proxy_tls(&proxy_address).unwrap(), None);
+        let proxy = store.get_proxy_by_address(&proxy_address, 1).unwrap();
+        assert_eq!(proxy.get_tls(), None);
+    }
+
+    #[test]
+    fn test_proxy_force_resync() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 1, 1);
+        let proxy_address = "127.0.0.1:7001".to_string();
+
+        assert_eq!(
+            store.set_proxy_force_resync("127.0.0.1:9999".to_string(), true, 0),
+            Err(MetaStoreError::ProxyNotFound)
+        );
+        assert!(store.get_audit_log().is_empty());
+
+        store
+            .set_proxy_force_resync(proxy_address.clone(), true, 233)
+            .unwrap();
+        let proxy = store.get_proxy_by_address(&proxy_address, 1).unwrap();
+        assert!(proxy.is_force_resync());
+        let log = store.get_audit_log();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].time, 233);
+        assert_eq!(log[0].action, "force_resync");
+        assert_eq!(log[0].target, proxy_address);
+        assert_eq!(log[0].epoch, store.get_global_epoch());
+
+        store
+            .set_proxy_force_resync(proxy_address.clone(), false, 234)
+            .unwrap();
+        let proxy = store.get_proxy_by_address(&proxy_address, 1).unwrap();
+        assert!(!proxy.is_force_resync());
+        assert_eq!(store.get_audit_log()[1].action, "clear_force_resync");
+
+        for i in 0..MAX_AUDIT_LOG_LEN {
+            store.add_audit_entry("test", proxy_address.clone(), i as i64);
+        }
+        let log = store.get_audit_log();
+        assert_eq!(log.len(), MAX_AUDIT_LOG_LEN);
+        assert_eq!(log[0].time, 0);
+    }
+
     #[test]
     fn test_limited_migration() {
         let mut store = MetaStore::default();
@@ -1702,7 +2669,9 @@ mod tests {
         let cluster_name = CLUSTER_NAME.to_string();
         store.add_cluster(cluster_name.clone(), 4).unwrap();
         store.auto_add_nodes(cluster_name.clone(), 4).unwrap();
-        store.migrate_slots(cluster_name.clone()).unwrap();
+        store
+            .migrate_slots(cluster_name.clone(), RestorePolicy::default())
+            .unwrap();
         let cluster = store
             .get_cluster_by_name(CLUSTER_NAME, migration_limit)
             .unwrap();
@@ -1729,7 +2698,9 @@ mod tests {
         let cluster_name = CLUSTER_NAME.to_string();
         store.add_cluster(cluster_name.clone(), 4).unwrap();
         store.auto_add_nodes(cluster_name.clone(), 4).unwrap();
-        store.migrate_slots(cluster_name.clone()).unwrap();
+        store
+            .migrate_slots(cluster_name.clone(), RestorePolicy::default())
+            .unwrap();
         let cluster = store
             .get_cluster_by_name(CLUSTER_NAME, migration_limit)
             .unwrap();
@@ -1747,6 +2718,46 @@ mod tests {
         assert_eq!(migrating_masters, 4);
     }
 
+    #[test]
+    fn test_migration_restore_policy() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 4, 3);
+
+        let cluster_name = CLUSTER_NAME.to_string();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        store.auto_add_nodes(cluster_name.clone(), 4).unwrap();
+        store
+            .migrate_slots(cluster_name.clone(), RestorePolicy::Replace)
+            .unwrap();
+
+        let cluster = store.get_cluster_by_name(CLUSTER_NAME, 0).unwrap();
+        let slot_ranges: Vec<_> = cluster
+            .get_nodes()
+            .iter()
+            .flat_map(|node| node.get_slots().iter())
+            .filter(|slot_range| !slot_range.tag.is_stable())
+            .cloned()
+            .collect();
+        assert!(!slot_ranges.is_empty());
+        for slot_range in slot_ranges.iter() {
+            let meta = slot_range.tag.get_migration_meta().unwrap();
+            assert_eq!(meta.restore_policy, RestorePolicy::Replace);
+        }
+
+        for slot_range in slot_ranges.into_iter() {
+            if !slot_range.tag.is_migrating() {
+                continue;
+            }
+            let task_meta = MigrationTaskMeta {
+                cluster_name: ClusterName::try_from(CLUSTER_NAME).unwrap(),
+                slot_range,
+            };
+            store.commit_migration(task_meta).unwrap();
+        }
+        let cluster = store.get_cluster_by_name(CLUSTER_NAME, 0).unwrap();
+        check_cluster_slots(cluster, 8);
+    }
+
     // Docs examples:
     #[test]
     fn test_one_proxy_per_host() {
@@ -1810,10 +2821,13 @@ mod tests {
             .unwrap()
             .get_proxy_address()
             .to_string();
-        store
+        let new_proxy = store
             .replace_failed_proxy(proxy_address.clone(), 1)
             .unwrap()
             .unwrap();
+        store
+            .finish_proxy_rebuilding(new_proxy.get_address().to_string())
+            .unwrap();
 
         for chunk in store
             .clusters
@@ -1879,6 +2893,323 @@ mod tests {
         check_cluster_and_proxy(&store);
     }
 
+    #[test]
+    fn test_failback_masters() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 4, 1);
+        let cluster_name = CLUSTER_NAME.to_string();
+        let name = ClusterName::try_from(cluster_name.as_str()).unwrap();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        let proxy_address = store.clusters.get(&name).unwrap().chunks[0].proxy_addresses[0].clone();
+        let new_proxy = store
+            .replace_failed_proxy(proxy_address, 1)
+            .unwrap()
+            .unwrap();
+        let role_position =
+            |store: &MetaStore| store.clusters.get(&name).unwrap().chunks[0].role_position;
+        assert_ne!(role_position(&store), ChunkRolePosition::Normal);
+
+        // The failback policy is manual by default.
+        store
+            .finish_proxy_rebuilding(new_proxy.get_address().to_string())
+            .unwrap();
+        assert!(store.failback_masters(0).is_empty());
+        assert_ne!(role_position(&store), ChunkRolePosition::Normal);
+
+        let mut config = HashMap::new();
+        config.insert("failback_policy".to_string(), "auto".to_string());
+        store.change_config(cluster_name.clone(), config).unwrap();
+        let epoch = store
+            .get_cluster_by_name(&cluster_name, 1)
+            .unwrap()
+            .get_epoch();
+        assert_eq!(store.failback_masters(233), vec![name.clone()]);
+        assert_eq!(role_position(&store), ChunkRolePosition::Normal);
+        assert!(
+            store
+                .get_cluster_by_name(&cluster_name, 1)
+                .unwrap()
+                .get_epoch()
+                > epoch
+        );
+        assert_eq!(
+            store.get_audit_log().last().unwrap().action,
+            "failback_masters"
+        );
+        assert!(store.failback_masters(233).is_empty());
+
+        // Wait for the initial sync of the new replicas.
+        let proxy_address = store.clusters.get(&name).unwrap().chunks[0].proxy_addresses[1].clone();
+        store
+            .replace_failed_proxy(proxy_address, 1)
+            .unwrap()
+            .unwrap();
+        assert!(store.failback_masters(233).is_empty());
+        assert_ne!(role_position(&store), ChunkRolePosition::Normal);
+    }
+
+    #[test]
+    fn test_proxy_rebuilding() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 3, 1);
+        let cluster_name = CLUSTER_NAME.to_string();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        assert!(store.get_rebuilding_proxies().is_empty());
+
+        let cluster = store.get_cluster_by_name(&cluster_name, 1).unwrap();
+        let failed_address = cluster.get_nodes()[0].get_proxy_address().to_string();
+        let new_proxy = store
+            .replace_failed_proxy(failed_address.clone(), 1)
+            .unwrap()
+            .unwrap();
+        let new_address = new_proxy.get_address().to_string();
+        assert_eq!(store.get_rebuilding_proxies(), vec![new_address.clone()]);
+        assert!(new_proxy
+            .get_nodes()
+            .iter()
+            .all(|node| node.get_role() == Role::Replica));
+
+        // Can't promote the replicas before the initial sync finishes.
+        let name = ClusterName::try_from(cluster_name.as_str()).unwrap();
+        store.balance_masters(cluster_name.clone()).unwrap();
+        let chunk = &store.clusters.get(&name).unwrap().chunks[0];
+        assert_ne!(chunk.role_position, ChunkRolePosition::Normal);
+
+        assert_eq!(
+            store.finish_proxy_rebuilding("127.0.0.1:9999".to_string()),
+            Err(MetaStoreError::ProxyNotFound)
+        );
+        store.finish_proxy_rebuilding(new_address.clone()).unwrap();
+        assert!(store.get_rebuilding_proxies().is_empty());
+        store.balance_masters(cluster_name.clone()).unwrap();
+        let chunk = &store.clusters.get(&name).unwrap().chunks[0];
+        assert_eq!(chunk.role_position, ChunkRolePosition::Normal);
+
+        check_cluster_and_proxy(&store);
+    }
+
+    #[test]
+    fn test_proxy_stats() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 1, 1);
+        let address = store.get_proxies().pop().unwrap();
+        let epoch = store.get_global_epoch();
+
+        let mut stats = ProxyRuntimeStats {
+            commands_processed: 100,
+            error_replies: 10,
+            connected_sessions: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            store.report_proxy_stats("127.0.0.1:9999".to_string(), stats.clone(), 0),
+            Err(MetaStoreError::ProxyNotFound)
+        );
+        store
+            .report_proxy_stats(address.clone(), stats.clone(), 100)
+            .unwrap();
+        let sample = store.get_proxy_stats(&address).unwrap();
+        assert_eq!(sample.qps, 0.0);
+
+        stats.commands_processed = 300;
+        stats.error_replies = 30;
+        store
+            .report_proxy_stats(address.clone(), stats.clone(), 110)
+            .unwrap();
+        let sample = store.get_proxy_stats(&address).unwrap();
+        assert_eq!(sample.time, 110);
+        assert_eq!(sample.stats, stats);
+        assert_eq!(sample.qps, 20.0);
+        assert_eq!(sample.error_rate, 0.1);
+        // The samples don't change the metadata of the proxies.
+        assert_eq!(store.get_global_epoch(), epoch);
+
+        // The counters are reset after the proxy restarts.
+        stats.commands_processed = 10;
+        store
+            .report_proxy_stats(address.clone(), stats.clone(), 120)
+            .unwrap();
+        assert_eq!(store.get_proxy_stats(&address).unwrap().qps, 0.0);
+        assert_eq!(store.get_all_proxy_stats().len(), 1);
+        assert!(store.get_proxy_stats(&address).unwrap().slot_qps.is_empty());
+
+        stats.slot_reads = vec![0; SLOT_LOAD_BUCKETS];
+        stats.slot_writes = vec![0; SLOT_LOAD_BUCKETS];
+        store
+            .report_proxy_stats(address.clone(), stats.clone(), 130)
+            .unwrap();
+        stats.slot_reads[0] = 100;
+        stats.slot_writes[0] = 50;
+        stats.slot_writes[1] = 20;
+        store
+            .report_proxy_stats(address.clone(), stats, 140)
+            .unwrap();
+        let slot_qps = store.get_proxy_stats(&address).unwrap().slot_qps;
+        assert_eq!(slot_qps.len(), SLOT_LOAD_BUCKETS);
+        assert_eq!(slot_qps[0], 15.0);
+        assert_eq!(slot_qps[1], 2.0);
+        assert_eq!(slot_qps[2], 0.0);
+
+        store.remove_proxy(address.clone()).unwrap();
+        assert!(store.get_proxy_stats(&address).is_err());
+    }
+
+    #[test]
+    fn test_cluster_stats() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 2, 2);
+        let cluster_name = CLUSTER_NAME.to_string();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        for (i, address) in store.get_proxies().into_iter().enumerate() {
+            let stats = ProxyRuntimeStats {
+                connected_sessions: i as u64 + 1,
+                ..Default::default()
+            };
+            store.report_proxy_stats(address, stats, 100).unwrap();
+        }
+
+        store.record_cluster_stats(100);
+        let samples = store.get_cluster_stats(&cluster_name, 0).unwrap();
+        assert_eq!(samples.len(), 1);
+        // Only the 2 proxies in the cluster are counted.
+        assert!(samples[0].connected_sessions < 1 + 2 + 3 + 4);
+        assert!(store
+            .get_cluster_stats(&cluster_name, 101)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            store.get_cluster_stats("notfound", 0),
+            Err(MetaStoreError::ClusterNotFound)
+        );
+
+        let json = serde_json::to_string(&store).unwrap();
+        let restored: MetaStore = serde_json::from_str(&json).unwrap();
+        assert!(restored
+            .get_cluster_stats(&cluster_name, 0)
+            .unwrap()
+            .is_empty());
+        store.cluster_stats.set_persisted(true);
+        let json = serde_json::to_string(&store).unwrap();
+        let restored: MetaStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.get_cluster_stats(&cluster_name, 0).unwrap().len(),
+            1
+        );
+
+        store.remove_cluster(cluster_name.clone()).unwrap();
+        assert!(store
+            .cluster_stats
+            .get(&ClusterName::try_from(CLUSTER_NAME).unwrap(), 0)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_migration_events() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 2, 2);
+        let cluster_name = CLUSTER_NAME.to_string();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        let proxy_address = store.get_proxies()[0].clone();
+        let gen_task_events = |cluster_name: &str| MigrationTaskEvents {
+            cluster_name: cluster_name.to_string(),
+            task: format!("{} MIGRATING 1 0-100 233", cluster_name),
+            proxy_address: String::new(),
+            finished: true,
+            events: vec![],
+            progress: None,
+        };
+        let events = vec![gen_task_events(CLUSTER_NAME), gen_task_events("notfound")];
+        assert_eq!(
+            store.report_migration_events("127.0.0.1:1".to_string(), events.clone()),
+            Err(MetaStoreError::ProxyNotFound)
+        );
+        store
+            .report_migration_events(proxy_address.clone(), events)
+            .unwrap();
+        let tasks = store.get_migration_events(&cluster_name).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].proxy_address, proxy_address);
+        assert_eq!(
+            store.get_migration_events("notfound"),
+            Err(MetaStoreError::ClusterNotFound)
+        );
+
+        let json = serde_json::to_string(&store).unwrap();
+        let restored: MetaStore = serde_json::from_str(&json).unwrap();
+        assert!(restored
+            .get_migration_events(&cluster_name)
+            .unwrap()
+            .is_empty());
+        store.migration_events.set_persisted(true);
+        let json = serde_json::to_string(&store).unwrap();
+        let restored: MetaStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.get_migration_events(&cluster_name).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_host_maintenance() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 3, 2);
+        let cluster_name = CLUSTER_NAME.to_string();
+        store.add_cluster(cluster_name.clone(), 4).unwrap();
+        let name = ClusterName::try_from(cluster_name.as_str()).unwrap();
+        let host = store.clusters.get(&name).unwrap().chunks[0].hosts[0].clone();
+
+        assert_eq!(
+            store.start_host_maintenance("127.0.0.9".to_string(), 0),
+            Err(MetaStoreError::HostNotFound)
+        );
+        let maintenance = store.get_host_maintenance(&host).unwrap();
+        assert!(!maintenance.in_maintenance);
+        assert_eq!(maintenance.masters.len(), 1);
+        assert!(!maintenance.safe_to_reboot);
+
+        let epoch = store
+            .get_cluster_by_name(&cluster_name, 1)
+            .unwrap()
+            .get_epoch();
+        store.start_host_maintenance(host.clone(), 233).unwrap();
+        let cluster = store.get_cluster_by_name(&cluster_name, 1).unwrap();
+        assert!(cluster.get_epoch() > epoch);
+        let chunk = &store.clusters.get(&name).unwrap().chunks[0];
+        assert_eq!(chunk.role_position, ChunkRolePosition::SecondChunkMaster);
+        let maintenance = store.get_host_maintenance(&host).unwrap();
+        assert!(maintenance.in_maintenance);
+        assert!(maintenance.masters.is_empty());
+        assert!(maintenance.safe_to_reboot);
+        assert_eq!(store.get_maintenance_hosts(), vec![host.clone()]);
+        assert_eq!(store.get_audit_log()[0].action, "start_host_maintenance");
+
+        // No new nodes on the host in maintenance.
+        assert_eq!(store.get_free_proxies().len(), 3);
+        assert!(store.get_free_proxies().iter().all(|p| p.host != host));
+        store.auto_add_nodes(cluster_name.clone(), 4).unwrap();
+        let chunk = &store.clusters.get(&name).unwrap().chunks[1];
+        assert!(chunk.hosts.iter().all(|h| h != &host));
+
+        // The masters are kept away until the maintenance ends.
+        store.balance_masters(cluster_name.clone()).unwrap();
+        let chunk = &store.clusters.get(&name).unwrap().chunks[0];
+        assert_eq!(chunk.role_position, ChunkRolePosition::SecondChunkMaster);
+
+        store.end_host_maintenance(host.clone(), 234).unwrap();
+        assert_eq!(
+            store.end_host_maintenance(host.clone(), 235),
+            Err(MetaStoreError::HostNotFound)
+        );
+        assert!(store.get_maintenance_hosts().is_empty());
+        assert!(store.get_free_proxies().iter().any(|p| p.host == host));
+        store.balance_masters(cluster_name.clone()).unwrap();
+        let chunk = &store.clusters.get(&name).unwrap().chunks[0];
+        assert_eq!(chunk.role_position, ChunkRolePosition::Normal);
+        assert!(!store.get_host_maintenance(&host).unwrap().safe_to_reboot);
+
+        check_cluster_and_proxy(&store);
+    }
+
     #[test]
     fn test_bump_epoch() {
         let mut store = MetaStore::default();
@@ -1947,4 +3278,42 @@ mod tests {
         assert!(new_epoch <= store.get_global_epoch());
         assert_eq!(cluster.get_epoch(), store.get_global_epoch());
     }
+
+    #[test]
+    fn test_check_slot_ranges() {
+        let mut store = MetaStore::default();
+        add_testing_proxies(&mut store, 4, 3);
+        store.add_cluster("testcluster".to_string(), 4).unwrap();
+        let cluster_name = ClusterName::try_from("testcluster").unwrap();
+        assert!(store.clusters[&cluster_name].check_slot_ranges().is_ok());
+
+        let mut other = store.clone();
+        other.bump_global_epoch();
+        let cluster = other.clusters.get_mut(&cluster_name).unwrap();
+        let stolen = cluster.chunks[0].stable_slots[0].clone();
+        cluster.chunks[0].stable_slots[1] = stolen;
+        assert!(cluster.check_slot_ranges().is_err());
+        let err = store.restore(other).unwrap_err();
+        assert_eq!(err.to_code(), "INVALID_SLOT_RANGES");
+    }
+
+    #[test]
+    fn test_proxy_config() {
+        let mut store = MetaStore::default();
+        assert!(store.get_proxy_config().is_empty());
+
+        let mut proxy_config = HashMap::new();
+        proxy_config.insert("slowlog_log_slower_than".to_string(), "20000".to_string());
+        store.set_proxy_config(proxy_config.clone()).unwrap();
+        assert_eq!(store.get_proxy_config(), proxy_config);
+
+        let mut invalid_config = proxy_config.clone();
+        invalid_config.insert("Address".to_string(), "127.0.0.1:5299".to_string());
+        let err = store.set_proxy_config(invalid_config).unwrap_err();
+        assert_eq!(err.to_code(), "INVALID_CONFIG");
+        assert_eq!(store.get_proxy_config(), proxy_config);
+
+        store.set_proxy_config(HashMap::new()).unwrap();
+        assert!(store.get_proxy_config().is_empty());
+    }
 }
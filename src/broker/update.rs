@@ -1,13 +1,15 @@
+use super::namespace::check_namespace_quota;
 use super::query::MetaStoreQuery;
 use super::store::{
     ChunkRolePosition, ChunkStore, ClusterStore, HostProxy, MetaStore, MetaStoreError,
-    ProxyResource, CHUNK_HALF_NODE_NUM, CHUNK_NODE_NUM, CHUNK_PARTS, NODES_PER_PROXY,
+    ProxyResource, ProxyStatsSample, CHUNK_HALF_NODE_NUM, CHUNK_NODE_NUM, NODES_PER_PROXY,
 };
 use crate::common::cluster::{
-    Cluster, Node, Proxy, Range, RangeList, ReplMeta, ReplPeer, SlotRange, SlotRangeTag,
+    Cluster, Node, Proxy, ProxyRuntimeStats, ProxyTlsMeta, Range, RangeList, ReplMeta, ReplPeer,
+    SlotRange, SlotRangeTag, SLOT_LOAD_BUCKETS,
 };
 use crate::common::cluster::{ClusterName, Role};
-use crate::common::config::ClusterConfig;
+use crate::common::config::{ClusterConfig, FailbackPolicy};
 use crate::common::utils::SLOT_NUM;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use std::cmp::Ordering;
@@ -176,7 +178,8 @@ impl<'a> MetaStoreUpdate<'a> {
         proxy_address: String,
         nodes: [String; NODES_PER_PROXY],
         host: Option<String>,
-    ) -> Result<(), MetaStoreError> {
+        proxy_id: Option<String>,
+    ) -> Result<String, MetaStoreError> {
         if proxy_address.split(':').count() != 2 {
             return Err(MetaStoreError::InvalidProxyAddress);
         }
@@ -186,38 +189,128 @@ impl<'a> MetaStoreUpdate<'a> {
             (None, None) => return Err(MetaStoreError::InvalidProxyAddress),
         };
 
+        if let Some(proxy_id) = proxy_id {
+            return self.register_proxy_by_id(proxy_id, proxy_address, nodes, host);
+        }
+
         self.store.bump_global_epoch();
 
         let exists = self.store.all_proxies.contains_key(&proxy_address);
 
-        self.store
+        let proxy = self
+            .store
             .all_proxies
             .entry(proxy_address.clone())
             .or_insert_with(|| ProxyResource {
+                proxy_id: gen_proxy_id(),
                 proxy_address: proxy_address.clone(),
                 node_addresses: nodes,
                 host,
                 cluster: None,
+                tls: None,
+                force_resync: false,
+                rebuilding: false,
             });
+        // The proxies added by the old versions don't have the id.
+        if proxy.proxy_id.is_empty() {
+            proxy.proxy_id = gen_proxy_id();
+        }
+        let proxy_id = proxy.proxy_id.clone();
 
         self.store.failed_proxies.remove(&proxy_address);
         self.store.failures.remove(&proxy_address);
 
         if !exists {
-            Ok(())
+            Ok(proxy_id)
         } else {
             Err(MetaStoreError::AlreadyExisted)
         }
     }
 
+    // Registering again with the same address works as a heartbeat.
+    // When the address changes, the proxy keeps its slots with the new address and nodes.
+    fn register_proxy_by_id(
+        &mut self,
+        proxy_id: String,
+        proxy_address: String,
+        nodes: [String; NODES_PER_PROXY],
+        host: String,
+    ) -> Result<String, MetaStoreError> {
+        let old_address = self
+            .store
+            .all_proxies
+            .values()
+            .find(|proxy| !proxy_id.is_empty() && proxy.proxy_id == proxy_id)
+            .map(|proxy| proxy.proxy_address.clone())
+            .ok_or(MetaStoreError::ProxyNotFound)?;
+
+        if old_address == proxy_address {
+            if self.store.failed_proxies.remove(&proxy_address) {
+                self.store.bump_global_epoch();
+            }
+            self.store.failures.remove(&proxy_address);
+            return Ok(proxy_id);
+        }
+
+        if self.store.all_proxies.contains_key(&proxy_address) {
+            return Err(MetaStoreError::AlreadyExisted);
+        }
+        let mut proxy = self
+            .store
+            .all_proxies
+            .remove(&old_address)
+            .ok_or(MetaStoreError::ProxyNotFound)?;
+
+        let new_epoch = self.store.bump_global_epoch();
+        if let Some(cluster) = proxy
+            .cluster
+            .as_ref()
+            .and_then(|cluster_name| self.store.clusters.get_mut(cluster_name))
+        {
+            for chunk in cluster.chunks.iter_mut() {
+                if chunk.proxy_addresses[0] == old_address {
+                    chunk.hosts[0] = host.clone();
+                    chunk.proxy_addresses[0] = proxy_address.clone();
+                    chunk.node_addresses[0] = nodes[0].clone();
+                    chunk.node_addresses[1] = nodes[1].clone();
+                    break;
+                } else if chunk.proxy_addresses[1] == old_address {
+                    chunk.hosts[1] = host.clone();
+                    chunk.proxy_addresses[1] = proxy_address.clone();
+                    chunk.node_addresses[2] = nodes[0].clone();
+                    chunk.node_addresses[3] = nodes[1].clone();
+                    break;
+                }
+            }
+            cluster.set_epoch(new_epoch);
+        }
+
+        info!(
+            "proxy {} is reattached from {} to {}",
+            proxy_id, old_address, proxy_address
+        );
+        proxy.proxy_address = proxy_address.clone();
+        proxy.node_addresses = nodes;
+        proxy.host = host;
+        self.store.all_proxies.insert(proxy_address.clone(), proxy);
+
+        for address in [old_address, proxy_address].iter() {
+            self.store.failed_proxies.remove(address);
+            self.store.failures.remove(address);
+            self.store.proxy_stats.remove(address);
+        }
+        Ok(proxy_id)
+    }
+
     pub fn add_cluster(
         &mut self,
         cluster_name: String,
         node_num: usize,
+        namespace: Option<String>,
     ) -> Result<(), MetaStoreError> {
         let cluster_name = ClusterName::try_from(cluster_name.as_str())
             .map_err(|_| MetaStoreError::InvalidClusterName)?;
-        if self.store.clusters.contains_key(&cluster_name) {
+        if self.cluster_name_in_use(&cluster_name) {
             return Err(MetaStoreError::AlreadyExisted);
         }
 
@@ -227,6 +320,14 @@ impl<'a> MetaStoreUpdate<'a> {
         let proxy_num =
             NonZeroUsize::new(node_num / 2).ok_or_else(|| MetaStoreError::InvalidNodeNum)?;
 
+        let mut config = ClusterConfig::default();
+        if let Some(namespace) = namespace.as_ref() {
+            check_namespace_quota(self.store, namespace, 1, node_num)?;
+            if let Some(ns) = self.store.namespaces.get(namespace) {
+                ns.apply_policy(&mut config)?;
+            }
+        }
+
         let proxy_resource_arr = self.generate_free_chunks(proxy_num)?;
         let chunk_stores = Self::proxy_resource_to_chunk_store(proxy_resource_arr, true);
 
@@ -236,7 +337,8 @@ impl<'a> MetaStoreUpdate<'a> {
             epoch,
             name: cluster_name.clone(),
             chunks: chunk_stores,
-            config: ClusterConfig::default(),
+            config,
+            namespace,
         };
 
         // Tag the proxies as occupied
@@ -316,12 +418,15 @@ impl<'a> MetaStoreUpdate<'a> {
             None => return Err(MetaStoreError::ClusterNotFound),
             Some(cluster_store) => cluster_store,
         };
+        self.store.cluster_stats.remove(&cluster_name);
+        self.store.migration_events.remove(&cluster_name);
 
         // Set proxies free.
         for chunk in cluster_store.chunks.iter() {
             for proxy_address in chunk.proxy_addresses.iter() {
                 if let Some(proxy) = self.store.all_proxies.get_mut(proxy_address) {
                     proxy.cluster = None;
+                    proxy.rebuilding = false;
                 }
             }
         }
@@ -330,6 +435,101 @@ impl<'a> MetaStoreUpdate<'a> {
         Ok(())
     }
 
+    // The old name is still in use by the proxies until the renaming is finished.
+    fn cluster_name_in_use(&self, cluster_name: &ClusterName) -> bool {
+        self.store.clusters.contains_key(cluster_name)
+            || self
+                .store
+                .clusters
+                .values()
+                .any(|cluster| &cluster.config.renamed_from == cluster_name)
+    }
+
+    pub fn rename_cluster(
+        &mut self,
+        cluster_name: String,
+        new_cluster_name: String,
+    ) -> Result<(), MetaStoreError> {
+        let cluster_name = ClusterName::try_from(cluster_name.as_str())
+            .map_err(|_| MetaStoreError::InvalidClusterName)?;
+        let new_cluster_name = ClusterName::try_from(new_cluster_name.as_str())
+            .map_err(|_| MetaStoreError::InvalidClusterName)?;
+
+        let cluster = self
+            .store
+            .clusters
+            .get(&cluster_name)
+            .ok_or(MetaStoreError::ClusterNotFound)?;
+        if cluster.config.is_renaming() {
+            return Err(MetaStoreError::ClusterRenaming);
+        }
+        let migrating = cluster
+            .chunks
+            .iter()
+            .any(|chunk| chunk.migrating_slots.iter().any(|slots| !slots.is_empty()));
+        if migrating {
+            return Err(MetaStoreError::MigrationRunning);
+        }
+        if self.cluster_name_in_use(&new_cluster_name) {
+            return Err(MetaStoreError::AlreadyExisted);
+        }
+
+        let mut cluster_store = self
+            .store
+            .clusters
+            .remove(&cluster_name)
+            .ok_or(MetaStoreError::ClusterNotFound)?;
+        for chunk in cluster_store.chunks.iter() {
+            for proxy_address in chunk.proxy_addresses.iter() {
+                if let Some(proxy) = self.store.all_proxies.get_mut(proxy_address) {
+                    proxy.cluster = Some(new_cluster_name.clone());
+                }
+            }
+        }
+        for cluster in self.store.clusters.values_mut() {
+            let config = &mut cluster.config;
+            if config.canary.cluster == cluster_name {
+                config.canary.cluster = new_cluster_name.clone();
+            }
+            if config.double_write_cluster == cluster_name {
+                config.double_write_cluster = new_cluster_name.clone();
+            }
+        }
+        self.store
+            .cluster_stats
+            .rename(&cluster_name, new_cluster_name.clone());
+        self.store
+            .migration_events
+            .rename(&cluster_name, new_cluster_name.clone());
+
+        let epoch = self.store.bump_global_epoch();
+        cluster_store.epoch = epoch;
+        cluster_store.name = new_cluster_name.clone();
+        cluster_store.config.renamed_from = cluster_name;
+        self.store.clusters.insert(new_cluster_name, cluster_store);
+        Ok(())
+    }
+
+    // Stops the proxies from accepting the old name.
+    pub fn finish_cluster_renaming(&mut self, cluster_name: String) -> Result<(), MetaStoreError> {
+        let cluster_name = ClusterName::try_from(cluster_name.as_str())
+            .map_err(|_| MetaStoreError::InvalidClusterName)?;
+        let cluster = self
+            .store
+            .clusters
+            .get_mut(&cluster_name)
+            .ok_or(MetaStoreError::ClusterNotFound)?;
+        if !cluster.config.is_renaming() {
+            return Ok(());
+        }
+        cluster.config.renamed_from = ClusterName::empty();
+        let epoch = self.store.bump_global_epoch();
+        if let Some(cluster) = self.store.clusters.get_mut(&cluster_name) {
+            cluster.epoch = epoch;
+        }
+        Ok(())
+    }
+
     pub fn auto_scale_up_nodes(
         &mut self,
         cluster_name: String,
@@ -369,6 +569,9 @@ impl<'a> MetaStoreUpdate<'a> {
                 {
                     return Err(MetaStoreError::MigrationRunning);
                 }
+                if let Some(namespace) = cluster.namespace.as_ref() {
+                    check_namespace_quota(self.store, namespace, 0, num)?;
+                }
             }
         };
 
@@ -456,6 +659,7 @@ impl<'a> MetaStoreUpdate<'a> {
             for proxy_address in chunk.proxy_addresses.iter() {
                 if let Some(proxy) = self.store.all_proxies.get_mut(proxy_address) {
                     proxy.cluster = None;
+                    proxy.rebuilding = false;
                 }
             }
         }
@@ -476,10 +680,63 @@ impl<'a> MetaStoreUpdate<'a> {
         self.store.all_proxies.remove(&proxy_address);
         self.store.failed_proxies.remove(&proxy_address);
         self.store.failures.remove(&proxy_address);
+        self.store.proxy_stats.remove(&proxy_address);
         self.store.bump_global_epoch();
         Ok(())
     }
 
+    pub fn set_proxy_tls(
+        &mut self,
+        proxy_address: String,
+        pkcs12_file: String,
+        password: String,
+    ) -> Result<ProxyTlsMeta, MetaStoreError> {
+        let proxy = self
+            .store
+            .all_proxies
+            .get_mut(&proxy_address)
+            .ok_or(MetaStoreError::ProxyNotFound)?;
+        let version = proxy.tls.as_ref().map(|tls| tls.version).unwrap_or(0) + 1;
+        let tls = ProxyTlsMeta {
+            pkcs12_file,
+            password,
+            version,
+        };
+        proxy.tls = Some(tls.clone());
+        Ok(tls)
+    }
+
+    pub fn remove_proxy_tls(&mut self, proxy_address: String) -> Result<(), MetaStoreError> {
+        let proxy = self
+            .store
+            .all_proxies
+            .get_mut(&proxy_address)
+            .ok_or(MetaStoreError::ProxyNotFound)?;
+        proxy.tls = None;
+        Ok(())
+    }
+
+    pub fn set_proxy_force_resync(
+        &mut self,
+        proxy_address: String,
+        force_resync: bool,
+        now: i64,
+    ) -> Result<(), MetaStoreError> {
+        let proxy = self
+            .store
+            .all_proxies
+            .get_mut(&proxy_address)
+            .ok_or(MetaStoreError::ProxyNotFound)?;
+        proxy.force_resync = force_resync;
+        let action = if force_resync {
+            "force_resync"
+        } else {
+            "clear_force_resync"
+        };
+        self.store.add_audit_entry(action, proxy_address, now);
+        Ok(())
+    }
+
     fn generate_free_chunks(
         &self,
         proxy_num: NonZeroUsize,
@@ -813,14 +1070,17 @@ impl<'a> MetaStoreUpdate<'a> {
         // Set this proxy free
         if let Some(proxy) = self.store.all_proxies.get_mut(&failed_proxy_address) {
             proxy.cluster = None;
+            proxy.rebuilding = false;
         }
         // Tag the new proxy as occupied
+        // and wait for its empty nodes to sync from the masters.
         if let Some(proxy) = self
             .store
             .all_proxies
             .get_mut(&proxy_resource.proxy_address)
         {
             proxy.cluster = Some(cluster_name);
+            proxy.rebuilding = true;
         }
 
         let proxy = MetaStoreQuery::new(self.store)
@@ -925,34 +1185,256 @@ impl<'a> MetaStoreUpdate<'a> {
             .map_err(|_| MetaStoreError::InvalidClusterName)?;
         let new_epoch = self.store.bump_global_epoch();
 
-        let failed_proxies = &self.store.failed_proxies;
-        let failures = &self.store.failures;
-
-        let failed_proxy_exists = |addresses: &[String; CHUNK_PARTS]| -> bool {
-            for address in addresses.iter() {
-                if failed_proxies.contains(address) || failures.contains_key(address) {
-                    return true;
-                }
-            }
-            false
+        let movable_chunks: Vec<usize> = match self.store.clusters.get(&cluster_name) {
+            None => return Err(MetaStoreError::ClusterNotFound),
+            Some(cluster) => cluster
+                .chunks
+                .iter()
+                .enumerate()
+                .filter(|(_, chunk)| self.masters_movable(chunk))
+                .map(|(index, _)| index)
+                .collect(),
         };
 
-        match self.store.clusters.get_mut(&cluster_name) {
-            None => return Err(MetaStoreError::ClusterNotFound),
-            Some(ref mut cluster) => {
-                for chunk in cluster.chunks.iter_mut() {
-                    if failed_proxy_exists(&chunk.proxy_addresses) {
-                        continue;
-                    }
+        if let Some(cluster) = self.store.clusters.get_mut(&cluster_name) {
+            for index in movable_chunks.into_iter() {
+                if let Some(chunk) = cluster.chunks.get_mut(index) {
                     chunk.role_position = ChunkRolePosition::Normal;
                 }
-                cluster.set_epoch(new_epoch);
             }
+            cluster.set_epoch(new_epoch);
         }
 
         Ok(())
     }
 
+    fn masters_movable(&self, chunk: &ChunkStore) -> bool {
+        for address in chunk.proxy_addresses.iter() {
+            if self.store.failed_proxies.contains(address)
+                || self.store.failures.contains_key(address)
+            {
+                return false;
+            }
+            // The replicas still in the initial sync can't be promoted.
+            if self
+                .store
+                .all_proxies
+                .get(address)
+                .map(|p| p.rebuilding)
+                .unwrap_or(false)
+            {
+                return false;
+            }
+        }
+        // Keep the masters away from the hosts in maintenance.
+        !chunk
+            .hosts
+            .iter()
+            .any(|host| self.store.maintenance_hosts.contains(host))
+    }
+
+    // Moves the masters back for the clusters with the `auto` failback policy
+    // after the new replicas finish the initial sync or the hosts finish maintenance.
+    pub fn failback_masters(&mut self, now: i64) -> Vec<ClusterName> {
+        let cluster_names: Vec<ClusterName> = self
+            .store
+            .clusters
+            .values()
+            .filter(|cluster| cluster.config.failback_policy == FailbackPolicy::Auto)
+            .filter(|cluster| {
+                cluster.chunks.iter().any(|chunk| {
+                    chunk.role_position != ChunkRolePosition::Normal && self.masters_movable(chunk)
+                })
+            })
+            .map(|cluster| cluster.name.clone())
+            .collect();
+
+        for cluster_name in cluster_names.iter() {
+            if let Err(err) = self.balance_masters(cluster_name.to_string()) {
+                error!("failed to fail back cluster {}: {}", cluster_name, err);
+                continue;
+            }
+            self.store
+                .add_audit_entry("failback_masters", cluster_name.to_string(), now);
+        }
+        cluster_names
+    }
+
+    // Promotes the replicas on the peer hosts for all the masters on this host.
+    // The masters whose peers are not available are kept and need manual operations.
+    pub fn start_host_maintenance(&mut self, host: String, now: i64) -> Result<(), MetaStoreError> {
+        if !self
+            .store
+            .all_proxies
+            .values()
+            .any(|proxy| proxy.host == host)
+        {
+            return Err(MetaStoreError::HostNotFound);
+        }
+
+        let host_proxies: Vec<(String, ClusterName)> = self
+            .store
+            .all_proxies
+            .values()
+            .filter(|proxy| proxy.host == host)
+            .filter_map(|proxy| {
+                proxy
+                    .cluster
+                    .clone()
+                    .map(|cluster_name| (proxy.proxy_address.clone(), cluster_name))
+            })
+            .collect();
+        self.store.maintenance_hosts.insert(host.clone());
+
+        for (proxy_address, cluster_name) in host_proxies.into_iter() {
+            if !self.peer_available(&cluster_name, &proxy_address) {
+                warn!(
+                    "cannot promote the peer of {} for host maintenance",
+                    proxy_address
+                );
+                continue;
+            }
+            self.takeover_master(&cluster_name, proxy_address)?;
+        }
+
+        self.store.bump_global_epoch();
+        self.store
+            .add_audit_entry("start_host_maintenance", host, now);
+        Ok(())
+    }
+
+    // The masters are not moved back until `balance_masters` is called.
+    pub fn end_host_maintenance(&mut self, host: String, now: i64) -> Result<(), MetaStoreError> {
+        if !self.store.maintenance_hosts.remove(&host) {
+            return Err(MetaStoreError::HostNotFound);
+        }
+        self.store.bump_global_epoch();
+        self.store
+            .add_audit_entry("end_host_maintenance", host, now);
+        Ok(())
+    }
+
+    // Called by the coordinator after all the replicas of the proxy finish the initial sync.
+    pub fn finish_proxy_rebuilding(&mut self, proxy_address: String) -> Result<(), MetaStoreError> {
+        let proxy = self
+            .store
+            .all_proxies
+            .get_mut(&proxy_address)
+            .ok_or(MetaStoreError::ProxyNotFound)?;
+        if proxy.rebuilding {
+            info!("proxy {} finished rebuilding", proxy_address);
+            proxy.rebuilding = false;
+            self.store.bump_global_epoch();
+        }
+        Ok(())
+    }
+
+    // The global epoch is not bumped since the proxies don't need the statistics.
+    pub fn report_proxy_stats(
+        &mut self,
+        proxy_address: String,
+        stats: ProxyRuntimeStats,
+        now: i64,
+    ) -> Result<(), MetaStoreError> {
+        if !self.store.all_proxies.contains_key(&proxy_address) {
+            return Err(MetaStoreError::ProxyNotFound);
+        }
+
+        let slot_qps = self
+            .store
+            .proxy_stats
+            .get(&proxy_address)
+            .and_then(|last| Self::calculate_slot_qps(last, &stats, now))
+            .unwrap_or_default();
+        let (qps, error_rate) = match self.store.proxy_stats.get(&proxy_address) {
+            Some(last)
+                if now > last.time
+                    && stats.commands_processed >= last.stats.commands_processed
+                    && stats.error_replies >= last.stats.error_replies =>
+            {
+                let commands = stats.commands_processed - last.stats.commands_processed;
+                let errors = stats.error_replies - last.stats.error_replies;
+                let qps = commands as f64 / (now - last.time) as f64;
+                let error_rate = if commands == 0 {
+                    0.0
+                } else {
+                    errors as f64 / commands as f64
+                };
+                (qps, error_rate)
+            }
+            _ => (0.0, 0.0),
+        };
+
+        self.store.proxy_stats.insert(
+            proxy_address,
+            ProxyStatsSample {
+                time: now,
+                stats,
+                qps,
+                error_rate,
+                slot_qps,
+            },
+        );
+        Ok(())
+    }
+
+    // Returns None if the proxy doesn't report the slot load or the counters are reset.
+    fn calculate_slot_qps(
+        last: &ProxyStatsSample,
+        stats: &ProxyRuntimeStats,
+        now: i64,
+    ) -> Option<Vec<f64>> {
+        let last_stats = &last.stats;
+        if now <= last.time
+            || stats.slot_reads.len() != SLOT_LOAD_BUCKETS
+            || stats.slot_writes.len() != SLOT_LOAD_BUCKETS
+            || last_stats.slot_reads.len() != SLOT_LOAD_BUCKETS
+            || last_stats.slot_writes.len() != SLOT_LOAD_BUCKETS
+        {
+            return None;
+        }
+        let elapsed = (now - last.time) as f64;
+        (0..SLOT_LOAD_BUCKETS)
+            .map(|bucket| {
+                let reads = stats.slot_reads[bucket].checked_sub(last_stats.slot_reads[bucket])?;
+                let writes =
+                    stats.slot_writes[bucket].checked_sub(last_stats.slot_writes[bucket])?;
+                Some((reads + writes) as f64 / elapsed)
+            })
+            .collect()
+    }
+
+    fn peer_available(&self, cluster_name: &ClusterName, proxy_address: &str) -> bool {
+        let cluster = match self.store.clusters.get(cluster_name) {
+            Some(cluster) => cluster,
+            None => return false,
+        };
+        for chunk in cluster.chunks.iter() {
+            let peer_part = if chunk.proxy_addresses[0] == proxy_address {
+                1
+            } else if chunk.proxy_addresses[1] == proxy_address {
+                0
+            } else {
+                continue;
+            };
+            let peer_address = &chunk.proxy_addresses[peer_part];
+            let rebuilding = self
+                .store
+                .all_proxies
+                .get(peer_address)
+                .map(|proxy| proxy.rebuilding)
+                .unwrap_or(false);
+            return !rebuilding
+                && !self.store.failed_proxies.contains(peer_address)
+                && !self.store.failures.contains_key(peer_address)
+                && !self
+                    .store
+                    .maintenance_hosts
+                    .contains(&chunk.hosts[peer_part]);
+        }
+        false
+    }
+
     pub fn change_config(
         &mut self,
         cluster_name: String,
@@ -982,3 +1464,18 @@ impl<'a> MetaStoreUpdate<'a> {
         Ok(())
     }
 }
+
+// A random UUID of version 4.
+fn gen_proxy_id() -> String {
+    let mut n: u128 = rand::random();
+    n = (n & !(0xf << 76)) | (0x4 << 76);
+    n = (n & !(0x3 << 62)) | (0x2 << 62);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        n >> 96,
+        (n >> 80) & 0xffff,
+        (n >> 64) & 0xffff,
+        (n >> 48) & 0xffff,
+        n & 0xffff_ffff_ffff
+    )
+}
@@ -0,0 +1,103 @@
+use std::fs;
+
+// The global allocator is set by the binaries instead of this library
+// so that the applications using this library could choose their own.
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("feature \"jemalloc\" and feature \"mimalloc\" cannot be enabled at the same time");
+
+pub fn get_allocator_name() -> &'static str {
+    if cfg!(feature = "jemalloc") {
+        "jemalloc"
+    } else if cfg!(feature = "mimalloc") {
+        "mimalloc"
+    } else {
+        "system"
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AllocatorStats {
+    pub allocated: usize,
+    pub active: usize,
+    pub resident: usize,
+}
+
+impl AllocatorStats {
+    // Bytes in the active pages relative to the bytes allocated by the application.
+    pub fn get_frag_ratio(&self) -> f64 {
+        if self.allocated == 0 {
+            return 0.0;
+        }
+        self.active as f64 / self.allocated as f64
+    }
+}
+
+#[cfg(feature = "jemalloc")]
+pub fn get_allocator_stats() -> Option<AllocatorStats> {
+    use jemalloc_ctl::{epoch, stats};
+
+    // The statistics are cached until the epoch is advanced.
+    epoch::advance().ok()?;
+    Some(AllocatorStats {
+        allocated: stats::allocated::read().ok()?,
+        active: stats::active::read().ok()?,
+        resident: stats::resident::read().ok()?,
+    })
+}
+
+// Only jemalloc provides the statistics.
+#[cfg(not(feature = "jemalloc"))]
+pub fn get_allocator_stats() -> Option<AllocatorStats> {
+    None
+}
+
+// Only works in Linux.
+pub fn get_process_rss() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<usize>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+// Lines in the format of the Redis `INFO` sections.
+pub fn allocator_info() -> Vec<String> {
+    let mut lines = vec![format!("allocator:{}", get_allocator_name())];
+    if let Some(rss) = get_process_rss() {
+        lines.push(format!("used_memory_rss:{}", rss));
+    }
+    if let Some(stats) = get_allocator_stats() {
+        lines.push(format!("allocator_allocated:{}", stats.allocated));
+        lines.push(format!("allocator_active:{}", stats.active));
+        lines.push(format!("allocator_resident:{}", stats.resident));
+        lines.push(format!(
+            "allocator_frag_ratio:{:.2}",
+            stats.get_frag_ratio()
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocator_info() {
+        let lines = allocator_info();
+        assert_eq!(lines[0], format!("allocator:{}", get_allocator_name()));
+
+        let stats = AllocatorStats {
+            allocated: 100,
+            active: 150,
+            resident: 200,
+        };
+        assert!((stats.get_frag_ratio() - 1.5).abs() < 1e-9);
+        assert_eq!(AllocatorStats::default().get_frag_ratio(), 0.0);
+    }
+}
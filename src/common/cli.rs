@@ -0,0 +1,233 @@
+use super::version::UNDERMOON_VERSION;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    UnknownOption(String),
+    MissingValue(String),
+    TooManyArguments(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownOption(name) => write!(f, "unknown option {}", name),
+            Self::MissingValue(name) => write!(f, "missing value for {}", name),
+            Self::TooManyArguments(arg) => write!(f, "unexpected argument {}", arg),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+struct CliOption {
+    name: &'static str,
+    // None for the flags without values.
+    value_name: Option<&'static str>,
+    help: &'static str,
+}
+
+pub const HELP_FLAG: &str = "--help";
+pub const VERSION_FLAG: &str = "--version";
+
+// Parses `--name value`, `--name=value`, the flags and at most one positional argument.
+pub struct CliParser {
+    bin_name: &'static str,
+    positional: Option<&'static str>,
+    options: Vec<CliOption>,
+}
+
+impl CliParser {
+    pub fn new(bin_name: &'static str) -> Self {
+        Self {
+            bin_name,
+            positional: None,
+            options: vec![],
+        }
+    }
+
+    pub fn positional(mut self, value_name: &'static str) -> Self {
+        self.positional = Some(value_name);
+        self
+    }
+
+    pub fn option(
+        mut self,
+        name: &'static str,
+        value_name: &'static str,
+        help: &'static str,
+    ) -> Self {
+        self.options.push(CliOption {
+            name,
+            value_name: Some(value_name),
+            help,
+        });
+        self
+    }
+
+    pub fn flag(mut self, name: &'static str, help: &'static str) -> Self {
+        self.options.push(CliOption {
+            name,
+            value_name: None,
+            help,
+        });
+        self
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(&self, args: I) -> Result<CliArgs, CliError> {
+        let mut cli_args = CliArgs::default();
+        let mut iter = args.into_iter();
+        while let Some(arg) = iter.next() {
+            if !arg.starts_with("--") {
+                if self.positional.is_none() || cli_args.positional.is_some() {
+                    return Err(CliError::TooManyArguments(arg));
+                }
+                cli_args.positional = Some(arg);
+                continue;
+            }
+            let (name, inline_value) = match arg.find('=') {
+                Some(pos) => (arg[..pos].to_string(), Some(arg[pos + 1..].to_string())),
+                None => (arg.clone(), None),
+            };
+            if name == HELP_FLAG || name == VERSION_FLAG {
+                cli_args.flags.insert(name);
+                continue;
+            }
+            let option = self
+                .options
+                .iter()
+                .find(|option| option.name == name)
+                .ok_or_else(|| CliError::UnknownOption(name.clone()))?;
+            if option.value_name.is_none() {
+                cli_args.flags.insert(name);
+                continue;
+            }
+            let value = match inline_value {
+                Some(value) => value,
+                None => iter
+                    .next()
+                    .ok_or_else(|| CliError::MissingValue(name.clone()))?,
+            };
+            cli_args.values.insert(name, value);
+        }
+        Ok(cli_args)
+    }
+
+    pub fn usage(&self) -> String {
+        let mut lines = vec![
+            format!("{} {}", self.bin_name, UNDERMOON_VERSION),
+            String::new(),
+        ];
+        let positional = self
+            .positional
+            .map(|p| format!(" [{}]", p))
+            .unwrap_or_default();
+        lines.push(format!("USAGE: {}{} [OPTIONS]", self.bin_name, positional));
+        lines.push(String::new());
+        lines.push("OPTIONS:".to_string());
+        let mut options: Vec<(String, &str)> = self
+            .options
+            .iter()
+            .map(|option| {
+                let name = match option.value_name {
+                    Some(value_name) => format!("{} <{}>", option.name, value_name),
+                    None => option.name.to_string(),
+                };
+                (name, option.help)
+            })
+            .collect();
+        options.push((HELP_FLAG.to_string(), "Prints the help information"));
+        options.push((VERSION_FLAG.to_string(), "Prints the version"));
+        let width = options
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        for (name, help) in options.into_iter() {
+            lines.push(format!("    {:width$}    {}", name, help, width = width));
+        }
+        lines.join("\n")
+    }
+
+    pub fn version(&self) -> String {
+        format!("{} {}", self.bin_name, UNDERMOON_VERSION)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    positional: Option<String>,
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl CliArgs {
+    pub fn get_positional(&self) -> Option<&str> {
+        self.positional.as_deref()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+}
+
+// The filters like "info" or "undermoon=debug" in `--log-level` override `RUST_LOG`.
+pub fn init_logger(log_level: Option<&str>) {
+    match log_level {
+        Some(filters) => env_logger::Builder::new().parse(filters).init(),
+        None => env_logger::init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_parser() -> CliParser {
+        CliParser::new("server_proxy")
+            .positional("config_file")
+            .option("--address", "address", "The address to listen on")
+            .flag("--validate-config", "Only validates the config")
+    }
+
+    fn parse(args: &[&str]) -> Result<CliArgs, CliError> {
+        gen_parser().parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_cli() {
+        let args = parse(&["conf/server-proxy.toml", "--address", "127.0.0.1:6001"]).unwrap();
+        assert_eq!(args.get_positional(), Some("conf/server-proxy.toml"));
+        assert_eq!(args.get("--address"), Some("127.0.0.1:6001"));
+        assert!(!args.has_flag("--validate-config"));
+
+        let args = parse(&["--address=127.0.0.1:6001", "--validate-config", "--help"]).unwrap();
+        assert_eq!(args.get_positional(), None);
+        assert_eq!(args.get("--address"), Some("127.0.0.1:6001"));
+        assert!(args.has_flag("--validate-config"));
+        assert!(args.has_flag(HELP_FLAG));
+
+        assert_eq!(
+            parse(&["--unknown"]).unwrap_err(),
+            CliError::UnknownOption("--unknown".to_string())
+        );
+        assert_eq!(
+            parse(&["--address"]).unwrap_err(),
+            CliError::MissingValue("--address".to_string())
+        );
+        assert_eq!(
+            parse(&["a.toml", "b.toml"]).unwrap_err(),
+            CliError::TooManyArguments("b.toml".to_string())
+        );
+
+        let usage = gen_parser().usage();
+        assert!(usage.contains("USAGE: server_proxy [config_file] [OPTIONS]"));
+        assert!(usage.contains("--address <address>    The address to listen on"));
+    }
+}
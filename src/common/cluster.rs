@@ -8,6 +8,67 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::iter::Peekable;
 use std::mem::swap;
+use std::str::FromStr;
+
+// Decides what the destination node does with the keys already existing in it.
+// `Skip` keeps the existing keys and `Fail` stops the migration and reports the keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RestorePolicy {
+    #[default]
+    Skip,
+    Replace,
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct InvalidRestorePolicy;
+
+impl FromStr for RestorePolicy {
+    type Err = InvalidRestorePolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "skip" => Ok(Self::Skip),
+            "replace" => Ok(Self::Replace),
+            "fail" => Ok(Self::Fail),
+            _ => Err(InvalidRestorePolicy),
+        }
+    }
+}
+
+impl RestorePolicy {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Replace => "replace",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+impl Serialize for RestorePolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RestorePolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid restore policy {}", s)))
+    }
+}
+
+// The optional argument after the migration meta, e.g. "RESTORE=replace".
+// It's omitted for the default policy to stay compatible with the older proxies.
+const RESTORE_POLICY_PREFIX: &str = "RESTORE=";
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct MigrationMeta {
@@ -16,6 +77,8 @@ pub struct MigrationMeta {
     pub src_node_address: String,
     pub dst_proxy_address: String,
     pub dst_node_address: String,
+    #[serde(default)]
+    pub restore_policy: RestorePolicy,
 }
 
 impl MigrationMeta {
@@ -26,28 +89,51 @@ impl MigrationMeta {
             src_node_address,
             dst_proxy_address,
             dst_node_address,
+            restore_policy,
         } = self;
-        vec![
+        let mut strs = vec![
             epoch.to_string(),
             src_proxy_address,
             src_node_address,
             dst_proxy_address,
             dst_node_address,
-        ]
+        ];
+        if restore_policy != RestorePolicy::default() {
+            strs.push(format!(
+                "{}{}",
+                RESTORE_POLICY_PREFIX,
+                restore_policy.to_str()
+            ));
+        }
+        strs
     }
 
-    pub fn from_strings<It>(it: &mut It) -> Option<Self>
+    pub fn from_strings<It>(it: &mut Peekable<It>) -> Option<Self>
     where
         It: Iterator<Item = String>,
     {
         let epoch_str = it.next()?;
-        Some(Self {
+        let mut meta = Self {
             epoch: epoch_str.parse::<u64>().ok()?,
             src_proxy_address: it.next()?,
             src_node_address: it.next()?,
             dst_proxy_address: it.next()?,
             dst_node_address: it.next()?,
-        })
+            restore_policy: RestorePolicy::default(),
+        };
+        let policy = it.peek().and_then(|s| {
+            let prefix = s.get(..RESTORE_POLICY_PREFIX.len())?;
+            if prefix.eq_ignore_ascii_case(RESTORE_POLICY_PREFIX) {
+                Some(RestorePolicy::from_str(&s[RESTORE_POLICY_PREFIX.len()..]))
+            } else {
+                None
+            }
+        });
+        if let Some(policy) = policy {
+            it.next()?;
+            meta.restore_policy = policy.ok()?;
+        }
+        Some(meta)
     }
 }
 
@@ -312,6 +398,107 @@ impl RangeMap {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlotRangeError {
+    OutOfRange {
+        owner: String,
+        range: Range,
+    },
+    Overlap {
+        owner: String,
+        range: Range,
+        other_owner: String,
+        other_range: Range,
+    },
+}
+
+impl fmt::Display for SlotRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OutOfRange { owner, range } => write!(
+                f,
+                "slots {}-{} of {} are not within 0-{}",
+                range.start(),
+                range.end(),
+                owner,
+                SLOT_NUM - 1
+            ),
+            Self::Overlap {
+                owner,
+                range,
+                other_owner,
+                other_range,
+            } => write!(
+                f,
+                "slots {}-{} of {} overlap with slots {}-{} of {}",
+                range.start(),
+                range.end(),
+                owner,
+                other_range.start(),
+                other_range.end(),
+                other_owner
+            ),
+        }
+    }
+}
+
+// Each slot could only be owned by one owner.
+pub fn check_slot_owners<'a, I>(owners: I) -> Result<(), SlotRangeError>
+where
+    I: Iterator<Item = (&'a str, &'a RangeList)>,
+{
+    let mut ranges: Vec<(&str, &Range)> = owners
+        .flat_map(|(owner, range_list)| range_list.get_ranges().iter().map(move |r| (owner, r)))
+        .collect();
+    check_slot_ranges_bound(ranges.iter().cloned())?;
+    ranges.sort_by_key(|(_, range)| range.start());
+    for pair in ranges.windows(2) {
+        let (owner, range) = pair[0];
+        let (other_owner, other_range) = pair[1];
+        if range.end() >= other_range.start() {
+            return Err(SlotRangeError::Overlap {
+                owner: owner.to_string(),
+                range: range.clone(),
+                other_owner: other_owner.to_string(),
+                other_range: other_range.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// All the slot ranges, including the importing ones, should be checked
+// before building the slot maps which iterate over every slot.
+pub fn check_slot_ranges_bound<'a, I>(ranges: I) -> Result<(), SlotRangeError>
+where
+    I: Iterator<Item = (&'a str, &'a Range)>,
+{
+    for (owner, range) in ranges {
+        if range.start() > range.end() || range.end() >= SLOT_NUM {
+            return Err(SlotRangeError::OutOfRange {
+                owner: owner.to_string(),
+                range: range.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Merges the slot ranges with the same tag.
+pub fn merge_slot_ranges(slot_ranges: Vec<SlotRange>) -> Vec<SlotRange> {
+    let mut merged: Vec<SlotRange> = Vec::with_capacity(slot_ranges.len());
+    for mut slot_range in slot_ranges.into_iter() {
+        match merged.iter_mut().find(|s| s.tag == slot_range.tag) {
+            Some(s) => s.range_list.merge_another(&mut slot_range.range_list),
+            None => {
+                slot_range.range_list.compact();
+                merged.push(slot_range);
+            }
+        }
+    }
+    merged
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct SlotRange {
     pub range_list: RangeList,
@@ -722,6 +909,9 @@ pub struct PeerProxy {
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Proxy {
+    // Stays the same after the address changes.
+    #[serde(default)]
+    proxy_id: String,
     address: String,
     epoch: u64,
     nodes: Vec<Node>,
@@ -729,6 +919,21 @@ pub struct Proxy {
     peers: Vec<PeerProxy>,
     #[serde(default)]
     clusters_config: HashMap<ClusterName, ClusterConfig>,
+    // Makes the proxy accept the metadata even if its local epoch is larger.
+    #[serde(default)]
+    force_resync: bool,
+    // Pushed by the coordinator with `UMCTL RELOADTLS`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls: Option<ProxyTlsMeta>,
+}
+
+// Only the reference to the PKCS#12 file on the proxy host is stored.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ProxyTlsMeta {
+    pub pkcs12_file: String,
+    pub password: String,
+    // Bumped on every rotation.
+    pub version: u64,
 }
 
 impl Proxy {
@@ -741,12 +946,15 @@ impl Proxy {
         clusters_config: HashMap<ClusterName, ClusterConfig>,
     ) -> Self {
         Self {
+            proxy_id: String::new(),
             address,
             epoch,
             nodes,
             free_nodes,
             peers,
             clusters_config,
+            force_resync: false,
+            tls: None,
         }
     }
     pub fn get_address(&self) -> &str {
@@ -787,6 +995,103 @@ impl Proxy {
     pub fn get_clusters_config(&self) -> &HashMap<ClusterName, ClusterConfig> {
         &self.clusters_config
     }
+
+    pub fn is_force_resync(&self) -> bool {
+        self.force_resync
+    }
+    pub fn set_force_resync(&mut self, force_resync: bool) {
+        self.force_resync = force_resync;
+    }
+    pub fn get_tls(&self) -> Option<&ProxyTlsMeta> {
+        self.tls.as_ref()
+    }
+    pub fn set_tls(&mut self, tls: Option<ProxyTlsMeta>) {
+        self.tls = tls;
+    }
+    pub fn get_proxy_id(&self) -> &str {
+        &self.proxy_id
+    }
+    pub fn set_proxy_id(&mut self, proxy_id: String) {
+        self.proxy_id = proxy_id;
+    }
+}
+
+// The commands are counted for every bucket of 64 slots.
+pub const SLOT_LOAD_BUCKET_SIZE: usize = 64;
+pub const SLOT_LOAD_BUCKETS: usize = SLOT_NUM / SLOT_LOAD_BUCKET_SIZE;
+
+// Reported by the coordinator from `UMCTL INFOSTATS` of the proxies.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ProxyRuntimeStats {
+    pub commands_processed: u64,
+    pub error_replies: u64,
+    pub connected_sessions: u64,
+    pub used_memory_sessions: u64,
+    pub migrating_tasks: u64,
+    pub committed_migrating_tasks: u64,
+    // The largest lag in seconds of the replicas of the masters in the proxy.
+    #[serde(default)]
+    pub replication_lag: u64,
+    // The commands of each slot bucket. Empty for the proxies of the older versions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slot_reads: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slot_writes: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum MigrationEventKind {
+    State,
+    Retry,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MigrationEvent {
+    // Unix timestamps in milliseconds of the first and the last occurrences.
+    pub time: i64,
+    pub last_time: i64,
+    pub kind: MigrationEventKind,
+    pub detail: String,
+    // The consecutive same events are merged.
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct MigrationProgress {
+    // Estimated from DBSIZE and a sampled SCAN before migrating.
+    // None if the estimation failed.
+    pub estimated_keys: Option<u64>,
+    pub estimated_bytes: Option<u64>,
+    pub migrated_keys: u64,
+    pub migrated_bytes: u64,
+}
+
+impl MigrationProgress {
+    // The estimation could be smaller than the migrated keys.
+    pub fn get_percentage(&self) -> Option<f64> {
+        let total = self.estimated_keys?.max(self.migrated_keys);
+        if total == 0 {
+            return None;
+        }
+        Some(self.migrated_keys as f64 * 100.0 / total as f64)
+    }
+}
+
+// Reported by the coordinator from `UMCTL MIGRATIONLOG` of the proxies.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MigrationTaskEvents {
+    pub cluster_name: String,
+    // `MigrationTaskMeta` in the string format
+    pub task: String,
+    // Set by the broker.
+    #[serde(default)]
+    pub proxy_address: String,
+    pub finished: bool,
+    pub events: Vec<MigrationEvent>,
+    // Only for the migrating tasks.
+    #[serde(default)]
+    pub progress: Option<MigrationProgress>,
 }
 
 #[cfg(test)]
@@ -812,6 +1117,7 @@ mod tests {
             src_node_address: "127.0.0.1:6379".to_string(),
             dst_proxy_address: "127.0.0.1:7001".to_string(),
             dst_node_address: "127.0.0.1:6380".to_string(),
+            restore_policy: RestorePolicy::default(),
         };
         assert_eq!(SlotRangeTag::Importing(meta.clone()), slot_range);
 
@@ -1136,4 +1442,102 @@ mod tests {
         assert_eq!(range_list.get_ranges()[0].start(), 0);
         assert_eq!(range_list.get_ranges()[0].end(), 233);
     }
+
+    #[test]
+    fn test_check_slot_owners() {
+        let a = RangeList::new(vec![Range(0, 1000), Range(2001, 3000)]);
+        let b = RangeList::new(vec![Range(1001, 2000)]);
+        assert!(check_slot_owners(vec![("a", &a), ("b", &b)].into_iter()).is_ok());
+
+        let c = RangeList::new(vec![Range(3000, 4000)]);
+        let err = check_slot_owners(vec![("a", &a), ("c", &c)].into_iter()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "slots 2001-3000 of a overlap with slots 3000-4000 of c"
+        );
+
+        let d = RangeList::new(vec![Range(5000, SLOT_NUM)]);
+        let err = check_slot_owners(vec![("d", &d)].into_iter()).unwrap_err();
+        assert_eq!(
+            err,
+            SlotRangeError::OutOfRange {
+                owner: "d".to_string(),
+                range: Range(5000, SLOT_NUM),
+            }
+        );
+    }
+
+    #[test]
+    fn test_merge_slot_ranges() {
+        let slot_ranges = vec![
+            SlotRange {
+                range_list: RangeList::new(vec![Range(0, 1000)]),
+                tag: SlotRangeTag::None,
+            },
+            SlotRange {
+                range_list: RangeList::new(vec![Range(3000, 4000)]),
+                tag: SlotRangeTag::Migrating(MigrationMeta {
+                    epoch: 233,
+                    src_proxy_address: "127.0.0.1:7000".to_string(),
+                    src_node_address: "127.0.0.1:6000".to_string(),
+                    dst_proxy_address: "127.0.0.1:7001".to_string(),
+                    dst_node_address: "127.0.0.1:6001".to_string(),
+                    restore_policy: RestorePolicy::default(),
+                }),
+            },
+            SlotRange {
+                range_list: RangeList::new(vec![Range(1001, 2000)]),
+                tag: SlotRangeTag::None,
+            },
+        ];
+        let merged = merge_slot_ranges(slot_ranges);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].get_range_list().get_ranges(), &[Range(0, 2000)]);
+        assert_eq!(
+            merged[1].get_range_list().get_ranges(),
+            &[Range(3000, 4000)]
+        );
+    }
+
+    #[test]
+    fn test_restore_policy_strings() {
+        let meta = MigrationMeta {
+            epoch: 233,
+            src_proxy_address: "127.0.0.1:7000".to_string(),
+            src_node_address: "127.0.0.1:6000".to_string(),
+            dst_proxy_address: "127.0.0.1:7001".to_string(),
+            dst_node_address: "127.0.0.1:6001".to_string(),
+            restore_policy: RestorePolicy::default(),
+        };
+        assert_eq!(meta.clone().into_strings().len(), 5);
+
+        let mut replace_meta = meta.clone();
+        replace_meta.restore_policy = RestorePolicy::Replace;
+        let mut strs = replace_meta.clone().into_strings();
+        assert_eq!(strs.last().unwrap(), "RESTORE=replace");
+        strs.push("1".to_string());
+        let mut it = strs.into_iter().peekable();
+        assert_eq!(MigrationMeta::from_strings(&mut it), Some(replace_meta));
+        // The next argument is not consumed.
+        assert_eq!(it.next(), Some("1".to_string()));
+
+        let strs = vec!["233", "a", "b", "c", "d", "restore=FAIL"];
+        let mut it = strs.into_iter().map(|s| s.to_string()).peekable();
+        let parsed = MigrationMeta::from_strings(&mut it).unwrap();
+        assert_eq!(parsed.restore_policy, RestorePolicy::Fail);
+
+        let strs = vec!["233", "a", "b", "c", "d", "RESTORE=unknown"];
+        let mut it = strs.into_iter().map(|s| s.to_string()).peekable();
+        assert!(MigrationMeta::from_strings(&mut it).is_none());
+
+        let json = r#"{
+                "epoch": 233,
+                "src_proxy_address": "127.0.0.1:7000",
+                "src_node_address": "127.0.0.1:6000",
+                "dst_proxy_address": "127.0.0.1:7001",
+                "dst_node_address": "127.0.0.1:6001"
+            }"#;
+        let parsed: MigrationMeta = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed, meta);
+    }
 }
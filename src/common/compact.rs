@@ -0,0 +1,293 @@
+use super::cluster::{
+    ClusterName, MigrationMeta, Range, RangeList, RestorePolicy, SlotRange, SlotRangeTag,
+};
+use super::utils::SLOT_NUM;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str;
+
+// The binary encoding of the cluster map in `UMCTL SETCLUSTER`.
+// The numbers are varints and the strings are length-prefixed.
+// Each slot range is encoded as the start and the offset of the end
+// so that most of them only take a few bytes.
+
+const TAG_NONE: u8 = 0;
+const TAG_MIGRATING: u8 = 1;
+const TAG_IMPORTING: u8 = 2;
+
+const RESTORE_SKIP: u8 = 0;
+const RESTORE_REPLACE: u8 = 1;
+const RESTORE_FAIL: u8 = 2;
+
+type ClusterMap = HashMap<ClusterName, HashMap<String, Vec<SlotRange>>>;
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidCompactPayload;
+
+#[derive(Default)]
+pub struct CompactEncoder {
+    buf: Vec<u8>,
+}
+
+impl CompactEncoder {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn put_varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.buf.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.buf.push(n as u8);
+    }
+
+    pub fn put_str(&mut self, s: &str) {
+        self.put_varint(s.len() as u64);
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn put_cluster_map(&mut self, cluster_map: &ClusterMap) {
+        self.put_varint(cluster_map.len() as u64);
+        for (cluster_name, node_map) in cluster_map.iter() {
+            self.put_str(cluster_name.as_str());
+            self.put_varint(node_map.len() as u64);
+            for (node, slot_ranges) in node_map.iter() {
+                self.put_str(node);
+                self.put_varint(slot_ranges.len() as u64);
+                for slot_range in slot_ranges.iter() {
+                    self.put_slot_range(slot_range);
+                }
+            }
+        }
+    }
+
+    fn put_slot_range(&mut self, slot_range: &SlotRange) {
+        let meta = match &slot_range.tag {
+            SlotRangeTag::None => {
+                self.buf.push(TAG_NONE);
+                None
+            }
+            SlotRangeTag::Migrating(meta) => {
+                self.buf.push(TAG_MIGRATING);
+                Some(meta)
+            }
+            SlotRangeTag::Importing(meta) => {
+                self.buf.push(TAG_IMPORTING);
+                Some(meta)
+            }
+        };
+        let ranges = slot_range.get_range_list().get_ranges();
+        self.put_varint(ranges.len() as u64);
+        for range in ranges.iter() {
+            self.put_varint(range.start() as u64);
+            self.put_varint(range.end().saturating_sub(range.start()) as u64);
+        }
+        if let Some(meta) = meta {
+            self.put_varint(meta.epoch);
+            self.put_str(&meta.src_proxy_address);
+            self.put_str(&meta.src_node_address);
+            self.put_str(&meta.dst_proxy_address);
+            self.put_str(&meta.dst_node_address);
+            self.buf.push(match meta.restore_policy {
+                RestorePolicy::Skip => RESTORE_SKIP,
+                RestorePolicy::Replace => RESTORE_REPLACE,
+                RestorePolicy::Fail => RESTORE_FAIL,
+            });
+        }
+    }
+}
+
+pub struct CompactDecoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CompactDecoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn get_byte(&mut self) -> Result<u8, InvalidCompactPayload> {
+        let b = *self.buf.get(self.pos).ok_or(InvalidCompactPayload)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    pub fn get_varint(&mut self) -> Result<u64, InvalidCompactPayload> {
+        let mut n: u64 = 0;
+        let mut shift = 0;
+        loop {
+            if shift >= 64 {
+                return Err(InvalidCompactPayload);
+            }
+            let b = self.get_byte()?;
+            n |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+
+    fn get_usize(&mut self) -> Result<usize, InvalidCompactPayload> {
+        usize::try_from(self.get_varint()?).map_err(|_| InvalidCompactPayload)
+    }
+
+    pub fn get_str(&mut self) -> Result<&'a str, InvalidCompactPayload> {
+        let len = self.get_usize()?;
+        let end = self.pos.checked_add(len).ok_or(InvalidCompactPayload)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(InvalidCompactPayload)?;
+        self.pos = end;
+        str::from_utf8(bytes).map_err(|_| InvalidCompactPayload)
+    }
+
+    pub fn get_cluster_map(&mut self) -> Result<ClusterMap, InvalidCompactPayload> {
+        let mut cluster_map = HashMap::new();
+        for _ in 0..self.get_usize()? {
+            let cluster_name =
+                ClusterName::try_from(self.get_str()?).map_err(|_| InvalidCompactPayload)?;
+            let node_map = cluster_map.entry(cluster_name).or_insert_with(HashMap::new);
+            for _ in 0..self.get_usize()? {
+                let node = self.get_str()?.to_string();
+                let slot_ranges = node_map.entry(node).or_insert_with(Vec::new);
+                for _ in 0..self.get_usize()? {
+                    slot_ranges.push(self.get_slot_range()?);
+                }
+            }
+        }
+        Ok(cluster_map)
+    }
+
+    fn get_slot_range(&mut self) -> Result<SlotRange, InvalidCompactPayload> {
+        let tag = self.get_byte()?;
+        let mut ranges = vec![];
+        for _ in 0..self.get_usize()? {
+            let start = self.get_usize()?;
+            let end = start
+                .checked_add(self.get_usize()?)
+                .ok_or(InvalidCompactPayload)?;
+            // The end is never less than the start since it's encoded as an offset.
+            if end >= SLOT_NUM {
+                return Err(InvalidCompactPayload);
+            }
+            ranges.push(Range(start, end));
+        }
+        let mut range_list = RangeList::new(ranges);
+        range_list.compact();
+        let tag = match tag {
+            TAG_NONE => SlotRangeTag::None,
+            TAG_MIGRATING => SlotRangeTag::Migrating(self.get_migration_meta()?),
+            TAG_IMPORTING => SlotRangeTag::Importing(self.get_migration_meta()?),
+            _ => return Err(InvalidCompactPayload),
+        };
+        Ok(SlotRange { range_list, tag })
+    }
+
+    fn get_migration_meta(&mut self) -> Result<MigrationMeta, InvalidCompactPayload> {
+        Ok(MigrationMeta {
+            epoch: self.get_varint()?,
+            src_proxy_address: self.get_str()?.to_string(),
+            src_node_address: self.get_str()?.to_string(),
+            dst_proxy_address: self.get_str()?.to_string(),
+            dst_node_address: self.get_str()?.to_string(),
+            restore_policy: match self.get_byte()? {
+                RESTORE_SKIP => RestorePolicy::Skip,
+                RESTORE_REPLACE => RestorePolicy::Replace,
+                RESTORE_FAIL => RestorePolicy::Fail,
+                _ => return Err(InvalidCompactPayload),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        let mut encoder = CompactEncoder::default();
+        let numbers = [0, 1, 127, 128, 16383, 16384, u64::MAX];
+        for n in numbers.iter() {
+            encoder.put_varint(*n);
+        }
+        let buf = encoder.into_bytes();
+        let mut decoder = CompactDecoder::new(&buf);
+        for n in numbers.iter() {
+            assert_eq!(decoder.get_varint(), Ok(*n));
+        }
+        assert!(decoder.is_empty());
+        assert_eq!(decoder.get_varint(), Err(InvalidCompactPayload));
+    }
+
+    #[test]
+    fn test_cluster_map() {
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let meta = MigrationMeta {
+            epoch: 233,
+            src_proxy_address: "127.0.0.1:7000".to_string(),
+            src_node_address: "127.0.0.1:6000".to_string(),
+            dst_proxy_address: "127.0.0.1:7001".to_string(),
+            dst_node_address: "127.0.0.1:6001".to_string(),
+            restore_policy: RestorePolicy::Replace,
+        };
+        let slot_ranges = vec![
+            SlotRange {
+                range_list: RangeList::new(vec![Range(0, 1000), Range(2000, 2000)]),
+                tag: SlotRangeTag::None,
+            },
+            SlotRange {
+                range_list: RangeList::from_single_range(Range(5000, 6000)),
+                tag: SlotRangeTag::Migrating(meta),
+            },
+        ];
+        let mut node_map = HashMap::new();
+        node_map.insert("127.0.0.1:6000".to_string(), slot_ranges.clone());
+        let mut cluster_map = HashMap::new();
+        cluster_map.insert(cluster_name.clone(), node_map);
+
+        let mut encoder = CompactEncoder::default();
+        encoder.put_cluster_map(&cluster_map);
+        let buf = encoder.into_bytes();
+
+        let decoded = CompactDecoder::new(&buf).get_cluster_map().unwrap();
+        let decoded_ranges = decoded
+            .get(&cluster_name)
+            .unwrap()
+            .get("127.0.0.1:6000")
+            .unwrap();
+        assert_eq!(decoded_ranges.len(), 2);
+        for (lhs, rhs) in decoded_ranges.iter().zip(slot_ranges.iter()) {
+            assert!(lhs.meta_eq(rhs));
+        }
+
+        // Truncated payload
+        let mut decoder = CompactDecoder::new(&buf[..buf.len() - 1]);
+        assert!(decoder.get_cluster_map().is_err());
+    }
+
+    #[test]
+    fn test_slot_range_out_of_range() {
+        let encode = |start: u64, offset: u64| {
+            let mut encoder = CompactEncoder::default();
+            encoder.buf.push(TAG_NONE);
+            encoder.put_varint(1);
+            encoder.put_varint(start);
+            encoder.put_varint(offset);
+            encoder.into_bytes()
+        };
+        let buf = encode(0, (SLOT_NUM - 1) as u64);
+        assert!(CompactDecoder::new(&buf).get_slot_range().is_ok());
+        let buf = encode(0, SLOT_NUM as u64);
+        assert!(CompactDecoder::new(&buf).get_slot_range().is_err());
+        let buf = encode(SLOT_NUM as u64, 0);
+        assert!(CompactDecoder::new(&buf).get_slot_range().is_err());
+        let buf = encode(0, u64::MAX);
+        assert!(CompactDecoder::new(&buf).get_slot_range().is_err());
+    }
+}
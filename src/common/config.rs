@@ -1,8 +1,11 @@
+use super::cluster::ClusterName;
+use super::utils::{glob_match, str_ascii_case_insensitive_eq, SLOT_NUM};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::str::FromStr;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ClusterConfig {
@@ -10,130 +13,1188 @@ pub struct ClusterConfig {
     pub compression_strategy: CompressionStrategy,
     #[serde(default)]
     pub migration_config: MigrationConfig,
+    #[serde(default)]
+    pub acl: AclConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub backend_flavor: BackendFlavor,
+    // In bytes. Zero means no limitation.
+    #[serde(default)]
+    pub max_key_size: u64,
+    #[serde(default)]
+    pub max_value_size: u64,
+    #[serde(default)]
+    pub ttl_policy: TtlPolicy,
+    // In seconds. Used by the `auto` ttl policy.
+    #[serde(default)]
+    pub default_ttl: u64,
+    #[serde(default)]
+    pub canary: CanaryConfig,
+    // The write commands will also be sent to this cluster asynchronously.
+    // Empty cluster name means disabled.
+    #[serde(default)]
+    pub double_write_cluster: ClusterName,
+    #[serde(default)]
+    pub read_preference: ReadPreference,
+    // In seconds. The replicas lagging behind more than this will not serve the reads.
+    // Zero means no limitation.
+    #[serde(default)]
+    pub max_replica_lag: u64,
+    // The old name still accepted by the proxies after renaming.
+    // Empty cluster name means the grace period is over.
+    #[serde(default)]
+    pub renamed_from: ClusterName,
+    #[serde(default)]
+    pub routing_mode: RoutingMode,
+    #[serde(default)]
+    pub unknown_command_policy: UnknownCommandPolicy,
+    // Only used by the memory broker.
+    #[serde(default)]
+    pub failback_policy: FailbackPolicy,
+    // Experimental.
+    #[serde(default)]
+    pub write_ack_policy: WriteAckPolicy,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            compression_strategy: CompressionStrategy::default(),
+            migration_config: MigrationConfig::default(),
+            acl: AclConfig::default(),
+            auth: AuthConfig::default(),
+            backend_flavor: BackendFlavor::default(),
+            max_key_size: 0,
+            max_value_size: 0,
+            ttl_policy: TtlPolicy::default(),
+            default_ttl: 0,
+            canary: CanaryConfig::default(),
+            double_write_cluster: ClusterName::empty(),
+            read_preference: ReadPreference::default(),
+            max_replica_lag: 0,
+            renamed_from: ClusterName::empty(),
+            routing_mode: RoutingMode::default(),
+            unknown_command_policy: UnknownCommandPolicy::default(),
+            failback_policy: FailbackPolicy::default(),
+            write_ack_policy: WriteAckPolicy::default(),
+        }
+    }
+}
+
+impl ClusterConfig {
+    pub fn set_field(&mut self, field: &str, value: &str) -> Result<(), ConfigError> {
+        let field = field.to_lowercase();
+        match field.as_str() {
+            "compression_strategy" => {
+                let strategy =
+                    CompressionStrategy::from_str(&value).map_err(|_| ConfigError::InvalidValue)?;
+                self.compression_strategy = strategy;
+            }
+            "backend_flavor" => {
+                let flavor =
+                    BackendFlavor::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.backend_flavor = flavor;
+            }
+            "max_key_size" => {
+                let v = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                self.max_key_size = v;
+            }
+            "max_value_size" => {
+                let v = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                self.max_value_size = v;
+            }
+            "ttl_policy" => {
+                let policy = TtlPolicy::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.ttl_policy = policy;
+            }
+            "default_ttl" => {
+                let v = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                self.default_ttl = v;
+            }
+            "double_write_cluster" => {
+                let cluster =
+                    ClusterName::try_from(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.double_write_cluster = cluster;
+            }
+            "read_preference" => {
+                let preference =
+                    ReadPreference::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.read_preference = preference;
+            }
+            "max_replica_lag" => {
+                let v = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                self.max_replica_lag = v;
+            }
+            "renamed_from" => {
+                let cluster =
+                    ClusterName::try_from(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.renamed_from = cluster;
+            }
+            "routing_mode" => {
+                let mode = RoutingMode::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.routing_mode = mode;
+            }
+            "unknown_command_policy" => {
+                let policy =
+                    UnknownCommandPolicy::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.unknown_command_policy = policy;
+            }
+            "failback_policy" => {
+                let policy =
+                    FailbackPolicy::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.failback_policy = policy;
+            }
+            "write_ack_policy" => {
+                let policy =
+                    WriteAckPolicy::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.write_ack_policy = policy;
+            }
+            _ => {
+                if field.starts_with("migration_") {
+                    let f = field
+                        .splitn(2, '_')
+                        .nth(1)
+                        .ok_or_else(|| ConfigError::FieldNotFound)?;
+                    return self.migration_config.set_field(f, value);
+                } else if let Some(f) = field.strip_prefix("acl_") {
+                    return self.acl.set_field(f, value);
+                } else if let Some(f) = field.strip_prefix("auth_") {
+                    return self.auth.set_field(f, value);
+                } else if let Some(f) = field.strip_prefix("canary_") {
+                    return self.canary.set_field(f, value);
+                } else {
+                    return Err(ConfigError::FieldNotFound);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_renaming(&self) -> bool {
+        !self.renamed_from.as_str().is_empty()
+    }
+
+    pub fn double_write_enabled(&self) -> bool {
+        !self.double_write_cluster.as_str().is_empty()
+    }
+
+    // The other clusters the proxies of this cluster need to know.
+    pub fn get_linked_clusters(&self) -> Vec<&ClusterName> {
+        let mut clusters = vec![];
+        if self.canary.is_enabled() {
+            clusters.push(&self.canary.cluster);
+        }
+        if self.double_write_enabled() && !clusters.contains(&&self.double_write_cluster) {
+            clusters.push(&self.double_write_cluster);
+        }
+        clusters
+    }
+
+    pub fn to_str_map(&self) -> HashMap<String, String> {
+        let mut fields = vec![
+            (
+                "compression_strategy",
+                self.compression_strategy.to_str().to_string(),
+            ),
+            (
+                "migration_max_migration_time",
+                self.migration_config.max_migration_time.to_string(),
+            ),
+            (
+                "migration_max_blocking_time",
+                self.migration_config.max_blocking_time.to_string(),
+            ),
+            (
+                "migration_scan_interval",
+                self.migration_config.scan_interval.to_string(),
+            ),
+            (
+                "migration_scan_count",
+                self.migration_config.scan_count.to_string(),
+            ),
+            (
+                "migration_node_bandwidth",
+                self.migration_config.node_bandwidth.to_string(),
+            ),
+            ("acl_categories", self.acl.categories_to_str()),
+            ("acl_key_patterns", self.acl.key_patterns.join(",")),
+            ("backend_flavor", self.backend_flavor.to_str().to_string()),
+            ("max_key_size", self.max_key_size.to_string()),
+            ("max_value_size", self.max_value_size.to_string()),
+            ("ttl_policy", self.ttl_policy.to_str().to_string()),
+            ("default_ttl", self.default_ttl.to_string()),
+            ("read_preference", self.read_preference.to_str().to_string()),
+            ("max_replica_lag", self.max_replica_lag.to_string()),
+        ];
+        // Empty value could not be passed in UMCTL SETCLUSTER.
+        if !self.auth.passwords.is_empty() {
+            fields.push(("auth_passwords", self.auth.passwords.join(",")));
+        }
+        if !self.auth.users.is_empty() {
+            fields.push(("auth_users", self.auth.users_to_str()));
+        }
+        if !self.auth.user_key_patterns.is_empty() {
+            fields.push((
+                "auth_user_key_patterns",
+                self.auth.user_key_patterns_to_str(),
+            ));
+        }
+        if self.canary.is_enabled() {
+            fields.push(("canary_cluster", self.canary.cluster.to_string()));
+            fields.push(("canary_percentage", self.canary.percentage.to_string()));
+        }
+        if self.double_write_enabled() {
+            fields.push((
+                "double_write_cluster",
+                self.double_write_cluster.to_string(),
+            ));
+        }
+        if self.is_renaming() {
+            fields.push(("renamed_from", self.renamed_from.to_string()));
+        }
+        let migration_config = &self.migration_config;
+        if migration_config.read_blocking_policy != BlockingPolicy::Queue {
+            fields.push((
+                "migration_read_blocking_policy",
+                migration_config.read_blocking_policy.to_str().to_string(),
+            ));
+        }
+        if migration_config.write_blocking_policy != BlockingPolicy::Queue {
+            fields.push((
+                "migration_write_blocking_policy",
+                migration_config.write_blocking_policy.to_str().to_string(),
+            ));
+        }
+        if self.routing_mode != RoutingMode::Slots {
+            fields.push(("routing_mode", self.routing_mode.to_str().to_string()));
+        }
+        if self.unknown_command_policy != UnknownCommandPolicy::Forward {
+            fields.push((
+                "unknown_command_policy",
+                self.unknown_command_policy.to_str().to_string(),
+            ));
+        }
+        if self.failback_policy != FailbackPolicy::Manual {
+            fields.push(("failback_policy", self.failback_policy.to_str().to_string()));
+        }
+        if self.write_ack_policy != WriteAckPolicy::Master {
+            fields.push((
+                "write_ack_policy",
+                self.write_ack_policy.to_str().to_string(),
+            ));
+        }
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CompressionStrategy {
+    Disabled = 0,
+    // Only allow SET, SETEX, PSETEX, SETNX, GET, GETSET , MGET, MSET, MSETNX commands for String data type
+    // as once compression is enabled other commands will get the wrong result.
+    SetGetOnly = 1,
+    // Allow all the String commands. User need to use lua script to
+    // bypass the compression.
+    AllowAll = 2,
+}
+
+impl Default for CompressionStrategy {
+    fn default() -> Self {
+        CompressionStrategy::Disabled
+    }
+}
+
+pub struct InvalidCompressionStr;
+
+impl FromStr for CompressionStrategy {
+    type Err = InvalidCompressionStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "disabled" => Ok(Self::Disabled),
+            "set_get_only" => Ok(Self::SetGetOnly),
+            "allow_all" => Ok(Self::AllowAll),
+            _ => Err(InvalidCompressionStr),
+        }
+    }
+}
+
+impl CompressionStrategy {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Disabled => "disabled",
+            Self::SetGetOnly => "set_get_only",
+            Self::AllowAll => "allow_all",
+        }
+    }
+}
+
+impl Serialize for CompressionStrategy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.clone().to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for CompressionStrategy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s)
+            .map_err(|_| D::Error::custom(format!("invalid compression strategy {}", s)))
+    }
+}
+
+// Used for the cache clusters to make sure all the keys will finally expire.
+// `Reject` rejects the writes without expiry while
+// `Auto` adds the default ttl to them if possible.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum TtlPolicy {
+    #[default]
+    Disabled,
+    Reject,
+    Auto,
+}
+
+pub struct InvalidTtlPolicyStr;
+
+impl FromStr for TtlPolicy {
+    type Err = InvalidTtlPolicyStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "disabled" => Ok(Self::Disabled),
+            "reject" => Ok(Self::Reject),
+            "auto" => Ok(Self::Auto),
+            _ => Err(InvalidTtlPolicyStr),
+        }
+    }
+}
+
+impl TtlPolicy {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Disabled => "disabled",
+            Self::Reject => "reject",
+            Self::Auto => "auto",
+        }
+    }
+}
+
+impl Serialize for TtlPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TtlPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid ttl policy {}", s)))
+    }
+}
+
+// Where the read commands are sent when the cluster has replicas.
+// `NearestZone` prefers the node on the same host as the proxy.
+// The replicas could return stale data.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ReadPreference {
+    #[default]
+    Primary,
+    ReplicaPreferred,
+    NearestZone,
+}
+
+pub struct InvalidReadPreferenceStr;
+
+impl FromStr for ReadPreference {
+    type Err = InvalidReadPreferenceStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "primary" => Ok(Self::Primary),
+            "replica_preferred" => Ok(Self::ReplicaPreferred),
+            "nearest_zone" => Ok(Self::NearestZone),
+            _ => Err(InvalidReadPreferenceStr),
+        }
+    }
+}
+
+impl ReadPreference {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Primary => "primary",
+            Self::ReplicaPreferred => "replica_preferred",
+            Self::NearestZone => "nearest_zone",
+        }
+    }
+}
+
+impl Serialize for ReadPreference {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ReadPreference {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid read preference {}", s)))
+    }
+}
+
+// `ConsistentHash` routes the keys with a hash ring weighted by the slots of the nodes
+// so that adding nodes only moves a small part of the keys without migrating the data.
+// Only for the cache workloads since the moved keys are lost.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum RoutingMode {
+    #[default]
+    Slots,
+    ConsistentHash,
+}
+
+pub struct InvalidRoutingModeStr;
+
+impl FromStr for RoutingMode {
+    type Err = InvalidRoutingModeStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "slots" => Ok(Self::Slots),
+            "consistent_hash" => Ok(Self::ConsistentHash),
+            _ => Err(InvalidRoutingModeStr),
+        }
+    }
+}
+
+impl RoutingMode {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Slots => "slots",
+            Self::ConsistentHash => "consistent_hash",
+        }
+    }
+}
+
+impl Serialize for RoutingMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RoutingMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid routing mode {}", s)))
+    }
+}
+
+// The commands not in the command table, e.g. the commands of the Redis modules,
+// are routed by the first argument as the key in `Forward`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum UnknownCommandPolicy {
+    #[default]
+    Forward,
+    Reject,
+}
+
+pub struct InvalidUnknownCommandPolicyStr;
+
+impl FromStr for UnknownCommandPolicy {
+    type Err = InvalidUnknownCommandPolicyStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "forward" => Ok(Self::Forward),
+            "reject" => Ok(Self::Reject),
+            _ => Err(InvalidUnknownCommandPolicyStr),
+        }
+    }
+}
+
+impl UnknownCommandPolicy {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Forward => "forward",
+            Self::Reject => "reject",
+        }
+    }
+}
+
+impl Serialize for UnknownCommandPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownCommandPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s)
+            .map_err(|_| D::Error::custom(format!("invalid unknown command policy {}", s)))
+    }
+}
+
+// After a failover, the masters are moved back to the original positions in `Auto`
+// once the new replicas finish syncing. `Manual` waits for the balance masters API.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum FailbackPolicy {
+    #[default]
+    Manual,
+    Auto,
+}
+
+pub struct InvalidFailbackPolicyStr;
+
+impl FromStr for FailbackPolicy {
+    type Err = InvalidFailbackPolicyStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "manual" => Ok(Self::Manual),
+            "auto" => Ok(Self::Auto),
+            _ => Err(InvalidFailbackPolicyStr),
+        }
+    }
+}
+
+impl FailbackPolicy {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Auto => "auto",
+        }
+    }
+}
+
+impl Serialize for FailbackPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for FailbackPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid failback policy {}", s)))
+    }
+}
+
+// The writes are replied after the master replies them in `Master`.
+// `Replica` also waits for any replica of the master to receive them,
+// in the best effort within `replica_ack_timeout` of the server proxy.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum WriteAckPolicy {
+    #[default]
+    Master,
+    Replica,
+}
+
+pub struct InvalidWriteAckPolicyStr;
+
+impl FromStr for WriteAckPolicy {
+    type Err = InvalidWriteAckPolicyStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "master" => Ok(Self::Master),
+            "replica" => Ok(Self::Replica),
+            _ => Err(InvalidWriteAckPolicyStr),
+        }
+    }
+}
+
+impl WriteAckPolicy {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Master => "master",
+            Self::Replica => "replica",
+        }
+    }
+}
+
+impl Serialize for WriteAckPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for WriteAckPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid write ack policy {}", s)))
+    }
+}
+
+// How the commands of the migrating slots are handled in the blocking window
+// before the destination takes over the slots.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum BlockingPolicy {
+    // Wait until the blocking is done. It adds the latency of the switch.
+    #[default]
+    Queue = 0,
+    // Reply TRYAGAIN immediately so that the clients could retry later.
+    Reject = 1,
+    // Redirect to the destination proxy which could redirect it back before its PRESWITCH.
+    Redirect = 2,
+}
+
+pub struct InvalidBlockingPolicyStr;
+
+impl FromStr for BlockingPolicy {
+    type Err = InvalidBlockingPolicyStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "queue" => Ok(Self::Queue),
+            "reject" => Ok(Self::Reject),
+            "redirect" => Ok(Self::Redirect),
+            _ => Err(InvalidBlockingPolicyStr),
+        }
+    }
+}
+
+impl BlockingPolicy {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Queue => "queue",
+            Self::Reject => "reject",
+            Self::Redirect => "redirect",
+        }
+    }
+
+    fn from_u8(n: u8) -> Self {
+        match n {
+            1 => Self::Reject,
+            2 => Self::Redirect,
+            _ => Self::Queue,
+        }
+    }
+}
+
+impl Serialize for BlockingPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockingPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid blocking policy {}", s)))
+    }
+}
+
+// Other RESP servers could be used as backends
+// though they don't support all the commands of Redis.
+// KeyDB is compatible with Redis so it just uses `Redis`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum BackendFlavor {
+    #[default]
+    Redis,
+    Dragonfly,
+}
+
+pub struct InvalidBackendFlavorStr;
+
+impl FromStr for BackendFlavor {
+    type Err = InvalidBackendFlavorStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "redis" => Ok(Self::Redis),
+            "dragonfly" => Ok(Self::Dragonfly),
+            _ => Err(InvalidBackendFlavorStr),
+        }
+    }
+}
+
+impl BackendFlavor {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Redis => "redis",
+            Self::Dragonfly => "dragonfly",
+        }
+    }
+}
+
+impl Serialize for BackendFlavor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for BackendFlavor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid backend flavor {}", s)))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AclCategory {
+    Read,
+    Write,
+    Scripting,
+}
+
+pub struct InvalidAclCategoryStr;
+
+impl FromStr for AclCategory {
+    type Err = InvalidAclCategoryStr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_lowercase();
+        match lowercase.as_str() {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            "scripting" => Ok(Self::Scripting),
+            _ => Err(InvalidAclCategoryStr),
+        }
+    }
+}
+
+impl AclCategory {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Scripting => "scripting",
+        }
+    }
+
+    fn all() -> Vec<Self> {
+        vec![Self::Read, Self::Write, Self::Scripting]
+    }
+}
+
+impl Serialize for AclCategory {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_str())
+    }
 }
 
-impl Default for ClusterConfig {
+impl<'de> Deserialize<'de> for AclCategory {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(|_| D::Error::custom(format!("invalid acl category {}", s)))
+    }
+}
+
+// Both fields are comma separated lists when set from a string,
+// e.g. "read,scripting" and "tenant1:*,shared:*".
+// "all" could be used for all the categories.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AclConfig {
+    pub categories: Vec<AclCategory>,
+    pub key_patterns: Vec<String>,
+}
+
+impl Default for AclConfig {
     fn default() -> Self {
         Self {
-            compression_strategy: CompressionStrategy::default(),
-            migration_config: MigrationConfig::default(),
+            categories: AclCategory::all(),
+            key_patterns: vec!["*".to_string()],
         }
     }
 }
 
-impl ClusterConfig {
-    pub fn set_field(&mut self, field: &str, value: &str) -> Result<(), ConfigError> {
+impl AclConfig {
+    fn set_field(&mut self, field: &str, value: &str) -> Result<(), ConfigError> {
         let field = field.to_lowercase();
         match field.as_str() {
-            "compression_strategy" => {
-                let strategy =
-                    CompressionStrategy::from_str(&value).map_err(|_| ConfigError::InvalidValue)?;
-                self.compression_strategy = strategy;
+            "categories" => {
+                if str_ascii_case_insensitive_eq(value, "all") {
+                    self.categories = AclCategory::all();
+                    return Ok(());
+                }
+                let mut categories = vec![];
+                for s in value.split(',').filter(|s| !s.is_empty()) {
+                    let category =
+                        AclCategory::from_str(s).map_err(|_| ConfigError::InvalidValue)?;
+                    if !categories.contains(&category) {
+                        categories.push(category);
+                    }
+                }
+                if categories.is_empty() {
+                    return Err(ConfigError::InvalidValue);
+                }
+                self.categories = categories;
             }
-            _ => {
-                if field.starts_with("migration_") {
-                    let f = field
-                        .splitn(2, '_')
-                        .nth(1)
-                        .ok_or_else(|| ConfigError::FieldNotFound)?;
-                    return self.migration_config.set_field(f, value);
-                } else {
-                    return Err(ConfigError::FieldNotFound);
+            "key_patterns" => {
+                let patterns: Vec<String> = value
+                    .split(',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect();
+                if patterns.is_empty() {
+                    return Err(ConfigError::InvalidValue);
                 }
+                self.key_patterns = patterns;
             }
+            _ => return Err(ConfigError::FieldNotFound),
         }
         Ok(())
     }
 
-    pub fn to_str_map(&self) -> HashMap<String, String> {
-        vec![
-            (
-                "compression_strategy",
-                self.compression_strategy.to_str().to_string(),
-            ),
-            (
-                "migration_max_migration_time",
-                self.migration_config.max_migration_time.to_string(),
-            ),
-            (
-                "migration_max_blocking_time",
-                self.migration_config.max_blocking_time.to_string(),
-            ),
-            (
-                "migration_scan_interval",
-                self.migration_config.scan_interval.to_string(),
-            ),
-            (
-                "migration_scan_count",
-                self.migration_config.scan_count.to_string(),
-            ),
-        ]
-        .into_iter()
-        .map(|(k, v)| (k.to_string(), v))
-        .collect()
+    pub fn categories_to_str(&self) -> String {
+        if AclCategory::all()
+            .iter()
+            .all(|c| self.categories.contains(c))
+        {
+            return "all".to_string();
+        }
+        self.categories
+            .iter()
+            .map(|c| c.to_str())
+            .collect::<Vec<&str>>()
+            .join(",")
+    }
+
+    pub fn allow_all(&self) -> bool {
+        self.categories.len() == AclCategory::all().len() && self.allow_all_keys()
+    }
+
+    pub fn allow_all_keys(&self) -> bool {
+        self.key_patterns.iter().any(|p| p == "*")
+    }
+
+    pub fn allow_category(&self, category: AclCategory) -> bool {
+        self.categories.contains(&category)
+    }
+
+    pub fn allow_key(&self, key: &[u8]) -> bool {
+        self.allow_all_keys()
+            || self
+                .key_patterns
+                .iter()
+                .any(|p| glob_match(p.as_bytes(), key))
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum CompressionStrategy {
-    Disabled = 0,
-    // Only allow SET, SETEX, PSETEX, SETNX, GET, GETSET , MGET, MSET, MSETNX commands for String data type
-    // as once compression is enabled other commands will get the wrong result.
-    SetGetOnly = 1,
-    // Allow all the String commands. User need to use lua script to
-    // bypass the compression.
-    AllowAll = 2,
+pub const MAX_AUTH_PASSWORDS: usize = 2;
+
+// Keeping two passwords at the same time allows rotating passwords
+// without disconnecting the clients using the old one.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct AuthConfig {
+    pub passwords: Vec<String>,
+    #[serde(default)]
+    pub users: Vec<AuthUser>,
+    // The users not in it could access all the keys.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub user_key_patterns: BTreeMap<String, Vec<String>>,
 }
 
-impl Default for CompressionStrategy {
-    fn default() -> Self {
-        CompressionStrategy::Disabled
+// The user of `AUTH <username> <password>` which could only run the commands of its categories.
+// The ACL of the cluster still applies.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AuthUser {
+    pub name: String,
+    pub password: String,
+    pub categories: Vec<AclCategory>,
+}
+
+impl AuthUser {
+    // <name>:<password>:<categories> where the categories are separated by '|'.
+    // The categories starting with '-' are removed, e.g. "all|-scripting".
+    // The password could contain ':'.
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        let mut name_rest = s.splitn(2, ':');
+        let name = name_rest.next().unwrap_or("");
+        let rest = name_rest.next().ok_or(ConfigError::InvalidValue)?;
+        let mut rest_categories = rest.rsplitn(2, ':');
+        let categories_str = rest_categories.next().unwrap_or("");
+        let password = rest_categories.next().ok_or(ConfigError::InvalidValue)?;
+        if name.is_empty() || password.is_empty() {
+            return Err(ConfigError::InvalidValue);
+        }
+
+        let mut categories = vec![];
+        for s in categories_str.split('|').filter(|s| !s.is_empty()) {
+            let (denied, s) = match s.strip_prefix('-') {
+                Some(s) => (true, s),
+                None => (false, s),
+            };
+            let parsed = if str_ascii_case_insensitive_eq(s, "all") {
+                AclCategory::all()
+            } else {
+                vec![AclCategory::from_str(s).map_err(|_| ConfigError::InvalidValue)?]
+            };
+            for category in parsed.into_iter() {
+                if denied {
+                    categories.retain(|c| *c != category);
+                } else if !categories.contains(&category) {
+                    categories.push(category);
+                }
+            }
+        }
+        if categories.is_empty() {
+            return Err(ConfigError::InvalidValue);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            password: password.to_string(),
+            categories,
+        })
+    }
+
+    fn to_str(&self) -> String {
+        let categories = if AclCategory::all()
+            .iter()
+            .all(|c| self.categories.contains(c))
+        {
+            "all".to_string()
+        } else {
+            self.categories
+                .iter()
+                .map(|c| c.to_str())
+                .collect::<Vec<&str>>()
+                .join("|")
+        };
+        format!("{}:{}:{}", self.name, self.password, categories)
+    }
+
+    pub fn allow_category(&self, category: AclCategory) -> bool {
+        self.categories.contains(&category)
+    }
+
+    pub fn allow_all_categories(&self) -> bool {
+        AclCategory::all()
+            .into_iter()
+            .all(|c| self.allow_category(c))
     }
 }
 
-pub struct InvalidCompressionStr;
+impl AuthConfig {
+    fn set_field(&mut self, field: &str, value: &str) -> Result<(), ConfigError> {
+        let field = field.to_lowercase();
+        match field.as_str() {
+            "passwords" => {
+                let passwords: Vec<String> = value
+                    .split(',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect();
+                if passwords.len() > MAX_AUTH_PASSWORDS {
+                    return Err(ConfigError::InvalidValue);
+                }
+                self.passwords = passwords;
+            }
+            "users" => {
+                let mut users: Vec<AuthUser> = vec![];
+                for s in value.split(',').filter(|s| !s.is_empty()) {
+                    let user = AuthUser::from_str(s)?;
+                    if users.iter().any(|u| u.name == user.name) {
+                        return Err(ConfigError::InvalidValue);
+                    }
+                    users.push(user);
+                }
+                self.users = users;
+            }
+            // <name>:<patterns> where the patterns are separated by '|', e.g. "reader:tenant1:*|shared:*".
+            "user_key_patterns" => {
+                let mut user_key_patterns = BTreeMap::new();
+                for s in value.split(',').filter(|s| !s.is_empty()) {
+                    let mut name_patterns = s.splitn(2, ':');
+                    let name = name_patterns.next().unwrap_or("");
+                    let patterns: Vec<String> = name_patterns
+                        .next()
+                        .unwrap_or("")
+                        .split('|')
+                        .filter(|p| !p.is_empty())
+                        .map(|p| p.to_string())
+                        .collect();
+                    if name.is_empty() || patterns.is_empty() {
+                        return Err(ConfigError::InvalidValue);
+                    }
+                    if user_key_patterns
+                        .insert(name.to_string(), patterns)
+                        .is_some()
+                    {
+                        return Err(ConfigError::InvalidValue);
+                    }
+                }
+                self.user_key_patterns = user_key_patterns;
+            }
+            _ => return Err(ConfigError::FieldNotFound),
+        }
+        Ok(())
+    }
 
-impl FromStr for CompressionStrategy {
-    type Err = InvalidCompressionStr;
+    pub fn password_required(&self) -> bool {
+        !self.passwords.is_empty() || !self.users.is_empty()
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lowercase = s.to_lowercase();
-        match lowercase.as_str() {
-            "disabled" => Ok(Self::Disabled),
-            "set_get_only" => Ok(Self::SetGetOnly),
-            "allow_all" => Ok(Self::AllowAll),
-            _ => Err(InvalidCompressionStr),
+    pub fn get_user(&self, name: &str) -> Option<&AuthUser> {
+        self.users.iter().find(|user| user.name == name)
+    }
+
+    pub fn check_user(&self, name: &str, password: &str) -> bool {
+        match self.get_user(name) {
+            Some(user) => user.password == password,
+            None => false,
         }
     }
-}
 
-impl CompressionStrategy {
-    pub fn to_str(self) -> &'static str {
-        match self {
-            Self::Disabled => "disabled",
-            Self::SetGetOnly => "set_get_only",
-            Self::AllowAll => "allow_all",
+    pub fn users_to_str(&self) -> String {
+        self.users
+            .iter()
+            .map(|user| user.to_str())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    pub fn user_key_patterns_to_str(&self) -> String {
+        self.user_key_patterns
+            .iter()
+            .map(|(name, patterns)| format!("{}:{}", name, patterns.join("|")))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    pub fn allow_user_key(&self, name: &str, key: &[u8]) -> bool {
+        match self.user_key_patterns.get(name) {
+            Some(patterns) => patterns
+                .iter()
+                .any(|p| p == "*" || glob_match(p.as_bytes(), key)),
+            None => true,
         }
     }
-}
 
-impl Serialize for CompressionStrategy {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(self.clone().to_str())
+    pub fn allow_user_all_keys(&self, name: &str) -> bool {
+        match self.user_key_patterns.get(name) {
+            Some(patterns) => patterns.iter().any(|p| p == "*"),
+            None => true,
+        }
+    }
+
+    pub fn check_password(&self, password: &str) -> bool {
+        !self.password_required() || self.passwords.iter().any(|p| p == password)
     }
 }
 
-impl<'de> Deserialize<'de> for CompressionStrategy {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        Self::from_str(&s)
-            .map_err(|_| D::Error::custom(format!("invalid compression strategy {}", s)))
+// Serves a percentage of the slots by another cluster
+// to move the tenants between clusters gradually.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct CanaryConfig {
+    // Empty cluster name means disabled.
+    pub cluster: ClusterName,
+    pub percentage: u64,
+}
+
+impl CanaryConfig {
+    fn set_field(&mut self, field: &str, value: &str) -> Result<(), ConfigError> {
+        let field = field.to_lowercase();
+        match field.as_str() {
+            "cluster" => {
+                let cluster =
+                    ClusterName::try_from(value).map_err(|_| ConfigError::InvalidValue)?;
+                self.cluster = cluster;
+            }
+            "percentage" => {
+                let v = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                if v > 100 {
+                    return Err(ConfigError::InvalidValue);
+                }
+                self.percentage = v;
+            }
+            _ => return Err(ConfigError::FieldNotFound),
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.cluster.as_str().is_empty()
+    }
+
+    // The slots are split by their order so that the hash tags still work.
+    pub fn get_canary_cluster(&self, slot: usize) -> Option<&ClusterName> {
+        if !self.is_enabled() || (slot as u64) * 100 >= self.percentage * (SLOT_NUM as u64) {
+            return None;
+        }
+        Some(&self.cluster)
     }
 }
 
@@ -143,6 +1204,14 @@ pub struct MigrationConfig {
     pub max_blocking_time: u64,
     pub scan_interval: u64,
     pub scan_count: u64,
+    // In bytes per second, shared by all the migration tasks of the same node.
+    // Zero means no limitation.
+    #[serde(default)]
+    pub node_bandwidth: u64,
+    #[serde(default)]
+    pub read_blocking_policy: BlockingPolicy,
+    #[serde(default)]
+    pub write_blocking_policy: BlockingPolicy,
 }
 
 impl MigrationConfig {
@@ -176,6 +1245,20 @@ impl MigrationConfig {
                 }
                 self.scan_count = v;
             }
+            "node_bandwidth" => {
+                let v = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                self.node_bandwidth = v;
+            }
+            "read_blocking_policy" => {
+                self.read_blocking_policy =
+                    BlockingPolicy::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+            }
+            "write_blocking_policy" => {
+                self.write_blocking_policy =
+                    BlockingPolicy::from_str(value).map_err(|_| ConfigError::InvalidValue)?;
+            }
             _ => return Err(ConfigError::FieldNotFound),
         }
         Ok(())
@@ -189,6 +1272,9 @@ impl Default for MigrationConfig {
             max_blocking_time: 10_000,       // 10 seconds waiting for switch
             scan_interval: 500,              // 500 microseconds
             scan_count: 16,
+            node_bandwidth: 0,
+            read_blocking_policy: BlockingPolicy::default(),
+            write_blocking_policy: BlockingPolicy::default(),
         }
     }
 }
@@ -198,6 +1284,9 @@ pub struct AtomicMigrationConfig {
     max_blocking_time: AtomicU64,
     scan_interval: AtomicU64,
     scan_count: AtomicU64,
+    node_bandwidth: AtomicU64,
+    read_blocking_policy: AtomicU8,
+    write_blocking_policy: AtomicU8,
 }
 
 impl Default for AtomicMigrationConfig {
@@ -213,6 +1302,9 @@ impl AtomicMigrationConfig {
             max_blocking_time: AtomicU64::new(config.max_blocking_time),
             scan_interval: AtomicU64::new(config.scan_interval),
             scan_count: AtomicU64::new(config.scan_count),
+            node_bandwidth: AtomicU64::new(config.node_bandwidth),
+            read_blocking_policy: AtomicU8::new(config.read_blocking_policy as u8),
+            write_blocking_policy: AtomicU8::new(config.write_blocking_policy as u8),
         }
     }
 
@@ -231,6 +1323,19 @@ impl AtomicMigrationConfig {
     pub fn get_scan_count(&self) -> u64 {
         self.scan_count.load(Ordering::SeqCst)
     }
+
+    pub fn get_node_bandwidth(&self) -> u64 {
+        self.node_bandwidth.load(Ordering::SeqCst)
+    }
+
+    pub fn get_blocking_policy(&self, is_read: bool) -> BlockingPolicy {
+        let policy = if is_read {
+            &self.read_blocking_policy
+        } else {
+            &self.write_blocking_policy
+        };
+        BlockingPolicy::from_u8(policy.load(Ordering::SeqCst))
+    }
 }
 
 #[derive(Debug)]
@@ -265,5 +1370,276 @@ mod tests {
             .set_field("migration_scan_count", "666")
             .unwrap();
         assert_eq!(cluster_config.migration_config.scan_count, 666);
+        cluster_config
+            .set_field("migration_node_bandwidth", "1048576")
+            .unwrap();
+        assert_eq!(cluster_config.migration_config.node_bandwidth, 1048576);
+        cluster_config
+            .set_field("migration_write_blocking_policy", "Reject")
+            .unwrap();
+        assert_eq!(
+            cluster_config.migration_config.write_blocking_policy,
+            BlockingPolicy::Reject
+        );
+        assert_eq!(
+            cluster_config.migration_config.read_blocking_policy,
+            BlockingPolicy::Queue
+        );
+        assert!(cluster_config
+            .set_field("migration_read_blocking_policy", "drop")
+            .is_err());
+        let atomic_config =
+            AtomicMigrationConfig::from_config(cluster_config.migration_config.clone());
+        assert_eq!(
+            atomic_config.get_blocking_policy(false),
+            BlockingPolicy::Reject
+        );
+        assert_eq!(
+            atomic_config.get_blocking_policy(true),
+            BlockingPolicy::Queue
+        );
+
+        cluster_config
+            .set_field("backend_flavor", "Dragonfly")
+            .unwrap();
+        assert_eq!(cluster_config.backend_flavor, BackendFlavor::Dragonfly);
+        assert!(cluster_config
+            .set_field("backend_flavor", "memcached")
+            .is_err());
+
+        cluster_config.set_field("max_key_size", "1024").unwrap();
+        assert_eq!(cluster_config.max_key_size, 1024);
+        cluster_config
+            .set_field("MAX_VALUE_SIZE", "1048576")
+            .unwrap();
+        assert_eq!(cluster_config.max_value_size, 1048576);
+        assert!(cluster_config.set_field("max_value_size", "-1").is_err());
+
+        cluster_config.set_field("ttl_policy", "Auto").unwrap();
+        assert_eq!(cluster_config.ttl_policy, TtlPolicy::Auto);
+        cluster_config.set_field("default_ttl", "3600").unwrap();
+        assert_eq!(cluster_config.default_ttl, 3600);
+        assert!(cluster_config.set_field("ttl_policy", "forever").is_err());
+
+        cluster_config
+            .set_field("read_preference", "Replica_Preferred")
+            .unwrap();
+        assert_eq!(
+            cluster_config.read_preference,
+            ReadPreference::ReplicaPreferred
+        );
+        assert!(cluster_config
+            .set_field("read_preference", "secondary")
+            .is_err());
+        cluster_config.set_field("max_replica_lag", "10").unwrap();
+        assert_eq!(cluster_config.max_replica_lag, 10);
+
+        assert_eq!(cluster_config.routing_mode, RoutingMode::Slots);
+        cluster_config
+            .set_field("routing_mode", "Consistent_Hash")
+            .unwrap();
+        assert_eq!(cluster_config.routing_mode, RoutingMode::ConsistentHash);
+        assert!(cluster_config.set_field("routing_mode", "ketama").is_err());
+
+        assert_eq!(
+            cluster_config.unknown_command_policy,
+            UnknownCommandPolicy::Forward
+        );
+        cluster_config
+            .set_field("unknown_command_policy", "Reject")
+            .unwrap();
+        assert_eq!(
+            cluster_config.unknown_command_policy,
+            UnknownCommandPolicy::Reject
+        );
+        assert!(cluster_config
+            .set_field("unknown_command_policy", "random")
+            .is_err());
+
+        assert_eq!(cluster_config.failback_policy, FailbackPolicy::Manual);
+        cluster_config.set_field("failback_policy", "AUTO").unwrap();
+        assert_eq!(cluster_config.failback_policy, FailbackPolicy::Auto);
+        assert!(cluster_config
+            .set_field("failback_policy", "never")
+            .is_err());
+
+        assert_eq!(cluster_config.write_ack_policy, WriteAckPolicy::Master);
+        cluster_config
+            .set_field("write_ack_policy", "Replica")
+            .unwrap();
+        assert_eq!(cluster_config.write_ack_policy, WriteAckPolicy::Replica);
+        assert!(cluster_config.set_field("write_ack_policy", "all").is_err());
+    }
+
+    #[test]
+    fn test_acl_config() {
+        let mut cluster_config = ClusterConfig::default();
+        assert!(cluster_config.acl.allow_all());
+        assert_eq!(cluster_config.acl.categories_to_str(), "all");
+
+        cluster_config.set_field("acl_categories", "read").unwrap();
+        cluster_config
+            .set_field("acl_key_patterns", "tenant1:*,shared")
+            .unwrap();
+        assert!(!cluster_config.acl.allow_all());
+        assert!(cluster_config.acl.allow_category(AclCategory::Read));
+        assert!(!cluster_config.acl.allow_category(AclCategory::Write));
+        assert!(cluster_config.acl.allow_key(b"tenant1:key"));
+        assert!(cluster_config.acl.allow_key(b"shared"));
+        assert!(!cluster_config.acl.allow_key(b"tenant2:key"));
+
+        let map = cluster_config.to_str_map();
+        assert_eq!(map.get("acl_categories").unwrap(), "read");
+        assert_eq!(map.get("acl_key_patterns").unwrap(), "tenant1:*,shared");
+
+        assert!(cluster_config.set_field("acl_categories", "admin").is_err());
+        assert!(cluster_config.set_field("acl_key_patterns", ",").is_err());
+    }
+
+    #[test]
+    fn test_auth_config() {
+        let mut cluster_config = ClusterConfig::default();
+        assert!(!cluster_config.auth.password_required());
+        assert!(cluster_config.auth.check_password("any"));
+        assert!(!cluster_config.to_str_map().contains_key("auth_passwords"));
+
+        cluster_config
+            .set_field("auth_passwords", "newpwd,oldpwd")
+            .unwrap();
+        assert!(cluster_config.auth.check_password("newpwd"));
+        assert!(cluster_config.auth.check_password("oldpwd"));
+        assert!(!cluster_config.auth.check_password("any"));
+        assert_eq!(
+            cluster_config.to_str_map().get("auth_passwords").unwrap(),
+            "newpwd,oldpwd"
+        );
+
+        assert!(cluster_config.set_field("auth_passwords", "a,b,c").is_err());
+        cluster_config.set_field("auth_passwords", "").unwrap();
+        assert!(!cluster_config.auth.password_required());
+
+        cluster_config
+            .set_field("auth_users", "alice:pass:word:read|write,bob:bobpwd:all")
+            .unwrap();
+        assert!(cluster_config.auth.password_required());
+        assert!(cluster_config.auth.check_user("alice", "pass:word"));
+        assert!(!cluster_config.auth.check_user("alice", "bobpwd"));
+        assert!(!cluster_config.auth.check_user("carol", "pass:word"));
+        let alice = cluster_config.auth.get_user("alice").unwrap();
+        assert!(alice.allow_category(AclCategory::Write));
+        assert!(!alice.allow_category(AclCategory::Scripting));
+        assert_eq!(
+            cluster_config.to_str_map().get("auth_users").unwrap(),
+            "alice:pass:word:read|write,bob:bobpwd:all"
+        );
+
+        assert!(cluster_config.set_field("auth_users", "alice:pwd").is_err());
+        assert!(cluster_config.set_field("auth_users", ":pwd:all").is_err());
+        assert!(cluster_config
+            .set_field("auth_users", "alice:pwd:admin")
+            .is_err());
+        assert!(cluster_config
+            .set_field("auth_users", "alice:pwd:all,alice:pwd2:read")
+            .is_err());
+        cluster_config.set_field("auth_users", "").unwrap();
+        assert!(!cluster_config.auth.password_required());
+
+        cluster_config
+            .set_field("auth_users", "carol:pwd:all|-scripting")
+            .unwrap();
+        let carol = cluster_config.auth.get_user("carol").unwrap();
+        assert!(carol.allow_category(AclCategory::Write));
+        assert!(!carol.allow_category(AclCategory::Scripting));
+        assert_eq!(
+            cluster_config.to_str_map().get("auth_users").unwrap(),
+            "carol:pwd:read|write"
+        );
+        assert!(cluster_config
+            .set_field("auth_users", "carol:pwd:read|-read")
+            .is_err());
+    }
+
+    #[test]
+    fn test_user_key_patterns_config() {
+        let mut cluster_config = ClusterConfig::default();
+        assert!(!cluster_config
+            .to_str_map()
+            .contains_key("auth_user_key_patterns"));
+        cluster_config
+            .set_field(
+                "auth_user_key_patterns",
+                "reader:tenant1:*|shared:*,admin:*",
+            )
+            .unwrap();
+        let auth = &cluster_config.auth;
+        assert!(auth.allow_user_key("reader", b"tenant1:key"));
+        assert!(auth.allow_user_key("reader", b"shared:key"));
+        assert!(!auth.allow_user_key("reader", b"tenant2:key"));
+        assert!(!auth.allow_user_all_keys("reader"));
+        assert!(auth.allow_user_all_keys("admin"));
+        assert!(auth.allow_user_key("other", b"tenant2:key"));
+        assert_eq!(
+            cluster_config
+                .to_str_map()
+                .get("auth_user_key_patterns")
+                .unwrap(),
+            "admin:*,reader:tenant1:*|shared:*"
+        );
+
+        assert!(cluster_config
+            .set_field("auth_user_key_patterns", "reader:")
+            .is_err());
+        assert!(cluster_config
+            .set_field("auth_user_key_patterns", "reader:a*,reader:b*")
+            .is_err());
+        cluster_config
+            .set_field("auth_user_key_patterns", "")
+            .unwrap();
+        assert!(cluster_config.auth.user_key_patterns.is_empty());
+    }
+
+    #[test]
+    fn test_canary_config() {
+        let mut cluster_config = ClusterConfig::default();
+        assert!(!cluster_config.canary.is_enabled());
+        assert!(cluster_config.canary.get_canary_cluster(0).is_none());
+        assert!(!cluster_config.to_str_map().contains_key("canary_cluster"));
+
+        cluster_config
+            .set_field("canary_cluster", "newcluster")
+            .unwrap();
+        assert!(cluster_config.canary.get_canary_cluster(0).is_none());
+        cluster_config.set_field("canary_percentage", "25").unwrap();
+        assert_eq!(
+            cluster_config
+                .canary
+                .get_canary_cluster(0)
+                .unwrap()
+                .as_str(),
+            "newcluster"
+        );
+        assert!(cluster_config.canary.get_canary_cluster(4095).is_some());
+        assert!(cluster_config.canary.get_canary_cluster(4096).is_none());
+        assert_eq!(
+            cluster_config
+                .to_str_map()
+                .get("canary_percentage")
+                .unwrap(),
+            "25"
+        );
+
+        cluster_config
+            .set_field("canary_percentage", "100")
+            .unwrap();
+        assert!(cluster_config
+            .canary
+            .get_canary_cluster(SLOT_NUM - 1)
+            .is_some());
+        assert!(cluster_config
+            .set_field("canary_percentage", "101")
+            .is_err());
+        assert!(cluster_config
+            .set_field("canary_cluster", "invalid name")
+            .is_err());
     }
 }
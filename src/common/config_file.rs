@@ -0,0 +1,152 @@
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ConfigFileError {
+    // Includes the unknown fields and the fields with invalid types.
+    Load(config::ConfigError),
+    InvalidField { field: String, error: String },
+}
+
+impl ConfigFileError {
+    pub fn invalid_field(field: &str, error: &str) -> Self {
+        Self::InvalidField {
+            field: field.to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Load(err) => write!(f, "{}", err),
+            Self::InvalidField { field, error } => write!(f, "invalid field {}: {}", field, error),
+        }
+    }
+}
+
+impl Error for ConfigFileError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match self {
+            Self::Load(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// Both `["a", "b"]` and `"a,b"` are supported since the env vars could only be strings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StringList {
+    List(Vec<String>),
+    Str(String),
+}
+
+impl Default for StringList {
+    fn default() -> Self {
+        Self::List(vec![])
+    }
+}
+
+impl StringList {
+    pub fn into_vec(self) -> Vec<String> {
+        let list = match self {
+            Self::List(list) => list,
+            Self::Str(s) => s.split(',').map(|s| s.to_string()).collect(),
+        };
+        list.into_iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+}
+
+// The priority from low to high: the config file, `extra`, the env vars with the prefix `UNDERMOON_`,
+// the command line arguments in `cli`.
+// The format of the config file is decided by the extension, e.g. .toml, .yaml or .json.
+pub fn load_config<T: DeserializeOwned>(
+    conf_file_path: Option<&str>,
+    extra: Option<config::Config>,
+    cli: &HashMap<String, String>,
+) -> Result<T, ConfigFileError> {
+    let mut s = config::Config::new();
+    if let Some(conf_file_path) = conf_file_path {
+        s.merge(config::File::with_name(conf_file_path))
+            .map_err(ConfigFileError::Load)?;
+    }
+    if let Some(extra) = extra {
+        s.merge(extra).map_err(ConfigFileError::Load)?;
+    }
+    // e.g. UNDERMOON_ADDRESS='127.0.0.1:5299'
+    s.merge(config::Environment::with_prefix("undermoon"))
+        .map_err(ConfigFileError::Load)?;
+    s.merge(config_from_map(cli)?)
+        .map_err(ConfigFileError::Load)?;
+    parse_config(s)
+}
+
+pub fn config_from_map(
+    fields: &HashMap<String, String>,
+) -> Result<config::Config, ConfigFileError> {
+    let mut s = config::Config::new();
+    for (field, value) in fields.iter() {
+        s.set(field, value.as_str())
+            .map_err(ConfigFileError::Load)?;
+    }
+    Ok(s)
+}
+
+// The types should reject the unknown fields by `#[serde(deny_unknown_fields)]`.
+pub fn parse_config<T: DeserializeOwned>(s: config::Config) -> Result<T, ConfigFileError> {
+    s.try_into().map_err(ConfigFileError::Load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct TestingConfig {
+        address: String,
+        conn_num: usize,
+        enabled: bool,
+        addresses: StringList,
+    }
+
+    fn parse_str(s: &str, format: config::FileFormat) -> Result<TestingConfig, ConfigFileError> {
+        let mut c = config::Config::new();
+        c.merge(config::File::from_str(s, format))
+            .map_err(ConfigFileError::Load)?;
+        parse_config(c)
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let toml = r#"
+            address = "127.0.0.1:5299"
+            conn_num = 4
+            addresses = ["a", "b"]
+        "#;
+        let c = parse_str(toml, config::FileFormat::Toml).unwrap();
+        assert_eq!(c.address, "127.0.0.1:5299");
+        assert_eq!(c.conn_num, 4);
+        assert!(!c.enabled);
+        assert_eq!(c.addresses.into_vec(), vec!["a", "b"]);
+
+        let yaml = "conn_num: 4\nenabled: true\naddresses: 'a, b,'\n";
+        let c = parse_str(yaml, config::FileFormat::Yaml).unwrap();
+        assert!(c.enabled);
+        assert_eq!(c.addresses.into_vec(), vec!["a", "b"]);
+
+        let json = r#"{"conn_num": "4", "address": "127.0.0.1:5299"}"#;
+        let c = parse_str(json, config::FileFormat::Json).unwrap();
+        assert_eq!(c.conn_num, 4);
+
+        assert!(parse_str("unknown_field = 1", config::FileFormat::Toml).is_err());
+        assert!(parse_str("conn_num = \"four\"", config::FileFormat::Toml).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use core::pin::Pin;
+use futures::stream::{Fuse, FusedStream, Stream};
+use futures::task::{Context, Poll};
+use futures::StreamExt;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+// At most this many items are moved from the underlying stream in each poll
+// so that a large burst can't keep it from yielding.
+const MAX_DRAIN_NUM: usize = 4096;
+
+// Interleaves the ready items of different keys in a round-robin way,
+// while the items of the same key keep their order.
+// This prevents a large pipeline of one session from delaying the small requests of the others.
+pub trait FairQueueStreamExt: Stream {
+    fn fair_queue<K, F>(self, key_of: F) -> FairQueue<Self, K, F>
+    where
+        Self: Sized,
+        K: Hash + Eq + Clone,
+        F: Fn(&Self::Item) -> K,
+    {
+        FairQueue::new(self, key_of)
+    }
+}
+impl<T: ?Sized> FairQueueStreamExt for T where T: Stream {}
+
+// The stream is boxed so that none of the fields needs a pin projection.
+#[must_use = "streams do nothing unless polled"]
+pub struct FairQueue<St: Stream, K, F> {
+    stream: Pin<Box<Fuse<St>>>,
+    key_of: F,
+    queues: HashMap<K, VecDeque<St::Item>>,
+    // The keys with queued items in the round-robin order.
+    order: VecDeque<K>,
+}
+
+impl<St: Stream, K, F> Unpin for FairQueue<St, K, F> {}
+
+impl<St, K, F> FairQueue<St, K, F>
+where
+    St: Stream,
+    K: Hash + Eq + Clone,
+    F: Fn(&St::Item) -> K,
+{
+    pub fn new(stream: St, key_of: F) -> Self {
+        Self {
+            stream: Box::pin(stream.fuse()),
+            key_of,
+            queues: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn queued_num(&self) -> usize {
+        self.queues.values().map(|queue| queue.len()).sum()
+    }
+}
+
+impl<St, K, F> Stream for FairQueue<St, K, F>
+where
+    St: Stream,
+    K: Hash + Eq + Clone,
+    F: Fn(&St::Item) -> K,
+{
+    type Item = St::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut terminated = false;
+        for _ in 0..MAX_DRAIN_NUM {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    let key = (this.key_of)(&item);
+                    let queue = this.queues.entry(key.clone()).or_default();
+                    if queue.is_empty() {
+                        this.order.push_back(key);
+                    }
+                    queue.push_back(item);
+                }
+                Poll::Ready(None) => {
+                    terminated = true;
+                    break;
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        let key = match this.order.pop_front() {
+            Some(key) => key,
+            None if terminated => return Poll::Ready(None),
+            None => return Poll::Pending,
+        };
+        let queue = this.queues.get_mut(&key).expect("FairQueue::poll_next");
+        let item = queue.pop_front();
+        if queue.is_empty() {
+            this.queues.remove(&key);
+        } else {
+            this.order.push_back(key);
+        }
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let queued = self.queued_num();
+        let (lower, upper) = self.stream.size_hint();
+        (
+            lower.saturating_add(queued),
+            upper.and_then(|upper| upper.checked_add(queued)),
+        )
+    }
+}
+
+impl<St, K, F> FusedStream for FairQueue<St, K, F>
+where
+    St: Stream,
+    K: Hash + Eq + Clone,
+    F: Fn(&St::Item) -> K,
+{
+    fn is_terminated(&self) -> bool {
+        self.stream.is_terminated() && self.order.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn test_fair_queue() {
+        let items = vec![(1, 0), (1, 1), (1, 2), (1, 3), (2, 0), (3, 0), (2, 1)];
+        let results: Vec<(usize, usize)> = stream::iter(items)
+            .fair_queue(|item| item.0)
+            .collect()
+            .await;
+        assert_eq!(
+            results,
+            vec![(1, 0), (2, 0), (3, 0), (1, 1), (2, 1), (1, 2), (1, 3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fair_queue_pending() {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let mut queue = rx.fair_queue(|item: &(usize, usize)| item.0);
+        for i in 0..3 {
+            tx.unbounded_send((1, i)).unwrap();
+        }
+        assert_eq!(queue.next().await, Some((1, 0)));
+        // The new key doesn't need to wait for all the queued items of the previous one.
+        tx.unbounded_send((2, 0)).unwrap();
+        assert_eq!(queue.next().await, Some((1, 1)));
+        assert_eq!(queue.next().await, Some((2, 0)));
+        assert_eq!(queue.next().await, Some((1, 2)));
+        drop(tx);
+        assert_eq!(queue.next().await, None);
+        assert!(queue.is_terminated());
+    }
+}
@@ -1,7 +1,17 @@
+pub mod allocator;
 pub mod batch;
+pub mod cli;
+// The impls generated by the derive macros of the pinned serde_derive are non-local.
+#[allow(non_local_definitions)]
 pub mod cluster;
+pub mod compact;
+#[allow(non_local_definitions)]
 pub mod config;
+#[allow(non_local_definitions)]
+pub mod config_file;
+pub mod fair_queue;
 pub mod future_group;
+pub mod platform;
 pub mod proto;
 pub mod resp_execution;
 pub mod response;
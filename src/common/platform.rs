@@ -0,0 +1,69 @@
+use futures::future;
+use socket2::Socket;
+use std::io;
+
+// Resolves on SIGTERM or SIGINT.
+#[cfg(unix)]
+pub async fn wait_shutdown_signal() -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let ctrl_c = Box::pin(tokio::signal::ctrl_c());
+    let res = match future::select(Box::pin(terminate.recv()), ctrl_c).await {
+        future::Either::Left(_) => Ok(()),
+        future::Either::Right((res, _)) => res,
+    };
+    res
+}
+
+// Resolves on CTRL_C_EVENT or CTRL_BREAK_EVENT from the console ctrl handler.
+#[cfg(not(unix))]
+pub async fn wait_shutdown_signal() -> io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+// `SO_REUSEADDR` on Windows allows stealing the port from a running process
+// so it's only set on unix.
+pub fn set_reuse_address(socket: &Socket) -> io::Result<()> {
+    if cfg!(unix) {
+        socket.set_reuse_address(true)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+// The thread-per-core mode can't share the same address without `SO_REUSEPORT`.
+#[cfg(not(unix))]
+pub fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use socket2::{Domain, SockAddr, Type};
+    use std::net::SocketAddr;
+
+    fn bind(address: SocketAddr) -> io::Result<Socket> {
+        let socket = Socket::new(Domain::ipv4(), Type::stream(), None)?;
+        set_reuse_address(&socket)?;
+        set_reuse_port(&socket)?;
+        socket.bind(&SockAddr::from(address))?;
+        socket.listen(16)?;
+        Ok(socket)
+    }
+
+    #[test]
+    fn test_reuse_port() {
+        let first = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = first.local_addr().unwrap().as_inet().unwrap();
+        assert!(bind(SocketAddr::V4(address)).is_ok());
+    }
+}
@@ -1,27 +1,32 @@
-use super::cluster::SlotRange;
-use super::utils::{has_flags, CmdParseError};
+use super::cluster::{
+    check_slot_owners, check_slot_ranges_bound, merge_slot_ranges, SlotRange, SlotRangeError,
+    SlotRangeTag,
+};
+use super::compact::{CompactDecoder, CompactEncoder};
+use super::utils::{has_flags, parse_args, resp_array_to_args, CmdParseError};
 use crate::common::cluster::ClusterName;
 use crate::common::config::ClusterConfig;
-use crate::protocol::{Array, BulkStr, Resp};
-use std::collections::HashMap;
+use crate::protocol::{Array, BinSafeStr, BulkStr, Resp, RespVec};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::fmt;
 use std::iter::Peekable;
 use std::str;
 
 macro_rules! try_parse {
-    ($expression:expr) => {{
+    ($expression:expr, $expected:expr) => {{
         match $expression {
             Ok(v) => (v),
-            Err(_) => return Err(CmdParseError {}),
+            Err(_) => return Err(CmdParseError::invalid($expected)),
         }
     }};
 }
 
 macro_rules! try_get {
-    ($expression:expr) => {{
+    ($expression:expr, $expected:expr) => {{
         match $expression {
             Some(v) => (v),
-            None => return Err(CmdParseError {}),
+            None => return Err(CmdParseError::missing($expected)),
         }
     }};
 }
@@ -46,8 +51,126 @@ impl ClusterMapFlags {
     }
 }
 
+// Advertised by `UMCTL CAPABILITIES` for the rolling upgrades.
+pub const FEATURE_FORCE_RESYNC: &str = "force_resync";
+pub const FEATURE_REPL_DIFF: &str = "repl_diff";
+pub const FEATURE_REPL_SYNC_STATE: &str = "repl_sync_state";
+pub const FEATURE_COMPACT_META: &str = "compact_meta";
+pub const PROXY_FEATURES: [&str; 4] = [
+    FEATURE_FORCE_RESYNC,
+    FEATURE_REPL_DIFF,
+    FEATURE_REPL_SYNC_STATE,
+    FEATURE_COMPACT_META,
+];
+
+// The sub-commands of the proxies without `UMCTL CAPABILITIES`.
+const LEGACY_UMCTL_COMMANDS: [&str; 10] = [
+    "LISTCLUSTER",
+    "SETCLUSTER",
+    "SETREPL",
+    "INFO",
+    "INFOREPL",
+    "INFOMGR",
+    "PRECHECK",
+    "PRESWITCH",
+    "FINALSWITCH",
+    "SLOWLOG",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyCapabilities {
+    // Empty for the proxies without `UMCTL CAPABILITIES`.
+    pub version: String,
+    pub commands: Vec<String>,
+    pub features: Vec<String>,
+}
+
+impl ProxyCapabilities {
+    pub fn legacy() -> Self {
+        Self {
+            version: String::new(),
+            commands: LEGACY_UMCTL_COMMANDS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            features: vec![],
+        }
+    }
+
+    pub fn supports_command(&self, sub_command: &str) -> bool {
+        self.commands
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(sub_command))
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    pub fn to_resp(&self) -> RespVec {
+        let lines = vec![
+            format!("version: {}", self.version),
+            format!("commands: {}", self.commands.join(" ")),
+            format!("features: {}", self.features.join(" ")),
+        ];
+        Resp::Arr(Array::Arr(
+            lines
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.into_bytes())))
+                .collect(),
+        ))
+    }
+
+    // The unknown fields are ignored so that more fields could be added later.
+    pub fn from_resp(resp: &RespVec) -> Result<Self, CmdParseError> {
+        let lines = match resp {
+            Resp::Arr(Array::Arr(lines)) => lines,
+            _ => return Err(CmdParseError::invalid("array")),
+        };
+        let mut capabilities = Self {
+            version: String::new(),
+            commands: vec![],
+            features: vec![],
+        };
+        for line in lines.iter() {
+            let line = match line {
+                Resp::Bulk(BulkStr::Str(s)) => try_parse!(str::from_utf8(s), "utf-8 string"),
+                _ => return Err(CmdParseError::invalid("bulk string")),
+            };
+            let mut kv = line.splitn(2, ':');
+            let key = try_get!(kv.next(), "key").trim();
+            let value = try_get!(kv.next(), "key: value").trim();
+            let values = value.split_whitespace().map(|s| s.to_string()).collect();
+            match key {
+                "version" => capabilities.version = value.to_string(),
+                "commands" => capabilities.commands = values,
+                "features" => capabilities.features = values,
+                _ => (),
+            }
+        }
+        Ok(capabilities)
+    }
+}
+
 const PEER_PREFIX: &str = "PEER";
 const CONFIG_PREFIX: &str = "CONFIG";
+const COMPACT_PREFIX: &str = "COMPACT";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidSlotRanges {
+    pub cluster_name: ClusterName,
+    pub error: SlotRangeError,
+}
+
+impl fmt::Display for InvalidSlotRanges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid slot ranges in cluster {}: {}",
+            self.cluster_name, self.error
+        )
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ProxyClusterMeta {
@@ -99,20 +222,62 @@ impl ProxyClusterMeta {
     ) -> Result<(Self, Result<(), ParseExtendedMetaError>), CmdParseError> {
         let arr = match resp {
             Resp::Arr(Array::Arr(ref arr)) => arr,
-            _ => return Err(CmdParseError {}),
+            _ => return Err(CmdParseError::invalid("array")),
         };
 
+        let is_compact = match arr.get(4) {
+            Some(Resp::Bulk(BulkStr::Str(s))) => {
+                s.as_ref().eq_ignore_ascii_case(COMPACT_PREFIX.as_bytes())
+            }
+            _ => false,
+        };
+        if is_compact {
+            return Self::from_compact_resp(arr);
+        }
+
         // Skip the "UMCTL SETCLUSTER"
-        let it = arr.iter().skip(2).flat_map(|resp| match resp {
-            Resp::Bulk(BulkStr::Str(safe_str)) => match str::from_utf8(safe_str.as_ref()) {
-                Ok(s) => Some(s.to_string()),
-                _ => None,
-            },
-            _ => None,
-        });
-        let mut it = it.peekable();
+        let args = resp_array_to_args(arr, 2)?;
+        parse_args(args.into_iter(), 2, Self::parse)
+    }
 
-        Self::parse(&mut it)
+    // UMCTL SETCLUSTER epoch flags COMPACT payload [CONFIG ...]
+    // The payload contains both the local and the peer cluster maps.
+    fn from_compact_resp<T: AsRef<[u8]>>(
+        arr: &[Resp<T>],
+    ) -> Result<(Self, Result<(), ParseExtendedMetaError>), CmdParseError> {
+        let invalid_payload = CmdParseError {
+            position: Some(5),
+            ..CmdParseError::invalid("compact payload")
+        };
+        let mut args = resp_array_to_args(&arr[..4], 2)?.into_iter();
+        let epoch = args.next().and_then(|epoch| epoch.parse::<u64>().ok());
+        let epoch = epoch.ok_or_else(|| CmdParseError {
+            position: Some(2),
+            ..CmdParseError::invalid("epoch")
+        })?;
+        let flags = ClusterMapFlags::from_arg(&args.next().unwrap_or_default());
+
+        let payload = match arr.get(5) {
+            Some(Resp::Bulk(BulkStr::Str(payload))) => payload.as_ref(),
+            _ => return Err(invalid_payload),
+        };
+        let mut decoder = CompactDecoder::new(payload);
+        let local = decoder
+            .get_cluster_map()
+            .map_err(|_| invalid_payload.clone())?;
+        let peer = decoder
+            .get_cluster_map()
+            .map_err(|_| invalid_payload.clone())?;
+        if !decoder.is_empty() {
+            return Err(invalid_payload);
+        }
+        let local = ProxyClusterMap::new(local);
+        let peer = ProxyClusterMap::new(peer);
+
+        let args = resp_array_to_args(arr, 6)?;
+        parse_args(args.into_iter(), 6, |it| {
+            Self::parse_extended(epoch, flags, local, peer, it)
+        })
     }
 
     pub fn parse<It>(
@@ -121,13 +286,26 @@ impl ProxyClusterMeta {
     where
         It: Iterator<Item = String>,
     {
-        let epoch_str = try_get!(it.next());
-        let epoch = try_parse!(epoch_str.parse::<u64>());
+        let epoch_str = try_get!(it.next(), "epoch");
+        let epoch = try_parse!(epoch_str.parse::<u64>(), "epoch");
 
-        let flags = ClusterMapFlags::from_arg(&try_get!(it.next()));
+        let flags = ClusterMapFlags::from_arg(&try_get!(it.next(), "flags"));
 
         let local = ProxyClusterMap::parse(it)?;
-        let mut peer = ProxyClusterMap::new(HashMap::new());
+        let peer = ProxyClusterMap::new(HashMap::new());
+        Self::parse_extended(epoch, flags, local, peer, it)
+    }
+
+    fn parse_extended<It>(
+        epoch: u64,
+        flags: ClusterMapFlags,
+        local: ProxyClusterMap,
+        mut peer: ProxyClusterMap,
+        it: &mut Peekable<It>,
+    ) -> Result<(Self, Result<(), ParseExtendedMetaError>), CmdParseError>
+    where
+        It: Iterator<Item = String>,
+    {
         let mut clusters_config = ClusterConfigMap::default();
         let mut extended_meta_result = Ok(());
 
@@ -136,16 +314,16 @@ impl ProxyClusterMeta {
                 PEER_PREFIX => peer = ProxyClusterMap::parse(it)?,
                 CONFIG_PREFIX => match ClusterConfigMap::parse(it) {
                     Ok(c) => clusters_config = c,
-                    Err(_) => {
+                    Err(err) => {
                         if local.get_map().is_empty() || peer.get_map().is_empty() {
-                            return Err(CmdParseError {});
+                            return Err(err);
                         } else {
                             error!("invalid cluster config from UMCTL SETCLUSTER but the local and peer metadata are complete. Ignore this error to protect the core functionality.");
                             extended_meta_result = Err(ParseExtendedMetaError {})
                         }
                     }
                 },
-                _ => return Err(CmdParseError {}),
+                _ => return Err(CmdParseError::invalid("PEER or CONFIG")),
             }
         }
 
@@ -177,6 +355,83 @@ impl ProxyClusterMeta {
         }
         args
     }
+
+    pub fn to_compact_args(&self) -> Vec<BinSafeStr> {
+        let mut encoder = CompactEncoder::default();
+        encoder.put_cluster_map(self.local.get_map());
+        encoder.put_cluster_map(self.peer.get_map());
+        let mut args = vec![
+            self.epoch.to_string().into_bytes(),
+            self.flags.to_arg().into_bytes(),
+            COMPACT_PREFIX.to_string().into_bytes(),
+            encoder.into_bytes(),
+        ];
+        let config = self.clusters_config.to_args();
+        if !config.is_empty() {
+            args.push(CONFIG_PREFIX.to_string().into_bytes());
+            args.extend(config.into_iter().map(String::into_bytes));
+        }
+        args
+    }
+
+    pub fn get_slot_range_num(&self) -> usize {
+        self.local.get_slot_range_num() + self.peer.get_slot_range_num()
+    }
+
+    // Merges the slot ranges of the same nodes and checks that
+    // every slot is only owned by one node, excluding the importing ones.
+    // All the slot ranges should be within SLOT_NUM.
+    pub fn normalize(&mut self) -> Result<(), InvalidSlotRanges> {
+        self.local.merge_slot_ranges();
+        self.peer.merge_slot_ranges();
+
+        let cluster_names: HashSet<&ClusterName> = self
+            .local
+            .cluster_map
+            .keys()
+            .chain(self.peer.cluster_map.keys())
+            .collect();
+        for cluster_name in cluster_names.into_iter() {
+            let all_ranges = self
+                .local
+                .cluster_map
+                .get(cluster_name)
+                .into_iter()
+                .chain(self.peer.cluster_map.get(cluster_name))
+                .flat_map(|node_map| node_map.iter())
+                .flat_map(|(node, slot_ranges)| {
+                    slot_ranges.iter().flat_map(move |slot_range| {
+                        slot_range
+                            .get_range_list()
+                            .get_ranges()
+                            .iter()
+                            .map(move |range| (node.as_str(), range))
+                    })
+                });
+            check_slot_ranges_bound(all_ranges).map_err(|error| InvalidSlotRanges {
+                cluster_name: cluster_name.clone(),
+                error,
+            })?;
+            let owners = self
+                .local
+                .cluster_map
+                .get(cluster_name)
+                .into_iter()
+                .chain(self.peer.cluster_map.get(cluster_name))
+                .flat_map(|node_map| node_map.iter())
+                .flat_map(|(node, slot_ranges)| {
+                    slot_ranges
+                        .iter()
+                        .filter(|slot_range| !matches!(slot_range.tag, SlotRangeTag::Importing(_)))
+                        .map(move |slot_range| (node.as_str(), slot_range.get_range_list()))
+                });
+            check_slot_owners(owners).map_err(|error| InvalidSlotRanges {
+                cluster_name: cluster_name.clone(),
+                error,
+            })?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -193,6 +448,23 @@ impl ProxyClusterMap {
         &self.cluster_map
     }
 
+    pub fn get_slot_range_num(&self) -> usize {
+        self.cluster_map
+            .values()
+            .flat_map(|node_map| node_map.values())
+            .map(|slot_ranges| slot_ranges.len())
+            .sum()
+    }
+
+    fn merge_slot_ranges(&mut self) {
+        for node_map in self.cluster_map.values_mut() {
+            for slot_ranges in node_map.values_mut() {
+                let merged = merge_slot_ranges(std::mem::take(slot_ranges));
+                *slot_ranges = merged;
+            }
+        }
+    }
+
     pub fn cluster_map_to_args(&self) -> Vec<String> {
         let mut args = vec![];
         for (cluster_name, node_map) in &self.cluster_map {
@@ -226,7 +498,7 @@ impl ProxyClusterMap {
                 None => break,
             }
 
-            let (cluster_name, address, slot_range) = try_parse!(Self::parse_cluster(it));
+            let (cluster_name, address, slot_range) = Self::parse_cluster(it)?;
             let cluster = cluster_map.entry(cluster_name).or_insert_with(HashMap::new);
             let slots = cluster.entry(address).or_insert_with(Vec::new);
             slots.push(slot_range);
@@ -241,11 +513,10 @@ impl ProxyClusterMap {
     where
         It: Iterator<Item = String>,
     {
-        let cluster_name = try_get!(it.next());
-        let cluster_name =
-            ClusterName::try_from(cluster_name.as_str()).map_err(|_| CmdParseError {})?;
-        let addr = try_get!(it.next());
-        let slot_range = try_parse!(Self::parse_tagged_slot_range(it));
+        let cluster_name = try_get!(it.next(), "cluster name");
+        let cluster_name = try_parse!(ClusterName::try_from(cluster_name.as_str()), "cluster name");
+        let addr = try_get!(it.next(), "node address");
+        let slot_range = Self::parse_tagged_slot_range(it)?;
         Ok((cluster_name, addr, slot_range))
     }
 
@@ -253,7 +524,11 @@ impl ProxyClusterMap {
     where
         It: Iterator<Item = String>,
     {
-        SlotRange::from_strings(it).ok_or_else(|| CmdParseError {})
+        const SLOT_RANGE_GRAMMAR: &str = "slot range like '1 0-1000' or 'migrating 1 0-1000 epoch src_proxy src_node dst_proxy dst_node'";
+        if it.peek().is_none() {
+            return Err(CmdParseError::missing(SLOT_RANGE_GRAMMAR));
+        }
+        SlotRange::from_strings(it).ok_or_else(|| CmdParseError::invalid(SLOT_RANGE_GRAMMAR))
     }
 }
 
@@ -309,13 +584,13 @@ impl ClusterConfigMap {
                 None => break,
             }
 
-            let (cluster_name, field, value) = try_parse!(Self::parse_config(it));
+            let (cluster_name, field, value) = Self::parse_config(it)?;
             let cluster_config = config_map
                 .entry(cluster_name)
                 .or_insert_with(ClusterConfig::default);
             if let Err(err) = cluster_config.set_field(&field, &value) {
                 warn!("failed to set config field {:?}", err);
-                return Err(CmdParseError {});
+                return Err(CmdParseError::invalid("config value"));
             }
         }
 
@@ -326,11 +601,10 @@ impl ClusterConfigMap {
     where
         It: Iterator<Item = String>,
     {
-        let cluster_name = try_get!(it.next());
-        let cluster_name =
-            ClusterName::try_from(cluster_name.as_str()).map_err(|_| CmdParseError {})?;
-        let field = try_get!(it.next());
-        let value = try_get!(it.next());
+        let cluster_name = try_get!(it.next(), "cluster name");
+        let cluster_name = try_parse!(ClusterName::try_from(cluster_name.as_str()), "cluster name");
+        let field = try_get!(it.next(), "config field");
+        let value = try_get!(it.next(), "config value");
         Ok((cluster_name, field, value))
     }
 
@@ -352,6 +626,7 @@ pub struct ParseExtendedMetaError {}
 
 #[cfg(test)]
 mod tests {
+    use super::super::cluster::Range;
     use super::super::config::CompressionStrategy;
     use super::*;
 
@@ -599,6 +874,36 @@ mod tests {
             "mycluster",
             "migration_scan_count",
             "16",
+            "mycluster",
+            "migration_node_bandwidth",
+            "0",
+            "mycluster",
+            "acl_categories",
+            "all",
+            "mycluster",
+            "acl_key_patterns",
+            "*",
+            "mycluster",
+            "backend_flavor",
+            "redis",
+            "mycluster",
+            "max_key_size",
+            "0",
+            "mycluster",
+            "max_value_size",
+            "0",
+            "mycluster",
+            "ttl_policy",
+            "disabled",
+            "mycluster",
+            "default_ttl",
+            "0",
+            "mycluster",
+            "read_preference",
+            "primary",
+            "mycluster",
+            "max_replica_lag",
+            "0",
             "othercluster",
             "compression_strategy",
             "disabled",
@@ -614,6 +919,36 @@ mod tests {
             "othercluster",
             "migration_scan_count",
             "16",
+            "othercluster",
+            "migration_node_bandwidth",
+            "0",
+            "othercluster",
+            "acl_categories",
+            "all",
+            "othercluster",
+            "acl_key_patterns",
+            "*",
+            "othercluster",
+            "backend_flavor",
+            "redis",
+            "othercluster",
+            "max_key_size",
+            "0",
+            "othercluster",
+            "max_value_size",
+            "0",
+            "othercluster",
+            "ttl_policy",
+            "disabled",
+            "othercluster",
+            "default_ttl",
+            "0",
+            "othercluster",
+            "read_preference",
+            "primary",
+            "othercluster",
+            "max_replica_lag",
+            "0",
         ];
         result_args.sort();
         full_args.sort();
@@ -772,6 +1107,36 @@ mod tests {
             "cluster_name",
             "migration_scan_count",
             "16",
+            "cluster_name",
+            "migration_node_bandwidth",
+            "0",
+            "cluster_name",
+            "acl_categories",
+            "all",
+            "cluster_name",
+            "acl_key_patterns",
+            "*",
+            "cluster_name",
+            "backend_flavor",
+            "redis",
+            "cluster_name",
+            "max_key_size",
+            "0",
+            "cluster_name",
+            "max_value_size",
+            "0",
+            "cluster_name",
+            "ttl_policy",
+            "disabled",
+            "cluster_name",
+            "default_ttl",
+            "0",
+            "cluster_name",
+            "read_preference",
+            "primary",
+            "cluster_name",
+            "max_replica_lag",
+            "0",
         ]
         .into_iter()
         .map(|s| s.to_string());
@@ -898,4 +1263,195 @@ mod tests {
 
         assert!(ProxyClusterMeta::parse(&mut it).is_err());
     }
+
+    #[test]
+    fn test_compact_proxy_cluster_meta() {
+        let arguments = vec![
+            "233",
+            "FORCE",
+            "cluster_name",
+            "127.0.0.1:7000",
+            "1",
+            "0-1000",
+            "PEER",
+            "cluster_name",
+            "127.0.0.2:7001",
+            "1",
+            "1001-2000",
+            "CONFIG",
+            "cluster_name",
+            "compression_strategy",
+            "set_get_only",
+        ];
+        let mut it = arguments.into_iter().map(|s| s.to_string()).peekable();
+        let (cluster_meta, _) = ProxyClusterMeta::parse(&mut it).unwrap();
+        assert_eq!(cluster_meta.get_slot_range_num(), 2);
+
+        let mut cmd = vec![b"UMCTL".to_vec(), b"SETCLUSTER".to_vec()];
+        cmd.extend(cluster_meta.to_compact_args());
+        let resp = Resp::Arr(Array::Arr(
+            cmd.into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s)))
+                .collect(),
+        ));
+        let (compact_meta, extended_res) = ProxyClusterMeta::from_resp(&resp).unwrap();
+        assert!(extended_res.is_ok());
+        assert_eq!(compact_meta.epoch, 233);
+        assert!(compact_meta.flags.force);
+        let cluster_name = ClusterName::try_from("cluster_name").unwrap();
+        let local = compact_meta.get_local().get_map();
+        let peer = compact_meta.get_peer().get_map();
+        assert!(local.get(&cluster_name).unwrap()["127.0.0.1:7000"][0]
+            .meta_eq(&cluster_meta.get_local().get_map()[&cluster_name]["127.0.0.1:7000"][0]));
+        assert_eq!(peer.get(&cluster_name).unwrap().len(), 1);
+        assert_eq!(
+            compact_meta
+                .get_configs()
+                .get_or_default(&cluster_name)
+                .compression_strategy,
+            CompressionStrategy::SetGetOnly
+        );
+    }
+
+    #[test]
+    fn test_normalize_proxy_cluster_meta() {
+        let arguments = vec![
+            "233",
+            "NOFLAG",
+            "cluster_name",
+            "127.0.0.1:7000",
+            "1",
+            "0-1000",
+            "cluster_name",
+            "127.0.0.1:7000",
+            "1",
+            "1001-2000",
+            "PEER",
+            "cluster_name",
+            "127.0.0.2:7001",
+            "1",
+            "2001-3000",
+        ];
+        let mut it = arguments.iter().map(|s| s.to_string()).peekable();
+        let (mut cluster_meta, _) = ProxyClusterMeta::parse(&mut it).unwrap();
+        cluster_meta.normalize().unwrap();
+        let cluster_name = ClusterName::try_from("cluster_name").unwrap();
+        let slot_ranges = &cluster_meta.get_local().get_map()[&cluster_name]["127.0.0.1:7000"];
+        assert_eq!(slot_ranges.len(), 1);
+        assert_eq!(
+            slot_ranges[0].get_range_list().get_ranges(),
+            &[Range(0, 2000)]
+        );
+
+        let mut arguments = arguments;
+        arguments[14] = "2000-3000";
+        let mut it = arguments.iter().map(|s| s.to_string()).peekable();
+        let (mut cluster_meta, _) = ProxyClusterMeta::parse(&mut it).unwrap();
+        let err = cluster_meta.normalize().unwrap_err();
+        assert_eq!(err.cluster_name, cluster_name);
+    }
+
+    #[test]
+    fn test_normalize_importing_out_of_range() {
+        let arguments = vec![
+            "233",
+            "NOFLAG",
+            "cluster_name",
+            "127.0.0.1:7000",
+            "1",
+            "0-1000",
+            "cluster_name",
+            "127.0.0.1:7000",
+            "IMPORTING",
+            "1",
+            "1001-18446744073709551615",
+            "233",
+            "127.0.0.2:7001",
+            "127.0.0.2:6001",
+            "127.0.0.1:7000",
+            "127.0.0.1:6000",
+        ];
+        let mut it = arguments.iter().map(|s| s.to_string()).peekable();
+        let (mut cluster_meta, _) = ProxyClusterMeta::parse(&mut it).unwrap();
+        let err = cluster_meta.normalize().unwrap_err();
+        assert_eq!(
+            err.cluster_name,
+            ClusterName::try_from("cluster_name").unwrap()
+        );
+        match err.error {
+            SlotRangeError::OutOfRange { .. } => (),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    fn gen_set_cluster_resp(args: &[&str]) -> RespVec {
+        let mut cmd = vec!["UMCTL", "SETCLUSTER"];
+        cmd.extend_from_slice(args);
+        Resp::Arr(Array::Arr(
+            cmd.into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.to_string().into_bytes())))
+                .collect(),
+        ))
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let resp = gen_set_cluster_resp(&["233", "NOFLAG", "cluster_name", "127.0.0.1:7000"]);
+        let err = ProxyClusterMeta::from_resp(&resp).unwrap_err();
+        assert_eq!(err.position, Some(6));
+
+        let resp = gen_set_cluster_resp(&["abc", "NOFLAG"]);
+        let err = ProxyClusterMeta::from_resp(&resp).unwrap_err();
+        assert_eq!(err.to_string(), "invalid epoch at argument 2");
+
+        let resp = gen_set_cluster_resp(&[
+            "233",
+            "NOFLAG",
+            "cluster_name",
+            "127.0.0.1:7000",
+            "1",
+            "0-1000",
+            "another_cluster",
+        ]);
+        let err = ProxyClusterMeta::from_resp(&resp).unwrap_err();
+        assert_eq!(err.to_string(), "missing node address at argument 9");
+
+        let resp = gen_set_cluster_resp(&[
+            "233",
+            "NOFLAG",
+            "cluster_name",
+            "127.0.0.1:7000",
+            "1",
+            "0-1000",
+            "CONFIG",
+            "cluster_name",
+            "compression_strategy",
+        ]);
+        let err = ProxyClusterMeta::from_resp(&resp).unwrap_err();
+        assert_eq!(err.to_string(), "missing config value at argument 11");
+    }
+
+    #[test]
+    fn test_proxy_capabilities() {
+        let capabilities = ProxyCapabilities {
+            version: "0.3.0".to_string(),
+            commands: vec!["SETCLUSTER".to_string(), "INFOSTATS".to_string()],
+            features: vec![FEATURE_FORCE_RESYNC.to_string()],
+        };
+        let resp = capabilities.to_resp();
+        let parsed = ProxyCapabilities::from_resp(&resp).unwrap();
+        assert_eq!(parsed, capabilities);
+        assert!(parsed.supports_command("infostats"));
+        assert!(!parsed.supports_command("RELOADTLS"));
+        assert!(parsed.has_feature(FEATURE_FORCE_RESYNC));
+        assert!(!parsed.has_feature(FEATURE_REPL_DIFF));
+
+        let legacy = ProxyCapabilities::legacy();
+        assert!(legacy.supports_command("SETREPL"));
+        assert!(!legacy.supports_command("INFOSTATS"));
+        assert!(legacy.features.is_empty());
+
+        let invalid = Resp::Bulk(BulkStr::Str(b"version: 0.3.0".to_vec()));
+        assert!(ProxyCapabilities::from_resp(&invalid).is_err());
+    }
 }
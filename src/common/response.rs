@@ -1,13 +1,75 @@
+// The errors generated by the proxy itself start with one of the codes below
+// so that the clients could decide whether to retry without parsing the messages.
+// The errors defined by Redis like MOVED, ASK, TRYAGAIN, NOAUTH and the argument errors are kept.
+// Retry after refreshing the cluster topology.
+pub const UM_ROUTING: &str = "UM-ROUTING";
+// Retry if the command is idempotent.
+pub const UM_TIMEOUT: &str = "UM-TIMEOUT";
+// Retry after a short delay.
+pub const UM_MIGRATING: &str = "UM-MIGRATING";
+// Retry with backoff.
+pub const UM_BACKEND: &str = "UM-BACKEND";
+// Reconnect to another proxy.
+pub const UM_SHUTDOWN: &str = "UM-SHUTDOWN";
+// The following ones should not be retried.
+pub const UM_DENIED: &str = "UM-DENIED";
+pub const UM_LIMIT: &str = "UM-LIMIT";
+pub const UM_UNSUPPORTED: &str = "UM-UNSUPPORTED";
+pub const UM_INVALID: &str = "UM-INVALID";
+pub const UM_INTERNAL: &str = "UM-INTERNAL";
+
 pub const OK_REPLY: &str = "OK";
+pub const QUEUED_REPLY: &str = "QUEUED";
+pub const RESET_REPLY: &str = "RESET";
 pub const OLD_EPOCH_REPLY: &str = "OLD_EPOCH";
 pub const TRY_AGAIN_REPLY: &str = "TRY_AGAIN";
 pub const NOT_READY_FOR_SWITCHING_REPLY: &str = "NOT_READY_FOR_SWITCHING";
 pub const TASK_NOT_FOUND: &str = "TASK_NOT_FOUND";
-pub const ERR_NOT_THE_SAME_SLOT: &str = "ERR_MULTI_SLOTS slots of the keys are not the same";
-pub const ERR_CLUSTER_NOT_FOUND: &str = "ERR_CLUSTER_NOT_FOUND";
-pub const ERR_BACKEND_CONNECTION: &str = "ERR_BACKEND_CONNECTION";
+pub const ERR_NOT_THE_SAME_SLOT: &str = "UM-INVALID slots of the keys are not the same";
+pub const ERR_CLUSTER_NOT_FOUND: &str = "UM-ROUTING cluster not found";
+pub const ERR_CLUSTER_REMOVED: &str = "UM-ROUTING cluster removed";
+pub const ERR_SLOT_NOT_COVERED: &str = "UM-ROUTING slot not covered";
+pub const ERR_MISSING_KEY: &str = "UM-INVALID missing key";
+pub const ERR_INVALID_COMMAND: &str = "UM-INVALID invalid command";
+pub const ERR_BACKEND_CONNECTION: &str = "UM-BACKEND failed to connect to backend";
+pub const ERR_BACKEND_OVERLOADED: &str = "UM-BACKEND backend is overloaded";
 pub const ERR_MOVED: &str = "MOVED";
-pub const CMD_NOT_SUPPORTED: &str = "ERR_COMMAND_NOT_SUPPORTED";
-pub const ERR_TOO_MANY_REDIRECTIONS: &str = "ERR_TOO_MANY_REDIRECTIONS";
+pub const CMD_NOT_SUPPORTED: &str = "UM-UNSUPPORTED command is not supported";
+pub const ERR_UNKNOWN_COMMAND: &str = "UM-UNSUPPORTED unknown command";
+pub const ERR_TOO_MANY_REDIRECTIONS: &str = "UM-ROUTING too many redirections";
 pub const MIGRATING_FINISHED: &str = "MIGRATING_FINISHED";
 pub const MIGRATION_TASK_NOT_FOUND: &str = "MIGRATION_TASK_NOT_FOUND";
+pub const ERR_ACL_CATEGORY_NOT_ALLOWED: &str = "UM-DENIED command category is not allowed";
+pub const ERR_ACL_KEY_NOT_ALLOWED: &str = "UM-DENIED key is not allowed";
+pub const ERR_NOAUTH: &str = "NOAUTH Authentication required.";
+pub const ERR_WRONGPASS: &str = "WRONGPASS invalid cluster name or password";
+pub const ERR_TIMEOUT: &str = "UM-TIMEOUT command timed out in proxy";
+pub const ERR_KEY_TOO_LARGE: &str = "UM-LIMIT key is larger than max_key_size";
+pub const ERR_VALUE_TOO_LARGE: &str = "UM-LIMIT value is larger than max_value_size";
+pub const ERR_RETRY: &str = "RETRY";
+pub const ERR_SHUTTING_DOWN: &str = "UM-SHUTDOWN proxy is shutting down";
+pub const ERR_MIGRATION_BLOCKING: &str = "TRYAGAIN slot is being switched for migration";
+pub const ERR_NOPROTO: &str = "NOPROTO unsupported protocol version";
+pub const ERR_CLIENT_TRACKING: &str =
+    "UM-UNSUPPORTED client tracking needs RESP3 or REDIRECT which are not supported";
+pub const ERR_INTERNAL: &str = "UM-INTERNAL unexpected error in proxy";
+pub const ERR_TTL_REQUIRED: &str = "UM-LIMIT expiry is required by the cluster";
+pub const ERR_CROSSSLOT: &str = "CROSSSLOT Keys in request don't hash to the same slot";
+pub const ERR_RELOAD_TLS: &str = "UM-INVALID failed to reload TLS config";
+pub const ERR_INVALID_ARGUMENTS: &str = "UM-INVALID invalid arguments";
+pub const ERR_EVAL_KEY_NUM: &str = "ERR Number of keys can't be greater than number of args";
+pub const ERR_EXECABORT: &str = "EXECABORT Transaction discarded because of previous errors.";
+pub const ERR_NESTED_MULTI: &str = "ERR MULTI calls can not be nested";
+pub const ERR_EXEC_WITHOUT_MULTI: &str = "ERR EXEC without MULTI";
+pub const ERR_DISCARD_WITHOUT_MULTI: &str = "ERR DISCARD without MULTI";
+pub const ERR_WATCH_INSIDE_MULTI: &str = "ERR WATCH inside MULTI is not allowed";
+pub const ERR_TRANSACTION_KEY_REQUIRED: &str =
+    "UM-UNSUPPORTED only the commands with keys are allowed in transactions";
+pub const ERR_TRANSACTION_NOT_ALLOWED: &str =
+    "UM-UNSUPPORTED transactions are not allowed with ttl_policy or double_write_cluster";
+pub const ERR_PUBSUB_CONTEXT: &str =
+    "ERR only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context";
+pub const ERR_MONITOR_CONTEXT: &str = "ERR only QUIT / RESET are allowed in the MONITOR mode";
+pub const ERR_MONITOR_NOT_ALLOWED: &str =
+    "UM-DENIED MONITOR requires the access to all the commands and keys";
+pub const ERR_MONITOR_LAGGED: &str = "UM-LIMIT monitor fell too far behind";
@@ -3,16 +3,131 @@ use crate::protocol::{Array, BulkStr, Resp};
 use crate::protocol::{BinSafeStr, RespVec};
 use crc16::{State, XMODEM};
 use futures::{stream, Stream};
+use std::any::Any;
+use std::cell::Cell;
 use std::cmp::min;
+use std::fmt;
+use std::iter::Peekable;
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::rc::Rc;
 use std::str;
 
 pub trait ThreadSafe: Send + Sync + 'static {}
 
 impl<T: Send + Sync + 'static> ThreadSafe for T {}
 
-#[derive(Debug)]
-pub struct CmdParseError {}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmdParseErrorKind {
+    Missing,
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CmdParseError {
+    pub kind: CmdParseErrorKind,
+    // The grammar of the offending argument.
+    pub expected: &'static str,
+    // The index of the offending argument in the whole command.
+    pub position: Option<usize>,
+}
+
+impl CmdParseError {
+    pub fn missing(expected: &'static str) -> Self {
+        Self {
+            kind: CmdParseErrorKind::Missing,
+            expected,
+            position: None,
+        }
+    }
+
+    pub fn invalid(expected: &'static str) -> Self {
+        Self {
+            kind: CmdParseErrorKind::Invalid,
+            expected,
+            position: None,
+        }
+    }
+}
+
+impl fmt::Display for CmdParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            CmdParseErrorKind::Missing => "missing",
+            CmdParseErrorKind::Invalid => "invalid",
+        };
+        match self.position {
+            Some(position) => write!(f, "{} {} at argument {}", kind, self.expected, position),
+            None => write!(f, "{} {}", kind, self.expected),
+        }
+    }
+}
+
+pub struct CountedArgs<It: Iterator<Item = String>> {
+    inner: It,
+    consumed: Rc<Cell<usize>>,
+}
+
+impl<It: Iterator<Item = String>> Iterator for CountedArgs<It> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let arg = self.inner.next()?;
+        self.consumed.set(self.consumed.get() + 1);
+        Some(arg)
+    }
+}
+
+// Runs the parser and locates the error by the consumed arguments.
+// `offset` is the number of the skipped arguments like "UMCTL SETCLUSTER".
+pub fn parse_args<T, It, F>(args: It, offset: usize, parse: F) -> Result<T, CmdParseError>
+where
+    It: Iterator<Item = String>,
+    F: FnOnce(&mut Peekable<CountedArgs<It>>) -> Result<T, CmdParseError>,
+{
+    let consumed = Rc::new(Cell::new(0));
+    let mut it = CountedArgs {
+        inner: args,
+        consumed: consumed.clone(),
+    }
+    .peekable();
+    let res = parse(&mut it);
+    // The peeked argument is not consumed by the parser.
+    let pending = res.is_err() && it.peek().is_some();
+    res.map_err(|mut err| {
+        if err.position.is_none() {
+            let mut consumed = consumed.get() - pending as usize;
+            // The invalid argument has been consumed.
+            if err.kind == CmdParseErrorKind::Invalid {
+                consumed = consumed.saturating_sub(1);
+            }
+            err.position = Some(offset + consumed);
+        }
+        err
+    })
+}
+
+// All the arguments should be valid utf-8 strings.
+pub fn resp_array_to_args<T: AsRef<[u8]>>(
+    arr: &[Resp<T>],
+    offset: usize,
+) -> Result<Vec<String>, CmdParseError> {
+    arr.iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, resp)| match resp {
+            Resp::Bulk(BulkStr::Str(s)) => str::from_utf8(s.as_ref())
+                .map(|s| s.to_string())
+                .map_err(|_| CmdParseError {
+                    position: Some(i),
+                    ..CmdParseError::invalid("utf-8 string")
+                }),
+            _ => Err(CmdParseError {
+                position: Some(i),
+                ..CmdParseError::invalid("bulk string")
+            }),
+        })
+        .collect()
+}
 
 pub fn has_flags(s: &str, delimiter: char, flag: &'static str) -> bool {
     s.split(delimiter)
@@ -35,6 +150,24 @@ pub fn resolve_first_address(address: &str) -> Option<SocketAddr> {
     }
 }
 
+// Resolves the address in the blocking thread pool
+// so that a slow DNS won't block the other tasks in the same thread.
+pub async fn lookup_first_address(address: &str) -> Option<SocketAddr> {
+    match tokio::net::lookup_host(address).await {
+        Ok(mut address_list) => match address_list.next() {
+            Some(address) => Some(address),
+            None => {
+                error!("can not resolve address {}", address);
+                None
+            }
+        },
+        Err(e) => {
+            error!("failed to resolve address {} {:?}", address, e);
+            None
+        }
+    }
+}
+
 pub fn get_resp_bytes(resp: &RespVec) -> Option<Vec<Vec<u8>>> {
     match resp {
         Resp::Arr(Array::Arr(ref resps)) => {
@@ -125,6 +258,21 @@ pub fn array_append_front(resp: &mut RespVec, preceding_elements: Vec<BinSafeStr
     }
 }
 
+// Returns success or not
+pub fn array_append_back(resp: &mut RespVec, following_elements: Vec<BinSafeStr>) -> bool {
+    match resp {
+        Resp::Arr(Array::Arr(ref mut resps)) => {
+            resps.extend(
+                following_elements
+                    .into_iter()
+                    .map(|s| Resp::Bulk(BulkStr::Str(s))),
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
 pub fn change_bulk_str(resp: &mut RespVec, data: Vec<u8>) -> bool {
     match resp {
         Resp::Bulk(BulkStr::Str(s)) => {
@@ -180,6 +328,16 @@ pub fn pretty_print_bytes(data: &[u8]) -> String {
     }
 }
 
+pub fn get_panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
 pub const SLOT_NUM: usize = 16384;
 
 pub const MIGRATING_TAG: &str = "MIGRATING";
@@ -246,10 +404,39 @@ pub fn byte_to_uppercase(b: u8) -> u8 {
     }
 }
 
+// Only supports `*` and `?`.
+pub fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            last_star = Some((p, i));
+            p += 1;
+        } else if let Some((star_p, star_i)) = last_star {
+            p = star_p + 1;
+            i = star_i + 1;
+            last_star = Some((star_p, star_i + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|b| *b == b'*')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_lookup_first_address() {
+        let address = lookup_first_address("127.0.0.1:6379").await;
+        assert_eq!(address, Some("127.0.0.1:6379".parse().unwrap()));
+        assert_eq!(lookup_first_address("127.0.0.1").await, None);
+    }
+
     #[test]
     fn test_get_hash_tag() {
         assert_eq!(
@@ -289,4 +476,18 @@ mod tests {
             assert_eq!(byte_to_uppercase(l), u);
         }
     }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"*", b"abc"));
+        assert!(glob_match(b"abc", b"abc"));
+        assert!(!glob_match(b"abc", b"abcd"));
+        assert!(glob_match(b"tenant:*", b"tenant:key"));
+        assert!(!glob_match(b"tenant:*", b"other:key"));
+        assert!(glob_match(b"a?c*", b"abcdef"));
+        assert!(glob_match(b"*:key", b"tenant:key"));
+        assert!(glob_match(b"a*b*c", b"aXXbYYc"));
+        assert!(!glob_match(b"a*b*c", b"aXXbYY"));
+    }
 }
@@ -5,7 +5,7 @@ use crate::common::track::TrackedFutureRegistry;
 use crate::common::utils::resolve_first_address;
 use crate::protocol::{Array, BulkStr, Resp, RespPacket, RespVec};
 use crate::proxy::command::{new_command_pair, CmdType, Command, TaskReply};
-use crate::proxy::session::{handle_session, CmdHandler, CmdReplyFuture};
+use crate::proxy::session::{handle_session, CmdHandler, CmdReplyFuture, SessionControl};
 use crate::proxy::slowlog::Slowlog;
 use futures::{FutureExt, StreamExt};
 use std::num::NonZeroUsize;
@@ -82,6 +82,7 @@ impl ApiService {
                 SESSION_BATCH_MIN_TIME,
                 SESSION_BATCH_MAX_TIME,
                 session_batch_buf,
+                SessionControl::default(),
             );
 
             let desc = format!("session: session_id={} peer={}", curr_session_id, peer);
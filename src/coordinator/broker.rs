@@ -1,4 +1,6 @@
-use crate::common::cluster::{Cluster, ClusterName, MigrationTaskMeta, Proxy};
+use crate::common::cluster::{
+    Cluster, ClusterName, MigrationTaskEvents, MigrationTaskMeta, Proxy, ProxyRuntimeStats,
+};
 use crate::common::utils::ThreadSafe;
 use futures::{Future, Stream};
 use mockall::automock;
@@ -46,6 +48,10 @@ mod trait_mod {
         fn get_failed_proxies<'s>(
             &'s self,
         ) -> Pin<Box<dyn Stream<Item = Result<String, MetaDataBrokerError>> + Send + 's>>;
+
+        fn get_rebuilding_proxies<'s>(
+            &'s self,
+        ) -> Pin<Box<dyn Stream<Item = Result<String, MetaDataBrokerError>> + Send + 's>>;
     }
 
     // Maybe we would want to support other database supporting redis protocol.
@@ -65,6 +71,23 @@ mod trait_mod {
             &'s self,
             meta: MigrationTaskMeta,
         ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>>;
+
+        fn finish_proxy_rebuilding<'s>(
+            &'s self,
+            proxy_address: String,
+        ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>>;
+
+        fn report_proxy_stats<'s>(
+            &'s self,
+            proxy_address: String,
+            stats: ProxyRuntimeStats,
+        ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>>;
+
+        fn report_migration_events<'s>(
+            &'s self,
+            proxy_address: String,
+            events: Vec<MigrationTaskEvents>,
+        ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>>;
     }
 }
 
@@ -0,0 +1,78 @@
+use super::core::CoordinateError;
+use crate::common::proto::ProxyCapabilities;
+use crate::protocol::{RedisClient, Resp};
+
+// The proxies before `UMCTL CAPABILITIES` was added reply an error,
+// so that the coordinator could still work with them during rolling upgrades.
+pub async fn get_proxy_capabilities<C: RedisClient>(
+    client: &mut C,
+) -> Result<ProxyCapabilities, CoordinateError> {
+    let cmd = vec![b"UMCTL".to_vec(), b"CAPABILITIES".to_vec()];
+    let resp = client
+        .execute_single(cmd)
+        .await
+        .map_err(CoordinateError::Redis)?;
+    if let Resp::Error(_) = resp {
+        return Ok(ProxyCapabilities::legacy());
+    }
+    ProxyCapabilities::from_resp(&resp).map_err(|_| {
+        error!("invalid UMCTL CAPABILITIES reply {:?}", resp);
+        CoordinateError::InvalidReply
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::proto::FEATURE_FORCE_RESYNC;
+    use crate::protocol::{Array, BinSafeStr, BulkStr, MockRedisClient};
+    use tokio;
+
+    fn capabilities_cmd() -> Vec<BinSafeStr> {
+        vec![b"UMCTL".to_vec(), b"CAPABILITIES".to_vec()]
+    }
+
+    #[tokio::test]
+    async fn test_get_proxy_capabilities() {
+        let mut mock_client = MockRedisClient::new();
+        mock_client
+            .expect_execute_single()
+            .withf(|command: &Vec<BinSafeStr>| command.eq(&capabilities_cmd()))
+            .times(1)
+            .returning(|_| {
+                let lines = vec![
+                    "version: 0.9.0",
+                    "commands: INFOSTATS SETCLUSTER",
+                    "features: force_resync",
+                ];
+                let reply = Resp::Arr(Array::Arr(
+                    lines
+                        .into_iter()
+                        .map(|line| Resp::Bulk(BulkStr::Str(line.to_string().into_bytes())))
+                        .collect(),
+                ));
+                Box::pin(async { Ok(reply) })
+            });
+        let capabilities = get_proxy_capabilities(&mut mock_client).await.unwrap();
+        assert_eq!(capabilities.version, "0.9.0");
+        assert!(capabilities.supports_command("INFOSTATS"));
+        assert!(capabilities.has_feature(FEATURE_FORCE_RESYNC));
+    }
+
+    #[tokio::test]
+    async fn test_get_legacy_proxy_capabilities() {
+        let mut mock_client = MockRedisClient::new();
+        mock_client
+            .expect_execute_single()
+            .withf(|command: &Vec<BinSafeStr>| command.eq(&capabilities_cmd()))
+            .times(1)
+            .returning(|_| {
+                let reply = Resp::Error(b"Invalid sub command".to_vec());
+                Box::pin(async { Ok(reply) })
+            });
+        let capabilities = get_proxy_capabilities(&mut mock_client).await.unwrap();
+        assert!(capabilities.supports_command("SETCLUSTER"));
+        assert!(!capabilities.supports_command("INFOSTATS"));
+        assert!(!capabilities.has_feature(FEATURE_FORCE_RESYNC));
+    }
+}
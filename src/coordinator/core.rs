@@ -1,5 +1,5 @@
 use super::broker::{MetaDataBrokerError, MetaManipulationBrokerError};
-use crate::common::cluster::{MigrationTaskMeta, Proxy};
+use crate::common::cluster::{MigrationTaskEvents, MigrationTaskMeta, Proxy, ProxyRuntimeStats};
 use crate::protocol::RedisClientError;
 use futures::{future, stream, Future, FutureExt, Stream, StreamExt, TryFutureExt};
 use futures_batch::ChunksTimeoutStreamExt;
@@ -19,6 +19,9 @@ pub trait ProxiesRetriever: Sync + Send + 'static {
 
 pub type ProxyFailure = String; // proxy address
 
+// None for the proxies not supporting the migration events.
+pub type ProxyMigrationEvents = Option<Vec<MigrationTaskEvents>>;
+
 pub trait FailureChecker: Sync + Send + 'static {
     fn check<'s>(
         &'s self,
@@ -554,6 +557,366 @@ impl<
     }
 }
 
+pub trait ReplicaSyncChecker: Sync + Send + 'static {
+    // Returns true when all the replicas of the proxy have finished the initial sync.
+    fn check<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<bool, CoordinateError>> + Send + 's>>;
+}
+
+pub trait RebuildCommitter: Sync + Send + 'static {
+    fn commit<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CoordinateError>> + Send + 's>>;
+}
+
+pub trait ReplicaRebuildSynchronizer {
+    type PRetriever: ProxiesRetriever;
+    type Checker: ReplicaSyncChecker;
+    type Committer: RebuildCommitter;
+
+    fn new(
+        proxy_retriever: Self::PRetriever,
+        checker: Self::Checker,
+        committer: Self::Committer,
+    ) -> Self;
+    fn run<'s>(&'s self) -> Pin<Box<dyn Stream<Item = Result<(), CoordinateError>> + Send + 's>>;
+}
+
+pub struct ParReplicaRebuildSynchronizer<
+    PR: ProxiesRetriever,
+    SC: ReplicaSyncChecker,
+    RC: RebuildCommitter,
+> {
+    proxy_retriever: PR,
+    checker: Arc<SC>,
+    committer: Arc<RC>,
+}
+
+impl<PR: ProxiesRetriever, SC: ReplicaSyncChecker, RC: RebuildCommitter>
+    ParReplicaRebuildSynchronizer<PR, SC, RC>
+{
+    async fn check_and_commit(
+        checker: &SC,
+        committer: &RC,
+        address: String,
+    ) -> Result<(), CoordinateError> {
+        if !checker.check(address.clone()).await? {
+            return Ok(());
+        }
+        if let Err(err) = committer.commit(address).await {
+            error!("failed to commit rebuilding: {:?}", err);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    async fn run_impl(&self) -> Result<(), CoordinateError> {
+        let checker = self.checker.clone();
+        let committer = self.committer.clone();
+        const BATCH_SIZE: usize = 10;
+        const BATCH_TIME: Duration = Duration::from_millis(1);
+
+        let mut res = Ok(());
+        let mut s = self
+            .proxy_retriever
+            .retrieve_proxies()
+            .chunks_timeout(BATCH_SIZE, BATCH_TIME);
+        while let Some(results) = s.next().await {
+            let mut proxies = vec![];
+            for r in results {
+                match r {
+                    Ok(proxy) => proxies.push(proxy),
+                    Err(err) => {
+                        error!("failed to get proxy: {:?}", err);
+                        res = Err(err);
+                    }
+                }
+            }
+            let futs: Vec<_> = proxies
+                .into_iter()
+                .map(|address| Self::check_and_commit(&checker, &committer, address))
+                .collect();
+            let results = future::join_all(futs).await;
+            for r in results.into_iter() {
+                if let Err(err) = r {
+                    error!("faild to sync rebuilding state, error: {:?}", err);
+                    res = Err(err);
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<PR: ProxiesRetriever, SC: ReplicaSyncChecker, RC: RebuildCommitter> ReplicaRebuildSynchronizer
+    for ParReplicaRebuildSynchronizer<PR, SC, RC>
+{
+    type PRetriever = PR;
+    type Checker = SC;
+    type Committer = RC;
+
+    fn new(
+        proxy_retriever: Self::PRetriever,
+        checker: Self::Checker,
+        committer: Self::Committer,
+    ) -> Self {
+        Self {
+            proxy_retriever,
+            checker: Arc::new(checker),
+            committer: Arc::new(committer),
+        }
+    }
+
+    fn run<'s>(&'s self) -> Pin<Box<dyn Stream<Item = Result<(), CoordinateError>> + Send + 's>> {
+        Box::pin(
+            self.run_impl()
+                .map(|res| stream::iter(vec![res]))
+                .flatten_stream(),
+        )
+    }
+}
+
+pub trait ProxyStatsCollector: Sync + Send + 'static {
+    // Returns None for the proxies not supporting the statistics.
+    fn collect<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<ProxyRuntimeStats>, CoordinateError>> + Send + 's>>;
+}
+
+pub trait ProxyStatsReporter: Sync + Send + 'static {
+    fn report<'s>(
+        &'s self,
+        address: String,
+        stats: ProxyRuntimeStats,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CoordinateError>> + Send + 's>>;
+}
+
+pub trait ProxyStatsSynchronizer {
+    type PRetriever: ProxiesRetriever;
+    type Collector: ProxyStatsCollector;
+    type Reporter: ProxyStatsReporter;
+
+    fn new(
+        proxy_retriever: Self::PRetriever,
+        collector: Self::Collector,
+        reporter: Self::Reporter,
+    ) -> Self;
+    fn run<'s>(&'s self) -> Pin<Box<dyn Stream<Item = Result<(), CoordinateError>> + Send + 's>>;
+}
+
+pub struct ParProxyStatsSynchronizer<
+    PR: ProxiesRetriever,
+    SC: ProxyStatsCollector,
+    SR: ProxyStatsReporter,
+> {
+    proxy_retriever: PR,
+    collector: Arc<SC>,
+    reporter: Arc<SR>,
+}
+
+impl<PR: ProxiesRetriever, SC: ProxyStatsCollector, SR: ProxyStatsReporter>
+    ParProxyStatsSynchronizer<PR, SC, SR>
+{
+    async fn collect_and_report(
+        collector: &SC,
+        reporter: &SR,
+        address: String,
+    ) -> Result<(), CoordinateError> {
+        match collector.collect(address.clone()).await? {
+            Some(stats) => reporter.report(address, stats).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn run_impl(&self) -> Result<(), CoordinateError> {
+        let collector = self.collector.clone();
+        let reporter = self.reporter.clone();
+        const BATCH_SIZE: usize = 10;
+        const BATCH_TIME: Duration = Duration::from_millis(1);
+
+        let mut res = Ok(());
+        let mut s = self
+            .proxy_retriever
+            .retrieve_proxies()
+            .chunks_timeout(BATCH_SIZE, BATCH_TIME);
+        while let Some(results) = s.next().await {
+            let mut proxies = vec![];
+            for r in results {
+                match r {
+                    Ok(proxy) => proxies.push(proxy),
+                    Err(err) => {
+                        error!("failed to get proxy: {:?}", err);
+                        res = Err(err);
+                    }
+                }
+            }
+            let futs: Vec<_> = proxies
+                .into_iter()
+                .map(|address| Self::collect_and_report(&collector, &reporter, address))
+                .collect();
+            let results = future::join_all(futs).await;
+            for r in results.into_iter() {
+                if let Err(err) = r {
+                    error!("faild to sync proxy stats, error: {:?}", err);
+                    res = Err(err);
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<PR: ProxiesRetriever, SC: ProxyStatsCollector, SR: ProxyStatsReporter> ProxyStatsSynchronizer
+    for ParProxyStatsSynchronizer<PR, SC, SR>
+{
+    type PRetriever = PR;
+    type Collector = SC;
+    type Reporter = SR;
+
+    fn new(
+        proxy_retriever: Self::PRetriever,
+        collector: Self::Collector,
+        reporter: Self::Reporter,
+    ) -> Self {
+        Self {
+            proxy_retriever,
+            collector: Arc::new(collector),
+            reporter: Arc::new(reporter),
+        }
+    }
+
+    fn run<'s>(&'s self) -> Pin<Box<dyn Stream<Item = Result<(), CoordinateError>> + Send + 's>> {
+        Box::pin(
+            self.run_impl()
+                .map(|res| stream::iter(vec![res]))
+                .flatten_stream(),
+        )
+    }
+}
+
+pub trait MigrationEventCollector: Sync + Send + 'static {
+    // Returns None for the proxies not supporting the migration events.
+    fn collect<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<ProxyMigrationEvents, CoordinateError>> + Send + 's>>;
+}
+
+pub trait MigrationEventReporter: Sync + Send + 'static {
+    fn report<'s>(
+        &'s self,
+        address: String,
+        events: Vec<MigrationTaskEvents>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CoordinateError>> + Send + 's>>;
+}
+
+pub trait MigrationEventSynchronizer {
+    type PRetriever: ProxiesRetriever;
+    type Collector: MigrationEventCollector;
+    type Reporter: MigrationEventReporter;
+
+    fn new(
+        proxy_retriever: Self::PRetriever,
+        collector: Self::Collector,
+        reporter: Self::Reporter,
+    ) -> Self;
+    fn run<'s>(&'s self) -> Pin<Box<dyn Stream<Item = Result<(), CoordinateError>> + Send + 's>>;
+}
+
+pub struct ParMigrationEventSynchronizer<
+    PR: ProxiesRetriever,
+    EC: MigrationEventCollector,
+    ER: MigrationEventReporter,
+> {
+    proxy_retriever: PR,
+    collector: Arc<EC>,
+    reporter: Arc<ER>,
+}
+
+impl<PR: ProxiesRetriever, EC: MigrationEventCollector, ER: MigrationEventReporter>
+    ParMigrationEventSynchronizer<PR, EC, ER>
+{
+    async fn collect_and_report(
+        collector: &EC,
+        reporter: &ER,
+        address: String,
+    ) -> Result<(), CoordinateError> {
+        match collector.collect(address.clone()).await? {
+            Some(events) if !events.is_empty() => reporter.report(address, events).await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn run_impl(&self) -> Result<(), CoordinateError> {
+        let collector = self.collector.clone();
+        let reporter = self.reporter.clone();
+        const BATCH_SIZE: usize = 10;
+        const BATCH_TIME: Duration = Duration::from_millis(1);
+
+        let mut res = Ok(());
+        let mut s = self
+            .proxy_retriever
+            .retrieve_proxies()
+            .chunks_timeout(BATCH_SIZE, BATCH_TIME);
+        while let Some(results) = s.next().await {
+            let mut proxies = vec![];
+            for r in results {
+                match r {
+                    Ok(proxy) => proxies.push(proxy),
+                    Err(err) => {
+                        error!("failed to get proxy: {:?}", err);
+                        res = Err(err);
+                    }
+                }
+            }
+            let futs: Vec<_> = proxies
+                .into_iter()
+                .map(|address| Self::collect_and_report(&collector, &reporter, address))
+                .collect();
+            let results = future::join_all(futs).await;
+            for r in results.into_iter() {
+                if let Err(err) = r {
+                    error!("faild to sync migration events, error: {:?}", err);
+                    res = Err(err);
+                }
+            }
+        }
+        res
+    }
+}
+
+impl<PR: ProxiesRetriever, EC: MigrationEventCollector, ER: MigrationEventReporter>
+    MigrationEventSynchronizer for ParMigrationEventSynchronizer<PR, EC, ER>
+{
+    type PRetriever = PR;
+    type Collector = EC;
+    type Reporter = ER;
+
+    fn new(
+        proxy_retriever: Self::PRetriever,
+        collector: Self::Collector,
+        reporter: Self::Reporter,
+    ) -> Self {
+        Self {
+            proxy_retriever,
+            collector: Arc::new(collector),
+            reporter: Arc::new(reporter),
+        }
+    }
+
+    fn run<'s>(&'s self) -> Pin<Box<dyn Stream<Item = Result<(), CoordinateError>> + Send + 's>> {
+        Box::pin(
+            self.run_impl()
+                .map(|res| stream::iter(vec![res]))
+                .flatten_stream(),
+        )
+    }
+}
+
 #[derive(Debug)]
 pub enum CoordinateError {
     Io(io::Error),
@@ -563,6 +926,7 @@ pub enum CoordinateError {
     InvalidReply,
     InvalidAddress,
     InvalidConfig,
+    InvalidMetadata,
 }
 
 impl fmt::Display for CoordinateError {
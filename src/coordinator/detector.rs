@@ -298,7 +298,8 @@ mod tests {
     use super::super::core::{FailureDetector, ParFailureDetector};
     use super::*;
     use crate::common::cluster::{
-        ClusterName, MigrationMeta, Node, RangeList, ReplMeta, Role, SlotRange, SlotRangeTag,
+        ClusterName, MigrationMeta, Node, RangeList, ReplMeta, RestorePolicy, Role, SlotRange,
+        SlotRangeTag,
     };
     use crate::common::config::ClusterConfig;
     use crate::protocol::{
@@ -403,6 +404,7 @@ mod tests {
             src_node_address: "redis1:port1".to_string(),
             dst_proxy_address: "host3:port3".to_string(),
             dst_node_address: "redis3:port3".to_string(),
+            restore_policy: RestorePolicy::default(),
         };
         let nodes = vec![
             Node::new(
@@ -1,7 +1,7 @@
 use super::broker::{MetaManipulationBroker, MetaManipulationBrokerError};
 use super::service::BrokerAddresses;
-use crate::broker::MEM_BROKER_API_VERSION;
-use crate::common::cluster::{MigrationTaskMeta, Proxy};
+use crate::broker::{BrokerClient, BrokerClientError};
+use crate::common::cluster::{MigrationTaskEvents, MigrationTaskMeta, Proxy, ProxyRuntimeStats};
 use futures::Future;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -23,98 +23,103 @@ impl HttpMetaManipulationBroker {
 }
 
 impl HttpMetaManipulationBroker {
-    fn gen_url(&self, path: &str) -> Option<String> {
+    fn gen_client(&self) -> Result<BrokerClient, MetaManipulationBrokerError> {
         let broker_addresses = self.broker_addresses.lease();
         let num = broker_addresses.len();
         let curr_index = self.broker_index.fetch_add(1, Ordering::Relaxed);
-        let broker = broker_addresses.get(curr_index % num)?;
-        let url = format!("http://{}{}{}", broker, MEM_BROKER_API_VERSION, path);
-        Some(url)
+        let broker = broker_addresses
+            .get(curr_index % num)
+            .ok_or(MetaManipulationBrokerError::NoBroker)?;
+        Ok(BrokerClient::new(self.client.clone(), broker.clone()))
     }
 
     async fn replace_proxy_impl(
         &self,
         failed_proxy_address: String,
     ) -> Result<Option<Proxy>, MetaManipulationBrokerError> {
-        let url = self
-            .gen_url(&format!("/proxies/failover/{}", failed_proxy_address))
-            .ok_or_else(|| MetaManipulationBrokerError::NoBroker)?;
-        let response = self.client.post(&url).send().await.map_err(|e| {
-            error!("Failed to replace proxy {:?}", e);
-            MetaManipulationBrokerError::RequestFailed
-        })?;
-
-        let status = response.status();
-
-        if status.is_success() {
-            let ReplaceProxyResponse { proxy } = response.json().await.map_err(|e| {
-                error!("Failed to get json payload {:?}", e);
-                MetaManipulationBrokerError::InvalidReply
-            })?;
-            Ok(proxy)
-        } else {
-            error!(
-                "replace_proxy: Failed to replace node: status code {:?}",
-                status
-            );
-            let result = response.text().await;
-            match result {
-                Ok(body) => {
-                    error!("replace_proxy: Error body: {:?}", body);
-                    Err(MetaManipulationBrokerError::InvalidReply)
-                }
-                Err(e) => {
-                    error!("replace_proxy: Failed to get body: {:?}", e);
-                    Err(MetaManipulationBrokerError::InvalidReply)
-                }
-            }
-        }
+        let client = self.gen_client()?;
+        client
+            .replace_failed_proxy(&failed_proxy_address)
+            .await
+            .map_err(|e| {
+                error!("replace_proxy: Failed to replace node: {}", e);
+                e.into()
+            })
     }
 
     async fn commit_migration_impl(
         &self,
         meta: MigrationTaskMeta,
     ) -> Result<(), MetaManipulationBrokerError> {
-        let url = self
-            .gen_url("/clusters/migrations")
-            .ok_or_else(|| MetaManipulationBrokerError::NoBroker)?;
-
-        let response = self
-            .client
-            .put(&url)
-            .json(&meta)
-            .send()
-            .await
-            .map_err(|e| {
-                error!("Failed to commit migration {:?}", e);
-                MetaManipulationBrokerError::RequestFailed
-            })?;
-
-        let status = response.status();
-
-        if status.is_success() || status.as_u16() == 404 {
-            Ok(())
-        } else {
-            error!("Failed to commit migration status code {:?}", status);
-            let result = response.text().await;
-            match result {
-                Ok(body) => {
-                    error!(
-                        "HttpMetaManipulationBroker::commit_migration Error body: {:?}",
-                        body
-                    );
-                    Err(MetaManipulationBrokerError::InvalidReply)
-                }
-                Err(e) => {
-                    error!(
-                        "HttpMetaManipulationBroker::commit_migration Failed to get body: {:?}",
-                        e
-                    );
-                    Err(MetaManipulationBrokerError::InvalidReply)
-                }
+        let client = self.gen_client()?;
+        match client.commit_migration(&meta).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.is_not_found() => Ok(()),
+            Err(e) => {
+                error!("Failed to commit migration {}", e);
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn finish_proxy_rebuilding_impl(
+        &self,
+        proxy_address: String,
+    ) -> Result<(), MetaManipulationBrokerError> {
+        let client = self.gen_client()?;
+        match client.finish_proxy_rebuilding(&proxy_address).await {
+            Ok(()) => Ok(()),
+            // The proxy could have been removed.
+            Err(e) if e.is_not_found() => Ok(()),
+            Err(e) => {
+                error!("Failed to finish proxy rebuilding {}", e);
+                Err(e.into())
             }
         }
     }
+
+    async fn report_proxy_stats_impl(
+        &self,
+        proxy_address: String,
+        stats: ProxyRuntimeStats,
+    ) -> Result<(), MetaManipulationBrokerError> {
+        let client = self.gen_client()?;
+        match client.report_proxy_stats(&proxy_address, &stats).await {
+            Ok(()) => Ok(()),
+            // The proxy could have been removed.
+            Err(e) if e.is_not_found() => Ok(()),
+            Err(e) => {
+                error!("Failed to report proxy stats {}", e);
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn report_migration_events_impl(
+        &self,
+        proxy_address: String,
+        events: Vec<MigrationTaskEvents>,
+    ) -> Result<(), MetaManipulationBrokerError> {
+        let client = self.gen_client()?;
+        match client.report_migration_events(&proxy_address, events).await {
+            Ok(()) => Ok(()),
+            // The proxy could have been removed.
+            Err(e) if e.is_not_found() => Ok(()),
+            Err(e) => {
+                error!("Failed to report migration events {}", e);
+                Err(e.into())
+            }
+        }
+    }
+}
+
+impl From<BrokerClientError> for MetaManipulationBrokerError {
+    fn from(err: BrokerClientError) -> Self {
+        match err {
+            BrokerClientError::RequestFailed(_) => Self::RequestFailed,
+            _ => Self::InvalidReply,
+        }
+    }
 }
 
 impl MetaManipulationBroker for HttpMetaManipulationBroker {
@@ -132,6 +137,34 @@ impl MetaManipulationBroker for HttpMetaManipulationBroker {
     ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>> {
         Box::pin(self.commit_migration_impl(meta))
     }
+
+    fn finish_proxy_rebuilding<'s>(
+        &'s self,
+        proxy_address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>> {
+        Box::pin(self.finish_proxy_rebuilding_impl(proxy_address))
+    }
+
+    fn report_proxy_stats<'s>(
+        &'s self,
+        proxy_address: String,
+        stats: ProxyRuntimeStats,
+    ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>> {
+        Box::pin(self.report_proxy_stats_impl(proxy_address, stats))
+    }
+
+    fn report_migration_events<'s>(
+        &'s self,
+        proxy_address: String,
+        events: Vec<MigrationTaskEvents>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), MetaManipulationBrokerError>> + Send + 's>> {
+        Box::pin(self.report_migration_events_impl(proxy_address, events))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MigrationEventsPayload {
+    pub tasks: Vec<MigrationTaskEvents>,
 }
 
 #[derive(Deserialize, Serialize)]
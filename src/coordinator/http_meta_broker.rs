@@ -1,6 +1,6 @@
 use super::broker::{MetaDataBroker, MetaDataBrokerError};
 use super::service::BrokerAddresses;
-use crate::broker::MEM_BROKER_API_VERSION;
+use crate::broker::{BrokerClient, BrokerClientError};
 use crate::common::cluster::{Cluster, ClusterName, Proxy};
 use crate::common::utils::vec_result_to_stream;
 use futures::{future, stream, Future, FutureExt, Stream, StreamExt};
@@ -27,13 +27,14 @@ impl HttpMetaBroker {
 }
 
 impl HttpMetaBroker {
-    fn gen_url(&self, path: &str) -> Option<String> {
+    fn gen_client(&self) -> Result<BrokerClient, MetaDataBrokerError> {
         let broker_addresses = self.broker_addresses.lease();
         let num = broker_addresses.len();
         let curr_index = self.broker_index.fetch_add(1, Ordering::Relaxed);
-        let broker = broker_addresses.get(curr_index % num)?;
-        let url = format!("http://{}{}{}", broker, MEM_BROKER_API_VERSION, path);
-        Some(url)
+        let broker = broker_addresses
+            .get(curr_index % num)
+            .ok_or(MetaDataBrokerError::NoBroker)?;
+        Ok(BrokerClient::new(self.client.clone(), broker.clone()))
     }
 
     async fn get_cluster_names_impl(
@@ -41,37 +42,22 @@ impl HttpMetaBroker {
         offset: usize,
         limit: usize,
     ) -> Result<Vec<ClusterName>, MetaDataBrokerError> {
-        let url = self
-            .gen_url("/clusters/names")
-            .ok_or_else(|| MetaDataBrokerError::NoBroker)?;
-        let url = format!("{}?offset={}&limit={}", url, offset, limit);
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("failed to get cluster names {:?}", e);
-            MetaDataBrokerError::RequestFailed
-        })?;
-        let ClusterNamesPayload { names } = response.json().await.map_err(|e| {
-            error!("failed to get cluster names from json {:?}", e);
-            MetaDataBrokerError::InvalidReply
-        })?;
-        Ok(names)
+        let client = self.gen_client()?;
+        client.get_cluster_names(offset, limit).await.map_err(|e| {
+            error!("failed to get cluster names {}", e);
+            e.into()
+        })
     }
 
     async fn get_cluster_impl(
         &self,
         name: ClusterName,
     ) -> Result<Option<Cluster>, MetaDataBrokerError> {
-        let url = self
-            .gen_url(&format!("/clusters/meta/{}", name))
-            .ok_or_else(|| MetaDataBrokerError::NoBroker)?;
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("failed to get cluster {:?}", e);
-            MetaDataBrokerError::RequestFailed
-        })?;
-        let ClusterPayload { cluster } = response.json().await.map_err(|e| {
-            error!("failed to get cluster from json {:?}", e);
-            MetaDataBrokerError::InvalidReply
-        })?;
-        Ok(cluster)
+        let client = self.gen_client()?;
+        client.get_cluster(&name).await.map_err(|e| {
+            error!("failed to get cluster {} {}", name, e);
+            e.into()
+        })
     }
 
     async fn get_proxy_addresses_impl(
@@ -79,34 +65,22 @@ impl HttpMetaBroker {
         offset: usize,
         limit: usize,
     ) -> Result<Vec<String>, MetaDataBrokerError> {
-        let url = self
-            .gen_url("/proxies/addresses")
-            .ok_or_else(|| MetaDataBrokerError::NoBroker)?;
-        let url = format!("{}?offset={}&limit={}", url, offset, limit);
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("failed to get proxy addresses {:?}", e);
-            MetaDataBrokerError::RequestFailed
-        })?;
-        let ProxyAddressesPayload { addresses } = response.json().await.map_err(|e| {
-            error!("failed to get proxy adddresses from json {:?}", e);
-            MetaDataBrokerError::InvalidReply
-        })?;
-        Ok(addresses)
+        let client = self.gen_client()?;
+        client
+            .get_proxy_addresses(offset, limit)
+            .await
+            .map_err(|e| {
+                error!("failed to get proxy addresses {}", e);
+                e.into()
+            })
     }
 
     async fn get_proxy_impl(&self, address: String) -> Result<Option<Proxy>, MetaDataBrokerError> {
-        let url = self
-            .gen_url(&format!("/proxies/meta/{}", address))
-            .ok_or_else(|| MetaDataBrokerError::NoBroker)?;
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("failed to get proxy {:?}", e);
-            MetaDataBrokerError::RequestFailed
-        })?;
-        let ProxyPayload { proxy } = response.json().await.map_err(move |e| {
-            error!("failed to get proxy {} from json {:?}", address, e);
-            MetaDataBrokerError::InvalidReply
-        })?;
-        Ok(proxy)
+        let client = self.gen_client()?;
+        client.get_proxy(&address).await.map_err(|e| {
+            error!("failed to get proxy {} {}", address, e);
+            e.into()
+        })
     }
 
     async fn add_failure_impl(
@@ -114,59 +88,47 @@ impl HttpMetaBroker {
         address: String,
         reporter_id: String,
     ) -> Result<(), MetaDataBrokerError> {
-        let url = self
-            .gen_url(&format!("/failures/{}/{}", address, reporter_id))
-            .ok_or_else(|| MetaDataBrokerError::NoBroker)?;
-        let response = self.client.post(&url).send().await.map_err(|e| {
-            error!("failed to add failures {:?}", e);
-            MetaDataBrokerError::RequestFailed
-        })?;
-        let status = response.status();
-        if status.is_success() {
-            Ok(())
-        } else {
-            let result = response.text().await;
-            match result {
-                Err(e) => {
-                    error!("Failed to get body: {:?}", e);
-                    Err(MetaDataBrokerError::InvalidReply)
-                }
-                Ok(body) => {
-                    error!("Error body: {:?}", body);
-                    Err(MetaDataBrokerError::InvalidReply)
-                }
-            }
-        }
+        let client = self.gen_client()?;
+        client
+            .add_failure(&address, &reporter_id)
+            .await
+            .map_err(|e| {
+                error!("failed to add failures {}", e);
+                e.into()
+            })
     }
 
     async fn get_failures_impl(&self) -> Result<Vec<String>, MetaDataBrokerError> {
-        let url = self
-            .gen_url("/failures")
-            .ok_or_else(|| MetaDataBrokerError::NoBroker)?;
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("Failed to get failures {:?}", e);
-            MetaDataBrokerError::RequestFailed
-        })?;
-        let FailuresPayload { addresses } = response.json().await.map_err(|e| {
-            error!("Failed to get failures from json {:?}", e);
-            MetaDataBrokerError::InvalidReply
-        })?;
-        Ok(addresses)
+        let client = self.gen_client()?;
+        client.get_failures().await.map_err(|e| {
+            error!("Failed to get failures {}", e);
+            e.into()
+        })
     }
 
     async fn get_failed_proxies_impl(&self) -> Result<Vec<String>, MetaDataBrokerError> {
-        let url = self
-            .gen_url("/proxies/failed/addresses")
-            .ok_or_else(|| MetaDataBrokerError::NoBroker)?;
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("Failed to get failed proxies {:?}", e);
-            MetaDataBrokerError::RequestFailed
-        })?;
-        let FailedProxiesPayload { addresses } = response.json().await.map_err(|e| {
-            error!("Failed to get failed proxies from json {:?}", e);
-            MetaDataBrokerError::InvalidReply
-        })?;
-        Ok(addresses)
+        let client = self.gen_client()?;
+        client.get_failed_proxies().await.map_err(|e| {
+            error!("Failed to get failed proxies {}", e);
+            e.into()
+        })
+    }
+
+    async fn get_rebuilding_proxies_impl(&self) -> Result<Vec<String>, MetaDataBrokerError> {
+        let client = self.gen_client()?;
+        client.get_rebuilding_proxies().await.map_err(|e| {
+            error!("Failed to get rebuilding proxies {}", e);
+            e.into()
+        })
+    }
+}
+
+impl From<BrokerClientError> for MetaDataBrokerError {
+    fn from(err: BrokerClientError) -> Self {
+        match err {
+            BrokerClientError::RequestFailed(_) => Self::RequestFailed,
+            _ => Self::InvalidReply,
+        }
     }
 }
 
@@ -247,6 +209,16 @@ impl MetaDataBroker for HttpMetaBroker {
                 .flatten_stream(),
         )
     }
+
+    fn get_rebuilding_proxies<'s>(
+        &'s self,
+    ) -> Pin<Box<dyn Stream<Item = Result<String, MetaDataBrokerError>> + Send + 's>> {
+        Box::pin(
+            self.get_rebuilding_proxies_impl()
+                .map(vec_result_to_stream)
+                .flatten_stream(),
+        )
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -278,3 +250,8 @@ pub struct FailuresPayload {
 pub struct FailedProxiesPayload {
     pub addresses: Vec<String>,
 }
+
+#[derive(Deserialize, Serialize)]
+pub struct RebuildingProxiesPayload {
+    pub addresses: Vec<String>,
+}
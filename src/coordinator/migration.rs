@@ -1,6 +1,10 @@
 use super::broker::MetaManipulationBroker;
-use super::core::{CoordinateError, MigrationCommitter, MigrationStateChecker};
-use crate::common::cluster::MigrationTaskMeta;
+use super::capability::get_proxy_capabilities;
+use super::core::{
+    CoordinateError, MigrationCommitter, MigrationEventCollector, MigrationEventReporter,
+    MigrationStateChecker, ProxyMigrationEvents,
+};
+use crate::common::cluster::{MigrationTaskEvents, MigrationTaskMeta};
 use crate::common::utils::vec_result_to_stream;
 use crate::protocol::{Array, BulkStr, Resp};
 use crate::protocol::{RedisClient, RedisClientFactory, RespVec};
@@ -122,15 +126,113 @@ impl<MB: MetaManipulationBroker> MigrationCommitter for BrokerMigrationCommitter
     }
 }
 
+pub struct MigrationEventRespCollector<F: RedisClientFactory> {
+    client_factory: Arc<F>,
+}
+
+impl<F: RedisClientFactory> MigrationEventRespCollector<F> {
+    pub fn new(client_factory: Arc<F>) -> Self {
+        Self { client_factory }
+    }
+
+    fn parse_events(reply: &RespVec) -> Option<Vec<MigrationTaskEvents>> {
+        let elements = match reply {
+            Resp::Arr(Array::Arr(elements)) => elements,
+            _ => return None,
+        };
+        let mut events = vec![];
+        for element in elements.iter() {
+            match element {
+                Resp::Bulk(BulkStr::Str(s)) => events.push(serde_json::from_slice(s).ok()?),
+                _ => return None,
+            }
+        }
+        Some(events)
+    }
+
+    async fn collect_impl(
+        &self,
+        address: String,
+    ) -> Result<Option<Vec<MigrationTaskEvents>>, CoordinateError> {
+        let mut client = self
+            .client_factory
+            .create_client(address.clone())
+            .await
+            .map_err(CoordinateError::Redis)?;
+        let capabilities = get_proxy_capabilities(&mut client).await?;
+        if !capabilities.supports_command("MIGRATIONLOG") {
+            debug!(
+                "skip collecting migration events from proxy {} of version {}",
+                address, capabilities.version
+            );
+            return Ok(None);
+        }
+        let migration_log_cmd = vec!["UMCTL".to_string(), "MIGRATIONLOG".to_string()]
+            .into_iter()
+            .map(String::into_bytes)
+            .collect();
+
+        let reply = client
+            .execute_single(migration_log_cmd)
+            .await
+            .map_err(CoordinateError::Redis)?;
+
+        Self::parse_events(&reply).map(Some).ok_or_else(|| {
+            error!("failed to send MIGRATIONLOG, invalid reply {:?}", reply);
+            CoordinateError::InvalidReply
+        })
+    }
+}
+
+impl<F: RedisClientFactory> MigrationEventCollector for MigrationEventRespCollector<F> {
+    fn collect<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<ProxyMigrationEvents, CoordinateError>> + Send + 's>>
+    {
+        Box::pin(self.collect_impl(address))
+    }
+}
+
+pub struct BrokerMigrationEventReporter<MB: MetaManipulationBroker> {
+    mani_broker: Arc<MB>,
+}
+
+impl<MB: MetaManipulationBroker> BrokerMigrationEventReporter<MB> {
+    pub fn new(mani_broker: Arc<MB>) -> Self {
+        Self { mani_broker }
+    }
+}
+
+impl<MB: MetaManipulationBroker> MigrationEventReporter for BrokerMigrationEventReporter<MB> {
+    fn report<'s>(
+        &'s self,
+        address: String,
+        events: Vec<MigrationTaskEvents>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CoordinateError>> + Send + 's>> {
+        Box::pin(
+            self.mani_broker
+                .report_migration_events(address.clone(), events)
+                .map_err(move |e| {
+                    error!("failed to report migration events of {} {:?}", address, e);
+                    CoordinateError::MetaMani(e)
+                }),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::broker::{MockMetaDataBroker, MockMetaManipulationBroker};
-    use super::super::core::{MigrationStateSynchronizer, ParMigrationStateSynchronizer};
+    use super::super::core::{
+        MigrationEventSynchronizer, MigrationStateSynchronizer, ParMigrationEventSynchronizer,
+        ParMigrationStateSynchronizer,
+    };
     use super::super::detector::BrokerProxiesRetriever;
     use super::super::sync::BrokerMetaRetriever;
     use super::*;
     use crate::common::cluster::{
-        ClusterName, MigrationMeta, Proxy, RangeList, SlotRange, SlotRangeTag,
+        ClusterName, MigrationMeta, Proxy, RangeList, RestorePolicy, SlotRange, SlotRangeTag,
     };
     use crate::coordinator::core::MockProxyMetaSender;
     use crate::protocol::{BinSafeStr, DummyRedisClientFactory, MockRedisClient};
@@ -184,6 +286,7 @@ mod tests {
                 src_node_address: "127.0.0.1:7000".to_string(),
                 dst_proxy_address: "127.0.0.1:6001".to_string(),
                 dst_node_address: "127.0.0.1:7001".to_string(),
+                restore_policy: RestorePolicy::default(),
             });
             let slot_range = SlotRange {
                 range_list: RangeList::try_from("1 233-666").unwrap(),
@@ -200,6 +303,7 @@ mod tests {
             src_node_address: "127.0.0.1:7000".to_string(),
             dst_proxy_address: "127.0.0.1:6001".to_string(),
             dst_node_address: "127.0.0.1:7001".to_string(),
+            restore_policy: RestorePolicy::default(),
         });
         let slot_range = SlotRange {
             range_list: RangeList::try_from("1 233-666").unwrap(),
@@ -292,4 +396,74 @@ mod tests {
         assert_eq!(res.len(), 1);
         res[0].as_ref().unwrap();
     }
+
+    const TASK_EVENTS_JSON: &str = r#"{"cluster_name":"mycluster","task":"mycluster MIGRATING 1 0-100 233","finished":true,"events":[{"time":1,"last_time":2,"kind":"Retry","detail":"pre_check not ready","count":2}]}"#;
+
+    fn create_event_client_func() -> impl RedisClient {
+        let mut mock_client = MockRedisClient::new();
+        let capabilities_cmd = vec![b"UMCTL".to_vec(), b"CAPABILITIES".to_vec()];
+        mock_client
+            .expect_execute_single()
+            .withf(move |command: &Vec<BinSafeStr>| command.eq(&capabilities_cmd))
+            .times(1)
+            .returning(move |_| {
+                let reply = Resp::Arr(Array::Arr(
+                    vec![
+                        "version: 0.9.0",
+                        "commands: INFOMGR MIGRATIONLOG",
+                        "features:",
+                    ]
+                    .into_iter()
+                    .map(|line| Resp::Bulk(BulkStr::Str(line.to_string().into_bytes())))
+                    .collect(),
+                ));
+                Box::pin(async { Ok(reply) })
+            });
+
+        let migration_log_cmd = vec![b"UMCTL".to_vec(), b"MIGRATIONLOG".to_vec()];
+        mock_client
+            .expect_execute_single()
+            .withf(move |command: &Vec<BinSafeStr>| command.eq(&migration_log_cmd))
+            .times(1)
+            .returning(move |_| {
+                let reply = Resp::Arr(Array::Arr(vec![Resp::Bulk(BulkStr::Str(
+                    TASK_EVENTS_JSON.to_string().into_bytes(),
+                ))]));
+                Box::pin(async { Ok(reply) })
+            });
+        mock_client
+    }
+
+    #[tokio::test]
+    async fn test_migration_event_sync() {
+        let mut mock_data_broker = MockMetaDataBroker::new();
+        mock_data_broker
+            .expect_get_failed_proxies()
+            .returning(|| Box::pin(stream::iter(vec![])));
+        mock_data_broker
+            .expect_get_proxy_addresses()
+            .returning(|| Box::pin(stream::iter(vec![Ok("127.0.0.1:6000".to_string())])));
+        let retriever = BrokerProxiesRetriever::new(Arc::new(mock_data_broker));
+
+        let factory = DummyRedisClientFactory::new(create_event_client_func);
+        let collector = MigrationEventRespCollector::new(Arc::new(factory));
+
+        let mut mock_mani_broker = MockMetaManipulationBroker::new();
+        mock_mani_broker
+            .expect_report_migration_events()
+            .withf(|address, events| {
+                address == "127.0.0.1:6000"
+                    && events.len() == 1
+                    && events[0].finished
+                    && events[0].events[0].count == 2
+            })
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(()) }));
+        let reporter = BrokerMigrationEventReporter::new(Arc::new(mock_mani_broker));
+
+        let sync = ParMigrationEventSynchronizer::new(retriever, collector, reporter);
+        let res: Vec<_> = sync.run().collect().await;
+        assert_eq!(res.len(), 1);
+        res[0].as_ref().unwrap();
+    }
 }
@@ -2,11 +2,17 @@
 mod api;
 #[allow(clippy::ptr_arg)]
 pub mod broker;
+mod capability;
 mod core;
 mod detector;
+// The impls generated by the derive macros of the pinned serde_derive are non-local.
+#[allow(non_local_definitions)]
 pub mod http_mani_broker;
+#[allow(non_local_definitions)]
 pub mod http_meta_broker;
 mod migration;
+mod rebuild;
 mod recover;
 pub mod service;
+mod stats;
 mod sync;
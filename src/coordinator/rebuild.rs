@@ -0,0 +1,260 @@
+use super::broker::{MetaDataBroker, MetaManipulationBroker};
+use super::capability::get_proxy_capabilities;
+use super::core::{CoordinateError, ProxiesRetriever, RebuildCommitter, ReplicaSyncChecker};
+use crate::common::proto::FEATURE_REPL_SYNC_STATE;
+use crate::protocol::{Array, BulkStr, Resp};
+use crate::protocol::{RedisClient, RedisClientFactory, RespVec};
+use futures::{Future, Stream, TryFutureExt, TryStreamExt};
+use std::pin::Pin;
+use std::str;
+use std::sync::Arc;
+
+pub struct BrokerRebuildingProxiesRetriever<B: MetaDataBroker> {
+    meta_data_broker: Arc<B>,
+}
+
+impl<B: MetaDataBroker> BrokerRebuildingProxiesRetriever<B> {
+    pub fn new(meta_data_broker: Arc<B>) -> Self {
+        Self { meta_data_broker }
+    }
+}
+
+impl<B: MetaDataBroker> ProxiesRetriever for BrokerRebuildingProxiesRetriever<B> {
+    fn retrieve_proxies<'s>(
+        &'s self,
+    ) -> Pin<Box<dyn Stream<Item = Result<String, CoordinateError>> + Send + 's>> {
+        Box::pin(
+            self.meta_data_broker
+                .get_rebuilding_proxies()
+                .map_err(CoordinateError::MetaData),
+        )
+    }
+}
+
+pub struct ReplicaSyncRespChecker<F: RedisClientFactory> {
+    client_factory: Arc<F>,
+}
+
+impl<F: RedisClientFactory> ReplicaSyncRespChecker<F> {
+    pub fn new(client_factory: Arc<F>) -> Self {
+        Self { client_factory }
+    }
+
+    // Returns None for the masters.
+    fn parse_replica_synced(element: &RespVec) -> Option<bool> {
+        let lines = match element {
+            Resp::Arr(Array::Arr(lines)) => lines,
+            _ => return None,
+        };
+        let mut is_replica = false;
+        let mut synced = false;
+        for line in lines.iter() {
+            let line = match line {
+                Resp::Bulk(BulkStr::Str(s)) => str::from_utf8(s).ok()?,
+                _ => return None,
+            };
+            match line.trim() {
+                "role:replica" => is_replica = true,
+                "synced:true" => synced = true,
+                _ => (),
+            }
+        }
+        if is_replica {
+            Some(synced)
+        } else {
+            None
+        }
+    }
+
+    async fn check_impl(&self, address: String) -> Result<bool, CoordinateError> {
+        let mut client = self
+            .client_factory
+            .create_client(address.clone())
+            .await
+            .map_err(CoordinateError::Redis)?;
+        // The older proxies do not report whether the replicas are synced.
+        let capabilities = get_proxy_capabilities(&mut client).await?;
+        if !capabilities.has_feature(FEATURE_REPL_SYNC_STATE) {
+            info!(
+                "proxy {} of version {} does not report sync state, treat it as synced",
+                address, capabilities.version
+            );
+            return Ok(true);
+        }
+        let info_repl_cmd = vec!["UMCTL".to_string(), "INFOREPL".to_string()]
+            .into_iter()
+            .map(String::into_bytes)
+            .collect();
+
+        let info_repl_reply = client
+            .execute_single(info_repl_cmd)
+            .await
+            .map_err(CoordinateError::Redis)?;
+
+        match info_repl_reply {
+            Resp::Arr(Array::Arr(arr)) => {
+                let states: Vec<bool> = arr.iter().filter_map(Self::parse_replica_synced).collect();
+                // The replicas could have not been set up.
+                Ok(!states.is_empty() && states.into_iter().all(|synced| synced))
+            }
+            reply => {
+                error!("failed to send INFOREPL, invalid reply {:?}", reply);
+                Err(CoordinateError::InvalidReply)
+            }
+        }
+    }
+}
+
+impl<F: RedisClientFactory> ReplicaSyncChecker for ReplicaSyncRespChecker<F> {
+    fn check<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<bool, CoordinateError>> + Send + 's>> {
+        Box::pin(self.check_impl(address))
+    }
+}
+
+pub struct BrokerRebuildCommitter<MB: MetaManipulationBroker> {
+    mani_broker: Arc<MB>,
+}
+
+impl<MB: MetaManipulationBroker> BrokerRebuildCommitter<MB> {
+    pub fn new(mani_broker: Arc<MB>) -> Self {
+        Self { mani_broker }
+    }
+}
+
+impl<MB: MetaManipulationBroker> RebuildCommitter for BrokerRebuildCommitter<MB> {
+    fn commit<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CoordinateError>> + Send + 's>> {
+        let address2 = address.clone();
+        Box::pin(
+            self.mani_broker
+                .finish_proxy_rebuilding(address.clone())
+                .map_err(move |e| {
+                    error!("failed to finish rebuilding {} {:?}", address2, e);
+                    CoordinateError::MetaMani(e)
+                })
+                .map_ok(move |()| {
+                    info!("successfully finish rebuilding {}", address);
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::broker::{MockMetaDataBroker, MockMetaManipulationBroker};
+    use super::super::core::{ParReplicaRebuildSynchronizer, ReplicaRebuildSynchronizer};
+    use super::*;
+    use crate::protocol::{BinSafeStr, DummyRedisClientFactory, MockRedisClient};
+    use futures::{stream, StreamExt};
+    use tokio;
+
+    fn gen_repl_entry(lines: &[&str]) -> RespVec {
+        Resp::Arr(Array::Arr(
+            lines
+                .iter()
+                .map(|line| Resp::Bulk(BulkStr::Str(format!("{}\n", line).into_bytes())))
+                .collect(),
+        ))
+    }
+
+    fn expect_capabilities(mock_client: &mut MockRedisClient, features: &'static str) {
+        let capabilities_cmd = vec![b"UMCTL".to_vec(), b"CAPABILITIES".to_vec()];
+        mock_client
+            .expect_execute_single()
+            .withf(move |command: &Vec<BinSafeStr>| command.eq(&capabilities_cmd))
+            .times(1)
+            .returning(move |_| {
+                let features_line = format!("features: {}", features);
+                let reply =
+                    gen_repl_entry(&["version: 0.9.0", "commands: INFOREPL", &features_line]);
+                Box::pin(async { Ok(reply) })
+            });
+    }
+
+    fn create_legacy_client_func() -> impl RedisClient {
+        let mut mock_client = MockRedisClient::new();
+        expect_capabilities(&mut mock_client, "");
+        mock_client
+    }
+
+    fn create_client_func(synced: bool) -> impl RedisClient {
+        let mut mock_client = MockRedisClient::new();
+        expect_capabilities(&mut mock_client, FEATURE_REPL_SYNC_STATE);
+
+        let info_repl_cmd = vec![b"UMCTL".to_vec(), b"INFOREPL".to_vec()];
+        mock_client
+            .expect_execute_single()
+            .withf(move |command: &Vec<BinSafeStr>| command.eq(&info_repl_cmd))
+            .times(1)
+            .returning(move |_| {
+                let synced_line = format!("synced:{}", synced);
+                let reply = Resp::Arr(Array::Arr(vec![
+                    gen_repl_entry(&[
+                        "cluster:mycluster",
+                        "role:master",
+                        "node_address:127.0.0.1:7000",
+                    ]),
+                    gen_repl_entry(&[
+                        "cluster:mycluster",
+                        "role:replica",
+                        "node_address:127.0.0.1:7001",
+                        "synced:true",
+                    ]),
+                    gen_repl_entry(&[
+                        "cluster:mycluster",
+                        "role:replica",
+                        "node_address:127.0.0.1:7002",
+                        &synced_line,
+                    ]),
+                ]));
+                Box::pin(async { Ok(reply) })
+            });
+
+        mock_client
+    }
+
+    #[tokio::test]
+    async fn test_replica_sync_checker() {
+        let factory = DummyRedisClientFactory::new(|| create_client_func(true));
+        let checker = ReplicaSyncRespChecker::new(Arc::new(factory));
+        assert!(checker.check("127.0.0.1:6000".to_string()).await.unwrap());
+
+        let factory = DummyRedisClientFactory::new(|| create_client_func(false));
+        let checker = ReplicaSyncRespChecker::new(Arc::new(factory));
+        assert!(!checker.check("127.0.0.1:6000".to_string()).await.unwrap());
+
+        let factory = DummyRedisClientFactory::new(create_legacy_client_func);
+        let checker = ReplicaSyncRespChecker::new(Arc::new(factory));
+        assert!(checker.check("127.0.0.1:6000".to_string()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_replica_rebuild_sync() {
+        let mut mock_data_broker = MockMetaDataBroker::new();
+        mock_data_broker
+            .expect_get_rebuilding_proxies()
+            .returning(|| Box::pin(stream::iter(vec![Ok("127.0.0.1:6000".to_string())])));
+        let retriever = BrokerRebuildingProxiesRetriever::new(Arc::new(mock_data_broker));
+
+        let factory = DummyRedisClientFactory::new(|| create_client_func(true));
+        let checker = ReplicaSyncRespChecker::new(Arc::new(factory));
+
+        let mut mock_mani_broker = MockMetaManipulationBroker::new();
+        mock_mani_broker
+            .expect_finish_proxy_rebuilding()
+            .withf(|address| address == "127.0.0.1:6000")
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(()) }));
+        let committer = BrokerRebuildCommitter::new(Arc::new(mock_mani_broker));
+
+        let sync = ParReplicaRebuildSynchronizer::new(retriever, checker, committer);
+        let res: Vec<_> = sync.run().collect().await;
+        assert_eq!(res.len(), 1);
+        res[0].as_ref().unwrap();
+    }
+}
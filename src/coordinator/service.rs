@@ -1,16 +1,25 @@
 use super::api::ApiService;
 use super::broker::{MetaDataBroker, MetaManipulationBroker};
 use super::core::{
-    CoordinateError, FailureDetector, FailureHandler, MigrationStateSynchronizer,
-    ParFailureDetector, ParFailureHandler, ParMigrationStateSynchronizer,
-    ProxyMetaRespSynchronizer, ProxyMetaSynchronizer,
+    CoordinateError, FailureDetector, FailureHandler, MigrationEventSynchronizer,
+    MigrationStateSynchronizer, ParFailureDetector, ParFailureHandler,
+    ParMigrationEventSynchronizer, ParMigrationStateSynchronizer, ParProxyStatsSynchronizer,
+    ParReplicaRebuildSynchronizer, ProxyMetaRespSynchronizer, ProxyMetaSynchronizer,
+    ProxyStatsSynchronizer, ReplicaRebuildSynchronizer,
 };
 use super::detector::{
     BrokerFailureReporter, BrokerOrderedProxiesRetriever, BrokerProxiesRetriever,
     PingFailureDetector,
 };
-use super::migration::{BrokerMigrationCommitter, MigrationStateRespChecker};
+use super::migration::{
+    BrokerMigrationCommitter, BrokerMigrationEventReporter, MigrationEventRespCollector,
+    MigrationStateRespChecker,
+};
+use super::rebuild::{
+    BrokerRebuildCommitter, BrokerRebuildingProxiesRetriever, ReplicaSyncRespChecker,
+};
 use super::recover::{BrokerProxyFailureRetriever, ReplaceNodeHandler};
+use super::stats::{BrokerProxyStatsReporter, ProxyStatsRespCollector};
 use super::sync::{BrokerMetaRetriever, ProxyMetaRespSender};
 use crate::common::utils::ThreadSafe;
 use crate::protocol::RedisClientFactory;
@@ -83,6 +92,9 @@ impl<DB: MetaDataBroker + ThreadSafe, MB: MetaManipulationBroker, F: RedisClient
             Box::pin(self.loop_proxy_sync()),
             Box::pin(self.loop_failure_handler()),
             Box::pin(self.loop_migration_sync()),
+            Box::pin(self.loop_rebuild_sync()),
+            Box::pin(self.loop_stats_sync()),
+            Box::pin(self.loop_migration_event_sync()),
             Box::pin(self.api_service.run()),
         ];
 
@@ -137,6 +149,39 @@ impl<DB: MetaDataBroker + ThreadSafe, MB: MetaManipulationBroker, F: RedisClient
         )
     }
 
+    fn gen_replica_rebuild_synchronizer(
+        data_broker: Arc<DB>,
+        mani_broker: Arc<MB>,
+        client_factory: Arc<F>,
+    ) -> impl ReplicaRebuildSynchronizer {
+        let proxy_retriever = BrokerRebuildingProxiesRetriever::new(data_broker);
+        let checker = ReplicaSyncRespChecker::new(client_factory);
+        let committer = BrokerRebuildCommitter::new(mani_broker);
+        ParReplicaRebuildSynchronizer::new(proxy_retriever, checker, committer)
+    }
+
+    fn gen_proxy_stats_synchronizer(
+        data_broker: Arc<DB>,
+        mani_broker: Arc<MB>,
+        client_factory: Arc<F>,
+    ) -> impl ProxyStatsSynchronizer {
+        let proxy_retriever = BrokerProxiesRetriever::new(data_broker);
+        let collector = ProxyStatsRespCollector::new(client_factory);
+        let reporter = BrokerProxyStatsReporter::new(mani_broker);
+        ParProxyStatsSynchronizer::new(proxy_retriever, collector, reporter)
+    }
+
+    fn gen_migration_event_synchronizer(
+        data_broker: Arc<DB>,
+        mani_broker: Arc<MB>,
+        client_factory: Arc<F>,
+    ) -> impl MigrationEventSynchronizer {
+        let proxy_retriever = BrokerProxiesRetriever::new(data_broker);
+        let collector = MigrationEventRespCollector::new(client_factory);
+        let reporter = BrokerMigrationEventReporter::new(mani_broker);
+        ParMigrationEventSynchronizer::new(proxy_retriever, collector, reporter)
+    }
+
     async fn loop_detect(&self) -> Result<(), CoordinateError> {
         let data_broker = self.data_broker.clone();
         let client_factory = self.client_factory.clone();
@@ -214,4 +259,71 @@ impl<DB: MetaDataBroker + ThreadSafe, MB: MetaManipulationBroker, F: RedisClient
             Delay::new(Duration::from_secs(1)).await;
         }
     }
+
+    async fn loop_rebuild_sync(&self) -> Result<(), CoordinateError> {
+        let data_broker = self.data_broker.clone();
+        let mani_broker = self.mani_broker.clone();
+        let client_factory = self.client_factory.clone();
+        loop {
+            trace!("start handling replica rebuilding");
+            defer!(trace!("handling replica rebuilding finished a round"));
+            let sync = Self::gen_replica_rebuild_synchronizer(
+                data_broker.clone(),
+                mani_broker.clone(),
+                client_factory.clone(),
+            );
+            let mut s = sync.run();
+            while let Some(r) = s.next().await {
+                if let Err(e) = r {
+                    error!("replica rebuild sync stream err {:?}", e)
+                }
+            }
+            Delay::new(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn loop_stats_sync(&self) -> Result<(), CoordinateError> {
+        let data_broker = self.data_broker.clone();
+        let mani_broker = self.mani_broker.clone();
+        let client_factory = self.client_factory.clone();
+        loop {
+            trace!("start collecting proxy stats");
+            defer!(trace!("collecting proxy stats finished a round"));
+            let sync = Self::gen_proxy_stats_synchronizer(
+                data_broker.clone(),
+                mani_broker.clone(),
+                client_factory.clone(),
+            );
+            let mut s = sync.run();
+            while let Some(r) = s.next().await {
+                if let Err(e) = r {
+                    error!("proxy stats sync stream err {:?}", e)
+                }
+            }
+            Delay::new(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn loop_migration_event_sync(&self) -> Result<(), CoordinateError> {
+        let data_broker = self.data_broker.clone();
+        let mani_broker = self.mani_broker.clone();
+        let client_factory = self.client_factory.clone();
+        loop {
+            trace!("start collecting migration events");
+            defer!(trace!("collecting migration events finished a round"));
+            let sync = Self::gen_migration_event_synchronizer(
+                data_broker.clone(),
+                mani_broker.clone(),
+                client_factory.clone(),
+            );
+            let mut s = sync.run();
+            while let Some(r) = s.next().await {
+                if let Err(e) = r {
+                    error!("migration event sync stream err {:?}", e)
+                }
+            }
+            // The events are only for the post-incident analysis.
+            Delay::new(Duration::from_secs(5)).await;
+        }
+    }
 }
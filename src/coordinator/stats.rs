@@ -0,0 +1,271 @@
+use super::broker::MetaManipulationBroker;
+use super::capability::get_proxy_capabilities;
+use super::core::{CoordinateError, ProxyStatsCollector, ProxyStatsReporter};
+use crate::common::cluster::{ProxyRuntimeStats, SLOT_LOAD_BUCKETS};
+use crate::protocol::{Array, BulkStr, Resp};
+use crate::protocol::{RedisClient, RedisClientFactory, RespVec};
+use futures::{Future, TryFutureExt};
+use std::pin::Pin;
+use std::str;
+use std::sync::Arc;
+
+pub struct ProxyStatsRespCollector<F: RedisClientFactory> {
+    client_factory: Arc<F>,
+}
+
+impl<F: RedisClientFactory> ProxyStatsRespCollector<F> {
+    pub fn new(client_factory: Arc<F>) -> Self {
+        Self { client_factory }
+    }
+
+    // The unknown fields are ignored for the proxies of other versions.
+    fn parse_stats(reply: &RespVec) -> Option<ProxyRuntimeStats> {
+        let lines = match reply {
+            Resp::Arr(Array::Arr(lines)) => lines,
+            _ => return None,
+        };
+        let mut stats = ProxyRuntimeStats::default();
+        for line in lines.iter() {
+            let line = match line {
+                Resp::Bulk(BulkStr::Str(s)) => str::from_utf8(s).ok()?,
+                _ => return None,
+            };
+            let mut kv = line.splitn(2, ':');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim().parse::<u64>().ok()?),
+                _ => return None,
+            };
+            match key {
+                "commands_processed" => stats.commands_processed = value,
+                "error_replies" => stats.error_replies = value,
+                "connected_sessions" => stats.connected_sessions = value,
+                "used_memory_sessions" => stats.used_memory_sessions = value,
+                "migrating_tasks" => stats.migrating_tasks = value,
+                "committed_migrating_tasks" => stats.committed_migrating_tasks = value,
+                "replication_lag" => stats.replication_lag = value,
+                "slot_load_buckets" if value as usize == SLOT_LOAD_BUCKETS => {
+                    stats.slot_reads = vec![0; SLOT_LOAD_BUCKETS];
+                    stats.slot_writes = vec![0; SLOT_LOAD_BUCKETS];
+                }
+                _ => Self::parse_slot_load(&mut stats, key, value),
+            }
+        }
+        Some(stats)
+    }
+
+    // `slot_reads_<bucket>` and `slot_writes_<bucket>` come after `slot_load_buckets`.
+    fn parse_slot_load(stats: &mut ProxyRuntimeStats, key: &str, value: u64) {
+        let (buckets, bucket) = if let Some(bucket) = key.strip_prefix("slot_reads_") {
+            (&mut stats.slot_reads, bucket)
+        } else if let Some(bucket) = key.strip_prefix("slot_writes_") {
+            (&mut stats.slot_writes, bucket)
+        } else {
+            return;
+        };
+        if let Some(counter) = bucket
+            .parse::<usize>()
+            .ok()
+            .and_then(|bucket| buckets.get_mut(bucket))
+        {
+            *counter = value;
+        }
+    }
+
+    async fn collect_impl(
+        &self,
+        address: String,
+    ) -> Result<Option<ProxyRuntimeStats>, CoordinateError> {
+        let mut client = self
+            .client_factory
+            .create_client(address.clone())
+            .await
+            .map_err(CoordinateError::Redis)?;
+        let capabilities = get_proxy_capabilities(&mut client).await?;
+        if !capabilities.supports_command("INFOSTATS") {
+            debug!(
+                "skip collecting stats from proxy {} of version {}",
+                address, capabilities.version
+            );
+            return Ok(None);
+        }
+        let info_stats_cmd = vec!["UMCTL".to_string(), "INFOSTATS".to_string()]
+            .into_iter()
+            .map(String::into_bytes)
+            .collect();
+
+        let reply = client
+            .execute_single(info_stats_cmd)
+            .await
+            .map_err(CoordinateError::Redis)?;
+
+        Self::parse_stats(&reply).map(Some).ok_or_else(|| {
+            error!("failed to send INFOSTATS, invalid reply {:?}", reply);
+            CoordinateError::InvalidReply
+        })
+    }
+}
+
+impl<F: RedisClientFactory> ProxyStatsCollector for ProxyStatsRespCollector<F> {
+    fn collect<'s>(
+        &'s self,
+        address: String,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<ProxyRuntimeStats>, CoordinateError>> + Send + 's>>
+    {
+        Box::pin(self.collect_impl(address))
+    }
+}
+
+pub struct BrokerProxyStatsReporter<MB: MetaManipulationBroker> {
+    mani_broker: Arc<MB>,
+}
+
+impl<MB: MetaManipulationBroker> BrokerProxyStatsReporter<MB> {
+    pub fn new(mani_broker: Arc<MB>) -> Self {
+        Self { mani_broker }
+    }
+}
+
+impl<MB: MetaManipulationBroker> ProxyStatsReporter for BrokerProxyStatsReporter<MB> {
+    fn report<'s>(
+        &'s self,
+        address: String,
+        stats: ProxyRuntimeStats,
+    ) -> Pin<Box<dyn Future<Output = Result<(), CoordinateError>> + Send + 's>> {
+        Box::pin(
+            self.mani_broker
+                .report_proxy_stats(address.clone(), stats)
+                .map_err(move |e| {
+                    error!("failed to report stats of {} {:?}", address, e);
+                    CoordinateError::MetaMani(e)
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::broker::{MockMetaDataBroker, MockMetaManipulationBroker};
+    use super::super::core::{ParProxyStatsSynchronizer, ProxyStatsSynchronizer};
+    use super::super::detector::BrokerProxiesRetriever;
+    use super::*;
+    use crate::protocol::{BinSafeStr, DummyRedisClientFactory, MockRedisClient};
+    use futures::{stream, StreamExt};
+    use tokio;
+
+    fn gen_lines_reply(lines: Vec<&str>) -> RespVec {
+        Resp::Arr(Array::Arr(
+            lines
+                .into_iter()
+                .map(|line| Resp::Bulk(BulkStr::Str(line.to_string().into_bytes())))
+                .collect(),
+        ))
+    }
+
+    fn expect_capabilities(mock_client: &mut MockRedisClient, commands: &'static str) {
+        let capabilities_cmd = vec![b"UMCTL".to_vec(), b"CAPABILITIES".to_vec()];
+        mock_client
+            .expect_execute_single()
+            .withf(move |command: &Vec<BinSafeStr>| command.eq(&capabilities_cmd))
+            .times(1)
+            .returning(move |_| {
+                let commands_line = format!("commands: {}", commands);
+                let reply = gen_lines_reply(vec!["version: 0.9.0", &commands_line, "features:"]);
+                Box::pin(async { Ok(reply) })
+            });
+    }
+
+    fn create_legacy_client_func() -> impl RedisClient {
+        let mut mock_client = MockRedisClient::new();
+        expect_capabilities(&mut mock_client, "INFO INFOREPL SETCLUSTER");
+        mock_client
+    }
+
+    fn create_client_func() -> impl RedisClient {
+        let mut mock_client = MockRedisClient::new();
+        expect_capabilities(&mut mock_client, "INFO INFOSTATS SETCLUSTER");
+
+        let info_stats_cmd = vec![b"UMCTL".to_vec(), b"INFOSTATS".to_vec()];
+        mock_client
+            .expect_execute_single()
+            .withf(move |command: &Vec<BinSafeStr>| command.eq(&info_stats_cmd))
+            .times(1)
+            .returning(move |_| {
+                let reply = gen_lines_reply(vec![
+                    "commands_processed: 100",
+                    "error_replies: 3",
+                    "connected_sessions: 5",
+                    "used_memory_sessions: 1024",
+                    "migrating_tasks: 2",
+                    "committed_migrating_tasks: 1",
+                    "replication_lag: 3",
+                    "slot_load_buckets: 256",
+                    "slot_reads_0: 60",
+                    "slot_writes_255: 40",
+                    "slot_reads_256: 1",
+                    "unknown_field: 7",
+                ]);
+                Box::pin(async { Ok(reply) })
+            });
+
+        mock_client
+    }
+
+    fn expected_stats() -> ProxyRuntimeStats {
+        let mut slot_reads = vec![0; SLOT_LOAD_BUCKETS];
+        slot_reads[0] = 60;
+        let mut slot_writes = vec![0; SLOT_LOAD_BUCKETS];
+        slot_writes[SLOT_LOAD_BUCKETS - 1] = 40;
+        ProxyRuntimeStats {
+            commands_processed: 100,
+            error_replies: 3,
+            connected_sessions: 5,
+            used_memory_sessions: 1024,
+            migrating_tasks: 2,
+            committed_migrating_tasks: 1,
+            replication_lag: 3,
+            slot_reads,
+            slot_writes,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_stats_collector() {
+        let factory = DummyRedisClientFactory::new(create_client_func);
+        let collector = ProxyStatsRespCollector::new(Arc::new(factory));
+        let stats = collector.collect("127.0.0.1:6000".to_string()).await;
+        assert_eq!(stats.unwrap(), Some(expected_stats()));
+
+        let factory = DummyRedisClientFactory::new(create_legacy_client_func);
+        let collector = ProxyStatsRespCollector::new(Arc::new(factory));
+        let stats = collector.collect("127.0.0.1:6000".to_string()).await;
+        assert_eq!(stats.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_stats_sync() {
+        let mut mock_data_broker = MockMetaDataBroker::new();
+        mock_data_broker
+            .expect_get_failed_proxies()
+            .returning(|| Box::pin(stream::iter(vec![])));
+        mock_data_broker
+            .expect_get_proxy_addresses()
+            .returning(|| Box::pin(stream::iter(vec![Ok("127.0.0.1:6000".to_string())])));
+        let retriever = BrokerProxiesRetriever::new(Arc::new(mock_data_broker));
+
+        let factory = DummyRedisClientFactory::new(create_client_func);
+        let collector = ProxyStatsRespCollector::new(Arc::new(factory));
+
+        let mut mock_mani_broker = MockMetaManipulationBroker::new();
+        mock_mani_broker
+            .expect_report_proxy_stats()
+            .withf(|address, stats| address == "127.0.0.1:6000" && stats == &expected_stats())
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(()) }));
+        let reporter = BrokerProxyStatsReporter::new(Arc::new(mock_mani_broker));
+
+        let sync = ParProxyStatsSynchronizer::new(retriever, collector, reporter);
+        let res: Vec<_> = sync.run().collect().await;
+        assert_eq!(res.len(), 1);
+        res[0].as_ref().unwrap();
+    }
+}
@@ -1,15 +1,23 @@
 use super::broker::MetaDataBroker;
+use super::capability::get_proxy_capabilities;
 use super::core::{CoordinateError, ProxyMetaRetriever, ProxyMetaSender};
-use crate::common::cluster::{ClusterName, Proxy, Role, SlotRange};
-use crate::common::proto::{ClusterConfigMap, ClusterMapFlags, ProxyClusterMap, ProxyClusterMeta};
+use crate::common::cluster::{ClusterName, Proxy, ProxyTlsMeta, Role, SlotRange};
+use crate::common::proto::{
+    ClusterConfigMap, ClusterMapFlags, ProxyClusterMap, ProxyClusterMeta, FEATURE_COMPACT_META,
+    FEATURE_FORCE_RESYNC,
+};
 use crate::common::response::{OK_REPLY, OLD_EPOCH_REPLY};
-use crate::protocol::{RedisClient, RedisClientFactory, Resp};
+use crate::protocol::{BinSafeStr, RedisClient, RedisClientFactory, Resp};
 use crate::replication::replicator::{encode_repl_meta, MasterMeta, ReplicaMeta, ReplicatorMeta};
 use futures::{Future, TryFutureExt};
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
 
+// Only use the compact encoding for the large metadata
+// so that it's still readable in most cases.
+const COMPACT_META_MIN_SLOT_RANGES: usize = 256;
+
 pub struct ProxyMetaRespSender<F: RedisClientFactory> {
     client_factory: Arc<F>,
 }
@@ -27,19 +35,86 @@ impl<F: RedisClientFactory> ProxyMetaRespSender<F> {
             .create_client(proxy.get_address().to_string())
             .await
             .map_err(CoordinateError::Redis)?;
+        // Only check the capabilities when the optional features are needed.
+        let slot_range_num = get_slot_range_num(&proxy);
+        let proxy_address = proxy.get_address().to_string();
+        let tls = proxy.get_tls().cloned();
+        let capabilities = if proxy.is_force_resync()
+            || slot_range_num >= COMPACT_META_MIN_SLOT_RANGES
+            || tls.is_some()
+        {
+            Some(get_proxy_capabilities(&mut client).await?)
+        } else {
+            None
+        };
+        let has_feature = |feature: &str| {
+            capabilities
+                .as_ref()
+                .map(|c| c.has_feature(feature))
+                .unwrap_or(false)
+        };
+        if proxy.is_force_resync() && !has_feature(FEATURE_FORCE_RESYNC) {
+            warn!(
+                "proxy {} does not support force resync",
+                proxy.get_address()
+            );
+        }
+        let compact =
+            slot_range_num >= COMPACT_META_MIN_SLOT_RANGES && has_feature(FEATURE_COMPACT_META);
+
+        // Only force it when the epoch of the proxy is still different
+        // so that the proxy will not keep resetting the same metadata.
+        let force = proxy.is_force_resync()
+            && has_feature(FEATURE_FORCE_RESYNC)
+            && get_proxy_epoch(&mut client).await? != proxy.get_epoch();
+        if force {
+            warn!(
+                "force resync proxy {} to epoch {}",
+                proxy.get_address(),
+                proxy.get_epoch()
+            );
+        }
+        let flags = ClusterMapFlags { force };
         let proxy_with_only_masters = filter_proxy_masters(proxy.clone());
+        let mut proxy_cluster_meta =
+            generate_proxy_cluster_meta(flags.clone(), proxy_with_only_masters);
+        if let Err(err) = proxy_cluster_meta.normalize() {
+            error!(
+                "invalid metadata for proxy {}: {}",
+                proxy.get_address(),
+                err
+            );
+            return Err(CoordinateError::InvalidMetadata);
+        }
         send_meta(
             &mut client,
             "SETREPL".to_string(),
-            generate_repl_meta_cmd_args(proxy, ClusterMapFlags { force: false }),
-        )
-        .await?;
-        send_meta(
-            &mut client,
-            "SETCLUSTER".to_string(),
-            generate_proxy_meta_cmd_args(ClusterMapFlags { force: false }, proxy_with_only_masters),
+            to_bin_args(generate_repl_meta_cmd_args(proxy, flags)),
         )
         .await?;
+        let proxy_meta_args = if compact {
+            proxy_cluster_meta.to_compact_args()
+        } else {
+            to_bin_args(proxy_cluster_meta.to_args())
+        };
+        send_meta(&mut client, "SETCLUSTER".to_string(), proxy_meta_args).await?;
+
+        if let Some(tls) = tls {
+            let supported = capabilities
+                .as_ref()
+                .map(|c| c.supports_command("RELOADTLS"))
+                .unwrap_or(false);
+            if supported {
+                send_meta(
+                    &mut client,
+                    "RELOADTLS".to_string(),
+                    generate_reload_tls_args(&tls),
+                )
+                .await?;
+            } else {
+                warn!("proxy {} does not support RELOADTLS", proxy_address);
+            }
+        }
         Ok(())
     }
 }
@@ -91,7 +166,26 @@ impl<B: MetaDataBroker> ProxyMetaRetriever for BrokerMetaRetriever<B> {
     }
 }
 
-fn generate_proxy_meta_cmd_args(flags: ClusterMapFlags, proxy: Proxy) -> Vec<String> {
+fn get_slot_range_num(proxy: &Proxy) -> usize {
+    let local: usize = proxy.get_nodes().iter().map(|n| n.get_slots().len()).sum();
+    let peer: usize = proxy.get_peers().iter().map(|p| p.slots.len()).sum();
+    local + peer
+}
+
+// The proxy only loads the file again when the version changes.
+fn generate_reload_tls_args(tls: &ProxyTlsMeta) -> Vec<BinSafeStr> {
+    vec![
+        tls.pkcs12_file.clone().into_bytes(),
+        tls.password.clone().into_bytes(),
+        tls.version.to_string().into_bytes(),
+    ]
+}
+
+fn to_bin_args(args: Vec<String>) -> Vec<BinSafeStr> {
+    args.into_iter().map(String::into_bytes).collect()
+}
+
+fn generate_proxy_cluster_meta(flags: ClusterMapFlags, proxy: Proxy) -> ProxyClusterMeta {
     let epoch = proxy.get_epoch();
     let clusters_config = ClusterConfigMap::new(proxy.get_clusters_config().clone());
 
@@ -115,26 +209,28 @@ fn generate_proxy_meta_cmd_args(flags: ClusterMapFlags, proxy: Proxy) -> Vec<Str
     }
     let local = ProxyClusterMap::new(cluster_map);
 
-    let proxy_cluster_meta = ProxyClusterMeta::new(epoch, flags, local, peer, clusters_config);
-    proxy_cluster_meta.to_args()
+    ProxyClusterMeta::new(epoch, flags, local, peer, clusters_config)
 }
 
 // sub_command should be SETCLUSTER, SETREPL
 async fn send_meta<C: RedisClient>(
     client: &mut C,
     sub_command: String,
-    args: Vec<String>,
+    args: Vec<BinSafeStr>,
 ) -> Result<(), CoordinateError> {
-    trace!("sending meta {} {:?}", sub_command, args);
-    let mut cmd = vec!["UMCTL".to_string(), sub_command.clone()];
+    trace!(
+        "sending meta {} {:?}",
+        sub_command,
+        args.iter()
+            .map(|arg| String::from_utf8_lossy(arg))
+            .collect::<Vec<_>>()
+    );
+    let mut cmd = vec![b"UMCTL".to_vec(), sub_command.clone().into_bytes()];
     cmd.extend(args);
-    let resp = client
-        .execute_single(cmd.into_iter().map(String::into_bytes).collect())
-        .await
-        .map_err(|e| {
-            error!("failed to send meta data of proxy {:?}", e);
-            CoordinateError::Redis(e)
-        })?;
+    let resp = client.execute_single(cmd).await.map_err(|e| {
+        error!("failed to send meta data of proxy {:?}", e);
+        CoordinateError::Redis(e)
+    })?;
     match resp {
         Resp::Error(err_str) => {
             if err_str == OLD_EPOCH_REPLY.as_bytes() {
@@ -157,6 +253,23 @@ async fn send_meta<C: RedisClient>(
     }
 }
 
+async fn get_proxy_epoch<C: RedisClient>(client: &mut C) -> Result<u64, CoordinateError> {
+    let cmd = vec![b"UMCTL".to_vec(), b"GETEPOCH".to_vec()];
+    let resp = client
+        .execute_single(cmd)
+        .await
+        .map_err(CoordinateError::Redis)?;
+    match resp {
+        Resp::Integer(int_bytes) => {
+            btoi::btoi::<u64>(&int_bytes).map_err(|_| CoordinateError::InvalidReply)
+        }
+        other => {
+            error!("invalid UMCTL GETEPOCH reply {:?}", other);
+            Err(CoordinateError::InvalidReply)
+        }
+    }
+}
+
 fn generate_repl_meta_cmd_args(proxy: Proxy, flags: ClusterMapFlags) -> Vec<String> {
     let epoch = proxy.get_epoch();
 
@@ -211,8 +324,10 @@ fn generate_repl_meta_cmd_args(proxy: Proxy, flags: ClusterMapFlags) -> Vec<Stri
     let repl_meta = ReplicatorMeta {
         epoch,
         flags,
+        diff: false,
         masters,
         replicas,
+        removed: vec![],
     };
 
     encode_repl_meta(repl_meta)
@@ -226,7 +341,9 @@ mod tests {
     use super::*;
     use crate::common::cluster::{Node, RangeList, ReplMeta, ReplPeer, SlotRange, SlotRangeTag};
     use crate::common::config::ClusterConfig;
-    use crate::protocol::{BinSafeStr, DummyRedisClientFactory, MockRedisClient, Resp};
+    use crate::protocol::{
+        Array, BinSafeStr, BulkStr, DummyRedisClientFactory, MockRedisClient, Resp,
+    };
     use futures::{stream, StreamExt};
     use std::convert::TryFrom;
     use std::sync::atomic::{AtomicUsize, Ordering};
@@ -341,7 +458,7 @@ mod tests {
         let res = send_meta(
             &mut mock_client,
             "SETCLUSTER".to_string(),
-            vec!["test_args".to_string()],
+            vec![b"test_args".to_vec()],
         )
         .await;
         assert!(res.is_ok());
@@ -395,6 +512,80 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    fn gen_testing_tls() -> ProxyTlsMeta {
+        ProxyTlsMeta {
+            pkcs12_file: "/path/to/identity.p12".to_string(),
+            password: "pwd".to_string(),
+            version: 3,
+        }
+    }
+
+    fn create_tls_client_func() -> impl RedisClient {
+        let mut mock_client = MockRedisClient::new();
+        let sub_cmd_is = |sub_cmd: &'static [u8]| {
+            move |command: &Vec<BinSafeStr>| command.get(1).map(|c| c.as_slice()) == Some(sub_cmd)
+        };
+        mock_client
+            .expect_execute_single()
+            .withf(sub_cmd_is(b"CAPABILITIES"))
+            .times(1)
+            .returning(|_| {
+                let lines = vec![
+                    "version: 0.3.0",
+                    "commands: SETCLUSTER SETREPL RELOADTLS",
+                    "features: ",
+                ];
+                let reply = Resp::Arr(Array::Arr(
+                    lines
+                        .into_iter()
+                        .map(|line| Resp::Bulk(BulkStr::Str(line.to_string().into_bytes())))
+                        .collect(),
+                ));
+                Box::pin(async { Ok(reply) })
+            });
+        mock_client
+            .expect_execute_single()
+            .withf(sub_cmd_is(b"SETREPL"))
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(Resp::Simple(b"OK".to_vec())) }));
+        mock_client
+            .expect_execute_single()
+            .withf(sub_cmd_is(b"SETCLUSTER"))
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(Resp::Simple(b"OK".to_vec())) }));
+        let mut reload_tls_cmd = vec![b"UMCTL".to_vec(), b"RELOADTLS".to_vec()];
+        reload_tls_cmd.extend(generate_reload_tls_args(&gen_testing_tls()));
+        mock_client
+            .expect_execute_single()
+            .withf(move |command: &Vec<BinSafeStr>| command.eq(&reload_tls_cmd))
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(Resp::Simple(b"OK".to_vec())) }));
+        mock_client
+    }
+
+    #[tokio::test]
+    async fn test_meta_resp_sender_with_tls() {
+        let client_factory = DummyRedisClientFactory::new(create_tls_client_func);
+        let sender = ProxyMetaRespSender::new(Arc::new(client_factory));
+        let mut proxy = gen_testing_proxy(Role::Master);
+        proxy.set_tls(Some(gen_testing_tls()));
+        let res = sender.send_meta(proxy).await;
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_generate_reload_tls_args() {
+        let args = generate_reload_tls_args(&gen_testing_tls());
+        assert_eq!(
+            args,
+            vec![
+                b"/path/to/identity.p12".to_vec(),
+                b"pwd".to_vec(),
+                b"3".to_vec()
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_meta_retriever() {
         let proxy_addr = "127.0.0.1:6000";
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+struct BucketState {
+    // Could be negative after sending a large batch.
+    available: f64,
+    last_time: Instant,
+}
+
+// A token bucket in bytes shared by the migration tasks of the same node.
+// The rate is passed in every time so that the config changes apply to the running tasks.
+pub struct BandwidthLimiter {
+    state: Mutex<BucketState>,
+}
+
+impl Default for BandwidthLimiter {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(BucketState {
+                available: 0.0,
+                last_time: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl BandwidthLimiter {
+    // Returns how long the caller should wait after sending the bytes.
+    // Zero rate means no limitation.
+    pub fn acquire(&self, bytes: u64, rate: u64) -> Duration {
+        if rate == 0 {
+            return Duration::from_secs(0);
+        }
+        let rate = rate as f64;
+        let mut state = self.state.lock().expect("BandwidthLimiter::acquire");
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_time).as_secs_f64();
+        state.last_time = now;
+        // Burst for at most one second.
+        state.available = (state.available + elapsed * rate).min(rate);
+        state.available -= bytes as f64;
+        if state.available >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-state.available / rate)
+        }
+    }
+}
+
+// node address => limiter
+// The limiters are dropped after all the tasks using them are done.
+#[derive(Default)]
+pub struct BandwidthLimiterMap {
+    limiters: Mutex<HashMap<String, Weak<BandwidthLimiter>>>,
+}
+
+impl BandwidthLimiterMap {
+    pub fn get(&self, node_address: &str) -> Arc<BandwidthLimiter> {
+        let mut limiters = self.limiters.lock().expect("BandwidthLimiterMap::get");
+        if let Some(limiter) = limiters.get(node_address).and_then(Weak::upgrade) {
+            return limiter;
+        }
+        limiters.retain(|_, limiter| limiter.strong_count() > 0);
+        let limiter = Arc::new(BandwidthLimiter::default());
+        limiters.insert(node_address.to_string(), Arc::downgrade(&limiter));
+        limiter
+    }
+
+    pub fn get_node_num(&self) -> usize {
+        self.limiters
+            .lock()
+            .expect("BandwidthLimiterMap::get_node_num")
+            .values()
+            .filter(|limiter| limiter.strong_count() > 0)
+            .count()
+    }
+}
+
+// The caller waits for the slowest one of the source and destination nodes.
+pub fn acquire_all(limiters: &[Arc<BandwidthLimiter>], bytes: u64, rate: u64) -> Duration {
+    limiters
+        .iter()
+        .map(|limiter| limiter.acquire(bytes, rate))
+        .max()
+        .unwrap_or_else(|| Duration::from_secs(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_bandwidth_limiter() {
+        let limiter_map = BandwidthLimiterMap::default();
+        let limiter1 = limiter_map.get("127.0.0.1:6000");
+        let limiter2 = limiter_map.get("127.0.0.1:6000");
+        assert!(Arc::ptr_eq(&limiter1, &limiter2));
+        assert_eq!(limiter_map.get_node_num(), 1);
+
+        assert_eq!(limiter1.acquire(1000, 0), Duration::from_secs(0));
+        let delay1 = limiter1.acquire(1000, 1000);
+        assert!(delay1 > Duration::from_millis(900));
+        // The second task shares the debt of the first one.
+        let delay2 = limiter2.acquire(1000, 1000);
+        assert!(delay2 > Duration::from_millis(1900));
+
+        let other = limiter_map.get("127.0.0.1:6001");
+        let delay = acquire_all(&[limiter1.clone(), other.clone()], 1000, 1000);
+        assert!(delay > Duration::from_millis(2900));
+        assert_eq!(limiter_map.get_node_num(), 2);
+
+        drop(limiter1);
+        drop(limiter2);
+        assert_eq!(limiter_map.get_node_num(), 1);
+    }
+}
@@ -0,0 +1,370 @@
+use super::task::{ScanResponse, SlotRangeArray};
+use crate::common::cluster::RangeList;
+use crate::protocol::{BinSafeStr, BulkStr, RedisClient, RedisClientError, Resp, RespVec};
+use crc64::crc64;
+use futures_timer::Delay;
+use std::str;
+use std::time::Duration;
+
+pub const DEFAULT_DIFF_KEYS_PER_SEC: u64 = 1000;
+pub const DEFAULT_MAX_DIFFS: usize = 100;
+const DIFF_SCAN_COUNT: u64 = 100;
+const KEY_TYPE_NONE: &str = "none";
+// The PTTL of the two nodes are not fetched at the same time.
+const TTL_TOLERANCE_MS: i64 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct KeyspaceDiffConfig {
+    pub slot_ranges: RangeList,
+    // Compares the digest of DUMP so it only works for the same Redis version.
+    pub compare_value: bool,
+    // Zero means no limitation.
+    pub max_keys_per_sec: u64,
+    pub max_diffs: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum KeyDiffKind {
+    // Only exists in the source.
+    Missing,
+    // Only exists in the target.
+    Extra,
+    Type,
+    Ttl,
+    Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct KeyDiff {
+    pub key: String,
+    pub kind: KeyDiffKind,
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct KeyspaceDiffReport {
+    pub scanned_keys: u64,
+    pub diffs: Vec<KeyDiff>,
+    // Stopped after finding `max_diffs` differences.
+    pub truncated: bool,
+}
+
+impl KeyspaceDiffReport {
+    // Returns false when it's full.
+    fn add(&mut self, diff: KeyDiff, max_diffs: usize) -> bool {
+        if self.diffs.len() >= max_diffs {
+            self.truncated = true;
+            return false;
+        }
+        self.diffs.push(diff);
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct KeyMeta {
+    key_type: String,
+    pttl: i64,
+    digest: Option<u64>,
+}
+
+impl KeyMeta {
+    fn exists(&self) -> bool {
+        self.key_type != KEY_TYPE_NONE
+    }
+}
+
+fn compare_key_meta(key: &[u8], source: &KeyMeta, target: &KeyMeta) -> Option<KeyDiff> {
+    // Deleted after scanning.
+    if !source.exists() {
+        return None;
+    }
+    let gen_diff = |kind, source: String, target: String| KeyDiff {
+        key: String::from_utf8_lossy(key).to_string(),
+        kind,
+        source,
+        target,
+    };
+    if !target.exists() {
+        return Some(gen_diff(
+            KeyDiffKind::Missing,
+            source.key_type.clone(),
+            target.key_type.clone(),
+        ));
+    }
+    if source.key_type != target.key_type {
+        return Some(gen_diff(
+            KeyDiffKind::Type,
+            source.key_type.clone(),
+            target.key_type.clone(),
+        ));
+    }
+    // -1 for no expiration
+    let ttl_mismatch = if source.pttl < 0 || target.pttl < 0 {
+        source.pttl != target.pttl
+    } else {
+        (source.pttl - target.pttl).abs() > TTL_TOLERANCE_MS
+    };
+    if ttl_mismatch {
+        return Some(gen_diff(
+            KeyDiffKind::Ttl,
+            source.pttl.to_string(),
+            target.pttl.to_string(),
+        ));
+    }
+    if source.digest != target.digest {
+        let digest_str = |digest: Option<u64>| {
+            digest
+                .map(|d| format!("{:016x}", d))
+                .unwrap_or_else(|| KEY_TYPE_NONE.to_string())
+        };
+        return Some(gen_diff(
+            KeyDiffKind::Value,
+            digest_str(source.digest),
+            digest_str(target.digest),
+        ));
+    }
+    None
+}
+
+async fn scan_keys<C: RedisClient>(
+    client: &mut C,
+    index: u64,
+) -> Result<ScanResponse, RedisClientError> {
+    let scan_cmd = vec![
+        b"SCAN".to_vec(),
+        index.to_string().into_bytes(),
+        b"COUNT".to_vec(),
+        DIFF_SCAN_COUNT.to_string().into_bytes(),
+    ];
+    let resp = client.execute_single(scan_cmd).await?;
+    ScanResponse::parse_scan(&resp).ok_or_else(|| {
+        error!("Invalid scan reply: {:?}", resp);
+        RedisClientError::InvalidReply
+    })
+}
+
+fn parse_key_type(resp: RespVec) -> Result<String, RedisClientError> {
+    match resp {
+        Resp::Simple(s) | Resp::Bulk(BulkStr::Str(s)) => {
+            Ok(String::from_utf8_lossy(&s).to_string())
+        }
+        others => {
+            error!("Invalid TYPE reply: {:?}", others);
+            Err(RedisClientError::InvalidReply)
+        }
+    }
+}
+
+fn parse_pttl(resp: RespVec) -> Result<i64, RedisClientError> {
+    match resp {
+        Resp::Integer(n) => btoi::btoi::<i64>(&n).map_err(|_| RedisClientError::InvalidReply),
+        others => {
+            error!("Invalid PTTL reply: {:?}", others);
+            Err(RedisClientError::InvalidReply)
+        }
+    }
+}
+
+fn parse_digest(resp: RespVec) -> Result<Option<u64>, RedisClientError> {
+    match resp {
+        Resp::Bulk(BulkStr::Str(data)) => Ok(Some(crc64(0, &data))),
+        Resp::Bulk(BulkStr::Nil) => Ok(None),
+        others => {
+            error!("Invalid DUMP reply: {:?}", others);
+            Err(RedisClientError::InvalidReply)
+        }
+    }
+}
+
+async fn get_key_metas<C: RedisClient>(
+    client: &mut C,
+    keys: &[BinSafeStr],
+    compare_value: bool,
+) -> Result<Vec<KeyMeta>, RedisClientError> {
+    if keys.is_empty() {
+        return Ok(vec![]);
+    }
+    let cmd_num = if compare_value { 3 } else { 2 };
+    let mut commands = Vec::with_capacity(keys.len() * cmd_num);
+    for key in keys.iter() {
+        commands.push(vec![b"TYPE".to_vec(), key.clone()]);
+        commands.push(vec![b"PTTL".to_vec(), key.clone()]);
+        if compare_value {
+            commands.push(vec![b"DUMP".to_vec(), key.clone()]);
+        }
+    }
+    let resps = client.execute_multi(commands).await?;
+    if resps.len() != keys.len() * cmd_num {
+        error!(
+            "mismatch batch result number, expected {}, found {}",
+            keys.len() * cmd_num,
+            resps.len()
+        );
+        return Err(RedisClientError::InvalidReply);
+    }
+
+    let mut metas = Vec::with_capacity(keys.len());
+    let mut resp_iter = resps.into_iter();
+    while let (Some(type_resp), Some(pttl_resp)) = (resp_iter.next(), resp_iter.next()) {
+        let digest = if compare_value {
+            let dump_resp = resp_iter.next().ok_or(RedisClientError::InvalidState)?;
+            parse_digest(dump_resp)?
+        } else {
+            None
+        };
+        metas.push(KeyMeta {
+            key_type: parse_key_type(type_resp)?,
+            pttl: parse_pttl(pttl_resp)?,
+            digest,
+        });
+    }
+    Ok(metas)
+}
+
+pub async fn throttle(key_num: usize, max_keys_per_sec: u64) {
+    if max_keys_per_sec == 0 || key_num == 0 {
+        return;
+    }
+    Delay::new(Duration::from_secs_f64(
+        key_num as f64 / max_keys_per_sec as f64,
+    ))
+    .await;
+}
+
+// Compares the keys in the slot ranges of the source with the target,
+// then finds the keys which only exist in the target.
+// The result could include the keys being written during the comparison.
+pub async fn diff_keyspace<C: RedisClient>(
+    source: &mut C,
+    target: &mut C,
+    config: &KeyspaceDiffConfig,
+) -> Result<KeyspaceDiffReport, RedisClientError> {
+    let slot_ranges = SlotRangeArray::new(config.slot_ranges.clone());
+    let mut report = KeyspaceDiffReport::default();
+
+    let mut index = 0;
+    loop {
+        let ScanResponse { next_index, keys } = scan_keys(source, index).await?;
+        let keys: Vec<_> = keys
+            .into_iter()
+            .filter(|key| slot_ranges.is_key_inside(key.as_slice()))
+            .collect();
+        let source_metas = get_key_metas(source, &keys, config.compare_value).await?;
+        let target_metas = get_key_metas(target, &keys, config.compare_value).await?;
+        report.scanned_keys += keys.len() as u64;
+        for ((key, source_meta), target_meta) in keys.iter().zip(source_metas).zip(target_metas) {
+            if let Some(diff) = compare_key_meta(key, &source_meta, &target_meta) {
+                if !report.add(diff, config.max_diffs) {
+                    return Ok(report);
+                }
+            }
+        }
+        throttle(keys.len(), config.max_keys_per_sec).await;
+        if next_index == 0 {
+            break;
+        }
+        index = next_index;
+    }
+
+    let mut index = 0;
+    loop {
+        let ScanResponse { next_index, keys } = scan_keys(target, index).await?;
+        let keys: Vec<_> = keys
+            .into_iter()
+            .filter(|key| slot_ranges.is_key_inside(key.as_slice()))
+            .collect();
+        let source_metas = get_key_metas(source, &keys, false).await?;
+        report.scanned_keys += keys.len() as u64;
+        for (key, source_meta) in keys.iter().zip(source_metas) {
+            if source_meta.exists() {
+                continue;
+            }
+            let diff = KeyDiff {
+                key: String::from_utf8_lossy(key).to_string(),
+                kind: KeyDiffKind::Extra,
+                source: KEY_TYPE_NONE.to_string(),
+                target: String::new(),
+            };
+            if !report.add(diff, config.max_diffs) {
+                return Ok(report);
+            }
+        }
+        throttle(keys.len(), config.max_keys_per_sec).await;
+        if next_index == 0 {
+            break;
+        }
+        index = next_index;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_meta(key_type: &str, pttl: i64, digest: Option<u64>) -> KeyMeta {
+        KeyMeta {
+            key_type: key_type.to_string(),
+            pttl,
+            digest,
+        }
+    }
+
+    #[test]
+    fn test_compare_key_meta() {
+        let key = b"key";
+        let string_meta = gen_meta("string", -1, Some(1));
+        assert!(compare_key_meta(key, &string_meta, &string_meta).is_none());
+        assert!(compare_key_meta(key, &gen_meta("none", -2, None), &string_meta).is_none());
+
+        let kind_of = |source: &KeyMeta, target: &KeyMeta| {
+            compare_key_meta(key, source, target).map(|diff| diff.kind)
+        };
+        let none_meta = gen_meta("none", -2, None);
+        assert_eq!(
+            kind_of(&string_meta, &none_meta),
+            Some(KeyDiffKind::Missing)
+        );
+        assert_eq!(
+            kind_of(&string_meta, &gen_meta("hash", -1, Some(1))),
+            Some(KeyDiffKind::Type)
+        );
+        assert_eq!(
+            kind_of(&string_meta, &gen_meta("string", 5000, Some(1))),
+            Some(KeyDiffKind::Ttl)
+        );
+        assert!(kind_of(
+            &gen_meta("string", 5000, None),
+            &gen_meta("string", 4500, None)
+        )
+        .is_none());
+        assert_eq!(
+            kind_of(&string_meta, &gen_meta("string", -1, Some(2))),
+            Some(KeyDiffKind::Value)
+        );
+
+        let diff = compare_key_meta(key, &string_meta, &gen_meta("list", -1, None)).unwrap();
+        assert_eq!(diff.key, "key");
+        assert_eq!(
+            (diff.source.as_str(), diff.target.as_str()),
+            ("string", "list")
+        );
+    }
+
+    #[test]
+    fn test_diff_report_limit() {
+        let mut report = KeyspaceDiffReport::default();
+        let diff = KeyDiff {
+            key: "key".to_string(),
+            kind: KeyDiffKind::Extra,
+            source: KEY_TYPE_NONE.to_string(),
+            target: String::new(),
+        };
+        assert!(report.add(diff.clone(), 1));
+        assert!(!report.add(diff, 1));
+        assert_eq!(report.diffs.len(), 1);
+        assert!(report.truncated);
+    }
+}
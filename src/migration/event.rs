@@ -0,0 +1,237 @@
+use super::task::MigrationState;
+use crate::common::cluster::{
+    MigrationEvent, MigrationEventKind, MigrationProgress, MigrationTaskEvents, MigrationTaskMeta,
+};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const MAX_TASK_EVENTS: usize = 64;
+pub const MAX_EVENT_TASKS: usize = 32;
+
+struct TaskEvents {
+    finished: bool,
+    events: VecDeque<MigrationEvent>,
+}
+
+#[derive(Default)]
+struct AtomicMigrationProgress {
+    started: AtomicBool,
+    estimated: AtomicBool,
+    estimated_keys: AtomicU64,
+    estimated_bytes: AtomicU64,
+    migrated_keys: AtomicU64,
+    migrated_bytes: AtomicU64,
+}
+
+// The events of a single migrating or importing task.
+pub struct TaskEventLog {
+    task: MigrationTaskMeta,
+    events: Mutex<TaskEvents>,
+    progress: AtomicMigrationProgress,
+}
+
+impl TaskEventLog {
+    fn new(task: MigrationTaskMeta) -> Self {
+        Self {
+            task,
+            events: Mutex::new(TaskEvents {
+                finished: false,
+                events: VecDeque::new(),
+            }),
+            progress: AtomicMigrationProgress::default(),
+        }
+    }
+
+    // Called by the migrating task before scanning the keys.
+    pub fn start_progress(&self, estimation: Option<(u64, u64)>) {
+        let progress = &self.progress;
+        if let Some((keys, bytes)) = estimation {
+            progress.estimated_keys.store(keys, Ordering::SeqCst);
+            progress.estimated_bytes.store(bytes, Ordering::SeqCst);
+            progress.estimated.store(true, Ordering::SeqCst);
+        }
+        progress.started.store(true, Ordering::SeqCst);
+    }
+
+    pub fn add_migrated(&self, keys: u64, bytes: u64) {
+        self.progress
+            .migrated_keys
+            .fetch_add(keys, Ordering::SeqCst);
+        self.progress
+            .migrated_bytes
+            .fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn get_progress(&self) -> Option<MigrationProgress> {
+        let progress = &self.progress;
+        if !progress.started.load(Ordering::SeqCst) {
+            return None;
+        }
+        let estimated = progress.estimated.load(Ordering::SeqCst);
+        let get_estimated = |n: &AtomicU64| {
+            if estimated {
+                Some(n.load(Ordering::SeqCst))
+            } else {
+                None
+            }
+        };
+        Some(MigrationProgress {
+            estimated_keys: get_estimated(&progress.estimated_keys),
+            estimated_bytes: get_estimated(&progress.estimated_bytes),
+            migrated_keys: progress.migrated_keys.load(Ordering::SeqCst),
+            migrated_bytes: progress.migrated_bytes.load(Ordering::SeqCst),
+        })
+    }
+
+    pub fn record(&self, kind: MigrationEventKind, detail: String) {
+        self.record_at(chrono::Utc::now().timestamp_millis(), kind, detail)
+    }
+
+    pub fn record_state(&self, state: MigrationState) {
+        self.record(MigrationEventKind::State, state.to_string())
+    }
+
+    fn record_at(&self, now: i64, kind: MigrationEventKind, detail: String) {
+        let mut task_events = self.events.lock().expect("TaskEventLog::record");
+        let events = &mut task_events.events;
+        // The retries could happen every few milliseconds.
+        if let Some(last) = events.back_mut() {
+            if last.kind == kind && last.detail == detail {
+                last.count += 1;
+                last.last_time = now;
+                return;
+            }
+        }
+        events.push_back(MigrationEvent {
+            time: now,
+            last_time: now,
+            kind,
+            detail,
+            count: 1,
+        });
+        while events.len() > MAX_TASK_EVENTS {
+            events.pop_front();
+        }
+    }
+
+    pub fn finish(&self) {
+        self.events.lock().expect("TaskEventLog::finish").finished = true;
+    }
+
+    fn get_events(&self) -> MigrationTaskEvents {
+        let task_events = self.events.lock().expect("TaskEventLog::get_events");
+        MigrationTaskEvents {
+            cluster_name: self.task.cluster_name.to_string(),
+            task: self.task.clone().into_strings().join(" "),
+            proxy_address: String::new(),
+            finished: task_events.finished,
+            events: task_events.events.iter().cloned().collect(),
+            progress: self.get_progress(),
+        }
+    }
+}
+
+// Keeps the events of the recent tasks after they are done
+// so that they can still be retrieved by `UMCTL MIGRATIONLOG`.
+#[derive(Default)]
+pub struct MigrationEventLog {
+    tasks: Mutex<VecDeque<Arc<TaskEventLog>>>,
+}
+
+impl MigrationEventLog {
+    // The task recreated with the same meta continues the previous log.
+    pub fn register(&self, task: MigrationTaskMeta) -> Arc<TaskEventLog> {
+        let mut tasks = self.tasks.lock().expect("MigrationEventLog::register");
+        if let Some(log) = tasks.iter().find(|log| log.task == task) {
+            return log.clone();
+        }
+        let log = Arc::new(TaskEventLog::new(task));
+        tasks.push_back(log.clone());
+        while tasks.len() > MAX_EVENT_TASKS {
+            tasks.pop_front();
+        }
+        log
+    }
+
+    pub fn get_all(&self) -> Vec<MigrationTaskEvents> {
+        self.tasks
+            .lock()
+            .expect("MigrationEventLog::get_all")
+            .iter()
+            .map(|log| log.get_events())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::{
+        ClusterName, MigrationMeta, Range, RangeList, RestorePolicy, SlotRange, SlotRangeTag,
+    };
+    use std::convert::TryFrom;
+
+    fn gen_task(start: usize) -> MigrationTaskMeta {
+        MigrationTaskMeta {
+            cluster_name: ClusterName::try_from("mycluster").unwrap(),
+            slot_range: SlotRange {
+                range_list: RangeList::from_single_range(Range(start, start + 100)),
+                tag: SlotRangeTag::Migrating(MigrationMeta {
+                    epoch: 233,
+                    src_proxy_address: "127.0.0.1:7000".to_string(),
+                    src_node_address: "127.0.0.1:6000".to_string(),
+                    dst_proxy_address: "127.0.0.1:7001".to_string(),
+                    dst_node_address: "127.0.0.1:6001".to_string(),
+                    restore_policy: RestorePolicy::default(),
+                }),
+            },
+        }
+    }
+
+    #[test]
+    fn test_migration_event_log() {
+        let event_log = MigrationEventLog::default();
+        let log = event_log.register(gen_task(0));
+        log.record_at(1, MigrationEventKind::State, "PRE_CHECK".to_string());
+        log.record_at(2, MigrationEventKind::Retry, "not ready".to_string());
+        log.record_at(3, MigrationEventKind::Retry, "not ready".to_string());
+        log.record_state(MigrationState::PreBlocking);
+        log.finish();
+
+        let all = event_log.get_all();
+        assert_eq!(all.len(), 1);
+        let task_events = &all[0];
+        assert_eq!(task_events.cluster_name, "mycluster");
+        assert!(task_events.task.starts_with("mycluster MIGRATING"));
+        assert!(task_events.finished);
+        assert_eq!(task_events.events.len(), 3);
+        let retry = &task_events.events[1];
+        assert_eq!((retry.time, retry.last_time, retry.count), (2, 3, 2));
+        assert_eq!(task_events.events[2].detail, "PRE_BLOCKING");
+
+        let same_log = event_log.register(gen_task(0));
+        assert!(Arc::ptr_eq(&log, &same_log));
+
+        assert!(task_events.progress.is_none());
+        log.start_progress(Some((200, 2000)));
+        log.add_migrated(50, 500);
+        log.add_migrated(50, 500);
+        let progress = event_log.get_all()[0].progress.clone().unwrap();
+        assert_eq!(progress.estimated_keys, Some(200));
+        assert_eq!(progress.migrated_bytes, 1000);
+        assert_eq!(progress.get_percentage(), Some(50.0));
+
+        for i in 0..MAX_TASK_EVENTS {
+            log.record_at(10, MigrationEventKind::Error, i.to_string());
+        }
+        assert_eq!(event_log.get_all()[0].events.len(), MAX_TASK_EVENTS);
+
+        for i in 1..=MAX_EVENT_TASKS {
+            event_log.register(gen_task(i * 100));
+        }
+        let all = event_log.get_all();
+        assert_eq!(all.len(), MAX_EVENT_TASKS);
+        assert!(all.iter().all(|task_events| !task_events.finished));
+    }
+}
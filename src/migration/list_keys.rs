@@ -0,0 +1,148 @@
+use super::diff::throttle;
+use super::task::ScanResponse;
+use crate::protocol::{BinSafeStr, RedisClient, RedisClientError, RedisClientFactory};
+use futures::{stream, Stream};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+pub const DEFAULT_LIST_KEYS_PER_SEC: u64 = 10000;
+pub const DEFAULT_LIST_KEYS_SCAN_COUNT: u64 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct ListKeysConfig {
+    pub scan_count: u64,
+    // Zero means no limitation.
+    pub max_keys_per_sec: u64,
+}
+
+struct ListKeysState<C: RedisClient> {
+    nodes: VecDeque<String>,
+    // The node being scanned and its next cursor.
+    client: Option<(C, u64)>,
+    failed: bool,
+}
+
+impl<C: RedisClient> ListKeysState<C> {
+    // Returns None after all the nodes are scanned. The empty batches are skipped.
+    async fn next_keys<F: RedisClientFactory<Client = C>>(
+        &mut self,
+        client_factory: &F,
+        config: &ListKeysConfig,
+    ) -> Result<Option<Vec<BinSafeStr>>, RedisClientError> {
+        loop {
+            let (mut client, index) = match self.client.take() {
+                Some(client) => client,
+                None => match self.nodes.pop_front() {
+                    Some(node) => (client_factory.create_client(node).await?, 0),
+                    None => return Ok(None),
+                },
+            };
+            let scan_cmd = vec![
+                b"SCAN".to_vec(),
+                index.to_string().into_bytes(),
+                b"COUNT".to_vec(),
+                config.scan_count.to_string().into_bytes(),
+            ];
+            let resp = client.execute_single(scan_cmd).await?;
+            let ScanResponse { next_index, keys } =
+                ScanResponse::parse_scan(&resp).ok_or_else(|| {
+                    error!("Invalid scan reply: {:?}", resp);
+                    RedisClientError::InvalidReply
+                })?;
+            if next_index != 0 {
+                self.client = Some((client, next_index));
+            }
+            throttle(keys.len(), config.max_keys_per_sec).await;
+            if !keys.is_empty() {
+                return Ok(Some(keys));
+            }
+        }
+    }
+}
+
+// Scans the nodes one by one without keeping the keys in memory.
+// Each item is the keys of one SCAN and the stream ends after the first error.
+pub fn list_keys<F: RedisClientFactory>(
+    client_factory: Arc<F>,
+    nodes: Vec<String>,
+    config: ListKeysConfig,
+) -> impl Stream<Item = Result<Vec<BinSafeStr>, RedisClientError>> {
+    let state = ListKeysState::<F::Client> {
+        nodes: nodes.into_iter().collect(),
+        client: None,
+        failed: false,
+    };
+    stream::unfold(state, move |mut state| {
+        let client_factory = client_factory.clone();
+        let config = config.clone();
+        async move {
+            if state.failed {
+                return None;
+            }
+            match state.next_keys(client_factory.as_ref(), &config).await {
+                Ok(Some(keys)) => Some((Ok(keys), state)),
+                Ok(None) => None,
+                Err(err) => {
+                    state.failed = true;
+                    Some((Err(err), state))
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Array, BulkStr, DummyRedisClientFactory, OptionalMulti, Resp, RespVec};
+    use futures::{future, Future, StreamExt};
+    use std::pin::Pin;
+
+    // SCAN 0 => [a, b], SCAN 5 => [], SCAN 7 => [c]
+    struct ScanClient;
+
+    impl RedisClient for ScanClient {
+        fn execute<'s>(
+            &'s mut self,
+            command: OptionalMulti<Vec<BinSafeStr>>,
+        ) -> Pin<
+            Box<dyn Future<Output = Result<OptionalMulti<RespVec>, RedisClientError>> + Send + 's>,
+        > {
+            let index = match &command {
+                OptionalMulti::Single(cmd) => cmd[1].clone(),
+                OptionalMulti::Multi(_) => return Box::pin(future::err(RedisClientError::Closed)),
+            };
+            let (next_index, keys): (&str, Vec<&str>) = match index.as_slice() {
+                b"0" => ("5", vec!["a", "b"]),
+                b"5" => ("7", vec![]),
+                _ => ("0", vec!["c"]),
+            };
+            let bulk = |s: &str| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec()));
+            let resp = Resp::Arr(Array::Arr(vec![
+                bulk(next_index),
+                Resp::Arr(Array::Arr(keys.into_iter().map(bulk).collect())),
+            ]));
+            Box::pin(future::ok(OptionalMulti::Single(resp)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_keys() {
+        let factory = Arc::new(DummyRedisClientFactory::new(|| ScanClient));
+        let config = ListKeysConfig {
+            scan_count: 10,
+            max_keys_per_sec: 0,
+        };
+        let nodes = vec!["127.0.0.1:6000".to_string(), "127.0.0.1:6001".to_string()];
+        let chunks: Vec<_> = list_keys(factory.clone(), nodes, config.clone())
+            .collect()
+            .await;
+        let chunks: Vec<Vec<BinSafeStr>> = chunks.into_iter().map(Result::unwrap).collect();
+        let a_b = vec![b"a".to_vec(), b"b".to_vec()];
+        let c = vec![b"c".to_vec()];
+        assert_eq!(chunks, vec![a_b.clone(), c.clone(), a_b, c]);
+
+        let chunks: Vec<_> = list_keys(factory, vec![], config).collect().await;
+        assert!(chunks.is_empty());
+    }
+}
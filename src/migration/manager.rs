@@ -1,8 +1,13 @@
+use super::bandwidth::BandwidthLimiterMap;
+use super::event::MigrationEventLog;
 use super::scan_task::{RedisScanImportingTask, RedisScanMigratingTask};
 use super::task::{ImportingTask, MigratingTask, MigrationError, MigrationState, SwitchArg};
-use crate::common::cluster::{ClusterName, MigrationTaskMeta, RangeList, SlotRangeTag};
+use crate::common::cluster::{
+    ClusterName, MigrationTaskEvents, MigrationTaskMeta, RangeList, SlotRangeTag,
+};
 use crate::common::config::{AtomicMigrationConfig, ClusterConfig};
 use crate::common::proto::{ClusterConfigMap, ProxyClusterMap};
+use crate::common::response::ERR_MISSING_KEY;
 use crate::common::track::TrackedFutureRegistry;
 use crate::common::utils::ThreadSafe;
 use crate::migration::task::MgrSubCmd;
@@ -53,6 +58,10 @@ where
     proxy_sender_factory: Arc<PTSF>,
     cmd_task_factory: Arc<CTF>,
     future_registry: Arc<TrackedFutureRegistry>,
+    // Shared by all the migrating tasks so that the tasks on the same node
+    // are limited by the node bandwidth together.
+    bandwidth_limiters: Arc<BandwidthLimiterMap>,
+    event_log: Arc<MigrationEventLog>,
 }
 
 impl<RCF, TSF, PTSF, CTF> MigrationManager<RCF, TSF, PTSF, CTF>
@@ -83,9 +92,15 @@ where
             proxy_sender_factory,
             cmd_task_factory,
             future_registry,
+            bandwidth_limiters: Arc::new(BandwidthLimiterMap::default()),
+            event_log: Arc::new(MigrationEventLog::default()),
         }
     }
 
+    pub fn get_migration_events(&self) -> Vec<MigrationTaskEvents> {
+        self.event_log.get_all()
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn create_new_migration_map<BCF: TaskBlockingControllerFactory>(
         &self,
@@ -108,6 +123,8 @@ where
             self.proxy_sender_factory.clone(),
             self.cmd_task_factory.clone(),
             blocking_ctrl_factory,
+            self.bandwidth_limiters.clone(),
+            self.event_log.clone(),
         )
     }
 
@@ -246,6 +263,50 @@ where
         Resp::Arr(Array::Arr(tasks))
     }
 
+    // The `migration` section of `INFO`.
+    pub fn gen_info_lines(&self) -> Vec<String> {
+        let mut tasks = vec![];
+        for (cluster_name, cluster_tasks) in self.task_map.iter() {
+            for (task_meta, mgr_task) in cluster_tasks.iter() {
+                let (migration_meta, role) = match &task_meta.slot_range.tag {
+                    SlotRangeTag::Migrating(meta) => (meta, "migrating"),
+                    SlotRangeTag::Importing(meta) => (meta, "importing"),
+                    SlotRangeTag::None => continue,
+                };
+                let state = match &mgr_task.task {
+                    Either::Left(task) => task.get_state(),
+                    Either::Right(task) => task.get_state(),
+                };
+                let slots = task_meta
+                    .slot_range
+                    .range_list
+                    .get_ranges()
+                    .iter()
+                    .map(|range| format!("{}-{}", range.start(), range.end()))
+                    .collect::<Vec<String>>()
+                    .join(";");
+                tasks.push(format!(
+                    "cluster={},role={},slots={},src={},dst={},state={}",
+                    cluster_name,
+                    role,
+                    slots,
+                    migration_meta.src_node_address,
+                    migration_meta.dst_node_address,
+                    state,
+                ));
+            }
+        }
+        tasks.sort();
+        let mut lines = vec![format!("migration_tasks:{}", tasks.len())];
+        lines.extend(
+            tasks
+                .into_iter()
+                .enumerate()
+                .map(|(i, task)| format!("migration_task_{}:{}", i, task)),
+        );
+        lines
+    }
+
     pub fn send(&self, mut cmd_task: T) -> Result<(), ClusterSendError<BlockingHintTask<T>>> {
         cmd_task.log_event(TaskEvent::SentToMigrationBackend);
 
@@ -259,6 +320,17 @@ where
         Self::send_helper(&self.task_map, cmd_task)
     }
 
+    pub fn contains_slot(&self, cluster_name: &ClusterName, slot: usize) -> bool {
+        let tasks = match self.task_map.get(cluster_name) {
+            Some(tasks) => tasks,
+            None => return false,
+        };
+        tasks.values().any(|mgr_task| match &mgr_task.task {
+            Either::Left(migrating_task) => migrating_task.contains_slot(slot),
+            Either::Right(importing_task) => importing_task.contains_slot(slot),
+        })
+    }
+
     fn send_helper(
         task_map: &TaskMap<T>,
         cmd_task: T,
@@ -269,7 +341,7 @@ where
                 let slot = match cmd_task.get_slot() {
                     Some(slot) => slot,
                     None => {
-                        let resp = Resp::Error("missing key".to_string().into_bytes());
+                        let resp = Resp::Error(ERR_MISSING_KEY.to_string().into_bytes());
                         cmd_task.set_resp_result(Ok(resp));
                         return Err(ClusterSendError::MissingKey);
                     }
@@ -315,7 +387,7 @@ where
             let slot = match cmd_task.get_slot() {
                 Some(slot) => slot,
                 None => {
-                    let resp = Resp::Error("missing key".to_string().into_bytes());
+                    let resp = Resp::Error(ERR_MISSING_KEY.to_string().into_bytes());
                     cmd_task.set_resp_result(Ok(resp));
                     return Err(ClusterSendError::MissingKey);
                 }
@@ -348,6 +420,8 @@ where
         proxy_sender_factory: Arc<PTSF>,
         cmd_task_factory: Arc<CTF>,
         blocking_ctrl_factory: Arc<BCF>,
+        bandwidth_limiters: Arc<BandwidthLimiterMap>,
+        event_log: Arc<MigrationEventLog>,
     ) -> (Self, Vec<NewTask<T>>)
     where
         RCF: RedisClientFactory,
@@ -443,6 +517,8 @@ where
                                 meta.clone(),
                                 client_factory.clone(),
                                 ctrl,
+                                &bandwidth_limiters,
+                                event_log.register(migration_meta.clone()),
                             ));
                             new_tasks.push(NewTask {
                                 cluster_name: cluster_name.clone(),
@@ -484,6 +560,7 @@ where
                                 sender_factory.clone(),
                                 proxy_sender_factory.clone(),
                                 cmd_task_factory.clone(),
+                                event_log.register(migration_meta.clone()),
                             ));
                             new_tasks.push(NewTask {
                                 cluster_name: cluster_name.clone(),
@@ -573,6 +650,25 @@ where
         metadata
     }
 
+    // Returns the number of all the tasks and the committed ones.
+    pub fn get_progress(&self) -> (usize, usize) {
+        let mut total = 0;
+        let mut committed = 0;
+        for tasks in self.task_map.values() {
+            for mgr_task in tasks.values() {
+                let state = match &mgr_task.task {
+                    Either::Left(migrating_task) => migrating_task.get_state(),
+                    Either::Right(importing_task) => importing_task.get_state(),
+                };
+                total += 1;
+                if state == MigrationState::SwitchCommitted {
+                    committed += 1;
+                }
+            }
+        }
+        (total, committed)
+    }
+
     pub fn get_states(&self, cluster_name: &ClusterName) -> HashMap<RangeList, MigrationState> {
         let mut m = HashMap::new();
         if let Some(tasks) = self.task_map.get(cluster_name) {
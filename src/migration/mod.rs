@@ -1,3 +1,9 @@
+pub mod bandwidth;
+// The impls generated by the derive macros of the pinned serde_derive are non-local.
+#[allow(non_local_definitions)]
+pub mod diff;
+pub mod event;
+pub mod list_keys;
 pub mod manager;
 pub mod scan_migration;
 mod scan_task;
@@ -1,5 +1,7 @@
+use super::bandwidth::{acquire_all, BandwidthLimiter};
+use super::event::TaskEventLog;
 use super::task::{ScanResponse, SlotRangeArray};
-use crate::common::cluster::SlotRange;
+use crate::common::cluster::{RestorePolicy, SlotRange};
 use crate::common::config::AtomicMigrationConfig;
 use crate::common::future_group::{new_auto_drop_future, FutureAutoStopHandle};
 use crate::common::resp_execution::keep_connecting_and_sending_cmd_with_cached_client;
@@ -21,13 +23,15 @@ use std::cmp::min;
 use std::num::NonZeroUsize;
 use std::pin::Pin;
 use std::sync::atomic::Ordering;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 pub const PTTL_NO_EXPIRE: &[u8] = b"-1";
 pub const PTTL_KEY_NOT_FOUND: &[u8] = b"-2";
 pub const RESTORE_NO_EXPIRE: &[u8] = b"0";
-const BUSYKEY_ERROR: &[u8] = b"BUSYKEY";
+pub const RESTORE_REPLACE: &[u8] = b"REPLACE";
+pub const BUSYKEY_ERROR: &[u8] = b"BUSYKEY";
+const ESTIMATION_SAMPLE_COUNT: u64 = 256;
 
 pub fn pttl_to_restore_expire_time(pttl: Vec<u8>) -> Vec<u8> {
     let mut expire_time = pttl;
@@ -53,6 +57,37 @@ fn pttl_need_to_be_no_expire(buf: &[u8]) -> bool {
     n < 0
 }
 
+// Returns the keys restored to the destination and the keys already existing in it.
+fn split_busy_keys(
+    keys: Vec<BinSafeStr>,
+    busy_indices: &[usize],
+) -> (Vec<BinSafeStr>, Vec<BinSafeStr>) {
+    let (busy_keys, keys): (Vec<_>, Vec<_>) = keys
+        .into_iter()
+        .enumerate()
+        .partition(|(i, _)| busy_indices.contains(i));
+    (
+        keys.into_iter().map(|(_, key)| key).collect(),
+        busy_keys.into_iter().map(|(_, key)| key).collect(),
+    )
+}
+
+// Scales DBSIZE by the ratio of the sampled keys inside the slot ranges.
+// Returns the estimated keys and bytes.
+fn estimate_from_sample(
+    db_size: u64,
+    sampled_keys: u64,
+    inside_keys: u64,
+    inside_bytes: u64,
+) -> (u64, u64) {
+    if sampled_keys == 0 || inside_keys == 0 {
+        return (0, 0);
+    }
+    let keys = (db_size as f64 * inside_keys as f64 / sampled_keys as f64) as u64;
+    let bytes = (keys as f64 * inside_bytes as f64 / inside_keys as f64) as u64;
+    (keys, bytes)
+}
+
 #[derive(Clone)]
 struct DataEntry {
     key: Vec<u8>,
@@ -69,12 +104,16 @@ pub struct ScanMigrationTask<T: CmdTask> {
 }
 
 impl<T: CmdTask> ScanMigrationTask<T> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<F: RedisClientFactory>(
         src_address: String,
         dst_address: String,
         slot_range: SlotRange,
         client_factory: Arc<F>,
         config: Arc<AtomicMigrationConfig>,
+        limiters: Vec<Arc<BandwidthLimiter>>,
+        restore_policy: RestorePolicy,
+        events: Arc<TaskEventLog>,
     ) -> Self {
         let ranges = slot_range.to_range_list();
         let slot_ranges = SlotRangeArray::new(ranges);
@@ -87,6 +126,9 @@ impl<T: CmdTask> ScanMigrationTask<T> {
             sender.clone(),
             receiver,
             config,
+            limiters,
+            restore_policy,
+            events,
         );
 
         Self {
@@ -113,7 +155,11 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         self.handle.take(Ordering::SeqCst).is_some()
     }
 
-    fn handle_forward(opt_multi_resp: OptionalMulti<RespVec>) -> Result<(), RedisClientError> {
+    // Records the indices of the keys already existing in the destination.
+    fn handle_forward(
+        opt_multi_resp: OptionalMulti<RespVec>,
+        busy_indices: &Mutex<Vec<usize>>,
+    ) -> Result<(), RedisClientError> {
         let resps = match opt_multi_resp {
             OptionalMulti::Single(r) => {
                 error!("unexpected single reply: {:?}", r);
@@ -121,17 +167,21 @@ impl<T: CmdTask> ScanMigrationTask<T> {
             }
             OptionalMulti::Multi(v) => v,
         };
-        for resp in resps.into_iter() {
+        let mut indices = vec![];
+        for (i, resp) in resps.into_iter().enumerate() {
             if let Resp::Error(err_msg) = resp {
                 if err_msg.get(..BUSYKEY_ERROR.len()) != Some(BUSYKEY_ERROR) {
                     error!("RESTORE error: {:?}", pretty_print_bytes(&err_msg));
                     return Err(RedisClientError::InvalidReply);
                 }
+                indices.push(i);
             }
         }
+        *busy_indices.lock().expect("handle_forward") = indices;
         Err(RedisClientError::Done)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn gen_future<F: RedisClientFactory>(
         src_address: String,
         dst_address: String,
@@ -140,6 +190,9 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         sync_tasks_sender: UnboundedSender<T>,
         sync_tasks_receiver: UnboundedReceiver<T>,
         config: Arc<AtomicMigrationConfig>,
+        limiters: Vec<Arc<BandwidthLimiter>>,
+        restore_policy: RestorePolicy,
+        events: Arc<TaskEventLog>,
     ) -> (MgrFut, FutureAutoStopHandle) {
         let interval = min(
             Duration::from_micros(config.get_scan_interval()),
@@ -161,6 +214,9 @@ impl<T: CmdTask> ScanMigrationTask<T> {
             sync_tasks_sender,
             sync_tasks_receiver,
             config,
+            limiters,
+            restore_policy,
+            events,
         );
 
         let (send, handle) = new_auto_drop_future(send);
@@ -169,6 +225,7 @@ impl<T: CmdTask> ScanMigrationTask<T> {
     }
 
     #[allow(clippy::cognitive_complexity)]
+    #[allow(clippy::too_many_arguments)]
     async fn keep_migrating<F: RedisClientFactory>(
         src_address: String,
         dst_address: String,
@@ -177,6 +234,9 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         sync_tasks_sender: UnboundedSender<T>,
         mut sync_tasks_receiver: UnboundedReceiver<T>,
         config: Arc<AtomicMigrationConfig>,
+        limiters: Vec<Arc<BandwidthLimiter>>,
+        restore_policy: RestorePolicy,
+        events: Arc<TaskEventLog>,
     ) -> Result<(), MigrationError> {
         const SLEEP_BATCH_TIMES: u64 = 10;
 
@@ -208,6 +268,7 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         let mut scan_index = 0;
         let mut cached_dst_client = None;
         let mut sleep_count = 0;
+        let mut estimated = false;
         loop {
             let mut src_client = match client_factory.create_client(src_address.clone()).await {
                 Ok(client) => client,
@@ -217,6 +278,23 @@ impl<T: CmdTask> ScanMigrationTask<T> {
                     continue;
                 }
             };
+            if !estimated {
+                estimated = true;
+                let estimation = match Self::estimate_keys(&slot_ranges, &mut src_client).await {
+                    Ok((keys, bytes)) => {
+                        info!(
+                            "estimated {} keys {} bytes to migrate from {}",
+                            keys, bytes, src_address
+                        );
+                        Some((keys, bytes))
+                    }
+                    Err(err) => {
+                        warn!("failed to estimate the keys of {}: {:?}", src_address, err);
+                        None
+                    }
+                };
+                events.start_progress(estimation);
+            }
             loop {
                 let sync_tasks = if sleep_count >= SLEEP_BATCH_TIMES {
                     sleep_count = 0;
@@ -251,6 +329,8 @@ impl<T: CmdTask> ScanMigrationTask<T> {
                             dst_address.clone(),
                             client_factory.clone(),
                             cmd_tasks,
+                            restore_policy,
+                            &events,
                         )
                         .await;
                         match res {
@@ -273,12 +353,23 @@ impl<T: CmdTask> ScanMigrationTask<T> {
                             dst_address.clone(),
                             client_factory.clone(),
                             scan_count,
+                            &limiters,
+                            config.get_node_bandwidth(),
+                            restore_policy,
+                            &events,
                         )
                         .await
                     }
                 };
 
                 match res {
+                    Err(MigrationError::KeyConflict(keys)) => {
+                        error!(
+                            "stop migrating since the keys already exist in {}: {:?}",
+                            dst_address, keys
+                        );
+                        return Err(MigrationError::KeyConflict(keys));
+                    }
                     Err(err) => {
                         error!("failed to scan and migrate {:?}", err);
                         break;
@@ -304,6 +395,7 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn scan_and_migrate_keys<F: RedisClientFactory>(
         slot_ranges: &SlotRangeArray,
         index: u64,
@@ -312,23 +404,65 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         dst_address: String,
         client_factory: Arc<F>,
         scan_count: u64,
-    ) -> Result<(u64, Option<F::Client>), RedisClientError> {
-        let ScanResponse { next_index, keys } =
-            Self::scan_keys(src_client, index, scan_count).await?;
-
-        let entries = Self::produce_entries(slot_ranges, keys, src_client).await?;
+        limiters: &[Arc<BandwidthLimiter>],
+        node_bandwidth: u64,
+        restore_policy: RestorePolicy,
+        events: &TaskEventLog,
+    ) -> Result<(u64, Option<F::Client>), MigrationError> {
+        let ScanResponse { next_index, keys } = Self::scan_keys(src_client, index, scan_count)
+            .await
+            .map_err(MigrationError::RedisClient)?;
+
+        let entries = Self::produce_entries(slot_ranges, keys, src_client)
+            .await
+            .map_err(MigrationError::RedisClient)?;
         if entries.is_empty() {
             return Ok((next_index, dst_client));
         }
 
+        // The blocking requests are not limited since the clients are waiting for them.
+        let bytes: usize = entries.iter().map(|entry| entry.raw_data.len()).sum();
+        let delay = acquire_all(limiters, bytes as u64, node_bandwidth);
+        if delay > Duration::from_secs(0) {
+            Delay::new(delay).await;
+        }
+
         let transferred_keys: Vec<_> = entries.iter().map(|entry| entry.key.clone()).collect();
-        let dst_client =
-            Self::forward_entries(dst_address, dst_client, client_factory, entries).await;
+        let (dst_client, busy_indices) = Self::forward_entries(
+            dst_address,
+            dst_client,
+            client_factory,
+            entries,
+            restore_policy,
+        )
+        .await;
+
+        if restore_policy != RestorePolicy::Fail || busy_indices.is_empty() {
+            let key_num = transferred_keys.len() as u64;
+            Self::delete_keys(src_client, transferred_keys)
+                .await
+                .map_err(MigrationError::RedisClient)?;
+            events.add_migrated(key_num, bytes as u64);
+            return Ok((next_index, Some(dst_client)));
+        }
 
-        Self::delete_keys(src_client, transferred_keys).await?;
-        Ok((next_index, Some(dst_client)))
+        // Keep the conflicting keys in both nodes for the administrators to decide.
+        let (transferred_keys, conflict_keys) = split_busy_keys(transferred_keys, &busy_indices);
+        if !transferred_keys.is_empty() {
+            let key_num = transferred_keys.len() as u64;
+            Self::delete_keys(src_client, transferred_keys)
+                .await
+                .map_err(MigrationError::RedisClient)?;
+            events.add_migrated(key_num, 0);
+        }
+        let conflict_keys = conflict_keys
+            .iter()
+            .map(|key| pretty_print_bytes(key))
+            .collect();
+        Err(MigrationError::KeyConflict(conflict_keys))
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn handle_blocking_requests<F: RedisClientFactory>(
         slot_ranges: &SlotRangeArray,
         dst_client: Option<F::Client>,
@@ -336,6 +470,8 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         dst_address: String,
         client_factory: Arc<F>,
         cmd_tasks: Vec<T>,
+        restore_policy: RestorePolicy,
+        events: &TaskEventLog,
     ) -> Result<Option<F::Client>, RedisClientError> {
         let keys = cmd_tasks
             .iter()
@@ -349,13 +485,33 @@ impl<T: CmdTask> ScanMigrationTask<T> {
                 } else {
                     let transferred_keys: Vec<_> =
                         entries.iter().map(|entry| entry.key.clone()).collect();
-                    let dst_client =
-                        Self::forward_entries(dst_address, dst_client, client_factory, entries)
-                            .await;
-
-                    Self::delete_keys(src_client, transferred_keys)
-                        .await
-                        .map(move |()| Some(dst_client))
+                    let bytes: usize = entries.iter().map(|entry| entry.raw_data.len()).sum();
+                    let (dst_client, busy_indices) = Self::forward_entries(
+                        dst_address,
+                        dst_client,
+                        client_factory,
+                        entries,
+                        restore_policy,
+                    )
+                    .await;
+
+                    // The conflicting keys will be reported by the scanning.
+                    let transferred_keys = if restore_policy == RestorePolicy::Fail {
+                        split_busy_keys(transferred_keys, &busy_indices).0
+                    } else {
+                        transferred_keys
+                    };
+                    if transferred_keys.is_empty() {
+                        Ok(Some(dst_client))
+                    } else {
+                        let key_num = transferred_keys.len() as u64;
+                        Self::delete_keys(src_client, transferred_keys)
+                            .await
+                            .map(move |()| {
+                                events.add_migrated(key_num, bytes as u64);
+                                Some(dst_client)
+                            })
+                    }
                 }
             }
             Err(err) => Err(err),
@@ -364,7 +520,7 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         let resp = if res.is_ok() {
             Resp::Simple(response::OK_REPLY.to_string().into_bytes())
         } else {
-            Resp::Error(b"failed to delete keys".to_vec())
+            Resp::Error(format!("{} failed to delete keys", response::UM_MIGRATING).into_bytes())
         };
 
         for cmd_task in cmd_tasks.into_iter() {
@@ -374,6 +530,38 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         res
     }
 
+    // DBSIZE plus a sampled SCAN so that the progress reported to the broker is meaningful.
+    async fn estimate_keys<C: RedisClient>(
+        slot_ranges: &SlotRangeArray,
+        src_client: &mut C,
+    ) -> Result<(u64, u64), RedisClientError> {
+        let resp = src_client.execute_single(vec![b"DBSIZE".to_vec()]).await?;
+        let db_size = match resp {
+            Resp::Integer(n) => {
+                btoi::btou::<u64>(&n).map_err(|_| RedisClientError::InvalidReply)?
+            }
+            others => {
+                error!("invalid DBSIZE reply: {:?}", others);
+                return Err(RedisClientError::InvalidReply);
+            }
+        };
+        if db_size == 0 {
+            return Ok((0, 0));
+        }
+
+        let ScanResponse { keys, .. } =
+            Self::scan_keys(src_client, 0, ESTIMATION_SAMPLE_COUNT).await?;
+        let sampled_keys = keys.len() as u64;
+        let entries = Self::produce_entries(slot_ranges, keys, src_client).await?;
+        let inside_bytes: usize = entries.iter().map(|entry| entry.raw_data.len()).sum();
+        Ok(estimate_from_sample(
+            db_size,
+            sampled_keys,
+            entries.len() as u64,
+            inside_bytes as u64,
+        ))
+    }
+
     async fn scan_keys<C: RedisClient>(
         src_client: &mut C,
         index: u64,
@@ -472,7 +660,8 @@ impl<T: CmdTask> ScanMigrationTask<T> {
         cached_dst_client: Option<F::Client>,
         client_factory: Arc<F>,
         entries: Vec<DataEntry>,
-    ) -> F::Client {
+        restore_policy: RestorePolicy,
+    ) -> (F::Client, Vec<usize>) {
         let mut commands = Vec::with_capacity(entries.len());
         for entry in entries.into_iter() {
             let DataEntry {
@@ -483,26 +672,33 @@ impl<T: CmdTask> ScanMigrationTask<T> {
 
             let expire_time = pttl_to_restore_expire_time(pttl);
 
-            let restore_cmd = vec![
+            let mut restore_cmd = vec![
                 "RESTORE".to_string().into_bytes(),
                 key,
                 expire_time,
                 raw_data,
             ];
+            if restore_policy == RestorePolicy::Replace {
+                restore_cmd.push(RESTORE_REPLACE.to_vec());
+            }
 
             commands.push(restore_cmd);
         }
 
+        let busy_indices = Arc::new(Mutex::new(vec![]));
+        let indices = busy_indices.clone();
         let retry_interval = Duration::from_millis(1);
-        keep_connecting_and_sending_cmd_with_cached_client(
+        let dst_client = keep_connecting_and_sending_cmd_with_cached_client(
             cached_dst_client,
             client_factory,
             dst_address,
             OptionalMulti::Multi(commands),
             retry_interval,
-            Self::handle_forward,
+            move |resp| Self::handle_forward(resp, &indices),
         )
-        .await
+        .await;
+        let busy_indices = busy_indices.lock().expect("forward_entries").clone();
+        (dst_client, busy_indices)
     }
 
     async fn delete_keys<C: RedisClient>(
@@ -528,3 +724,16 @@ impl<T: CmdTask> Drop for ScanMigrationTask<T> {
         self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_from_sample() {
+        assert_eq!(estimate_from_sample(1000, 0, 0, 0), (0, 0));
+        assert_eq!(estimate_from_sample(1000, 100, 0, 0), (0, 0));
+        assert_eq!(estimate_from_sample(1000, 100, 25, 2500), (250, 25000));
+        assert_eq!(estimate_from_sample(1000, 100, 100, 100), (1000, 1000));
+    }
+}
@@ -1,12 +1,15 @@
+use super::bandwidth::BandwidthLimiterMap;
+use super::event::TaskEventLog;
 use super::scan_migration::ScanMigrationTask;
 use super::task::{
     AtomicMigrationState, ImportingTask, MgrSubCmd, MigratingTask, MigrationError, MigrationState,
     SwitchArg,
 };
 use crate::common::cluster::{
-    ClusterName, MigrationMeta, MigrationTaskMeta, RangeMap, SlotRange, SlotRangeTag,
+    ClusterName, MigrationEventKind, MigrationMeta, MigrationTaskMeta, RangeMap, SlotRange,
+    SlotRangeTag,
 };
-use crate::common::config::AtomicMigrationConfig;
+use crate::common::config::{AtomicMigrationConfig, BlockingPolicy};
 use crate::common::resp_execution::keep_connecting_and_sending_cmd;
 use crate::common::response;
 use crate::common::utils::{gen_moved, pretty_print_bytes, ThreadSafe};
@@ -16,7 +19,7 @@ use crate::protocol::{
 };
 use crate::proxy::backend::{CmdTask, CmdTaskFactory, ReqTask};
 use crate::proxy::blocking::{BlockingHandle, BlockingHintTask, TaskBlockingController};
-use crate::proxy::cluster::ClusterSendError;
+use crate::proxy::cluster::{ClusterSendError, ClusterTag};
 use crate::proxy::command::CmdTypeTuple;
 use crate::proxy::migration_backend::RestoreDataCmdTaskHandler;
 use crate::proxy::sender::{CmdTaskSender, CmdTaskSenderFactory};
@@ -57,6 +60,7 @@ where
     blocking_ctrl: Arc<BC>,
     phantom: PhantomData<T>,
     active_redirection: bool,
+    events: Arc<TaskEventLog>,
 }
 
 impl<RCF, T, BC> RedisScanMigratingTask<RCF, T, BC>
@@ -74,14 +78,23 @@ where
         meta: MigrationMeta,
         client_factory: Arc<RCF>,
         blocking_ctrl: Arc<BC>,
+        bandwidth_limiters: &BandwidthLimiterMap,
+        events: Arc<TaskEventLog>,
     ) -> Self {
         let (stop_signal_sender, stop_signal_receiver) = oneshot::channel();
+        let limiters = vec![
+            bandwidth_limiters.get(&meta.src_node_address),
+            bandwidth_limiters.get(&meta.dst_node_address),
+        ];
         let task = ScanMigrationTask::new(
             meta.src_node_address.clone(),
             meta.dst_node_address.clone(),
             slot_range.clone(),
             client_factory.clone(),
             mgr_config.clone(),
+            limiters,
+            meta.restore_policy,
+            events.clone(),
         );
         let range_map = RangeMap::from(slot_range.get_range_list());
         let active_redirection = config.active_redirection;
@@ -99,6 +112,7 @@ where
             blocking_ctrl,
             phantom: PhantomData,
             active_redirection,
+            events,
         }
     }
 
@@ -122,18 +136,26 @@ where
     async fn pre_check(&self) {
         let state = self.state.clone();
         let meta = self.meta.clone();
+        let events = self.events.clone();
 
         let handle_pre_check = move |resp: RespVec| -> Result<(), RedisClientError> {
             match resp {
                 Resp::Error(err_str) => {
                     if err_str == response::NOT_READY_FOR_SWITCHING_REPLY.as_bytes() {
-                        debug!("pre_check not ready, try again {:?}", meta)
+                        debug!("pre_check not ready, try again {:?}", meta);
+                        events.record(MigrationEventKind::Retry, "pre_check not ready".to_string());
                     } else if err_str == response::TASK_NOT_FOUND.as_bytes() {
                         warn!("peer task not found");
+                        events.record(
+                            MigrationEventKind::Retry,
+                            "pre_check peer task not found".to_string(),
+                        );
                     } else {
-                        error!(
-                            "failed to check: {:?}",
-                            pretty_print_bytes(err_str.as_slice())
+                        let err_str = pretty_print_bytes(err_str.as_slice());
+                        error!("failed to check: {:?}", err_str);
+                        events.record(
+                            MigrationEventKind::Error,
+                            format!("failed to check: {}", err_str),
                         );
                     }
                     Ok(())
@@ -141,6 +163,7 @@ where
                 _reply => {
                     info!("pre_check done");
                     state.set_state(MigrationState::PreBlocking);
+                    events.record_state(MigrationState::PreBlocking);
                     Err(RedisClientError::Done)
                 }
             }
@@ -178,6 +201,7 @@ where
             Delay::new(Duration::from_millis(1)).await;
         }
         state.set_state(MigrationState::PreSwitch);
+        self.events.record_state(MigrationState::PreSwitch);
         info!("pre_block done");
         blocking_handle
     }
@@ -185,24 +209,36 @@ where
     async fn pre_switch(&self) {
         let state = self.state.clone();
         let meta = self.meta.clone();
+        let events = self.events.clone();
 
         let handle_pre_switch = move |resp: RespVec| -> Result<(), RedisClientError> {
             match resp {
                 Resp::Error(err_str) => {
                     if err_str == response::NOT_READY_FOR_SWITCHING_REPLY.as_bytes() {
-                        debug!("pre_switch not ready, try again {:?}", meta)
+                        debug!("pre_switch not ready, try again {:?}", meta);
+                        events.record(
+                            MigrationEventKind::Retry,
+                            "pre_switch not ready".to_string(),
+                        );
                     } else if err_str == response::TASK_NOT_FOUND.as_bytes() {
-                        warn!("task not found, try again {:?}", meta)
+                        warn!("task not found, try again {:?}", meta);
+                        events.record(
+                            MigrationEventKind::Retry,
+                            "pre_switch peer task not found".to_string(),
+                        );
                     } else {
-                        error!(
-                            "failed to switch: {:?}",
-                            pretty_print_bytes(err_str.as_slice())
+                        let err_str = pretty_print_bytes(err_str.as_slice());
+                        error!("failed to switch: {:?}", err_str);
+                        events.record(
+                            MigrationEventKind::Error,
+                            format!("failed to pre_switch: {}", err_str),
                         );
                     }
                     Ok(())
                 }
                 _reply => {
                     state.set_state(MigrationState::Scanning);
+                    events.record_state(MigrationState::Scanning);
                     Err(RedisClientError::Done)
                 }
             }
@@ -248,11 +284,14 @@ where
         match fut.await {
             Ok(()) => {
                 state.set_state(MigrationState::FinalSwitch);
+                self.events.record_state(MigrationState::FinalSwitch);
                 info!("migration future finished forwarding data");
                 Ok(())
             }
             Err(err) => {
                 error!("migration future finished error: {:?}", err);
+                self.events
+                    .record(MigrationEventKind::Error, format!("{:?}", err));
                 Err(err)
             }
         }
@@ -261,20 +300,23 @@ where
     async fn final_switch(&self) {
         let state = self.state.clone();
         let meta = self.meta.clone();
+        let events = self.events.clone();
 
         let handle_final_switch = move |resp: RespVec| -> Result<(), RedisClientError> {
             match resp {
                 Resp::Error(err_str) => {
-                    error!(
-                        "failed to switch: {:?} {:?}",
-                        pretty_print_bytes(err_str.as_slice()),
-                        meta,
+                    let err_str = pretty_print_bytes(err_str.as_slice());
+                    error!("failed to switch: {:?} {:?}", err_str, meta);
+                    events.record(
+                        MigrationEventKind::Retry,
+                        format!("failed to final_switch: {}", err_str),
                     );
                     Ok(())
                 }
                 _reply => {
                     info!("final_switch done: {:?}", meta);
                     state.set_state(MigrationState::SwitchCommitted);
+                    events.record_state(MigrationState::SwitchCommitted);
                     Err(RedisClientError::Done)
                 }
             }
@@ -309,7 +351,13 @@ where
         let timeout = Duration::from_secs(self.mgr_config.get_max_migration_time());
         let mut timeout_fut = Delay::new(timeout).fuse();
         select! {
-            () = timeout_fut => error!("migration timeout after {:?}, force to commit migration", timeout),
+            () = timeout_fut => {
+                error!("migration timeout after {:?}, force to commit migration", timeout);
+                self.events.record(
+                    MigrationEventKind::Error,
+                    format!("migration timeout after {:?}, force to commit migration", timeout),
+                );
+            },
             res = self.run_migration().fuse() => res?,
         };
         final_switch.await;
@@ -342,6 +390,10 @@ where
 
         if let Err(err) = res {
             error!("Migration failed {:?}. Force to go ahead.", err);
+            self.events.record(
+                MigrationEventKind::Error,
+                format!("blocking failed {:?}, force to go ahead", err),
+            );
         }
 
         scan_migrate.await
@@ -351,7 +403,7 @@ where
 impl<RCF, T, BC> MigratingTask for RedisScanMigratingTask<RCF, T, BC>
 where
     RCF: RedisClientFactory,
-    T: CmdTask,
+    T: CmdTask + ClusterTag,
     BC: TaskBlockingController,
 {
     type Task = T;
@@ -365,23 +417,32 @@ where
         };
 
         let meta = self.meta.clone();
+        let events = self.events.clone();
         let fut = self.run();
 
         let fut = async move {
+            events.record_state(MigrationState::PreCheck);
             let r = select! {
                 res = fut.fuse() => res,
                 _ = receiver.fuse() => Err(MigrationError::Canceled),
             };
-            match r {
+            let r = match r {
                 Ok(()) => {
                     info!("Migrating tasks stopped {:?}", meta);
+                    events.record(MigrationEventKind::State, "STOPPED".to_string());
                     Ok(())
                 }
                 Err(err) => {
                     error!("migration exit with error: {:?}", err);
+                    events.record(
+                        MigrationEventKind::Error,
+                        format!("exit with error: {:?}", err),
+                    );
                     Err(err)
                 }
-            }
+            };
+            events.finish();
+            r
         };
 
         Box::pin(fut)
@@ -400,6 +461,26 @@ where
             }
             MigrationState::PreBlocking | MigrationState::PreSwitch => {
                 let need_blocking = self.blocking_ctrl.is_blocking();
+                if need_blocking {
+                    let policy = self.mgr_config.get_blocking_policy(cmd_task.is_read_cmd());
+                    match policy {
+                        BlockingPolicy::Queue => (),
+                        BlockingPolicy::Reject => {
+                            let resp = Resp::Error(
+                                response::ERR_MIGRATION_BLOCKING.to_string().into_bytes(),
+                            );
+                            cmd_task.set_resp_result(Ok(resp));
+                            return Ok(());
+                        }
+                        BlockingPolicy::Redirect => {
+                            return handle_redirection(
+                                cmd_task,
+                                self.meta.dst_proxy_address.clone(),
+                                self.active_redirection,
+                            )
+                        }
+                    }
+                }
                 return Err(ClusterSendError::SlotNotFound(BlockingHintTask::new(
                     cmd_task,
                     need_blocking,
@@ -490,6 +571,7 @@ where
     >,
     _cmd_task_factory: Arc<CTF>,
     active_redirection: bool,
+    events: Arc<TaskEventLog>,
 }
 
 impl<RCF, TSF, PTSF, CTF> RedisScanImportingTask<RCF, TSF, PTSF, CTF>
@@ -512,6 +594,7 @@ where
         sender_factory: Arc<TSF>,
         proxy_sender_factory: Arc<PTSF>,
         cmd_task_factory: Arc<CTF>,
+        events: Arc<TaskEventLog>,
     ) -> Self {
         let src_sender = sender_factory.create(meta.src_node_address.clone());
         let dst_sender = sender_factory.create(meta.dst_node_address.clone());
@@ -521,6 +604,7 @@ where
             dst_sender,
             src_proxy_sender,
             cmd_task_factory.clone(),
+            meta.restore_policy,
         );
         let (stop_signal_sender, stop_signal_receiver) = oneshot::channel();
         let range_map = RangeMap::from(slot_range.get_range_list());
@@ -537,6 +621,7 @@ where
             cmd_handler,
             _cmd_task_factory: cmd_task_factory,
             active_redirection,
+            events,
         }
     }
 }
@@ -562,14 +647,20 @@ where
         };
 
         let meta = self.meta.clone();
+        let events = self.events.clone();
         let fut = self.cmd_handler.run_task_handler();
         let stop_handle = self.cmd_handler.get_stop_handle();
 
         let fut = async move {
+            events.record_state(MigrationState::PreCheck);
             let res = future::select(Box::pin(fut.fuse()), Box::pin(receiver.fuse())).await;
             match res {
                 future::Either::Left(_) => {
                     error!("handler exited unexpectedly");
+                    events.record(
+                        MigrationEventKind::Error,
+                        "handler exited unexpectedly".to_string(),
+                    );
                 }
                 future::Either::Right((_, handler_task)) => {
                     info!("Received stop signal. Wait for the handler to finish all the remaining commnands.");
@@ -578,6 +669,8 @@ where
                 }
             };
             warn!("Importing tasks stopped {:?}", meta);
+            events.record(MigrationEventKind::State, "STOPPED".to_string());
+            events.finish();
             Ok(())
         };
 
@@ -625,11 +718,13 @@ where
             return Err(MigrationError::IncompatibleVersion);
         }
 
-        match sub_cmd {
-            MgrSubCmd::PreCheck => self.state.set_state(MigrationState::PreCheck),
-            MgrSubCmd::PreSwitch => self.state.set_state(MigrationState::PreSwitch),
-            MgrSubCmd::FinalSwitch => self.state.set_state(MigrationState::SwitchCommitted),
-        }
+        let state = match sub_cmd {
+            MgrSubCmd::PreCheck => MigrationState::PreCheck,
+            MgrSubCmd::PreSwitch => MigrationState::PreSwitch,
+            MgrSubCmd::FinalSwitch => MigrationState::SwitchCommitted,
+        };
+        self.state.set_state(state);
+        self.events.record_state(state);
         Ok(())
     }
 }
@@ -664,7 +759,7 @@ fn handle_redirection<T: CmdTask>(
     let slot = match cmd_task.get_slot() {
         Some(slot) => slot,
         None => {
-            let resp = Resp::Error("missing key".to_string().into_bytes());
+            let resp = Resp::Error(response::ERR_MISSING_KEY.to_string().into_bytes());
             cmd_task.set_resp_result(Ok(resp));
             return Ok(());
         }
@@ -166,6 +166,8 @@ pub enum MigrationError {
     Io(io::Error),
     Timeout,
     InvalidConfig,
+    // The keys already exist in the destination with the `fail` restore policy.
+    KeyConflict(Vec<String>),
 }
 
 impl fmt::Display for MigrationError {
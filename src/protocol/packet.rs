@@ -1,11 +1,11 @@
 use super::decoder::DecodeError;
 use super::encoder::{command_to_buf, encode_resp};
 use super::fp::{RFunctor, VFunctor};
-use super::resp::{BinSafeStr, IndexedResp, Resp, RespSlice, RespVec};
+use super::resp::{Array, BinSafeStr, BulkStr, IndexedResp, Resp, RespSlice, RespVec};
 use super::stateless::{parse_indexed_resp, ParseError};
 use crate::common::utils::{
-    array_append_front, change_bulk_array_element, change_bulk_str, get_command_element,
-    get_command_len, left_trim_array, ThreadSafe,
+    array_append_back, array_append_front, change_bulk_array_element, change_bulk_str,
+    get_command_element, get_command_len, left_trim_array, ThreadSafe,
 };
 use crate::protocol::EncodeError;
 use bytes::BytesMut;
@@ -107,6 +107,21 @@ impl RespPacket {
         }
     }
 
+    // Used for memory accounting.
+    pub fn get_approx_size(&self) -> usize {
+        match self {
+            Self::Indexed(indexed_resp) => indexed_resp.get_data().len(),
+            Self::Data(resp) => get_resp_size(resp),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        match self {
+            Self::Indexed(indexed_resp) => indexed_resp.get_data().first() == Some(&b'-'),
+            Self::Data(resp) => matches!(resp, Resp::Error(_)),
+        }
+    }
+
     pub fn get_array_element(&self, index: usize) -> Option<&[u8]> {
         match self {
             Self::Indexed(indexed_resp) => indexed_resp.get_array_element(index),
@@ -184,6 +199,20 @@ impl RespPacket {
         }
     }
 
+    pub fn append_cmd(&mut self, following_elements: Vec<BinSafeStr>) -> bool {
+        match self {
+            Self::Indexed(index_resp) => {
+                let mut resp = index_resp.to_resp_vec();
+                if !array_append_back(&mut resp, following_elements) {
+                    return false;
+                }
+                *self = Self::Data(resp);
+                true
+            }
+            Self::Data(resp) => array_append_back(resp, following_elements),
+        }
+    }
+
     pub fn change_bulk_str(&mut self, data: Vec<u8>) -> bool {
         let mut resp = match self {
             Self::Indexed(indexed_resp) => indexed_resp.to_resp_vec(),
@@ -197,6 +226,15 @@ impl RespPacket {
     }
 }
 
+fn get_resp_size(resp: &RespVec) -> usize {
+    match resp {
+        Resp::Error(s) | Resp::Simple(s) | Resp::Integer(s) => s.len(),
+        Resp::Bulk(BulkStr::Str(s)) => s.len(),
+        Resp::Bulk(BulkStr::Nil) | Resp::Arr(Array::Nil) => 0,
+        Resp::Arr(Array::Arr(resps)) => resps.iter().map(get_resp_size).sum(),
+    }
+}
+
 impl From<RespVec> for RespPacket {
     fn from(resp: RespVec) -> Self {
         RespPacket::from_resp_vec(resp)
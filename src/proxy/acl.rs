@@ -0,0 +1,650 @@
+use super::backend::ConnFactory;
+use super::cluster::ClusterTag;
+use super::command::{uppercase_cmd_name, Command, DataCmdType};
+use super::key_spec::{get_key_spec, get_numkeys_index};
+use super::manager::SharedMetaMap;
+use super::session::CmdCtx;
+use crate::common::cluster::ClusterName;
+use crate::common::config::{AclCategory, AclConfig, AuthConfig, AuthUser};
+use crate::protocol::{BinSafeStr, RespPacket};
+use btoi::btou;
+
+#[derive(Debug, PartialEq)]
+pub enum AclError {
+    CategoryNotAllowed,
+    KeyNotAllowed,
+    InvalidRequest,
+}
+
+pub struct CmdAclChecker<C: ConnFactory<Pkt = RespPacket>> {
+    meta_map: SharedMetaMap<C>,
+}
+
+impl<C: ConnFactory<Pkt = RespPacket>> CmdAclChecker<C> {
+    pub fn new(meta_map: SharedMetaMap<C>) -> Self {
+        Self { meta_map }
+    }
+
+    pub fn check(&self, cmd_ctx: &CmdCtx, user: Option<&str>) -> Result<(), AclError> {
+        self.check_cmd(cmd_ctx.get_cluster_name(), cmd_ctx.get_cmd(), user)
+    }
+
+    pub fn check_cmd(
+        &self,
+        cluster_name: &ClusterName,
+        cmd: &Command,
+        user: Option<&str>,
+    ) -> Result<(), AclError> {
+        let meta_map = self.meta_map.lease();
+        match meta_map.get_cluster_map().get_config(cluster_name) {
+            Some(config) => {
+                check_cmd_acl(&config.acl, cmd)?;
+                match user.and_then(|user| config.auth.get_user(user)) {
+                    Some(user) => check_user_acl(&config.auth, user, cmd),
+                    None => Ok(()),
+                }
+            }
+            // The cluster not found error will be returned later.
+            None => Ok(()),
+        }
+    }
+}
+
+impl<C: ConnFactory<Pkt = RespPacket>> CmdAclChecker<C> {
+    // MONITOR streams the commands of all the sessions in the cluster.
+    pub fn allow_monitor(&self, cluster_name: &ClusterName, user: Option<&str>) -> bool {
+        let meta_map = self.meta_map.lease();
+        match meta_map.get_cluster_map().get_config(cluster_name) {
+            Some(config) => {
+                let user = user.and_then(|user| config.auth.get_user(user));
+                is_unrestricted(&config.acl, &config.auth, user)
+            }
+            None => true,
+        }
+    }
+
+    // The keys not allowed by the key patterns are removed from the replies of SCAN.
+    pub fn filter_keys(
+        &self,
+        cluster_name: &ClusterName,
+        keys: Vec<BinSafeStr>,
+    ) -> Vec<BinSafeStr> {
+        let meta_map = self.meta_map.lease();
+        match meta_map.get_cluster_map().get_config(cluster_name) {
+            Some(config) => keys
+                .into_iter()
+                .filter(|key| config.acl.allow_key(key))
+                .collect(),
+            None => keys,
+        }
+    }
+}
+
+pub fn is_unrestricted(acl: &AclConfig, auth: &AuthConfig, user: Option<&AuthUser>) -> bool {
+    if !acl.allow_all() {
+        return false;
+    }
+    match user {
+        Some(user) => user.allow_all_categories() && auth.allow_user_all_keys(&user.name),
+        None => true,
+    }
+}
+
+pub fn check_user_acl(auth: &AuthConfig, user: &AuthUser, cmd: &Command) -> Result<(), AclError> {
+    let category = get_cmd_category(cmd).ok_or(AclError::InvalidRequest)?;
+    if !user.allow_category(category) {
+        return Err(AclError::CategoryNotAllowed);
+    }
+    if auth.allow_user_all_keys(&user.name) {
+        return Ok(());
+    }
+    if has_external_key_patterns(cmd) {
+        return Err(AclError::KeyNotAllowed);
+    }
+    for key in get_cmd_keys(cmd)?.into_iter() {
+        if !auth.allow_user_key(&user.name, key) {
+            return Err(AclError::KeyNotAllowed);
+        }
+    }
+    Ok(())
+}
+
+pub fn check_cmd_acl(acl: &AclConfig, cmd: &Command) -> Result<(), AclError> {
+    if acl.allow_all() {
+        return Ok(());
+    }
+
+    let category = get_cmd_category(cmd).ok_or(AclError::InvalidRequest)?;
+    if !acl.allow_category(category) {
+        return Err(AclError::CategoryNotAllowed);
+    }
+
+    if acl.allow_all_keys() {
+        return Ok(());
+    }
+    if has_external_key_patterns(cmd) {
+        return Err(AclError::KeyNotAllowed);
+    }
+    for key in get_cmd_keys(cmd)?.into_iter() {
+        if !acl.allow_key(key) {
+            return Err(AclError::KeyNotAllowed);
+        }
+    }
+    Ok(())
+}
+
+// The commands are categorized by their `readonly` and `write` flags in the command table.
+// The unknown commands and the commands with neither flag are treated as writes.
+pub fn get_cmd_category(cmd: &Command) -> Option<AclCategory> {
+    let cmd_name = cmd.get_command_element(0)?;
+    let stack_cmd_name = uppercase_cmd_name(cmd_name)?;
+    let cmd_name: &[u8] = &stack_cmd_name;
+
+    let category = match cmd_name {
+        b"EVAL" | b"EVALSHA" | b"SCRIPT" => AclCategory::Scripting,
+        _ => match get_key_spec(cmd_name) {
+            Some(spec) if spec.readonly => AclCategory::Read,
+            _ => AclCategory::Write,
+        },
+    };
+    Some(category)
+}
+
+// Commands not listed here use the key positions in the command table,
+// or get their movable keys parsed from the arguments.
+// Unknown commands only get their first argument checked.
+pub fn get_cmd_key_indices(cmd: &Command) -> Result<Vec<usize>, AclError> {
+    let len = cmd.get_command_len().ok_or(AclError::InvalidRequest)?;
+    let indices: Vec<usize> = match cmd.get_data_cmd_type() {
+        DataCmdType::MGET
+        | DataCmdType::DEL
+        | DataCmdType::EXISTS
+        | DataCmdType::UNLINK
+        | DataCmdType::TOUCH => (1..len).collect(),
+        DataCmdType::MSET | DataCmdType::MSETNX => (1..len).step_by(2).collect(),
+        DataCmdType::BLPOP | DataCmdType::BRPOP | DataCmdType::BZPOPMIN | DataCmdType::BZPOPMAX => {
+            (1..len.saturating_sub(1)).collect()
+        }
+        DataCmdType::BRPOPLPUSH
+        | DataCmdType::BLMOVE
+        | DataCmdType::RPOPLPUSH
+        | DataCmdType::RENAME
+        | DataCmdType::RENAMENX
+        | DataCmdType::SMOVE => vec![1, 2],
+        DataCmdType::BITOP => (2..len).collect(),
+        DataCmdType::SCAN => vec![],
+        DataCmdType::EVAL | DataCmdType::EVALSHA => get_numkeys_indices(cmd, len, 2)?,
+        _ => match cmd.get_command_element(0).and_then(get_key_spec) {
+            Some(spec) if spec.has_fixed_keys() => spec.get_key_indices(len),
+            Some(spec) if spec.movable_keys => get_movable_key_indices(cmd, len)?,
+            _ => vec![1],
+        },
+    };
+    Ok(indices)
+}
+
+// The positions of the `movable_keys` commands depend on their arguments.
+fn get_movable_key_indices(cmd: &Command, len: usize) -> Result<Vec<usize>, AclError> {
+    let cmd_name = cmd.get_command_element(0).ok_or(AclError::InvalidRequest)?;
+    let is = |name: &[u8]| cmd_name.eq_ignore_ascii_case(name);
+    let arg_is = |i: usize, name: &[u8]| {
+        cmd.get_command_element(i)
+            .map(|arg| arg.eq_ignore_ascii_case(name))
+            .unwrap_or(false)
+    };
+
+    if let Some(numkeys_index) = get_numkeys_index(cmd_name) {
+        get_numkeys_indices(cmd, len, numkeys_index)
+    } else if is(b"ZUNIONSTORE") || is(b"ZINTERSTORE") || is(b"ZDIFFSTORE") {
+        // ZUNIONSTORE destination numkeys key [key ...] [WEIGHTS ...] [AGGREGATE ...]
+        let mut indices = vec![1];
+        indices.extend(get_numkeys_indices(cmd, len, 2)?);
+        Ok(indices)
+    } else if is(b"SORT") || is(b"GEORADIUS") || is(b"GEORADIUSBYMEMBER") {
+        // SORT key ... [STORE destination]
+        // GEORADIUS key ... [STORE key] [STOREDIST key]
+        let mut indices = vec![1];
+        let mut i = 2;
+        while i < len {
+            if arg_is(i, b"STORE") || (!is(b"SORT") && arg_is(i, b"STOREDIST")) {
+                indices.push(i + 1);
+                i += 1;
+            }
+            i += 1;
+        }
+        Ok(indices)
+    } else if is(b"XREAD") || is(b"XREADGROUP") {
+        // XREAD [COUNT count] [BLOCK ms] STREAMS key [key ...] id [id ...]
+        let streams = (1..len)
+            .find(|i| arg_is(*i, b"STREAMS"))
+            .ok_or(AclError::InvalidRequest)?;
+        let rest = len - streams - 1;
+        if rest == 0 || !rest.is_multiple_of(2) {
+            return Err(AclError::InvalidRequest);
+        }
+        Ok((streams + 1..streams + 1 + rest / 2).collect())
+    } else if is(b"MIGRATE") {
+        // MIGRATE host port key|"" db timeout [COPY] [REPLACE] [AUTH password]
+        //   [AUTH2 username password] [KEYS key [key ...]]
+        let key = cmd.get_command_element(3).ok_or(AclError::InvalidRequest)?;
+        if !key.is_empty() {
+            return Ok(vec![3]);
+        }
+        let mut i = 6;
+        while i < len {
+            if arg_is(i, b"AUTH") {
+                i += 2;
+            } else if arg_is(i, b"AUTH2") {
+                i += 3;
+            } else if arg_is(i, b"KEYS") {
+                return Ok((i + 1..len).collect());
+            } else {
+                i += 1;
+            }
+        }
+        Ok(vec![])
+    } else {
+        // Unknown positions could not be checked.
+        Err(AclError::InvalidRequest)
+    }
+}
+
+// numkeys key [key ...]
+fn get_numkeys_indices(
+    cmd: &Command,
+    len: usize,
+    numkeys_index: usize,
+) -> Result<Vec<usize>, AclError> {
+    let key_num = cmd
+        .get_command_element(numkeys_index)
+        .and_then(|n| btou::<usize>(n).ok())
+        .ok_or(AclError::InvalidRequest)?;
+    let first_key = numkeys_index + 1;
+    if key_num > len.saturating_sub(first_key) {
+        return Err(AclError::InvalidRequest);
+    }
+    Ok((first_key..first_key + key_num).collect())
+}
+
+// The BY and GET patterns of SORT could read any key,
+// so they are only allowed when all the keys are allowed.
+fn has_external_key_patterns(cmd: &Command) -> bool {
+    let is_sort = cmd
+        .get_command_element(0)
+        .map(|name| name.eq_ignore_ascii_case(b"SORT"))
+        .unwrap_or(false);
+    if !is_sort {
+        return false;
+    }
+    let len = cmd.get_command_len().unwrap_or(0);
+    let mut i = 2;
+    while i + 1 < len {
+        let (arg, pattern) = match (cmd.get_command_element(i), cmd.get_command_element(i + 1)) {
+            (Some(arg), Some(pattern)) => (arg, pattern),
+            _ => return true,
+        };
+        if arg.eq_ignore_ascii_case(b"BY") && pattern.contains(&b'*') {
+            return true;
+        }
+        if arg.eq_ignore_ascii_case(b"GET") && pattern != b"#" {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+pub fn get_cmd_keys(cmd: &Command) -> Result<Vec<&[u8]>, AclError> {
+    let indices = get_cmd_key_indices(cmd)?;
+    let mut keys = Vec::with_capacity(indices.len());
+    for i in indices.into_iter() {
+        let key = cmd.get_command_element(i).ok_or(AclError::InvalidRequest)?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::config::ClusterConfig;
+    use crate::protocol::{Array, BulkStr, Resp};
+
+    fn gen_cmd(cmd: Vec<&str>) -> Command {
+        let arr = cmd
+            .into_iter()
+            .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+            .collect();
+        let packet = RespPacket::Data(Resp::Arr(Array::Arr(arr)));
+        Command::new(Box::new(packet))
+    }
+
+    fn gen_acl(categories: &str, key_patterns: &str) -> AclConfig {
+        let mut config = ClusterConfig::default();
+        config.set_field("acl_categories", categories).unwrap();
+        config.set_field("acl_key_patterns", key_patterns).unwrap();
+        config.acl
+    }
+
+    #[test]
+    fn test_default_acl() {
+        let acl = AclConfig::default();
+        assert!(check_cmd_acl(&acl, &gen_cmd(vec!["SET", "key", "value"])).is_ok());
+        assert!(check_cmd_acl(&acl, &gen_cmd(vec!["EVAL", "script", "0"])).is_ok());
+    }
+
+    #[test]
+    fn test_user_acl() {
+        let mut config = ClusterConfig::default();
+        config
+            .set_field("auth_users", "reader:pwd:read,admin:pwd:all")
+            .unwrap();
+        let auth = &config.auth;
+        let reader = auth.get_user("reader").unwrap();
+        assert!(check_user_acl(auth, reader, &gen_cmd(vec!["GET", "key"])).is_ok());
+        assert_eq!(
+            check_user_acl(auth, reader, &gen_cmd(vec!["SET", "key", "value"])),
+            Err(AclError::CategoryNotAllowed)
+        );
+        let admin = auth.get_user("admin").unwrap();
+        assert!(check_user_acl(auth, admin, &gen_cmd(vec!["EVAL", "script", "0"])).is_ok());
+    }
+
+    #[test]
+    fn test_user_key_patterns_acl() {
+        let mut config = ClusterConfig::default();
+        config
+            .set_field("auth_users", "reader:pwd:read,admin:pwd:all")
+            .unwrap();
+        config
+            .set_field("auth_user_key_patterns", "reader:tenant:*")
+            .unwrap();
+        let auth = &config.auth;
+        let reader = auth.get_user("reader").unwrap();
+        assert!(check_user_acl(auth, reader, &gen_cmd(vec!["GET", "tenant:a"])).is_ok());
+        assert_eq!(
+            check_user_acl(auth, reader, &gen_cmd(vec!["MGET", "tenant:a", "other:b"])),
+            Err(AclError::KeyNotAllowed)
+        );
+        let admin = auth.get_user("admin").unwrap();
+        assert!(check_user_acl(auth, admin, &gen_cmd(vec!["GET", "other:b"])).is_ok());
+    }
+
+    #[test]
+    fn test_read_only_acl() {
+        let acl = gen_acl("read", "*");
+        assert!(check_cmd_acl(&acl, &gen_cmd(vec!["GET", "key"])).is_ok());
+        assert!(check_cmd_acl(&acl, &gen_cmd(vec!["hgetall", "key"])).is_ok());
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["SET", "key", "value"])),
+            Err(AclError::CategoryNotAllowed)
+        );
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["EVAL", "script", "1", "key"])),
+            Err(AclError::CategoryNotAllowed)
+        );
+        let read_cmds = vec![
+            vec!["HRANDFIELD", "key"],
+            vec!["ZRANDMEMBER", "key"],
+            vec!["SMISMEMBER", "key", "m"],
+            vec!["ZMSCORE", "key", "m"],
+            vec!["SINTERCARD", "2", "{t}a", "{t}b"],
+            vec!["LPOS", "key", "e"],
+            vec!["LCS", "{t}a", "{t}b"],
+            vec!["SUBSTR", "key", "0", "1"],
+            vec!["BITFIELD_RO", "key", "GET", "u8", "0"],
+            vec!["GEOSEARCH", "key", "FROMMEMBER", "m", "BYRADIUS", "1", "km"],
+            vec!["XINFO", "STREAM", "key"],
+            vec!["XPENDING", "key", "group"],
+            vec!["EXPIRETIME", "key"],
+        ];
+        for cmd in read_cmds.into_iter() {
+            let cmd = gen_cmd(cmd);
+            assert_eq!(get_cmd_category(&cmd), Some(AclCategory::Read));
+            assert!(check_cmd_acl(&acl, &cmd).is_ok());
+        }
+        assert_eq!(
+            get_cmd_category(&gen_cmd(vec!["GETDEL", "key"])),
+            Some(AclCategory::Write)
+        );
+        assert_eq!(
+            get_cmd_category(&gen_cmd(vec!["UNKNOWN", "key"])),
+            Some(AclCategory::Write)
+        );
+    }
+
+    #[test]
+    fn test_key_patterns_acl() {
+        let acl = gen_acl("all", "tenant:*");
+        assert!(check_cmd_acl(&acl, &gen_cmd(vec!["SET", "tenant:key", "v"])).is_ok());
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["SET", "other:key", "v"])),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert!(check_cmd_acl(
+            &acl,
+            &gen_cmd(vec!["MSET", "tenant:a", "other:b", "tenant:c", "d"])
+        )
+        .is_ok());
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["MGET", "tenant:a", "other:b"])),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check_cmd_acl(
+                &acl,
+                &gen_cmd(vec!["EVAL", "script", "2", "tenant:a", "other:b"])
+            ),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["BLPOP", "tenant:a", "other:b", "0"])),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert!(check_cmd_acl(&acl, &gen_cmd(vec!["BLPOP", "tenant:a", "0"])).is_ok());
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["TOUCH", "tenant:a", "other:b"])),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert!(check_cmd_acl(&acl, &gen_cmd(vec!["SCAN", "0", "COUNT", "10"])).is_ok());
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["GET"])),
+            Err(AclError::InvalidRequest)
+        );
+    }
+
+    #[test]
+    fn test_unrestricted() {
+        let mut config = ClusterConfig::default();
+        config
+            .set_field("auth_users", "reader:pwd:read,tenant:pwd:all,admin:pwd:all")
+            .unwrap();
+        config
+            .set_field("auth_user_key_patterns", "tenant:tenant:*")
+            .unwrap();
+        let auth = &config.auth;
+        assert!(is_unrestricted(&config.acl, auth, None));
+        assert!(is_unrestricted(&config.acl, auth, auth.get_user("admin")));
+        assert!(!is_unrestricted(&config.acl, auth, auth.get_user("reader")));
+        assert!(!is_unrestricted(&config.acl, auth, auth.get_user("tenant")));
+        assert!(!is_unrestricted(&gen_acl("read", "*"), auth, None));
+        assert!(!is_unrestricted(&gen_acl("all", "tenant:*"), auth, None));
+    }
+
+    #[test]
+    fn test_movable_keys() {
+        let indices = |cmd: Vec<&str>| get_cmd_key_indices(&gen_cmd(cmd));
+        assert_eq!(
+            indices(vec![
+                "ZUNIONSTORE",
+                "dest",
+                "2",
+                "a",
+                "b",
+                "WEIGHTS",
+                "1",
+                "2"
+            ]),
+            Ok(vec![1, 3, 4])
+        );
+        assert_eq!(
+            indices(vec!["zinterstore", "dest", "3", "a", "b"]),
+            Err(AclError::InvalidRequest)
+        );
+        assert_eq!(
+            indices(vec!["ZDIFFSTORE", "dest", "2", "a", "b"]),
+            Ok(vec![1, 3, 4])
+        );
+        assert_eq!(
+            indices(vec!["ZINTER", "2", "a", "b", "WITHSCORES"]),
+            Ok(vec![2, 3])
+        );
+        assert_eq!(
+            indices(vec!["SINTERCARD", "1", "a", "LIMIT", "1"]),
+            Ok(vec![2])
+        );
+        assert_eq!(
+            indices(vec!["SORT", "a", "LIMIT", "0", "1", "STORE", "dest"]),
+            Ok(vec![1, 6])
+        );
+        assert_eq!(
+            indices(vec![
+                "GEORADIUS",
+                "a",
+                "0",
+                "0",
+                "1",
+                "km",
+                "STOREDIST",
+                "dest"
+            ]),
+            Ok(vec![1, 7])
+        );
+        assert_eq!(
+            indices(vec![
+                "GEORADIUSBYMEMBER",
+                "a",
+                "m",
+                "1",
+                "km",
+                "STORE",
+                "dest"
+            ]),
+            Ok(vec![1, 6])
+        );
+        assert_eq!(
+            indices(vec!["XREAD", "COUNT", "1", "STREAMS", "a", "b", "0", "0"]),
+            Ok(vec![4, 5])
+        );
+        assert_eq!(
+            indices(vec!["XREADGROUP", "GROUP", "g", "c", "STREAMS", "a", ">"]),
+            Ok(vec![5])
+        );
+        assert_eq!(
+            indices(vec!["XREAD", "STREAMS", "a"]),
+            Err(AclError::InvalidRequest)
+        );
+        assert_eq!(
+            indices(vec!["MIGRATE", "host", "6379", "a", "0", "1000"]),
+            Ok(vec![3])
+        );
+        assert_eq!(
+            indices(vec![
+                "MIGRATE", "host", "6379", "", "0", "1000", "AUTH", "KEYS", "KEYS", "a", "b"
+            ]),
+            Ok(vec![9, 10])
+        );
+    }
+
+    #[test]
+    fn test_movable_keys_acl() {
+        let acl = gen_acl("all", "tenant:*");
+        let check = |cmd: Vec<&str>| check_cmd_acl(&acl, &gen_cmd(cmd));
+        assert!(check(vec!["ZUNIONSTORE", "tenant:d", "1", "tenant:a"]).is_ok());
+        assert_eq!(
+            check(vec!["ZUNIONSTORE", "tenant:d", "2", "tenant:a", "other:b"]),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check(vec!["SORT", "tenant:a", "STORE", "other:b"]),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check(vec!["SORT", "tenant:a", "BY", "other:*"]),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check(vec!["SORT", "tenant:a", "GET", "other:*"]),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert!(check(vec!["SORT", "tenant:a", "BY", "nosort", "GET", "#"]).is_ok());
+        assert_eq!(
+            check(vec!["XREAD", "STREAMS", "tenant:a", "other:b", "0", "0"]),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check(vec![
+                "GEORADIUS",
+                "tenant:a",
+                "0",
+                "0",
+                "1",
+                "km",
+                "STORE",
+                "other:b"
+            ]),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check(vec![
+                "MIGRATE", "h", "1", "", "0", "0", "KEYS", "tenant:a", "other:b"
+            ]),
+            Err(AclError::KeyNotAllowed)
+        );
+
+        let mut config = ClusterConfig::default();
+        config.set_field("auth_users", "reader:pwd:all").unwrap();
+        config
+            .set_field("auth_user_key_patterns", "reader:tenant:*")
+            .unwrap();
+        let auth = &config.auth;
+        let reader = auth.get_user("reader").unwrap();
+        assert_eq!(
+            check_user_acl(
+                auth,
+                reader,
+                &gen_cmd(vec!["ZINTERSTORE", "tenant:d", "2", "tenant:a", "other:b"])
+            ),
+            Err(AclError::KeyNotAllowed)
+        );
+        assert_eq!(
+            check_user_acl(
+                auth,
+                reader,
+                &gen_cmd(vec!["SORT", "tenant:a", "BY", "o:*"])
+            ),
+            Err(AclError::KeyNotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_eval_huge_key_num() {
+        let acl = gen_acl("all", "tenant:*");
+        assert_eq!(
+            check_cmd_acl(&acl, &gen_cmd(vec!["EVAL", "", "9999999999999"])),
+            Err(AclError::InvalidRequest)
+        );
+        assert_eq!(
+            get_cmd_key_indices(&gen_cmd(vec!["EVAL", "", "18446744073709551615", "k"])),
+            Err(AclError::InvalidRequest)
+        );
+        assert_eq!(
+            get_cmd_key_indices(&gen_cmd(vec!["EVAL", "", "2", "tenant:a"])),
+            Err(AclError::InvalidRequest)
+        );
+        assert_eq!(
+            get_cmd_key_indices(&gen_cmd(vec!["EVAL", "", "1", "tenant:a"])),
+            Ok(vec![3])
+        );
+    }
+}
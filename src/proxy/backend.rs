@@ -1,16 +1,26 @@
 use super::command::{CommandError, CommandResult};
+use super::limiter::{BatchPermits, GradientLimiter};
+use super::sentinel::{get_sentinel_master_name, resolve_master_address, wait_switch_master};
 use super::service::ServerProxyConfig;
 use super::slowlog::TaskEvent;
 use crate::common::batch::TryChunksTimeoutStreamExt;
-use crate::common::utils::{resolve_first_address, ThreadSafe};
+use crate::common::fair_queue::FairQueueStreamExt;
+use crate::common::response::ERR_BACKEND_CONNECTION;
+use crate::common::utils::{lookup_first_address, ThreadSafe};
 use crate::protocol::{
-    new_simple_packet_codec, DecodeError, EncodeError, EncodedPacket, FromResp, MonoPacket,
-    OptionalMulti, Packet, Resp, RespCodec, RespVec,
+    new_simple_packet_codec, Array, BulkStr, DecodeError, EncodeError, EncodedPacket, FromResp,
+    MonoPacket, OptionalMulti, Packet, Resp, RespCodec, RespVec,
 };
 use futures::channel::mpsc;
-use futures::{select, stream, Future, FutureExt, Sink, SinkExt, Stream, StreamExt, TryStreamExt};
+use futures::stream::FusedStream;
+use futures::{
+    future, select, stream, Future, FutureExt, Sink, SinkExt, Stream, StreamExt, TryStreamExt,
+};
 use futures_timer::Delay;
+use rand::Rng;
 use std::boxed::Box;
+use std::cmp::min;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
 use std::fmt;
 use std::io;
@@ -20,9 +30,10 @@ use std::num::NonZeroUsize;
 use std::pin::Pin;
 use std::result::Result;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
+use tokio::time;
 use tokio_util::codec::Decoder;
 
 pub type BackendResult<T> = Result<T, BackendError>;
@@ -51,6 +62,9 @@ pub trait CmdTask: ThreadSafe {
 
     fn get_key(&self) -> Option<&[u8]>;
     fn get_slot(&self) -> Option<usize>;
+    // The backends only keep the order of the tasks with the same session key.
+    // None for the tasks not from the client sessions.
+    fn get_session_key(&self) -> Option<usize>;
     fn set_result(self, result: CommandResult<Self::Pkt>);
     fn get_packet(&self) -> Self::Pkt;
     fn get_type(&self) -> Self::TaskType;
@@ -135,6 +149,13 @@ impl<T: CmdTask> CmdTask for ReqTask<T> {
         }
     }
 
+    fn get_session_key(&self) -> Option<usize> {
+        match self {
+            Self::Simple(t) => t.get_session_key(),
+            Self::Multi(v) => v.iter().find_map(|t| t.get_session_key()),
+        }
+    }
+
     fn set_result(self, result: CommandResult<Self::Pkt>) {
         match self {
             Self::Simple(t) => match result {
@@ -221,17 +242,24 @@ impl<T: CmdTask> CmdTask for ReqTask<T> {
 }
 
 #[derive(Debug)]
-pub struct BackendSendError<T>(T);
+pub enum BackendSendError<T> {
+    Closed(T),
+    Overloaded(T),
+}
 
 impl<T> BackendSendError<T> {
     pub fn into_inner(self) -> T {
-        self.0
+        match self {
+            Self::Closed(t) => t,
+            Self::Overloaded(t) => t,
+        }
     }
 }
 
 pub struct BackendNode<H: CmdTaskResultHandler> {
     tx: mpsc::UnboundedSender<H::Task>,
     conn_failed: Arc<AtomicBool>,
+    limiter: Arc<GradientLimiter>,
 }
 
 impl<H: CmdTaskResultHandler> BackendNode<H> {
@@ -251,28 +279,42 @@ impl<H: CmdTaskResultHandler> BackendNode<H> {
     {
         let (tx, rx) = mpsc::unbounded();
         let conn_failed = Arc::new(AtomicBool::new(false));
+        let limiter = Arc::new(GradientLimiter::new(config.backend_concurrency_limit));
         let handle_backend_fut = handle_backend(
             handler,
             rx,
             conn_failed.clone(),
+            limiter.clone(),
             address,
+            config.sentinel_addresses.clone(),
             config.backend_batch_min_time,
             config.backend_batch_max_time,
             config.backend_batch_buf,
+            config.backend_inflight_window,
+            config.backend_session_fairness,
+            config.backend_idle_check_time,
             conn_factory,
         );
-        (Self { tx, conn_failed }, handle_backend_fut)
+        let node = Self {
+            tx,
+            conn_failed,
+            limiter,
+        };
+        (node, handle_backend_fut)
     }
 
     pub fn send(&self, mut cmd_task: H::Task) -> Result<(), BackendSendError<H::Task>> {
         cmd_task.log_event(TaskEvent::SentToWritingQueue);
         if self.conn_failed.load(Ordering::SeqCst) {
-            return Err(BackendSendError(cmd_task));
+            return Err(BackendSendError::Closed(cmd_task));
+        }
+        if !self.limiter.try_acquire() {
+            return Err(BackendSendError::Overloaded(cmd_task));
         }
-        self.tx
-            .unbounded_send(cmd_task)
-            .map(|_| ())
-            .map_err(|e| BackendSendError(e.into_inner()))
+        self.tx.unbounded_send(cmd_task).map(|_| ()).map_err(|e| {
+            self.limiter.release(1);
+            BackendSendError::Closed(e.into_inner())
+        })
     }
 
     pub fn is_closed(&self) -> bool {
@@ -293,11 +335,74 @@ pub trait ConnFactory: ThreadSafe {
     ) -> Pin<Box<dyn Future<Output = CreateConnResult<Self::Pkt>> + Send>>;
 }
 
-pub struct DefaultConnFactory<P>(PhantomData<P>);
+// The tasks queued during the connection will wait for at most this long.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const IDLE_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+// The failed backends could only be reconnected at this rate
+// so that all the connections to it won't come back at the same time.
+const RECONNECTS_PER_SEC: f64 = 2.0;
+const RECONNECT_BURST: f64 = 4.0;
+
+struct ReconnectBudgetState {
+    tokens: f64,
+    last_time: Instant,
+}
+
+// The reconnection budget of each backend. The healthy backends are not limited.
+#[derive(Default)]
+struct ReconnectBudget {
+    failed_backends: Mutex<HashMap<SocketAddr, ReconnectBudgetState>>,
+}
+
+impl ReconnectBudget {
+    fn try_acquire(&self, addr: SocketAddr, now: Instant) -> bool {
+        let mut failed_backends = self
+            .failed_backends
+            .lock()
+            .expect("ReconnectBudget::try_acquire");
+        let state = match failed_backends.get_mut(&addr) {
+            Some(state) => state,
+            None => return true,
+        };
+        let elapsed = now.saturating_duration_since(state.last_time).as_secs_f64();
+        state.last_time = now;
+        state.tokens = (state.tokens + elapsed * RECONNECTS_PER_SEC).min(RECONNECT_BURST);
+        if state.tokens < 1.0 {
+            return false;
+        }
+        state.tokens -= 1.0;
+        true
+    }
+
+    fn on_result(&self, addr: SocketAddr, success: bool, now: Instant) {
+        let mut failed_backends = self
+            .failed_backends
+            .lock()
+            .expect("ReconnectBudget::on_result");
+        if success {
+            failed_backends.remove(&addr);
+        } else {
+            failed_backends
+                .entry(addr)
+                .or_insert_with(|| ReconnectBudgetState {
+                    tokens: RECONNECT_BURST,
+                    last_time: now,
+                });
+        }
+    }
+}
+
+pub struct DefaultConnFactory<P> {
+    budget: Arc<ReconnectBudget>,
+    phantom: PhantomData<P>,
+}
 
 impl<P> Default for DefaultConnFactory<P> {
     fn default() -> Self {
-        Self(PhantomData)
+        Self {
+            budget: Arc::new(ReconnectBudget::default()),
+            phantom: PhantomData,
+        }
     }
 }
 
@@ -308,7 +413,15 @@ impl<P: MonoPacket> ConnFactory for DefaultConnFactory<P> {
         &self,
         addr: SocketAddr,
     ) -> Pin<Box<dyn Future<Output = CreateConnResult<Self::Pkt>> + Send>> {
-        Box::pin(create_conn(addr))
+        if !self.budget.try_acquire(addr, Instant::now()) {
+            return Box::pin(future::ready(Err(BackendError::TooManyReconnects)));
+        }
+        let budget = self.budget.clone();
+        Box::pin(async move {
+            let res = create_conn(addr).await;
+            budget.on_result(addr, res.is_ok(), Instant::now());
+            res
+        })
     }
 }
 
@@ -316,12 +429,16 @@ async fn create_conn<T>(address: SocketAddr) -> CreateConnResult<T>
 where
     T: MonoPacket,
 {
-    let socket = match TcpStream::connect(address).await {
-        Ok(socket) => socket,
-        Err(err) => {
+    let socket = match time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(Ok(socket)) => socket,
+        Ok(Err(err)) => {
             error!("failed to connect: {} {:?}", address, err);
             return Err(BackendError::Io(err));
         }
+        Err(_) => {
+            error!("connect timeout: {}", address);
+            return Err(BackendError::Io(io::Error::from(io::ErrorKind::TimedOut)));
+        }
     };
 
     let (encoder, decoder) = new_simple_packet_codec::<T, T>();
@@ -347,6 +464,16 @@ where
 }
 
 const MAX_BACKEND_RETRY: usize = 3;
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY_EXP: u32 = 5;
+
+// Doubles for each consecutive failure up to 32 seconds.
+// The jitter is between 0.5 and 1 so that the connections to the same backend
+// won't retry at the same time.
+fn get_reconnect_delay(failures: u32, jitter: f64) -> Duration {
+    let exp = min(failures.saturating_sub(1), MAX_RECONNECT_DELAY_EXP);
+    (RECONNECT_BASE_DELAY * 2u32.pow(exp)).mul_f64(jitter)
+}
 
 struct RetryState<T: CmdTask> {
     retry_times: usize,
@@ -358,42 +485,74 @@ pub async fn handle_backend<H, F>(
     handler: Arc<H>,
     task_receiver: mpsc::UnboundedReceiver<H::Task>,
     conn_failed: Arc<AtomicBool>,
+    limiter: Arc<GradientLimiter>,
     address: String,
+    sentinel_addresses: Vec<String>,
     backend_batch_min_time: usize,
     backend_batch_max_time: usize,
     backend_batch_buf: NonZeroUsize,
+    backend_inflight_window: usize,
+    backend_session_fairness: bool,
+    backend_idle_check_time: u64,
     conn_factory: Arc<F>,
 ) -> Result<(), BackendError>
 where
     H: CmdTaskResultHandler,
     F: ConnFactory<Pkt = <H::Task as CmdTask>::Pkt> + Send + Sync + 'static,
 {
-    // TODO: move this to upper layer.
-    let sock_address = match resolve_first_address(&address) {
-        Some(addr) => addr,
-        None => {
-            error!("invalid address: {:?}", address);
-            return Err(BackendError::InvalidAddress);
-        }
-    };
+    let sentinel_master = get_sentinel_master_name(&address).map(|name| name.to_string());
 
     let mut retry_state: Option<RetryState<H::Task>> = None;
+    let mut conn_failures: u32 = 0;
 
     let batch_min_time = Duration::from_nanos(backend_batch_min_time as u64);
     let batch_max_time = Duration::from_nanos(backend_batch_max_time as u64);
+    let idle_check_time = match backend_idle_check_time {
+        0 => None,
+        t => Some(Duration::from_millis(t)),
+    };
+    let task_receiver = if backend_session_fairness {
+        task_receiver
+            .fair_queue(|task: &H::Task| task.get_session_key())
+            .left_stream()
+    } else {
+        task_receiver.right_stream()
+    };
     let mut task_receiver = task_receiver
         .try_chunks_timeout(backend_batch_buf, batch_min_time, batch_max_time)
         .fuse();
 
     loop {
-        let (writer, reader) = match conn_factory.create_conn(sock_address).await {
+        // The tasks are queued in `task_receiver` during the connection.
+        // The address is resolved every time in case the DNS record is changed.
+        let conn_res = match sentinel_master.as_ref() {
+            Some(master_name) => {
+                match resolve_master_address(&sentinel_addresses, master_name).await {
+                    Ok(sock_address) => conn_factory.create_conn(sock_address).await,
+                    Err(err) => {
+                        error!("failed to resolve master from sentinels: {:?}", err);
+                        Err(BackendError::InvalidAddress)
+                    }
+                }
+            }
+            None => match lookup_first_address(&address).await {
+                Some(sock_address) => conn_factory.create_conn(sock_address).await,
+                None => Err(BackendError::InvalidAddress),
+            },
+        };
+        let (writer, reader) = match conn_res {
             Ok(conn) => conn,
             Err(err) => {
                 conn_failed.store(true, Ordering::SeqCst);
                 error!("failed to connect: {} {:?}", address, err);
-                retry_state.take();
+                if let Some(state) = retry_state.take() {
+                    limiter.release(state.tasks.len());
+                }
 
-                let mut timeout_fut = Delay::new(Duration::from_secs(1)).fuse();
+                conn_failures = conn_failures.saturating_add(1);
+                let jitter = rand::thread_rng().gen_range(0.5, 1.0);
+                let delay = get_reconnect_delay(conn_failures, jitter);
+                let mut timeout_fut = Delay::new(delay).fuse();
                 loop {
                     let mut tasks_fut = task_receiver.next().fuse();
                     let tasks_opt = select! {
@@ -407,9 +566,10 @@ where
                             return Err(BackendError::Canceled);
                         }
                     };
+                    limiter.release(tasks.len());
                     for task in tasks.into_iter() {
                         task.set_resp_result(Ok(Resp::Error(
-                            format!("failed to connect to {}", address).into_bytes(),
+                            format!("{}: {}", ERR_BACKEND_CONNECTION, address).into_bytes(),
                         )))
                     }
                 }
@@ -417,16 +577,50 @@ where
             }
         };
         conn_failed.store(false, Ordering::SeqCst);
-
-        let res = handle_conn(
-            writer,
-            reader,
-            &mut task_receiver,
-            handler.clone(),
-            backend_batch_buf,
-            retry_state.take(),
-        )
-        .await;
+        conn_failures = 0;
+
+        let conn_fut = if backend_inflight_window == 0 {
+            handle_conn(
+                writer,
+                reader,
+                &mut task_receiver,
+                handler.clone(),
+                &limiter,
+                backend_batch_buf,
+                idle_check_time,
+                retry_state.take(),
+            )
+            .left_future()
+        } else {
+            handle_pipelined_conn(
+                writer,
+                reader,
+                &mut task_receiver,
+                handler.clone(),
+                &limiter,
+                backend_batch_buf,
+                backend_inflight_window,
+                idle_check_time,
+                retry_state.take(),
+            )
+            .right_future()
+        };
+        let res = match sentinel_master.as_ref() {
+            None => conn_fut.await,
+            Some(master_name) => {
+                let mut conn_fut = Box::pin(conn_fut.fuse());
+                let mut switch_fut = Box::pin(
+                    wait_switch_master(sentinel_addresses.clone(), master_name.clone()).fuse(),
+                );
+                select! {
+                    res = conn_fut => res,
+                    () = switch_fut => {
+                        warn!("master is switched, reconnect to the new master: {}", address);
+                        continue;
+                    },
+                }
+            }
+        };
         match res {
             Ok(()) => {
                 warn!("task receiver is closed");
@@ -441,12 +635,15 @@ where
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_conn<H, S>(
     mut writer: ConnSink<<<H as CmdTaskResultHandler>::Task as CmdTask>::Pkt>,
     mut reader: ConnStream<<<H as CmdTaskResultHandler>::Task as CmdTask>::Pkt>,
     task_receiver: &mut S,
     handler: Arc<H>,
+    limiter: &GradientLimiter,
     backend_batch_buf: NonZeroUsize,
+    idle_check_time: Option<Duration>,
     mut retry_state_opt: Option<RetryState<H::Task>>,
 ) -> Result<(), (BackendError, Option<RetryState<H::Task>>)>
 where
@@ -454,6 +651,7 @@ where
     S: Stream<Item = Vec<H::Task>> + Unpin,
 {
     let mut packets = Vec::with_capacity(backend_batch_buf.get());
+    let mut last_active = Instant::now();
 
     loop {
         let (retry_times_opt, mut tasks) = match retry_state_opt.take() {
@@ -467,11 +665,22 @@ where
             }
         };
 
+        if let Some(ping) = gen_idle_check_ping(idle_check_time, last_active, &tasks) {
+            if let Err(err) = ping_conn(&mut writer, &mut reader, ping).await {
+                warn!("idle backend connection is broken: {:?}", err);
+                // The tasks are not sent yet so they are not counted as retries.
+                let retry_times = retry_times_opt.unwrap_or(0);
+                return Err((err, Some(RetryState { retry_times, tasks })));
+            }
+        }
+
+        let mut permits = BatchPermits::new(limiter, tasks.len());
         for task in tasks.iter_mut() {
             task.log_event(TaskEvent::WritingQueueReceived);
             packets.push(task.get_packet());
         }
 
+        let start = Instant::now();
         let mut batch = stream::iter(packets.drain(..)).map(Ok);
         let res = writer.send_all(&mut batch).await;
 
@@ -482,6 +691,9 @@ where
         if let Err(err) = res {
             error!("backend write error: {}", err);
             let retry_state = handle_conn_err(retry_times_opt, tasks, &err);
+            if retry_state.is_some() {
+                permits.forget();
+            }
             return Err((err, retry_state));
         }
 
@@ -501,16 +713,197 @@ where
                     failed_tasks.extend(tasks_iter);
                     let err = BackendError::Io(io::Error::from(io::ErrorKind::BrokenPipe));
                     let retry_state = handle_conn_err(retry_times_opt, failed_tasks, &err);
+                    if retry_state.is_some() {
+                        permits.forget();
+                    }
                     return Err((err, retry_state));
                 }
             };
 
             task.log_event(TaskEvent::ReceivedFromBackend);
             handler.handle_task(task, packet_res);
+            permits.release_one();
+        }
+        limiter.on_sample(start.elapsed());
+        last_active = Instant::now();
+    }
+}
+
+struct InflightTask<T: CmdTask> {
+    task: T,
+    retry_times: usize,
+    // Only set for the last task of a batch to sample the latency.
+    batch_start: Option<Instant>,
+}
+
+enum PipelineEvent<T: CmdTask> {
+    Tasks(usize, Vec<T>),
+    Reply(Option<Result<T::Pkt, BackendError>>),
+}
+
+// Keeps sending the following batches without waiting for the replies of the previous ones
+// until the in-flight tasks reach the window.
+// The replies are matched with the tasks in the sending order.
+#[allow(clippy::too_many_arguments)]
+async fn handle_pipelined_conn<H, S>(
+    mut writer: ConnSink<<<H as CmdTaskResultHandler>::Task as CmdTask>::Pkt>,
+    mut reader: ConnStream<<<H as CmdTaskResultHandler>::Task as CmdTask>::Pkt>,
+    task_receiver: &mut S,
+    handler: Arc<H>,
+    limiter: &GradientLimiter,
+    backend_batch_buf: NonZeroUsize,
+    inflight_window: usize,
+    idle_check_time: Option<Duration>,
+    mut retry_state_opt: Option<RetryState<H::Task>>,
+) -> Result<(), (BackendError, Option<RetryState<H::Task>>)>
+where
+    H: CmdTaskResultHandler,
+    S: Stream<Item = Vec<H::Task>> + FusedStream + Unpin,
+{
+    let mut packets = Vec::with_capacity(backend_batch_buf.get());
+    let mut inflight: VecDeque<InflightTask<H::Task>> = VecDeque::new();
+    let mut permits = BatchPermits::new(limiter, 0);
+    let mut receiver_closed = false;
+    let mut last_active = Instant::now();
+
+    loop {
+        let event = if let Some(RetryState { retry_times, tasks }) = retry_state_opt.take() {
+            PipelineEvent::Tasks(retry_times, tasks)
+        } else if inflight.is_empty() {
+            let tasks = match task_receiver.next().await {
+                Some(tasks) => tasks,
+                None => return Ok(()),
+            };
+            if let Some(ping) = gen_idle_check_ping(idle_check_time, last_active, &tasks) {
+                if let Err(err) = ping_conn(&mut writer, &mut reader, ping).await {
+                    warn!("idle backend connection is broken: {:?}", err);
+                    // The tasks are not sent yet so they are not counted as retries.
+                    return Err((
+                        err,
+                        Some(RetryState {
+                            retry_times: 0,
+                            tasks,
+                        }),
+                    ));
+                }
+            }
+            PipelineEvent::Tasks(0, tasks)
+        } else if receiver_closed || inflight.len() >= inflight_window {
+            PipelineEvent::Reply(reader.next().await)
+        } else {
+            select! {
+                tasks = task_receiver.next() => match tasks {
+                    Some(tasks) => PipelineEvent::Tasks(0, tasks),
+                    None => {
+                        receiver_closed = true;
+                        continue;
+                    }
+                },
+                reply = reader.next().fuse() => PipelineEvent::Reply(reply),
+            }
+        };
+
+        match event {
+            PipelineEvent::Tasks(retry_times, mut tasks) => {
+                permits.add(tasks.len());
+                for task in tasks.iter_mut() {
+                    task.log_event(TaskEvent::WritingQueueReceived);
+                    packets.push(task.get_packet());
+                }
+
+                let start = Instant::now();
+                let mut batch = stream::iter(packets.drain(..)).map(Ok);
+                let res = writer.send_all(&mut batch).await;
+
+                let last = tasks.len().saturating_sub(1);
+                for (i, mut task) in tasks.into_iter().enumerate() {
+                    task.log_event(TaskEvent::SentToBackend);
+                    inflight.push_back(InflightTask {
+                        task,
+                        retry_times,
+                        batch_start: if i == last { Some(start) } else { None },
+                    });
+                }
+
+                if let Err(err) = res {
+                    error!("backend write error: {}", err);
+                    return Err(handle_pipeline_err(inflight, permits, err));
+                }
+            }
+            PipelineEvent::Reply(Some(packet_res)) => {
+                let InflightTask {
+                    mut task,
+                    batch_start,
+                    ..
+                } = match inflight.pop_front() {
+                    Some(inflight_task) => inflight_task,
+                    None => {
+                        error!("unexpected reply without in-flight task");
+                        return Err((BackendError::InvalidState, None));
+                    }
+                };
+                task.log_event(TaskEvent::ReceivedFromBackend);
+                handler.handle_task(task, packet_res);
+                permits.release_one();
+                last_active = Instant::now();
+                if let Some(start) = batch_start {
+                    limiter.on_sample(start.elapsed());
+                }
+            }
+            PipelineEvent::Reply(None) => {
+                error!("Failed to read packet. Connection is closed.");
+                let err = BackendError::Io(io::Error::from(io::ErrorKind::BrokenPipe));
+                return Err(handle_pipeline_err(inflight, permits, err));
+            }
         }
     }
 }
 
+// The connections idle for a long time could have been dropped silently by NAT or conntrack.
+// Returns the PING packet when the connection needs to be checked before reuse.
+fn gen_idle_check_ping<T: CmdTask>(
+    idle_check_time: Option<Duration>,
+    last_active: Instant,
+    tasks: &[T],
+) -> Option<T::Pkt> {
+    match idle_check_time {
+        Some(idle_check_time) if last_active.elapsed() >= idle_check_time => (),
+        _ => return None,
+    }
+    let hint = tasks.first()?.get_packet().get_hint();
+    let ping = Resp::Arr(Array::Arr(vec![Resp::Bulk(BulkStr::Str(b"PING".to_vec()))]));
+    Some(T::Pkt::from_resp(ping, hint))
+}
+
+// Any reply, including an error, shows that the connection still works.
+async fn ping_conn<P: Packet>(
+    writer: &mut ConnSink<P>,
+    reader: &mut ConnStream<P>,
+    ping: P,
+) -> Result<(), BackendError> {
+    writer.send(ping).await?;
+    match time::timeout(IDLE_CHECK_TIMEOUT, reader.next()).await {
+        Ok(Some(res)) => res.map(|_| ()),
+        Ok(None) => Err(BackendError::Io(io::Error::from(io::ErrorKind::BrokenPipe))),
+        Err(_) => Err(BackendError::Io(io::Error::from(io::ErrorKind::TimedOut))),
+    }
+}
+
+// All the in-flight tasks are retried together.
+fn handle_pipeline_err<T: CmdTask>(
+    inflight: VecDeque<InflightTask<T>>,
+    permits: BatchPermits,
+    err: BackendError,
+) -> (BackendError, Option<RetryState<T>>) {
+    let retry_times = inflight.iter().map(|t| t.retry_times).max();
+    let tasks = inflight.into_iter().map(|t| t.task).collect();
+    let retry_state = handle_conn_err(retry_times, tasks, &err);
+    if retry_state.is_some() {
+        permits.forget();
+    }
+    (err, retry_state)
+}
+
 fn handle_conn_err<T: CmdTask>(
     retry_times_opt: Option<usize>,
     tasks: Vec<T>,
@@ -546,6 +939,8 @@ pub enum BackendError {
     InvalidAddress,
     Canceled,
     InvalidState,
+    Overloaded,
+    TooManyReconnects,
 }
 
 impl fmt::Display for BackendError {
@@ -566,3 +961,57 @@ impl Error for BackendError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::RespPacket;
+
+    #[test]
+    fn test_reconnect_delay() {
+        assert_eq!(get_reconnect_delay(1, 1.0), Duration::from_secs(1));
+        assert_eq!(get_reconnect_delay(3, 1.0), Duration::from_secs(4));
+        assert_eq!(get_reconnect_delay(3, 0.5), Duration::from_secs(2));
+        assert_eq!(get_reconnect_delay(100, 1.0), Duration::from_secs(32));
+    }
+
+    #[test]
+    fn test_reconnect_budget() {
+        let budget = ReconnectBudget::default();
+        let addr: SocketAddr = "127.0.0.1:6379".parse().unwrap();
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(budget.try_acquire(addr, now));
+        }
+
+        budget.on_result(addr, false, now);
+        for _ in 0..(RECONNECT_BURST as usize) {
+            assert!(budget.try_acquire(addr, now));
+        }
+        assert!(!budget.try_acquire(addr, now));
+        assert!(budget.try_acquire(addr, now + Duration::from_secs(1)));
+
+        budget.on_result(addr, true, now);
+        assert!(budget.try_acquire(addr, now));
+    }
+
+    fn gen_conn(replies: Vec<RespVec>) -> (ConnSink<RespPacket>, ConnStream<RespPacket>) {
+        let writer = futures::sink::drain().sink_map_err(|_| BackendError::Canceled);
+        let replies = replies
+            .into_iter()
+            .map(|resp| Ok(RespPacket::from_resp_vec(resp)));
+        (Box::pin(writer), Box::pin(stream::iter(replies)))
+    }
+
+    #[tokio::test]
+    async fn test_ping_conn() {
+        let ping = || RespPacket::from_resp_vec(Resp::Simple(b"PING".to_vec()));
+        let (mut writer, mut reader) = gen_conn(vec![Resp::Simple(b"PONG".to_vec())]);
+        assert!(ping_conn(&mut writer, &mut reader, ping()).await.is_ok());
+        // The connection is closed.
+        assert!(ping_conn(&mut writer, &mut reader, ping()).await.is_err());
+
+        let (mut writer, mut reader) = gen_conn(vec![Resp::Error(b"NOAUTH".to_vec())]);
+        assert!(ping_conn(&mut writer, &mut reader, ping()).await.is_ok());
+    }
+}
@@ -10,6 +10,7 @@ use super::sender::{
 use super::service::ServerProxyConfig;
 use super::slowlog::TaskEvent;
 use crate::common::cluster::ClusterName;
+use crate::common::response::UM_INTERNAL;
 use crate::common::track::TrackedFutureRegistry;
 use crate::common::utils::{ThreadSafe, Wrapper};
 use crate::protocol::{Resp, RespVec};
@@ -275,7 +276,7 @@ where
         if let Err(err) = self.queue_sender.send(cmd_task) {
             let cmd_task = err.into_inner();
             cmd_task.set_resp_result(Ok(Resp::Error(
-                b"failed to send to blocking queue".to_vec(),
+                format!("{} failed to send to blocking queue", UM_INTERNAL).into_bytes(),
             )));
             error!("failed to send to blocking queue");
             return Err(BackendError::Canceled);
@@ -431,6 +432,10 @@ impl<T: CmdTask + ClusterTag> ClusterTag for CounterTask<T> {
     fn set_cluster_name(&mut self, cluster_name: ClusterName) {
         self.inner.set_cluster_name(cluster_name)
     }
+
+    fn is_read_cmd(&self) -> bool {
+        self.inner.is_read_cmd()
+    }
 }
 
 impl<T: CmdTask> CmdTask for CounterTask<T> {
@@ -446,6 +451,10 @@ impl<T: CmdTask> CmdTask for CounterTask<T> {
         self.inner.get_slot()
     }
 
+    fn get_session_key(&self) -> Option<usize> {
+        self.inner.get_session_key()
+    }
+
     fn set_result(self, result: CommandResult<Self::Pkt>) {
         self.into_inner().set_result(result)
     }
@@ -509,6 +518,10 @@ impl<T: CmdTask> CmdTask for BlockingHintTask<T> {
         self.inner.get_slot()
     }
 
+    fn get_session_key(&self) -> Option<usize> {
+        self.inner.get_session_key()
+    }
+
     fn set_result(self, result: CommandResult<Self::Pkt>) {
         self.into_inner().set_result(result)
     }
@@ -545,6 +558,10 @@ impl<T: CmdTask + ClusterTag> ClusterTag for BlockingHintTask<T> {
     fn set_cluster_name(&mut self, cluster_name: ClusterName) {
         self.inner.set_cluster_name(cluster_name)
     }
+
+    fn is_read_cmd(&self) -> bool {
+        self.inner.is_read_cmd()
+    }
 }
 
 impl<T: CmdTask + ClusterTag> IntoTask<T> for BlockingHintTask<T> {
@@ -0,0 +1,332 @@
+use super::command::{Command, DataCmdType};
+use crate::protocol::{Array, BinSafeStr, BulkStr, Resp, RespVec};
+use std::cmp::min;
+use std::str;
+use std::time::{Duration, Instant};
+
+// The blocking list, sorted set and stream commands are sent to the backend owning the slot on dedicated connections
+// so that they won't block the other commands in the shared pipeline.
+// Dropping the connection in the middle of blocking closes it and unblocks the backend,
+// which happens when the client disconnects.
+pub const BLOCKING_CONN_POOL_SIZE: usize = 16;
+// A long timeout is split into rounds so that the changes of the slot owner could be noticed.
+pub const BLOCKING_ROUND_TIMEOUT: Duration = Duration::from_secs(30);
+// The connection needs to wait a little longer than the timeout sent to the backend.
+pub const BLOCKING_CONN_TIMEOUT: Duration = Duration::from_secs(35);
+// Reply nil a little before the proxy times out the command.
+const PROXY_TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+pub struct BlockingTimeout {
+    // None means blocking forever.
+    deadline: Option<Instant>,
+    // The command needs to be replied before the proxy times it out.
+    proxy_deadline: Option<Instant>,
+    // Redis before 6.0 only accepts integer timeouts.
+    decimal: bool,
+    // The BLOCK option of XREAD and XREADGROUP is in milliseconds.
+    millis: bool,
+}
+
+impl BlockingTimeout {
+    // `proxy_timeout` is the `command_timeout_blocking` in milliseconds and zero means no timeout.
+    pub fn parse(arg: &[u8], proxy_timeout: u64, now: Instant) -> Option<Self> {
+        let s = str::from_utf8(arg).ok()?;
+        let secs: f64 = s.parse().ok()?;
+        if !secs.is_finite() || secs < 0.0 {
+            return None;
+        }
+        let timeout = if secs == 0.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(secs))
+        };
+        Some(Self::new(
+            timeout,
+            proxy_timeout,
+            now,
+            s.contains('.'),
+            false,
+        ))
+    }
+
+    // For the `BLOCK milliseconds` option of XREAD and XREADGROUP.
+    pub fn parse_millis(arg: &[u8], proxy_timeout: u64, now: Instant) -> Option<Self> {
+        let s = str::from_utf8(arg).ok()?;
+        let millis: u64 = s.parse().ok()?;
+        let timeout = if millis == 0 {
+            None
+        } else {
+            Some(Duration::from_millis(millis))
+        };
+        Some(Self::new(timeout, proxy_timeout, now, false, true))
+    }
+
+    fn new(
+        timeout: Option<Duration>,
+        proxy_timeout: u64,
+        now: Instant,
+        decimal: bool,
+        millis: bool,
+    ) -> Self {
+        let proxy_timeout = Duration::from_millis(proxy_timeout);
+        let proxy_timeout = if proxy_timeout > PROXY_TIMEOUT_MARGIN {
+            Some(proxy_timeout - PROXY_TIMEOUT_MARGIN)
+        } else {
+            None
+        };
+        Self {
+            deadline: timeout.map(|t| now + t),
+            proxy_deadline: proxy_timeout.map(|t| now + t),
+            decimal,
+            millis,
+        }
+    }
+
+    // Returns the timeout argument for the next round, or None after the deadline.
+    pub fn next_round(&self, now: Instant) -> Option<BinSafeStr> {
+        let remaining = |deadline: Option<Instant>| match deadline {
+            None => BLOCKING_ROUND_TIMEOUT,
+            Some(deadline) => min(
+                deadline.saturating_duration_since(now),
+                BLOCKING_ROUND_TIMEOUT,
+            ),
+        };
+        let timeout = remaining(self.deadline);
+        let max_timeout = remaining(self.proxy_deadline);
+        // Zero means blocking forever for Redis.
+        let arg = if self.millis {
+            let millis = min(timeout, max_timeout).as_millis();
+            if millis == 0 {
+                return None;
+            }
+            millis.to_string()
+        } else if self.decimal {
+            let timeout = min(timeout, max_timeout);
+            if timeout.as_millis() == 0 {
+                return None;
+            }
+            format!("{}.{:03}", timeout.as_secs(), timeout.subsec_millis())
+        } else {
+            // Round up the timeout of the command but never exceed the proxy timeout.
+            let mut secs = timeout.as_secs();
+            if timeout.subsec_nanos() != 0 {
+                secs += 1;
+            }
+            let secs = min(secs, max_timeout.as_secs());
+            if secs == 0 {
+                return None;
+            }
+            secs.to_string()
+        };
+        Some(arg.into_bytes())
+    }
+}
+
+// The timeout is the last argument except for XREAD and XREADGROUP
+// which only block with the `BLOCK milliseconds` option.
+pub fn get_blocking_timeout_index(cmd: &Command) -> Option<usize> {
+    let len = cmd.get_command_len()?;
+    match cmd.get_data_cmd_type() {
+        DataCmdType::XREAD | DataCmdType::XREADGROUP => get_block_option_index(cmd, len),
+        _ => len.checked_sub(1),
+    }
+}
+
+// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS ...
+fn get_block_option_index(cmd: &Command, len: usize) -> Option<usize> {
+    let mut i = match cmd.get_data_cmd_type() {
+        DataCmdType::XREADGROUP => 4,
+        _ => 1,
+    };
+    while i < len {
+        let arg = cmd.get_command_element(i)?;
+        if arg.eq_ignore_ascii_case(b"STREAMS") {
+            return None;
+        }
+        if arg.eq_ignore_ascii_case(b"BLOCK") {
+            return Some(i + 1).filter(|index| *index < len);
+        }
+        if arg.eq_ignore_ascii_case(b"NOACK") {
+            i += 1;
+        } else {
+            i += 2;
+        }
+    }
+    None
+}
+
+pub fn get_command_elements(cmd: &Command) -> Option<Vec<BinSafeStr>> {
+    let len = cmd.get_command_len()?;
+    (0..len)
+        .map(|i| cmd.get_command_element(i).map(|e| e.to_vec()))
+        .collect()
+}
+
+// The command with its timeout argument replaced.
+pub fn gen_blocking_round_cmd(
+    elements: &[BinSafeStr],
+    timeout_index: usize,
+    timeout_arg: BinSafeStr,
+) -> Vec<BinSafeStr> {
+    let mut elements = elements.to_vec();
+    if let Some(timeout) = elements.get_mut(timeout_index) {
+        *timeout = timeout_arg;
+    }
+    elements
+}
+
+// The XREAD or XREADGROUP without the `BLOCK milliseconds` option for polling.
+pub fn gen_non_blocking_stream_cmd(
+    elements: &[BinSafeStr],
+    timeout_index: usize,
+) -> Vec<BinSafeStr> {
+    elements
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i + 1 != timeout_index && *i != timeout_index)
+        .map(|(_, e)| e.clone())
+        .collect()
+}
+
+pub fn is_blocking_timeout_reply(resp: &RespVec) -> bool {
+    matches!(resp, Resp::Arr(Array::Nil) | Resp::Bulk(BulkStr::Nil))
+}
+
+pub fn gen_blocking_timeout_reply(data_cmd_type: DataCmdType) -> RespVec {
+    match data_cmd_type {
+        DataCmdType::BLPOP
+        | DataCmdType::BRPOP
+        | DataCmdType::BZPOPMIN
+        | DataCmdType::BZPOPMAX
+        | DataCmdType::XREAD
+        | DataCmdType::XREADGROUP => Resp::Arr(Array::Nil),
+        _ => Resp::Bulk(BulkStr::Nil),
+    }
+}
+
+// The slot is moved during blocking and the command needs to be routed again.
+pub fn is_redirection_reply(resp: &RespVec) -> bool {
+    match resp {
+        Resp::Error(err) => err.starts_with(b"MOVED ") || err.starts_with(b"ASK "),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::RespPacket;
+
+    fn gen_cmd(elements: Vec<&str>) -> Command {
+        let resp = Resp::Arr(Array::Arr(
+            elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect(),
+        ));
+        Command::new(Box::new(RespPacket::from_resp_vec(resp)))
+    }
+
+    #[test]
+    fn test_blocking_timeout() {
+        let now = Instant::now();
+        let timeout = BlockingTimeout::parse(b"0", 0, now).unwrap();
+        let later = now + Duration::from_secs(3600);
+        assert_eq!(timeout.next_round(later), Some(b"30".to_vec()));
+
+        let timeout = BlockingTimeout::parse(b"45", 0, now).unwrap();
+        assert_eq!(timeout.next_round(now), Some(b"30".to_vec()));
+        let round = now + Duration::from_millis(30_500);
+        assert_eq!(timeout.next_round(round), Some(b"15".to_vec()));
+        assert_eq!(timeout.next_round(now + Duration::from_secs(45)), None);
+
+        let timeout = BlockingTimeout::parse(b"1.5", 0, now).unwrap();
+        let round = now + Duration::from_millis(200);
+        assert_eq!(timeout.next_round(round), Some(b"1.300".to_vec()));
+
+        // The proxy timeout is 10 seconds.
+        let timeout = BlockingTimeout::parse(b"0", 10_000, now).unwrap();
+        assert_eq!(
+            timeout.next_round(now + Duration::from_secs(1)),
+            Some(b"8".to_vec())
+        );
+        assert_eq!(timeout.next_round(now + Duration::from_secs(9)), None);
+        let timeout = BlockingTimeout::parse(b"0.5", 10_000, now).unwrap();
+        assert_eq!(timeout.next_round(now), Some(b"0.500".to_vec()));
+
+        let timeout = BlockingTimeout::parse_millis(b"0", 0, now).unwrap();
+        assert_eq!(timeout.next_round(now), Some(b"30000".to_vec()));
+        let timeout = BlockingTimeout::parse_millis(b"1500", 0, now).unwrap();
+        let round = now + Duration::from_millis(200);
+        assert_eq!(timeout.next_round(round), Some(b"1300".to_vec()));
+        assert_eq!(timeout.next_round(now + Duration::from_millis(1500)), None);
+        assert!(BlockingTimeout::parse_millis(b"1.5", 0, now).is_none());
+
+        assert!(BlockingTimeout::parse(b"-1", 0, now).is_none());
+        assert!(BlockingTimeout::parse(b"abc", 0, now).is_none());
+    }
+
+    #[test]
+    fn test_gen_blocking_round_cmd() {
+        let cmd = gen_cmd(vec!["BLMOVE", "a", "b", "LEFT", "RIGHT", "100"]);
+        let index = get_blocking_timeout_index(&cmd).unwrap();
+        let elements = get_command_elements(&cmd).unwrap();
+        let round_cmd = gen_blocking_round_cmd(&elements, index, b"30".to_vec());
+        assert_eq!(round_cmd[0], b"BLMOVE".to_vec());
+        assert_eq!(round_cmd[4], b"RIGHT".to_vec());
+        assert_eq!(round_cmd[5], b"30".to_vec());
+
+        assert!(is_blocking_timeout_reply(&gen_blocking_timeout_reply(
+            DataCmdType::BLPOP
+        )));
+        assert!(is_blocking_timeout_reply(&Resp::Arr(Array::Nil)));
+        assert!(is_blocking_timeout_reply(&Resp::Bulk(BulkStr::Nil)));
+        assert!(!is_blocking_timeout_reply(&Resp::Bulk(BulkStr::Str(
+            b"v".to_vec()
+        ))));
+        assert!(is_redirection_reply(&Resp::Error(
+            b"MOVED 233 127.0.0.1:6001".to_vec()
+        )));
+    }
+
+    #[test]
+    fn test_stream_blocking_cmd() {
+        let cmd = gen_cmd(vec!["XREAD", "COUNT", "1", "STREAMS", "s", "$"]);
+        assert_eq!(get_blocking_timeout_index(&cmd), None);
+        let cmd = gen_cmd(vec!["XREAD", "STREAMS", "BLOCK", "0"]);
+        assert_eq!(get_blocking_timeout_index(&cmd), None);
+
+        let cmd = gen_cmd(vec![
+            "xread", "COUNT", "1", "block", "100", "STREAMS", "s", "0",
+        ]);
+        assert_eq!(get_blocking_timeout_index(&cmd), Some(4));
+        let elements = get_command_elements(&cmd).unwrap();
+        let round_cmd = gen_blocking_round_cmd(&elements, 4, b"30000".to_vec());
+        assert_eq!(round_cmd[4], b"30000".to_vec());
+        assert_eq!(round_cmd[7], b"0".to_vec());
+        let polling_cmd = gen_non_blocking_stream_cmd(&elements, 4);
+        let expected: Vec<BinSafeStr> = vec!["xread", "COUNT", "1", "STREAMS", "s", "0"]
+            .into_iter()
+            .map(|s| s.as_bytes().to_vec())
+            .collect();
+        assert_eq!(polling_cmd, expected);
+
+        let cmd = gen_cmd(vec![
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "c",
+            "NOACK",
+            "BLOCK",
+            "0",
+            "STREAMS",
+            "s",
+            ">",
+        ]);
+        assert_eq!(get_blocking_timeout_index(&cmd), Some(6));
+        assert!(is_blocking_timeout_reply(&gen_blocking_timeout_reply(
+            DataCmdType::XREADGROUP
+        )));
+    }
+}
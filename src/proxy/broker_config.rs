@@ -0,0 +1,159 @@
+use super::service::ServerProxyConfig;
+use crate::broker::MEM_BROKER_API_VERSION;
+use futures_timer::Delay;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const BROKER_CONFIG_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct ProxyConfigPayload {
+    config: HashMap<String, String>,
+}
+
+// Fetches the proxy config shared by all the proxies from the broker.
+pub async fn fetch_proxy_config(
+    client: &reqwest::Client,
+    broker_address: &str,
+) -> Result<HashMap<String, String>, reqwest::Error> {
+    let url = format!(
+        "http://{}{}/proxies/config",
+        broker_address, MEM_BROKER_API_VERSION
+    );
+    let response = client.get(&url).send().await?.error_for_status()?;
+    let ProxyConfigPayload { config } = response.json().await?;
+    Ok(config)
+}
+
+// Returns the changed fields which could only be applied after restarting,
+// including the removed ones.
+pub fn update_proxy_config(
+    config: &ServerProxyConfig,
+    last_config: &HashMap<String, String>,
+    new_config: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut not_applied = vec![];
+    for (field, value) in new_config.iter() {
+        if last_config.get(field) == Some(value) {
+            continue;
+        }
+        if let Err(err) = config.set_value(field, value) {
+            debug!("failed to set {} to {}: {:?}", field, value, err);
+            not_applied.push(field.clone());
+        }
+    }
+    for field in last_config.keys() {
+        if !new_config.contains_key(field) {
+            not_applied.push(field.clone());
+        }
+    }
+    not_applied.sort();
+    not_applied
+}
+
+// Keeps applying the config changes from the broker.
+pub async fn watch_proxy_config(
+    config: Arc<ServerProxyConfig>,
+    client: reqwest::Client,
+    broker_address: String,
+    mut last_config: HashMap<String, String>,
+    interval: Duration,
+) {
+    loop {
+        Delay::new(interval).await;
+        let new_config = match fetch_proxy_config(&client, &broker_address).await {
+            Ok(new_config) => new_config,
+            Err(err) => {
+                error!("failed to fetch proxy config from broker: {:?}", err);
+                continue;
+            }
+        };
+        if new_config == last_config {
+            continue;
+        }
+        info!("proxy config from broker changed: {:?}", new_config);
+        let not_applied = update_proxy_config(&config, &last_config, &new_config);
+        if !not_applied.is_empty() {
+            warn!(
+                "the changes of {:?} will only be applied after restarting",
+                not_applied
+            );
+        }
+        last_config = new_config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::tls::TlsAcceptorHolder;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicI64, AtomicU64};
+
+    fn gen_config() -> ServerProxyConfig {
+        let n = NonZeroUsize::new(2).unwrap();
+        ServerProxyConfig {
+            address: "127.0.0.1:5299".to_string(),
+            announce_address: "127.0.0.1:5299".to_string(),
+            auto_select_cluster: true,
+            slowlog_len: n,
+            slowlog_log_slower_than: AtomicI64::new(0),
+            slowlog_sample_rate: AtomicU64::new(0),
+            thread_number: n,
+            thread_per_core: false,
+            session_channel_size: 1,
+            backend_channel_size: 1,
+            backend_conn_num: n,
+            backend_batch_min_time: 0,
+            backend_batch_max_time: 0,
+            backend_batch_buf: n,
+            session_batch_min_time: 0,
+            session_batch_max_time: 0,
+            session_batch_buf: n,
+            active_redirection: false,
+            max_redirections: None,
+            backend_redirection: false,
+            sentinel_addresses: vec![],
+            request_coalescing: false,
+            backend_concurrency_limit: 0,
+            backend_inflight_window: 0,
+            backend_session_fairness: true,
+            backend_idle_check_time: 0,
+            tls: TlsAcceptorHolder::default(),
+            command_timeout_fast: AtomicU64::new(0),
+            command_timeout_slow: AtomicU64::new(0),
+            command_timeout_blocking: AtomicU64::new(0),
+            accept_backlog: 1024,
+            max_accepts_per_tick: 0,
+            accept_storm_pause_time: 0,
+            session_panic_isolation: true,
+            reply_order_check: false,
+            read_after_write_window: 0,
+            replica_ack_timeout: 1000,
+            monitor_buffer_size: 1,
+            max_memory: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn test_update_proxy_config() {
+        let config = gen_config();
+
+        let mut last_config = HashMap::new();
+        last_config.insert("thread_number".to_string(), "2".to_string());
+        last_config.insert("backend_conn_num".to_string(), "2".to_string());
+        let mut new_config = last_config.clone();
+        new_config.insert("slowlog_log_slower_than".to_string(), "233".to_string());
+        new_config.insert("thread_number".to_string(), "4".to_string());
+        new_config.remove("backend_conn_num");
+
+        let not_applied = update_proxy_config(&config, &last_config, &new_config);
+        assert_eq!(
+            not_applied,
+            vec!["backend_conn_num".to_string(), "thread_number".to_string()]
+        );
+        assert_eq!(config.get_slowlog_log_slower_than(), 233);
+        assert!(update_proxy_config(&config, &new_config, &new_config).is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+use super::backend::{ConnFactory, DefaultConnFactory};
+use super::executor::{ForwardHandler, SharedForwardHandler};
+use super::manager::{BackendTask, MetaMap, SharedMetaMap};
+use super::memory::MemoryTracker;
+use super::middleware::CmdMiddleware;
+use super::percore::bind_current_core;
+use super::sender::CmdTaskSenderWrapper;
+use super::service::{ServerProxyConfig, ServerProxyService};
+use super::slot_hook::SlotOwnershipHook;
+use super::slowlog::SlowRequestLogger;
+use super::stats::ProxyStats;
+use crate::common::config::ClusterConfig;
+use crate::common::platform::wait_shutdown_signal;
+use crate::common::track::TrackedFutureRegistry;
+use crate::protocol::{RedisClientFactory, RespPacket, SimpleRedisClientFactory};
+use arc_swap::ArcSwap;
+use futures::future;
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use string_error::into_err;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+pub type ProxyService<F, C> = ServerProxyService<SharedForwardHandler<F, C>>;
+
+// Constructs the server proxy for the binaries and tests embedding this crate.
+pub struct ProxyBuilder<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> {
+    config: Arc<ServerProxyConfig>,
+    cluster_config: ClusterConfig,
+    client_factory: Arc<F>,
+    conn_factory: Arc<C>,
+    meta_map: Option<SharedMetaMap<C>>,
+    future_registry: Arc<TrackedFutureRegistry>,
+    middlewares: Vec<Box<dyn CmdMiddleware>>,
+    slot_hooks: Vec<Box<dyn SlotOwnershipHook>>,
+    sender_wrappers: Vec<Box<dyn CmdTaskSenderWrapper<BackendTask>>>,
+}
+
+impl ProxyBuilder<SimpleRedisClientFactory, DefaultConnFactory<RespPacket>> {
+    pub fn new(config: ServerProxyConfig, cluster_config: ClusterConfig) -> Self {
+        let timeout = Duration::new(1, 0);
+        Self {
+            config: Arc::new(config),
+            cluster_config,
+            client_factory: Arc::new(SimpleRedisClientFactory::new(timeout)),
+            conn_factory: Arc::new(DefaultConnFactory::default()),
+            meta_map: None,
+            future_registry: Arc::new(TrackedFutureRegistry::default()),
+            middlewares: vec![],
+            slot_hooks: vec![],
+            sender_wrappers: vec![],
+        }
+    }
+}
+
+impl<F, C> ProxyBuilder<F, C>
+where
+    F: RedisClientFactory,
+    C: ConnFactory<Pkt = RespPacket>,
+{
+    pub fn client_factory<F2: RedisClientFactory>(
+        self,
+        client_factory: Arc<F2>,
+    ) -> ProxyBuilder<F2, C> {
+        ProxyBuilder {
+            config: self.config,
+            cluster_config: self.cluster_config,
+            client_factory,
+            conn_factory: self.conn_factory,
+            meta_map: self.meta_map,
+            future_registry: self.future_registry,
+            middlewares: self.middlewares,
+            slot_hooks: self.slot_hooks,
+            sender_wrappers: self.sender_wrappers,
+        }
+    }
+
+    // The meta map set before will be dropped since it depends on the connection factory.
+    pub fn conn_factory<C2: ConnFactory<Pkt = RespPacket>>(
+        self,
+        conn_factory: Arc<C2>,
+    ) -> ProxyBuilder<F, C2> {
+        ProxyBuilder {
+            config: self.config,
+            cluster_config: self.cluster_config,
+            client_factory: self.client_factory,
+            conn_factory,
+            meta_map: None,
+            future_registry: self.future_registry,
+            middlewares: self.middlewares,
+            slot_hooks: self.slot_hooks,
+            sender_wrappers: self.sender_wrappers,
+        }
+    }
+
+    // Used for sharing the metadata with the embedding users.
+    pub fn meta_map(mut self, meta_map: SharedMetaMap<C>) -> Self {
+        self.meta_map = Some(meta_map);
+        self
+    }
+
+    pub fn future_registry(mut self, future_registry: Arc<TrackedFutureRegistry>) -> Self {
+        self.future_registry = future_registry;
+        self
+    }
+
+    pub fn middleware(mut self, middleware: Box<dyn CmdMiddleware>) -> Self {
+        self.middlewares.push(middleware);
+        self
+    }
+
+    pub fn slot_hook(mut self, hook: Box<dyn SlotOwnershipHook>) -> Self {
+        self.slot_hooks.push(hook);
+        self
+    }
+
+    pub fn sender_wrapper(mut self, wrapper: Box<dyn CmdTaskSenderWrapper<BackendTask>>) -> Self {
+        self.sender_wrappers.push(wrapper);
+        self
+    }
+
+    pub fn get_config(&self) -> Arc<ServerProxyConfig> {
+        self.config.clone()
+    }
+
+    // The returned service could run in the runtime of the users.
+    pub fn build(self) -> ProxyService<F, C> {
+        let slow_request_logger = Arc::new(SlowRequestLogger::new(self.config.clone()));
+        let memory_tracker = Arc::new(MemoryTracker::new(self.config.clone()));
+        let stats = Arc::new(ProxyStats::default());
+        let meta_map = self
+            .meta_map
+            .unwrap_or_else(|| Arc::new(ArcSwap::new(Arc::new(MetaMap::empty()))));
+
+        let mut handler = ForwardHandler::new(
+            self.config.clone(),
+            self.cluster_config,
+            self.client_factory,
+            slow_request_logger.clone(),
+            meta_map,
+            self.conn_factory,
+            self.future_registry.clone(),
+            memory_tracker.clone(),
+            stats.clone(),
+        );
+        for middleware in self.middlewares.into_iter() {
+            handler.register_middleware(middleware);
+        }
+        for hook in self.slot_hooks.into_iter() {
+            handler.register_slot_hook(hook);
+        }
+        for wrapper in self.sender_wrappers.into_iter() {
+            handler.register_sender_wrapper(wrapper);
+        }
+
+        ServerProxyService::new(
+            self.config,
+            SharedForwardHandler::from_handler(handler),
+            slow_request_logger,
+            self.future_registry,
+            memory_tracker,
+            stats,
+        )
+    }
+
+    pub fn build_runtime(&self) -> io::Result<Runtime> {
+        tokio::runtime::Builder::new()
+            .threaded_scheduler()
+            .core_threads(self.config.thread_number.get())
+            .enable_all()
+            .build()
+    }
+
+    // Blocks until the server proxy exits.
+    // SIGTERM and SIGINT, or Ctrl-C on Windows, will shut it down gracefully.
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        if self.config.thread_per_core {
+            return self.run_thread_per_core();
+        }
+        let mut runtime = self.build_runtime()?;
+        let server = self.build();
+        runtime.block_on(async {
+            let s = server.clone();
+            tokio::spawn(async move {
+                match wait_shutdown_signal().await {
+                    Ok(()) => s.shutdown(),
+                    Err(err) => error!("failed to listen to the shutdown signals: {:?}", err),
+                }
+            });
+            server.run().await
+        })
+    }
+
+    // Each core thread runs a single-threaded runtime and accepts its own sessions
+    // so that the sessions will never be moved to the other cores.
+    // The current thread only waits for the shutdown signals and the exits of the cores.
+    fn run_thread_per_core(self) -> Result<(), Box<dyn Error>> {
+        let core_num = self.config.thread_number.get();
+        let server = self.build();
+        let (exit_sender, mut exit_receiver) = mpsc::unbounded_channel();
+
+        let mut core_threads = Vec::with_capacity(core_num);
+        for core_id in 0..core_num {
+            let server = server.clone();
+            let exit_sender = exit_sender.clone();
+            let core_thread = thread::Builder::new()
+                .name(format!("proxy-core-{}", core_id))
+                .spawn(move || {
+                    bind_current_core(core_id);
+                    let res = build_core_runtime()
+                        .map_err(|err| err.to_string())
+                        .and_then(|mut runtime| {
+                            runtime
+                                .block_on(server.run())
+                                .map_err(|err| err.to_string())
+                        });
+                    if let Err(err) = res.as_ref() {
+                        error!("core {} exited: {}", core_id, err);
+                    }
+                    if exit_sender.send(()).is_err() {
+                        debug!("the control thread has exited");
+                    }
+                    res
+                })?;
+            core_threads.push(core_thread);
+        }
+
+        let mut runtime = build_core_runtime()?;
+        runtime.block_on(async {
+            match future::select(
+                Box::pin(wait_shutdown_signal()),
+                Box::pin(exit_receiver.recv()),
+            )
+            .await
+            {
+                future::Either::Left((Err(err), _)) => {
+                    error!("failed to listen to the shutdown signals: {:?}", err)
+                }
+                future::Either::Left((Ok(()), _)) | future::Either::Right(_) => (),
+            }
+        });
+        server.shutdown();
+
+        let mut result = Ok(());
+        for core_thread in core_threads.into_iter() {
+            let res = core_thread
+                .join()
+                .unwrap_or_else(|_| Err("core thread panicked".to_string()));
+            if result.is_ok() {
+                result = res;
+            }
+        }
+        result.map_err(into_err)
+    }
+}
+
+fn build_core_runtime() -> io::Result<Runtime> {
+    tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config_file::ServerProxyConfigFile;
+
+    fn gen_builder(
+        config_file: ServerProxyConfigFile,
+    ) -> ProxyBuilder<SimpleRedisClientFactory, DefaultConnFactory<RespPacket>> {
+        let (config, cluster_config) = config_file.into_config().unwrap();
+        ProxyBuilder::new(config, cluster_config)
+    }
+
+    #[tokio::test]
+    async fn test_build_with_defaults() {
+        let builder = gen_builder(ServerProxyConfigFile::default());
+        let config = builder.get_config();
+        assert_eq!(config.announce_address, config.address);
+
+        let server = builder.build();
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_build_runtime() {
+        let builder = gen_builder(ServerProxyConfigFile::default());
+        let mut runtime = builder.build_runtime().unwrap();
+        assert_eq!(runtime.block_on(async { 1 }), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_with_invalid_address() {
+        let config_file = ServerProxyConfigFile {
+            address: "invalid_address".to_string(),
+            ..Default::default()
+        };
+        let server = gen_builder(config_file).build();
+        assert!(server.run().await.is_err());
+    }
+}
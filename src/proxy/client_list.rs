@@ -0,0 +1,466 @@
+use super::command::Command;
+use super::session::SessionAuth;
+use crate::common::cluster::ClusterName;
+use crate::common::response;
+use crate::protocol::{Array, BulkStr, Resp, RespVec};
+use btoi::btou;
+use dashmap::DashMap;
+use std::str;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use tokio::sync::watch;
+
+const ERR_SYNTAX: &str = "ERR syntax error";
+const ERR_NO_SUCH_CLIENT: &str = "ERR No such client";
+const ERR_INVALID_CLIENT_NAME: &str =
+    "ERR Client names cannot contain spaces, newlines or special characters.";
+
+// The metadata of a client connection shown by `CLIENT LIST`.
+pub struct ClientInfo {
+    id: usize,
+    peer: String,
+    created: Instant,
+    auth: Arc<RwLock<SessionAuth>>,
+    name: Mutex<Option<String>>,
+    // The last command name and when it's received.
+    last_cmd: Mutex<(String, Instant)>,
+    killed: watch::Sender<bool>,
+}
+
+impl ClientInfo {
+    fn get_cluster_name(&self) -> ClusterName {
+        self.auth
+            .read()
+            .expect("ClientInfo::get_cluster_name")
+            .get_cluster_name()
+            .clone()
+    }
+
+    fn kill(&self) {
+        if self.killed.broadcast(true).is_err() {
+            debug!("session {} is already closed", self.id);
+        }
+    }
+
+    fn to_line(&self, now: Instant) -> String {
+        let name = self
+            .name
+            .lock()
+            .expect("ClientInfo::to_line")
+            .clone()
+            .unwrap_or_default();
+        let (cmd, last_time) = self.last_cmd.lock().expect("ClientInfo::to_line").clone();
+        format!(
+            "id={} addr={} name={} age={} idle={} cmd={}",
+            self.id,
+            self.peer,
+            name,
+            now.saturating_duration_since(self.created).as_secs(),
+            now.saturating_duration_since(last_time).as_secs(),
+            cmd,
+        )
+    }
+}
+
+// Only the sessions of the same cluster could be listed and killed by each other.
+#[derive(Default)]
+pub struct ClientRegistry {
+    clients: DashMap<usize, Arc<ClientInfo>>,
+}
+
+impl ClientRegistry {
+    // The session should be closed when the returned receiver gets true.
+    pub fn register(
+        registry: Arc<ClientRegistry>,
+        id: usize,
+        peer: String,
+        auth: Arc<RwLock<SessionAuth>>,
+    ) -> (ClientHandle, watch::Receiver<bool>) {
+        let (killed, killed_receiver) = watch::channel(false);
+        let now = Instant::now();
+        let info = Arc::new(ClientInfo {
+            id,
+            peer,
+            created: now,
+            auth,
+            name: Mutex::new(None),
+            last_cmd: Mutex::new((String::new(), now)),
+            killed,
+        });
+        registry.clients.insert(id, info.clone());
+        (ClientHandle { registry, info }, killed_receiver)
+    }
+
+    fn get_clients(&self, cluster_name: &ClusterName) -> Vec<Arc<ClientInfo>> {
+        let mut clients: Vec<Arc<ClientInfo>> = self
+            .clients
+            .iter()
+            .map(|entry| entry.value().clone())
+            .filter(|info| &info.get_cluster_name() == cluster_name)
+            .collect();
+        clients.sort_by_key(|info| info.id);
+        clients
+    }
+
+    fn kill(&self, cluster_name: &ClusterName, filter: &KillFilter, caller_id: usize) -> usize {
+        let clients = self.get_clients(cluster_name);
+        let killed: Vec<_> = clients
+            .into_iter()
+            .filter(|info| filter.matches(info, caller_id))
+            .collect();
+        for info in killed.iter() {
+            info.kill();
+        }
+        killed.len()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct KillFilter {
+    id: Option<usize>,
+    addr: Option<String>,
+    skip_me: bool,
+}
+
+impl KillFilter {
+    // `CLIENT KILL <addr>` or `CLIENT KILL [ID <id>] [ADDR <addr>] [SKIPME <yes|no>]`.
+    // Returns whether it's the old form which replies OK.
+    fn parse(cmd: &Command) -> Result<(Self, bool), &'static str> {
+        let len = cmd.get_command_len().unwrap_or(0);
+        if len == 3 {
+            let addr = cmd.get_command_element(2).ok_or(ERR_SYNTAX)?;
+            let addr = str::from_utf8(addr).map_err(|_| ERR_SYNTAX)?;
+            let filter = Self {
+                addr: Some(addr.to_string()),
+                ..Default::default()
+            };
+            return Ok((filter, true));
+        }
+        if len < 4 || len % 2 == 1 {
+            return Err(ERR_SYNTAX);
+        }
+
+        let mut filter = Self {
+            skip_me: true,
+            ..Default::default()
+        };
+        for i in (2..len).step_by(2) {
+            let (field, value) = match (cmd.get_command_element(i), cmd.get_command_element(i + 1))
+            {
+                (Some(field), Some(value)) => (field.to_ascii_uppercase(), value),
+                _ => return Err(ERR_SYNTAX),
+            };
+            match field.as_slice() {
+                b"ID" => filter.id = Some(btou::<usize>(value).map_err(|_| ERR_NO_SUCH_CLIENT)?),
+                b"ADDR" => {
+                    let addr = str::from_utf8(value).map_err(|_| ERR_SYNTAX)?;
+                    filter.addr = Some(addr.to_string());
+                }
+                b"SKIPME" => {
+                    filter.skip_me = match value.to_ascii_lowercase().as_slice() {
+                        b"yes" => true,
+                        b"no" => false,
+                        _ => return Err(ERR_SYNTAX),
+                    }
+                }
+                _ => return Err(ERR_SYNTAX),
+            }
+        }
+        Ok((filter, false))
+    }
+
+    fn matches(&self, info: &ClientInfo, caller_id: usize) -> bool {
+        if self.skip_me && info.id == caller_id {
+            return false;
+        }
+        if let Some(id) = self.id {
+            if info.id != id {
+                return false;
+            }
+        }
+        if let Some(addr) = self.addr.as_ref() {
+            if &info.peer != addr {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Unregistered from the registry on drop.
+pub struct ClientHandle {
+    registry: Arc<ClientRegistry>,
+    info: Arc<ClientInfo>,
+}
+
+impl ClientHandle {
+    pub fn record_cmd(&self, cmd: &Command) {
+        let name = match cmd.get_command_element(0) {
+            Some(name) => name,
+            None => return,
+        };
+        let mut last_cmd = self.info.last_cmd.lock().expect("ClientHandle::record_cmd");
+        last_cmd.0.clear();
+        last_cmd
+            .0
+            .push_str(&String::from_utf8_lossy(name).to_ascii_lowercase());
+        last_cmd.1 = Instant::now();
+    }
+
+    pub fn handle_client_cmd(&self, cmd: &Command) -> RespVec {
+        let sub_cmd = match cmd.get_command_element(1) {
+            Some(sub_cmd) => sub_cmd.to_ascii_uppercase(),
+            None => {
+                return Resp::Error(b"ERR wrong number of arguments for 'client' command".to_vec())
+            }
+        };
+        let cluster_name = self.info.get_cluster_name();
+        match sub_cmd.as_slice() {
+            b"ID" => Resp::Integer(self.info.id.to_string().into_bytes()),
+            b"GETNAME" => match self.info.name.lock().expect("handle_client_cmd").clone() {
+                Some(name) => Resp::Bulk(BulkStr::Str(name.into_bytes())),
+                None => Resp::Bulk(BulkStr::Nil),
+            },
+            b"SETNAME" => {
+                let name = match cmd.get_command_element(2) {
+                    Some(name) if cmd.get_command_len() == Some(3) => name,
+                    _ => return Resp::Error(ERR_SYNTAX.to_string().into_bytes()),
+                };
+                if !name.iter().all(|b| (b'!'..=b'~').contains(b)) {
+                    return Resp::Error(ERR_INVALID_CLIENT_NAME.to_string().into_bytes());
+                }
+                // An empty name removes the name.
+                let name = if name.is_empty() {
+                    None
+                } else {
+                    Some(String::from_utf8_lossy(name).to_string())
+                };
+                *self.info.name.lock().expect("handle_client_cmd") = name;
+                Resp::Simple(response::OK_REPLY.to_string().into_bytes())
+            }
+            b"LIST" => {
+                let ids = match Self::parse_list_ids(cmd) {
+                    Ok(ids) => ids,
+                    Err(err) => return Resp::Error(err.to_string().into_bytes()),
+                };
+                let now = Instant::now();
+                let lines: Vec<String> = self
+                    .registry
+                    .get_clients(&cluster_name)
+                    .into_iter()
+                    .filter(|info| ids.is_empty() || ids.contains(&info.id))
+                    .map(|info| info.to_line(now) + "\n")
+                    .collect();
+                Resp::Bulk(BulkStr::Str(lines.concat().into_bytes()))
+            }
+            b"KILL" => {
+                let (filter, old_form) = match KillFilter::parse(cmd) {
+                    Ok(res) => res,
+                    Err(err) => return Resp::Error(err.to_string().into_bytes()),
+                };
+                let killed = self.registry.kill(&cluster_name, &filter, self.info.id);
+                if !old_form {
+                    Resp::Integer(killed.to_string().into_bytes())
+                } else if killed == 0 {
+                    Resp::Error(ERR_NO_SUCH_CLIENT.to_string().into_bytes())
+                } else {
+                    Resp::Simple(response::OK_REPLY.to_string().into_bytes())
+                }
+            }
+            // The invalidation messages need the RESP3 push replies or the redirection
+            // to another connection, and HELLO only accepts RESP2,
+            // so the tracking is always off and RESET has nothing to reset for it.
+            b"TRACKING" => match cmd.get_command_element(2) {
+                Some(mode) if mode.eq_ignore_ascii_case(b"OFF") => {
+                    Resp::Simple(response::OK_REPLY.to_string().into_bytes())
+                }
+                Some(mode) if mode.eq_ignore_ascii_case(b"ON") => {
+                    Resp::Error(response::ERR_CLIENT_TRACKING.to_string().into_bytes())
+                }
+                Some(_) => Resp::Error(ERR_SYNTAX.to_string().into_bytes()),
+                None => Resp::Error(
+                    b"ERR wrong number of arguments for 'client|tracking' command".to_vec(),
+                ),
+            },
+            b"TRACKINGINFO" => Resp::Arr(Array::Arr(vec![
+                Resp::Bulk(BulkStr::Str(b"flags".to_vec())),
+                Resp::Arr(Array::Arr(vec![Resp::Bulk(BulkStr::Str(b"off".to_vec()))])),
+                Resp::Bulk(BulkStr::Str(b"redirect".to_vec())),
+                Resp::Integer(b"-1".to_vec()),
+                Resp::Bulk(BulkStr::Str(b"prefixes".to_vec())),
+                Resp::Arr(Array::Arr(vec![])),
+            ])),
+            b"GETREDIR" => Resp::Integer(b"-1".to_vec()),
+            _ => Resp::Error(response::CMD_NOT_SUPPORTED.to_string().into_bytes()),
+        }
+    }
+
+    // `CLIENT LIST [ID <id> ...]`. Returns empty ids for all the clients.
+    fn parse_list_ids(cmd: &Command) -> Result<Vec<usize>, &'static str> {
+        let len = cmd.get_command_len().unwrap_or(0);
+        if len == 2 {
+            return Ok(vec![]);
+        }
+        match cmd.get_command_element(2) {
+            Some(field) if len > 3 && field.eq_ignore_ascii_case(b"ID") => (),
+            _ => return Err(ERR_SYNTAX),
+        }
+        (3..len)
+            .map(|i| {
+                cmd.get_command_element(i)
+                    .and_then(|id| btou::<usize>(id).ok())
+                    .ok_or(ERR_SYNTAX)
+            })
+            .collect()
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        self.registry.clients.remove(&self.info.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Array, RespPacket};
+    use std::convert::TryFrom;
+
+    fn gen_cmd(elements: Vec<&str>) -> Command {
+        let resp = Resp::Arr(Array::Arr(
+            elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect(),
+        ));
+        Command::new(Box::new(RespPacket::from_resp_vec(resp)))
+    }
+
+    fn gen_auth(cluster_name: &str) -> Arc<RwLock<SessionAuth>> {
+        let cluster_name = ClusterName::try_from(cluster_name).unwrap();
+        Arc::new(RwLock::new(SessionAuth::new(cluster_name)))
+    }
+
+    fn register(
+        registry: &Arc<ClientRegistry>,
+        id: usize,
+        cluster_name: &str,
+    ) -> (ClientHandle, watch::Receiver<bool>) {
+        let peer = format!("127.0.0.1:{}", 5000 + id);
+        ClientRegistry::register(registry.clone(), id, peer, gen_auth(cluster_name))
+    }
+
+    #[test]
+    fn test_client_name() {
+        let registry = Arc::new(ClientRegistry::default());
+        let (client, _) = register(&registry, 1, "mycluster");
+        let id = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "ID"]));
+        assert_eq!(id, Resp::Integer(b"1".to_vec()));
+        let name = client.handle_client_cmd(&gen_cmd(vec!["client", "getname"]));
+        assert_eq!(name, Resp::Bulk(BulkStr::Nil));
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "SETNAME", "worker"]));
+        assert_eq!(reply, Resp::Simple(b"OK".to_vec()));
+        let name = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "GETNAME"]));
+        assert_eq!(name, Resp::Bulk(BulkStr::Str(b"worker".to_vec())));
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "SETNAME", "a b"]));
+        assert!(matches!(reply, Resp::Error(_)));
+
+        let cmd = gen_cmd(vec!["GET", "key"]);
+        client.record_cmd(&cmd);
+        let line = client.info.to_line(Instant::now());
+        assert!(line.starts_with("id=1 addr=127.0.0.1:5001 name=worker age=0 idle=0"));
+        assert!(line.ends_with("cmd=get"));
+
+        drop(client);
+        assert!(registry.clients.is_empty());
+    }
+
+    #[test]
+    fn test_client_tracking() {
+        let registry = Arc::new(ClientRegistry::default());
+        let (client, _) = register(&registry, 1, "mycluster");
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "TRACKING", "on"]));
+        assert_eq!(
+            reply,
+            Resp::Error(response::ERR_CLIENT_TRACKING.as_bytes().to_vec())
+        );
+        let reply =
+            client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "TRACKING", "ON", "REDIRECT", "2"]));
+        assert_eq!(
+            reply,
+            Resp::Error(response::ERR_CLIENT_TRACKING.as_bytes().to_vec())
+        );
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "TRACKING", "OFF"]));
+        assert_eq!(reply, Resp::Simple(b"OK".to_vec()));
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "TRACKING", "maybe"]));
+        assert!(matches!(reply, Resp::Error(_)));
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "TRACKING"]));
+        assert!(matches!(reply, Resp::Error(_)));
+
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "GETREDIR"]));
+        assert_eq!(reply, Resp::Integer(b"-1".to_vec()));
+        let reply = client.handle_client_cmd(&gen_cmd(vec!["CLIENT", "TRACKINGINFO"]));
+        let flags = match reply {
+            Resp::Arr(Array::Arr(fields)) => fields[1].clone(),
+            other => panic!("unexpected reply {:?}", other),
+        };
+        let off = Resp::Arr(Array::Arr(vec![Resp::Bulk(BulkStr::Str(b"off".to_vec()))]));
+        assert_eq!(flags, off);
+    }
+
+    #[test]
+    fn test_client_list_and_kill() {
+        let registry = Arc::new(ClientRegistry::default());
+        let (client1, killed1) = register(&registry, 1, "mycluster");
+        let (_client2, killed2) = register(&registry, 2, "mycluster");
+        let (_client3, killed3) = register(&registry, 3, "othercluster");
+
+        let list = client1.handle_client_cmd(&gen_cmd(vec!["CLIENT", "LIST"]));
+        let list = match list {
+            Resp::Bulk(BulkStr::Str(s)) => String::from_utf8(s).unwrap(),
+            other => panic!("unexpected reply {:?}", other),
+        };
+        let ids: Vec<&str> = list
+            .lines()
+            .map(|line| line.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["id=1", "id=2"]);
+        let list = client1.handle_client_cmd(&gen_cmd(vec!["CLIENT", "LIST", "ID", "2"]));
+        assert!(matches!(list, Resp::Bulk(BulkStr::Str(s)) if s.starts_with(b"id=2 ")));
+
+        // The sessions of the other clusters and itself are skipped.
+        let reply = client1.handle_client_cmd(&gen_cmd(vec!["CLIENT", "KILL", "ID", "3"]));
+        assert_eq!(reply, Resp::Integer(b"0".to_vec()));
+        let reply = client1.handle_client_cmd(&gen_cmd(vec!["CLIENT", "KILL", "ID", "1"]));
+        assert_eq!(reply, Resp::Integer(b"0".to_vec()));
+        let cmd = gen_cmd(vec!["CLIENT", "KILL", "ADDR", "127.0.0.1:5002"]);
+        assert_eq!(
+            client1.handle_client_cmd(&cmd),
+            Resp::Integer(b"1".to_vec())
+        );
+        assert!(*killed2.borrow());
+        assert!(!*killed3.borrow());
+
+        let reply = client1.handle_client_cmd(&gen_cmd(vec!["CLIENT", "KILL", "127.0.0.1:5001"]));
+        assert_eq!(reply, Resp::Simple(b"OK".to_vec()));
+        assert!(*killed1.borrow());
+        let reply = client1.handle_client_cmd(&gen_cmd(vec!["CLIENT", "KILL", "127.0.0.1:9"]));
+        assert!(matches!(reply, Resp::Error(_)));
+    }
+
+    #[test]
+    fn test_parse_kill_filter() {
+        let cmd = gen_cmd(vec!["CLIENT", "KILL", "ID", "2", "SKIPME", "no"]);
+        let expected = KillFilter {
+            id: Some(2),
+            addr: None,
+            skip_me: false,
+        };
+        assert_eq!(KillFilter::parse(&cmd), Ok((expected, false)));
+        let cmd = gen_cmd(vec!["CLIENT", "KILL", "ID", "2", "SKIPME"]);
+        assert!(KillFilter::parse(&cmd).is_err());
+        let cmd = gen_cmd(vec!["CLIENT", "KILL", "LADDR", "127.0.0.1:5299"]);
+        assert!(KillFilter::parse(&cmd).is_err());
+    }
+}
@@ -1,18 +1,21 @@
 use super::backend::{BackendError, CmdTask, IntoTask};
+use super::ring::HashRing;
 use super::sender::{CmdTaskSender, CmdTaskSenderFactory};
 use super::slot::SlotMap;
 use crate::common::cluster::{ClusterName, RangeList, SlotRange, SlotRangeTag};
-use crate::common::config::ClusterConfig;
+use crate::common::config::{ClusterConfig, ReadPreference, RoutingMode};
 use crate::common::proto::ProxyClusterMeta;
-use crate::common::response::ERR_CLUSTER_NOT_FOUND;
+use crate::common::response::{ERR_CLUSTER_NOT_FOUND, ERR_MISSING_KEY, ERR_SLOT_NOT_COVERED};
 use crate::common::utils::gen_moved;
 use crate::migration::task::MigrationState;
 use crate::protocol::{Array, BulkStr, Resp, RespVec};
+use crate::replication::replicator::MasterReplicator;
 use crc64::crc64;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::iter::Iterator;
+use std::sync::Arc;
 
 pub const DEFAULT_CLUSTER: &str = "admin";
 
@@ -37,6 +40,24 @@ impl Error for ClusterMetaError {
 pub trait ClusterTag {
     fn get_cluster_name(&self) -> &ClusterName;
     fn set_cluster_name(&mut self, cluster_name: ClusterName);
+
+    // Only the read commands could be sent to the replicas.
+    fn is_read_cmd(&self) -> bool {
+        false
+    }
+}
+
+// master node address => replicator
+pub type MasterReplicatorMap = HashMap<String, Arc<dyn MasterReplicator>>;
+// master node address => replica node addresses
+type ReplicaAddressMap = HashMap<String, Vec<String>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlotOwner {
+    // The backend node address.
+    Local(String),
+    // The peer proxy address.
+    Remote(String),
 }
 
 pub struct ClusterBackendMap<S: CmdTaskSender, P: CmdTaskSender>
@@ -47,6 +68,8 @@ where
 {
     local_clusters: HashMap<ClusterName, LocalCluster<S>>,
     remote_clusters: HashMap<ClusterName, RemoteCluster<P>>,
+    // Only for the clusters in the consistent hashing mode.
+    rings: HashMap<ClusterName, ClusterRing>,
 }
 
 impl<S: CmdTaskSender, P: CmdTaskSender> Default for ClusterBackendMap<S, P>
@@ -59,6 +82,7 @@ where
         Self {
             local_clusters: HashMap::new(),
             remote_clusters: HashMap::new(),
+            rings: HashMap::new(),
         }
     }
 }
@@ -78,8 +102,22 @@ where
         peer_sender_factory: &PF,
         active_redirection: bool,
         cluster_config: &ClusterConfig,
+        master_replicators: &MasterReplicatorMap,
+        service_address: &str,
     ) -> Self {
         let epoch = cluster_meta.get_epoch();
+        let replica_addresses: ReplicaAddressMap = master_replicators
+            .iter()
+            .map(|(master, replicator)| {
+                let replicas = replicator
+                    .get_meta()
+                    .replicas
+                    .iter()
+                    .map(|replica| replica.node_address.clone())
+                    .collect();
+                (master.clone(), replicas)
+            })
+            .collect();
 
         let mut local_clusters = HashMap::new();
         for (cluster_name, slot_ranges) in cluster_meta.get_local().get_map().iter() {
@@ -87,12 +125,20 @@ where
                 .get_configs()
                 .get(cluster_name)
                 .unwrap_or_else(|| cluster_config.clone());
+            let read_backends = gen_read_backends(
+                config.read_preference,
+                slot_ranges,
+                &replica_addresses,
+                service_address,
+            );
             let local_cluster = LocalCluster::from_slot_map(
                 sender_factory,
                 cluster_name.clone(),
                 epoch,
                 slot_ranges.clone(),
                 config,
+                read_backends,
+                master_replicators,
             );
             local_clusters.insert(cluster_name.clone(), local_cluster);
         }
@@ -108,9 +154,29 @@ where
             );
             remote_clusters.insert(cluster_name.clone(), remote_cluster);
         }
+
+        let local_map = cluster_meta.get_local().get_map();
+        let peer_map = cluster_meta.get_peer().get_map();
+        let mut rings = HashMap::new();
+        for cluster_name in local_map.keys().chain(peer_map.keys()) {
+            let config = cluster_meta
+                .get_configs()
+                .get(cluster_name)
+                .unwrap_or_else(|| cluster_config.clone());
+            if config.routing_mode != RoutingMode::ConsistentHash {
+                continue;
+            }
+            let ring = ClusterRing::new(
+                service_address,
+                local_map.get(cluster_name),
+                peer_map.get(cluster_name),
+            );
+            rings.insert(cluster_name.clone(), ring);
+        }
         Self {
             local_clusters,
             remote_clusters,
+            rings,
         }
     }
 
@@ -146,25 +212,97 @@ where
         ]))
     }
 
+    // The `clusters` section of `INFO`.
+    pub fn gen_clusters_info(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("local_clusters:{}", self.local_clusters.len()),
+            format!("remote_clusters:{}", self.remote_clusters.len()),
+        ];
+        let mut local: Vec<_> = self.local_clusters.values().collect();
+        local.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+        for (i, cluster) in local.into_iter().enumerate() {
+            lines.push(format!(
+                "local_cluster_{}:name={},epoch={},backends={},slots={}",
+                i,
+                cluster.name,
+                cluster.epoch,
+                cluster.slot_ranges.len(),
+                count_slots(cluster.slot_ranges.values().flatten()),
+            ));
+        }
+        let mut remote: Vec<_> = self.remote_clusters.values().collect();
+        remote.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+        for (i, cluster) in remote.into_iter().enumerate() {
+            lines.push(format!(
+                "remote_cluster_{}:name={},epoch={},proxies={},slots={}",
+                i,
+                cluster.name,
+                cluster.epoch,
+                cluster.slot_ranges.len(),
+                count_slots(cluster.slot_ranges.values().flatten()),
+            ));
+        }
+        lines
+    }
+
+    // The `backends` section of `INFO`.
+    pub fn gen_backends_info(&self) -> Vec<String> {
+        let mut backends: Vec<(&ClusterName, &String, &Vec<SlotRange>)> = self
+            .local_clusters
+            .values()
+            .flat_map(|cluster| {
+                cluster
+                    .slot_ranges
+                    .iter()
+                    .map(move |(address, slot_ranges)| (&cluster.name, address, slot_ranges))
+            })
+            .collect();
+        backends.sort_by(|a, b| (a.0.as_str(), a.1).cmp(&(b.0.as_str(), b.1)));
+        let mut lines = vec![format!("backends:{}", backends.len())];
+        for (i, (cluster_name, address, slot_ranges)) in backends.into_iter().enumerate() {
+            lines.push(format!(
+                "backend_{}:cluster={},address={},slots={},migrating_slots={},importing_slots={}",
+                i,
+                cluster_name,
+                address,
+                count_slots(slot_ranges.iter()),
+                count_slots(slot_ranges.iter().filter(|s| s.tag.is_migrating())),
+                count_slots(slot_ranges.iter().filter(|s| s.tag.is_importing())),
+            ));
+        }
+        lines
+    }
+
     pub fn send(
         &self,
         cmd_task: <S as CmdTaskSender>::Task,
     ) -> Result<(), ClusterSendError<<S as CmdTaskSender>::Task>> {
+        // Falls back to the slot table when the ring is empty.
+        let slot = match self.rings.get(cmd_task.get_cluster_name()) {
+            Some(ring) => cmd_task
+                .get_key()
+                .and_then(|key| ring.get_slot(key))
+                .or_else(|| cmd_task.get_slot()),
+            None => cmd_task.get_slot(),
+        };
         let (cmd_task, cluster_exists) = match self.local_clusters.get(cmd_task.get_cluster_name())
         {
-            Some(local_cluster) => match local_cluster.send(cmd_task) {
-                Err(ClusterSendError::SlotNotFound(cmd_task)) => (cmd_task, true),
-                others => return others,
-            },
+            Some(local_cluster) => {
+                match local_cluster.send(cmd_task.is_read_cmd(), slot, cmd_task) {
+                    Err(ClusterSendError::SlotNotFound(cmd_task)) => (cmd_task, true),
+                    others => return others,
+                }
+            }
             None => (cmd_task, false),
         };
 
         match self.remote_clusters.get(cmd_task.get_cluster_name()) {
-            Some(remote_cluster) => remote_cluster.send_remote(cmd_task),
+            Some(remote_cluster) => remote_cluster.send_remote(slot, cmd_task),
             None => {
                 if cluster_exists {
                     let resp = Resp::Error(
-                        format!("slot not found: {}", cmd_task.get_cluster_name()).into_bytes(),
+                        format!("{}: {}", ERR_SLOT_NOT_COVERED, cmd_task.get_cluster_name())
+                            .into_bytes(),
                     );
                     cmd_task.set_resp_result(Ok(resp));
                     Err(ClusterSendError::SlotNotCovered)
@@ -193,7 +331,8 @@ where
             }
             None => {
                 let resp = Resp::Error(
-                    format!("slot not found: {}", cmd_task.get_cluster_name()).into_bytes(),
+                    format!("{}: {}", ERR_SLOT_NOT_COVERED, cmd_task.get_cluster_name())
+                        .into_bytes(),
                 );
                 cmd_task.set_resp_result(Ok(resp));
                 Err(ClusterSendError::SlotNotCovered)
@@ -248,6 +387,28 @@ where
         Ok(Resp::Arr(Array::Arr(local)))
     }
 
+    pub fn gen_cluster_shards(
+        &self,
+        cluster_name: ClusterName,
+        service_address: String,
+        migration_states: &HashMap<RangeList, MigrationState>,
+    ) -> Result<RespVec, String> {
+        let mut local =
+            self.local_clusters
+                .get(&cluster_name)
+                .map_or(Ok(vec![]), |local_cluster| {
+                    local_cluster.gen_local_cluster_shards(service_address, migration_states)
+                })?;
+        let mut remote = self
+            .remote_clusters
+            .get(&cluster_name)
+            .map_or(Ok(vec![]), |remote_cluster| {
+                remote_cluster.gen_remote_cluster_shards(migration_states)
+            })?;
+        local.append(&mut remote);
+        Ok(Resp::Arr(Array::Arr(local)))
+    }
+
     pub fn auto_select_cluster(&self) -> Option<ClusterName> {
         {
             let local = &self.local_clusters;
@@ -272,14 +433,185 @@ where
             .map(|local_cluster| &local_cluster.config)
     }
 
+    pub fn get_local_backend(&self, cluster_name: &ClusterName, slot: usize) -> Option<&str> {
+        self.local_clusters
+            .get(cluster_name)
+            .and_then(|local_cluster| local_cluster.local_backend.slot_map.get(slot))
+    }
+
+    pub fn get_slot_owner(&self, cluster_name: &ClusterName, slot: usize) -> Option<SlotOwner> {
+        if let Some(node) = self.get_local_backend(cluster_name, slot) {
+            return Some(SlotOwner::Local(node.to_string()));
+        }
+        self.remote_clusters
+            .get(cluster_name)
+            .and_then(|remote_cluster| remote_cluster.slot_map.get(slot))
+            .map(|address| SlotOwner::Remote(address.to_string()))
+    }
+
+    pub fn get_local_nodes(&self, cluster_name: &ClusterName) -> Option<Vec<String>> {
+        self.local_clusters.get(cluster_name).map(|local_cluster| {
+            local_cluster
+                .local_backend
+                .slot_map
+                .get_addresses()
+                .to_vec()
+        })
+    }
+
     pub fn cluster_exists(&self, cluster_name: &ClusterName) -> bool {
         self.local_clusters.contains_key(cluster_name)
             || self.remote_clusters.contains_key(cluster_name)
     }
+
+    // Picks the smallest cluster name if the user names are not unique.
+    pub fn find_user_cluster(&self, user: &str) -> Option<ClusterName> {
+        self.local_clusters
+            .iter()
+            .filter(|(_, local_cluster)| local_cluster.config.auth.get_user(user).is_some())
+            .map(|(name, _)| name)
+            .min_by(|a, b| a.as_str().cmp(b.as_str()))
+            .cloned()
+    }
+
+    // The local clusters which are neither in the new map nor renamed to another one.
+    pub fn get_removed_clusters(&self, new_map: &Self) -> Vec<ClusterName> {
+        self.local_clusters
+            .keys()
+            .filter(|cluster_name| {
+                !new_map.local_clusters.contains_key(cluster_name)
+                    && new_map.get_renamed_cluster(cluster_name).is_none()
+            })
+            .cloned()
+            .collect()
+    }
+
+    // The old name of a renamed cluster is still accepted until the renaming is finished.
+    pub fn get_renamed_cluster(&self, cluster_name: &ClusterName) -> Option<&ClusterName> {
+        if cluster_name.as_str().is_empty() {
+            return None;
+        }
+        self.local_clusters
+            .iter()
+            .find(|(_, local_cluster)| &local_cluster.config.renamed_from == cluster_name)
+            .map(|(name, _)| name)
+    }
+
+    // Returns one of the proxies owning the cluster after all its slots are moved out.
+    pub fn get_moved_address(&self, cluster_name: &ClusterName) -> Option<&str> {
+        if self.local_clusters.contains_key(cluster_name) {
+            return None;
+        }
+        self.remote_clusters
+            .get(cluster_name)?
+            .slot_ranges
+            .keys()
+            .min()
+            .map(|address| address.as_str())
+    }
+
+    // Falls back to the original cluster when the canary cluster is not found in this proxy.
+    pub fn get_canary_cluster(
+        &self,
+        cluster_name: &ClusterName,
+        slot: usize,
+    ) -> Option<&ClusterName> {
+        let canary_cluster = self
+            .get_config(cluster_name)?
+            .canary
+            .get_canary_cluster(slot)?;
+        if canary_cluster == cluster_name || !self.cluster_exists(canary_cluster) {
+            return None;
+        }
+        Some(canary_cluster)
+    }
+}
+
+// Routes the keys to the proxies first and then to the masters of the selected proxy
+// since the proxies only know the slots of the peer proxies but not their masters.
+// Each owner is represented by one of its slots so that the rest of the routing
+// is the same as the slot mode.
+struct ClusterRing {
+    local_proxy: Option<usize>,
+    proxy_ring: HashRing,
+    proxy_slots: Vec<usize>,
+    node_ring: HashRing,
+    node_slots: Vec<usize>,
+}
+
+// Returns the owners sorted by the address with their slot numbers and first slots.
+// The importing slots are not owned yet.
+fn get_ring_owners(node_map: &HashMap<String, Vec<SlotRange>>) -> Vec<(&str, usize, usize)> {
+    let mut owners: Vec<(&str, usize, usize)> = node_map
+        .iter()
+        .filter_map(|(address, slot_ranges)| {
+            let ranges: Vec<_> = slot_ranges
+                .iter()
+                .filter(|slot_range| !slot_range.tag.is_importing())
+                .flat_map(|slot_range| slot_range.get_range_list().get_ranges().iter())
+                .collect();
+            let first_slot = ranges.iter().map(|range| range.start()).min()?;
+            let slots = ranges
+                .iter()
+                .map(|range| range.end() + 1 - range.start())
+                .sum();
+            Some((address.as_str(), slots, first_slot))
+        })
+        .collect();
+    owners.sort();
+    owners
 }
 
+impl ClusterRing {
+    fn new(
+        service_address: &str,
+        local: Option<&HashMap<String, Vec<SlotRange>>>,
+        peer: Option<&HashMap<String, Vec<SlotRange>>>,
+    ) -> Self {
+        let empty = HashMap::new();
+        let nodes = get_ring_owners(local.unwrap_or(&empty));
+        let mut proxies = get_ring_owners(peer.unwrap_or(&empty));
+        let local_slots: usize = nodes.iter().map(|(_, slots, _)| slots).sum();
+        let local_first_slot = nodes.iter().map(|(_, _, first_slot)| *first_slot).min();
+        if let Some(first_slot) = local_first_slot {
+            proxies.push((service_address, local_slots, first_slot));
+            proxies.sort();
+        }
+        let local_proxy = proxies
+            .iter()
+            .position(|(address, _, _)| *address == service_address);
+
+        let to_ring = |owners: &[(&str, usize, usize)]| {
+            let weights: Vec<(&str, usize)> = owners
+                .iter()
+                .map(|(address, slots, _)| (*address, *slots))
+                .collect();
+            HashRing::new(&weights)
+        };
+        Self {
+            local_proxy,
+            proxy_ring: to_ring(&proxies),
+            proxy_slots: proxies.iter().map(|(_, _, slot)| *slot).collect(),
+            node_ring: to_ring(&nodes),
+            node_slots: nodes.iter().map(|(_, _, slot)| *slot).collect(),
+        }
+    }
+
+    fn get_slot(&self, key: &[u8]) -> Option<usize> {
+        let proxy = self.proxy_ring.get(key)?;
+        if Some(proxy) == self.local_proxy {
+            let node = self.node_ring.get(key)?;
+            self.node_slots.get(node).cloned()
+        } else {
+            self.proxy_slots.get(proxy).cloned()
+        }
+    }
+}
+
+// The senders are precomputed in the order of the backend index of `slot_map`
+// so that routing a command only needs to index the arrays.
 struct SenderMap<S: CmdTaskSender> {
-    nodes: HashMap<String, S>,
+    senders: Vec<S>,
     slot_map: SlotMap,
 }
 
@@ -288,14 +620,72 @@ impl<S: CmdTaskSender> SenderMap<S> {
         sender_factory: &F,
         slot_map: &HashMap<String, Vec<SlotRange>>,
     ) -> Self {
-        let mut nodes = HashMap::new();
-        for addr in slot_map.keys() {
-            nodes.insert(addr.to_string(), sender_factory.create(addr.to_string()));
+        let slot_map = SlotMap::from_ranges(slot_map.clone());
+        let senders = slot_map
+            .get_addresses()
+            .iter()
+            .map(|addr| sender_factory.create(addr.to_string()))
+            .collect();
+        Self { senders, slot_map }
+    }
+
+    fn get_by_address(&self, address: &str) -> Option<&S> {
+        let index = self.slot_map.get_index_by_address(address)?;
+        self.senders.get(index)
+    }
+}
+
+// Returns the master node address => the node address serving its read commands.
+fn gen_read_backends(
+    read_preference: ReadPreference,
+    slot_map: &HashMap<String, Vec<SlotRange>>,
+    replica_addresses: &ReplicaAddressMap,
+    service_address: &str,
+) -> HashMap<String, String> {
+    let local_host = get_host(service_address);
+    let mut read_backends = HashMap::new();
+    for master in slot_map.keys() {
+        let replicas = match replica_addresses.get(master) {
+            Some(replicas) => replicas,
+            None => continue,
+        };
+        let replica = match read_preference {
+            ReadPreference::Primary => None,
+            ReadPreference::ReplicaPreferred => replicas.first(),
+            ReadPreference::NearestZone if get_host(master) == local_host => None,
+            ReadPreference::NearestZone => replicas
+                .iter()
+                .find(|replica| get_host(replica) == local_host),
+        };
+        if let Some(replica) = replica {
+            read_backends.insert(master.clone(), replica.clone());
         }
-        Self {
-            nodes,
-            slot_map: SlotMap::from_ranges(slot_map.clone()),
+    }
+    read_backends
+}
+
+fn get_host(address: &str) -> &str {
+    address.rsplitn(2, ':').last().unwrap_or(address)
+}
+
+struct ReadBackend<S: CmdTaskSender> {
+    sender: S,
+    // Used to skip the stale replicas.
+    master_replicator: Option<Arc<dyn MasterReplicator>>,
+    // In seconds. Zero means no limitation.
+    max_replica_lag: u64,
+}
+
+impl<S: CmdTaskSender> ReadBackend<S> {
+    fn is_fresh(&self) -> bool {
+        if self.max_replica_lag == 0 {
+            return true;
         }
+        let lag = self
+            .master_replicator
+            .as_ref()
+            .and_then(|replicator| replicator.get_replication_lag());
+        matches!(lag, Some(lag) if lag <= self.max_replica_lag)
     }
 }
 
@@ -305,6 +695,8 @@ pub struct LocalCluster<S: CmdTaskSender> {
     local_backend: SenderMap<S>,
     slot_ranges: HashMap<String, Vec<SlotRange>>,
     config: ClusterConfig,
+    // The replicas serving the read commands indexed by the backend index of `local_backend`.
+    read_backends: Vec<Option<ReadBackend<S>>>,
 }
 
 impl<S: CmdTaskSender> LocalCluster<S> {
@@ -314,14 +706,30 @@ impl<S: CmdTaskSender> LocalCluster<S> {
         epoch: u64,
         slot_map: HashMap<String, Vec<SlotRange>>,
         config: ClusterConfig,
+        read_backends: HashMap<String, String>,
+        master_replicators: &MasterReplicatorMap,
     ) -> Self {
         let local_backend = SenderMap::from_slot_map(sender_factory, &slot_map);
+        let read_backends = local_backend
+            .slot_map
+            .get_addresses()
+            .iter()
+            .map(|master| {
+                let replica = read_backends.get(master)?;
+                Some(ReadBackend {
+                    sender: sender_factory.create(replica.clone()),
+                    master_replicator: master_replicators.get(master).cloned(),
+                    max_replica_lag: config.max_replica_lag,
+                })
+            })
+            .collect();
         LocalCluster {
             name,
             epoch,
             local_backend,
             slot_ranges: slot_map,
             config,
+            read_backends,
         }
     }
 
@@ -341,26 +749,34 @@ impl<S: CmdTaskSender> LocalCluster<S> {
 
     pub fn send(
         &self,
+        is_read_cmd: bool,
+        slot: Option<usize>,
         cmd_task: <S as CmdTaskSender>::Task,
     ) -> Result<(), ClusterSendError<<S as CmdTaskSender>::Task>> {
-        let slot = match cmd_task.get_slot() {
+        let slot = match slot {
             Some(slot) => slot,
             None => {
-                let resp = Resp::Error("missing key".to_string().into_bytes());
+                let resp = Resp::Error(ERR_MISSING_KEY.to_string().into_bytes());
                 cmd_task.set_resp_result(Ok(resp));
                 return Err(ClusterSendError::MissingKey);
             }
         };
 
-        match self.local_backend.slot_map.get(slot) {
-            Some(addr) => match self.local_backend.nodes.get(addr) {
-                Some(sender) => sender.send(cmd_task).map_err(ClusterSendError::Backend),
-                None => {
-                    warn!("failed to get node");
-                    Err(ClusterSendError::SlotNotFound(cmd_task))
-                }
-            },
-            None => Err(ClusterSendError::SlotNotFound(cmd_task)),
+        let index = match self.local_backend.slot_map.get_index(slot) {
+            Some(index) => index,
+            None => return Err(ClusterSendError::SlotNotFound(cmd_task)),
+        };
+        // Fall back to the master if the replica lags too much.
+        let sender = match self.read_backends.get(index) {
+            Some(Some(backend)) if is_read_cmd && backend.is_fresh() => Some(&backend.sender),
+            _ => self.local_backend.senders.get(index),
+        };
+        match sender {
+            Some(sender) => sender.send(cmd_task).map_err(ClusterSendError::Backend),
+            None => {
+                warn!("failed to get node");
+                Err(ClusterSendError::SlotNotFound(cmd_task))
+            }
         }
     }
 
@@ -395,6 +811,22 @@ impl<S: CmdTaskSender> LocalCluster<S> {
         slot_ranges.insert(service_address, slots);
         gen_cluster_slots_helper(&slot_ranges, migration_states)
     }
+
+    pub fn gen_local_cluster_shards(
+        &self,
+        service_address: String,
+        migration_states: &HashMap<RangeList, MigrationState>,
+    ) -> Result<Vec<RespVec>, String> {
+        let slots: Vec<SlotRange> = self
+            .slot_ranges
+            .values()
+            .flatten()
+            .cloned()
+            .collect::<Vec<SlotRange>>();
+        let mut slot_ranges = HashMap::new();
+        slot_ranges.insert(service_address, slots);
+        gen_cluster_shards_helper(&self.name, &slot_ranges, migration_states)
+    }
 }
 
 pub struct RemoteCluster<P: CmdTaskSender> {
@@ -437,11 +869,15 @@ impl<P: CmdTaskSender> RemoteCluster<P> {
         Resp::Arr(Array::Arr(arr))
     }
 
-    pub fn send_remote<T: CmdTask>(&self, cmd_task: T) -> Result<(), ClusterSendError<T>> {
-        let slot = match cmd_task.get_slot() {
+    pub fn send_remote<T: CmdTask>(
+        &self,
+        slot: Option<usize>,
+        cmd_task: T,
+    ) -> Result<(), ClusterSendError<T>> {
+        let slot = match slot {
             Some(slot) => slot,
             None => {
-                let resp = Resp::Error("missing key".to_string().into_bytes());
+                let resp = Resp::Error(ERR_MISSING_KEY.to_string().into_bytes());
                 cmd_task.set_resp_result(Ok(resp));
                 return Err(ClusterSendError::MissingKey);
             }
@@ -462,7 +898,7 @@ impl<P: CmdTaskSender> RemoteCluster<P> {
                 }
             }
             None => {
-                let resp = Resp::Error(format!("slot not covered {}", slot).into_bytes());
+                let resp = Resp::Error(format!("{} {}", ERR_SLOT_NOT_COVERED, slot).into_bytes());
                 cmd_task.set_resp_result(Ok(resp));
                 Err(ClusterSendError::SlotNotCovered)
             }
@@ -476,7 +912,7 @@ impl<P: CmdTaskSender> RemoteCluster<P> {
         address: &str,
     ) -> Result<(), ClusterSendError<<P as CmdTaskSender>::Task>> {
         if let Some(remote_backend) = self.remote_backend.as_ref() {
-            match remote_backend.nodes.get(address) {
+            match remote_backend.get_by_address(address) {
                 Some(sender) => sender.send(cmd_task).map_err(ClusterSendError::Backend),
                 None => {
                     warn!("failed to get node");
@@ -509,6 +945,19 @@ impl<P: CmdTaskSender> RemoteCluster<P> {
     ) -> Result<Vec<RespVec>, String> {
         gen_cluster_slots_helper(&self.slot_ranges, migration_states)
     }
+
+    pub fn gen_remote_cluster_shards(
+        &self,
+        migration_states: &HashMap<RangeList, MigrationState>,
+    ) -> Result<Vec<RespVec>, String> {
+        gen_cluster_shards_helper(&self.name, &self.slot_ranges, migration_states)
+    }
+}
+
+fn count_slots<'a, I: Iterator<Item = &'a SlotRange>>(slot_ranges: I) -> usize {
+    slot_ranges
+        .map(|slot_range| slot_range.range_list.get_slots_num())
+        .sum()
 }
 
 fn format_slot_ranges(slot_ranges: &HashMap<String, Vec<SlotRange>>) -> Vec<RespVec> {
@@ -626,6 +1075,15 @@ impl<T: CmdTask> ClusterSendError<T> {
     }
 }
 
+// The fake node id is made of the cluster name and the hash of the proxy address.
+fn gen_node_id(name: &ClusterName, addr: &str) -> String {
+    let mut name_seg = format!("{:_<20}", name.to_string());
+    name_seg.truncate(20);
+    let mut addr_hash_seg = format!("{:_<20x}", crc64(0, addr.as_bytes()));
+    addr_hash_seg.truncate(20);
+    format!("{}{}", name_seg, addr_hash_seg)
+}
+
 fn gen_cluster_nodes_helper(
     name: &ClusterName,
     epoch: u64,
@@ -634,12 +1092,8 @@ fn gen_cluster_nodes_helper(
     local: bool,
 ) -> String {
     let mut cluster_nodes = String::from("");
-    let mut name_seg = format!("{:_<20}", name.to_string());
-    name_seg.truncate(20);
     for (addr, ranges) in slot_ranges {
-        let mut addr_hash_seg = format!("{:_<20x}", crc64(0, addr.as_bytes()));
-        addr_hash_seg.truncate(20);
-        let id = format!("{}{}", name_seg, addr_hash_seg);
+        let id = gen_node_id(name, addr);
 
         let mut slot_range_str = String::new();
         let slot_range = ranges
@@ -741,10 +1195,68 @@ fn gen_cluster_slots_helper(
     Ok(slot_range_element)
 }
 
+// Each server proxy is a shard with only the master node
+// since the replicas are hidden behind the server proxies.
+fn gen_cluster_shards_helper(
+    name: &ClusterName,
+    slot_ranges: &HashMap<String, Vec<SlotRange>>,
+    migration_states: &HashMap<RangeList, MigrationState>,
+) -> Result<Vec<RespVec>, String> {
+    let bulk = |s: &str| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec()));
+    let mut shards = Vec::new();
+    for (addr, ranges) in slot_ranges {
+        let mut segs = addr.split(':');
+        let host = segs
+            .next()
+            .ok_or_else(|| format!("invalid address {}", addr))?;
+        let port = segs
+            .next()
+            .ok_or_else(|| format!("invalid address {}", addr))?;
+
+        let mut slots = vec![];
+        for slot_range in ranges {
+            if should_ignore_slots(slot_range, migration_states) {
+                continue;
+            }
+            for range in slot_range.get_range_list().get_ranges().iter() {
+                slots.push(Resp::Integer(range.start().to_string().into_bytes()));
+                slots.push(Resp::Integer(range.end().to_string().into_bytes()));
+            }
+        }
+        if slots.is_empty() {
+            continue;
+        }
+
+        let node = Resp::Arr(Array::Arr(vec![
+            bulk("id"),
+            bulk(&gen_node_id(name, addr)),
+            bulk("port"),
+            Resp::Integer(port.as_bytes().to_vec()),
+            bulk("ip"),
+            bulk(host),
+            bulk("endpoint"),
+            bulk(host),
+            bulk("role"),
+            bulk("master"),
+            bulk("replication-offset"),
+            Resp::Integer(b"0".to_vec()),
+            bulk("health"),
+            bulk("online"),
+        ]));
+        shards.push(Resp::Arr(Array::Arr(vec![
+            bulk("slots"),
+            Resp::Arr(Array::Arr(slots)),
+            bulk("nodes"),
+            Resp::Arr(Array::Arr(vec![node])),
+        ])));
+    }
+    Ok(shards)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::cluster::{MigrationMeta, RangeList};
+    use crate::common::cluster::{MigrationMeta, RangeList, RestorePolicy};
     use crate::protocol::{Array, BulkStr};
     use std::convert::TryFrom;
     use std::iter::repeat;
@@ -775,6 +1287,7 @@ mod tests {
             src_node_address: "127.0.0.1:6379".to_string(),
             dst_proxy_address: "127.0.0.1:7001".to_string(),
             dst_node_address: "127.0.0.1:6380".to_string(),
+            restore_policy: RestorePolicy::default(),
         };
         let tag = if migrating {
             SlotRangeTag::Migrating(meta)
@@ -791,6 +1304,53 @@ mod tests {
         slot_ranges
     }
 
+    fn gen_node_map(ranges: &[(&str, &str)]) -> HashMap<String, Vec<SlotRange>> {
+        ranges
+            .iter()
+            .map(|(address, range_list)| {
+                let slot_range = SlotRange {
+                    range_list: RangeList::try_from(*range_list).unwrap(),
+                    tag: SlotRangeTag::None,
+                };
+                (address.to_string(), vec![slot_range])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cluster_ring() {
+        let local1 = gen_node_map(&[
+            ("127.0.0.1:6000", "1 0-4095"),
+            ("127.0.0.1:6001", "1 4096-8191"),
+        ]);
+        let peer1 = gen_node_map(&[("127.0.0.1:7001", "1 8192-16383")]);
+        let ring1 = ClusterRing::new("127.0.0.1:7000", Some(&local1), Some(&peer1));
+        let local2 = gen_node_map(&[
+            ("127.0.0.1:6002", "1 8192-12287"),
+            ("127.0.0.1:6003", "1 12288-16383"),
+        ]);
+        let peer2 = gen_node_map(&[("127.0.0.1:7000", "2 0-4095 4096-8191")]);
+        let ring2 = ClusterRing::new("127.0.0.1:7001", Some(&local2), Some(&peer2));
+
+        // Both proxies select the same owner.
+        let mut local_slots = HashMap::new();
+        for i in 0..1000 {
+            let key = format!("key{}", i);
+            let slot1 = ring1.get_slot(key.as_bytes()).unwrap();
+            let slot2 = ring2.get_slot(key.as_bytes()).unwrap();
+            assert_eq!(slot1 < 8192, slot2 < 8192);
+            let local_slot = if slot1 < 8192 { slot1 } else { slot2 };
+            *local_slots.entry(local_slot).or_insert(0) += 1;
+        }
+        let mut slots: Vec<usize> = local_slots.keys().cloned().collect();
+        slots.sort();
+        assert_eq!(slots, vec![0, 4096, 8192, 12288]);
+        assert_eq!(ring1.get_slot(b"{user}.a"), ring1.get_slot(b"{user}.b"));
+
+        let empty = ClusterRing::new("127.0.0.1:7000", None, None);
+        assert_eq!(empty.get_slot(b"key"), None);
+    }
+
     #[test]
     fn test_gen_cluster_nodes() {
         let m = HashMap::new();
@@ -988,8 +1548,98 @@ mod tests {
         assert_eq!(output.len(), 0);
     }
 
+    #[test]
+    fn test_gen_cluster_shards() {
+        let m = HashMap::new();
+        let name = ClusterName::try_from("testcluster").unwrap();
+        let slot_ranges = gen_testing_slot_ranges("127.0.0.1:5299");
+        let output = gen_cluster_shards_helper(&name, &slot_ranges, &m).unwrap();
+        assert_eq!(output.len(), 1);
+        let shard = match &output[0] {
+            Resp::Arr(Array::Arr(shard)) => shard,
+            others => panic!("unexpected shard {:?}", others),
+        };
+        assert_eq!(shard.len(), 4);
+        let slots = match &shard[1] {
+            Resp::Arr(Array::Arr(slots)) => slots,
+            others => panic!("unexpected slots {:?}", others),
+        };
+        let mut slots: Vec<String> = slots
+            .iter()
+            .map(|slot| match slot {
+                Resp::Integer(n) => String::from_utf8(n.clone()).unwrap(),
+                others => panic!("unexpected slot {:?}", others),
+            })
+            .collect();
+        slots.sort();
+        assert_eq!(slots, vec!["0", "100", "300", "300"]);
+        let node = match &shard[3] {
+            Resp::Arr(Array::Arr(nodes)) => match &nodes[0] {
+                Resp::Arr(Array::Arr(node)) => node,
+                others => panic!("unexpected node {:?}", others),
+            },
+            others => panic!("unexpected nodes {:?}", others),
+        };
+        assert_eq!(
+            node[1],
+            Resp::Bulk(BulkStr::Str(
+                b"testcluster_________9f8fca2805923328____".to_vec()
+            ))
+        );
+        assert_eq!(node[3], Resp::Integer(b"5299".to_vec()));
+        assert_eq!(node[9], Resp::Bulk(BulkStr::Str(b"master".to_vec())));
+
+        // The migrating slots are owned by the destination.
+        let slot_ranges = gen_testing_migration_slot_ranges(true);
+        let output = gen_cluster_shards_helper(&name, &slot_ranges, &m).unwrap();
+        assert!(output.is_empty());
+    }
+
     #[test]
     fn test_default_cluster_length() {
         ClusterName::try_from(DEFAULT_CLUSTER).unwrap();
     }
+
+    #[test]
+    fn test_gen_read_backends() {
+        let mut slot_map = gen_testing_slot_ranges("10.0.0.1:6379");
+        slot_map.extend(gen_testing_slot_ranges("10.0.0.2:6379"));
+        let mut replica_addresses = HashMap::new();
+        replica_addresses.insert(
+            "10.0.0.1:6379".to_string(),
+            vec!["10.0.0.2:6380".to_string()],
+        );
+        replica_addresses.insert(
+            "10.0.0.2:6379".to_string(),
+            vec!["10.0.0.3:6380".to_string(), "10.0.0.1:6380".to_string()],
+        );
+        let service_address = "10.0.0.1:5299";
+
+        let backends = gen_read_backends(
+            ReadPreference::Primary,
+            &slot_map,
+            &replica_addresses,
+            service_address,
+        );
+        assert!(backends.is_empty());
+
+        let backends = gen_read_backends(
+            ReadPreference::ReplicaPreferred,
+            &slot_map,
+            &replica_addresses,
+            service_address,
+        );
+        assert_eq!(backends.len(), 2);
+        assert_eq!(backends.get("10.0.0.1:6379").unwrap(), "10.0.0.2:6380");
+        assert_eq!(backends.get("10.0.0.2:6379").unwrap(), "10.0.0.3:6380");
+
+        let backends = gen_read_backends(
+            ReadPreference::NearestZone,
+            &slot_map,
+            &replica_addresses,
+            service_address,
+        );
+        assert_eq!(backends.len(), 1);
+        assert_eq!(backends.get("10.0.0.2:6379").unwrap(), "10.0.0.1:6380");
+    }
 }
@@ -0,0 +1,162 @@
+use super::backend::CmdTask;
+use super::cluster::ClusterTag;
+use super::command::CommandError;
+use super::session::CmdCtx;
+use crate::common::cluster::ClusterName;
+use crate::protocol::RespVec;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Keep the in-flight map small so that it won't become the bottleneck.
+const MAX_COALESCING_KEYS: usize = 1024;
+
+type CoalescingKey = (ClusterName, Vec<u8>);
+
+pub enum CoalescingResult<'a> {
+    // The reply will be sent to the command by the leader.
+    Waiting,
+    // The command should be sent to the backend.
+    Leader(CmdCtx, CoalescingGuard<'a>),
+    Bypass(CmdCtx),
+}
+
+// Only one GET for the same key will be sent to the backend at the same time.
+// The others wait for the reply of it.
+pub struct GetCoalescer {
+    inflight: Mutex<HashMap<CoalescingKey, Vec<CmdCtx>>>,
+}
+
+impl Default for GetCoalescer {
+    fn default() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl GetCoalescer {
+    pub fn coalesce(&self, cmd_ctx: CmdCtx) -> CoalescingResult<'_> {
+        // The session has written the key recently or the write is not replied yet.
+        // Joining the GET sent before the write could read the old value.
+        if cmd_ctx.is_pinned_to_master() {
+            return CoalescingResult::Bypass(cmd_ctx);
+        }
+        let key = match cmd_ctx.get_key() {
+            Some(key) => (cmd_ctx.get_cluster_name().clone(), key.to_vec()),
+            None => return CoalescingResult::Bypass(cmd_ctx),
+        };
+
+        let mut inflight = self.inflight.lock().expect("GetCoalescer::coalesce");
+        if let Some(waiters) = inflight.get_mut(&key) {
+            waiters.push(cmd_ctx);
+            return CoalescingResult::Waiting;
+        }
+        if inflight.len() >= MAX_COALESCING_KEYS {
+            return CoalescingResult::Bypass(cmd_ctx);
+        }
+        inflight.insert(key.clone(), vec![]);
+        let guard = CoalescingGuard {
+            coalescer: self,
+            key: Some(key),
+        };
+        CoalescingResult::Leader(cmd_ctx, guard)
+    }
+
+    fn remove(&self, key: &CoalescingKey) -> Vec<CmdCtx> {
+        self.inflight
+            .lock()
+            .expect("GetCoalescer::remove")
+            .remove(key)
+            .unwrap_or_default()
+    }
+}
+
+// The waiters get `Dropped` if the guard is dropped without replying.
+pub struct CoalescingGuard<'a> {
+    coalescer: &'a GetCoalescer,
+    key: Option<CoalescingKey>,
+}
+
+impl<'a> CoalescingGuard<'a> {
+    pub fn reply(mut self, result: Result<RespVec, CommandError>) {
+        let key = match self.key.take() {
+            Some(key) => key,
+            None => return,
+        };
+        for waiter in self.coalescer.remove(&key).into_iter() {
+            waiter.set_resp_result(result.clone());
+        }
+    }
+}
+
+impl<'a> Drop for CoalescingGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.coalescer.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Array, BulkStr, Resp, RespPacket};
+    use crate::proxy::command::{new_command_pair, CmdReplyReceiver, Command};
+    use futures::executor::block_on;
+    use std::convert::TryFrom;
+
+    fn gen_get(key: &str) -> (CmdCtx, CmdReplyReceiver) {
+        let resp = Resp::Arr(Array::Arr(vec![
+            Resp::Bulk(BulkStr::Str(b"GET".to_vec())),
+            Resp::Bulk(BulkStr::Str(key.as_bytes().to_vec())),
+        ]));
+        let cmd = Command::new(Box::new(RespPacket::from_resp_vec(resp)));
+        let (reply_sender, reply_receiver) = new_command_pair(&cmd);
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let cmd_ctx = CmdCtx::new(cluster_name, cmd, reply_sender, 0, false);
+        (cmd_ctx, reply_receiver)
+    }
+
+    #[test]
+    fn test_coalescing() {
+        let coalescer = GetCoalescer::default();
+        let (cmd_ctx1, _receiver1) = gen_get("key");
+        let (cmd_ctx2, receiver2) = gen_get("key");
+        let (cmd_ctx3, _receiver3) = gen_get("other");
+
+        let guard = match coalescer.coalesce(cmd_ctx1) {
+            CoalescingResult::Leader(_, guard) => guard,
+            _ => panic!(),
+        };
+        assert!(matches!(
+            coalescer.coalesce(cmd_ctx2),
+            CoalescingResult::Waiting
+        ));
+        assert!(matches!(
+            coalescer.coalesce(cmd_ctx3),
+            CoalescingResult::Leader(..)
+        ));
+
+        guard.reply(Ok(Resp::Bulk(BulkStr::Str(b"value".to_vec()))));
+        let reply = block_on(receiver2).unwrap().into_resp_vec();
+        assert_eq!(reply, Resp::Bulk(BulkStr::Str(b"value".to_vec())));
+        assert!(coalescer.inflight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_coalescing_guard_dropped() {
+        let coalescer = GetCoalescer::default();
+        let (cmd_ctx1, _receiver1) = gen_get("key");
+        let (cmd_ctx2, receiver2) = gen_get("key");
+        let guard = match coalescer.coalesce(cmd_ctx1) {
+            CoalescingResult::Leader(_, guard) => guard,
+            _ => panic!(),
+        };
+        assert!(matches!(
+            coalescer.coalesce(cmd_ctx2),
+            CoalescingResult::Waiting
+        ));
+        drop(guard);
+        assert!(block_on(receiver2).is_err());
+    }
+}
@@ -1,8 +1,12 @@
+use super::key_spec::{get_key_spec, get_numkeys_index};
 use super::slowlog::Slowlog;
-use crate::common::utils::{byte_to_uppercase, generate_slot};
+use crate::common::config::BackendFlavor;
+use crate::common::response;
+use crate::common::utils::{byte_to_uppercase, bytes_ascii_case_insensitive_eq, generate_slot};
 use crate::protocol::{BinSafeStr, RespPacket, RespSlice, RespVec};
 use arrayvec::ArrayVec;
 use backtrace::Backtrace;
+use btoi::btou;
 use futures::channel::oneshot;
 use futures::task::{Context, Poll};
 use futures::Future;
@@ -17,14 +21,37 @@ use std::str;
 
 const MAX_COMMAND_NAME_LENGTH: usize = 64;
 
+// The command name converted on the stack to avoid allocation.
+// The underlying `deref` will take the real length intead of the whole MAX_COMMAND_NAME_LENGTH array.
+pub type CmdName = ArrayVec<[u8; MAX_COMMAND_NAME_LENGTH]>;
+
+// Returns None for the unexpectedly long command names.
+fn convert_cmd_name<F: Fn(u8) -> u8>(cmd_name: &[u8], convert: F) -> Option<CmdName> {
+    let mut stack_cmd_name = CmdName::new();
+    for b in cmd_name {
+        stack_cmd_name.try_push(convert(*b)).ok()?;
+    }
+    Some(stack_cmd_name)
+}
+
+pub fn uppercase_cmd_name(cmd_name: &[u8]) -> Option<CmdName> {
+    convert_cmd_name(cmd_name, byte_to_uppercase)
+}
+
+pub fn lowercase_cmd_name(cmd_name: &[u8]) -> Option<CmdName> {
+    convert_cmd_name(cmd_name, |b| b.to_ascii_lowercase())
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CmdType {
     Ping,
     Info,
     Auth,
+    Hello,
     Quit,
     Echo,
     Select,
+    Reset,
     Others,
     Invalid,
     UmCtl,
@@ -34,27 +61,41 @@ pub enum CmdType {
     Config,
     Command,
     Asking,
+    Subscribe,
+    Unsubscribe,
+    PSubscribe,
+    PUnsubscribe,
+    SSubscribe,
+    SUnsubscribe,
+    Multi,
+    Exec,
+    Discard,
+    Watch,
+    Unwatch,
+    Client,
+    Monitor,
 }
 
 impl CmdType {
     fn from_cmd_name(cmd_name: &[u8]) -> Self {
-        let mut stack_cmd_name = ArrayVec::<[u8; MAX_COMMAND_NAME_LENGTH]>::new();
-        for b in cmd_name {
-            if let Err(err) = stack_cmd_name.try_push(byte_to_uppercase(*b)) {
-                error!("Unexpected long command name: {:?} {:?}", cmd_name, err);
+        let stack_cmd_name = match uppercase_cmd_name(cmd_name) {
+            Some(stack_cmd_name) => stack_cmd_name,
+            None => {
+                error!("Unexpected long command name: {:?}", cmd_name);
                 return CmdType::Others;
             }
-        }
-        // The underlying `deref` will take the real length intead of the whole MAX_COMMAND_NAME_LENGTH array;
+        };
         let cmd_name: &[u8] = &stack_cmd_name;
 
         match cmd_name {
             b"PING" => CmdType::Ping,
             b"INFO" => CmdType::Info,
             b"AUTH" => CmdType::Auth,
+            b"HELLO" => CmdType::Hello,
             b"QUIT" => CmdType::Quit,
             b"ECHO" => CmdType::Echo,
             b"SELECT" => CmdType::Select,
+            b"RESET" => CmdType::Reset,
             b"UMCTL" => CmdType::UmCtl,
             b"UMFORWARD" => CmdType::UmForward,
             b"UMSYNC" => CmdType::UmSync,
@@ -62,6 +103,19 @@ impl CmdType {
             b"CONFIG" => CmdType::Config,
             b"COMMAND" => CmdType::Command,
             b"ASKING" => CmdType::Asking,
+            b"SUBSCRIBE" => CmdType::Subscribe,
+            b"UNSUBSCRIBE" => CmdType::Unsubscribe,
+            b"PSUBSCRIBE" => CmdType::PSubscribe,
+            b"PUNSUBSCRIBE" => CmdType::PUnsubscribe,
+            b"SSUBSCRIBE" => CmdType::SSubscribe,
+            b"SUNSUBSCRIBE" => CmdType::SUnsubscribe,
+            b"MULTI" => CmdType::Multi,
+            b"EXEC" => CmdType::Exec,
+            b"DISCARD" => CmdType::Discard,
+            b"WATCH" => CmdType::Watch,
+            b"UNWATCH" => CmdType::Unwatch,
+            b"CLIENT" => CmdType::Client,
+            b"MONITOR" => CmdType::Monitor,
             _ => CmdType::Others,
         }
     }
@@ -105,12 +159,15 @@ pub enum DataCmdType {
     STRLEN,
     EVAL,
     EVALSHA,
+    SCRIPT,
+    SCAN,
     DEL,
     EXISTS,
     // List commands
     BLPOP,
     BRPOP,
     BRPOPLPUSH,
+    BLMOVE,
     LPOP,
     RPOP,
     RPOPLPUSH,
@@ -123,12 +180,17 @@ pub enum DataCmdType {
     SPOP,
     SREM,
     // Sorted Set commands
+    BZPOPMAX,
+    BZPOPMIN,
     ZPOPMAX,
     ZPOPMIN,
     ZREM,
     ZREMRANGEBYLEX,
     ZREMRANGEBYRANK,
     ZREMRANGEBYSCORE,
+    // Stream commands
+    XREAD,
+    XREADGROUP,
     // Key commands
     EXPIRE,
     EXPIREAT,
@@ -138,22 +200,19 @@ pub enum DataCmdType {
     RENAME,
     RENAMENX,
     UNLINK,
+    TOUCH,
     Others,
 }
 
 impl DataCmdType {
     fn from_cmd_name(cmd_name: &[u8]) -> Self {
-        let mut stack_cmd_name = ArrayVec::<[u8; MAX_COMMAND_NAME_LENGTH]>::new();
-        for b in cmd_name {
-            if let Err(err) = stack_cmd_name.try_push(byte_to_uppercase(*b)) {
-                error!(
-                    "Unexpected long data command name: {:?} {:?}",
-                    cmd_name, err
-                );
+        let stack_cmd_name = match uppercase_cmd_name(cmd_name) {
+            Some(stack_cmd_name) => stack_cmd_name,
+            None => {
+                error!("Unexpected long data command name: {:?}", cmd_name);
                 return DataCmdType::Others;
             }
-        }
-        // The underlying `deref` will take the real length intead of the whole MAX_COMMAND_NAME_LENGTH array;
+        };
         let cmd_name: &[u8] = &stack_cmd_name;
 
         match cmd_name {
@@ -183,11 +242,14 @@ impl DataCmdType {
             b"STRLEN" => DataCmdType::STRLEN,
             b"EVAL" => DataCmdType::EVAL,
             b"EVALSHA" => DataCmdType::EVALSHA,
+            b"SCRIPT" => DataCmdType::SCRIPT,
+            b"SCAN" => DataCmdType::SCAN,
             b"DEL" => DataCmdType::DEL,
             b"EXISTS" => DataCmdType::EXISTS,
             b"BLPOP" => DataCmdType::BLPOP,
             b"BRPOP" => DataCmdType::BRPOP,
             b"BRPOPLPUSH" => DataCmdType::BRPOPLPUSH,
+            b"BLMOVE" => DataCmdType::BLMOVE,
             b"EXPIRE" => DataCmdType::EXPIRE,
             b"EXPIREAT" => DataCmdType::EXPIREAT,
             b"PEXPIRE" => DataCmdType::PEXPIRE,
@@ -205,12 +267,17 @@ impl DataCmdType {
             b"SPOP" => DataCmdType::SPOP,
             b"SREM" => DataCmdType::SREM,
             b"UNLINK" => DataCmdType::UNLINK,
+            b"TOUCH" => DataCmdType::TOUCH,
+            b"BZPOPMAX" => DataCmdType::BZPOPMAX,
+            b"BZPOPMIN" => DataCmdType::BZPOPMIN,
             b"ZPOPMAX" => DataCmdType::ZPOPMAX,
             b"ZPOPMIN" => DataCmdType::ZPOPMIN,
             b"ZREM" => DataCmdType::ZREM,
             b"ZREMRANGEBYLEX" => DataCmdType::ZREMRANGEBYLEX,
             b"ZREMRANGEBYRANK" => DataCmdType::ZREMRANGEBYRANK,
             b"ZREMRANGEBYSCORE" => DataCmdType::ZREMRANGEBYSCORE,
+            b"XREAD" => DataCmdType::XREAD,
+            b"XREADGROUP" => DataCmdType::XREADGROUP,
             _ => DataCmdType::Others,
         }
     }
@@ -260,6 +327,113 @@ pub fn requires_blocking_migration(data_cmd_type: DataCmdType) -> bool {
     }
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CmdTimeoutClass {
+    Fast,
+    Slow,
+    Blocking,
+}
+
+impl CmdTimeoutClass {
+    fn from_cmd_name(cmd_name: &[u8]) -> Self {
+        let stack_cmd_name = match uppercase_cmd_name(cmd_name) {
+            Some(stack_cmd_name) => stack_cmd_name,
+            None => return CmdTimeoutClass::Fast,
+        };
+        let cmd_name: &[u8] = &stack_cmd_name;
+
+        match cmd_name {
+            b"BLPOP" | b"BRPOP" | b"BRPOPLPUSH" | b"BLMOVE" | b"BZPOPMIN" | b"BZPOPMAX"
+            | b"XREAD" | b"XREADGROUP" | b"WAIT" => CmdTimeoutClass::Blocking,
+            b"SORT" | b"SUNION" | b"SUNIONSTORE" | b"SINTER" | b"SINTERSTORE" | b"SDIFF"
+            | b"SDIFFSTORE" | b"ZUNIONSTORE" | b"ZINTERSTORE" | b"SMEMBERS" | b"HGETALL"
+            | b"HKEYS" | b"HVALS" | b"LRANGE" | b"ZRANGE" | b"ZREVRANGE" | b"ZRANGEBYSCORE"
+            | b"ZREVRANGEBYSCORE" | b"ZRANGEBYLEX" | b"ZREVRANGEBYLEX" | b"EVAL" | b"EVALSHA"
+            | b"DUMP" | b"RESTORE" => CmdTimeoutClass::Slow,
+            _ => CmdTimeoutClass::Fast,
+        }
+    }
+
+    pub fn from_packet(packet: &RespPacket) -> Self {
+        match packet.get_array_element(0) {
+            Some(cmd_name) => CmdTimeoutClass::from_cmd_name(cmd_name),
+            None => CmdTimeoutClass::Fast,
+        }
+    }
+}
+
+// Reject the commands not implemented by the backend
+// so that they won't result in unexpected errors or behaviors.
+pub const HELLO_PROTOCOL_VERSION: i64 = 2;
+
+#[derive(Debug, PartialEq)]
+pub struct HelloArgs {
+    // (cluster name, password)
+    pub auth: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+// SETNAME is ignored since CLIENT SETNAME is not supported either.
+pub fn parse_hello_args(cmd: &Command) -> Result<HelloArgs, String> {
+    let mut args = HelloArgs { auth: None };
+    let protover = match cmd.get_command_element(1) {
+        Some(protover) => protover,
+        None => return Ok(args),
+    };
+    match btoi::btoi::<i64>(protover) {
+        Ok(HELLO_PROTOCOL_VERSION) => (),
+        Ok(_) => return Err(response::ERR_NOPROTO.to_string()),
+        Err(_) => {
+            return Err(String::from(
+                "ERR Protocol version is not an integer or out of range",
+            ))
+        }
+    }
+
+    let mut index = 2;
+    while let Some(option) = cmd.get_command_element(index) {
+        let option = str::from_utf8(option).unwrap_or("").to_uppercase();
+        match option.as_str() {
+            "AUTH" => {
+                let user = cmd.get_command_element(index + 1);
+                let password = cmd.get_command_element(index + 2);
+                match (user, password) {
+                    (Some(user), Some(password)) => {
+                        args.auth = Some((user.to_vec(), password.to_vec()))
+                    }
+                    _ => return Err(String::from("ERR Syntax error in HELLO option 'AUTH'")),
+                }
+                index += 3;
+            }
+            "SETNAME" if cmd.get_command_element(index + 1).is_some() => index += 2,
+            _ => return Err(format!("ERR Syntax error in HELLO option '{}'", option)),
+        }
+    }
+    Ok(args)
+}
+
+fn is_supported_by_flavor(flavor: BackendFlavor, packet: &RespPacket) -> bool {
+    let element_in = |index: usize, names: &[&str]| match packet.get_array_element(index) {
+        Some(element) => names
+            .iter()
+            .any(|name| bytes_ascii_case_insensitive_eq(element, name.as_bytes())),
+        None => false,
+    };
+
+    match flavor {
+        BackendFlavor::Redis => true,
+        BackendFlavor::Dragonfly => {
+            if element_in(0, &["DEBUG"]) {
+                return false;
+            }
+            if element_in(0, &["OBJECT"]) {
+                return element_in(1, &["ENCODING", "HELP"]);
+            }
+            true
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CommandInfo {
     cmd_type: CmdType,
@@ -281,8 +455,45 @@ impl CommandInfo {
 
     fn get_key(data_cmd_type: DataCmdType, packet: &RespPacket) -> Option<&[u8]> {
         match data_cmd_type {
-            DataCmdType::EVAL | DataCmdType::EVALSHA => packet.get_array_element(3),
-            _ => packet.get_array_element(1),
+            // EVAL script numkeys key [key ...] arg [arg ...]
+            DataCmdType::EVAL | DataCmdType::EVALSHA => {
+                let key_num = packet
+                    .get_array_element(2)
+                    .and_then(|n| btou::<usize>(n).ok())?;
+                if key_num == 0 {
+                    return None;
+                }
+                packet.get_array_element(3)
+            }
+            // XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+            DataCmdType::XREAD | DataCmdType::XREADGROUP => {
+                let len = packet.get_array_len()?;
+                (1..len)
+                    .find(|i| {
+                        packet
+                            .get_array_element(*i)
+                            .map(|arg| arg.eq_ignore_ascii_case(b"STREAMS"))
+                            .unwrap_or(false)
+                    })
+                    .and_then(|i| packet.get_array_element(i + 1))
+            }
+            _ => {
+                let cmd_name = packet.get_array_element(0)?;
+                if let Some(numkeys_index) = get_numkeys_index(cmd_name) {
+                    let key_num = packet
+                        .get_array_element(numkeys_index)
+                        .and_then(|n| btou::<usize>(n).ok())?;
+                    if key_num == 0 {
+                        return None;
+                    }
+                    return packet.get_array_element(numkeys_index + 1);
+                }
+                let index = get_key_spec(cmd_name)
+                    .filter(|spec| spec.has_fixed_keys())
+                    .map(|spec| spec.first_key as usize)
+                    .unwrap_or(1);
+                packet.get_array_element(index)
+            }
         }
     }
 }
@@ -291,12 +502,26 @@ impl CommandInfo {
 pub struct Command {
     request: Box<RespPacket>,
     info: CommandInfo,
+    // Set by the session with `reply_order_check` to verify the order of the replies.
+    seq: Option<u64>,
 }
 
 impl Command {
     pub fn new(request: Box<RespPacket>) -> Self {
         let info = CommandInfo::new(&request);
-        Self { request, info }
+        Self {
+            request,
+            info,
+            seq: None,
+        }
+    }
+
+    pub fn set_seq(&mut self, seq: u64) {
+        self.seq = Some(seq);
+    }
+
+    pub fn get_seq(&self) -> Option<u64> {
+        self.seq
     }
 
     pub fn into_packet(self) -> Box<RespPacket> {
@@ -328,7 +553,12 @@ impl Command {
     }
 
     pub fn change_element(&mut self, index: usize, data: Vec<u8>) -> bool {
-        self.request.change_bulk_array_element(index, data)
+        if !self.request.change_bulk_array_element(index, data) {
+            return false;
+        }
+        // The command name or the key could be changed.
+        self.info = CommandInfo::new(&self.request);
+        true
     }
 
     pub fn extract_inner_cmd(&mut self, removed_num: usize) -> Option<usize> {
@@ -345,6 +575,14 @@ impl Command {
         true
     }
 
+    pub fn append_cmd(&mut self, following_elements: Vec<BinSafeStr>) -> bool {
+        if !self.request.append_cmd(following_elements) {
+            return false;
+        }
+        self.info = CommandInfo::new(&self.request);
+        true
+    }
+
     pub fn get_type(&self) -> CmdType {
         self.info.cmd_type
     }
@@ -353,6 +591,14 @@ impl Command {
         self.info.data_cmd_type
     }
 
+    pub fn get_timeout_class(&self) -> CmdTimeoutClass {
+        CmdTimeoutClass::from_packet(&self.request)
+    }
+
+    pub fn is_supported_by(&self, flavor: BackendFlavor) -> bool {
+        is_supported_by_flavor(flavor, &self.request)
+    }
+
     pub fn get_key(&self) -> Option<&[u8]> {
         CommandInfo::get_key(self.get_data_cmd_type(), &self.request)
     }
@@ -360,12 +606,19 @@ impl Command {
     pub fn get_slot(&self) -> Option<usize> {
         self.info.slot
     }
+
+    // Not in the command table so the key could be at the wrong position.
+    pub fn is_unknown(&self) -> bool {
+        self.get_command_element(0).and_then(get_key_spec).is_none()
+    }
 }
 
 pub struct TaskReply {
     request: Box<RespPacket>,
     packet: Box<RespPacket>,
     slowlog: Slowlog,
+    // The sequence number of the request in the session.
+    seq: Option<u64>,
 }
 
 impl TaskReply {
@@ -374,18 +627,37 @@ impl TaskReply {
             request,
             packet,
             slowlog,
+            seq: None,
         }
     }
 
+    pub fn with_seq(mut self, seq: Option<u64>) -> Self {
+        self.seq = seq;
+        self
+    }
+
+    pub fn get_seq(&self) -> Option<u64> {
+        self.seq
+    }
+
     pub fn into_inner(self) -> (Box<RespPacket>, Box<RespPacket>, Slowlog) {
         let Self {
             request,
             packet,
             slowlog,
+            ..
         } = self;
         (request, packet, slowlog)
     }
 
+    pub fn get_packet(&self) -> &RespPacket {
+        &self.packet
+    }
+
+    pub fn get_request(&self) -> &RespPacket {
+        &self.request
+    }
+
     pub fn into_resp_vec(self) -> RespVec {
         let (_, packet, _) = self.into_inner();
         packet.into_resp_vec()
@@ -434,7 +706,14 @@ impl CmdReplySender {
             Some(reply_sender) => {
                 if let Err(CommandError::Dropped) = &res {
                     match self.data_cmd_type {
-                        DataCmdType::BLPOP | DataCmdType::BRPOP | DataCmdType::BRPOPLPUSH => {
+                        DataCmdType::BLPOP
+                        | DataCmdType::BRPOP
+                        | DataCmdType::BRPOPLPUSH
+                        | DataCmdType::BLMOVE
+                        | DataCmdType::BZPOPMIN
+                        | DataCmdType::BZPOPMAX
+                        | DataCmdType::XREAD
+                        | DataCmdType::XREADGROUP => {
                             error!("blocking command is dropped")
                         }
                         _ => error!("command is dropped {:?}", Backtrace::new()),
@@ -479,6 +758,7 @@ pub enum CommandError {
     Dropped,
     Canceled,
     InnerError,
+    Timeout,
 }
 
 impl Clone for CommandError {
@@ -492,6 +772,7 @@ impl Clone for CommandError {
             Self::Dropped => Self::Dropped,
             Self::Canceled => Self::Canceled,
             Self::InnerError => Self::InnerError,
+            Self::Timeout => Self::Timeout,
         }
     }
 }
@@ -523,6 +804,8 @@ mod tests {
     #[test]
     fn test_parse_cmd_type() {
         assert_eq!(CmdType::from_cmd_name(b"pInG"), CmdType::Ping);
+        assert_eq!(CmdType::from_cmd_name(b"reset"), CmdType::Reset);
+        assert_eq!(CmdType::from_cmd_name(b"Hello"), CmdType::Hello);
         assert_eq!(CmdType::from_cmd_name(b"get"), CmdType::Others);
     }
 
@@ -534,6 +817,137 @@ mod tests {
         assert_eq!(DataCmdType::from_cmd_name(b"HMGET"), DataCmdType::Others);
     }
 
+    #[test]
+    fn test_eval_key() {
+        let gen_cmd = |elements: Vec<&str>| {
+            let elements = elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect();
+            Command::new(Box::new(RespPacket::Data(Resp::Arr(Array::Arr(elements)))))
+        };
+        let eval = gen_cmd(vec!["EVAL", "return 1", "2", "a", "b", "arg"]);
+        assert_eq!(eval.get_key(), Some("a".as_bytes()));
+        assert_eq!(eval.get_slot(), Some(generate_slot(b"a")));
+        let evalsha = gen_cmd(vec!["EVALSHA", "sha", "0", "arg"]);
+        assert_eq!(evalsha.get_key(), None);
+        assert_eq!(evalsha.get_slot(), None);
+        let invalid = gen_cmd(vec!["EVAL", "return 1", "x", "a"]);
+        assert_eq!(invalid.get_slot(), None);
+    }
+
+    #[test]
+    fn test_movable_key() {
+        let gen_cmd = |elements: Vec<&str>| {
+            let elements = elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect();
+            Command::new(Box::new(RespPacket::Data(Resp::Arr(Array::Arr(elements)))))
+        };
+        let xread = gen_cmd(vec!["XREAD", "BLOCK", "0", "streams", "s", "$"]);
+        assert_eq!(xread.get_data_cmd_type(), DataCmdType::XREAD);
+        assert_eq!(xread.get_key(), Some("s".as_bytes()));
+        let xreadgroup = gen_cmd(vec!["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", ">"]);
+        assert_eq!(xreadgroup.get_key(), Some("s".as_bytes()));
+        let invalid = gen_cmd(vec!["XREAD", "BLOCK", "0"]);
+        assert_eq!(invalid.get_key(), None);
+        let zinter = gen_cmd(vec!["ZINTER", "2", "a", "b"]);
+        assert_eq!(zinter.get_key(), Some("a".as_bytes()));
+    }
+
+    #[test]
+    fn test_parse_timeout_class() {
+        assert_eq!(
+            CmdTimeoutClass::from_cmd_name(b"sort"),
+            CmdTimeoutClass::Slow
+        );
+        assert_eq!(
+            CmdTimeoutClass::from_cmd_name(b"BLPOP"),
+            CmdTimeoutClass::Blocking
+        );
+        assert_eq!(
+            CmdTimeoutClass::from_cmd_name(b"GET"),
+            CmdTimeoutClass::Fast
+        );
+    }
+
+    #[test]
+    fn test_convert_cmd_name() {
+        let cmd_name = uppercase_cmd_name(b"hGetAll").unwrap();
+        assert_eq!(&cmd_name[..], b"HGETALL");
+        let cmd_name = lowercase_cmd_name(b"hGetAll").unwrap();
+        assert_eq!(&cmd_name[..], b"hgetall");
+        let long_name = vec![b'a'; MAX_COMMAND_NAME_LENGTH + 1];
+        assert!(uppercase_cmd_name(&long_name).is_none());
+        assert_eq!(CmdType::from_cmd_name(&long_name), CmdType::Others);
+    }
+
+    #[test]
+    fn test_backend_flavor_supported_cmd() {
+        let gen_cmd = |elements: Vec<&str>| {
+            let elements = elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect();
+            Command::new(Box::new(RespPacket::Data(Resp::Arr(Array::Arr(elements)))))
+        };
+        let debug = gen_cmd(vec!["debug", "sleep", "0"]);
+        let object_freq = gen_cmd(vec!["OBJECT", "freq", "key"]);
+        let object_encoding = gen_cmd(vec!["OBJECT", "encoding", "key"]);
+        let get = gen_cmd(vec!["GET", "key"]);
+        assert!(debug.is_supported_by(BackendFlavor::Redis));
+        assert!(object_freq.is_supported_by(BackendFlavor::Redis));
+        assert!(!debug.is_supported_by(BackendFlavor::Dragonfly));
+        assert!(!object_freq.is_supported_by(BackendFlavor::Dragonfly));
+        assert!(object_encoding.is_supported_by(BackendFlavor::Dragonfly));
+        assert!(get.is_supported_by(BackendFlavor::Dragonfly));
+    }
+
+    #[test]
+    fn test_parse_hello_args() {
+        let gen_cmd = |elements: Vec<&str>| {
+            let elements = elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect();
+            Command::new(Box::new(RespPacket::Data(Resp::Arr(Array::Arr(elements)))))
+        };
+        assert_eq!(
+            parse_hello_args(&gen_cmd(vec!["HELLO"])),
+            Ok(HelloArgs { auth: None })
+        );
+        assert_eq!(
+            parse_hello_args(&gen_cmd(vec![
+                "HELLO",
+                "2",
+                "setname",
+                "myclient",
+                "auth",
+                "mycluster",
+                "mypassword"
+            ])),
+            Ok(HelloArgs {
+                auth: Some((b"mycluster".to_vec(), b"mypassword".to_vec()))
+            })
+        );
+        // AUTH is not processed when the version is not supported.
+        assert_eq!(
+            parse_hello_args(&gen_cmd(vec![
+                "HELLO",
+                "3",
+                "AUTH",
+                "mycluster",
+                "mypassword"
+            ])),
+            Err(response::ERR_NOPROTO.to_string())
+        );
+        assert!(parse_hello_args(&gen_cmd(vec!["HELLO", "two"])).is_err());
+        assert!(parse_hello_args(&gen_cmd(vec!["HELLO", "2", "AUTH", "mycluster"])).is_err());
+        assert!(parse_hello_args(&gen_cmd(vec!["HELLO", "2", "SETNAME"])).is_err());
+        assert!(parse_hello_args(&gen_cmd(vec!["HELLO", "2", "MAXAGE", "1"])).is_err());
+    }
+
     #[test]
     fn test_umforward() {
         let request = RespPacket::Data(Resp::Arr(Array::Arr(vec![
@@ -0,0 +1,2459 @@
+// Generated by docs/generate_command_table.py. Do not edit.
+use super::key_spec::KeySpec;
+
+// Sorted by the lowercase command names.
+pub const COMMAND_KEY_SPECS: &[(&str, KeySpec)] = &[
+    (
+        "append",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "asking",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "auth",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "bgrewriteaof",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "bgsave",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "bitcount",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "bitfield",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "bitfield_ro",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "bitop",
+        KeySpec {
+            first_key: 2,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "bitpos",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "blmove",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "blpop",
+        KeySpec {
+            first_key: 1,
+            last_key: -2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "brpop",
+        KeySpec {
+            first_key: 1,
+            last_key: -2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "brpoplpush",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "bzpopmax",
+        KeySpec {
+            first_key: 1,
+            last_key: -2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "bzpopmin",
+        KeySpec {
+            first_key: 1,
+            last_key: -2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "client",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "cluster",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "command",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "config",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "copy",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "dbsize",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "debug",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "decr",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "decrby",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "del",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "discard",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "dump",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "echo",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "eval",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "evalsha",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "exec",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "exists",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "expire",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "expireat",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "expiretime",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "flushall",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "flushdb",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "geoadd",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "geodist",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "geohash",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "geopos",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "georadius",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "georadius_ro",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "georadiusbymember",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "georadiusbymember_ro",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "geosearch",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "geosearchstore",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "get",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "getbit",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "getdel",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "getex",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "getrange",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "getset",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "hdel",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "hexists",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hget",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hgetall",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hincrby",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "hincrbyfloat",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "hkeys",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hlen",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hmget",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hmset",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "host:",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "hrandfield",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hscan",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hset",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "hsetnx",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "hstrlen",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "hvals",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "incr",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "incrby",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "incrbyfloat",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "info",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "keys",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "lastsave",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "latency",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "lcs",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "lindex",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "linsert",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "llen",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "lmove",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "lolwut",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "lpop",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "lpos",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "lpush",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "lpushx",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "lrange",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "lrem",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "lset",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "ltrim",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "memory",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "mget",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "migrate",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "module",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "monitor",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "move",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "mset",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 2,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "msetnx",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 2,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "multi",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "object",
+        KeySpec {
+            first_key: 2,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "persist",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "pexpire",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "pexpireat",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "pexpiretime",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "pfadd",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "pfcount",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "pfdebug",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "pfmerge",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "pfselftest",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "ping",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "post",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "psetex",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "psubscribe",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "psync",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "pttl",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "publish",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "pubsub",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "punsubscribe",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "randomkey",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "readonly",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "readwrite",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "rename",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "renamenx",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "replconf",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "replicaof",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "restore",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "restore-asking",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "role",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "rpop",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "rpoplpush",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "rpush",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "rpushx",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "sadd",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "save",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "scan",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "scard",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "script",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "sdiff",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "sdiffstore",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "select",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "set",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "setbit",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "setex",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "setnx",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "setrange",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "shutdown",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "sinter",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "sintercard",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "sinterstore",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "sismember",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "slaveof",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "slowlog",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "smembers",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "smismember",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "smove",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "sort",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "spop",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "srandmember",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "srem",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "sscan",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "strlen",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "subscribe",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "substr",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "sunion",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "sunionstore",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "swapdb",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "sync",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "time",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "touch",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "ttl",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "type",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "unlink",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "unsubscribe",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "unwatch",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "wait",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "watch",
+        KeySpec {
+            first_key: 1,
+            last_key: -1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: false,
+        },
+    ),
+    (
+        "xack",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "xadd",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "xclaim",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "xdel",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "xgroup",
+        KeySpec {
+            first_key: 2,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "xinfo",
+        KeySpec {
+            first_key: 2,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "xlen",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "xpending",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "xrange",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "xread",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "xreadgroup",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "xrevrange",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "xsetid",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "xtrim",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zadd",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zcard",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zcount",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zdiff",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zdiffstore",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zincrby",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zinter",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zintercard",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: true,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zinterstore",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zlexcount",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zmscore",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zpopmax",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zpopmin",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zrandmember",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrange",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrangebylex",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrangebyscore",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrangestore",
+        KeySpec {
+            first_key: 1,
+            last_key: 2,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zrank",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrem",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zremrangebylex",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zremrangebyrank",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zremrangebyscore",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: false,
+            write: true,
+        },
+    ),
+    (
+        "zrevrange",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrevrangebylex",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrevrangebyscore",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zrevrank",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zscan",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zscore",
+        KeySpec {
+            first_key: 1,
+            last_key: 1,
+            step: 1,
+            movable_keys: false,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zunion",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: true,
+            write: false,
+        },
+    ),
+    (
+        "zunionstore",
+        KeySpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            movable_keys: true,
+            readonly: false,
+            write: true,
+        },
+    ),
+];
@@ -0,0 +1,318 @@
+use super::service::ServerProxyConfig;
+use super::tls::{TlsAcceptorHolder, TlsIdentityFile};
+use crate::common::config::ClusterConfig;
+use crate::common::config_file::{ConfigFileError, StringList};
+use crate::MAX_REDIRECTIONS;
+use std::cmp::min;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicI64, AtomicU64};
+
+// The fields of `conf/server-proxy.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerProxyConfigFile {
+    pub address: String,
+    // The same as `address` by default.
+    pub announce_address: Option<String>,
+    pub auto_select_cluster: bool,
+    pub slowlog_len: usize,
+    pub slowlog_log_slower_than: i64,
+    pub slowlog_sample_rate: u64,
+    pub thread_number: usize,
+    pub thread_per_core: bool,
+    pub session_channel_size: usize,
+    pub backend_channel_size: usize,
+    pub backend_conn_num: usize,
+    pub backend_batch_min_time: usize,
+    pub backend_batch_max_time: usize,
+    pub backend_batch_buf: usize,
+    pub session_batch_min_time: usize,
+    pub session_batch_max_time: usize,
+    pub session_batch_buf: usize,
+    pub active_redirection: bool,
+    // Zero means no limitation.
+    pub max_redirections: usize,
+    pub backend_redirection: bool,
+    pub sentinel_addresses: StringList,
+    pub request_coalescing: bool,
+    pub backend_concurrency_limit: usize,
+    pub backend_inflight_window: usize,
+    pub backend_session_fairness: bool,
+    pub backend_idle_check_time: u64,
+    pub command_timeout_fast: u64,
+    pub command_timeout_slow: u64,
+    pub command_timeout_blocking: u64,
+    pub max_memory: u64,
+    pub accept_backlog: usize,
+    pub max_accepts_per_tick: usize,
+    pub accept_storm_pause_time: u64,
+    pub session_panic_isolation: bool,
+    pub reply_order_check: bool,
+    pub read_after_write_window: u64,
+    pub replica_ack_timeout: u64,
+    pub monitor_buffer_size: usize,
+    pub tls_pkcs12_file: Option<String>,
+    pub tls_pkcs12_password: String,
+
+    // The default cluster config
+    pub compression_strategy: Option<String>,
+    pub migration_max_migration_time: Option<String>,
+    pub migration_max_blocking_time: Option<String>,
+    pub migration_scan_interval: Option<String>,
+    pub migration_scan_count: Option<String>,
+    pub migration_node_bandwidth: Option<String>,
+    pub migration_read_blocking_policy: Option<String>,
+    pub migration_write_blocking_policy: Option<String>,
+    pub acl_categories: Option<String>,
+    pub acl_key_patterns: Option<String>,
+    pub auth_passwords: Option<String>,
+    pub auth_users: Option<String>,
+    pub auth_user_key_patterns: Option<String>,
+    pub backend_flavor: Option<String>,
+    pub max_key_size: Option<String>,
+    pub max_value_size: Option<String>,
+    pub ttl_policy: Option<String>,
+    pub default_ttl: Option<String>,
+    pub canary_cluster: Option<String>,
+    pub canary_percentage: Option<String>,
+    pub double_write_cluster: Option<String>,
+    pub read_preference: Option<String>,
+    pub max_replica_lag: Option<String>,
+    pub routing_mode: Option<String>,
+    pub unknown_command_policy: Option<String>,
+}
+
+impl Default for ServerProxyConfigFile {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:5299".to_string(),
+            announce_address: None,
+            auto_select_cluster: true,
+            slowlog_len: 1024,
+            slowlog_log_slower_than: 50000,
+            slowlog_sample_rate: 1000,
+            thread_number: 2,
+            thread_per_core: false,
+            session_channel_size: 4096,
+            backend_channel_size: 4096,
+            backend_conn_num: 2,
+            backend_batch_min_time: 20000,
+            backend_batch_max_time: 400_000,
+            backend_batch_buf: 10,
+            session_batch_min_time: 20000,
+            session_batch_max_time: 400_000,
+            session_batch_buf: 10,
+            active_redirection: false,
+            max_redirections: 0,
+            backend_redirection: false,
+            sentinel_addresses: StringList::default(),
+            request_coalescing: false,
+            backend_concurrency_limit: 0,
+            backend_inflight_window: 0,
+            backend_session_fairness: true,
+            backend_idle_check_time: 60000,
+            command_timeout_fast: 0,
+            command_timeout_slow: 0,
+            command_timeout_blocking: 0,
+            max_memory: 0,
+            accept_backlog: 1024,
+            max_accepts_per_tick: 0,
+            accept_storm_pause_time: 0,
+            session_panic_isolation: true,
+            reply_order_check: false,
+            read_after_write_window: 0,
+            replica_ack_timeout: 1000,
+            monitor_buffer_size: 10000,
+            tls_pkcs12_file: None,
+            tls_pkcs12_password: String::new(),
+            compression_strategy: None,
+            migration_max_migration_time: None,
+            migration_max_blocking_time: None,
+            migration_scan_interval: None,
+            migration_scan_count: None,
+            migration_node_bandwidth: None,
+            migration_read_blocking_policy: None,
+            migration_write_blocking_policy: None,
+            acl_categories: None,
+            acl_key_patterns: None,
+            auth_passwords: None,
+            auth_users: None,
+            auth_user_key_patterns: None,
+            backend_flavor: None,
+            max_key_size: None,
+            max_value_size: None,
+            ttl_policy: None,
+            default_ttl: None,
+            canary_cluster: None,
+            canary_percentage: None,
+            double_write_cluster: None,
+            read_preference: None,
+            max_replica_lag: None,
+            routing_mode: None,
+            unknown_command_policy: None,
+        }
+    }
+}
+
+fn non_zero(field: &str, n: usize) -> Result<NonZeroUsize, ConfigFileError> {
+    NonZeroUsize::new(n).ok_or_else(|| ConfigFileError::invalid_field(field, "should not be zero"))
+}
+
+impl ServerProxyConfigFile {
+    fn gen_cluster_config(&self) -> Result<ClusterConfig, ConfigFileError> {
+        let cluster_fields = [
+            ("compression_strategy", &self.compression_strategy),
+            (
+                "migration_max_migration_time",
+                &self.migration_max_migration_time,
+            ),
+            (
+                "migration_max_blocking_time",
+                &self.migration_max_blocking_time,
+            ),
+            ("migration_scan_interval", &self.migration_scan_interval),
+            ("migration_scan_count", &self.migration_scan_count),
+            ("migration_node_bandwidth", &self.migration_node_bandwidth),
+            (
+                "migration_read_blocking_policy",
+                &self.migration_read_blocking_policy,
+            ),
+            (
+                "migration_write_blocking_policy",
+                &self.migration_write_blocking_policy,
+            ),
+            ("acl_categories", &self.acl_categories),
+            ("acl_key_patterns", &self.acl_key_patterns),
+            ("auth_passwords", &self.auth_passwords),
+            ("auth_users", &self.auth_users),
+            ("auth_user_key_patterns", &self.auth_user_key_patterns),
+            ("backend_flavor", &self.backend_flavor),
+            ("max_key_size", &self.max_key_size),
+            ("max_value_size", &self.max_value_size),
+            ("ttl_policy", &self.ttl_policy),
+            ("default_ttl", &self.default_ttl),
+            ("canary_cluster", &self.canary_cluster),
+            ("canary_percentage", &self.canary_percentage),
+            ("double_write_cluster", &self.double_write_cluster),
+            ("read_preference", &self.read_preference),
+            ("max_replica_lag", &self.max_replica_lag),
+            ("routing_mode", &self.routing_mode),
+            ("unknown_command_policy", &self.unknown_command_policy),
+        ];
+        let mut cluster_config = ClusterConfig::default();
+        for (field, value) in cluster_fields.iter() {
+            if let Some(value) = value {
+                cluster_config
+                    .set_field(field, value)
+                    .map_err(|err| ConfigFileError::invalid_field(field, &err.to_string()))?;
+            }
+        }
+        Ok(cluster_config)
+    }
+
+    // Also loads the TLS identity file.
+    pub fn into_config(self) -> Result<(ServerProxyConfig, ClusterConfig), ConfigFileError> {
+        let cluster_config = self.gen_cluster_config()?;
+
+        let max_redirections = if self.max_redirections != 0 {
+            NonZeroUsize::new(min(MAX_REDIRECTIONS, self.max_redirections))
+        } else {
+            None
+        };
+        let tls = TlsAcceptorHolder::default();
+        if let Some(pkcs12_file) = self.tls_pkcs12_file {
+            let identity_file = TlsIdentityFile {
+                pkcs12_file,
+                password: self.tls_pkcs12_password,
+            };
+            tls.load(identity_file).map_err(|err| {
+                ConfigFileError::invalid_field("tls_pkcs12_file", &format!("{:?}", err))
+            })?;
+        }
+
+        let address = self.address;
+        let announce_address = self.announce_address.unwrap_or_else(|| address.clone());
+        let config = ServerProxyConfig {
+            address,
+            announce_address,
+            auto_select_cluster: self.auto_select_cluster,
+            slowlog_len: non_zero("slowlog_len", self.slowlog_len)?,
+            slowlog_log_slower_than: AtomicI64::new(self.slowlog_log_slower_than),
+            slowlog_sample_rate: AtomicU64::new(self.slowlog_sample_rate),
+            thread_number: non_zero("thread_number", self.thread_number)?,
+            thread_per_core: self.thread_per_core,
+            session_channel_size: self.session_channel_size,
+            backend_channel_size: self.backend_channel_size,
+            backend_conn_num: non_zero("backend_conn_num", self.backend_conn_num)?,
+            backend_batch_min_time: self.backend_batch_min_time,
+            backend_batch_max_time: self.backend_batch_max_time,
+            backend_batch_buf: non_zero("backend_batch_buf", self.backend_batch_buf)?,
+            session_batch_min_time: self.session_batch_min_time,
+            session_batch_max_time: self.session_batch_max_time,
+            session_batch_buf: non_zero("session_batch_buf", self.session_batch_buf)?,
+            active_redirection: self.active_redirection,
+            max_redirections,
+            backend_redirection: self.backend_redirection,
+            sentinel_addresses: self.sentinel_addresses.into_vec(),
+            request_coalescing: self.request_coalescing,
+            backend_concurrency_limit: self.backend_concurrency_limit,
+            backend_inflight_window: self.backend_inflight_window,
+            backend_session_fairness: self.backend_session_fairness,
+            backend_idle_check_time: self.backend_idle_check_time,
+            tls,
+            command_timeout_fast: AtomicU64::new(self.command_timeout_fast),
+            command_timeout_slow: AtomicU64::new(self.command_timeout_slow),
+            command_timeout_blocking: AtomicU64::new(self.command_timeout_blocking),
+            max_memory: AtomicU64::new(self.max_memory),
+            accept_backlog: self.accept_backlog,
+            max_accepts_per_tick: self.max_accepts_per_tick,
+            accept_storm_pause_time: self.accept_storm_pause_time,
+            session_panic_isolation: self.session_panic_isolation,
+            reply_order_check: self.reply_order_check,
+            read_after_write_window: self.read_after_write_window,
+            replica_ack_timeout: self.replica_ack_timeout,
+            monitor_buffer_size: self.monitor_buffer_size,
+        };
+        Ok((config, cluster_config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::config::CompressionStrategy;
+    use crate::common::config_file::parse_config;
+
+    #[test]
+    fn test_server_proxy_config_file() {
+        let mut s = config::Config::new();
+        s.merge(config::File::with_name("conf/server-proxy.toml"))
+            .unwrap();
+        let config_file: ServerProxyConfigFile = parse_config(s).unwrap();
+        let (config, cluster_config) = config_file.into_config().unwrap();
+        assert_eq!(config.address, "127.0.0.1:5299");
+        assert_eq!(config.max_redirections, NonZeroUsize::new(4));
+        assert!(config.sentinel_addresses.is_empty());
+        assert_eq!(
+            cluster_config.compression_strategy,
+            CompressionStrategy::Disabled
+        );
+
+        let (config, _) = ServerProxyConfigFile::default().into_config().unwrap();
+        assert_eq!(config.announce_address, config.address);
+
+        let mut s = config::Config::new();
+        s.set("thread_number", "0").unwrap();
+        let config_file: ServerProxyConfigFile = parse_config(s).unwrap();
+        assert!(config_file.into_config().is_err());
+
+        let mut s = config::Config::new();
+        s.set("compression_strategy", "unknown").unwrap();
+        let config_file: ServerProxyConfigFile = parse_config(s).unwrap();
+        assert!(config_file.into_config().is_err());
+
+        let mut s = config::Config::new();
+        s.set("thread_numbr", "2").unwrap();
+        assert!(parse_config::<ServerProxyConfigFile>(s).is_err());
+    }
+}
@@ -0,0 +1,319 @@
+use super::acl::get_cmd_category;
+use super::backend::ConnFactory;
+use super::cluster::ClusterTag;
+use super::command::{new_command_pair, CmdReplyReceiver, Command, DataCmdType, TaskResult};
+use super::manager::SharedMetaMap;
+use super::session::{CmdCtx, CmdReplyFuture};
+use crate::common::config::AclCategory;
+use crate::common::utils::generate_slot;
+use crate::protocol::{Array, BulkStr, Resp, RespPacket, RespVec};
+use btoi::btoi;
+use futures::future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+pub struct DoubleWriteStats {
+    sent: AtomicU64,
+    matched: AtomicU64,
+    diverged: AtomicU64,
+    failed: AtomicU64,
+    skipped: AtomicU64,
+}
+
+impl DoubleWriteStats {
+    fn record(&self, primary: &Result<RespVec, ()>, secondary: &Result<RespVec, ()>) {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        match (primary, secondary) {
+            (_, Err(())) => {
+                self.failed.fetch_add(1, Ordering::SeqCst);
+            }
+            (Ok(p), Ok(s)) if p == s => {
+                self.matched.fetch_add(1, Ordering::SeqCst);
+            }
+            _ => {
+                debug!("double write diverged: {:?} {:?}", primary, secondary);
+                self.diverged.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub fn info(&self) -> RespVec {
+        let lines = vec![
+            format!("sent: {}", self.sent.load(Ordering::SeqCst)),
+            format!("matched: {}", self.matched.load(Ordering::SeqCst)),
+            format!("diverged: {}", self.diverged.load(Ordering::SeqCst)),
+            format!("failed: {}", self.failed.load(Ordering::SeqCst)),
+            format!("skipped: {}", self.skipped.load(Ordering::SeqCst)),
+        ];
+        Resp::Arr(Array::Arr(
+            lines
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.into_bytes())))
+                .collect(),
+        ))
+    }
+}
+
+// Applies the write commands to both the old and the new cluster
+// for the cluster-to-cluster moves without slot migration.
+// Only the replies of the old cluster are returned to the clients.
+pub struct DoubleWriter<C: ConnFactory<Pkt = RespPacket>> {
+    meta_map: SharedMetaMap<C>,
+    stats: Arc<DoubleWriteStats>,
+}
+
+impl<C: ConnFactory<Pkt = RespPacket>> DoubleWriter<C> {
+    pub fn new(meta_map: SharedMetaMap<C>) -> Self {
+        Self {
+            meta_map,
+            stats: Arc::new(DoubleWriteStats::default()),
+        }
+    }
+
+    pub fn get_stats(&self) -> &DoubleWriteStats {
+        &self.stats
+    }
+
+    // Returns the single key commands to be sent to the double write cluster.
+    pub fn gen_secondary_cmds(&self, cmd_ctx: &CmdCtx) -> Vec<(CmdCtx, CmdReplyReceiver)> {
+        let meta_map = self.meta_map.lease();
+        let cluster_map = meta_map.get_cluster_map();
+        let cluster_name = cmd_ctx.get_cluster_name();
+        let dst_cluster = match cluster_map.get_config(cluster_name) {
+            Some(config) if config.double_write_enabled() => &config.double_write_cluster,
+            _ => return vec![],
+        };
+        if dst_cluster == cluster_name || !cluster_map.cluster_exists(dst_cluster) {
+            return vec![];
+        }
+        if !is_double_write_cmd(cmd_ctx.get_cmd()) {
+            return vec![];
+        }
+
+        let packets = match split_write_cmd(cmd_ctx.get_cmd()) {
+            Some(packets) => packets,
+            None => {
+                warn!("skip double writing the command across slots");
+                self.stats.skipped.fetch_add(1, Ordering::SeqCst);
+                return vec![];
+            }
+        };
+        packets
+            .into_iter()
+            .map(|packet| {
+                let cmd = Command::new(Box::new(packet));
+                let (reply_sender, reply_receiver) = new_command_pair(&cmd);
+                let secondary_cmd_ctx = CmdCtx::new(
+                    dst_cluster.clone(),
+                    cmd,
+                    reply_sender,
+                    cmd_ctx.get_session_id(),
+                    false,
+                );
+                (secondary_cmd_ctx, reply_receiver)
+            })
+            .collect()
+    }
+
+    // The replies of the new cluster are compared in the background
+    // without blocking the replies of the old one.
+    pub fn compare_replies<'a>(
+        &self,
+        fut: CmdReplyFuture<'a>,
+        data_cmd_type: DataCmdType,
+        reply_receivers: Vec<CmdReplyReceiver>,
+    ) -> CmdReplyFuture<'a> {
+        let stats = self.stats.clone();
+        CmdReplyFuture::Right(Box::pin(async move {
+            let res = fut.await;
+            let primary = match &res {
+                Ok(reply) => Ok(reply.get_packet().to_resp_vec()),
+                Err(_) => Err(()),
+            };
+            tokio::spawn(async move {
+                let results = future::join_all(reply_receivers).await;
+                let secondary = merge_replies(data_cmd_type, results);
+                stats.record(&primary, &secondary);
+            });
+            res
+        }))
+    }
+}
+
+fn is_double_write_cmd(cmd: &Command) -> bool {
+    match cmd.get_data_cmd_type() {
+        DataCmdType::BLPOP
+        | DataCmdType::BRPOP
+        | DataCmdType::BRPOPLPUSH
+        | DataCmdType::BLMOVE
+        | DataCmdType::BZPOPMIN
+        | DataCmdType::BZPOPMAX
+        | DataCmdType::XREADGROUP => false,
+        _ => !matches!(get_cmd_category(cmd), Some(AclCategory::Read) | None),
+    }
+}
+
+// MSET, DEL, UNLINK and TOUCH could not be sent to the backends directly
+// since their keys might be in different slots.
+// Returns None for MSETNX across slots which could not be split without losing its atomicity.
+fn split_write_cmd(cmd: &Command) -> Option<Vec<RespPacket>> {
+    let len = cmd.get_command_len().unwrap_or(0);
+    let gen_packet = |elements: Vec<&[u8]>| {
+        RespPacket::from_resp_vec(Resp::Arr(Array::Arr(
+            elements
+                .into_iter()
+                .map(|e| Resp::Bulk(BulkStr::Str(e.to_vec())))
+                .collect(),
+        )))
+    };
+    let element = |i| cmd.get_command_element(i).unwrap_or(b"");
+    let split_keys = |cmd_name: &'static [u8]| {
+        (1..len)
+            .map(|i| gen_packet(vec![cmd_name, element(i)]))
+            .collect()
+    };
+    let packets = match cmd.get_data_cmd_type() {
+        DataCmdType::MSET if len >= 3 => (1..len - 1)
+            .step_by(2)
+            .map(|i| gen_packet(vec![b"SET", element(i), element(i + 1)]))
+            .collect(),
+        DataCmdType::DEL if len > 2 => split_keys(b"DEL"),
+        DataCmdType::UNLINK if len > 2 => split_keys(b"UNLINK"),
+        DataCmdType::TOUCH if len > 2 => split_keys(b"TOUCH"),
+        DataCmdType::MSETNX if len >= 3 => {
+            let slot = generate_slot(element(1));
+            let same_slot = (3..len)
+                .step_by(2)
+                .all(|i| generate_slot(element(i)) == slot);
+            if !same_slot {
+                return None;
+            }
+            vec![cmd.get_packet()]
+        }
+        _ => vec![cmd.get_packet()],
+    };
+    Some(packets)
+}
+
+fn merge_replies(data_cmd_type: DataCmdType, results: Vec<TaskResult>) -> Result<RespVec, ()> {
+    let mut resps = vec![];
+    for result in results.into_iter() {
+        let resp = result.map_err(|_| ())?.into_resp_vec();
+        if let Resp::Error(_) = resp {
+            return Ok(resp);
+        }
+        resps.push(resp);
+    }
+    if resps.len() == 1 {
+        return resps.pop().ok_or(());
+    }
+
+    match data_cmd_type {
+        DataCmdType::MSET => Ok(Resp::Simple(b"OK".to_vec())),
+        DataCmdType::DEL | DataCmdType::UNLINK | DataCmdType::TOUCH => {
+            let mut count: i64 = 0;
+            for resp in resps.iter() {
+                match resp {
+                    Resp::Integer(n) => count += btoi::<i64>(n).map_err(|_| ())?,
+                    _ => return Err(()),
+                }
+            }
+            Ok(Resp::Integer(count.to_string().into_bytes()))
+        }
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::command::TaskReply;
+    use crate::proxy::slowlog::Slowlog;
+
+    fn gen_cmd(elements: Vec<&str>) -> Command {
+        let resp = Resp::Arr(Array::Arr(
+            elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect(),
+        ));
+        Command::new(Box::new(RespPacket::from_resp_vec(resp)))
+    }
+
+    fn gen_result(resp: RespVec) -> TaskResult {
+        let request = Box::new(RespPacket::from_resp_vec(Resp::Simple(vec![])));
+        let packet = Box::new(RespPacket::from_resp_vec(resp));
+        Ok(Box::new(TaskReply::new(
+            request,
+            packet,
+            Slowlog::new(0, false),
+        )))
+    }
+
+    #[test]
+    fn test_split_write_cmd() {
+        assert!(!is_double_write_cmd(&gen_cmd(vec!["GET", "a"])));
+        assert!(!is_double_write_cmd(&gen_cmd(vec!["BLPOP", "a", "0"])));
+        assert!(is_double_write_cmd(&gen_cmd(vec!["SET", "a", "1"])));
+
+        let packets = split_write_cmd(&gen_cmd(vec!["MSET", "a", "1", "b", "2"])).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].get_array_element(0), Some(&b"SET"[..]));
+        assert_eq!(packets[1].get_array_element(1), Some(&b"b"[..]));
+        assert_eq!(packets[1].get_array_element(2), Some(&b"2"[..]));
+        let split_len = |elements| split_write_cmd(&gen_cmd(elements)).map(|p| p.len());
+        assert_eq!(split_len(vec!["DEL", "a", "b"]), Some(2));
+        assert_eq!(split_len(vec!["DEL", "a"]), Some(1));
+
+        let packets = split_write_cmd(&gen_cmd(vec!["UNLINK", "a", "b", "c"])).unwrap();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[2].get_array_element(0), Some(&b"UNLINK"[..]));
+        assert_eq!(packets[2].get_array_element(1), Some(&b"c"[..]));
+        let packets = split_write_cmd(&gen_cmd(vec!["TOUCH", "a", "b"])).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].get_array_element(0), Some(&b"TOUCH"[..]));
+        assert_eq!(split_len(vec!["TOUCH", "a"]), Some(1));
+
+        // MSETNX is only sent as a whole when all the keys are in the same slot.
+        let msetnx = vec!["MSETNX", "{user1}a", "1", "{user1}b", "2"];
+        assert_eq!(split_len(msetnx), Some(1));
+        let slot_a = generate_slot(b"a");
+        let other_key = (0..)
+            .map(|i| format!("key{}", i))
+            .find(|key| generate_slot(key.as_bytes()) != slot_a)
+            .unwrap();
+        let msetnx = vec!["MSETNX", "a", "1", other_key.as_str(), "2"];
+        assert_eq!(split_len(msetnx), None);
+    }
+
+    #[test]
+    fn test_merge_replies() {
+        let results = vec![
+            gen_result(Resp::Integer(b"1".to_vec())),
+            gen_result(Resp::Integer(b"0".to_vec())),
+        ];
+        assert_eq!(
+            merge_replies(DataCmdType::DEL, results),
+            Ok(Resp::Integer(b"1".to_vec()))
+        );
+        let results = vec![
+            gen_result(Resp::Integer(b"1".to_vec())),
+            gen_result(Resp::Integer(b"1".to_vec())),
+        ];
+        assert_eq!(
+            merge_replies(DataCmdType::UNLINK, results),
+            Ok(Resp::Integer(b"2".to_vec()))
+        );
+
+        let stats = DoubleWriteStats::default();
+        let ok = Ok(Resp::Simple(b"OK".to_vec()));
+        stats.record(&ok, &ok);
+        stats.record(&ok, &Ok(Resp::Error(b"ERR".to_vec())));
+        stats.record(&ok, &Err(()));
+        assert_eq!(stats.sent.load(Ordering::SeqCst), 3);
+        assert_eq!(stats.matched.load(Ordering::SeqCst), 1);
+        assert_eq!(stats.diverged.load(Ordering::SeqCst), 1);
+        assert_eq!(stats.failed.load(Ordering::SeqCst), 1);
+    }
+}
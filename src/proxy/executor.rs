@@ -1,33 +1,96 @@
-use super::backend::{CmdTask, CmdTaskFactory, ConnFactory};
-use super::cluster::{ClusterMetaError, ClusterTag};
-use super::command::{CmdReplyReceiver, CmdType, DataCmdType, TaskResult};
+use super::acl::{get_cmd_category, get_cmd_keys, AclError, CmdAclChecker};
+use super::backend::{CmdTask, CmdTaskFactory, ConnFactory, ReqTask};
+use super::blocking_cmd::{
+    gen_blocking_round_cmd, gen_blocking_timeout_reply, gen_non_blocking_stream_cmd,
+    get_blocking_timeout_index, get_command_elements, is_blocking_timeout_reply,
+    is_redirection_reply, BlockingTimeout, BLOCKING_CONN_POOL_SIZE, BLOCKING_CONN_TIMEOUT,
+};
+use super::cluster::{ClusterMetaError, ClusterTag, SlotOwner};
+use super::coalescing::{CoalescingResult, GetCoalescer};
+use super::command::{
+    parse_hello_args, CmdReplyReceiver, CmdTimeoutClass, CmdType, Command, CommandError,
+    DataCmdType, TaskResult, HELLO_PROTOCOL_VERSION,
+};
 use super::compress::{CmdCompressor, CompressionError, CompressionStrategyMetaMapConfig};
-use super::manager::{MetaManager, SharedMetaMap};
+use super::double_write::DoubleWriter;
+use super::info::InfoSections;
+use super::manager::{AuthName, BackendTask, MetaManager, SharedMetaMap};
+use super::memory::MemoryTracker;
+use super::middleware::{run_middlewares, CmdMiddleware};
+use super::monitor::{MonitorReceiver, Monitors};
+use super::multi_key::{group_key_indices, reassemble_values, KeyGroup};
+use super::pubsub::{SubscriberHandle, SubscriberReceiver, Subscribers};
+use super::redirection::{
+    parse_redirection, BackendRedirector, Redirection, MAX_BACKEND_REDIRECTIONS,
+};
+use super::replica_ack::ReplicaAckTracker;
+use super::scan::{decode_cursor, next_cursor, MAX_SCAN_NODES};
+use super::script::{
+    eval_key_num_exceeds_args, get_loaded_sha, is_noscript_error, merge_script_exists, ScriptCache,
+};
+use super::sender::CmdTaskSenderWrapper;
 use super::service::ServerProxyConfig;
-use super::session::{CmdCtx, CmdCtxFactory, CmdCtxHandler, CmdReplyFuture};
+use super::session::{CmdCtx, CmdCtxFactory, CmdCtxHandler, CmdReplyFuture, SessionAuth};
+use super::size_limit::{CmdSizeChecker, SizeLimitError};
+use super::slot_hook::SlotOwnershipHook;
 use super::slowlog::{slowlogs_to_resp, SlowRequestLogger};
+use super::stats::ProxyStats;
+use super::tls::TlsIdentityFile;
+use super::transaction::{gen_transaction_cmds, get_exec_reply, TransactionFuture};
+use super::ttl::{CmdTtlChecker, TtlPolicyError};
 use crate::common::cluster::ClusterName;
-use crate::common::config::ClusterConfig;
-use crate::common::proto::ProxyClusterMeta;
+use crate::common::config::{AclCategory, ClusterConfig, UnknownCommandPolicy, WriteAckPolicy};
+use crate::common::proto::{ProxyCapabilities, ProxyClusterMeta, PROXY_FEATURES};
 use crate::common::response;
 use crate::common::track::TrackedFutureRegistry;
 use crate::common::utils::{
-    change_bulk_array_element, generate_slot, same_slot, str_ascii_case_insensitive_eq,
+    change_bulk_array_element, gen_moved, generate_slot, same_slot, str_ascii_case_insensitive_eq,
 };
 use crate::common::version::UNDERMOON_VERSION;
 use crate::migration::manager::SwitchError;
 use crate::migration::task::parse_switch_command;
-use crate::migration::task::MgrSubCmd;
-use crate::protocol::{Array, BulkStr, RedisClientFactory, Resp, RespPacket, RespVec, VFunctor};
+use crate::migration::task::{MgrSubCmd, ScanResponse};
+use crate::protocol::{
+    Array, BinSafeStr, BulkStr, PooledRedisClientFactory, RedisClient, RedisClientError,
+    RedisClientFactory, Resp, RespPacket, RespVec, VFunctor,
+};
 use crate::replication::replicator::ReplicatorMeta;
 use atoi::atoi;
 use btoi::btou;
 use futures::future;
 use futures_timer::Delay;
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::str;
 use std::sync::{self, Arc};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+// The connections for MULTI/EXEC are kept for each backend node.
+const TRANSACTION_CONN_POOL_SIZE: usize = 16;
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Advertised by `UMCTL CAPABILITIES`. Should be updated with the new sub-commands.
+const UMCTL_SUB_COMMANDS: [&str; 18] = [
+    "LISTCLUSTER",
+    "SETCLUSTER",
+    "SETREPL",
+    "INFO",
+    "INFOREPL",
+    "INFOMGR",
+    "MIGRATIONLOG",
+    "INFOSTATS",
+    "INFODOUBLEWRITE",
+    "CAPABILITIES",
+    "PRECHECK",
+    "PRESWITCH",
+    "FINALSWITCH",
+    "SLOWLOG",
+    "DEBUG",
+    "GETEPOCH",
+    "RELOADTLS",
+    "LASTERR",
+];
 
 pub struct SharedForwardHandler<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> {
     handler: sync::Arc<ForwardHandler<F, C>>,
@@ -50,6 +113,7 @@ where
     F: RedisClientFactory,
     C: ConnFactory<Pkt = RespPacket>,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: Arc<ServerProxyConfig>,
         cluster_config: ClusterConfig,
@@ -58,6 +122,8 @@ where
         meta_map: SharedMetaMap<C>,
         conn_factory: Arc<C>,
         future_registry: Arc<TrackedFutureRegistry>,
+        memory_tracker: Arc<MemoryTracker>,
+        stats: Arc<ProxyStats>,
     ) -> Self {
         Self {
             handler: sync::Arc::new(ForwardHandler::new(
@@ -68,9 +134,18 @@ where
                 meta_map,
                 conn_factory,
                 future_registry,
+                memory_tracker,
+                stats,
             )),
         }
     }
+
+    // Used for registering the middlewares before sharing the handler.
+    pub fn from_handler(handler: ForwardHandler<F, C>) -> Self {
+        Self {
+            handler: sync::Arc::new(handler),
+        }
+    }
 }
 
 impl<F, C> CmdCtxHandler for SharedForwardHandler<F, C>
@@ -82,10 +157,54 @@ where
         &self,
         cmd_ctx: CmdCtx,
         reply_receiver: CmdReplyReceiver,
-        session_cluster_name: &sync::RwLock<ClusterName>,
+        session_auth: &sync::RwLock<SessionAuth>,
     ) -> CmdReplyFuture {
         self.handler
-            .handle_cmd_ctx(cmd_ctx, reply_receiver, session_cluster_name)
+            .handle_cmd_ctx(cmd_ctx, reply_receiver, session_auth)
+    }
+
+    fn get_moved_address(&self, cluster_name: &ClusterName) -> Option<String> {
+        self.handler.get_moved_address(cluster_name)
+    }
+
+    fn get_slot_owner(&self, auth: &SessionAuth, slot: usize) -> Result<SlotOwner, String> {
+        self.handler.get_slot_owner(auth, slot)
+    }
+
+    fn get_local_nodes(&self, auth: &SessionAuth) -> Result<Vec<String>, String> {
+        self.handler.get_local_nodes(auth)
+    }
+
+    fn check_session_auth(&self, auth: &SessionAuth) -> Result<(), String> {
+        self.handler.check_session_auth(auth)
+    }
+
+    fn watch_removed_clusters(&self) -> Option<watch::Receiver<Arc<Vec<ClusterName>>>> {
+        self.handler.watch_removed_clusters()
+    }
+
+    fn register_monitor(&self, auth: &SessionAuth) -> Result<MonitorReceiver, String> {
+        self.handler.register_monitor(auth)
+    }
+
+    fn register_subscriber(
+        &self,
+        auth: &SessionAuth,
+    ) -> Result<(SubscriberHandle, SubscriberReceiver), String> {
+        self.handler.register_subscriber(auth)
+    }
+
+    fn check_multi(&self, auth: &SessionAuth) -> Result<(), String> {
+        self.handler.check_multi(auth)
+    }
+
+    fn exec_transaction(
+        &self,
+        auth: &SessionAuth,
+        slot: usize,
+        cmds: Vec<Command>,
+    ) -> TransactionFuture {
+        self.handler.exec_transaction(auth, slot, cmds)
     }
 }
 
@@ -94,7 +213,22 @@ pub struct ForwardHandler<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket
     manager: MetaManager<F, C>,
     slow_request_logger: Arc<SlowRequestLogger>,
     compressor: CmdCompressor<CompressionStrategyMetaMapConfig<C>>,
+    acl_checker: CmdAclChecker<C>,
+    size_checker: CmdSizeChecker<C>,
+    double_writer: DoubleWriter<C>,
+    ttl_checker: CmdTtlChecker<C>,
+    redirector: BackendRedirector<C>,
+    get_coalescer: GetCoalescer,
+    middlewares: Vec<Box<dyn CmdMiddleware>>,
     future_registry: Arc<TrackedFutureRegistry>,
+    memory_tracker: Arc<MemoryTracker>,
+    stats: Arc<ProxyStats>,
+    transaction_client_factory: Arc<PooledRedisClientFactory>,
+    blocking_client_factory: Arc<PooledRedisClientFactory>,
+    script_cache: ScriptCache,
+    replica_ack_tracker: Arc<ReplicaAckTracker>,
+    monitors: Monitors,
+    subscribers: Subscribers,
 }
 
 impl<F, C> ForwardHandler<F, C>
@@ -102,6 +236,7 @@ where
     F: RedisClientFactory,
     C: ConnFactory<Pkt = RespPacket>,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: Arc<ServerProxyConfig>,
         cluster_config: ClusterConfig,
@@ -110,22 +245,63 @@ where
         meta_map: SharedMetaMap<C>,
         conn_factory: Arc<C>,
         future_registry: Arc<TrackedFutureRegistry>,
+        memory_tracker: Arc<MemoryTracker>,
+        stats: Arc<ProxyStats>,
     ) -> Self {
+        let monitors = Monitors::new(config.monitor_buffer_size);
         Self {
             config: config.clone(),
             manager: MetaManager::new(
-                config,
+                config.clone(),
                 cluster_config,
                 client_factory,
-                conn_factory,
+                conn_factory.clone(),
                 meta_map.clone(),
                 future_registry.clone(),
             ),
             slow_request_logger,
-            compressor: CmdCompressor::new(CompressionStrategyMetaMapConfig::new(meta_map)),
+            compressor: CmdCompressor::new(CompressionStrategyMetaMapConfig::new(meta_map.clone())),
+            acl_checker: CmdAclChecker::new(meta_map.clone()),
+            size_checker: CmdSizeChecker::new(meta_map.clone()),
+            double_writer: DoubleWriter::new(meta_map.clone()),
+            ttl_checker: CmdTtlChecker::new(meta_map.clone()),
+            redirector: BackendRedirector::new(
+                config,
+                meta_map,
+                conn_factory,
+                future_registry.clone(),
+            ),
+            get_coalescer: GetCoalescer::default(),
+            middlewares: vec![],
             future_registry,
+            memory_tracker,
+            stats,
+            transaction_client_factory: Arc::new(PooledRedisClientFactory::new(
+                TRANSACTION_CONN_POOL_SIZE,
+                TRANSACTION_TIMEOUT,
+            )),
+            blocking_client_factory: Arc::new(PooledRedisClientFactory::new(
+                BLOCKING_CONN_POOL_SIZE,
+                BLOCKING_CONN_TIMEOUT,
+            )),
+            script_cache: ScriptCache::default(),
+            replica_ack_tracker: Arc::new(ReplicaAckTracker::default()),
+            monitors,
+            subscribers: Subscribers::default(),
         }
     }
+
+    pub fn register_middleware(&mut self, middleware: Box<dyn CmdMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    pub fn register_slot_hook(&mut self, hook: Box<dyn SlotOwnershipHook>) {
+        self.manager.register_slot_hook(hook);
+    }
+
+    pub fn register_sender_wrapper(&mut self, wrapper: Box<dyn CmdTaskSenderWrapper<BackendTask>>) {
+        self.manager.register_sender_wrapper(wrapper);
+    }
 }
 
 impl<F, C> ForwardHandler<F, C>
@@ -133,37 +309,117 @@ where
     F: RedisClientFactory,
     C: ConnFactory<Pkt = RespPacket>,
 {
-    fn handle_auth(&self, mut cmd_ctx: CmdCtx, session_cluster_name: &sync::RwLock<ClusterName>) {
+    // AUTH <cluster_name> [password]
+    fn handle_auth(&self, mut cmd_ctx: CmdCtx, session_auth: &sync::RwLock<SessionAuth>) {
         let key = cmd_ctx.get_key();
-        let cluster = match key {
-            None => {
-                return cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    String::from("Missing cluster name").into_bytes(),
-                )))
+        let password = cmd_ctx
+            .get_cmd()
+            .get_command_element(2)
+            .map(|password| password.to_vec());
+        let current_cluster = cmd_ctx.get_cluster_name().clone();
+        match self.auth_session(&current_cluster, key, password.as_deref(), session_auth) {
+            Ok(cluster_name) => {
+                cmd_ctx.set_cluster_name(cluster_name);
+                cmd_ctx.set_resp_result(Ok(Resp::Simple(String::from("OK").into_bytes())));
             }
-            Some(cluster_name) => match str::from_utf8(&cluster_name) {
-                Ok(cluster) => cluster.to_string(),
-                Err(_) => {
-                    return cmd_ctx.set_resp_result(Ok(Resp::Error(
-                        String::from("Invalid cluster name").into_bytes(),
-                    )))
-                }
+            Err(err) => cmd_ctx.set_resp_result(Ok(Resp::Error(err.into_bytes()))),
+        }
+    }
+
+    // The name could be a cluster name or a user name of a cluster.
+    // A single argument which is not a cluster name is the password of the current cluster
+    // for the legacy clients.
+    fn auth_session(
+        &self,
+        current_cluster: &ClusterName,
+        name: Option<&[u8]>,
+        password: Option<&[u8]>,
+        session_auth: &sync::RwLock<SessionAuth>,
+    ) -> Result<ClusterName, String> {
+        let name = match name {
+            None => return Err(String::from("Missing cluster name")),
+            Some(name) => match str::from_utf8(name) {
+                Ok(name) => name.to_string(),
+                Err(_) => return Err(String::from("Invalid cluster name")),
             },
         };
-        let cluster_name = match ClusterName::try_from(cluster.as_str()) {
-            Ok(cluster_name) => cluster_name,
-            _err => {
-                return cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    String::from("Cluster name is too long").into_bytes(),
-                )))
-            }
+        let password = match password {
+            None => None,
+            Some(password) => match str::from_utf8(password) {
+                Ok(password) => Some(password.to_string()),
+                Err(_) => return Err(response::ERR_WRONGPASS.to_string()),
+            },
         };
 
-        *session_cluster_name
+        let (cluster_name, user, password) =
+            match self
+                .manager
+                .resolve_auth_name(current_cluster, &name, password.is_some())
+            {
+                AuthName::User(cluster_name) => (cluster_name, Some(name), password),
+                AuthName::Password(cluster_name) => (cluster_name, None, Some(name)),
+                AuthName::Cluster => {
+                    let cluster_name = match ClusterName::try_from(name.as_str()) {
+                        Ok(cluster_name) => cluster_name,
+                        _err => return Err(String::from("Cluster name is too long")),
+                    };
+                    // The session will be bound to the new name if the cluster is being renamed.
+                    let cluster_name = self
+                        .manager
+                        .get_selected_cluster(&cluster_name, false)
+                        .unwrap_or(cluster_name);
+                    (cluster_name, None, password)
+                }
+            };
+        if !self
+            .manager
+            .check_auth(&cluster_name, user.as_deref(), password.as_deref())
+        {
+            return Err(response::ERR_WRONGPASS.to_string());
+        }
+
+        session_auth
             .write()
-            .expect("ForwardHandler::handle_auth") = cluster_name.clone();
-        cmd_ctx.set_cluster_name(cluster_name);
-        cmd_ctx.set_resp_result(Ok(Resp::Simple(String::from("OK").into_bytes())));
+            .expect("ForwardHandler::auth_session")
+            .set(cluster_name.clone(), user, password);
+        Ok(cluster_name)
+    }
+
+    // Only RESP2 is supported for all the clusters.
+    // Like Redis, `HELLO 3` fails with NOPROTO before AUTH is processed
+    // so that the clients could fall back to RESP2 and authenticate again.
+    fn handle_hello(&self, mut cmd_ctx: CmdCtx, session_auth: &sync::RwLock<SessionAuth>) {
+        let args = match parse_hello_args(cmd_ctx.get_cmd()) {
+            Ok(args) => args,
+            Err(err) => return cmd_ctx.set_resp_result(Ok(Resp::Error(err.into_bytes()))),
+        };
+        if let Some((name, password)) = args.auth {
+            let current_cluster = cmd_ctx.get_cluster_name().clone();
+            match self.auth_session(&current_cluster, Some(&name), Some(&password), session_auth) {
+                Ok(cluster_name) => cmd_ctx.set_cluster_name(cluster_name),
+                Err(err) => return cmd_ctx.set_resp_result(Ok(Resp::Error(err.into_bytes()))),
+            }
+        }
+
+        let bulk = |s: &str| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec()));
+        let session_id = cmd_ctx.get_session_id().to_string().into_bytes();
+        let reply = vec![
+            bulk("server"),
+            bulk("undermoon"),
+            bulk("version"),
+            bulk(UNDERMOON_VERSION),
+            bulk("proto"),
+            Resp::Integer(HELLO_PROTOCOL_VERSION.to_string().into_bytes()),
+            bulk("id"),
+            Resp::Integer(session_id),
+            bulk("mode"),
+            bulk("cluster"),
+            bulk("role"),
+            bulk("master"),
+            bulk("modules"),
+            Resp::Arr(Array::Arr(vec![])),
+        ];
+        cmd_ctx.set_resp_result(Ok(Resp::Arr(Array::Arr(reply))));
     }
 
     fn handle_cluster(&self, cmd_ctx: CmdCtx) {
@@ -185,6 +441,14 @@ where
                 Ok(resp) => cmd_ctx.set_resp_result(Ok(resp)),
                 Err(s) => cmd_ctx.set_resp_result(Ok(Resp::Error(s.into_bytes()))),
             }
+        } else if str_ascii_case_insensitive_eq(&sub_cmd, "shards") {
+            let cluster_shards = self
+                .manager
+                .gen_cluster_shards(cmd_ctx.get_cluster_name().clone());
+            match cluster_shards {
+                Ok(resp) => cmd_ctx.set_resp_result(Ok(resp)),
+                Err(s) => cmd_ctx.set_resp_result(Ok(Resp::Error(s.into_bytes()))),
+            }
         } else if str_ascii_case_insensitive_eq(&sub_cmd, "keyslot") {
             match cmd_ctx.get_cmd().get_command_element(2) {
                 Some(key) => {
@@ -192,13 +456,14 @@ where
                     cmd_ctx.set_resp_result(Ok(Resp::Integer(slot.to_string().into_bytes())));
                 }
                 None => {
-                    cmd_ctx
-                        .set_resp_result(Ok(Resp::Error(String::from("Missing key").into_bytes())));
+                    cmd_ctx.set_resp_result(Ok(Resp::Error(
+                        response::ERR_MISSING_KEY.to_string().into_bytes(),
+                    )));
                 }
             }
         } else {
             cmd_ctx.set_resp_result(Ok(Resp::Error(
-                String::from("Unsupported sub command").into_bytes(),
+                format!("{} unsupported sub command", response::UM_UNSUPPORTED).into_bytes(),
             )));
         }
     }
@@ -207,7 +472,7 @@ where
         let sub_cmd = match cmd_ctx.get_cmd().get_command_element(index) {
             None => {
                 cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    String::from("Missing sub command").into_bytes(),
+                    format!("{} missing sub command", response::UM_INVALID).into_bytes(),
                 )));
                 return None;
             }
@@ -215,7 +480,7 @@ where
                 Ok(sub_cmd) => sub_cmd.to_string(),
                 Err(_) => {
                     cmd_ctx.set_resp_result(Ok(Resp::Error(
-                        String::from("Invalid sub command").into_bytes(),
+                        format!("{} invalid sub command", response::UM_INVALID).into_bytes(),
                     )));
                     return None;
                 }
@@ -250,6 +515,26 @@ where
             self.handle_umctl_info_repl(cmd_ctx);
         } else if sub_cmd.eq("INFOMGR") {
             self.handle_umctl_info_migration(cmd_ctx);
+        } else if sub_cmd.eq("MIGRATIONLOG") {
+            self.handle_umctl_migration_log(cmd_ctx);
+        } else if sub_cmd.eq("CAPABILITIES") {
+            let capabilities = ProxyCapabilities {
+                version: UNDERMOON_VERSION.to_string(),
+                commands: UMCTL_SUB_COMMANDS.iter().map(|s| s.to_string()).collect(),
+                features: PROXY_FEATURES.iter().map(|s| s.to_string()).collect(),
+            };
+            cmd_ctx.set_resp_result(Ok(capabilities.to_resp()));
+        } else if sub_cmd.eq("INFOSTATS") {
+            let resp = self.stats.info(
+                &self.memory_tracker,
+                self.manager.get_migration_progress(),
+                self.manager.get_max_replication_lag(),
+                self.manager.get_meta_lock_stats(),
+            );
+            cmd_ctx.set_resp_result(Ok(resp));
+        } else if sub_cmd.eq("INFODOUBLEWRITE") {
+            let resp = self.double_writer.get_stats().info();
+            cmd_ctx.set_resp_result(Ok(resp));
         } else if sub_cmd.eq(MgrSubCmd::PreCheck.as_str()) {
             self.handle_umctl_mgr_cmd(cmd_ctx, MgrSubCmd::PreCheck);
         } else if sub_cmd.eq(MgrSubCmd::PreSwitch.as_str()) {
@@ -262,6 +547,8 @@ where
             self.handle_umctl_debug(cmd_ctx);
         } else if sub_cmd.eq("GETEPOCH") {
             self.handle_umctl_get_epoch(cmd_ctx);
+        } else if sub_cmd.eq("RELOADTLS") {
+            self.handle_umctl_reload_tls(cmd_ctx);
         } else {
             cmd_ctx.set_resp_result(Ok(Resp::Error(
                 String::from("Invalid sub command").into_bytes(),
@@ -270,29 +557,44 @@ where
     }
 
     fn handle_umctl_set_cluster(&self, cmd_ctx: CmdCtx) {
-        let (cluster_meta, extended_res) =
+        let (mut cluster_meta, extended_res) =
             match ProxyClusterMeta::from_resp(&cmd_ctx.get_cmd().get_resp_slice()) {
                 Ok(r) => r,
-                Err(_) => {
+                Err(err) => {
+                    error!("invalid UMCTL SETCLUSTER: {}", err);
                     cmd_ctx.set_resp_result(Ok(Resp::Error(
-                        String::from("Invalid arguments").into_bytes(),
+                        format!("Invalid arguments: {}", err).into_bytes(),
                     )));
                     return;
                 }
             };
+        if let Err(err) = cluster_meta.normalize() {
+            error!("reject UMCTL SETCLUSTER: {}", err);
+            cmd_ctx.set_resp_result(Ok(Resp::Error(err.to_string().into_bytes())));
+            return;
+        }
 
+        let local_masters: HashSet<String> = cluster_meta
+            .get_local()
+            .get_map()
+            .values()
+            .flat_map(|nodes| nodes.keys().cloned())
+            .collect();
         match self.manager.set_meta(cluster_meta) {
-            Ok(()) => match extended_res {
-                Ok(()) => {
-                    debug!("Successfully update local meta data");
-                    cmd_ctx.set_resp_result(Ok(Resp::Simple("OK".to_string().into_bytes())));
-                }
-                Err(_) => {
-                    cmd_ctx.set_resp_result(Ok(Resp::Simple(
-                        "WARNING: ignored invalid config".to_string().into_bytes(),
-                    )));
+            Ok(()) => {
+                self.replica_ack_tracker.retain_masters(&local_masters);
+                match extended_res {
+                    Ok(()) => {
+                        debug!("Successfully update local meta data");
+                        cmd_ctx.set_resp_result(Ok(Resp::Simple("OK".to_string().into_bytes())));
+                    }
+                    Err(_) => {
+                        cmd_ctx.set_resp_result(Ok(Resp::Simple(
+                            "WARNING: ignored invalid config".to_string().into_bytes(),
+                        )));
+                    }
                 }
-            },
+            }
             Err(err) => match err {
                 ClusterMetaError::OldEpoch => cmd_ctx.set_resp_result(Ok(Resp::Error(
                     response::OLD_EPOCH_REPLY.to_string().into_bytes(),
@@ -307,9 +609,10 @@ where
     fn handle_umctl_setrepl(&self, cmd_ctx: CmdCtx) {
         let meta = match ReplicatorMeta::from_resp(&cmd_ctx.get_cmd().get_resp_slice()) {
             Ok(m) => m,
-            Err(_) => {
+            Err(err) => {
+                error!("invalid UMCTL SETREPL: {}", err);
                 cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    String::from("Invalid arguments").into_bytes(),
+                    format!("Invalid arguments: {}", err).into_bytes(),
                 )));
                 return;
             }
@@ -378,6 +681,18 @@ where
         cmd_ctx.set_resp_result(Ok(Resp::Arr(Array::Arr(packet))))
     }
 
+    // Each element is the events of a task in json.
+    fn handle_umctl_migration_log(&self, cmd_ctx: CmdCtx) {
+        let mut packet = vec![];
+        for task_events in self.manager.get_migration_events().into_iter() {
+            match serde_json::to_string(&task_events) {
+                Ok(s) => packet.push(Resp::Bulk(BulkStr::Str(s.into_bytes()))),
+                Err(err) => error!("failed to encode migration events: {:?}", err),
+            }
+        }
+        cmd_ctx.set_resp_result(Ok(Resp::Arr(Array::Arr(packet))))
+    }
+
     fn handle_umctl_slowlog(&self, cmd_ctx: CmdCtx) {
         let (cmd_ctx, sub_cmd) = match Self::get_sub_command(cmd_ctx, 2) {
             Some((cmd_ctx, sub_cmd)) => (cmd_ctx, sub_cmd),
@@ -434,6 +749,54 @@ where
         cmd_ctx.set_resp_result(Ok(Resp::Integer(epoch.to_string().into_bytes())))
     }
 
+    // UMCTL RELOADTLS [pkcs12_file password [version]]
+    // Without arguments it reloads the current file.
+    // The version is sent by the coordinator so that the same config is only loaded once.
+    fn handle_umctl_reload_tls(&self, cmd_ctx: CmdCtx) {
+        let cmd = cmd_ctx.get_cmd();
+        let args = (
+            cmd.get_command_element(2),
+            cmd.get_command_element(3),
+            cmd.get_command_element(4),
+        );
+        let invalid_args = || Resp::Error(response::ERR_INVALID_ARGUMENTS.to_string().into_bytes());
+        let res = match args {
+            (None, None, None) => self.config.tls.reload().map(|()| true),
+            (Some(file), Some(password), version) => {
+                let identity_file = match (str::from_utf8(file), str::from_utf8(password)) {
+                    (Ok(file), Ok(password)) => TlsIdentityFile {
+                        pkcs12_file: file.to_string(),
+                        password: password.to_string(),
+                    },
+                    _ => return cmd_ctx.set_resp_result(Ok(invalid_args())),
+                };
+                let version = match version.map(btou::<u64>) {
+                    None => 0,
+                    Some(Ok(version)) => version,
+                    Some(Err(_)) => return cmd_ctx.set_resp_result(Ok(invalid_args())),
+                };
+                self.config.tls.load_version(identity_file, version)
+            }
+            _ => return cmd_ctx.set_resp_result(Ok(invalid_args())),
+        };
+        match res {
+            Ok(loaded) => {
+                if loaded {
+                    info!("TLS config reloaded");
+                }
+                cmd_ctx.set_resp_result(Ok(Resp::Simple(
+                    response::OK_REPLY.to_string().into_bytes(),
+                )))
+            }
+            Err(err) => {
+                error!("failed to reload TLS config: {:?}", err);
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    format!("{}: {}", response::ERR_RELOAD_TLS, err).into_bytes(),
+                )))
+            }
+        }
+    }
+
     fn handle_config(&self, cmd_ctx: CmdCtx) {
         let (cmd_ctx, sub_cmd) = match Self::get_sub_command(cmd_ctx, 1) {
             Some((cmd_ctx, sub_cmd)) => (cmd_ctx, sub_cmd.to_uppercase()),
@@ -449,7 +812,8 @@ where
                 Ok(value) => value,
                 Err(_) => {
                     cmd_ctx.set_resp_result(Ok(Resp::Error(
-                        format!("config field {} not found", field).into_bytes(),
+                        format!("{} config field {} not found", response::UM_INVALID, field)
+                            .into_bytes(),
                     )));
                     return;
                 }
@@ -468,18 +832,22 @@ where
                 Ok(()) => {
                     cmd_ctx.set_resp_result(Ok(Resp::Simple(String::from("OK").into_bytes())))
                 }
-                Err(err) => {
-                    cmd_ctx.set_resp_result(Ok(Resp::Error(format!("{:?}", err).into_bytes())))
-                }
+                Err(err) => cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    format!("{} {:?}", response::UM_INVALID, err).into_bytes(),
+                ))),
             }
         } else {
             cmd_ctx.set_resp_result(Ok(Resp::Error(
-                "invalid config sub-command".to_string().into_bytes(),
+                format!("{} invalid config sub-command", response::UM_INVALID).into_bytes(),
             )))
         }
     }
 
     fn handle_data_cmd(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> CmdReplyFuture {
+        if let Some(slot) = cmd_ctx.get_slot() {
+            let is_write = get_cmd_category(cmd_ctx.get_cmd()) == Some(AclCategory::Write);
+            self.stats.record_slot_command(slot, is_write);
+        }
         match cmd_ctx.get_data_cmd_type() {
             DataCmdType::MGET => {
                 CmdReplyFuture::Right(Box::pin(self.handle_mget(cmd_ctx, reply_receiver)))
@@ -501,11 +869,50 @@ where
                     "EXISTS",
                 )))
             }
-            DataCmdType::BLPOP | DataCmdType::BRPOP | DataCmdType::BRPOPLPUSH => {
+            DataCmdType::UNLINK if cmd_ctx.get_cmd().get_command_element(2).is_some() => {
+                CmdReplyFuture::Right(Box::pin(self.handle_multi_int_cmd(
+                    cmd_ctx,
+                    reply_receiver,
+                    "UNLINK",
+                )))
+            }
+            DataCmdType::TOUCH if cmd_ctx.get_cmd().get_command_element(2).is_some() => {
+                CmdReplyFuture::Right(Box::pin(self.handle_multi_int_cmd(
+                    cmd_ctx,
+                    reply_receiver,
+                    "TOUCH",
+                )))
+            }
+            DataCmdType::BLPOP
+            | DataCmdType::BRPOP
+            | DataCmdType::BRPOPLPUSH
+            | DataCmdType::BLMOVE
+            | DataCmdType::BZPOPMIN
+            | DataCmdType::BZPOPMAX => CmdReplyFuture::Right(Box::pin(
+                self.handle_list_blocking_commands(cmd_ctx, reply_receiver),
+            )),
+            DataCmdType::XREAD | DataCmdType::XREADGROUP
+                if get_blocking_timeout_index(cmd_ctx.get_cmd()).is_some() =>
+            {
                 CmdReplyFuture::Right(Box::pin(
-                    self.handle_list_blocking_commands(cmd_ctx, reply_receiver),
+                    self.handle_stream_blocking_commands(cmd_ctx, reply_receiver),
                 ))
             }
+            DataCmdType::EVAL | DataCmdType::EVALSHA => {
+                CmdReplyFuture::Right(Box::pin(self.handle_eval(cmd_ctx, reply_receiver)))
+            }
+            DataCmdType::SCRIPT => {
+                CmdReplyFuture::Right(Box::pin(self.handle_script(cmd_ctx, reply_receiver)))
+            }
+            DataCmdType::SCAN => {
+                CmdReplyFuture::Right(Box::pin(self.handle_scan(cmd_ctx, reply_receiver)))
+            }
+            DataCmdType::GET if self.config.request_coalescing => {
+                CmdReplyFuture::Right(Box::pin(self.handle_coalesced_get(cmd_ctx, reply_receiver)))
+            }
+            _ if self.config.backend_redirection => CmdReplyFuture::Right(Box::pin(
+                self.handle_backend_redirection(cmd_ctx, reply_receiver),
+            )),
             _ => {
                 self.handle_single_key_data_cmd(cmd_ctx);
                 CmdReplyFuture::Left(reply_receiver)
@@ -513,170 +920,506 @@ where
         }
     }
 
-    async fn handle_mget(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
-        let arg_len = cmd_ctx.get_cmd().get_command_len().unwrap_or(0);
+    async fn handle_coalesced_get(
+        &self,
+        cmd_ctx: CmdCtx,
+        reply_receiver: CmdReplyReceiver,
+    ) -> TaskResult {
+        match self.get_coalescer.coalesce(cmd_ctx) {
+            CoalescingResult::Waiting => reply_receiver.await,
+            CoalescingResult::Bypass(cmd_ctx) => {
+                self.send_single_key_data_cmd(cmd_ctx, reply_receiver).await
+            }
+            CoalescingResult::Leader(cmd_ctx, guard) => {
+                let res = self.send_single_key_data_cmd(cmd_ctx, reply_receiver).await;
+                guard.reply(
+                    res.as_ref()
+                        .map(|reply| reply.get_packet().to_resp_vec())
+                        .map_err(|err| err.clone()),
+                );
+                res
+            }
+        }
+    }
 
-        if !self.config.active_redirection {
-            let in_same_slot =
-                same_slot((1..arg_len).filter_map(|i| cmd_ctx.get_cmd().get_command_element(i)));
-            if !in_same_slot {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
-                )));
+    async fn send_single_key_data_cmd(
+        &self,
+        cmd_ctx: CmdCtx,
+        reply_receiver: CmdReplyReceiver,
+    ) -> TaskResult {
+        if self.config.backend_redirection {
+            return self
+                .handle_backend_redirection(cmd_ctx, reply_receiver)
+                .await;
+        }
+        self.handle_single_key_data_cmd(cmd_ctx);
+        reply_receiver.await
+    }
+
+    // Follow the MOVED and ASK replied by the backends in cluster mode.
+    async fn handle_backend_redirection(
+        &self,
+        cmd_ctx: CmdCtx,
+        reply_receiver: CmdReplyReceiver,
+    ) -> TaskResult {
+        let cluster_name = cmd_ctx.get_cluster();
+        let backend = cmd_ctx.get_slot().and_then(|slot| {
+            self.manager
+                .get_local_backend(&cluster_name, slot)
+                .map(|backend| (slot, backend))
+        });
+        let (slot, backend) = match backend {
+            Some(slot_backend) => slot_backend,
+            // The MOVED of remote slots are generated by the proxy itself.
+            None => {
+                self.handle_single_key_data_cmd(cmd_ctx);
                 return reply_receiver.await;
             }
+        };
+
+        let epoch = self.manager.get_epoch();
+        let context = cmd_ctx.get_context();
+        let cmd_ctx = match self.compress_cmd_ctx(cmd_ctx) {
+            Some(cmd_ctx) => cmd_ctx,
+            None => return reply_receiver.await,
+        };
+        match self.redirector.get_cached_node(epoch, &backend, slot) {
+            Some(address) => self.redirector.send(ReqTask::Simple(cmd_ctx), address),
+            None => self.manager.send(cmd_ctx),
         }
 
-        let factory = CmdCtxFactory::default();
-        let mut futs = vec![];
-        for i in 1.. {
-            let key = match cmd_ctx.get_cmd().get_command_element(i) {
-                Some(key) => key,
-                None => break,
+        let factory = CmdCtxFactory;
+        let mut reply_receiver = reply_receiver;
+        for _ in 0..MAX_BACKEND_REDIRECTIONS {
+            let reply = reply_receiver.await?;
+            let redirection = match parse_redirection(&reply.get_packet().to_resp_slice()) {
+                Some(redirection) => redirection,
+                None => return Ok(reply),
             };
-            let resp = Resp::Arr(Array::Arr(vec![
-                Resp::Bulk(BulkStr::Str(b"GET".to_vec())),
-                Resp::Bulk(BulkStr::Str(key.to_vec())),
-            ]));
-            let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), resp);
-            futs.push(fut);
-            self.handle_single_key_data_cmd(sub_cmd_ctx);
+
+            let (request, _, _) = reply.into_inner();
+            let (cmd_ctx, receiver) = factory.create_with_packet(context.clone(), request);
+            reply_receiver = receiver;
+            match redirection {
+                Redirection::Moved { slot, address } => {
+                    self.redirector
+                        .set_cached_node(epoch, backend.clone(), slot, address.clone());
+                    self.redirector.send(ReqTask::Simple(cmd_ctx), address);
+                }
+                Redirection::Ask { address, .. } => {
+                    let asking = Box::new(RespPacket::from_resp_vec(Resp::Arr(Array::Arr(vec![
+                        Resp::Bulk(BulkStr::Str(b"ASKING".to_vec())),
+                    ]))));
+                    let (asking_ctx, asking_receiver) =
+                        factory.create_with_packet(context.clone(), asking);
+                    self.redirector
+                        .send(ReqTask::Multi(vec![asking_ctx, cmd_ctx]), address);
+                    if let Err(err) = asking_receiver.await {
+                        warn!("failed to send ASKING: {:?}", err);
+                    }
+                }
+            }
         }
+        reply_receiver.await
+    }
 
-        if futs.is_empty() {
+    // EVALSHA is retried as EVAL with the script cached from SCRIPT LOAD on NOSCRIPT,
+    // which also loads the script into the backend.
+    async fn handle_eval(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
+        if eval_key_num_exceeds_args(cmd_ctx.get_cmd()) {
             cmd_ctx.set_resp_result(Ok(Resp::Error(
-                b"ERR wrong number of arguments for 'mget' command".to_vec(),
+                response::ERR_EVAL_KEY_NUM.to_string().into_bytes(),
             )));
             return reply_receiver.await;
         }
-
-        let mut values = vec![];
-        let res = future::join_all(futs).await;
-        for sub_result in res.into_iter() {
-            let reply = match sub_result {
-                Ok(reply) => reply,
-                Err(err) => return Err(err),
-            };
-            if let Resp::Error(err) = &reply {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(err.clone())));
+        match get_cmd_keys(cmd_ctx.get_cmd()) {
+            Ok(keys) if !keys.is_empty() && !same_slot(keys.iter().cloned()) => {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
+                )));
                 return reply_receiver.await;
             }
-            values.push(reply);
+            _ => (),
         }
 
-        let resp = Resp::Arr(Array::Arr(values));
+        let script = match cmd_ctx.get_data_cmd_type() {
+            DataCmdType::EVALSHA => cmd_ctx
+                .get_cmd()
+                .get_command_element(1)
+                .and_then(|sha| self.script_cache.get(&cmd_ctx.get_cluster(), sha)),
+            _ => None,
+        };
+        let script = match script {
+            Some(script) => script,
+            None => return self.send_eval(cmd_ctx, reply_receiver).await,
+        };
+
+        let factory = CmdCtxFactory;
+        let packet = Box::new(cmd_ctx.get_cmd().get_packet());
+        let (sub_cmd_ctx, sub_reply_receiver) =
+            factory.create_with_packet(cmd_ctx.get_context(), packet);
+        let mut resp = self
+            .send_eval(sub_cmd_ctx, sub_reply_receiver)
+            .await?
+            .into_resp_vec();
+        if is_noscript_error(&resp) {
+            let mut eval = cmd_ctx.get_cmd().get_packet().into_resp_vec();
+            change_bulk_array_element(&mut eval, 0, b"EVAL".to_vec());
+            change_bulk_array_element(&mut eval, 1, script);
+            let packet = Box::new(RespPacket::from_resp_vec(eval));
+            let (sub_cmd_ctx, sub_reply_receiver) =
+                factory.create_with_packet(cmd_ctx.get_context(), packet);
+            resp = self
+                .send_eval(sub_cmd_ctx, sub_reply_receiver)
+                .await?
+                .into_resp_vec();
+        }
         cmd_ctx.set_resp_result(Ok(resp));
         reply_receiver.await
     }
 
-    async fn handle_mset(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
-        let arg_len = cmd_ctx.get_cmd().get_command_len().unwrap_or(0);
-
-        if !self.config.active_redirection {
-            let in_same_slot = same_slot(
-                (0..(arg_len / 2)).filter_map(|i| cmd_ctx.get_cmd().get_command_element(2 * i + 1)),
-            );
-            if !in_same_slot {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
-                )));
+    // The scripts without keys could be run on any local backend.
+    async fn send_eval(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
+        let key_num = cmd_ctx
+            .get_cmd()
+            .get_command_element(2)
+            .and_then(|n| btou::<usize>(n).ok());
+        if key_num == Some(0) {
+            let address = self
+                .manager
+                .get_local_nodes(&cmd_ctx.get_cluster())
+                .filter(|nodes| !nodes.is_empty())
+                .map(|nodes| nodes[cmd_ctx.get_session_id() % nodes.len()].clone());
+            if let Some(address) = address {
+                self.redirector.send(ReqTask::Simple(cmd_ctx), address);
                 return reply_receiver.await;
             }
         }
+        self.send_single_key_data_cmd(cmd_ctx, reply_receiver).await
+    }
 
-        let factory = CmdCtxFactory::default();
-        let mut futs = vec![];
-        for i in 0.. {
-            let key = match cmd_ctx.get_cmd().get_command_element(2 * i + 1) {
-                Some(key) => key,
-                None => break,
-            };
-            let value = match cmd_ctx.get_cmd().get_command_element(2 * i + 2) {
-                Some(value) => value,
-                None => {
-                    // The existing sub commands will be set with Canceled.
-                    cmd_ctx.set_resp_result(Ok(Resp::Error(
-                        b"ERR wrong number of arguments for 'mset' command".to_vec(),
-                    )));
-                    return reply_receiver.await;
-                }
-            };
-            let resp = Resp::Arr(Array::Arr(vec![
-                Resp::Bulk(BulkStr::Str(b"SET".to_vec())),
-                Resp::Bulk(BulkStr::Str(key.to_vec())),
-                Resp::Bulk(BulkStr::Str(value.to_vec())),
-            ]));
-            let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), resp);
-            futs.push(fut);
-            self.handle_single_key_data_cmd(sub_cmd_ctx);
+    // SCRIPT LOAD, SCRIPT FLUSH and SCRIPT EXISTS are sent to all the local backends.
+    async fn handle_script(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
+        let sub_cmd = cmd_ctx
+            .get_cmd()
+            .get_command_element(1)
+            .map(|s| String::from_utf8_lossy(s).to_uppercase())
+            .unwrap_or_default();
+        if !["LOAD", "FLUSH", "EXISTS"].contains(&sub_cmd.as_str()) {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                response::CMD_NOT_SUPPORTED.to_string().into_bytes(),
+            )));
+            return reply_receiver.await;
         }
 
-        if futs.is_empty() {
+        let cluster_name = cmd_ctx.get_cluster();
+        let nodes = self
+            .manager
+            .get_local_nodes(&cluster_name)
+            .unwrap_or_default();
+        if nodes.is_empty() {
             cmd_ctx.set_resp_result(Ok(Resp::Error(
-                b"ERR wrong number of arguments for 'mset' command".to_vec(),
+                format!("{}: {}", response::ERR_CLUSTER_NOT_FOUND, cluster_name).into_bytes(),
             )));
             return reply_receiver.await;
         }
 
-        let res = future::join_all(futs).await;
-        for sub_result in res.into_iter() {
-            let reply = match sub_result {
-                Ok(reply) => reply,
-                Err(err) => return Err(err),
-            };
-            if let Resp::Error(err) = reply {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(err)));
+        let factory = CmdCtxFactory;
+        let request = cmd_ctx.get_cmd().get_packet().into_resp_vec();
+        let mut futs = vec![];
+        for node in nodes.into_iter() {
+            let (sub_cmd_ctx, fut) =
+                factory.create_with_ctx(cmd_ctx.get_context(), request.clone());
+            futs.push(fut);
+            self.redirector.send(ReqTask::Simple(sub_cmd_ctx), node);
+        }
+
+        let mut replies = vec![];
+        for sub_result in future::join_all(futs).await.into_iter() {
+            let reply = sub_result?;
+            if let Resp::Error(err) = &reply {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(err.clone())));
                 return reply_receiver.await;
             }
+            replies.push(reply);
         }
 
-        let resp = Resp::Simple(response::OK_REPLY.to_string().into_bytes());
+        let resp = match sub_cmd.as_str() {
+            "LOAD" => {
+                let sha = replies.first().and_then(get_loaded_sha);
+                let script = cmd_ctx.get_cmd().get_command_element(2);
+                if let (Some(sha), Some(script)) = (sha, script) {
+                    if !self.script_cache.add(&cluster_name, sha, script.to_vec()) {
+                        warn!("the script cache of {} is full", cluster_name);
+                    }
+                }
+                replies.swap_remove(0)
+            }
+            "FLUSH" => {
+                self.script_cache.flush(&cluster_name);
+                replies.swap_remove(0)
+            }
+            _ => merge_script_exists(replies)
+                .unwrap_or_else(|| Resp::Error(response::ERR_INTERNAL.to_string().into_bytes())),
+        };
         cmd_ctx.set_resp_result(Ok(resp));
         reply_receiver.await
     }
 
-    // DEL and EXISTS
-    async fn handle_multi_int_cmd(
-        &self,
-        cmd_ctx: CmdCtx,
-        reply_receiver: CmdReplyReceiver,
-        cmd_name: &'static str,
-    ) -> TaskResult {
-        let arg_len = cmd_ctx.get_cmd().get_command_len().unwrap_or(0);
-
-        if !self.config.active_redirection {
-            let in_same_slot =
-                same_slot((1..arg_len).filter_map(|i| cmd_ctx.get_cmd().get_command_element(i)));
-            if !in_same_slot {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
-                )));
+    // SCAN iterates all the local backends behind a single cursor.
+    async fn handle_scan(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
+        let cluster_name = cmd_ctx.get_cluster();
+        let mut nodes = self
+            .manager
+            .get_local_nodes(&cluster_name)
+            .unwrap_or_default();
+        nodes.sort();
+        if nodes.is_empty() {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                format!("{}: {}", response::ERR_CLUSTER_NOT_FOUND, cluster_name).into_bytes(),
+            )));
+            return reply_receiver.await;
+        }
+        if nodes.len() > MAX_SCAN_NODES {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                format!("{} too many backends to scan", response::UM_UNSUPPORTED).into_bytes(),
+            )));
+            return reply_receiver.await;
+        }
+
+        let cursor = match cmd_ctx
+            .get_cmd()
+            .get_command_element(1)
+            .and_then(|cursor| btou::<u64>(cursor).ok())
+        {
+            Some(cursor) => cursor,
+            None => {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(b"ERR invalid cursor".to_vec())));
+                return reply_receiver.await;
+            }
+        };
+        let cursor = match decode_cursor(&nodes, cursor) {
+            Some(cursor) => cursor,
+            None => {
+                cmd_ctx.set_resp_result(Ok(gen_scan_reply(0, vec![])));
                 return reply_receiver.await;
             }
+        };
+
+        let mut request = cmd_ctx.get_cmd().get_packet().into_resp_vec();
+        change_bulk_array_element(
+            &mut request,
+            1,
+            cursor.backend_cursor.to_string().into_bytes(),
+        );
+        let factory = CmdCtxFactory;
+        let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), request);
+        let node = nodes[cursor.node_index].clone();
+        self.redirector.send(ReqTask::Simple(sub_cmd_ctx), node);
+
+        let reply = fut.await?;
+        let resp = match ScanResponse::parse_scan(&reply) {
+            Some(ScanResponse { next_index, keys }) => {
+                let keys = self.acl_checker.filter_keys(&cluster_name, keys);
+                match next_cursor(&nodes, cursor.node_index, next_index) {
+                    Some(next) => gen_scan_reply(next, keys),
+                    None => Resp::Error(
+                        format!("{} backend cursor too large", response::UM_UNSUPPORTED)
+                            .into_bytes(),
+                    ),
+                }
+            }
+            // Errors are replied directly.
+            None => reply,
+        };
+        cmd_ctx.set_resp_result(Ok(resp));
+        reply_receiver.await
+    }
+
+    async fn handle_mget(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
+        let keys: Vec<BinSafeStr> = (1..)
+            .map(|i| cmd_ctx.get_cmd().get_command_element(i))
+            .take_while(Option::is_some)
+            .filter_map(|key| key.map(|key| key.to_vec()))
+            .collect();
+        if keys.is_empty() {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                b"ERR wrong number of arguments for 'mget' command".to_vec(),
+            )));
+            return reply_receiver.await;
+        }
+        if !self.check_multi_key_slots(&cmd_ctx, &keys) {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
+            )));
+            return reply_receiver.await;
         }
 
+        let groups = self.group_keys_by_backend(&cmd_ctx, &keys);
         let factory = CmdCtxFactory::default();
         let mut futs = vec![];
-        for i in 1.. {
-            let key = match cmd_ctx.get_cmd().get_command_element(i) {
-                Some(key) => key,
-                None => break,
+        for group in groups.iter() {
+            let mut elements = vec![Resp::Bulk(BulkStr::Str(b"MGET".to_vec()))];
+            for i in group.iter() {
+                elements.push(Resp::Bulk(BulkStr::Str(keys[*i].clone())));
+            }
+            let resp = Resp::Arr(Array::Arr(elements));
+            let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), resp);
+            futs.push(fut);
+            self.handle_single_key_data_cmd(sub_cmd_ctx);
+        }
+
+        let mut replies = vec![];
+        let res = future::join_all(futs).await;
+        for sub_result in res.into_iter() {
+            let reply = match sub_result {
+                Ok(reply) => reply,
+                Err(err) => return Err(err),
             };
-            let resp = Resp::Arr(Array::Arr(vec![
-                Resp::Bulk(BulkStr::Str(cmd_name.to_string().into_bytes())),
-                Resp::Bulk(BulkStr::Str(key.to_vec())),
-            ]));
+            if let Resp::Error(err) = &reply {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(err.clone())));
+                return reply_receiver.await;
+            }
+            replies.push(reply);
+        }
+
+        let resp = match reassemble_values(keys.len(), &groups, replies) {
+            Some(values) => Resp::Arr(Array::Arr(values)),
+            None => Resp::Error(response::ERR_INTERNAL.to_string().into_bytes()),
+        };
+        cmd_ctx.set_resp_result(Ok(resp));
+        reply_receiver.await
+    }
+
+    async fn handle_mset(&self, cmd_ctx: CmdCtx, reply_receiver: CmdReplyReceiver) -> TaskResult {
+        let arg_len = cmd_ctx.get_cmd().get_command_len().unwrap_or(0);
+        if arg_len < 3 || arg_len % 2 != 1 {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                b"ERR wrong number of arguments for 'mset' command".to_vec(),
+            )));
+            return reply_receiver.await;
+        }
+        let mut keys = vec![];
+        let mut values = vec![];
+        for i in 0..(arg_len / 2) {
+            let key = cmd_ctx.get_cmd().get_command_element(2 * i + 1);
+            let value = cmd_ctx.get_cmd().get_command_element(2 * i + 2);
+            if let (Some(key), Some(value)) = (key, value) {
+                keys.push(key.to_vec());
+                values.push(value.to_vec());
+            }
+        }
+        if !self.check_multi_key_slots(&cmd_ctx, &keys) {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
+            )));
+            return reply_receiver.await;
+        }
+
+        let groups = self.group_keys_by_backend(&cmd_ctx, &keys);
+        let factory = CmdCtxFactory::default();
+        let mut futs = vec![];
+        for group in groups.iter() {
+            let mut elements = vec![Resp::Bulk(BulkStr::Str(b"MSET".to_vec()))];
+            for i in group.iter() {
+                elements.push(Resp::Bulk(BulkStr::Str(keys[*i].clone())));
+                elements.push(Resp::Bulk(BulkStr::Str(values[*i].clone())));
+            }
+            let resp = Resp::Arr(Array::Arr(elements));
             let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), resp);
             futs.push(fut);
             self.handle_single_key_data_cmd(sub_cmd_ctx);
         }
 
-        if futs.is_empty() {
+        let res = future::join_all(futs).await;
+        for sub_result in res.into_iter() {
+            let reply = match sub_result {
+                Ok(reply) => reply,
+                Err(err) => return Err(err),
+            };
+            if let Resp::Error(err) = reply {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(err)));
+                return reply_receiver.await;
+            }
+        }
+
+        let resp = Resp::Simple(response::OK_REPLY.to_string().into_bytes());
+        cmd_ctx.set_resp_result(Ok(resp));
+        reply_receiver.await
+    }
+
+    // The keys could be in different slots only when they are all owned by this proxy,
+    // or the proxy could redirect the commands to the others.
+    fn check_multi_key_slots(&self, cmd_ctx: &CmdCtx, keys: &[BinSafeStr]) -> bool {
+        if self.config.active_redirection || same_slot(keys.iter().map(|key| key.as_slice())) {
+            return true;
+        }
+        let cluster_name = cmd_ctx.get_cluster_name();
+        keys.iter().all(|key| {
+            matches!(
+                self.manager
+                    .get_slot_owner(cluster_name, generate_slot(key)),
+                Some(SlotOwner::Local(_))
+            )
+        })
+    }
+
+    // The keys of the same backend are sent in one sub-command.
+    fn group_keys_by_backend(&self, cmd_ctx: &CmdCtx, keys: &[BinSafeStr]) -> Vec<Vec<usize>> {
+        let cluster_name = cmd_ctx.get_cluster_name();
+        group_key_indices(keys, |key| {
+            let slot = generate_slot(key);
+            match self.manager.get_direct_node(cluster_name, slot) {
+                Some(node) => KeyGroup::Backend(node),
+                None if self.manager.is_migrating_or_canary(cluster_name, slot) => {
+                    KeyGroup::Key(key.to_vec())
+                }
+                None => KeyGroup::Slot(slot),
+            }
+        })
+    }
+
+    // DEL and EXISTS
+    async fn handle_multi_int_cmd(
+        &self,
+        cmd_ctx: CmdCtx,
+        reply_receiver: CmdReplyReceiver,
+        cmd_name: &'static str,
+    ) -> TaskResult {
+        let keys: Vec<BinSafeStr> = (1..)
+            .map(|i| cmd_ctx.get_cmd().get_command_element(i))
+            .take_while(Option::is_some)
+            .filter_map(|key| key.map(|key| key.to_vec()))
+            .collect();
+        if keys.is_empty() {
             cmd_ctx.set_resp_result(Ok(Resp::Error(
                 format!("ERR wrong number of arguments for '{}' command", cmd_name).into_bytes(),
             )));
             return reply_receiver.await;
         }
+        if !self.check_multi_key_slots(&cmd_ctx, &keys) {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
+            )));
+            return reply_receiver.await;
+        }
+
+        // The duplicated keys are in the same group so that they're only counted once by DEL.
+        // The keys of the migrating slots are sent one by one
+        // so that each of them gets checked by the importing side.
+        let groups = self.group_keys_by_backend(&cmd_ctx, &keys);
+        let factory = CmdCtxFactory::default();
+        let mut futs = vec![];
+        for group in groups.iter() {
+            let mut elements = vec![Resp::Bulk(BulkStr::Str(cmd_name.as_bytes().to_vec()))];
+            for i in group.iter() {
+                elements.push(Resp::Bulk(BulkStr::Str(keys[*i].clone())));
+            }
+            let resp = Resp::Arr(Array::Arr(elements));
+            let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), resp);
+            futs.push(fut);
+            self.handle_single_key_data_cmd(sub_cmd_ctx);
+        }
 
         let mut count: usize = 0;
         let res = future::join_all(futs).await;
@@ -694,8 +1437,13 @@ where
                     let n = match btou::<usize>(&data) {
                         Ok(n) => n,
                         Err(err) => {
-                            let err_str =
-                                format!("unexpected reply from {}: {:?} {:?}", cmd_name, data, err);
+                            let err_str = format!(
+                                "{} unexpected reply from {}: {:?} {:?}",
+                                response::UM_BACKEND,
+                                cmd_name,
+                                data,
+                                err
+                            );
                             cmd_ctx.set_resp_result(Ok(Resp::Error(err_str.into_bytes())));
                             return reply_receiver.await;
                         }
@@ -703,7 +1451,12 @@ where
                     count += n;
                 }
                 others => {
-                    let err_str = format!("unexpected reply from {}: {:?}", cmd_name, others);
+                    let err_str = format!(
+                        "{} unexpected reply from {}: {:?}",
+                        response::UM_BACKEND,
+                        cmd_name,
+                        others
+                    );
                     cmd_ctx.set_resp_result(Ok(Resp::Error(err_str.into_bytes())));
                     return reply_receiver.await;
                 }
@@ -726,6 +1479,9 @@ where
             DataCmdType::BLPOP => ("LPOP", true),
             DataCmdType::BRPOP => ("RPOP", true),
             DataCmdType::BRPOPLPUSH => ("RPOPLPUSH", false),
+            DataCmdType::BLMOVE => ("LMOVE", false),
+            DataCmdType::BZPOPMIN => ("ZPOPMIN", true),
+            DataCmdType::BZPOPMAX => ("ZPOPMAX", true),
             _ => {
                 let cmd_name = cmd_ctx
                     .get_cmd()
@@ -743,6 +1499,9 @@ where
             (DataCmdType::BLPOP, Some(len)) if len > 2 => len,
             (DataCmdType::BRPOP, Some(len)) if len > 2 => len,
             (DataCmdType::BRPOPLPUSH, Some(len)) if len == 4 => len,
+            (DataCmdType::BLMOVE, Some(len)) if len == 6 => len,
+            (DataCmdType::BZPOPMIN, Some(len)) if len > 2 => len,
+            (DataCmdType::BZPOPMAX, Some(len)) if len > 2 => len,
             _ => {
                 let cmd_name = cmd_ctx
                     .get_cmd()
@@ -756,18 +1515,19 @@ where
             }
         };
 
+        let proxy_timeout = self.config.get_command_timeout(CmdTimeoutClass::Blocking);
         let timeout = match cmd_ctx.get_cmd().get_command_last_element() {
             None => {
                 cmd_ctx.set_resp_result(Ok(Resp::Error(b"ERR wrong number of arguments".to_vec())));
                 return reply_receiver.await;
             }
-            Some(last) => match btoi::btou::<u64>(last) {
-                Err(_) => {
+            Some(last) => match BlockingTimeout::parse(last, proxy_timeout, Instant::now()) {
+                None => {
                     cmd_ctx
                         .set_resp_result(Ok(Resp::Error(b"ERR invalid timeout argument".to_vec())));
                     return reply_receiver.await;
                 }
-                Ok(timeout) => timeout,
+                Some(timeout) => timeout,
             },
         };
 
@@ -783,12 +1543,24 @@ where
             }
         }
 
+        if let Some((slot, node)) = self.get_blocking_node(&cmd_ctx) {
+            let elements = get_command_elements(cmd_ctx.get_cmd()).unwrap_or_default();
+            // Falls back to polling when the slot is moved.
+            let resp = self
+                .block_on_node(&cmd_ctx, &elements, arg_len - 1, slot, node, &timeout)
+                .await;
+            if let Some(resp) = resp {
+                cmd_ctx.set_resp_result(Ok(resp));
+                return reply_receiver.await;
+            }
+        }
+
         let factory = CmdCtxFactory::default();
-        let mut retry_num = 0;
         loop {
+            let expired = timeout.next_round(Instant::now()).is_none();
             let mut cmds = vec![];
             if lrpop {
-                // BLPOP, BRPOP
+                // BLPOP, BRPOP, BZPOPMIN, BZPOPMAX
                 // exclude the timeout argument
                 for i in 1..(arg_len - 1) {
                     let key = match cmd_ctx.get_cmd().get_command_element(i) {
@@ -805,7 +1577,7 @@ where
                     cmds.push((key, resp));
                 }
             } else {
-                // BRPOPLPUSH
+                // BRPOPLPUSH, BLMOVE
                 let mut resp = cmd_ctx.get_cmd().get_resp_slice().map(|b| b.to_vec());
                 change_bulk_array_element(
                     &mut resp,
@@ -815,7 +1587,7 @@ where
                 if let Resp::Arr(Array::Arr(ref mut resps)) = resp {
                     resps.pop(); // pop out the timeout argument
                 }
-                // BRPOPLPUSH does not need to care about key.
+                // BRPOPLPUSH and BLMOVE do not need to care about key.
                 cmds.push((vec![], resp));
             }
 
@@ -833,7 +1605,7 @@ where
                 };
 
                 match resp {
-                    Resp::Bulk(BulkStr::Nil) if timeout == 0 || retry_num < timeout => {}
+                    Resp::Bulk(BulkStr::Nil) if !expired => {}
                     Resp::Bulk(BulkStr::Nil) if lrpop => {
                         // BLPOP, BRPOP need to change resposne to Array::Nil.
                         cmd_ctx.set_resp_result(Ok(Resp::Arr(Array::Nil)));
@@ -848,6 +1620,18 @@ where
                         cmd_ctx.set_resp_result(Ok(resp));
                         return reply_receiver.await;
                     }
+                    // ZPOPMIN and ZPOPMAX reply an empty array when there's no member.
+                    Resp::Arr(Array::Arr(ref arr)) if arr.is_empty() && !expired => {}
+                    Resp::Arr(Array::Arr(ref arr)) if arr.is_empty() => {
+                        cmd_ctx.set_resp_result(Ok(Resp::Arr(Array::Nil)));
+                        return reply_receiver.await;
+                    }
+                    Resp::Arr(Array::Arr(mut arr)) if lrpop => {
+                        // BZPOPMIN, BZPOPMAX need to include the key before the member and score.
+                        arr.insert(0, Resp::Bulk(BulkStr::Str(key)));
+                        cmd_ctx.set_resp_result(Ok(Resp::Arr(Array::Arr(arr))));
+                        return reply_receiver.await;
+                    }
                     resp => {
                         cmd_ctx.set_resp_result(Ok(resp));
                         return reply_receiver.await;
@@ -855,32 +1639,341 @@ where
                 }
             }
 
-            retry_num += 1;
             Delay::new(Duration::from_secs(1)).await;
         }
     }
 
+    // XREAD and XREADGROUP with the `BLOCK milliseconds` option.
+    async fn handle_stream_blocking_commands(
+        &self,
+        cmd_ctx: CmdCtx,
+        reply_receiver: CmdReplyReceiver,
+    ) -> TaskResult {
+        let proxy_timeout = self.config.get_command_timeout(CmdTimeoutClass::Blocking);
+        let timeout_index = get_blocking_timeout_index(cmd_ctx.get_cmd());
+        let timeout = timeout_index.and_then(|index| {
+            let arg = cmd_ctx.get_cmd().get_command_element(index)?;
+            BlockingTimeout::parse_millis(arg, proxy_timeout, Instant::now())
+        });
+        let (timeout_index, timeout) = match (timeout_index, timeout) {
+            (Some(timeout_index), Some(timeout)) => (timeout_index, timeout),
+            _ => {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(b"ERR invalid timeout argument".to_vec())));
+                return reply_receiver.await;
+            }
+        };
+
+        match get_cmd_keys(cmd_ctx.get_cmd()) {
+            Ok(keys) if same_slot(keys.iter().copied()) => (),
+            Ok(_) => {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    response::ERR_NOT_THE_SAME_SLOT.to_string().into_bytes(),
+                )));
+                return reply_receiver.await;
+            }
+            Err(_) => {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    response::ERR_INVALID_COMMAND.to_string().into_bytes(),
+                )));
+                return reply_receiver.await;
+            }
+        }
+
+        let mut elements = match get_command_elements(cmd_ctx.get_cmd()) {
+            Some(elements) => elements,
+            None => {
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    response::ERR_INVALID_COMMAND.to_string().into_bytes(),
+                )));
+                return reply_receiver.await;
+            }
+        };
+        // The `$` ID means the last ID when the command starts to block.
+        // It's replaced by the last ID so that the entries added between the rounds
+        // or during polling won't be missed.
+        if let Err(res) = self.resolve_last_stream_ids(&cmd_ctx, &mut elements).await {
+            cmd_ctx.set_resp_result(res);
+            return reply_receiver.await;
+        }
+
+        if let Some((slot, node)) = self.get_blocking_node(&cmd_ctx) {
+            // Falls back to polling when the slot is moved.
+            let resp = self
+                .block_on_node(&cmd_ctx, &elements, timeout_index, slot, node, &timeout)
+                .await;
+            if let Some(resp) = resp {
+                cmd_ctx.set_resp_result(Ok(resp));
+                return reply_receiver.await;
+            }
+        }
+
+        let polling_cmd = gen_non_blocking_stream_cmd(&elements, timeout_index);
+        let factory = CmdCtxFactory;
+        loop {
+            let expired = timeout.next_round(Instant::now()).is_none();
+            let resp = Resp::Arr(Array::Arr(
+                polling_cmd
+                    .iter()
+                    .map(|s| Resp::Bulk(BulkStr::Str(s.clone())))
+                    .collect(),
+            ));
+            let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), resp);
+            self.handle_single_key_data_cmd(sub_cmd_ctx);
+
+            match fut.await {
+                Ok(Resp::Arr(Array::Nil)) if !expired => (),
+                res => {
+                    cmd_ctx.set_resp_result(res);
+                    return reply_receiver.await;
+                }
+            }
+
+            Delay::new(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn resolve_last_stream_ids(
+        &self,
+        cmd_ctx: &CmdCtx,
+        cmd: &mut [BinSafeStr],
+    ) -> Result<(), Result<RespVec, CommandError>> {
+        let invalid_cmd = || {
+            Err(Ok(Resp::Error(
+                response::ERR_INVALID_COMMAND.to_string().into_bytes(),
+            )))
+        };
+        let streams = match cmd
+            .iter()
+            .position(|arg| arg.eq_ignore_ascii_case(b"STREAMS"))
+        {
+            Some(streams) => streams,
+            None => return invalid_cmd(),
+        };
+        let key_num = (cmd.len() - streams - 1) / 2;
+
+        let factory = CmdCtxFactory;
+        for i in (streams + 1)..(streams + 1 + key_num) {
+            if cmd[i + key_num].as_slice() != b"$" {
+                continue;
+            }
+            let xrevrange = vec![
+                b"XREVRANGE".to_vec(),
+                cmd[i].clone(),
+                b"+".to_vec(),
+                b"-".to_vec(),
+                b"COUNT".to_vec(),
+                b"1".to_vec(),
+            ];
+            let resp = Resp::Arr(Array::Arr(
+                xrevrange
+                    .into_iter()
+                    .map(|s| Resp::Bulk(BulkStr::Str(s)))
+                    .collect(),
+            ));
+            let (sub_cmd_ctx, fut) = factory.create_with_ctx(cmd_ctx.get_context(), resp);
+            self.handle_single_key_data_cmd(sub_cmd_ctx);
+
+            // XREVRANGE replies [[id, [field, value, ...]]] or an empty array.
+            let last_id = match fut.await {
+                Ok(Resp::Arr(Array::Arr(entries))) => match entries.first() {
+                    None => b"0-0".to_vec(),
+                    Some(Resp::Arr(Array::Arr(entry))) => match entry.first() {
+                        Some(Resp::Bulk(BulkStr::Str(id))) => id.clone(),
+                        _ => return invalid_cmd(),
+                    },
+                    Some(_) => return invalid_cmd(),
+                },
+                res => return Err(res),
+            };
+            cmd[i + key_num] = last_id;
+        }
+        Ok(())
+    }
+
+    // Only the commands with all the keys in the same slot owned by the local backend
+    // could be blocked on the dedicated connections.
+    fn get_blocking_node(&self, cmd_ctx: &CmdCtx) -> Option<(usize, String)> {
+        let keys = get_cmd_keys(cmd_ctx.get_cmd()).ok()?;
+        let slot = generate_slot(keys.first()?);
+        if !same_slot(keys.into_iter()) {
+            return None;
+        }
+        let node = self
+            .manager
+            .get_direct_node(cmd_ctx.get_cluster_name(), slot)?;
+        Some((slot, node))
+    }
+
+    // Returns None when the slot is moved during blocking.
+    async fn block_on_node(
+        &self,
+        cmd_ctx: &CmdCtx,
+        elements: &[BinSafeStr],
+        timeout_index: usize,
+        slot: usize,
+        node: String,
+        timeout: &BlockingTimeout,
+    ) -> Option<RespVec> {
+        let cluster_name = cmd_ctx.get_cluster_name();
+        let mut client = match self
+            .blocking_client_factory
+            .create_client(node.clone())
+            .await
+        {
+            Ok(client) => client,
+            Err(err) => {
+                error!("failed to create blocking connection to {}: {}", node, err);
+                let err_msg = format!("{} {}", response::ERR_BACKEND_CONNECTION, node);
+                return Some(Resp::Error(err_msg.into_bytes()));
+            }
+        };
+        loop {
+            let timeout_arg = match timeout.next_round(Instant::now()) {
+                Some(timeout_arg) => timeout_arg,
+                None => return Some(gen_blocking_timeout_reply(cmd_ctx.get_data_cmd_type())),
+            };
+            let cmd = gen_blocking_round_cmd(elements, timeout_index, timeout_arg);
+            let resp = match client.execute_single(cmd).await {
+                Ok(resp) => resp,
+                Err(err) => {
+                    error!("failed to run blocking command on {}: {}", node, err);
+                    let err_msg = format!("{} {}", response::ERR_BACKEND_CONNECTION, node);
+                    return Some(Resp::Error(err_msg.into_bytes()));
+                }
+            };
+            if is_redirection_reply(&resp) {
+                return None;
+            }
+            if !is_blocking_timeout_reply(&resp) {
+                return Some(resp);
+            }
+            if self.manager.get_direct_node(cluster_name, slot).as_ref() != Some(&node) {
+                return None;
+            }
+        }
+    }
+
     fn handle_single_key_data_cmd(&self, cmd_ctx: CmdCtx) {
+        if let Some(cmd_ctx) = self.compress_cmd_ctx(cmd_ctx) {
+            self.manager.send(cmd_ctx);
+        }
+    }
+
+    // Returns None if the error reply has been set.
+    fn compress_cmd_ctx(&self, cmd_ctx: CmdCtx) -> Option<CmdCtx> {
         let mut cmd_ctx = cmd_ctx;
         match self.compressor.try_compressing_cmd_ctx(&mut cmd_ctx) {
             Ok(())
             | Err(CompressionError::UnsupportedCmdType)
-            | Err(CompressionError::Disabled) => (),
+            | Err(CompressionError::Disabled) => Some(cmd_ctx),
             Err(CompressionError::InvalidRequest) | Err(CompressionError::InvalidResp) => {
-                return cmd_ctx
-                    .set_resp_result(Ok(Resp::Error("invalid command".to_string().into_bytes())));
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    response::ERR_INVALID_COMMAND.to_string().into_bytes(),
+                )));
+                None
             }
             Err(CompressionError::RestrictedCmd) => {
-                let err_msg = "unsupported string command when compression is enabled";
-                return cmd_ctx.set_resp_result(Ok(Resp::Error(err_msg.to_string().into_bytes())));
+                let err_msg = format!(
+                    "{} unsupported string command when compression is enabled",
+                    response::UM_UNSUPPORTED
+                );
+                cmd_ctx.set_resp_result(Ok(Resp::Error(err_msg.into_bytes())));
+                None
             }
             Err(CompressionError::Io(err)) => {
-                return cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    format!("failed to compress data: {:?}", err).into_bytes(),
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    format!(
+                        "{} failed to compress data: {:?}",
+                        response::UM_INTERNAL,
+                        err
+                    )
+                    .into_bytes(),
                 )));
+                None
             }
         }
-        self.manager.send(cmd_ctx);
+    }
+
+    // The local masters of the keys of the writes which need to be acknowledged by the replicas.
+    fn get_replica_ack_masters(&self, cmd_ctx: &CmdCtx) -> Vec<String> {
+        let cluster_name = cmd_ctx.get_cluster_name();
+        if self.manager.get_write_ack_policy(cluster_name) != WriteAckPolicy::Replica {
+            return vec![];
+        }
+        if get_cmd_category(cmd_ctx.get_cmd()) != Some(AclCategory::Write) {
+            return vec![];
+        }
+        let keys = match get_cmd_keys(cmd_ctx.get_cmd()) {
+            Ok(keys) => keys,
+            Err(_) => return vec![],
+        };
+        // The remote proxies wait for their own masters.
+        let mut masters: Vec<String> = keys
+            .into_iter()
+            .filter_map(|key| {
+                self.manager
+                    .get_local_backend(cluster_name, generate_slot(key))
+            })
+            .collect();
+        masters.sort();
+        masters.dedup();
+        masters
+    }
+
+    fn handle_replica_ack<'a>(
+        &self,
+        fut: CmdReplyFuture<'a>,
+        ack_masters: Vec<String>,
+    ) -> CmdReplyFuture<'a> {
+        if ack_masters.is_empty() {
+            return fut;
+        }
+        let tracker = self.replica_ack_tracker.clone();
+        let stats = self.stats.clone();
+        let timeout = Duration::from_millis(self.config.replica_ack_timeout);
+        CmdReplyFuture::Right(Box::pin(async move {
+            let res = fut.await;
+            let written = match &res {
+                Ok(reply) => !matches!(reply.get_packet().to_resp_slice(), Resp::Error(_)),
+                Err(_) => false,
+            };
+            // Still reply the write in the best effort.
+            if written && !tracker.wait_ack(ack_masters, timeout).await {
+                stats.record_unacked_write();
+            }
+            res
+        }))
+    }
+
+    fn handle_double_write<'a>(
+        &self,
+        fut: CmdReplyFuture<'a>,
+        data_cmd_type: DataCmdType,
+        secondary_cmds: Vec<(CmdCtx, CmdReplyReceiver)>,
+    ) -> CmdReplyFuture<'a> {
+        if secondary_cmds.is_empty() {
+            return fut;
+        }
+        let mut reply_receivers = vec![];
+        for (secondary_cmd_ctx, reply_receiver) in secondary_cmds.into_iter() {
+            self.handle_single_key_data_cmd(secondary_cmd_ctx);
+            reply_receivers.push(reply_receiver);
+        }
+        self.double_writer
+            .compare_replies(fut, data_cmd_type, reply_receivers)
+    }
+
+    fn wrap_timeout(fut: CmdReplyFuture, timeout: u64) -> CmdReplyFuture {
+        if timeout == 0 {
+            return fut;
+        }
+        let delay = Delay::new(Duration::from_millis(timeout));
+        CmdReplyFuture::Right(Box::pin(async move {
+            match future::select(fut, delay).await {
+                future::Either::Left((res, _)) => res,
+                future::Either::Right(((), _)) => Err(CommandError::Timeout),
+            }
+        }))
     }
 
     fn handle_umforward(
@@ -896,7 +1989,9 @@ where
         let times = match str::parse::<usize>(redirection_times.as_str()) {
             Ok(times) => times,
             Err(_) => {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(b"invalid redirection times".to_vec())));
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    format!("{} invalid redirection times", response::UM_INVALID).into_bytes(),
+                )));
                 return CmdReplyFuture::Left(reply_receiver);
             }
         };
@@ -905,7 +2000,9 @@ where
         match cmd_ctx.extract_inner_cmd(2) {
             Some(cmd_len) if cmd_len > 0 => (),
             _ => {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(b"missing forwarded command".to_vec())));
+                cmd_ctx.set_resp_result(Ok(Resp::Error(
+                    format!("{} missing forwarded command", response::UM_INVALID).into_bytes(),
+                )));
                 return CmdReplyFuture::Left(reply_receiver);
             }
         }
@@ -914,9 +2011,126 @@ where
         self.handle_data_cmd(cmd_ctx, reply_receiver)
     }
 
+    fn handle_info(&self, cmd_ctx: CmdCtx) {
+        let names: Vec<String> = (1..cmd_ctx.get_cmd().get_command_len().unwrap_or(1))
+            .filter_map(|i| cmd_ctx.get_cmd().get_command_element(i))
+            .map(|s| str::from_utf8(s).unwrap_or("").to_string())
+            .collect();
+
+        let mut sections = InfoSections::default();
+        sections.register("server", true, || {
+            vec![format!("version:{}", UNDERMOON_VERSION)]
+        });
+        sections.register("memory", true, || self.memory_tracker.gen_info_lines());
+        sections.register("stats", true, || {
+            self.stats
+                .gen_info_fields(
+                    &self.memory_tracker,
+                    self.manager.get_migration_progress(),
+                    self.manager.get_max_replication_lag(),
+                    self.manager.get_meta_lock_stats(),
+                )
+                .into_iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect()
+        });
+        self.manager.register_info_sections(&mut sections);
+        sections.register("slowlog", false, || {
+            self.slow_request_logger.gen_info_lines()
+        });
+
+        let info = sections.gen_info(&names);
+        cmd_ctx.set_resp_result(Ok(Resp::Bulk(BulkStr::Str(info.into_bytes()))));
+    }
+
     fn handle_umsync(&self, cmd_ctx: CmdCtx) {
         self.manager.send_sync_task(cmd_ctx);
     }
+
+    // The pub/sub commands bypass the command handling so the cluster and the password are checked here.
+    fn select_session_cluster(&self, auth: &SessionAuth) -> Result<ClusterName, String> {
+        let cluster_name = auth.get_cluster_name();
+        let selected = self
+            .manager
+            .get_selected_cluster(cluster_name, self.config.auto_select_cluster);
+        let cluster_name = selected.unwrap_or_else(|| cluster_name.clone());
+        if !self
+            .manager
+            .check_auth(&cluster_name, auth.get_user(), auth.get_password())
+        {
+            return Err(response::ERR_NOAUTH.to_string());
+        }
+        Ok(cluster_name)
+    }
+
+    // The TTL policy and the double writes could not be applied to the queued commands
+    // so the transactions are rejected on the clusters with them.
+    fn select_transaction_cluster(&self, auth: &SessionAuth) -> Result<ClusterName, String> {
+        let cluster_name = self.select_session_cluster(auth)?;
+        if !self.manager.is_transaction_allowed(&cluster_name) {
+            return Err(response::ERR_TRANSACTION_NOT_ALLOWED.to_string());
+        }
+        Ok(cluster_name)
+    }
+
+    // Returns the backend node owning the slot.
+    // The transactions are rejected with TRYAGAIN while the slot is being migrated.
+    // The compression is not applied to the transactions.
+    fn check_transaction(
+        &self,
+        auth: &SessionAuth,
+        slot: usize,
+        cmds: &[Command],
+    ) -> Result<String, String> {
+        // Checked again since the cluster config could be changed after MULTI.
+        let cluster_name = self.select_transaction_cluster(auth)?;
+        for cmd in cmds.iter() {
+            self.acl_checker
+                .check_cmd(&cluster_name, cmd, auth.get_user())
+                .map_err(|err| get_acl_error_msg(err).to_string())?;
+            self.size_checker
+                .check_cmd(&cluster_name, cmd)
+                .map_err(|err| get_size_error_msg(err).to_string())?;
+        }
+        if let Some(node) = self.manager.get_direct_node(&cluster_name, slot) {
+            return Ok(node);
+        }
+        // The keys of the migrating or canary slots could be on different nodes.
+        if self.manager.is_migrating_or_canary(&cluster_name, slot) {
+            return Err(response::ERR_MIGRATION_BLOCKING.to_string());
+        }
+        match self.manager.get_slot_owner(&cluster_name, slot) {
+            Some(SlotOwner::Local(node)) => Ok(node),
+            Some(SlotOwner::Remote(address)) => Err(gen_moved(slot, address)),
+            None => Err(format!("{} {}", response::ERR_SLOT_NOT_COVERED, slot)),
+        }
+    }
+}
+
+fn gen_scan_reply(cursor: u64, keys: Vec<BinSafeStr>) -> RespVec {
+    Resp::Arr(Array::Arr(vec![
+        Resp::Bulk(BulkStr::Str(cursor.to_string().into_bytes())),
+        Resp::Arr(Array::Arr(
+            keys.into_iter()
+                .map(|key| Resp::Bulk(BulkStr::Str(key)))
+                .collect(),
+        )),
+    ]))
+}
+
+fn get_acl_error_msg(err: AclError) -> &'static str {
+    match err {
+        AclError::CategoryNotAllowed => response::ERR_ACL_CATEGORY_NOT_ALLOWED,
+        AclError::KeyNotAllowed => response::ERR_ACL_KEY_NOT_ALLOWED,
+        AclError::InvalidRequest => response::ERR_INVALID_COMMAND,
+    }
+}
+
+fn get_size_error_msg(err: SizeLimitError) -> &'static str {
+    match err {
+        SizeLimitError::KeyTooLarge => response::ERR_KEY_TOO_LARGE,
+        SizeLimitError::ValueTooLarge => response::ERR_VALUE_TOO_LARGE,
+    }
 }
 
 impl<F, C> CmdCtxHandler for ForwardHandler<F, C>
@@ -928,11 +2142,14 @@ where
         &self,
         cmd_ctx: CmdCtx,
         reply_receiver: CmdReplyReceiver,
-        session_cluster_name: &sync::RwLock<ClusterName>,
+        session_auth: &sync::RwLock<SessionAuth>,
     ) -> CmdReplyFuture {
-        let mut cmd_ctx = cmd_ctx;
-        if self.config.auto_select_cluster {
-            cmd_ctx = self.manager.try_select_cluster(cmd_ctx);
+        let mut cmd_ctx = self
+            .manager
+            .try_select_cluster(cmd_ctx, self.config.auto_select_cluster);
+        if let Err(err_msg) = run_middlewares(&self.middlewares, &mut cmd_ctx) {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(err_msg.into_bytes())));
+            return CmdReplyFuture::Left(reply_receiver);
         }
 
         let cmd_type = cmd_ctx.get_cmd().get_type();
@@ -940,10 +2157,9 @@ where
             CmdType::Ping => {
                 cmd_ctx.set_resp_result(Ok(Resp::Simple(String::from("OK").into_bytes())))
             }
-            CmdType::Info => cmd_ctx.set_resp_result(Ok(Resp::Bulk(BulkStr::Str(
-                format!("version:{}\r\n", UNDERMOON_VERSION,).into_bytes(),
-            )))),
-            CmdType::Auth => self.handle_auth(cmd_ctx, session_cluster_name),
+            CmdType::Info => self.handle_info(cmd_ctx),
+            CmdType::Auth => self.handle_auth(cmd_ctx, session_auth),
+            CmdType::Hello => self.handle_hello(cmd_ctx, session_auth),
             CmdType::Quit => {
                 cmd_ctx.set_resp_result(Ok(Resp::Simple(String::from("OK").into_bytes())))
             }
@@ -954,14 +2170,28 @@ where
                     .map(|msg| msg.to_vec())
                 {
                     Some(msg) => cmd_ctx.set_resp_result(Ok(Resp::Bulk(BulkStr::Str(msg)))),
-                    None => cmd_ctx.set_resp_result(Ok(Resp::Error(b"Missing message".to_vec()))),
+                    None => cmd_ctx.set_resp_result(Ok(Resp::Error(
+                        format!("{} missing message", response::UM_INVALID).into_bytes(),
+                    ))),
                 }
             }
             CmdType::Select => cmd_ctx.set_resp_result(Ok(Resp::Simple(
                 response::OK_REPLY.to_string().into_bytes(),
             ))),
+            CmdType::Reset => {
+                session_auth.write().expect("ForwardHandler::reset").reset();
+                let cluster_name = session_auth
+                    .read()
+                    .expect("ForwardHandler::reset")
+                    .get_cluster_name()
+                    .clone();
+                cmd_ctx.set_cluster_name(cluster_name);
+                cmd_ctx.set_resp_result(Ok(Resp::Simple(
+                    response::RESET_REPLY.to_string().into_bytes(),
+                )))
+            }
             CmdType::Invalid => cmd_ctx.set_resp_result(Ok(Resp::Error(
-                String::from("Invalid command").into_bytes(),
+                response::ERR_INVALID_COMMAND.to_string().into_bytes(),
             ))),
             CmdType::UmCtl => self.handle_umctl(cmd_ctx),
             CmdType::UmForward => return self.handle_umforward(cmd_ctx, reply_receiver),
@@ -974,8 +2204,353 @@ where
             CmdType::Asking => cmd_ctx.set_resp_result(Ok(Resp::Simple(
                 response::OK_REPLY.to_string().into_bytes(),
             ))),
-            CmdType::Others => return self.handle_data_cmd(cmd_ctx, reply_receiver),
+            // Handled by the session itself.
+            CmdType::Subscribe
+            | CmdType::Unsubscribe
+            | CmdType::PSubscribe
+            | CmdType::PUnsubscribe
+            | CmdType::SSubscribe
+            | CmdType::SUnsubscribe
+            | CmdType::Multi
+            | CmdType::Exec
+            | CmdType::Discard
+            | CmdType::Watch
+            | CmdType::Unwatch
+            | CmdType::Client
+            | CmdType::Monitor => cmd_ctx.set_resp_result(Ok(Resp::Error(
+                response::CMD_NOT_SUPPORTED.to_string().into_bytes(),
+            ))),
+            CmdType::Others => {
+                let (authenticated, user) = {
+                    let auth = session_auth.read().expect("ForwardHandler::handle_cmd_ctx");
+                    let authenticated = self.manager.check_auth(
+                        cmd_ctx.get_cluster_name(),
+                        auth.get_user(),
+                        auth.get_password(),
+                    );
+                    (authenticated, auth.get_user().map(|user| user.to_string()))
+                };
+                if !authenticated {
+                    cmd_ctx.set_resp_result(Ok(Resp::Error(
+                        response::ERR_NOAUTH.to_string().into_bytes(),
+                    )));
+                    return CmdReplyFuture::Left(reply_receiver);
+                }
+                if let Err(err) = self.acl_checker.check(&cmd_ctx, user.as_deref()) {
+                    let err_msg = get_acl_error_msg(err);
+                    cmd_ctx.set_resp_result(Ok(Resp::Error(err_msg.to_string().into_bytes())));
+                    return CmdReplyFuture::Left(reply_receiver);
+                }
+                // Same as Redis, only the data commands passing the auth and ACL are monitored.
+                // The admin commands such as UMCTL and CONFIG could carry the passwords.
+                self.monitors.feed(
+                    cmd_ctx.get_cluster_name(),
+                    cmd_ctx.get_client_addr(),
+                    cmd_ctx.get_cmd(),
+                );
+                if let Err(err) = self.size_checker.check(&cmd_ctx) {
+                    let err_msg = get_size_error_msg(err);
+                    cmd_ctx.set_resp_result(Ok(Resp::Error(err_msg.to_string().into_bytes())));
+                    return CmdReplyFuture::Left(reply_receiver);
+                }
+                if let Err(err) = self.ttl_checker.check(&mut cmd_ctx) {
+                    let err_msg = match err {
+                        TtlPolicyError::MissingExpiry => response::ERR_TTL_REQUIRED,
+                        TtlPolicyError::InvalidRequest => response::ERR_INVALID_COMMAND,
+                    };
+                    cmd_ctx.set_resp_result(Ok(Resp::Error(err_msg.to_string().into_bytes())));
+                    return CmdReplyFuture::Left(reply_receiver);
+                }
+                let flavor = self.manager.get_backend_flavor(cmd_ctx.get_cluster_name());
+                if !cmd_ctx.get_cmd().is_supported_by(flavor) {
+                    cmd_ctx.set_resp_result(Ok(Resp::Error(
+                        format!("{} by {}", response::CMD_NOT_SUPPORTED, flavor.to_str())
+                            .into_bytes(),
+                    )));
+                    return CmdReplyFuture::Left(reply_receiver);
+                }
+                if cmd_ctx.get_cmd().is_unknown() {
+                    let cluster_name = cmd_ctx.get_cluster_name();
+                    match self.manager.get_unknown_command_policy(cluster_name) {
+                        UnknownCommandPolicy::Forward => self.stats.record_unknown_command(),
+                        UnknownCommandPolicy::Reject => {
+                            cmd_ctx.set_resp_result(Ok(Resp::Error(
+                                response::ERR_UNKNOWN_COMMAND.to_string().into_bytes(),
+                            )));
+                            return CmdReplyFuture::Left(reply_receiver);
+                        }
+                    }
+                }
+                let timeout = self
+                    .config
+                    .get_command_timeout(cmd_ctx.get_cmd().get_timeout_class());
+                let secondary_cmds = self.double_writer.gen_secondary_cmds(&cmd_ctx);
+                let ack_masters = self.get_replica_ack_masters(&cmd_ctx);
+                let data_cmd_type = cmd_ctx.get_data_cmd_type();
+                let fut = self.handle_data_cmd(cmd_ctx, reply_receiver);
+                let fut = self.handle_replica_ack(fut, ack_masters);
+                let fut = self.handle_double_write(fut, data_cmd_type, secondary_cmds);
+                return Self::wrap_timeout(fut, timeout);
+            }
         };
         CmdReplyFuture::Left(reply_receiver)
     }
+
+    fn get_moved_address(&self, cluster_name: &ClusterName) -> Option<String> {
+        self.manager.get_moved_address(cluster_name)
+    }
+
+    fn get_slot_owner(&self, auth: &SessionAuth, slot: usize) -> Result<SlotOwner, String> {
+        let cluster_name = self.select_session_cluster(auth)?;
+        self.manager
+            .get_slot_owner(&cluster_name, slot)
+            .ok_or_else(|| format!("{} {}", response::ERR_SLOT_NOT_COVERED, slot))
+    }
+
+    fn get_local_nodes(&self, auth: &SessionAuth) -> Result<Vec<String>, String> {
+        let cluster_name = self.select_session_cluster(auth)?;
+        self.manager
+            .get_local_nodes(&cluster_name)
+            .ok_or_else(|| format!("{}: {}", response::ERR_CLUSTER_NOT_FOUND, cluster_name))
+    }
+
+    fn check_session_auth(&self, auth: &SessionAuth) -> Result<(), String> {
+        self.select_session_cluster(auth).map(|_| ())
+    }
+
+    fn watch_removed_clusters(&self) -> Option<watch::Receiver<Arc<Vec<ClusterName>>>> {
+        Some(self.manager.watch_removed_clusters())
+    }
+
+    fn register_monitor(&self, auth: &SessionAuth) -> Result<MonitorReceiver, String> {
+        let cluster_name = self.select_session_cluster(auth)?;
+        // The restricted users should not see the commands of the other users.
+        if !self
+            .acl_checker
+            .allow_monitor(&cluster_name, auth.get_user())
+        {
+            return Err(response::ERR_MONITOR_NOT_ALLOWED.to_string());
+        }
+        Ok(self.monitors.register(cluster_name))
+    }
+
+    fn register_subscriber(
+        &self,
+        auth: &SessionAuth,
+    ) -> Result<(SubscriberHandle, SubscriberReceiver), String> {
+        let cluster_name = self.select_session_cluster(auth)?;
+        Ok(self.subscribers.register(cluster_name))
+    }
+
+    fn check_multi(&self, auth: &SessionAuth) -> Result<(), String> {
+        self.select_transaction_cluster(auth).map(|_| ())
+    }
+
+    fn exec_transaction(
+        &self,
+        auth: &SessionAuth,
+        slot: usize,
+        cmds: Vec<Command>,
+    ) -> TransactionFuture {
+        let node = match self.check_transaction(auth, slot, &cmds) {
+            Ok(node) => node,
+            Err(err_msg) => return Box::pin(future::ready(Resp::Error(err_msg.into_bytes()))),
+        };
+        let commands = gen_transaction_cmds(&cmds);
+        let client_factory = self.transaction_client_factory.clone();
+        Box::pin(async move {
+            let res = match client_factory.create_client(node.clone()).await {
+                Ok(mut client) => client.execute_multi(commands).await,
+                Err(err) => Err(err),
+            };
+            match res {
+                Ok(replies) => get_exec_reply(replies),
+                // Whether the transaction has been committed is unknown.
+                Err(RedisClientError::Timeout) => {
+                    Resp::Error(response::ERR_TIMEOUT.to_string().into_bytes())
+                }
+                Err(err) => {
+                    error!("failed to run transaction on {}: {}", node, err);
+                    let err_msg = format!("{} {}", response::ERR_BACKEND_CONNECTION, node);
+                    Resp::Error(err_msg.into_bytes())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::backend::DefaultConnFactory;
+    use crate::proxy::command::{new_command_pair, TaskReply};
+    use crate::proxy::config_file::ServerProxyConfigFile;
+    use crate::proxy::manager::MetaMap;
+    use crate::proxy::slowlog::Slowlog;
+    use arc_swap::ArcSwap;
+    use futures::{FutureExt, StreamExt};
+    use tokio::time;
+
+    type TestHandler = ForwardHandler<PooledRedisClientFactory, DefaultConnFactory<RespPacket>>;
+
+    fn gen_handler() -> TestHandler {
+        let (config, cluster_config) = ServerProxyConfigFile::default().into_config().unwrap();
+        let config = Arc::new(config);
+        ForwardHandler::new(
+            config.clone(),
+            cluster_config,
+            Arc::new(PooledRedisClientFactory::new(1, Duration::from_secs(1))),
+            Arc::new(SlowRequestLogger::new(config.clone())),
+            Arc::new(ArcSwap::new(Arc::new(MetaMap::empty()))),
+            Arc::new(DefaultConnFactory::default()),
+            Arc::new(TrackedFutureRegistry::default()),
+            Arc::new(MemoryTracker::new(config)),
+            Arc::new(ProxyStats::default()),
+        )
+    }
+
+    fn handle_cmd(handler: &TestHandler, auth: &sync::RwLock<SessionAuth>, args: Vec<&str>) {
+        let cmd = gen_cmd(args);
+        let (reply_sender, reply_receiver) = new_command_pair(&cmd);
+        let cluster_name = auth.read().unwrap().get_cluster_name().clone();
+        let cmd_ctx = CmdCtx::new(cluster_name, cmd, reply_sender, 0, false);
+        drop(handler.handle_cmd_ctx(cmd_ctx, reply_receiver, auth));
+    }
+
+    fn gen_cmd(elements: Vec<&str>) -> Command {
+        let resp = Resp::Arr(Array::Arr(
+            elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect(),
+        ));
+        Command::new(Box::new(RespPacket::from_resp_vec(resp)))
+    }
+
+    #[tokio::test]
+    async fn test_wrap_timeout_without_reply() {
+        // The backend never replies.
+        let (_reply_sender, reply_receiver) = new_command_pair(&gen_cmd(vec!["GET", "a"]));
+        let fut = TestHandler::wrap_timeout(CmdReplyFuture::Left(reply_receiver), 10);
+        let res = time::timeout(Duration::from_secs(3), fut).await;
+        assert!(matches!(res, Ok(Err(CommandError::Timeout))));
+    }
+
+    #[tokio::test]
+    async fn test_wrap_timeout_with_reply() {
+        let cmd = gen_cmd(vec!["GET", "a"]);
+        let (mut reply_sender, reply_receiver) = new_command_pair(&cmd);
+        let fut = TestHandler::wrap_timeout(CmdReplyFuture::Left(reply_receiver), 1000);
+        let reply = TaskReply::new(
+            Box::new(cmd.get_packet()),
+            Box::new(RespPacket::from_resp_vec(Resp::Bulk(BulkStr::Nil))),
+            Slowlog::new(0, false),
+        );
+        reply_sender.send(Ok(Box::new(reply))).unwrap();
+        let reply = fut.await.unwrap();
+        assert_eq!(reply.get_packet().to_resp_vec(), Resp::Bulk(BulkStr::Nil));
+
+        // Zero disables the timeout.
+        let (_reply_sender, reply_receiver) = new_command_pair(&cmd);
+        let fut = TestHandler::wrap_timeout(CmdReplyFuture::Left(reply_receiver), 0);
+        assert!(time::timeout(Duration::from_millis(50), fut).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_monitor_skips_admin_commands() {
+        let handler = gen_handler();
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let auth = sync::RwLock::new(SessionAuth::new(cluster_name));
+        let mut receiver = handler.register_monitor(&auth.read().unwrap()).unwrap();
+
+        handle_cmd(
+            &handler,
+            &auth,
+            vec![
+                "UMCTL",
+                "SETCLUSTER",
+                "1",
+                "NOFLAG",
+                "mycluster",
+                "127.0.0.1:6379",
+                "1",
+                "0-16383",
+                "CONFIG",
+                "mycluster",
+                "auth_passwords",
+                "secret",
+            ],
+        );
+        handle_cmd(
+            &handler,
+            &auth,
+            vec!["UMCTL", "RELOADTLS", "id.p12", "secret"],
+        );
+        handle_cmd(&handler, &auth, vec!["CONFIG", "GET", "*"]);
+        handle_cmd(&handler, &auth, vec!["AUTH", "secret"]);
+        assert!(receiver.next().now_or_never().is_none());
+
+        handle_cmd(&handler, &auth, vec!["GET", "key"]);
+        let line = receiver.next().await.unwrap();
+        assert!(line.ends_with("\"GET\" \"key\""));
+        assert!(receiver.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_not_allowed() {
+        let handler = gen_handler();
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let auth = sync::RwLock::new(SessionAuth::new(cluster_name));
+        let setcluster = vec![
+            "UMCTL",
+            "SETCLUSTER",
+            "1",
+            "NOFLAG",
+            "mycluster",
+            "127.0.0.1:6379",
+            "1",
+            "0-16383",
+        ];
+        handle_cmd(&handler, &auth, setcluster.clone());
+        assert!(handler.check_multi(&auth.read().unwrap()).is_ok());
+
+        let mut setcluster = setcluster;
+        setcluster[2] = "2";
+        setcluster.extend(vec!["CONFIG", "mycluster", "ttl_policy", "reject"]);
+        handle_cmd(&handler, &auth, setcluster);
+        assert_eq!(
+            handler.check_multi(&auth.read().unwrap()),
+            Err(response::ERR_TRANSACTION_NOT_ALLOWED.to_string())
+        );
+        let res =
+            handler.check_transaction(&auth.read().unwrap(), 0, &[gen_cmd(vec!["SET", "a", "1"])]);
+        assert_eq!(res, Err(response::ERR_TRANSACTION_NOT_ALLOWED.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_umctl_sub_commands_dispatched() {
+        let handler = gen_handler();
+        let invalid = Resp::Error(String::from("Invalid sub command").into_bytes());
+        for sub_cmd in UMCTL_SUB_COMMANDS.iter() {
+            // Handled by the session before reaching the handler.
+            if *sub_cmd == "LASTERR" {
+                continue;
+            }
+            let cmd = gen_cmd(vec!["UMCTL", sub_cmd]);
+            let (reply_sender, reply_receiver) = new_command_pair(&cmd);
+            let cluster_name = ClusterName::try_from("mycluster").unwrap();
+            handler.handle_umctl(CmdCtx::new(cluster_name, cmd, reply_sender, 0, false));
+            let res = time::timeout(Duration::from_secs(3), reply_receiver).await;
+            if let Ok(Ok(reply)) = res {
+                assert_ne!(reply.get_packet().to_resp_vec(), invalid, "{}", sub_cmd);
+            }
+        }
+
+        let cmd = gen_cmd(vec!["UMCTL", "UMSYNC"]);
+        let (reply_sender, reply_receiver) = new_command_pair(&cmd);
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        handler.handle_umctl(CmdCtx::new(cluster_name, cmd, reply_sender, 0, false));
+        let reply = reply_receiver.await.unwrap();
+        assert_eq!(reply.get_packet().to_resp_vec(), invalid);
+    }
 }
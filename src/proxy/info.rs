@@ -0,0 +1,96 @@
+// The sections of `INFO` in the format of the Redis `INFO` command.
+// Each subsystem registers its own section so that the clients could query only the sections they need.
+// The large sections such as `clusters` and `backends` are only returned when specified.
+const ALL_SECTIONS: &str = "all";
+const DEFAULT_SECTIONS: &str = "default";
+
+struct InfoSection<'a> {
+    name: &'static str,
+    default: bool,
+    gen_lines: Box<dyn Fn() -> Vec<String> + 'a>,
+}
+
+#[derive(Default)]
+pub struct InfoSections<'a> {
+    sections: Vec<InfoSection<'a>>,
+}
+
+impl<'a> InfoSections<'a> {
+    // The lines should be in the format of `key:value`.
+    pub fn register<F>(&mut self, name: &'static str, default: bool, gen_lines: F)
+    where
+        F: Fn() -> Vec<String> + 'a,
+    {
+        self.sections.push(InfoSection {
+            name,
+            default,
+            gen_lines: Box::new(gen_lines),
+        });
+    }
+
+    // The section names are case-insensitive and the unknown ones are ignored.
+    pub fn gen_info(&self, names: &[String]) -> String {
+        let names: Vec<String> = names.iter().map(|name| name.to_lowercase()).collect();
+        let with_default = names.is_empty() || names.iter().any(|name| name == DEFAULT_SECTIONS);
+        let selected = |section: &InfoSection| {
+            (with_default && section.default)
+                || names
+                    .iter()
+                    .any(|name| name == ALL_SECTIONS || name == section.name)
+        };
+        self.sections
+            .iter()
+            .filter(|section| selected(section))
+            .map(|section| {
+                let mut lines = vec![format!("# {}", gen_title(section.name))];
+                lines.extend((section.gen_lines)());
+                lines.join("\r\n") + "\r\n"
+            })
+            .collect::<Vec<String>>()
+            .join("\r\n")
+    }
+}
+
+fn gen_title(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_sections<'a>() -> InfoSections<'a> {
+        let mut sections = InfoSections::default();
+        sections.register("server", true, || vec!["version:0.1".to_string()]);
+        sections.register("clusters", false, || {
+            vec![
+                "local_clusters:1".to_string(),
+                "remote_clusters:0".to_string(),
+            ]
+        });
+        sections
+    }
+
+    #[test]
+    fn test_gen_info() {
+        let sections = gen_sections();
+        assert_eq!(sections.gen_info(&[]), "# Server\r\nversion:0.1\r\n");
+        assert_eq!(
+            sections.gen_info(&["default".to_string()]),
+            "# Server\r\nversion:0.1\r\n"
+        );
+        assert_eq!(
+            sections.gen_info(&["CLUSTERS".to_string()]),
+            "# Clusters\r\nlocal_clusters:1\r\nremote_clusters:0\r\n"
+        );
+        assert_eq!(
+            sections.gen_info(&["all".to_string()]),
+            "# Server\r\nversion:0.1\r\n\r\n# Clusters\r\nlocal_clusters:1\r\nremote_clusters:0\r\n"
+        );
+        assert_eq!(sections.gen_info(&["unknown".to_string()]), "");
+    }
+}
@@ -0,0 +1,127 @@
+use super::command::lowercase_cmd_name;
+use super::command_table::COMMAND_KEY_SPECS;
+
+// The key positions and the `readonly` and `write` flags returned by the `COMMAND` of Redis.
+// Negative `last_key` counts from the end of the command.
+// The keys of the `movable_keys` commands can't be found by the positions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeySpec {
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+    pub movable_keys: bool,
+    pub readonly: bool,
+    pub write: bool,
+}
+
+impl KeySpec {
+    pub fn has_fixed_keys(&self) -> bool {
+        !self.movable_keys && self.first_key > 0 && self.step > 0
+    }
+
+    pub fn get_key_indices(&self, cmd_len: usize) -> Vec<usize> {
+        if !self.has_fixed_keys() {
+            return vec![];
+        }
+        let last_key = if self.last_key < 0 {
+            cmd_len as i64 + self.last_key
+        } else {
+            self.last_key
+        };
+        if last_key < self.first_key {
+            return vec![];
+        }
+        (self.first_key..=last_key)
+            .step_by(self.step as usize)
+            .map(|i| i as usize)
+            .collect()
+    }
+}
+
+pub fn get_key_spec(cmd_name: &[u8]) -> Option<&'static KeySpec> {
+    let stack_cmd_name = lowercase_cmd_name(cmd_name)?;
+    let cmd_name: &[u8] = &stack_cmd_name;
+    COMMAND_KEY_SPECS
+        .binary_search_by(|(name, _)| name.as_bytes().cmp(cmd_name))
+        .ok()
+        .map(|i| &COMMAND_KEY_SPECS[i].1)
+}
+
+// The movable keys of these commands follow their `numkeys` argument,
+// e.g. ZINTER numkeys key [key ...] [WEIGHTS ...]
+pub fn get_numkeys_index(cmd_name: &[u8]) -> Option<usize> {
+    let stack_cmd_name = lowercase_cmd_name(cmd_name)?;
+    let cmd_name: &[u8] = &stack_cmd_name;
+    match cmd_name {
+        b"zinter" | b"zunion" | b"zdiff" | b"zintercard" | b"sintercard" => Some(1),
+        _ => None,
+    }
+}
+
+// Used by `--dump-command-table` to verify the table.
+pub fn dump_command_table() -> String {
+    let lines: Vec<String> = COMMAND_KEY_SPECS
+        .iter()
+        .map(|(name, spec)| {
+            let mut flags = String::new();
+            if spec.movable_keys {
+                flags.push_str(" movablekeys");
+            }
+            if spec.readonly {
+                flags.push_str(" readonly");
+            }
+            if spec.write {
+                flags.push_str(" write");
+            }
+            format!(
+                "{} {} {} {}{}",
+                name, spec.first_key, spec.last_key, spec.step, flags
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_table_sorted() {
+        for pair in COMMAND_KEY_SPECS.windows(2) {
+            assert!(pair[0].0 < pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_get_key_spec() {
+        let spec = get_key_spec(b"Get").unwrap();
+        assert_eq!(spec.get_key_indices(2), vec![1]);
+        let spec = get_key_spec(b"MSET").unwrap();
+        assert_eq!(spec.get_key_indices(5), vec![1, 3]);
+        let spec = get_key_spec(b"blpop").unwrap();
+        assert_eq!(spec.get_key_indices(4), vec![1, 2]);
+        let spec = get_key_spec(b"object").unwrap();
+        assert_eq!(spec.get_key_indices(3), vec![2]);
+        // Invalid commands will be rejected when the keys are missing.
+        assert_eq!(spec.get_key_indices(2), vec![2]);
+        let spec = get_key_spec(b"del").unwrap();
+        assert_eq!(spec.get_key_indices(1), Vec::<usize>::new());
+        let spec = get_key_spec(b"eval").unwrap();
+        assert!(!spec.has_fixed_keys());
+        assert!(get_key_spec(b"ping").unwrap().get_key_indices(1).is_empty());
+        assert!(get_key_spec(b"unknown").is_none());
+    }
+
+    #[test]
+    fn test_command_flags() {
+        for (name, spec) in COMMAND_KEY_SPECS.iter() {
+            assert!(!(spec.readonly && spec.write), "{}", name);
+        }
+        assert!(get_key_spec(b"lpos").unwrap().readonly);
+        assert!(get_key_spec(b"XINFO").unwrap().readonly);
+        assert!(get_key_spec(b"set").unwrap().write);
+        let ping = get_key_spec(b"ping").unwrap();
+        assert!(!ping.readonly && !ping.write);
+    }
+}
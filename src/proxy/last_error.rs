@@ -0,0 +1,124 @@
+use super::slowlog::SlowlogRecord;
+use crate::protocol::{Array, BulkStr, Resp, RespPacket, RespVec};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+
+// The number of the errors kept in each session for `UMCTL LASTERR`.
+pub const LAST_ERROR_NUMBER: usize = 8;
+
+struct ErrorRecord {
+    created: DateTime<Utc>,
+    command: Vec<String>,
+    error: String,
+    // The owner of the slot of the key when the error is replied.
+    backend: Option<String>,
+}
+
+#[derive(Default)]
+pub struct LastErrors {
+    records: VecDeque<ErrorRecord>,
+}
+
+impl LastErrors {
+    pub fn record(
+        &mut self,
+        created: DateTime<Utc>,
+        request: Option<&RespPacket>,
+        error: &RespPacket,
+        backend: Option<String>,
+    ) {
+        let error = match error.to_resp_slice() {
+            Resp::Error(err) => String::from_utf8_lossy(err).to_string(),
+            others => format!("{:?}", others),
+        };
+        if self.records.len() >= LAST_ERROR_NUMBER {
+            self.records.pop_front();
+        }
+        self.records.push_back(ErrorRecord {
+            created,
+            command: request
+                .map(SlowlogRecord::get_brief_command)
+                .unwrap_or_default(),
+            error,
+            backend,
+        });
+    }
+
+    // The latest error comes first.
+    pub fn to_resp(&self) -> RespVec {
+        let elements = self
+            .records
+            .iter()
+            .rev()
+            .map(|record| {
+                let lines = vec![
+                    format!("created: {}", record.created.to_rfc3339()),
+                    format!("command: {}", record.command.join(" ")),
+                    format!("error: {}", record.error),
+                    format!("backend: {}", record.backend.as_deref().unwrap_or("")),
+                ];
+                Resp::Arr(Array::Arr(
+                    lines
+                        .into_iter()
+                        .map(|line| Resp::Bulk(BulkStr::Str(line.into_bytes())))
+                        .collect(),
+                ))
+            })
+            .collect();
+        Resp::Arr(Array::Arr(elements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_request(key: &str) -> RespPacket {
+        RespPacket::from_resp_vec(Resp::Arr(Array::Arr(vec![
+            Resp::Bulk(BulkStr::Str(b"GET".to_vec())),
+            Resp::Bulk(BulkStr::Str(key.as_bytes().to_vec())),
+        ])))
+    }
+
+    fn get_lines(resp: &RespVec) -> Vec<Vec<String>> {
+        match resp {
+            Resp::Arr(Array::Arr(records)) => records
+                .iter()
+                .map(|record| match record {
+                    Resp::Arr(Array::Arr(lines)) => lines
+                        .iter()
+                        .map(|line| match line {
+                            Resp::Bulk(BulkStr::Str(s)) => String::from_utf8(s.clone()).unwrap(),
+                            _ => panic!("unexpected line"),
+                        })
+                        .collect(),
+                    _ => panic!("unexpected record"),
+                })
+                .collect(),
+            _ => panic!("unexpected reply"),
+        }
+    }
+
+    #[test]
+    fn test_last_errors() {
+        let mut last_errors = LastErrors::default();
+        assert!(get_lines(&last_errors.to_resp()).is_empty());
+
+        let error = RespPacket::from_resp_vec(Resp::Error(b"UM-BACKEND overloaded".to_vec()));
+        for i in 0..LAST_ERROR_NUMBER + 2 {
+            let request = gen_request(&format!("key{}", i));
+            let backend = Some("127.0.0.1:6379".to_string());
+            last_errors.record(Utc::now(), Some(&request), &error, backend);
+        }
+        last_errors.record(Utc::now(), None, &error, None);
+
+        let records = get_lines(&last_errors.to_resp());
+        assert_eq!(records.len(), LAST_ERROR_NUMBER);
+        assert_eq!(records[0][1], "command: ");
+        assert_eq!(records[0][3], "backend: ");
+        assert_eq!(records[1][1], "command: GET key9");
+        assert_eq!(records[1][2], "error: UM-BACKEND overloaded");
+        assert_eq!(records[1][3], "backend: 127.0.0.1:6379");
+        assert_eq!(records[7][1], "command: GET key3");
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const INITIAL_LIMIT: usize = 64;
+const MIN_LIMIT: usize = 8;
+const SMOOTHING: f64 = 0.2;
+// Number of samples for the long term rtt.
+const LONG_RTT_WINDOW: f64 = 600.0;
+
+struct GradientState {
+    estimated_limit: f64,
+    long_rtt: Option<f64>,
+}
+
+// Adjusts the allowed in-flight commands according to the observed latency,
+// based on the gradient algorithm from netflix/concurrency-limits.
+// The limit decreases when the latency of a batch is higher than the long term one.
+// Zero `max_limit` means no limitation.
+pub struct GradientLimiter {
+    max_limit: usize,
+    limit: AtomicUsize,
+    inflight: AtomicUsize,
+    state: Mutex<GradientState>,
+}
+
+impl GradientLimiter {
+    pub fn new(max_limit: usize) -> Self {
+        let initial_limit = if max_limit == 0 {
+            0
+        } else {
+            INITIAL_LIMIT.min(max_limit).max(MIN_LIMIT.min(max_limit))
+        };
+        Self {
+            max_limit,
+            limit: AtomicUsize::new(initial_limit),
+            inflight: AtomicUsize::new(0),
+            state: Mutex::new(GradientState {
+                estimated_limit: initial_limit as f64,
+                long_rtt: None,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_limit != 0
+    }
+
+    pub fn get_limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    pub fn get_inflight(&self) -> usize {
+        self.inflight.load(Ordering::Relaxed)
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let prev = self.inflight.fetch_add(1, Ordering::SeqCst);
+        if prev >= self.get_limit() {
+            self.inflight.fetch_sub(1, Ordering::SeqCst);
+            return false;
+        }
+        true
+    }
+
+    pub fn release(&self, n: usize) {
+        if !self.is_enabled() || n == 0 {
+            return;
+        }
+        let _ = self
+            .inflight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |inflight| {
+                Some(inflight.saturating_sub(n))
+            });
+    }
+
+    pub fn on_sample(&self, rtt: Duration) {
+        if !self.is_enabled() {
+            return;
+        }
+        let rtt = rtt.as_secs_f64();
+        if rtt <= 0.0 {
+            return;
+        }
+
+        let mut state = self.state.lock().expect("GradientLimiter::on_sample");
+        let mut long_rtt = match state.long_rtt {
+            Some(long_rtt) => long_rtt + (rtt - long_rtt) / LONG_RTT_WINDOW,
+            None => rtt,
+        };
+        // Let the long term rtt recover quickly after the latency goes down.
+        if long_rtt / rtt > 2.0 {
+            long_rtt *= 0.95;
+        }
+        state.long_rtt = Some(long_rtt);
+
+        let limit = state.estimated_limit;
+        let gradient = (long_rtt / rtt).clamp(0.5, 1.0);
+        let queue_size = limit.sqrt();
+        let new_limit = limit * gradient + queue_size;
+        let new_limit = limit * (1.0 - SMOOTHING) + new_limit * SMOOTHING;
+        let new_limit = new_limit
+            .max(MIN_LIMIT.min(self.max_limit) as f64)
+            .min(self.max_limit as f64);
+        state.estimated_limit = new_limit;
+        self.limit.store(new_limit as usize, Ordering::Relaxed);
+    }
+}
+
+// Releases the permits of a batch that are not released one by one,
+// e.g. when the connection future is dropped.
+pub struct BatchPermits<'a> {
+    limiter: &'a GradientLimiter,
+    count: usize,
+}
+
+impl<'a> BatchPermits<'a> {
+    pub fn new(limiter: &'a GradientLimiter, count: usize) -> Self {
+        Self { limiter, count }
+    }
+
+    pub fn add(&mut self, count: usize) {
+        self.count += count;
+    }
+
+    pub fn release_one(&mut self) {
+        if self.count > 0 {
+            self.count -= 1;
+            self.limiter.release(1);
+        }
+    }
+
+    // The tasks will be retried and keep their permits.
+    pub fn forget(mut self) {
+        self.count = 0;
+    }
+}
+
+impl<'a> Drop for BatchPermits<'a> {
+    fn drop(&mut self) {
+        self.limiter.release(self.count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_limiter() {
+        let limiter = GradientLimiter::new(0);
+        for _ in 0..1000 {
+            assert!(limiter.try_acquire());
+        }
+        limiter.on_sample(Duration::from_millis(100));
+        assert_eq!(limiter.get_inflight(), 0);
+    }
+
+    #[test]
+    fn test_acquire_and_release() {
+        let limiter = GradientLimiter::new(10);
+        assert_eq!(limiter.get_limit(), 10);
+        for _ in 0..10 {
+            assert!(limiter.try_acquire());
+        }
+        assert!(!limiter.try_acquire());
+        limiter.release(3);
+        assert_eq!(limiter.get_inflight(), 7);
+        assert!(limiter.try_acquire());
+        limiter.release(100);
+        assert_eq!(limiter.get_inflight(), 0);
+    }
+
+    #[test]
+    fn test_batch_permits() {
+        let limiter = GradientLimiter::new(10);
+        for _ in 0..5 {
+            assert!(limiter.try_acquire());
+        }
+        let mut permits = BatchPermits::new(&limiter, 3);
+        permits.release_one();
+        assert_eq!(limiter.get_inflight(), 4);
+        drop(permits);
+        assert_eq!(limiter.get_inflight(), 2);
+
+        let mut permits = BatchPermits::new(&limiter, 1);
+        permits.add(1);
+        permits.forget();
+        assert_eq!(limiter.get_inflight(), 2);
+    }
+
+    #[test]
+    fn test_limit_adjustment() {
+        let limiter = GradientLimiter::new(1000);
+        let initial_limit = limiter.get_limit();
+        for _ in 0..100 {
+            limiter.on_sample(Duration::from_millis(1));
+        }
+        let stable_limit = limiter.get_limit();
+        assert!(stable_limit > initial_limit);
+
+        for _ in 0..100 {
+            limiter.on_sample(Duration::from_millis(50));
+        }
+        let degraded_limit = limiter.get_limit();
+        assert!(degraded_limit < stable_limit);
+        assert!(degraded_limit >= MIN_LIMIT);
+    }
+}
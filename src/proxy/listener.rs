@@ -0,0 +1,127 @@
+use crate::common::platform::{set_reuse_address, set_reuse_port};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::cmp::min;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+pub const ACCEPT_TICK: Duration = Duration::from_millis(100);
+// The storm mode is entered after reaching the limit in all the ticks of one second.
+const STORM_DETECT_TICKS: usize = 10;
+
+// The connections exceeding the backlog in the kernel will be dropped
+// and the clients will retry with backoff.
+// With `reuse_port`, the kernel will balance the connections between the listeners
+// binding the same address.
+pub fn bind_listener(
+    address: SocketAddr,
+    backlog: usize,
+    reuse_port: bool,
+) -> io::Result<TcpListener> {
+    let domain = match address {
+        SocketAddr::V4(_) => Domain::ipv4(),
+        SocketAddr::V6(_) => Domain::ipv6(),
+    };
+    let socket = Socket::new(domain, Type::stream(), Some(Protocol::tcp()))?;
+    set_reuse_address(&socket)?;
+    if reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(&SockAddr::from(address))?;
+    socket.listen(min(backlog, i32::MAX as usize) as i32)?;
+    let listener = socket.into_tcp_listener();
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+// Stops accepting for a while so that the reconnect storms
+// will not stall the existing sessions.
+pub struct AcceptLimiter {
+    max_accepts_per_tick: usize,
+    storm_pause_time: Duration,
+    tick_start: Instant,
+    accepts: usize,
+    full_ticks: usize,
+}
+
+impl AcceptLimiter {
+    pub fn new(max_accepts_per_tick: usize, storm_pause_time: Duration, now: Instant) -> Self {
+        Self {
+            max_accepts_per_tick,
+            storm_pause_time,
+            tick_start: now,
+            accepts: 0,
+            full_ticks: 0,
+        }
+    }
+
+    // Returns the time to wait before accepting the next connection.
+    pub fn on_accept(&mut self, now: Instant) -> Option<Duration> {
+        if self.max_accepts_per_tick == 0 {
+            return None;
+        }
+
+        let elapsed = now.saturating_duration_since(self.tick_start);
+        if elapsed >= ACCEPT_TICK {
+            if self.accepts < self.max_accepts_per_tick || elapsed >= ACCEPT_TICK * 2 {
+                self.full_ticks = 0;
+            }
+            self.tick_start = now;
+            self.accepts = 0;
+        }
+
+        self.accepts += 1;
+        if self.accepts < self.max_accepts_per_tick {
+            return None;
+        }
+
+        self.full_ticks += 1;
+        if self.storm_pause_time != Duration::from_secs(0) && self.full_ticks >= STORM_DETECT_TICKS
+        {
+            warn!(
+                "connection storm detected, stop accepting for {:?}",
+                self.storm_pause_time
+            );
+            self.full_ticks = 0;
+            return Some(self.storm_pause_time);
+        }
+        let tick_end = self.tick_start + ACCEPT_TICK;
+        Some(tick_end.saturating_duration_since(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_limiter() {
+        let now = Instant::now();
+        let pause = Duration::from_secs(3);
+
+        let mut limiter = AcceptLimiter::new(0, pause, now);
+        assert!((0..100).all(|_| limiter.on_accept(now).is_none()));
+
+        let mut limiter = AcceptLimiter::new(2, pause, now);
+        let mut tick = now;
+        for _ in 0..STORM_DETECT_TICKS - 1 {
+            assert_eq!(limiter.on_accept(tick), None);
+            assert_eq!(limiter.on_accept(tick), Some(ACCEPT_TICK));
+            tick += ACCEPT_TICK;
+        }
+        assert_eq!(limiter.on_accept(tick), None);
+        assert_eq!(limiter.on_accept(tick), Some(pause));
+
+        // The slow accepts reset the storm detection.
+        tick += pause;
+        assert_eq!(limiter.on_accept(tick), None);
+        tick += ACCEPT_TICK * 3;
+        assert_eq!(limiter.on_accept(tick), None);
+        assert_eq!(
+            limiter.on_accept(tick + ACCEPT_TICK / 2),
+            Some(ACCEPT_TICK / 2)
+        );
+        assert_eq!(limiter.full_ticks, 1);
+    }
+}
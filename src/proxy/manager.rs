@@ -1,19 +1,29 @@
 use super::backend::{BackendError, CmdTask, ConnFactory, IntoTask};
 use super::blocking::{
     gen_basic_blocking_sender_factory, gen_blocking_sender_factory, BasicBlockingSenderFactory,
-    BlockingBackendSenderFactory, BlockingCmdTaskSender, BlockingMap, CounterTask,
+    BlockingBackendSenderFactory, BlockingCmdTaskSender, BlockingHintTask, BlockingMap,
+    CounterTask,
 };
-use super::cluster::{ClusterBackendMap, ClusterMetaError, ClusterSendError, ClusterTag};
+use super::cluster::{
+    ClusterBackendMap, ClusterMetaError, ClusterSendError, ClusterTag, SlotOwner,
+};
+use super::info::InfoSections;
+use super::meta_lock::{MetaLock, MetaLockStats};
+use super::percore::CoreLocalSwap;
 use super::reply::{DecompressCommitHandlerFactory, ReplyCommitHandlerFactory};
 use super::sender::{
     gen_migration_sender_factory, gen_sender_factory, BackendSenderFactory, CmdTaskSender,
-    CmdTaskSenderFactory, MigrationBackendSenderFactory,
+    CmdTaskSenderFactory, CmdTaskSenderWrapper, MigrationBackendSenderFactory, WrappedSender,
+    WrappedSenderFactory,
 };
 use super::service::ServerProxyConfig;
 use super::session::{CmdCtx, CmdCtxFactory};
+use super::slot_hook::{diff_owned_slots, get_owned_slots, OwnedSlots, SlotOwnershipHook};
 use super::slowlog::TaskEvent;
-use crate::common::cluster::{ClusterName, MigrationTaskMeta, SlotRangeTag};
-use crate::common::config::ClusterConfig;
+use crate::common::cluster::{ClusterName, MigrationTaskEvents, MigrationTaskMeta, SlotRangeTag};
+use crate::common::config::{
+    BackendFlavor, ClusterConfig, TtlPolicy, UnknownCommandPolicy, WriteAckPolicy,
+};
 use crate::common::proto::ProxyClusterMeta;
 use crate::common::response;
 use crate::common::track::TrackedFutureRegistry;
@@ -24,9 +34,11 @@ use crate::protocol::{Array, BulkStr, RedisClientFactory, Resp, RespPacket, Resp
 use crate::replication::manager::ReplicatorManager;
 use crate::replication::replicator::ReplicatorMeta;
 use arc_swap::{ArcSwap, Lease};
+use std::convert::TryFrom;
 use std::num::NonZeroUsize;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
 
 pub struct MetaMap<S: CmdTaskSender, P: CmdTaskSender, T>
 where
@@ -74,21 +86,34 @@ type MigrationSenderFactory<C> =
     MigrationBackendSenderFactory<DecompressCommitHandlerFactory<CmdCtx, C>, C>;
 type MigrationProxySenderFactory<C> = MigrationBackendSenderFactory<ReplyCommitHandlerFactory, C>;
 
+// The task type of the senders which could be wrapped by `CmdTaskSenderWrapper`.
+pub type BackendTask = BlockingHintTask<CmdCtx>;
+
 type ProxyMetaMap<C> = MetaMap<
-    <SenderFactory<C> as CmdTaskSenderFactory>::Sender,
+    WrappedSender<<SenderFactory<C> as CmdTaskSenderFactory>::Sender>,
     <PeerSenderFactory<C> as CmdTaskSenderFactory>::Sender,
     CmdCtx,
 >;
 pub type SharedMetaMap<C> = Arc<ArcSwap<ProxyMetaMap<C>>>;
 
+pub enum AuthName {
+    Cluster,
+    // The cluster of the user
+    User(ClusterName),
+    // The current cluster of the session
+    Password(ClusterName),
+}
+
 pub struct MetaManager<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> {
     config: Arc<ServerProxyConfig>,
     // Now replicator is not in meta_map, if later we need consistency
     // between replication metadata and other metadata, we should put that
     // inside meta_map.
     meta_map: SharedMetaMap<C>,
+    // Only has the copies of `meta_map` in the thread-per-core mode.
+    core_meta_maps: CoreLocalSwap<ProxyMetaMap<C>>,
     epoch: AtomicU64,
-    lock: Mutex<()>, // This is the write lock for `epoch`, `cluster`, and `task`.
+    lock: MetaLock, // This is the write lock for `epoch`, `cluster`, and `task`.
     replicator_manager: ReplicatorManager<F>,
     migration_manager: MigrationManager<
         F,
@@ -98,8 +123,15 @@ pub struct MetaManager<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>>
     >,
     sender_factory: SenderFactory<C>,
     peer_sender_factory: PeerSenderFactory<C>,
+    sender_wrappers: Vec<Box<dyn CmdTaskSenderWrapper<BackendTask>>>,
     blocking_map: Arc<BlockingMap<BasicSenderFactory<C>, BlockingTaskRetrySender<C>>>,
     cluster_config: ClusterConfig,
+    slot_hooks: Vec<Box<dyn SlotOwnershipHook>>,
+    // Only maintained when there're any slot hooks.
+    owned_slots: Mutex<OwnedSlots>,
+    // Notifies the sessions of the clusters removed by the last `set_meta`.
+    removed_clusters_sender: watch::Sender<Arc<Vec<ClusterName>>>,
+    removed_clusters_receiver: watch::Receiver<Arc<Vec<ClusterName>>>,
 }
 
 impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C> {
@@ -144,13 +176,21 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
             future_registry.clone(),
         ));
         let cmd_ctx_factory = Arc::new(CmdCtxFactory::default());
+        let core_num = if config.thread_per_core {
+            config.thread_number.get()
+        } else {
+            0
+        };
+        let core_meta_maps = CoreLocalSwap::new(meta_map.clone(), core_num);
         let config_clone = config.clone();
         let cluster_config_clone = cluster_config.clone();
+        let (removed_clusters_sender, removed_clusters_receiver) = watch::channel(Arc::new(vec![]));
         Self {
             config,
             meta_map,
+            core_meta_maps,
             epoch: AtomicU64::new(0),
-            lock: Mutex::new(()),
+            lock: MetaLock::new(),
             replicator_manager: ReplicatorManager::new(
                 client_factory.clone(),
                 future_registry.clone(),
@@ -166,11 +206,25 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
             ),
             sender_factory,
             peer_sender_factory,
+            sender_wrappers: vec![],
             blocking_map,
             cluster_config,
+            slot_hooks: vec![],
+            owned_slots: Mutex::new(OwnedSlots::new()),
+            removed_clusters_sender,
+            removed_clusters_receiver,
         }
     }
 
+    pub fn register_slot_hook(&mut self, hook: Box<dyn SlotOwnershipHook>) {
+        self.slot_hooks.push(hook);
+    }
+
+    // Only applies to the backend senders created by the later `set_meta`.
+    pub fn register_sender_wrapper(&mut self, wrapper: Box<dyn CmdTaskSenderWrapper<BackendTask>>) {
+        self.sender_wrappers.push(wrapper);
+    }
+
     pub fn gen_cluster_nodes(&self, cluster_name: ClusterName) -> String {
         let meta_map = self.meta_map.load();
         let migration_states = meta_map.migration_map.get_states(&cluster_name);
@@ -191,43 +245,71 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
         )
     }
 
+    pub fn gen_cluster_shards(&self, cluster_name: ClusterName) -> Result<RespVec, String> {
+        let meta_map = self.meta_map.load();
+        let migration_states = meta_map.migration_map.get_states(&cluster_name);
+        meta_map.cluster_map.gen_cluster_shards(
+            cluster_name,
+            self.config.announce_address.clone(),
+            &migration_states,
+        )
+    }
+
     pub fn get_clusters(&self) -> Vec<ClusterName> {
         self.meta_map.load().cluster_map.get_clusters()
     }
 
+    pub fn watch_removed_clusters(&self) -> watch::Receiver<Arc<Vec<ClusterName>>> {
+        self.removed_clusters_receiver.clone()
+    }
+
     pub fn set_meta(&self, cluster_meta: ProxyClusterMeta) -> Result<(), ClusterMetaError> {
         let active_redirection = self.config.active_redirection;
 
-        let sender_factory = &self.sender_factory;
+        let sender_factory = WrappedSenderFactory::new(&self.sender_factory, &self.sender_wrappers);
         let peer_sender_factory = &self.peer_sender_factory;
         let migration_manager = &self.migration_manager;
         let cluster_config = &self.cluster_config;
 
-        {
-            let _guard = self.lock.lock().expect("MetaManager::set_meta");
-
-            if cluster_meta.get_epoch() <= self.epoch.load(Ordering::SeqCst)
+        let is_old_epoch = || {
+            cluster_meta.get_epoch() <= self.epoch.load(Ordering::SeqCst)
                 && !cluster_meta.get_flags().force
-            {
+        };
+        if is_old_epoch() {
+            return Err(ClusterMetaError::OldEpoch);
+        }
+
+        // The backend map is the heaviest part so it's built outside the lock.
+        // The replication metadata is sent before the cluster metadata.
+        let master_replicators = self.replicator_manager.get_master_replicators();
+        let cluster_map = ClusterBackendMap::from_cluster_map(
+            &cluster_meta,
+            &sender_factory,
+            peer_sender_factory,
+            active_redirection,
+            cluster_config,
+            &master_replicators,
+            &self.config.announce_address,
+        );
+
+        let (slot_changes, removed_clusters) = {
+            let _guard = self.lock.lock();
+
+            // Check it again in case another update has finished in the meantime.
+            if is_old_epoch() {
                 return Err(ClusterMetaError::OldEpoch);
             }
 
             let old_meta_map = self.meta_map.load();
-            let cluster_map = ClusterBackendMap::from_cluster_map(
-                &cluster_meta,
-                sender_factory,
-                peer_sender_factory,
-                active_redirection,
-                cluster_config,
-            );
             let (migration_map, new_tasks) = migration_manager.create_new_migration_map(
                 &old_meta_map.migration_map,
                 cluster_meta.get_local(),
                 cluster_meta.get_configs(),
                 self.blocking_map.clone(),
             );
+            let removed_clusters = old_meta_map.cluster_map.get_removed_clusters(&cluster_map);
 
-            self.meta_map.store(Arc::new(MetaMap {
+            self.core_meta_maps.store(Arc::new(MetaMap {
                 cluster_map,
                 migration_map,
             }));
@@ -235,13 +317,42 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
             self.epoch.store(cluster_meta.get_epoch(), Ordering::SeqCst);
 
             self.migration_manager.run_tasks(new_tasks);
+
+            let slot_changes = if self.slot_hooks.is_empty() {
+                vec![]
+            } else {
+                let new_owned_slots = get_owned_slots(cluster_meta.get_local());
+                let mut owned_slots = self.owned_slots.lock().expect("MetaManager::set_meta");
+                let changes =
+                    diff_owned_slots(&owned_slots, &new_owned_slots, cluster_meta.get_epoch());
+                *owned_slots = new_owned_slots;
+                changes
+            };
+            (slot_changes, removed_clusters)
         };
 
+        if !removed_clusters.is_empty() {
+            info!("clusters removed: {:?}", removed_clusters);
+            if let Err(err) = self
+                .removed_clusters_sender
+                .broadcast(Arc::new(removed_clusters))
+            {
+                error!("failed to notify the removed clusters: {:?}", err);
+            }
+        }
+
+        if !slot_changes.is_empty() {
+            for hook in self.slot_hooks.iter() {
+                hook.on_slots_changed(&slot_changes);
+            }
+        }
+
         Ok(())
     }
 
     pub fn update_replicators(&self, meta: ReplicatorMeta) -> Result<(), ClusterMetaError> {
-        self.replicator_manager.update_replicators(meta)
+        self.replicator_manager
+            .update_replicators(meta, |cluster_name| self.get_backend_flavor(cluster_name))
     }
 
     pub fn get_replication_info(&self) -> RespVec {
@@ -263,6 +374,21 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
         ]))
     }
 
+    pub fn register_info_sections<'a>(&'a self, sections: &mut InfoSections<'a>) {
+        sections.register("clusters", false, move || {
+            self.meta_map.load().cluster_map.gen_clusters_info()
+        });
+        sections.register("backends", false, move || {
+            self.meta_map.load().cluster_map.gen_backends_info()
+        });
+        sections.register("migration", false, move || {
+            self.meta_map.load().migration_map.gen_info_lines()
+        });
+        sections.register("replication", false, move || {
+            self.replicator_manager.gen_info_lines()
+        });
+    }
+
     pub fn handle_switch(
         &self,
         switch_arg: SwitchArg,
@@ -299,9 +425,31 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
         self.meta_map.load().migration_map.get_finished_tasks()
     }
 
+    pub fn get_migration_events(&self) -> Vec<MigrationTaskEvents> {
+        self.migration_manager.get_migration_events()
+    }
+
+    pub fn get_meta_lock_stats(&self) -> MetaLockStats {
+        self.lock.get_stats()
+    }
+
+    pub fn get_migration_progress(&self) -> (usize, usize) {
+        self.meta_map.load().migration_map.get_progress()
+    }
+
+    // In seconds. Zero if no replica has reported the lag.
+    pub fn get_max_replication_lag(&self) -> u64 {
+        self.replicator_manager
+            .get_master_replicators()
+            .values()
+            .filter_map(|replicator| replicator.get_replication_lag())
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn send(&self, cmd_ctx: CmdCtx) {
         let max_redirections = self.config.max_redirections;
-        send_cmd_ctx(&self.meta_map, cmd_ctx, max_redirections);
+        send_cmd_ctx(self.core_meta_maps.local(), cmd_ctx, max_redirections);
     }
 
     pub fn send_sync_task(&self, cmd_ctx: CmdCtx) {
@@ -318,7 +466,8 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
                 }
                 ClusterSendError::ActiveRedirection { task, .. } => {
                     task.set_resp_result(Ok(Resp::Error(
-                        b"unexpected active redirection".to_vec(),
+                        format!("{} unexpected active redirection", response::UM_INTERNAL)
+                            .into_bytes(),
                     )));
                 }
                 other_err => {
@@ -328,20 +477,194 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
         }
     }
 
-    pub fn try_select_cluster(&self, mut cmd_ctx: CmdCtx) -> CmdCtx {
-        let exists = self
-            .meta_map
+    pub fn try_select_cluster(&self, mut cmd_ctx: CmdCtx, auto_select: bool) -> CmdCtx {
+        if let Some(cluster_name) =
+            self.get_selected_cluster(cmd_ctx.get_cluster_name(), auto_select)
+        {
+            cmd_ctx.set_cluster_name(cluster_name);
+        }
+        cmd_ctx
+    }
+
+    // Returns None if the cluster exists or there's no cluster to select.
+    pub fn get_selected_cluster(
+        &self,
+        cluster_name: &ClusterName,
+        auto_select: bool,
+    ) -> Option<ClusterName> {
+        {
+            let meta_map = self.core_meta_maps.local().lease();
+            let cluster_map = &meta_map.cluster_map;
+            if cluster_map.cluster_exists(cluster_name) {
+                return None;
+            }
+            if let Some(new_cluster_name) = cluster_map.get_renamed_cluster(cluster_name) {
+                return Some(new_cluster_name.clone());
+            }
+        }
+        if !auto_select {
+            return None;
+        }
+        self.meta_map.load().cluster_map.auto_select_cluster()
+    }
+
+    pub fn check_auth(
+        &self,
+        cluster_name: &ClusterName,
+        user: Option<&str>,
+        password: Option<&str>,
+    ) -> bool {
+        let meta_map = self.core_meta_maps.local().lease();
+        match meta_map.cluster_map.get_config(cluster_name) {
+            None => true,
+            Some(config) => match (user, password) {
+                (Some(user), Some(password)) => config.auth.check_user(user, password),
+                (Some(_), None) => false,
+                (None, Some(password)) => config.auth.check_password(password),
+                (None, None) => !config.auth.password_required(),
+            },
+        }
+    }
+
+    // Resolves the first argument of AUTH. The cluster names take precedence over the user names.
+    pub fn resolve_auth_name(
+        &self,
+        current_cluster: &ClusterName,
+        name: &str,
+        has_password: bool,
+    ) -> AuthName {
+        let meta_map = self.core_meta_maps.local().lease();
+        let cluster_map = &meta_map.cluster_map;
+        if let Ok(cluster_name) = ClusterName::try_from(name) {
+            if cluster_map.cluster_exists(&cluster_name)
+                || cluster_map.get_renamed_cluster(&cluster_name).is_some()
+            {
+                return AuthName::Cluster;
+            }
+        }
+        if has_password {
+            return match cluster_map.find_user_cluster(name) {
+                Some(cluster_name) => AuthName::User(cluster_name),
+                None => AuthName::Cluster,
+            };
+        }
+        let password_required = match cluster_map.get_config(current_cluster) {
+            Some(config) => config.auth.password_required(),
+            None => false,
+        };
+        if password_required {
+            AuthName::Password(current_cluster.clone())
+        } else {
+            AuthName::Cluster
+        }
+    }
+
+    pub fn get_backend_flavor(&self, cluster_name: &ClusterName) -> BackendFlavor {
+        self.core_meta_maps
+            .local()
             .lease()
             .cluster_map
-            .cluster_exists(cmd_ctx.get_cluster_name());
-        if exists {
-            return cmd_ctx;
-        }
+            .get_config(cluster_name)
+            .map(|config| config.backend_flavor)
+            .unwrap_or_default()
+    }
 
-        if let Some(cluster_name) = self.meta_map.load().cluster_map.auto_select_cluster() {
-            cmd_ctx.set_cluster_name(cluster_name);
+    // The TTL policy and the double writes could not be applied to the transactions.
+    pub fn is_transaction_allowed(&self, cluster_name: &ClusterName) -> bool {
+        self.core_meta_maps
+            .local()
+            .lease()
+            .cluster_map
+            .get_config(cluster_name)
+            .map(|config| {
+                config.ttl_policy == TtlPolicy::Disabled && !config.double_write_enabled()
+            })
+            .unwrap_or(true)
+    }
+
+    pub fn get_unknown_command_policy(&self, cluster_name: &ClusterName) -> UnknownCommandPolicy {
+        self.core_meta_maps
+            .local()
+            .lease()
+            .cluster_map
+            .get_config(cluster_name)
+            .map(|config| config.unknown_command_policy)
+            .unwrap_or_default()
+    }
+
+    pub fn get_write_ack_policy(&self, cluster_name: &ClusterName) -> WriteAckPolicy {
+        self.core_meta_maps
+            .local()
+            .lease()
+            .cluster_map
+            .get_config(cluster_name)
+            .map(|config| config.write_ack_policy)
+            .unwrap_or_default()
+    }
+
+    pub fn get_local_backend(&self, cluster_name: &ClusterName, slot: usize) -> Option<String> {
+        self.core_meta_maps
+            .local()
+            .lease()
+            .cluster_map
+            .get_local_backend(cluster_name, slot)
+            .map(|address| address.to_string())
+    }
+
+    pub fn get_slot_owner(&self, cluster_name: &ClusterName, slot: usize) -> Option<SlotOwner> {
+        self.core_meta_maps
+            .local()
+            .lease()
+            .cluster_map
+            .get_slot_owner(cluster_name, slot)
+    }
+
+    // Returns the backend node only when the commands of the slot could be sent to it directly,
+    // i.e. the slot is not being migrated and not routed to a canary cluster.
+    pub fn get_direct_node(&self, cluster_name: &ClusterName, slot: usize) -> Option<String> {
+        let meta_map = self.core_meta_maps.local().lease();
+        if Self::is_slot_redirected(&meta_map, cluster_name, slot) {
+            return None;
         }
-        cmd_ctx
+        match meta_map.cluster_map.get_slot_owner(cluster_name, slot)? {
+            SlotOwner::Local(node) => Some(node),
+            SlotOwner::Remote(_) => None,
+        }
+    }
+
+    // The keys of the migrating or canary slots could be on different nodes.
+    pub fn is_migrating_or_canary(&self, cluster_name: &ClusterName, slot: usize) -> bool {
+        let meta_map = self.core_meta_maps.local().lease();
+        Self::is_slot_redirected(&meta_map, cluster_name, slot)
+    }
+
+    fn is_slot_redirected(
+        meta_map: &ProxyMetaMap<C>,
+        cluster_name: &ClusterName,
+        slot: usize,
+    ) -> bool {
+        meta_map.migration_map.contains_slot(cluster_name, slot)
+            || meta_map
+                .cluster_map
+                .get_canary_cluster(cluster_name, slot)
+                .is_some()
+    }
+
+    pub fn get_local_nodes(&self, cluster_name: &ClusterName) -> Option<Vec<String>> {
+        self.core_meta_maps
+            .local()
+            .lease()
+            .cluster_map
+            .get_local_nodes(cluster_name)
+    }
+
+    pub fn get_moved_address(&self, cluster_name: &ClusterName) -> Option<String> {
+        self.core_meta_maps
+            .local()
+            .lease()
+            .cluster_map
+            .get_moved_address(cluster_name)
+            .map(|address| address.to_string())
     }
 
     pub fn get_epoch(&self) -> u64 {
@@ -351,10 +674,18 @@ impl<F: RedisClientFactory, C: ConnFactory<Pkt = RespPacket>> MetaManager<F, C>
 
 pub fn send_cmd_ctx<C: ConnFactory<Pkt = RespPacket>>(
     meta_map: &SharedMetaMap<C>,
-    cmd_ctx: CmdCtx,
+    mut cmd_ctx: CmdCtx,
     max_redirections: Option<NonZeroUsize>,
 ) {
     let meta_map = meta_map.lease();
+    if let Some(slot) = cmd_ctx.get_slot() {
+        if let Some(canary_cluster) = meta_map
+            .cluster_map
+            .get_canary_cluster(cmd_ctx.get_cluster_name(), slot)
+        {
+            cmd_ctx.set_cluster_name(canary_cluster.clone());
+        }
+    }
     let mut cmd_ctx = match meta_map.migration_map.send(cmd_ctx) {
         Ok(()) => return,
         Err(e) => match e {
@@ -414,24 +745,23 @@ fn send_cmd_ctx_to_remote_directly<C: ConnFactory<Pkt = RespPacket>>(
     address: String,
     max_redirections: Option<NonZeroUsize>,
 ) {
-    let times = cmd_ctx
-        .get_redirection_times()
-        .or_else(|| max_redirections.map(|n| n.get() - 1));
+    let times = match get_next_redirection_times(cmd_ctx.get_redirection_times(), max_redirections)
+    {
+        Ok(times) => times,
+        Err(err) => {
+            cmd_ctx.set_resp_result(Ok(Resp::Error(err.to_string().into_bytes())));
+            return;
+        }
+    };
     if let Some(times) = times {
-        let times = match times.checked_sub(1) {
-            None => {
-                cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    response::ERR_TOO_MANY_REDIRECTIONS.to_string().into_bytes(),
-                )));
-                return;
-            }
-            Some(times) => times,
-        };
-
         let res = cmd_ctx.wrap_cmd(vec![b"UMFORWARD".to_vec(), times.to_string().into_bytes()]);
         if !res {
             cmd_ctx.set_resp_result(Ok(Resp::Error(
-                b"failed to wrap command for redirections".to_vec(),
+                format!(
+                    "{} failed to wrap command for redirections",
+                    response::UM_INTERNAL
+                )
+                .into_bytes(),
             )));
             return;
         }
@@ -448,6 +778,22 @@ fn send_cmd_ctx_to_remote_directly<C: ConnFactory<Pkt = RespPacket>>(
     }
 }
 
+// Returns the redirection times left for the next proxy,
+// or None if the redirections are not limited.
+fn get_next_redirection_times(
+    redirection_times: Option<usize>,
+    max_redirections: Option<NonZeroUsize>,
+) -> Result<Option<usize>, &'static str> {
+    let times = redirection_times.or_else(|| max_redirections.map(|n| n.get() - 1));
+    match times {
+        None => Ok(None),
+        Some(times) => times
+            .checked_sub(1)
+            .map(Some)
+            .ok_or(response::ERR_TOO_MANY_REDIRECTIONS),
+    }
+}
+
 pub struct BlockingTaskRetrySender<C: ConnFactory<Pkt = RespPacket>> {
     meta_map: SharedMetaMap<C>,
     max_redirections: Option<NonZeroUsize>,
@@ -472,3 +818,40 @@ impl<C: ConnFactory<Pkt = RespPacket>> CmdTaskSender for BlockingTaskRetrySender
 }
 
 impl<C: ConnFactory<Pkt = RespPacket>> BlockingCmdTaskSender for BlockingTaskRetrySender<C> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited_redirections() {
+        assert_eq!(get_next_redirection_times(None, None), Ok(None));
+    }
+
+    #[test]
+    fn test_redirections_within_limit() {
+        let max = NonZeroUsize::new(3);
+        // The first redirection from the proxy receiving the command.
+        assert_eq!(get_next_redirection_times(None, max), Ok(Some(1)));
+        // The command forwarded by UMFORWARD from another proxy.
+        assert_eq!(get_next_redirection_times(Some(1), max), Ok(Some(0)));
+        assert_eq!(get_next_redirection_times(Some(5), None), Ok(Some(4)));
+    }
+
+    #[test]
+    fn test_too_many_redirections() {
+        assert_eq!(
+            get_next_redirection_times(Some(0), NonZeroUsize::new(3)),
+            Err(response::ERR_TOO_MANY_REDIRECTIONS)
+        );
+        assert_eq!(
+            get_next_redirection_times(Some(0), None),
+            Err(response::ERR_TOO_MANY_REDIRECTIONS)
+        );
+        // Only one hop is allowed so the command could not be redirected.
+        assert_eq!(
+            get_next_redirection_times(None, NonZeroUsize::new(1)),
+            Err(response::ERR_TOO_MANY_REDIRECTIONS)
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use super::service::ServerProxyConfig;
+use crate::common::allocator::allocator_info;
+use dashmap::DashMap;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const TOP_CONSUMER_NUM: usize = 10;
+
+pub struct SessionMemoryUsage {
+    session_id: usize,
+    peer: String,
+    used: AtomicUsize,
+    shed: AtomicBool,
+}
+
+impl SessionMemoryUsage {
+    fn get_used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+// Approximate memory of the session buffers and the queued commands and replies.
+pub struct MemoryTracker {
+    config: Arc<ServerProxyConfig>,
+    total: AtomicUsize,
+    sessions: DashMap<usize, Arc<SessionMemoryUsage>>,
+}
+
+impl MemoryTracker {
+    pub fn new(config: Arc<ServerProxyConfig>) -> Self {
+        Self {
+            config,
+            total: AtomicUsize::new(0),
+            sessions: DashMap::new(),
+        }
+    }
+
+    pub fn register(tracker: Arc<MemoryTracker>, session_id: usize, peer: String) -> SessionMemory {
+        let usage = Arc::new(SessionMemoryUsage {
+            session_id,
+            peer,
+            used: AtomicUsize::new(0),
+            shed: AtomicBool::new(false),
+        });
+        tracker.sessions.insert(session_id, usage.clone());
+        SessionMemory { tracker, usage }
+    }
+
+    pub fn get_total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn get_session_num(&self) -> usize {
+        self.sessions.len()
+    }
+
+    // Returns (session_id, peer, used) in descending order of the used memory.
+    pub fn get_top_consumers(&self, n: usize) -> Vec<(usize, String, usize)> {
+        let mut consumers: Vec<(usize, String, usize)> = self
+            .sessions
+            .iter()
+            .map(|entry| {
+                let usage = entry.value();
+                (usage.session_id, usage.peer.clone(), usage.get_used())
+            })
+            .collect();
+        consumers.sort_by_key(|c| Reverse(c.2));
+        consumers.truncate(n);
+        consumers
+    }
+
+    // The `memory` section of `INFO`.
+    pub fn gen_info_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("used_memory_sessions:{}", self.get_total()),
+            format!("max_memory:{}", self.config.get_max_memory()),
+            format!("connected_sessions:{}", self.get_session_num()),
+        ];
+        lines.extend(allocator_info());
+        for (i, (session_id, peer, used)) in self
+            .get_top_consumers(TOP_CONSUMER_NUM)
+            .into_iter()
+            .enumerate()
+        {
+            lines.push(format!(
+                "top_consumer_{}:session_id={},peer={},used={}",
+                i, session_id, peer, used
+            ));
+        }
+        lines
+    }
+
+    fn update(&self, usage: &SessionMemoryUsage, used: usize) {
+        let old = usage.used.swap(used, Ordering::Relaxed);
+        if used >= old {
+            self.total.fetch_add(used - old, Ordering::Relaxed);
+        } else {
+            self.total.fetch_sub(old - used, Ordering::Relaxed);
+        }
+        if used > old {
+            self.try_shed();
+        }
+    }
+
+    // Marks the largest consumer which will then be closed by its session.
+    fn try_shed(&self) {
+        let max_memory = self.config.get_max_memory() as usize;
+        if max_memory == 0 || self.get_total() <= max_memory {
+            return;
+        }
+        let largest = self
+            .sessions
+            .iter()
+            .filter(|entry| !entry.value().shed.load(Ordering::Relaxed))
+            .max_by_key(|entry| entry.value().get_used())
+            .map(|entry| entry.value().clone());
+        if let Some(usage) = largest {
+            warn!(
+                "memory {} exceeds max_memory {}, shed session {} {} using {}",
+                self.get_total(),
+                max_memory,
+                usage.session_id,
+                usage.peer,
+                usage.get_used()
+            );
+            usage.shed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn remove(&self, usage: &SessionMemoryUsage) {
+        self.total.fetch_sub(usage.get_used(), Ordering::Relaxed);
+        self.sessions.remove(&usage.session_id);
+    }
+}
+
+// Unregistered from the tracker on drop.
+pub struct SessionMemory {
+    tracker: Arc<MemoryTracker>,
+    usage: Arc<SessionMemoryUsage>,
+}
+
+impl SessionMemory {
+    pub fn update(&self, used: usize) {
+        self.tracker.update(&self.usage, used)
+    }
+
+    pub fn is_shed(&self) -> bool {
+        self.usage.shed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for SessionMemory {
+    fn drop(&mut self) {
+        self.tracker.remove(&self.usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::tls::TlsAcceptorHolder;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicI64, AtomicU64};
+
+    fn gen_config(max_memory: u64) -> Arc<ServerProxyConfig> {
+        let n = NonZeroUsize::new(1).unwrap();
+        Arc::new(ServerProxyConfig {
+            address: "127.0.0.1:5299".to_string(),
+            announce_address: "127.0.0.1:5299".to_string(),
+            auto_select_cluster: true,
+            slowlog_len: n,
+            slowlog_log_slower_than: AtomicI64::new(0),
+            slowlog_sample_rate: AtomicU64::new(0),
+            thread_number: n,
+            thread_per_core: false,
+            session_channel_size: 1,
+            backend_channel_size: 1,
+            backend_conn_num: n,
+            backend_batch_min_time: 0,
+            backend_batch_max_time: 0,
+            backend_batch_buf: n,
+            session_batch_min_time: 0,
+            session_batch_max_time: 0,
+            session_batch_buf: n,
+            active_redirection: false,
+            max_redirections: None,
+            backend_redirection: false,
+            sentinel_addresses: vec![],
+            request_coalescing: false,
+            backend_concurrency_limit: 0,
+            backend_inflight_window: 0,
+            backend_session_fairness: true,
+            backend_idle_check_time: 0,
+            tls: TlsAcceptorHolder::default(),
+            command_timeout_fast: AtomicU64::new(0),
+            command_timeout_slow: AtomicU64::new(0),
+            command_timeout_blocking: AtomicU64::new(0),
+            accept_backlog: 1024,
+            max_accepts_per_tick: 0,
+            accept_storm_pause_time: 0,
+            session_panic_isolation: true,
+            reply_order_check: false,
+            read_after_write_window: 0,
+            replica_ack_timeout: 1000,
+            monitor_buffer_size: 1,
+            max_memory: AtomicU64::new(max_memory),
+        })
+    }
+
+    #[test]
+    fn test_memory_tracker() {
+        let tracker = Arc::new(MemoryTracker::new(gen_config(100)));
+        let s1 = MemoryTracker::register(tracker.clone(), 1, "peer1".to_string());
+        let s2 = MemoryTracker::register(tracker.clone(), 2, "peer2".to_string());
+
+        s1.update(30);
+        s2.update(50);
+        s1.update(20);
+        assert_eq!(tracker.get_total(), 70);
+        assert_eq!(
+            tracker.get_top_consumers(1),
+            vec![(2, "peer2".to_string(), 50)]
+        );
+        assert!(!s1.is_shed());
+        assert!(!s2.is_shed());
+
+        s2.update(90);
+        assert!(!s1.is_shed());
+        assert!(s2.is_shed());
+
+        drop(s2);
+        assert_eq!(tracker.get_total(), 20);
+        assert_eq!(tracker.get_session_num(), 1);
+        assert!(tracker
+            .gen_info_lines()
+            .contains(&"used_memory_sessions:20".to_string()));
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// The meta updates blocked longer than this will be logged.
+pub const META_LOCK_WARN_TIME: Duration = Duration::from_secs(1);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MetaLockStats {
+    // In milliseconds
+    pub max_wait_time: u64,
+    pub max_hold_time: u64,
+    pub blocked_times: u64,
+}
+
+#[derive(Default)]
+struct MetaLockState {
+    waiters: AtomicUsize,
+    // In milliseconds since the UNIX epoch. Zero if nobody holds the lock.
+    holding_since: AtomicI64,
+    // The `holding_since` already reported by the watchdog.
+    reported_since: AtomicI64,
+    max_wait_time: AtomicU64,
+    max_hold_time: AtomicU64,
+    blocked_times: AtomicU64,
+}
+
+impl MetaLockState {
+    // Returns the time that the waiters have been blocked by the current holder,
+    // only once for each holder.
+    fn check_blocked(&self, now: i64) -> Option<Duration> {
+        let holding_since = self.holding_since.load(Ordering::SeqCst);
+        if holding_since == 0 || self.waiters.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let blocked = Duration::from_millis(now.saturating_sub(holding_since).max(0) as u64);
+        if blocked < META_LOCK_WARN_TIME
+            || self.reported_since.swap(holding_since, Ordering::SeqCst) == holding_since
+        {
+            return None;
+        }
+        self.blocked_times.fetch_add(1, Ordering::SeqCst);
+        Some(blocked)
+    }
+}
+
+// The write lock of the metadata which tracks how long the updates wait for and hold it.
+// The watchdog runs in its own thread since a stuck update could block the runtime threads.
+pub struct MetaLock {
+    lock: Mutex<()>,
+    state: Arc<MetaLockState>,
+}
+
+impl Default for MetaLock {
+    fn default() -> Self {
+        Self::new_without_watchdog()
+    }
+}
+
+impl MetaLock {
+    pub fn new() -> Self {
+        let meta_lock = Self::new_without_watchdog();
+        let state = Arc::downgrade(&meta_lock.state);
+        let res = thread::Builder::new()
+            .name("meta-lock-watchdog".to_string())
+            .spawn(move || run_watchdog(state));
+        if let Err(err) = res {
+            error!("failed to start the meta lock watchdog: {:?}", err);
+        }
+        meta_lock
+    }
+
+    fn new_without_watchdog() -> Self {
+        Self {
+            lock: Mutex::new(()),
+            state: Arc::new(MetaLockState::default()),
+        }
+    }
+
+    pub fn lock(&self) -> MetaLockGuard<'_> {
+        let state = &self.state;
+        let start = Instant::now();
+        state.waiters.fetch_add(1, Ordering::SeqCst);
+        let guard = self.lock.lock().expect("MetaLock::lock");
+        state.waiters.fetch_sub(1, Ordering::SeqCst);
+
+        let wait_time = start.elapsed();
+        state
+            .max_wait_time
+            .fetch_max(wait_time.as_millis() as u64, Ordering::SeqCst);
+        if wait_time >= META_LOCK_WARN_TIME {
+            warn!("meta update waited for the lock for {:?}", wait_time);
+        }
+        state.holding_since.store(now_millis(), Ordering::SeqCst);
+        MetaLockGuard {
+            _guard: guard,
+            state,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn get_stats(&self) -> MetaLockStats {
+        let state = &self.state;
+        MetaLockStats {
+            max_wait_time: state.max_wait_time.load(Ordering::SeqCst),
+            max_hold_time: state.max_hold_time.load(Ordering::SeqCst),
+            blocked_times: state.blocked_times.load(Ordering::SeqCst),
+        }
+    }
+}
+
+pub struct MetaLockGuard<'a> {
+    _guard: MutexGuard<'a, ()>,
+    state: &'a MetaLockState,
+    start: Instant,
+}
+
+impl<'a> Drop for MetaLockGuard<'a> {
+    fn drop(&mut self) {
+        let hold_time = self.start.elapsed();
+        self.state.holding_since.store(0, Ordering::SeqCst);
+        self.state
+            .max_hold_time
+            .fetch_max(hold_time.as_millis() as u64, Ordering::SeqCst);
+        if hold_time >= META_LOCK_WARN_TIME {
+            warn!("meta update held the lock for {:?}", hold_time);
+        }
+    }
+}
+
+// Exits after the lock is dropped.
+fn run_watchdog(state: Weak<MetaLockState>) {
+    loop {
+        thread::sleep(WATCHDOG_INTERVAL);
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        if let Some(blocked) = state.check_blocked(now_millis()) {
+            error!(
+                "meta updates have been blocked for {:?} by the current holder of the lock",
+                blocked
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meta_lock_blocked() {
+        let meta_lock = MetaLock::default();
+        {
+            let _guard = meta_lock.lock();
+        }
+        let state = &meta_lock.state;
+        assert_eq!(state.holding_since.load(Ordering::SeqCst), 0);
+        assert_eq!(state.check_blocked(now_millis()), None);
+
+        let _guard = meta_lock.lock();
+        let holding_since = state.holding_since.load(Ordering::SeqCst);
+        assert!(holding_since > 0);
+        // No waiter
+        assert_eq!(state.check_blocked(holding_since + 2000), None);
+
+        state.waiters.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(state.check_blocked(holding_since + 10), None);
+        assert_eq!(
+            state.check_blocked(holding_since + 2000),
+            Some(Duration::from_secs(2))
+        );
+        // Only reported once for the same holder.
+        assert_eq!(state.check_blocked(holding_since + 3000), None);
+        assert_eq!(meta_lock.get_stats().blocked_times, 1);
+    }
+}
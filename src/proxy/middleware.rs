@@ -0,0 +1,194 @@
+use super::acl::get_cmd_key_indices;
+use super::command::{CmdType, Command};
+use super::session::CmdCtx;
+use crate::common::response::ERR_INVALID_COMMAND;
+use crate::common::utils::ThreadSafe;
+use std::collections::HashMap;
+
+// Commands pass through the registered middlewares in order before routing.
+// This is used by the users embedding this crate to extend the proxy.
+pub trait CmdMiddleware: ThreadSafe {
+    // Returns the error message replied to the client if the command is rejected.
+    fn handle(&self, cmd_ctx: &mut CmdCtx) -> Result<(), String>;
+}
+
+pub fn run_middlewares(
+    middlewares: &[Box<dyn CmdMiddleware>],
+    cmd_ctx: &mut CmdCtx,
+) -> Result<(), String> {
+    for middleware in middlewares.iter() {
+        middleware.handle(cmd_ctx)?;
+    }
+    Ok(())
+}
+
+// Renames the commands, e.g. from a disguised `MYFLUSHALL` to `FLUSHALL`.
+pub struct RenameMiddleware {
+    // Uppercase original name => new name
+    renames: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl RenameMiddleware {
+    pub fn new(renames: HashMap<String, String>) -> Self {
+        let renames = renames
+            .into_iter()
+            .map(|(from, to)| (from.to_uppercase().into_bytes(), to.into_bytes()))
+            .collect();
+        Self { renames }
+    }
+}
+
+impl CmdMiddleware for RenameMiddleware {
+    fn handle(&self, cmd_ctx: &mut CmdCtx) -> Result<(), String> {
+        let new_name = match cmd_ctx.get_cmd().get_command_element(0) {
+            Some(name) => match self.renames.get(&name.to_ascii_uppercase()) {
+                Some(new_name) => new_name.clone(),
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        if !cmd_ctx.change_cmd_element(0, new_name) {
+            return Err(ERR_INVALID_COMMAND.to_string());
+        }
+        Ok(())
+    }
+}
+
+// Adds the prefix to all the keys of the data commands
+// so that multiple tenants could share the same cluster.
+// Note that the keys inside the replies such as `SCAN` are not changed.
+pub struct KeyPrefixMiddleware {
+    prefix: Vec<u8>,
+}
+
+impl KeyPrefixMiddleware {
+    pub fn new(prefix: Vec<u8>) -> Self {
+        Self { prefix }
+    }
+}
+
+impl CmdMiddleware for KeyPrefixMiddleware {
+    fn handle(&self, cmd_ctx: &mut CmdCtx) -> Result<(), String> {
+        if cmd_ctx.get_cmd_type() != CmdType::Others {
+            return Ok(());
+        }
+        let indices =
+            get_cmd_key_indices(cmd_ctx.get_cmd()).map_err(|_| ERR_INVALID_COMMAND.to_string())?;
+        for i in indices.into_iter() {
+            let key = match cmd_ctx.get_cmd().get_command_element(i) {
+                Some(key) => key,
+                None => continue,
+            };
+            let mut prefixed_key = self.prefix.clone();
+            prefixed_key.extend_from_slice(key);
+            if !cmd_ctx.change_cmd_element(i, prefixed_key) {
+                return Err(ERR_INVALID_COMMAND.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Rejects the commands with the error returned by the validator.
+pub struct ValidationMiddleware<F>
+where
+    F: Fn(&Command) -> Result<(), String> + ThreadSafe,
+{
+    validator: F,
+}
+
+impl<F> ValidationMiddleware<F>
+where
+    F: Fn(&Command) -> Result<(), String> + ThreadSafe,
+{
+    pub fn new(validator: F) -> Self {
+        Self { validator }
+    }
+}
+
+impl<F> CmdMiddleware for ValidationMiddleware<F>
+where
+    F: Fn(&Command) -> Result<(), String> + ThreadSafe,
+{
+    fn handle(&self, cmd_ctx: &mut CmdCtx) -> Result<(), String> {
+        (self.validator)(cmd_ctx.get_cmd())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::ClusterName;
+    use crate::protocol::{Array, BulkStr, Resp, RespPacket};
+    use crate::proxy::command::{new_command_pair, DataCmdType};
+    use std::convert::TryFrom;
+
+    fn gen_cmd_ctx(cmd: Vec<&str>) -> CmdCtx {
+        let arr = cmd
+            .into_iter()
+            .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+            .collect();
+        let packet = RespPacket::Data(Resp::Arr(Array::Arr(arr)));
+        let cmd = Command::new(Box::new(packet));
+        let (reply_sender, _reply_receiver) = new_command_pair(&cmd);
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        CmdCtx::new(cluster_name, cmd, reply_sender, 0, false)
+    }
+
+    #[test]
+    fn test_rename_middleware() {
+        let mut renames = HashMap::new();
+        renames.insert("mydel".to_string(), "DEL".to_string());
+        let middleware = RenameMiddleware::new(renames);
+
+        let mut cmd_ctx = gen_cmd_ctx(vec!["MyDel", "key"]);
+        assert!(middleware.handle(&mut cmd_ctx).is_ok());
+        assert_eq!(cmd_ctx.get_cmd().get_command_element(0), Some(&b"DEL"[..]));
+        assert_eq!(cmd_ctx.get_data_cmd_type(), DataCmdType::DEL);
+
+        let mut cmd_ctx = gen_cmd_ctx(vec!["GET", "key"]);
+        assert!(middleware.handle(&mut cmd_ctx).is_ok());
+        assert_eq!(cmd_ctx.get_cmd().get_command_element(0), Some(&b"GET"[..]));
+    }
+
+    #[test]
+    fn test_key_prefix_middleware() {
+        let middleware = KeyPrefixMiddleware::new(b"tenant:".to_vec());
+        let mut cmd_ctx = gen_cmd_ctx(vec!["MSET", "a", "1", "b", "2"]);
+        let slot = cmd_ctx.get_cmd().get_slot();
+        assert!(middleware.handle(&mut cmd_ctx).is_ok());
+        let cmd = cmd_ctx.get_cmd();
+        assert_eq!(cmd.get_command_element(1), Some(&b"tenant:a"[..]));
+        assert_eq!(cmd.get_command_element(2), Some(&b"1"[..]));
+        assert_eq!(cmd.get_command_element(3), Some(&b"tenant:b"[..]));
+        assert_ne!(cmd.get_slot(), slot);
+
+        let mut cmd_ctx = gen_cmd_ctx(vec!["PING"]);
+        assert!(middleware.handle(&mut cmd_ctx).is_ok());
+        assert_eq!(cmd_ctx.get_cmd().get_command_len(), Some(1));
+    }
+
+    #[test]
+    fn test_middleware_chain() {
+        let middlewares: Vec<Box<dyn CmdMiddleware>> = vec![
+            Box::new(KeyPrefixMiddleware::new(b"tenant:".to_vec())),
+            Box::new(ValidationMiddleware::new(|cmd: &Command| {
+                match cmd.get_data_cmd_type() {
+                    DataCmdType::DEL => Err("DEL is not allowed".to_string()),
+                    _ => Ok(()),
+                }
+            })),
+        ];
+        let mut cmd_ctx = gen_cmd_ctx(vec!["GET", "key"]);
+        assert!(run_middlewares(&middlewares, &mut cmd_ctx).is_ok());
+        assert_eq!(
+            cmd_ctx.get_cmd().get_command_element(1),
+            Some(&b"tenant:key"[..])
+        );
+        let mut cmd_ctx = gen_cmd_ctx(vec!["DEL", "key"]);
+        assert_eq!(
+            run_middlewares(&middlewares, &mut cmd_ctx),
+            Err("DEL is not allowed".to_string())
+        );
+    }
+}
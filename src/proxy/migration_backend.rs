@@ -1,9 +1,12 @@
 use super::backend::{CmdTask, CmdTaskFactory, ReqTask};
 use super::command::{requires_blocking_migration, CmdTypeTuple, CommandError};
 use super::sender::CmdTaskSender;
+use crate::common::cluster::RestorePolicy;
 use crate::common::response;
 use crate::common::utils::pretty_print_bytes;
-use crate::migration::scan_migration::{pttl_to_restore_expire_time, PTTL_KEY_NOT_FOUND};
+use crate::migration::scan_migration::{
+    pttl_to_restore_expire_time, BUSYKEY_ERROR, PTTL_KEY_NOT_FOUND, RESTORE_REPLACE,
+};
 use crate::protocol::{Array, BinSafeStr, BulkStr, RFunctor, Resp, RespVec, VFunctor};
 use atomic_option::AtomicOption;
 use dashmap::DashSet;
@@ -17,7 +20,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 const KEY_NOT_EXISTS: &str = "0";
-const FAILED_TO_ACCESS_SOURCE: &str = "MIGRATION_FORWARD: failed to access source node";
+const FAILED_TO_ACCESS_SOURCE: &str = "UM-MIGRATING failed to access source node";
 
 type ReplyFuture = Pin<Box<dyn Future<Output = Result<RespVec, CommandError>> + Send>>;
 type DataEntryFuture =
@@ -228,6 +231,7 @@ impl<F: CmdTaskFactory> MgrCmdStateRestoreForward<F> {
         state: MgrCmdStateDumpPttl<F>,
         entry: DataEntry,
         cmd_task_factory: &F,
+        restore_policy: RestorePolicy,
     ) -> (Self, ReqTask<F::Task>, ReplyFuture) {
         let MgrCmdStateDumpPttl {
             inner_task,
@@ -236,7 +240,7 @@ impl<F: CmdTaskFactory> MgrCmdStateRestoreForward<F> {
         } = state;
         let task_context = inner_task.get_context();
         let DataEntry { raw_data, pttl } = entry;
-        let resp = Self::gen_restore_resp(&key, raw_data, pttl);
+        let resp = Self::gen_restore_resp(&key, raw_data, pttl, restore_policy);
         let (restore_cmd_task, restore_reply_fut) =
             cmd_task_factory.create_with_ctx(inner_task.get_context(), resp);
 
@@ -252,15 +256,23 @@ impl<F: CmdTaskFactory> MgrCmdStateRestoreForward<F> {
         )
     }
 
-    fn gen_restore_resp(key: &[u8], raw_data: BinSafeStr, pttl: BinSafeStr) -> RespVec {
+    fn gen_restore_resp(
+        key: &[u8],
+        raw_data: BinSafeStr,
+        pttl: BinSafeStr,
+        restore_policy: RestorePolicy,
+    ) -> RespVec {
         let expire_time = pttl_to_restore_expire_time(pttl);
 
-        let elements = vec![
+        let mut elements = vec![
             Resp::Bulk(BulkStr::Str("RESTORE".to_string().into_bytes())),
             Resp::Bulk(BulkStr::Str(key.into())),
             Resp::Bulk(BulkStr::Str(expire_time)),
             Resp::Bulk(BulkStr::Str(raw_data)),
         ];
+        if restore_policy == RestorePolicy::Replace {
+            elements.push(Resp::Bulk(BulkStr::Str(RESTORE_REPLACE.to_vec())));
+        }
         Resp::Arr(Array::Arr(elements))
     }
 }
@@ -325,6 +337,7 @@ where
     )>,
     cmd_task_factory: Arc<F>,
     key_lock: Arc<KeyLock>,
+    restore_policy: RestorePolicy,
 }
 
 impl<F, S, PS> RestoreDataCmdTaskHandler<F, S, PS>
@@ -339,6 +352,7 @@ where
         dst_sender: S,
         src_proxy_sender: PS,
         cmd_task_factory: Arc<F>,
+        restore_policy: RestorePolicy,
     ) -> Self {
         let src_sender = Arc::new(src_sender);
         let dst_sender = Arc::new(dst_sender);
@@ -368,6 +382,7 @@ where
             task_receivers,
             cmd_task_factory,
             key_lock,
+            restore_policy,
         }
     }
 
@@ -421,6 +436,7 @@ where
             restore_task_sender,
             dst_sender.clone(),
             cmd_task_factory.clone(),
+            self.restore_policy,
         );
 
         let restore_task_handler = Self::handle_restore(
@@ -428,6 +444,7 @@ where
             src_sender,
             del_task_sender.clone(),
             cmd_task_factory,
+            self.restore_policy,
         );
 
         let umsync_task_handler = Self::handle_umsync_task(umsync_task_receiver, dst_sender);
@@ -611,6 +628,7 @@ where
         restore_task_sender: RestoreTaskSender<F>,
         dst_sender: Arc<S>,
         cmd_task_factory: Arc<F>,
+        restore_policy: RestorePolicy,
     ) {
         while let Some((state, reply_fut)) = dump_pttl_task_receiver.next().await {
             let res = reply_fut.await;
@@ -634,8 +652,12 @@ where
                 }
             };
 
-            let (state, req_task, reply_receiver) =
-                MgrCmdStateRestoreForward::from_state_exists(state, entry, &(*cmd_task_factory));
+            let (state, req_task, reply_receiver) = MgrCmdStateRestoreForward::from_state_exists(
+                state,
+                entry,
+                &(*cmd_task_factory),
+                restore_policy,
+            );
             if let Err(err) = dst_sender.send(req_task) {
                 debug!("failed to send restore and forward: {:?}", err);
             }
@@ -651,6 +673,7 @@ where
         src_sender: Arc<S>,
         del_task_sender: DeleteKeyTaskSender,
         cmd_task_factory: Arc<F>,
+        restore_policy: RestorePolicy,
     ) {
         while let Some((state, reply_fut)) = restore_task_receiver.next().await {
             let resp = match reply_fut.await {
@@ -667,11 +690,18 @@ where
             } = state;
             drop(lock_guard);
 
-            const BUSYKEY: &[u8] = b"BUSYKEY";
             match resp {
                 Resp::Simple(_) => (),
-                Resp::Error(err)
-                    if err.get(..BUSYKEY.len()).map(|p| p == BUSYKEY) == Some(true) => {}
+                Resp::Error(err) if err.get(..BUSYKEY_ERROR.len()) == Some(BUSYKEY_ERROR) => {
+                    // Keep the key in the source node for the administrators to decide.
+                    if restore_policy == RestorePolicy::Fail {
+                        error!(
+                            "key already exists in the destination: {:?}",
+                            pretty_print_bytes(&key)
+                        );
+                        continue;
+                    }
+                }
                 others => {
                     let pretty_resp = others.as_ref().map(|s| pretty_print_bytes(&s));
                     error!("unexpected RESTORE result: {:?}", pretty_resp);
@@ -750,7 +780,7 @@ where
             (Some(key), Some(slot)) => (key.to_vec(), slot),
             _ => {
                 cmd_task.set_resp_result(Ok(Resp::Error(
-                    String::from("Missing key while migrating").into_bytes(),
+                    response::ERR_MISSING_KEY.to_string().into_bytes(),
                 )));
                 return;
             }
@@ -770,7 +800,7 @@ where
             let (state, _) = err.into_inner();
             let cmd_task: F::Task = state.into_inner();
             cmd_task.set_resp_result(Ok(Resp::Error(
-                String::from("Migration backend canceled").into_bytes(),
+                format!("{} migration backend canceled", response::UM_MIGRATING).into_bytes(),
             )));
         }
     }
@@ -787,7 +817,12 @@ where
         if let Err(err) = dst_sender.send(task) {
             let cmd_task: F::Task = state.into_inner();
             cmd_task.set_resp_result(Ok(Resp::Error(
-                format!("Migration backend error: {:?}", err).into_bytes(),
+                format!(
+                    "{} migration backend error: {:?}",
+                    response::UM_MIGRATING,
+                    err
+                )
+                .into_bytes(),
             )));
             return Err(());
         }
@@ -1093,6 +1128,7 @@ mod tests {
             DummyCmdTaskSender::new(true, HashMap::new(), 666),
             DummyCmdTaskSender::new(false, HashMap::new(), 0),
             Arc::new(CmdCtxFactory::default()),
+            RestorePolicy::default(),
         );
 
         let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1116,6 +1152,7 @@ mod tests {
             DummyCmdTaskSender::new(false, HashMap::new(), 1),
             DummyCmdTaskSender::new(false, HashMap::new(), 0),
             Arc::new(CmdCtxFactory::default()),
+            RestorePolicy::default(),
         );
 
         let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1142,6 +1179,7 @@ mod tests {
             DummyCmdTaskSender::new(false, HashMap::new(), 233),
             DummyCmdTaskSender::new(false, HashMap::new(), 0),
             Arc::new(CmdCtxFactory::default()),
+            RestorePolicy::default(),
         );
 
         let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1173,6 +1211,7 @@ mod tests {
                 DummyCmdTaskSender::new(true, err_set.clone(), 666),
                 DummyCmdTaskSender::new(false, HashMap::new(), 0),
                 Arc::new(CmdCtxFactory::default()),
+                RestorePolicy::default(),
             );
 
             let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1205,6 +1244,7 @@ mod tests {
                 DummyCmdTaskSender::new(true, err_set.clone(), 666),
                 DummyCmdTaskSender::new(false, HashMap::new(), 0),
                 Arc::new(CmdCtxFactory::default()),
+                RestorePolicy::default(),
             );
 
             let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1241,6 +1281,7 @@ mod tests {
                 DummyCmdTaskSender::new(false, HashMap::new(), 666),
                 DummyCmdTaskSender::new(false, HashMap::new(), 0),
                 Arc::new(CmdCtxFactory::default()),
+                RestorePolicy::default(),
             );
 
             let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1273,6 +1314,7 @@ mod tests {
                 DummyCmdTaskSender::new(false, HashMap::new(), 666),
                 DummyCmdTaskSender::new(false, HashMap::new(), 0),
                 Arc::new(CmdCtxFactory::default()),
+                RestorePolicy::default(),
             );
 
             let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1305,6 +1347,7 @@ mod tests {
                 DummyCmdTaskSender::new(false, err_set.clone(), 666),
                 DummyCmdTaskSender::new(false, HashMap::new(), 0),
                 Arc::new(CmdCtxFactory::default()),
+                RestorePolicy::default(),
             );
 
             let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1337,6 +1380,7 @@ mod tests {
             DummyCmdTaskSender::new(false, HashMap::new(), -2),
             DummyCmdTaskSender::new(false, HashMap::new(), 0),
             Arc::new(CmdCtxFactory::default()),
+            RestorePolicy::default(),
         );
 
         let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["GET", "somekey"]);
@@ -1363,6 +1407,7 @@ mod tests {
             DummyCmdTaskSender::new(false, HashMap::new(), 1),
             DummyCmdTaskSender::new(false, HashMap::new(), 0),
             Arc::new(CmdCtxFactory::default()),
+            RestorePolicy::default(),
         );
 
         let (cmd_ctx, reply_receiver) = gen_test_cmd_ctx(vec!["DEL", "somekey"]);
@@ -1,14 +1,52 @@
+mod acl;
 pub mod backend;
 pub mod blocking;
+mod blocking_cmd;
+// The impls generated by the derive macros of the pinned serde_derive are non-local.
+#[allow(non_local_definitions)]
+pub mod broker_config;
+pub mod builder;
+mod client_list;
 pub mod cluster;
+mod coalescing;
 pub mod command;
+mod command_table;
 mod compress;
+#[allow(non_local_definitions)]
+pub mod config_file;
+mod double_write;
 pub mod executor;
+mod info;
+pub mod key_spec;
+mod last_error;
+pub mod limiter;
+mod listener;
 pub mod manager;
+pub mod memory;
+mod meta_lock;
+pub mod middleware;
 pub mod migration_backend;
+mod monitor;
+mod multi_key;
+mod notification;
+mod percore;
+mod pubsub;
+mod recent_writes;
+mod redirection;
+mod replica_ack;
 pub mod reply;
+mod ring;
+mod scan;
+mod script;
 pub mod sender;
+mod sentinel;
 pub mod service;
 pub mod session;
+mod size_limit;
 mod slot;
+pub mod slot_hook;
 pub mod slowlog;
+pub mod stats;
+pub mod tls;
+mod transaction;
+mod ttl;
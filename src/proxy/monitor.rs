@@ -0,0 +1,325 @@
+use super::command::{CmdType, Command};
+use super::session::{wait_shutdown, CmdHandler, SessionError};
+use crate::common::cluster::ClusterName;
+use crate::common::response;
+use crate::protocol::{EncodeError, Resp, RespPacket, RespVec};
+use futures::channel::mpsc;
+use futures::task::{Context, Poll};
+use futures::{future, select, stream, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+// The data commands processed by the proxy are sent to the sessions in the `MONITOR` mode
+// of the same cluster, in the format of Redis `MONITOR` with the cluster name in place of the db.
+// Each monitor has a bounded buffer and is disconnected when it's full
+// so that a slow monitor won't slow down the proxy.
+
+// The max lines sent in one write.
+const MAX_LINE_BATCH: usize = 64;
+
+type SessionReq = Result<Box<RespPacket>, SessionError>;
+
+struct MonitorSender {
+    cluster_name: ClusterName,
+    sender: mpsc::Sender<String>,
+    lagged: Arc<AtomicBool>,
+}
+
+pub struct Monitors {
+    buffer_size: usize,
+    senders: Mutex<Vec<MonitorSender>>,
+    // Avoids generating the lines when there's no monitor.
+    monitor_num: AtomicUsize,
+}
+
+impl Monitors {
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            buffer_size,
+            senders: Mutex::new(vec![]),
+            monitor_num: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn get_monitor_num(&self) -> usize {
+        self.monitor_num.load(Ordering::Relaxed)
+    }
+
+    pub fn register(&self, cluster_name: ClusterName) -> MonitorReceiver {
+        let (sender, receiver) = mpsc::channel(self.buffer_size);
+        let lagged = Arc::new(AtomicBool::new(false));
+        let mut senders = self.senders.lock().expect("Monitors::register");
+        senders.push(MonitorSender {
+            cluster_name,
+            sender,
+            lagged: lagged.clone(),
+        });
+        self.monitor_num.store(senders.len(), Ordering::Relaxed);
+        MonitorReceiver { receiver, lagged }
+    }
+
+    pub fn feed(&self, cluster_name: &ClusterName, client_addr: Option<&str>, cmd: &Command) {
+        if self.get_monitor_num() == 0 {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+        let line = gen_monitor_line(timestamp, cluster_name, client_addr, cmd);
+
+        let mut senders = self.senders.lock().expect("Monitors::feed");
+        senders.retain_mut(|monitor| {
+            if &monitor.cluster_name != cluster_name {
+                return !monitor.sender.is_closed();
+            }
+            match monitor.sender.try_send(line.clone()) {
+                Ok(()) => true,
+                Err(err) => {
+                    if err.is_full() {
+                        warn!("disconnect the monitor falling behind");
+                        monitor.lagged.store(true, Ordering::Relaxed);
+                    }
+                    false
+                }
+            }
+        });
+        self.monitor_num.store(senders.len(), Ordering::Relaxed);
+    }
+}
+
+pub struct MonitorReceiver {
+    receiver: mpsc::Receiver<String>,
+    lagged: Arc<AtomicBool>,
+}
+
+impl MonitorReceiver {
+    // Returns true if it's disconnected for falling behind.
+    pub fn is_lagged(&self) -> bool {
+        self.lagged.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for MonitorReceiver {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+// Handles the MONITOR at the front of read_buf and the following requests
+// until the session leaves the monitor mode by RESET.
+// Returns false if the session should be closed.
+pub async fn handle_monitor<H, R, W>(
+    handler: &H,
+    reader: &mut R,
+    writer: &mut W,
+    read_buf: &mut VecDeque<SessionReq>,
+    mut shutdown: Option<&mut watch::Receiver<bool>>,
+) -> Result<bool, SessionError>
+where
+    H: CmdHandler,
+    R: Stream<Item = Vec<SessionReq>> + Unpin,
+    W: Sink<Box<RespPacket>, Error = EncodeError<Box<RespPacket>>> + Unpin,
+{
+    match read_buf.pop_front() {
+        Some(req) => req?,
+        None => return Ok(true),
+    };
+    let mut receiver = match handler.register_monitor() {
+        Ok(receiver) => receiver,
+        Err(err_msg) => {
+            send_replies(writer, vec![Resp::Error(err_msg.into_bytes())]).await?;
+            return Ok(true);
+        }
+    };
+    send_replies(writer, vec![gen_ok()]).await?;
+
+    loop {
+        while let Some(req) = read_buf.pop_front() {
+            let cmd = Command::new(req?);
+            match cmd.get_type() {
+                // Leave the monitor mode and let the session reset the rest.
+                CmdType::Reset => {
+                    read_buf.push_front(Ok(cmd.into_packet()));
+                    return Ok(true);
+                }
+                CmdType::Quit => {
+                    send_replies(writer, vec![gen_ok()]).await?;
+                    return Ok(false);
+                }
+                _ => {
+                    let err = response::ERR_MONITOR_CONTEXT.to_string().into_bytes();
+                    send_replies(writer, vec![Resp::Error(err)]).await?;
+                }
+            }
+        }
+
+        let mut read_fut = reader.next().fuse();
+        let mut line_fut = receiver.next().fuse();
+        let mut shutdown_fut = match shutdown.as_mut() {
+            Some(shutdown) => future::Either::Left(Box::pin(wait_shutdown(shutdown))),
+            None => future::Either::Right(future::pending::<()>()),
+        }
+        .fuse();
+
+        let line = select! {
+            reqs = read_fut => match reqs {
+                Some(reqs) => {
+                    read_buf.extend(reqs);
+                    continue;
+                }
+                None => return Ok(false),
+            },
+            line = line_fut => line,
+            () = shutdown_fut => return Ok(true),
+        };
+        let line = match line {
+            Some(line) => line,
+            None if receiver.is_lagged() => {
+                let err = response::ERR_MONITOR_LAGGED.to_string().into_bytes();
+                send_replies(writer, vec![Resp::Error(err)]).await?;
+                return Ok(false);
+            }
+            None => return Ok(false),
+        };
+        let mut lines = vec![Resp::Simple(line.into_bytes())];
+        while lines.len() < MAX_LINE_BATCH {
+            match receiver.next().now_or_never() {
+                Some(Some(line)) => lines.push(Resp::Simple(line.into_bytes())),
+                _ => break,
+            }
+        }
+        send_replies(writer, lines).await?;
+    }
+}
+
+async fn send_replies<W>(writer: &mut W, replies: Vec<RespVec>) -> Result<(), SessionError>
+where
+    W: Sink<Box<RespPacket>, Error = EncodeError<Box<RespPacket>>> + Unpin,
+{
+    let mut batch = stream::iter(
+        replies
+            .into_iter()
+            .map(|resp| Box::new(RespPacket::from_resp_vec(resp))),
+    )
+    .map(Ok);
+    writer.send_all(&mut batch).await.map_err(|err| {
+        error!("writer error: {}", err);
+        match err {
+            EncodeError::Io(err) => SessionError::Io(err),
+            EncodeError::NotReady(_) => SessionError::InvalidState,
+        }
+    })
+}
+
+fn gen_ok() -> RespVec {
+    Resp::Simple(response::OK_REPLY.to_string().into_bytes())
+}
+
+fn gen_monitor_line(
+    timestamp: f64,
+    cluster_name: &ClusterName,
+    client_addr: Option<&str>,
+    cmd: &Command,
+) -> String {
+    let mut line = format!(
+        "{:.6} [{} {}]",
+        timestamp,
+        cluster_name,
+        client_addr.unwrap_or("unknown")
+    );
+    let len = cmd.get_command_len().unwrap_or(0);
+    for i in 0..len {
+        let arg = match cmd.get_command_element(i) {
+            Some(arg) => arg,
+            None => continue,
+        };
+        line.push(' ');
+        line.push_str(&quote_arg(arg));
+    }
+    line
+}
+
+fn quote_arg(arg: &[u8]) -> String {
+    let mut s = String::with_capacity(arg.len() + 2);
+    s.push('"');
+    for b in arg {
+        match b {
+            b'\\' => s.push_str("\\\\"),
+            b'"' => s.push_str("\\\""),
+            b'\n' => s.push_str("\\n"),
+            b'\r' => s.push_str("\\r"),
+            b'\t' => s.push_str("\\t"),
+            0x07 => s.push_str("\\a"),
+            0x08 => s.push_str("\\b"),
+            b if b.is_ascii_graphic() || *b == b' ' => s.push(*b as char),
+            b => s.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    s.push('"');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Array, BulkStr, Resp, RespPacket};
+    use futures::StreamExt;
+    use std::convert::TryFrom;
+
+    fn gen_cmd(args: Vec<&[u8]>) -> Command {
+        let elements = args
+            .into_iter()
+            .map(|arg| Resp::Bulk(BulkStr::Str(arg.to_vec())))
+            .collect();
+        let packet = RespPacket::from_resp_vec(Resp::Arr(Array::Arr(elements)));
+        Command::new(Box::new(packet))
+    }
+
+    #[test]
+    fn test_gen_monitor_line() {
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let cmd = gen_cmd(vec![b"set", b"key", b"a \"b\"\n\x01"]);
+        assert_eq!(
+            gen_monitor_line(1.5, &cluster_name, Some("127.0.0.1:6000"), &cmd),
+            "1.500000 [mycluster 127.0.0.1:6000] \"set\" \"key\" \"a \\\"b\\\"\\n\\x01\""
+        );
+    }
+
+    #[tokio::test]
+    async fn test_monitors() {
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let monitors = Monitors::new(1);
+        let cmd = gen_cmd(vec![b"get", b"key"]);
+        monitors.feed(&cluster_name, None, &cmd);
+
+        let mut receiver = monitors.register(cluster_name.clone());
+        assert_eq!(monitors.get_monitor_num(), 1);
+        let another_cluster = ClusterName::try_from("another").unwrap();
+        monitors.feed(&another_cluster, None, &cmd);
+        monitors.feed(&cluster_name, None, &cmd);
+        let line = receiver.next().await.unwrap();
+        assert!(line.ends_with("[mycluster unknown] \"get\" \"key\""));
+
+        // The capacity is the buffer size plus the number of the senders.
+        for _ in 0..3 {
+            monitors.feed(&cluster_name, None, &cmd);
+        }
+        assert_eq!(monitors.get_monitor_num(), 0);
+        assert!(receiver.is_lagged());
+        assert!(receiver.next().await.is_some());
+        assert!(receiver.next().await.is_some());
+        assert!(receiver.next().await.is_none());
+
+        let receiver = monitors.register(another_cluster);
+        drop(receiver);
+        monitors.feed(&cluster_name, None, &cmd);
+        assert_eq!(monitors.get_monitor_num(), 0);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::protocol::{Array, BinSafeStr, Resp, RespVec};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// The multi-key commands are split into the sub-commands of the same backend
+// so that the keys don't need to be in the same slot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KeyGroup {
+    // The slots which could be sent to the backend directly.
+    Backend(String),
+    // The slots owned by the other proxies are sent separately.
+    Slot(usize),
+    // The keys of the migrating or canary slots could be on different nodes,
+    // and the importing side only checks the first key of a command.
+    Key(BinSafeStr),
+}
+
+// Groups the indices of the keys in the order of their first appearance.
+pub fn group_key_indices<K, F>(keys: &[Vec<u8>], mut group_of: F) -> Vec<Vec<usize>>
+where
+    K: Hash + Eq,
+    F: FnMut(&[u8]) -> K,
+{
+    let mut group_indices: HashMap<K, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for (i, key) in keys.iter().enumerate() {
+        let next_index = groups.len();
+        let index = *group_indices.entry(group_of(key)).or_insert(next_index);
+        if index == next_index {
+            groups.push(vec![]);
+        }
+        groups[index].push(i);
+    }
+    groups
+}
+
+// Puts the values of the sub-commands back in the order of the keys.
+pub fn reassemble_values(
+    key_num: usize,
+    groups: &[Vec<usize>],
+    replies: Vec<RespVec>,
+) -> Option<Vec<RespVec>> {
+    let mut values: Vec<Option<RespVec>> = (0..key_num).map(|_| None).collect();
+    for (group, reply) in groups.iter().zip(replies) {
+        let group_values = match reply {
+            Resp::Arr(Array::Arr(group_values)) => group_values,
+            _ => return None,
+        };
+        if group_values.len() != group.len() {
+            return None;
+        }
+        for (i, value) in group.iter().zip(group_values) {
+            *values.get_mut(*i)? = Some(value);
+        }
+    }
+    values.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::BulkStr;
+
+    fn bulk(s: &str) -> RespVec {
+        Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn test_group_key_indices() {
+        let keys: Vec<Vec<u8>> = vec!["a1", "b1", "a2", "c1", "b2"]
+            .into_iter()
+            .map(|k| k.as_bytes().to_vec())
+            .collect();
+        let groups = group_key_indices(&keys, |key| key[0]);
+        assert_eq!(groups, vec![vec![0, 2], vec![1, 4], vec![3]]);
+        assert!(group_key_indices(&[], |key| key.to_vec()).is_empty());
+    }
+
+    #[test]
+    fn test_group_migrating_keys() {
+        let keys: Vec<Vec<u8>> = vec!["m1", "b1", "m2", "r1", "b2", "r2"]
+            .into_iter()
+            .map(|k| k.as_bytes().to_vec())
+            .collect();
+        // "m" stands for the migrating slot and "r" for the remote one.
+        let groups = group_key_indices(&keys, |key| match key[0] {
+            b'm' => KeyGroup::Key(key.to_vec()),
+            b'r' => KeyGroup::Slot(1),
+            _ => KeyGroup::Backend("127.0.0.1:6379".to_string()),
+        });
+        assert_eq!(groups, vec![vec![0], vec![1, 4], vec![2], vec![3, 5]]);
+    }
+
+    #[test]
+    fn test_group_duplicated_migrating_keys() {
+        let keys: Vec<Vec<u8>> = vec!["m1", "m2", "m1"]
+            .into_iter()
+            .map(|k| k.as_bytes().to_vec())
+            .collect();
+        // DEL m1 m2 m1 should only count m1 once.
+        let groups = group_key_indices(&keys, |key| KeyGroup::Key(key.to_vec()));
+        assert_eq!(groups, vec![vec![0, 2], vec![1]]);
+    }
+
+    #[test]
+    fn test_reassemble_values() {
+        let groups = vec![vec![0, 2], vec![1]];
+        let replies = vec![
+            Resp::Arr(Array::Arr(vec![bulk("v0"), bulk("v2")])),
+            Resp::Arr(Array::Arr(vec![Resp::Bulk(BulkStr::Nil)])),
+        ];
+        let values = reassemble_values(3, &groups, replies).unwrap();
+        assert_eq!(
+            values,
+            vec![bulk("v0"), Resp::Bulk(BulkStr::Nil), bulk("v2")]
+        );
+
+        let replies = vec![Resp::Arr(Array::Arr(vec![bulk("v0")])), bulk("v1")];
+        assert!(reassemble_values(3, &groups, replies).is_none());
+        // Missing replies.
+        let replies = vec![Resp::Arr(Array::Arr(vec![bulk("v0"), bulk("v2")]))];
+        assert!(reassemble_values(3, &groups, replies).is_none());
+    }
+}
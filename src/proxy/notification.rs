@@ -0,0 +1,80 @@
+use crate::common::utils::generate_slot;
+
+// The keyspace notifications are only published by the node where the key is changed,
+// so their channels can't be routed by the slots of the channel names.
+// `__keyspace@<db>__:<key>` is published by the owner of the key,
+// while `__keyevent@<db>__:<event>` could be published by any node.
+const KEYSPACE_PREFIX: &[u8] = b"__keyspace@";
+const KEYEVENT_PREFIX: &[u8] = b"__keyevent@";
+const DB_SUFFIX: &[u8] = b"__:";
+
+#[derive(Debug, PartialEq)]
+pub enum NotificationChannel<'a> {
+    KeySpace(&'a [u8]),
+    KeyEvent(&'a [u8]),
+}
+
+pub fn parse_notification_channel(channel: &[u8]) -> Option<NotificationChannel<'_>> {
+    if let Some(key) = strip_db_prefix(channel, KEYSPACE_PREFIX) {
+        return Some(NotificationChannel::KeySpace(key));
+    }
+    strip_db_prefix(channel, KEYEVENT_PREFIX).map(NotificationChannel::KeyEvent)
+}
+
+fn strip_db_prefix<'a>(channel: &'a [u8], prefix: &[u8]) -> Option<&'a [u8]> {
+    if !channel.starts_with(prefix) {
+        return None;
+    }
+    let rest = &channel[prefix.len()..];
+    let db_len = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+    if db_len == 0 || !rest[db_len..].starts_with(DB_SUFFIX) {
+        return None;
+    }
+    Some(&rest[db_len + DB_SUFFIX.len()..])
+}
+
+// Returns None for the key event channels which should be subscribed from all the nodes.
+pub fn get_channel_slot(channel: &[u8]) -> Option<usize> {
+    match parse_notification_channel(channel) {
+        Some(NotificationChannel::KeySpace(key)) => Some(generate_slot(key)),
+        Some(NotificationChannel::KeyEvent(_)) => None,
+        None => Some(generate_slot(channel)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notification_channel() {
+        assert_eq!(
+            parse_notification_channel(b"__keyspace@0__:mykey"),
+            Some(NotificationChannel::KeySpace(b"mykey"))
+        );
+        assert_eq!(
+            parse_notification_channel(b"__keyspace@12__:a:b"),
+            Some(NotificationChannel::KeySpace(b"a:b"))
+        );
+        assert_eq!(
+            parse_notification_channel(b"__keyevent@0__:expired"),
+            Some(NotificationChannel::KeyEvent(b"expired"))
+        );
+        assert_eq!(parse_notification_channel(b"__keyspace@__:mykey"), None);
+        assert_eq!(parse_notification_channel(b"__keyspace@0:mykey"), None);
+        assert_eq!(parse_notification_channel(b"mychannel"), None);
+    }
+
+    #[test]
+    fn test_get_channel_slot() {
+        assert_eq!(
+            get_channel_slot(b"__keyspace@0__:mykey"),
+            Some(generate_slot(b"mykey"))
+        );
+        assert_eq!(get_channel_slot(b"__keyevent@0__:del"), None);
+        assert_eq!(
+            get_channel_slot(b"mychannel"),
+            Some(generate_slot(b"mychannel"))
+        );
+    }
+}
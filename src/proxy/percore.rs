@@ -0,0 +1,80 @@
+use arc_swap::ArcSwap;
+use std::cell::Cell;
+use std::sync::Arc;
+
+thread_local! {
+    static CORE_ID: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+// Called by each core thread in the thread-per-core mode before running its runtime.
+pub fn bind_current_core(core_id: usize) {
+    CORE_ID.with(|id| id.set(Some(core_id)))
+}
+
+pub fn get_current_core() -> Option<usize> {
+    CORE_ID.with(|id| id.get())
+}
+
+// Keeps one copy of the `ArcSwap` for each core
+// so that the readers in different cores will not contend on the same one.
+// All the copies are only updated by `store`.
+pub struct CoreLocalSwap<T> {
+    global: Arc<ArcSwap<T>>,
+    cores: Vec<Arc<ArcSwap<T>>>,
+}
+
+impl<T> CoreLocalSwap<T> {
+    pub fn new(global: Arc<ArcSwap<T>>, core_num: usize) -> Self {
+        let cores = (0..core_num)
+            .map(|_| Arc::new(ArcSwap::new(global.load())))
+            .collect();
+        Self { global, cores }
+    }
+
+    // Falls back to the global one outside the core threads.
+    pub fn local(&self) -> &Arc<ArcSwap<T>> {
+        get_current_core()
+            .and_then(|core_id| self.cores.get(core_id))
+            .unwrap_or(&self.global)
+    }
+
+    pub fn store(&self, value: Arc<T>) {
+        for core in self.cores.iter() {
+            core.store(value.clone());
+        }
+        self.global.store(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_core_local_swap() {
+        let global = Arc::new(ArcSwap::new(Arc::new(0)));
+        let swap = Arc::new(CoreLocalSwap::new(global.clone(), 2));
+        assert!(Arc::ptr_eq(swap.local(), &global));
+
+        swap.store(Arc::new(1));
+        assert_eq!(*global.lease(), 1);
+
+        let s = swap.clone();
+        let g = global.clone();
+        thread::spawn(move || {
+            bind_current_core(1);
+            assert_eq!(get_current_core(), Some(1));
+            assert!(!Arc::ptr_eq(s.local(), &g));
+            assert_eq!(*s.local().lease(), 1);
+            s.store(Arc::new(2));
+            assert_eq!(*s.local().lease(), 2);
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(get_current_core(), None);
+        assert_eq!(*swap.local().lease(), 2);
+        assert!(swap.cores.iter().all(|core| *core.lease() == 2));
+    }
+}
@@ -0,0 +1,1395 @@
+use super::backend::{ConnFactory, ConnSink, ConnStream, DefaultConnFactory};
+use super::cluster::SlotOwner;
+use super::command::{CmdType, Command};
+use super::notification::get_channel_slot;
+use super::session::{wait_shutdown, CmdHandler, SessionError};
+use crate::common::cluster::ClusterName;
+use crate::common::response;
+use crate::common::utils::{gen_moved, generate_slot, lookup_first_address};
+use crate::protocol::{Array, BinSafeStr, BulkStr, EncodeError, Resp, RespPacket};
+use futures::channel::mpsc;
+use futures::{future, select, stream, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time;
+
+// The pub/sub commands are handled by the session itself.
+// The channels are subscribed from the backends owning their slots
+// and resubscribed from the new owners after failover or slot migration.
+// The patterns are subscribed from all the local backends of the cluster.
+// The keyspace notification channels are subscribed from the backends publishing them.
+// The backend connections are shared by all the sessions of the same cluster.
+// Each channel or pattern is only subscribed once from a backend no matter how many sessions
+// subscribe it, and the published messages are fanned out to the sessions subscribing them.
+
+const TOPOLOGY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const BACKEND_CONN_TIMEOUT: Duration = Duration::from_secs(3);
+
+type SessionReq = Result<Box<RespPacket>, SessionError>;
+
+pub fn is_pubsub_cmd(cmd_type: CmdType) -> bool {
+    matches!(
+        cmd_type,
+        CmdType::Subscribe
+            | CmdType::Unsubscribe
+            | CmdType::PSubscribe
+            | CmdType::PUnsubscribe
+            | CmdType::SSubscribe
+            | CmdType::SUnsubscribe
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChannelKind {
+    Regular,
+    Shard,
+}
+
+impl ChannelKind {
+    fn sub_kind(self) -> SubKind {
+        match self {
+            Self::Regular => SubKind::Channel,
+            Self::Shard => SubKind::ShardChannel,
+        }
+    }
+
+    fn subscribe_reply(self) -> &'static [u8] {
+        match self {
+            Self::Regular => b"subscribe",
+            Self::Shard => b"ssubscribe",
+        }
+    }
+
+    fn unsubscribe_reply(self) -> &'static [u8] {
+        match self {
+            Self::Regular => b"unsubscribe",
+            Self::Shard => b"sunsubscribe",
+        }
+    }
+}
+
+// The patterns and the key event channels are subscribed from all the local nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BroadcastKind {
+    Pattern,
+    KeyEvent,
+}
+
+impl BroadcastKind {
+    fn sub_kind(self) -> SubKind {
+        match self {
+            Self::Pattern => SubKind::Pattern,
+            Self::KeyEvent => SubKind::Channel,
+        }
+    }
+}
+
+// What is subscribed from the backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SubKind {
+    Channel,
+    ShardChannel,
+    Pattern,
+}
+
+impl SubKind {
+    fn subscribe_cmd(self) -> &'static [u8] {
+        match self {
+            Self::Channel => b"SUBSCRIBE",
+            Self::ShardChannel => b"SSUBSCRIBE",
+            Self::Pattern => b"PSUBSCRIBE",
+        }
+    }
+
+    fn unsubscribe_cmd(self) -> &'static [u8] {
+        match self {
+            Self::Channel => b"UNSUBSCRIBE",
+            Self::ShardChannel => b"SUNSUBSCRIBE",
+            Self::Pattern => b"PUNSUBSCRIBE",
+        }
+    }
+
+    // Returns the kind and the channel or pattern of the published messages.
+    fn from_msg(msg: &RespPacket) -> Option<(Self, &[u8])> {
+        let msg_type = msg.get_array_element(0)?;
+        let kind = if msg_type.eq_ignore_ascii_case(b"message") {
+            Self::Channel
+        } else if msg_type.eq_ignore_ascii_case(b"smessage") {
+            Self::ShardChannel
+        } else if msg_type.eq_ignore_ascii_case(b"pmessage") {
+            Self::Pattern
+        } else {
+            return None;
+        };
+        Some((kind, msg.get_array_element(1)?))
+    }
+}
+
+pub enum BackendEvent {
+    Message(String, RespPacket),
+    Closed(String),
+}
+
+pub type SubscriberReceiver = mpsc::UnboundedReceiver<BackendEvent>;
+
+type SessionId = usize;
+
+// The shared subscriber connection to one backend.
+struct NodeSubscriber {
+    conn_id: usize,
+    cmd_sender: mpsc::UnboundedSender<RespPacket>,
+    // (kind, channel or pattern) => the sessions subscribing it
+    targets: HashMap<(SubKind, BinSafeStr), HashSet<SessionId>>,
+    sessions: HashMap<SessionId, mpsc::UnboundedSender<BackendEvent>>,
+}
+
+impl NodeSubscriber {
+    fn new(conn_id: usize, cmd_sender: mpsc::UnboundedSender<RespPacket>) -> Self {
+        Self {
+            conn_id,
+            cmd_sender,
+            targets: HashMap::new(),
+            sessions: HashMap::new(),
+        }
+    }
+
+    // Returns the targets not subscribed from the backend yet.
+    fn add(
+        &mut self,
+        session_id: SessionId,
+        event_sender: &mpsc::UnboundedSender<BackendEvent>,
+        kind: SubKind,
+        targets: &[BinSafeStr],
+    ) -> Vec<BinSafeStr> {
+        self.sessions
+            .entry(session_id)
+            .or_insert_with(|| event_sender.clone());
+        let mut new_targets = vec![];
+        for target in targets.iter() {
+            let sessions = self.targets.entry((kind, target.clone())).or_default();
+            if sessions.is_empty() {
+                new_targets.push(target.clone());
+            }
+            sessions.insert(session_id);
+        }
+        new_targets
+    }
+
+    // Returns the targets no longer subscribed by any session.
+    fn remove(
+        &mut self,
+        session_id: SessionId,
+        kind: SubKind,
+        targets: &[BinSafeStr],
+    ) -> Vec<BinSafeStr> {
+        let mut removed = vec![];
+        for target in targets.iter() {
+            let key = (kind, target.clone());
+            let is_empty = match self.targets.get_mut(&key) {
+                Some(sessions) => sessions.remove(&session_id) && sessions.is_empty(),
+                None => false,
+            };
+            if is_empty {
+                self.targets.remove(&key);
+                removed.push(target.clone());
+            }
+        }
+        if !self
+            .targets
+            .values()
+            .any(|sessions| sessions.contains(&session_id))
+        {
+            self.sessions.remove(&session_id);
+        }
+        removed
+    }
+
+    // Returns the targets no longer subscribed by any session.
+    fn remove_session(&mut self, session_id: SessionId) -> HashMap<SubKind, Vec<BinSafeStr>> {
+        let mut removed: HashMap<SubKind, Vec<BinSafeStr>> = HashMap::new();
+        self.targets.retain(|(kind, target), sessions| {
+            if sessions.remove(&session_id) && sessions.is_empty() {
+                removed.entry(*kind).or_default().push(target.clone());
+                return false;
+            }
+            true
+        });
+        self.sessions.remove(&session_id);
+        removed
+    }
+
+    fn has_session(&self, session_id: SessionId) -> bool {
+        self.sessions.contains_key(&session_id)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    fn send(&self, cmd_name: &[u8], targets: &[BinSafeStr]) -> bool {
+        let mut elements = vec![cmd_name.to_vec()];
+        elements.extend(targets.iter().cloned());
+        self.cmd_sender.unbounded_send(gen_array(elements)).is_ok()
+    }
+
+    // The errors are sent to all the sessions while the confirmations are dropped.
+    fn dispatch(&mut self, node: &str, msg: RespPacket) {
+        let session_ids: Vec<SessionId> = match SubKind::from_msg(&msg) {
+            Some((kind, target)) => match self.targets.get(&(kind, target.to_vec())) {
+                Some(sessions) => sessions.iter().cloned().collect(),
+                None => return,
+            },
+            None if matches!(msg.to_resp_vec(), Resp::Error(_)) => {
+                self.sessions.keys().cloned().collect()
+            }
+            None => return,
+        };
+        for session_id in session_ids.into_iter() {
+            if let Some(sender) = self.sessions.get(&session_id) {
+                let event = BackendEvent::Message(node.to_string(), msg.clone());
+                let _ = sender.unbounded_send(event);
+            }
+        }
+    }
+
+    fn close(self, node: &str) {
+        for sender in self.sessions.values() {
+            let _ = sender.unbounded_send(BackendEvent::Closed(node.to_string()));
+        }
+    }
+}
+
+// (cluster, node) => the shared subscriber connection
+type NodeSubscribers = Arc<Mutex<HashMap<(ClusterName, String), NodeSubscriber>>>;
+
+pub struct Subscribers {
+    nodes: NodeSubscribers,
+    // Used for both the session ids and the connection ids.
+    next_id: Arc<AtomicUsize>,
+}
+
+impl Default for Subscribers {
+    fn default() -> Self {
+        Self {
+            nodes: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl Subscribers {
+    pub fn register(&self, cluster_name: ClusterName) -> (SubscriberHandle, SubscriberReceiver) {
+        let (event_sender, event_receiver) = mpsc::unbounded();
+        let handle = SubscriberHandle {
+            cluster_name,
+            session_id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            nodes: self.nodes.clone(),
+            next_id: self.next_id.clone(),
+            event_sender,
+        };
+        (handle, event_receiver)
+    }
+}
+
+// Subscribes from the shared connections on behalf of one session.
+// All the subscriptions of the session are released after this is dropped.
+pub struct SubscriberHandle {
+    cluster_name: ClusterName,
+    session_id: SessionId,
+    nodes: NodeSubscribers,
+    next_id: Arc<AtomicUsize>,
+    event_sender: mpsc::UnboundedSender<BackendEvent>,
+}
+
+impl SubscriberHandle {
+    fn get_key(&self, node: &str) -> (ClusterName, String) {
+        (self.cluster_name.clone(), node.to_string())
+    }
+
+    fn is_connected(&self, node: &str) -> bool {
+        self.nodes
+            .lock()
+            .expect("SubscriberHandle::is_connected")
+            .get(&self.get_key(node))
+            .map(|node_sub| node_sub.has_session(self.session_id))
+            .unwrap_or(false)
+    }
+
+    // Returns false if the node could not be connected.
+    async fn subscribe(&self, node: &str, kind: SubKind, targets: &[BinSafeStr]) -> bool {
+        let key = self.get_key(node);
+        let connected = self
+            .nodes
+            .lock()
+            .expect("SubscriberHandle::subscribe")
+            .contains_key(&key);
+        if !connected {
+            let (sink, stream) = match connect_backend(node).await {
+                Some(conn) => conn,
+                None => return false,
+            };
+            let conn_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let (cmd_sender, cmd_receiver) = mpsc::unbounded();
+            let mut nodes = self.nodes.lock().expect("SubscriberHandle::subscribe");
+            // The connection created by another session at the same time is used instead.
+            if !nodes.contains_key(&key) {
+                nodes.insert(key.clone(), NodeSubscriber::new(conn_id, cmd_sender));
+                let nodes = self.nodes.clone();
+                let conn_key = key.clone();
+                tokio::spawn(async move {
+                    forward_backend_msgs(nodes, conn_key, conn_id, sink, stream, cmd_receiver).await
+                });
+            }
+        }
+
+        let mut nodes = self.nodes.lock().expect("SubscriberHandle::subscribe");
+        let node_sub = match nodes.get_mut(&key) {
+            Some(node_sub) => node_sub,
+            None => return false,
+        };
+        let new_targets = node_sub.add(self.session_id, &self.event_sender, kind, targets);
+        if new_targets.is_empty() || node_sub.send(kind.subscribe_cmd(), &new_targets) {
+            return true;
+        }
+        if let Some(node_sub) = nodes.remove(&key) {
+            node_sub.close(node);
+        }
+        false
+    }
+
+    // The connection is closed after no session subscribes from it.
+    fn unsubscribe(&self, node: &str, kind: SubKind, targets: &[BinSafeStr]) {
+        let key = self.get_key(node);
+        let mut nodes = self.nodes.lock().expect("SubscriberHandle::unsubscribe");
+        let node_sub = match nodes.get_mut(&key) {
+            Some(node_sub) if node_sub.has_session(self.session_id) => node_sub,
+            _ => return,
+        };
+        let removed = node_sub.remove(self.session_id, kind, targets);
+        if !removed.is_empty() {
+            node_sub.send(kind.unsubscribe_cmd(), &removed);
+        }
+        if node_sub.is_idle() {
+            nodes.remove(&key);
+        }
+    }
+}
+
+impl Drop for SubscriberHandle {
+    fn drop(&mut self) {
+        let mut nodes = match self.nodes.lock() {
+            Ok(nodes) => nodes,
+            Err(_) => return,
+        };
+        let cluster_name = &self.cluster_name;
+        let session_id = self.session_id;
+        nodes.retain(|(cluster, _), node_sub| {
+            if cluster != cluster_name || !node_sub.has_session(session_id) {
+                return true;
+            }
+            for (kind, targets) in node_sub.remove_session(session_id).into_iter() {
+                node_sub.send(kind.unsubscribe_cmd(), &targets);
+            }
+            !node_sub.is_idle()
+        });
+    }
+}
+
+#[derive(Default)]
+struct ChannelMap {
+    // channel => (slot, node)
+    // The node is None when the channel is waiting to be resubscribed.
+    channels: HashMap<BinSafeStr, (usize, Option<String>)>,
+}
+
+impl ChannelMap {
+    fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    fn insert(&mut self, channel: BinSafeStr, slot: usize, node: Option<String>) -> Option<String> {
+        self.channels
+            .insert(channel, (slot, node))
+            .and_then(|(_, node)| node)
+    }
+
+    fn remove(&mut self, channel: &[u8]) -> Option<String> {
+        self.channels.remove(channel).and_then(|(_, node)| node)
+    }
+
+    fn get_all(&self) -> Vec<BinSafeStr> {
+        self.channels.keys().cloned().collect()
+    }
+
+    // slot => node
+    fn get_slot_nodes(&self) -> HashMap<usize, Option<String>> {
+        self.channels
+            .values()
+            .map(|(slot, node)| (*slot, node.clone()))
+            .collect()
+    }
+
+    fn get_slot_channels(&self, slot: usize) -> Vec<BinSafeStr> {
+        self.channels
+            .iter()
+            .filter(|(_, (s, _))| *s == slot)
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    fn is_subscribed_from(&self, channel: &[u8], node: &str) -> bool {
+        self.channels
+            .get(channel)
+            .map(|(_, n)| n.as_deref() == Some(node))
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Default)]
+struct Subscriptions {
+    channels: ChannelMap,
+    shard_channels: ChannelMap,
+    // pattern => the nodes subscribed from
+    patterns: HashMap<BinSafeStr, HashSet<String>>,
+    // key event channel => the nodes subscribed from
+    key_events: HashMap<BinSafeStr, HashSet<String>>,
+}
+
+impl Subscriptions {
+    fn get_channel_map(&mut self, kind: ChannelKind) -> &mut ChannelMap {
+        match kind {
+            ChannelKind::Regular => &mut self.channels,
+            ChannelKind::Shard => &mut self.shard_channels,
+        }
+    }
+
+    fn get_broadcast_map(
+        &mut self,
+        kind: BroadcastKind,
+    ) -> &mut HashMap<BinSafeStr, HashSet<String>> {
+        match kind {
+            BroadcastKind::Pattern => &mut self.patterns,
+            BroadcastKind::KeyEvent => &mut self.key_events,
+        }
+    }
+
+    // Same as Redis, the shard channels are counted separately.
+    fn get_count(&self, kind: ChannelKind) -> usize {
+        match kind {
+            ChannelKind::Regular => {
+                self.channels.len() + self.key_events.len() + self.patterns.len()
+            }
+            ChannelKind::Shard => self.shard_channels.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.get_count(ChannelKind::Regular) == 0 && self.get_count(ChannelKind::Shard) == 0
+    }
+
+    fn remove_node(&mut self, node: &str) {
+        for nodes in self.patterns.values_mut() {
+            nodes.remove(node);
+        }
+        for nodes in self.key_events.values_mut() {
+            nodes.remove(node);
+        }
+    }
+
+    // The confirmations from the backends are dropped since they are generated by the proxy,
+    // and the messages from the old owners are dropped after the slots are migrated.
+    fn should_forward(&self, node: &str, msg: &RespPacket) -> bool {
+        let kind = match msg.get_array_element(0) {
+            Some(kind) => kind,
+            None => return matches!(msg.to_resp_vec(), Resp::Error(_)),
+        };
+        let target = match msg.get_array_element(1) {
+            Some(target) => target,
+            None => return false,
+        };
+        if kind.eq_ignore_ascii_case(b"message") {
+            self.channels.is_subscribed_from(target, node)
+                || self
+                    .key_events
+                    .get(target)
+                    .map(|nodes| nodes.contains(node))
+                    .unwrap_or(false)
+        } else if kind.eq_ignore_ascii_case(b"smessage") {
+            self.shard_channels.is_subscribed_from(target, node)
+        } else if kind.eq_ignore_ascii_case(b"pmessage") {
+            self.patterns
+                .get(target)
+                .map(|nodes| nodes.contains(node))
+                .unwrap_or(false)
+        } else {
+            false
+        }
+    }
+}
+
+struct PubSub<'a, H: CmdHandler> {
+    handler: &'a H,
+    subs: Subscriptions,
+    subscriber: SubscriberHandle,
+}
+
+impl<'a, H: CmdHandler> PubSub<'a, H> {
+    fn new(handler: &'a H, subscriber: SubscriberHandle) -> Self {
+        Self {
+            handler,
+            subs: Subscriptions::default(),
+            subscriber,
+        }
+    }
+
+    async fn handle_cmd(&mut self, cmd: Command) -> Vec<RespPacket> {
+        let args: Vec<BinSafeStr> = (1..cmd.get_command_len().unwrap_or(0))
+            .filter_map(|i| cmd.get_command_element(i).map(|arg| arg.to_vec()))
+            .collect();
+        match cmd.get_type() {
+            CmdType::Subscribe => self.subscribe(args).await,
+            CmdType::Unsubscribe => self.unsubscribe(ChannelKind::Regular, args).await,
+            CmdType::PSubscribe => self.psubscribe(args).await,
+            CmdType::PUnsubscribe => self.punsubscribe(args).await,
+            CmdType::SSubscribe => self.ssubscribe(args).await,
+            CmdType::SUnsubscribe => self.unsubscribe(ChannelKind::Shard, args).await,
+            CmdType::Ping => {
+                let msg = args.into_iter().next().unwrap_or_default();
+                vec![gen_array(vec![b"pong".to_vec(), msg])]
+            }
+            CmdType::Quit => vec![RespPacket::from_resp_vec(Resp::Simple(
+                response::OK_REPLY.to_string().into_bytes(),
+            ))],
+            _ => vec![gen_error(response::ERR_PUBSUB_CONTEXT.to_string())],
+        }
+    }
+
+    async fn ssubscribe(&mut self, channels: Vec<BinSafeStr>) -> Vec<RespPacket> {
+        let slot = match channels.first() {
+            Some(channel) => generate_slot(channel),
+            None => return vec![gen_wrong_args_error("ssubscribe")],
+        };
+        if channels
+            .iter()
+            .any(|channel| generate_slot(channel) != slot)
+        {
+            return vec![gen_error(response::ERR_NOT_THE_SAME_SLOT.to_string())];
+        }
+
+        let node = match self.handler.get_slot_owner(slot) {
+            Ok(SlotOwner::Local(node)) => node,
+            Ok(SlotOwner::Remote(address)) => return vec![gen_error(gen_moved(slot, address))],
+            Err(err_msg) => return vec![gen_error(err_msg)],
+        };
+        let kind = ChannelKind::Shard;
+        if !self.send_backend(&node, kind.sub_kind(), &channels).await {
+            let err_msg = format!("{} {}", response::ERR_BACKEND_CONNECTION, node);
+            return vec![gen_error(err_msg)];
+        }
+
+        let mut replies = vec![];
+        for channel in channels.into_iter() {
+            self.update_channel(kind, channel.clone(), slot, Some(node.clone()));
+            replies.push(gen_reply(
+                kind.subscribe_reply(),
+                Some(channel),
+                self.subs.get_count(kind),
+            ));
+        }
+        replies
+    }
+
+    // Unlike the shard channels, the channels of one SUBSCRIBE could be in different slots.
+    // The channels failed to subscribe are kept and resubscribed in the next topology check.
+    async fn subscribe(&mut self, channels: Vec<BinSafeStr>) -> Vec<RespPacket> {
+        if channels.is_empty() {
+            return vec![gen_wrong_args_error("subscribe")];
+        }
+
+        // The owner is None for the key event channels.
+        let mut owners = Vec::with_capacity(channels.len());
+        let mut node_channels: HashMap<String, Vec<BinSafeStr>> = HashMap::new();
+        let mut key_events = vec![];
+        for channel in channels.into_iter() {
+            let slot = match get_channel_slot(&channel) {
+                Some(slot) => slot,
+                None => {
+                    key_events.push(channel.clone());
+                    owners.push((channel, None));
+                    continue;
+                }
+            };
+            let node = match self.handler.get_slot_owner(slot) {
+                Ok(SlotOwner::Local(node)) => Some(node),
+                Ok(SlotOwner::Remote(_)) => None,
+                Err(err_msg) => return vec![gen_error(err_msg)],
+            };
+            if let Some(node) = node.as_ref() {
+                node_channels
+                    .entry(node.clone())
+                    .or_default()
+                    .push(channel.clone());
+            }
+            owners.push((channel, Some((slot, node))));
+        }
+        let event_nodes = if key_events.is_empty() {
+            vec![]
+        } else {
+            match self.handler.get_local_nodes() {
+                Ok(nodes) => nodes,
+                Err(err_msg) => return vec![gen_error(err_msg)],
+            }
+        };
+
+        let kind = ChannelKind::Regular;
+        let event_nodes = self
+            .send_to_nodes(event_nodes, BroadcastKind::KeyEvent, &key_events)
+            .await;
+        let mut failed_nodes = HashSet::new();
+        for (node, channels) in node_channels.into_iter() {
+            if !self.send_backend(&node, kind.sub_kind(), &channels).await {
+                failed_nodes.insert(node);
+            }
+        }
+
+        let mut replies = vec![];
+        for (channel, owner) in owners.into_iter() {
+            match owner {
+                Some((slot, node)) => {
+                    let node = node.filter(|node| !failed_nodes.contains(node));
+                    self.update_channel(kind, channel.clone(), slot, node);
+                }
+                None => self
+                    .subs
+                    .key_events
+                    .entry(channel.clone())
+                    .or_default()
+                    .extend(event_nodes.iter().cloned()),
+            }
+            replies.push(gen_reply(
+                kind.subscribe_reply(),
+                Some(channel),
+                self.subs.get_count(kind),
+            ));
+        }
+        replies
+    }
+
+    async fn unsubscribe(
+        &mut self,
+        kind: ChannelKind,
+        channels: Vec<BinSafeStr>,
+    ) -> Vec<RespPacket> {
+        let channels = if channels.is_empty() {
+            let mut all = self.subs.get_channel_map(kind).get_all();
+            if kind == ChannelKind::Regular {
+                all.extend(self.subs.key_events.keys().cloned());
+            }
+            all
+        } else {
+            channels
+        };
+        if channels.is_empty() {
+            return vec![gen_reply(
+                kind.unsubscribe_reply(),
+                None,
+                self.subs.get_count(kind),
+            )];
+        }
+
+        let mut replies = vec![];
+        let mut node_channels: HashMap<String, Vec<BinSafeStr>> = HashMap::new();
+        for channel in channels.into_iter() {
+            if let Some(node) = self.subs.get_channel_map(kind).remove(&channel) {
+                node_channels.entry(node).or_default().push(channel.clone());
+            }
+            if kind == ChannelKind::Regular {
+                for node in self.subs.key_events.remove(&channel).unwrap_or_default() {
+                    node_channels.entry(node).or_default().push(channel.clone());
+                }
+            }
+            replies.push(gen_reply(
+                kind.unsubscribe_reply(),
+                Some(channel),
+                self.subs.get_count(kind),
+            ));
+        }
+        for (node, channels) in node_channels.into_iter() {
+            self.subscriber
+                .unsubscribe(&node, kind.sub_kind(), &channels);
+        }
+        replies
+    }
+
+    // The patterns failed to subscribe from some nodes are resubscribed in the next topology check.
+    async fn psubscribe(&mut self, patterns: Vec<BinSafeStr>) -> Vec<RespPacket> {
+        if patterns.is_empty() {
+            return vec![gen_wrong_args_error("psubscribe")];
+        }
+        let nodes = match self.handler.get_local_nodes() {
+            Ok(nodes) => nodes,
+            Err(err_msg) => return vec![gen_error(err_msg)],
+        };
+
+        let nodes = self
+            .send_to_nodes(nodes, BroadcastKind::Pattern, &patterns)
+            .await;
+        for pattern in patterns.iter() {
+            self.subs
+                .patterns
+                .entry(pattern.clone())
+                .or_default()
+                .extend(nodes.iter().cloned());
+        }
+
+        let count = self.subs.get_count(ChannelKind::Regular);
+        patterns
+            .into_iter()
+            .map(|pattern| gen_reply(b"psubscribe", Some(pattern), count))
+            .collect()
+    }
+
+    async fn reset(&mut self) {
+        self.unsubscribe(ChannelKind::Regular, vec![]).await;
+        self.unsubscribe(ChannelKind::Shard, vec![]).await;
+        self.punsubscribe(vec![]).await;
+    }
+
+    async fn punsubscribe(&mut self, patterns: Vec<BinSafeStr>) -> Vec<RespPacket> {
+        let patterns = if patterns.is_empty() {
+            self.subs.patterns.keys().cloned().collect()
+        } else {
+            patterns
+        };
+        if patterns.is_empty() {
+            let count = self.subs.get_count(ChannelKind::Regular);
+            return vec![gen_reply(b"punsubscribe", None, count)];
+        }
+
+        let mut replies = vec![];
+        let mut node_patterns: HashMap<String, Vec<BinSafeStr>> = HashMap::new();
+        for pattern in patterns.into_iter() {
+            for node in self.subs.patterns.remove(&pattern).unwrap_or_default() {
+                node_patterns.entry(node).or_default().push(pattern.clone());
+            }
+            let count = self.subs.get_count(ChannelKind::Regular);
+            replies.push(gen_reply(b"punsubscribe", Some(pattern), count));
+        }
+        for (node, patterns) in node_patterns.into_iter() {
+            self.subscriber
+                .unsubscribe(&node, SubKind::Pattern, &patterns);
+        }
+        replies
+    }
+
+    fn handle_backend_msg(&self, node: &str, msg: RespPacket) -> Vec<RespPacket> {
+        if self.subs.should_forward(node, &msg) {
+            vec![msg]
+        } else {
+            vec![]
+        }
+    }
+
+    fn handle_conn_closed(&mut self, node: &str) {
+        self.subs.remove_node(node);
+    }
+
+    async fn check_topology(&mut self) -> Vec<RespPacket> {
+        let replies = self.check_channels(ChannelKind::Shard).await;
+        self.check_channels(ChannelKind::Regular).await;
+        self.check_broadcast(BroadcastKind::KeyEvent).await;
+        self.check_broadcast(BroadcastKind::Pattern).await;
+        replies
+    }
+
+    // The channels are resubscribed from the new owners of the slots.
+    // If the slots are no longer served by this proxy, the shard channels are unsubscribed
+    // like Redis Cluster while the regular channels are kept to resubscribe later.
+    async fn check_channels(&mut self, kind: ChannelKind) -> Vec<RespPacket> {
+        let mut replies = vec![];
+        for (slot, node) in self.subs.get_channel_map(kind).get_slot_nodes().into_iter() {
+            let channels = self.subs.get_channel_map(kind).get_slot_channels(slot);
+            let connected = node
+                .as_ref()
+                .map(|node| self.subscriber.is_connected(node))
+                .unwrap_or(false);
+            if let Ok(SlotOwner::Local(new_node)) = self.handler.get_slot_owner(slot) {
+                if connected && node.as_ref() == Some(&new_node) {
+                    continue;
+                }
+                if self
+                    .send_backend(&new_node, kind.sub_kind(), &channels)
+                    .await
+                {
+                    info!("resubscribe slot {} from {:?} to {}", slot, node, new_node);
+                    for channel in channels.into_iter() {
+                        self.update_channel(kind, channel, slot, Some(new_node.clone()));
+                    }
+                    continue;
+                }
+            }
+
+            match kind {
+                ChannelKind::Shard => {
+                    info!("unsubscribe slot {} from {:?}", slot, node);
+                    if let Some(node) = node.as_ref() {
+                        self.subscriber
+                            .unsubscribe(node, kind.sub_kind(), &channels);
+                    }
+                    for channel in channels.into_iter() {
+                        self.subs.shard_channels.remove(&channel);
+                        replies.push(gen_reply(
+                            kind.unsubscribe_reply(),
+                            Some(channel),
+                            self.subs.get_count(kind),
+                        ));
+                    }
+                }
+                ChannelKind::Regular if node.is_some() => {
+                    warn!("failed to resubscribe slot {} from {:?}", slot, node);
+                    for channel in channels.into_iter() {
+                        self.update_channel(kind, channel, slot, None);
+                    }
+                }
+                ChannelKind::Regular => (),
+            }
+        }
+        replies
+    }
+
+    // The patterns and the key event channels are subscribed from the new nodes.
+    async fn check_broadcast(&mut self, kind: BroadcastKind) {
+        if self.subs.get_broadcast_map(kind).is_empty() {
+            return;
+        }
+        let nodes = match self.handler.get_local_nodes() {
+            Ok(nodes) => nodes,
+            Err(err_msg) => {
+                warn!("failed to get local nodes for {:?}: {}", kind, err_msg);
+                return;
+            }
+        };
+
+        // The targets are unsubscribed from the nodes no longer serving this cluster.
+        let mut removed: HashMap<String, Vec<BinSafeStr>> = HashMap::new();
+        for (target, nodes_subscribed) in self.subs.get_broadcast_map(kind).iter_mut() {
+            nodes_subscribed.retain(|node| {
+                if nodes.contains(node) {
+                    return true;
+                }
+                removed
+                    .entry(node.clone())
+                    .or_default()
+                    .push(target.clone());
+                false
+            });
+        }
+        for (node, targets) in removed.into_iter() {
+            self.subscriber
+                .unsubscribe(&node, kind.sub_kind(), &targets);
+        }
+        for node in nodes.into_iter() {
+            let targets: Vec<BinSafeStr> = self
+                .subs
+                .get_broadcast_map(kind)
+                .iter()
+                .filter(|(_, nodes_subscribed)| !nodes_subscribed.contains(&node))
+                .map(|(target, _)| target.clone())
+                .collect();
+            if targets.is_empty() {
+                continue;
+            }
+            if self.send_backend(&node, kind.sub_kind(), &targets).await {
+                info!("resubscribe {} {:?} from {}", targets.len(), kind, node);
+                for target in targets.iter() {
+                    if let Some(nodes_subscribed) =
+                        self.subs.get_broadcast_map(kind).get_mut(target)
+                    {
+                        nodes_subscribed.insert(node.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns the nodes subscribed from successfully.
+    async fn send_to_nodes(
+        &mut self,
+        nodes: Vec<String>,
+        kind: BroadcastKind,
+        targets: &[BinSafeStr],
+    ) -> HashSet<String> {
+        let mut subscribed = HashSet::new();
+        if targets.is_empty() {
+            return subscribed;
+        }
+        for node in nodes.into_iter() {
+            if self.send_backend(&node, kind.sub_kind(), targets).await {
+                subscribed.insert(node);
+            }
+        }
+        subscribed
+    }
+
+    fn update_channel(
+        &mut self,
+        kind: ChannelKind,
+        channel: BinSafeStr,
+        slot: usize,
+        node: Option<String>,
+    ) {
+        let old_node = self
+            .subs
+            .get_channel_map(kind)
+            .insert(channel.clone(), slot, node.clone());
+        if let Some(old_node) = old_node {
+            if node.as_ref() != Some(&old_node) {
+                self.subscriber
+                    .unsubscribe(&old_node, kind.sub_kind(), slice::from_ref(&channel));
+            }
+        }
+    }
+
+    async fn send_backend(&mut self, node: &str, kind: SubKind, targets: &[BinSafeStr]) -> bool {
+        self.subscriber.subscribe(node, kind, targets).await
+    }
+}
+
+async fn connect_backend(node: &str) -> Option<(ConnSink<RespPacket>, ConnStream<RespPacket>)> {
+    let address = lookup_first_address(node).await?;
+    let conn_fut = DefaultConnFactory::<RespPacket>::default().create_conn(address);
+    match time::timeout(BACKEND_CONN_TIMEOUT, conn_fut).await {
+        Ok(Ok(conn)) => Some(conn),
+        _ => {
+            warn!("failed to connect to {} for pub/sub", node);
+            None
+        }
+    }
+}
+
+enum ConnEvent {
+    Cmd(Option<RespPacket>),
+    Msg(Option<RespPacket>),
+}
+
+// Sends the commands to the backend and fans out the messages
+// until the connection is closed or no session subscribes from it.
+async fn forward_backend_msgs(
+    nodes: NodeSubscribers,
+    key: (ClusterName, String),
+    conn_id: usize,
+    mut sink: ConnSink<RespPacket>,
+    mut stream: ConnStream<RespPacket>,
+    mut cmd_receiver: mpsc::UnboundedReceiver<RespPacket>,
+) {
+    let node = key.1.clone();
+    loop {
+        let event = select! {
+            cmd = cmd_receiver.next().fuse() => ConnEvent::Cmd(cmd),
+            msg = stream.next().fuse() => ConnEvent::Msg(msg.and_then(|msg| msg.ok())),
+        };
+        match event {
+            ConnEvent::Cmd(Some(cmd)) => {
+                if let Err(err) = sink.send(cmd).await {
+                    warn!("failed to send pub/sub command to {}: {:?}", node, err);
+                    break;
+                }
+            }
+            // The subscriber is removed after no session subscribes from it.
+            ConnEvent::Cmd(None) => return,
+            ConnEvent::Msg(Some(msg)) => {
+                let mut nodes = nodes.lock().expect("forward_backend_msgs");
+                if let Some(node_sub) = nodes.get_mut(&key) {
+                    if node_sub.conn_id == conn_id {
+                        node_sub.dispatch(&node, msg);
+                    }
+                }
+            }
+            ConnEvent::Msg(None) => break,
+        }
+    }
+
+    let mut nodes = nodes.lock().expect("forward_backend_msgs");
+    let is_current = nodes
+        .get(&key)
+        .map(|node_sub| node_sub.conn_id == conn_id)
+        .unwrap_or(false);
+    if is_current {
+        if let Some(node_sub) = nodes.remove(&key) {
+            node_sub.close(&node);
+        }
+    }
+}
+
+// Handles the requests in read_buf and the following ones until all the channels are unsubscribed.
+// Returns false if the session should be closed.
+pub async fn handle_pubsub<H, R, W>(
+    handler: &H,
+    reader: &mut R,
+    writer: &mut W,
+    read_buf: &mut VecDeque<SessionReq>,
+    mut shutdown: Option<&mut watch::Receiver<bool>>,
+) -> Result<bool, SessionError>
+where
+    H: CmdHandler,
+    R: Stream<Item = Vec<SessionReq>> + Unpin,
+    W: Sink<Box<RespPacket>, Error = EncodeError<Box<RespPacket>>> + Unpin,
+{
+    let (subscriber, mut event_receiver) = match handler.register_subscriber() {
+        Ok(subscriber) => subscriber,
+        Err(err_msg) => {
+            if let Some(req) = read_buf.pop_front() {
+                req?;
+            }
+            send_replies(writer, vec![gen_error(err_msg)]).await?;
+            return Ok(true);
+        }
+    };
+    let mut pubsub = PubSub::new(handler, subscriber);
+    let mut topology_check = time::interval(TOPOLOGY_CHECK_INTERVAL);
+
+    loop {
+        while let Some(req) = read_buf.pop_front() {
+            let cmd = Command::new(req?);
+            // Leave the pub/sub mode and let the session reset the rest.
+            if cmd.get_type() == CmdType::Reset {
+                pubsub.reset().await;
+                read_buf.push_front(Ok(cmd.into_packet()));
+                return Ok(true);
+            }
+            let is_quit = cmd.get_type() == CmdType::Quit;
+            let replies = pubsub.handle_cmd(cmd).await;
+            send_replies(writer, replies).await?;
+            if is_quit {
+                return Ok(false);
+            }
+            if pubsub.subs.is_empty() {
+                return Ok(true);
+            }
+        }
+
+        let mut read_fut = reader.next().fuse();
+        let mut event_fut = event_receiver.next();
+        let mut tick_fut = Box::pin(topology_check.tick().fuse());
+        let mut shutdown_fut = match shutdown.as_mut() {
+            Some(shutdown) => future::Either::Left(Box::pin(wait_shutdown(shutdown))),
+            None => future::Either::Right(future::pending::<()>()),
+        }
+        .fuse();
+
+        let replies = select! {
+            reqs = read_fut => match reqs {
+                Some(reqs) => {
+                    read_buf.extend(reqs);
+                    continue;
+                }
+                None => return Ok(false),
+            },
+            event = event_fut => match event {
+                Some(BackendEvent::Message(node, msg)) => pubsub.handle_backend_msg(&node, msg),
+                Some(BackendEvent::Closed(node)) => {
+                    warn!("pub/sub connection to {} is closed", node);
+                    pubsub.handle_conn_closed(&node);
+                    pubsub.check_topology().await
+                }
+                None => vec![],
+            },
+            _ = tick_fut => pubsub.check_topology().await,
+            () = shutdown_fut => return Ok(true),
+        };
+        send_replies(writer, replies).await?;
+        if pubsub.subs.is_empty() {
+            return Ok(true);
+        }
+    }
+}
+
+async fn send_replies<W>(writer: &mut W, replies: Vec<RespPacket>) -> Result<(), SessionError>
+where
+    W: Sink<Box<RespPacket>, Error = EncodeError<Box<RespPacket>>> + Unpin,
+{
+    let mut batch = stream::iter(replies.into_iter().map(Box::new)).map(Ok);
+    writer.send_all(&mut batch).await.map_err(|err| {
+        error!("writer error: {}", err);
+        match err {
+            EncodeError::Io(err) => SessionError::Io(err),
+            EncodeError::NotReady(_) => SessionError::InvalidState,
+        }
+    })
+}
+
+fn gen_array(elements: Vec<BinSafeStr>) -> RespPacket {
+    let elements = elements
+        .into_iter()
+        .map(|e| Resp::Bulk(BulkStr::Str(e)))
+        .collect();
+    RespPacket::from_resp_vec(Resp::Arr(Array::Arr(elements)))
+}
+
+fn gen_error(err_msg: String) -> RespPacket {
+    RespPacket::from_resp_vec(Resp::Error(err_msg.into_bytes()))
+}
+
+fn gen_wrong_args_error(cmd_name: &str) -> RespPacket {
+    gen_error(format!(
+        "ERR wrong number of arguments for '{}' command",
+        cmd_name
+    ))
+}
+
+fn gen_reply(kind: &[u8], channel: Option<BinSafeStr>, count: usize) -> RespPacket {
+    let channel = match channel {
+        Some(channel) => Resp::Bulk(BulkStr::Str(channel)),
+        None => Resp::Bulk(BulkStr::Nil),
+    };
+    RespPacket::from_resp_vec(Resp::Arr(Array::Arr(vec![
+        Resp::Bulk(BulkStr::Str(kind.to_vec())),
+        channel,
+        Resp::Integer(count.to_string().into_bytes()),
+    ])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn gen_msg(elements: Vec<&str>) -> RespPacket {
+        gen_array(
+            elements
+                .into_iter()
+                .map(|e| e.as_bytes().to_vec())
+                .collect(),
+        )
+    }
+
+    fn node(node: &str) -> Option<String> {
+        Some(node.to_string())
+    }
+
+    #[test]
+    fn test_shard_channels() {
+        let mut subs = Subscriptions::default();
+        let channels = &mut subs.shard_channels;
+        assert_eq!(channels.insert(b"a".to_vec(), 1, node("node1")), None);
+        assert_eq!(channels.insert(b"b".to_vec(), 1, node("node1")), None);
+        assert_eq!(channels.insert(b"c".to_vec(), 2, node("node2")), None);
+        assert_eq!(channels.get_slot_nodes().len(), 2);
+        assert_eq!(channels.get_slot_channels(1).len(), 2);
+        assert_eq!(subs.get_count(ChannelKind::Shard), 3);
+        assert_eq!(subs.get_count(ChannelKind::Regular), 0);
+
+        assert!(subs.should_forward("node1", &gen_msg(vec!["smessage", "a", "msg"])));
+        assert!(subs.should_forward("node1", &gen_msg(vec!["SMESSAGE", "b", "msg"])));
+        assert!(!subs.should_forward("node2", &gen_msg(vec!["smessage", "a", "msg"])));
+        assert!(!subs.should_forward("node1", &gen_msg(vec!["smessage", "d", "msg"])));
+        assert!(!subs.should_forward("node1", &gen_msg(vec!["message", "a", "msg"])));
+        assert!(!subs.should_forward("node1", &gen_msg(vec!["ssubscribe", "a", "1"])));
+        assert!(subs.should_forward("node1", &gen_error("ERR".to_string())));
+
+        // The slot is migrated to another node.
+        let channels = &mut subs.shard_channels;
+        assert_eq!(
+            channels.insert(b"a".to_vec(), 1, node("node2")),
+            node("node1")
+        );
+        assert_eq!(channels.remove(b"b"), node("node1"));
+        assert_eq!(channels.remove(b"b"), None);
+        assert!(!subs.should_forward("node1", &gen_msg(vec!["smessage", "a", "msg"])));
+        assert!(subs.should_forward("node2", &gen_msg(vec!["smessage", "a", "msg"])));
+    }
+
+    #[test]
+    fn test_regular_channels_and_patterns() {
+        let mut subs = Subscriptions::default();
+        subs.channels.insert(b"a".to_vec(), 1, node("node1"));
+        // Waiting to be resubscribed after failover.
+        subs.channels.insert(b"b".to_vec(), 2, None);
+        subs.patterns
+            .entry(b"p*".to_vec())
+            .or_default()
+            .insert("node1".to_string());
+        subs.patterns
+            .entry(b"p*".to_vec())
+            .or_default()
+            .insert("node2".to_string());
+        assert_eq!(subs.get_count(ChannelKind::Regular), 3);
+        assert_eq!(subs.get_count(ChannelKind::Shard), 0);
+        assert!(!subs.is_empty());
+
+        assert!(subs.should_forward("node1", &gen_msg(vec!["message", "a", "msg"])));
+        assert!(!subs.should_forward("node1", &gen_msg(vec!["message", "b", "msg"])));
+        assert!(!subs.should_forward("node1", &gen_msg(vec!["smessage", "a", "msg"])));
+        let pmessage = gen_msg(vec!["pmessage", "p*", "p1", "msg"]);
+        assert!(subs.should_forward("node2", &pmessage));
+
+        // The patterns are resubscribed after the connection is closed.
+        subs.remove_node("node2");
+        assert!(!subs.should_forward("node2", &pmessage));
+        assert!(subs.should_forward("node1", &pmessage));
+        assert_eq!(subs.channels.get_slot_nodes().get(&2), Some(&None));
+    }
+
+    #[test]
+    fn test_key_event_channels() {
+        let mut subs = Subscriptions::default();
+        let channel = b"__keyevent@0__:expired".to_vec();
+        let nodes = subs.get_broadcast_map(BroadcastKind::KeyEvent);
+        let nodes = nodes.entry(channel).or_default();
+        nodes.insert("node1".to_string());
+        nodes.insert("node2".to_string());
+        assert_eq!(subs.get_count(ChannelKind::Regular), 1);
+
+        let msg = gen_msg(vec!["message", "__keyevent@0__:expired", "key1"]);
+        assert!(subs.should_forward("node1", &msg));
+        assert!(subs.should_forward("node2", &msg));
+        assert!(!subs.should_forward("node3", &msg));
+
+        subs.remove_node("node2");
+        assert!(!subs.should_forward("node2", &msg));
+        assert!(subs.should_forward("node1", &msg));
+    }
+
+    fn targets(targets: Vec<&str>) -> Vec<BinSafeStr> {
+        targets.into_iter().map(|t| t.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_node_subscriber_ref_count() {
+        let (cmd_sender, _cmd_receiver) = mpsc::unbounded();
+        let mut node_sub = NodeSubscriber::new(0, cmd_sender);
+        let (sender1, _receiver1) = mpsc::unbounded();
+        let (sender2, _receiver2) = mpsc::unbounded();
+
+        let kind = SubKind::Channel;
+        assert_eq!(
+            node_sub.add(1, &sender1, kind, &targets(vec!["a", "b"])),
+            targets(vec!["a", "b"])
+        );
+        // Only the new channels are subscribed from the backend.
+        assert_eq!(
+            node_sub.add(2, &sender2, kind, &targets(vec!["b", "c"])),
+            targets(vec!["c"])
+        );
+        // The same name of another kind is subscribed separately.
+        assert_eq!(
+            node_sub.add(2, &sender2, SubKind::Pattern, &targets(vec!["a"])),
+            targets(vec!["a"])
+        );
+
+        assert_eq!(
+            node_sub.remove(1, kind, &targets(vec!["a", "b"])),
+            targets(vec!["a"])
+        );
+        assert!(!node_sub.has_session(1));
+        assert!(node_sub.has_session(2));
+        assert!(node_sub.remove(1, kind, &targets(vec!["c"])).is_empty());
+
+        let mut removed = node_sub.remove_session(2);
+        let mut channels = removed.remove(&kind).unwrap();
+        channels.sort();
+        assert_eq!(channels, targets(vec!["b", "c"]));
+        assert_eq!(removed.remove(&SubKind::Pattern), Some(targets(vec!["a"])));
+        assert!(node_sub.is_idle());
+        assert!(!node_sub.has_session(2));
+    }
+
+    #[test]
+    fn test_node_subscriber_fan_out() {
+        let (cmd_sender, _cmd_receiver) = mpsc::unbounded();
+        let mut node_sub = NodeSubscriber::new(0, cmd_sender);
+        let (sender1, mut receiver1) = mpsc::unbounded();
+        let (sender2, mut receiver2) = mpsc::unbounded();
+        node_sub.add(1, &sender1, SubKind::Channel, &targets(vec!["a"]));
+        node_sub.add(2, &sender2, SubKind::Channel, &targets(vec!["a", "b"]));
+        node_sub.add(2, &sender2, SubKind::Pattern, &targets(vec!["p*"]));
+
+        let is_msg = |event: Option<BackendEvent>, msg: &RespPacket| match event {
+            Some(BackendEvent::Message(node, m)) => {
+                node == "node1" && m.to_resp_vec() == msg.to_resp_vec()
+            }
+            _ => false,
+        };
+
+        let msg = gen_msg(vec!["message", "a", "msg"]);
+        node_sub.dispatch("node1", msg.clone());
+        assert!(is_msg(receiver1.try_next().ok().flatten(), &msg));
+        assert!(is_msg(receiver2.try_next().ok().flatten(), &msg));
+
+        let msg = gen_msg(vec!["message", "b", "msg"]);
+        node_sub.dispatch("node1", msg.clone());
+        assert!(receiver1.try_next().is_err());
+        assert!(is_msg(receiver2.try_next().ok().flatten(), &msg));
+
+        let msg = gen_msg(vec!["pmessage", "p*", "p1", "msg"]);
+        node_sub.dispatch("node1", msg.clone());
+        assert!(receiver1.try_next().is_err());
+        assert!(is_msg(receiver2.try_next().ok().flatten(), &msg));
+
+        // The confirmations are dropped.
+        node_sub.dispatch("node1", gen_msg(vec!["subscribe", "a", "1"]));
+        assert!(receiver1.try_next().is_err());
+        assert!(receiver2.try_next().is_err());
+
+        node_sub.close("node1");
+        assert!(matches!(
+            receiver1.try_next(),
+            Ok(Some(BackendEvent::Closed(_)))
+        ));
+        assert!(matches!(
+            receiver2.try_next(),
+            Ok(Some(BackendEvent::Closed(_)))
+        ));
+    }
+
+    #[test]
+    fn test_subscriber_handle_drop() {
+        let subscribers = Subscribers::default();
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let (handle1, _receiver1) = subscribers.register(cluster_name.clone());
+        let (handle2, _receiver2) = subscribers.register(cluster_name.clone());
+        let (cmd_sender, mut cmd_receiver) = mpsc::unbounded();
+        {
+            let mut nodes = subscribers.nodes.lock().unwrap();
+            let mut node_sub = NodeSubscriber::new(0, cmd_sender);
+            node_sub.add(
+                handle1.session_id,
+                &handle1.event_sender,
+                SubKind::Channel,
+                &targets(vec!["a", "b"]),
+            );
+            node_sub.add(
+                handle2.session_id,
+                &handle2.event_sender,
+                SubKind::Channel,
+                &targets(vec!["b"]),
+            );
+            nodes.insert((cluster_name, "node1".to_string()), node_sub);
+        }
+        assert!(handle1.is_connected("node1"));
+        assert!(!handle1.is_connected("node2"));
+
+        drop(handle1);
+        let unsubscribe = gen_msg(vec!["UNSUBSCRIBE", "a"]);
+        let cmd = cmd_receiver.try_next().ok().flatten();
+        assert_eq!(
+            cmd.map(|cmd| cmd.to_resp_vec()),
+            Some(unsubscribe.to_resp_vec())
+        );
+        assert!(handle2.is_connected("node1"));
+
+        // The connection is released after all the sessions are gone.
+        drop(handle2);
+        let unsubscribe = gen_msg(vec!["UNSUBSCRIBE", "b"]);
+        let cmd = cmd_receiver.try_next().ok().flatten();
+        assert_eq!(
+            cmd.map(|cmd| cmd.to_resp_vec()),
+            Some(unsubscribe.to_resp_vec())
+        );
+        assert!(subscribers.nodes.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::protocol::BinSafeStr;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// The keys written by each session within the window are kept
+// so that the following reads of them go to the master instead of the replicas.
+// The writes not replied yet are also kept for the request coalescing
+// so that the reads of them won't join the GET sent before the writes.
+pub const MAX_RECENT_WRITE_KEYS: usize = 1024;
+
+pub struct RecentWrites {
+    window: Duration,
+    keys: HashMap<BinSafeStr, Instant>,
+    order: VecDeque<(BinSafeStr, Instant)>,
+    // Too many keys are written within the window and some are dropped before expired,
+    // so all the reads are pinned until this time.
+    pin_all_until: Option<Instant>,
+    track_pending: bool,
+    pending: HashMap<BinSafeStr, usize>,
+}
+
+impl RecentWrites {
+    pub fn new(window: Duration, track_pending: bool) -> Self {
+        Self {
+            window,
+            keys: HashMap::new(),
+            order: VecDeque::new(),
+            pin_all_until: None,
+            track_pending,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.window != Duration::from_secs(0) || self.track_pending
+    }
+
+    // Returns the keys to be passed to `finish_pending` after the writes are replied.
+    pub fn record<'a, It: Iterator<Item = &'a [u8]>>(
+        &mut self,
+        keys: It,
+        now: Instant,
+    ) -> Option<Vec<BinSafeStr>> {
+        let keys: Vec<BinSafeStr> = keys.map(|key| key.to_vec()).collect();
+        if self.window != Duration::from_secs(0) {
+            for key in keys.iter() {
+                self.keys.insert(key.clone(), now);
+                self.order.push_back((key.clone(), now));
+            }
+            self.prune(now);
+        }
+        if !self.track_pending || keys.is_empty() {
+            return None;
+        }
+        for key in keys.iter() {
+            *self.pending.entry(key.clone()).or_insert(0) += 1;
+        }
+        Some(keys)
+    }
+
+    pub fn finish_pending(&mut self, keys: Vec<BinSafeStr>) {
+        for key in keys.into_iter() {
+            if let Some(count) = self.pending.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    self.pending.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn should_pin<'a, It: Iterator<Item = &'a [u8]>>(
+        &mut self,
+        mut keys: It,
+        now: Instant,
+    ) -> bool {
+        self.prune(now);
+        if self.pin_all_until.is_some() {
+            return true;
+        }
+        keys.any(|key| self.keys.contains_key(key) || self.pending.contains_key(key))
+    }
+
+    fn prune(&mut self, now: Instant) {
+        if let Some(until) = self.pin_all_until {
+            if until <= now {
+                self.pin_all_until = None;
+            }
+        }
+        while let Some((_, written)) = self.order.front() {
+            let expired = now.duration_since(*written) >= self.window;
+            if !expired && self.order.len() <= MAX_RECENT_WRITE_KEYS {
+                break;
+            }
+            let (key, written) = match self.order.pop_front() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if !expired {
+                self.pin_all_until = Some(written + self.window);
+            }
+            // The key could be written again later.
+            if self.keys.get(&key) == Some(&written) {
+                self.keys.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_writes() {
+        let window = Duration::from_millis(100);
+        let mut recent_writes = RecentWrites::new(window, false);
+        assert!(recent_writes.is_enabled());
+        assert!(!RecentWrites::new(Duration::from_secs(0), false).is_enabled());
+
+        let now = Instant::now();
+        recent_writes.record(vec!["a".as_bytes()].into_iter(), now);
+        let later = now + Duration::from_millis(50);
+        recent_writes.record(vec!["a".as_bytes(), "b".as_bytes()].into_iter(), later);
+        assert!(recent_writes.should_pin(vec!["a".as_bytes()].into_iter(), later));
+        assert!(!recent_writes.should_pin(vec!["c".as_bytes()].into_iter(), later));
+
+        // The second write of `a` is still in the window.
+        let after_first = now + Duration::from_millis(120);
+        assert!(recent_writes.should_pin(vec!["a".as_bytes()].into_iter(), after_first));
+        let after_all = now + Duration::from_millis(200);
+        assert!(!recent_writes.should_pin(vec!["a".as_bytes()].into_iter(), after_all));
+        assert!(!recent_writes.should_pin(vec!["b".as_bytes()].into_iter(), after_all));
+    }
+
+    #[test]
+    fn test_too_many_recent_writes() {
+        let window = Duration::from_millis(100);
+        let mut recent_writes = RecentWrites::new(window, false);
+        let now = Instant::now();
+        let keys: Vec<String> = (0..=MAX_RECENT_WRITE_KEYS).map(|i| i.to_string()).collect();
+        recent_writes.record(keys.iter().map(|k| k.as_bytes()), now);
+        assert!(recent_writes.should_pin(vec!["c".as_bytes()].into_iter(), now));
+        let expired = now + window;
+        assert!(!recent_writes.should_pin(vec!["c".as_bytes()].into_iter(), expired));
+    }
+
+    #[test]
+    fn test_pending_writes() {
+        let mut recent_writes = RecentWrites::new(Duration::from_secs(0), true);
+        assert!(recent_writes.is_enabled());
+        let now = Instant::now();
+        let pending1 = recent_writes
+            .record(vec!["a".as_bytes()].into_iter(), now)
+            .unwrap();
+        let pending2 = recent_writes
+            .record(vec!["a".as_bytes()].into_iter(), now)
+            .unwrap();
+        assert!(recent_writes.should_pin(vec!["a".as_bytes()].into_iter(), now));
+        assert!(!recent_writes.should_pin(vec!["b".as_bytes()].into_iter(), now));
+
+        recent_writes.finish_pending(pending1);
+        assert!(recent_writes.should_pin(vec!["a".as_bytes()].into_iter(), now));
+        recent_writes.finish_pending(pending2);
+        assert!(!recent_writes.should_pin(vec!["a".as_bytes()].into_iter(), now));
+    }
+}
@@ -0,0 +1,179 @@
+use super::backend::{ConnFactory, ReqTask};
+use super::manager::SharedMetaMap;
+use super::reply::DecompressCommitHandlerFactory;
+use super::sender::{
+    gen_migration_sender_factory, CmdTaskSender, CmdTaskSenderFactory,
+    MigrationBackendSenderFactory,
+};
+use super::service::ServerProxyConfig;
+use super::session::CmdCtx;
+use crate::common::track::TrackedFutureRegistry;
+use crate::protocol::{Resp, RespPacket};
+use btoi::btou;
+use std::collections::HashMap;
+use std::str;
+use std::sync::{Arc, RwLock};
+
+pub const MAX_BACKEND_REDIRECTIONS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redirection {
+    Moved { slot: usize, address: String },
+    Ask { slot: usize, address: String },
+}
+
+// Parses `MOVED <slot> <address>` and `ASK <slot> <address>`
+// replied by backends running in cluster mode.
+pub fn parse_redirection<T: AsRef<[u8]>>(resp: &Resp<T>) -> Option<Redirection> {
+    let err = match resp {
+        Resp::Error(err) => str::from_utf8(err.as_ref()).ok()?,
+        _ => return None,
+    };
+    let mut segs = err.split_whitespace();
+    let tag = segs.next()?;
+    let slot = btou::<usize>(segs.next()?.as_bytes()).ok()?;
+    let address = segs.next()?.to_string();
+    if segs.next().is_some() {
+        return None;
+    }
+    match tag {
+        "MOVED" => Some(Redirection::Moved { slot, address }),
+        "ASK" => Some(Redirection::Ask { slot, address }),
+        _ => None,
+    }
+}
+
+type RedirectionSenderFactory<C> =
+    MigrationBackendSenderFactory<DecompressCommitHandlerFactory<CmdCtx, C>, C>;
+type RedirectionSender<C> = <RedirectionSenderFactory<C> as CmdTaskSenderFactory>::Sender;
+
+struct SlotCache {
+    epoch: u64,
+    // backend address => (slot => redirected node address)
+    backends: HashMap<String, HashMap<usize, String>>,
+}
+
+// Forwards the commands to the nodes that backends in cluster mode redirect to.
+// Both the slot cache and the senders are dropped when the epoch changes.
+pub struct BackendRedirector<C: ConnFactory<Pkt = RespPacket>> {
+    sender_factory: RedirectionSenderFactory<C>,
+    senders: RwLock<HashMap<String, Arc<RedirectionSender<C>>>>,
+    slot_cache: RwLock<SlotCache>,
+}
+
+impl<C: ConnFactory<Pkt = RespPacket>> BackendRedirector<C> {
+    pub fn new(
+        config: Arc<ServerProxyConfig>,
+        meta_map: SharedMetaMap<C>,
+        conn_factory: Arc<C>,
+        future_registry: Arc<TrackedFutureRegistry>,
+    ) -> Self {
+        let sender_factory = gen_migration_sender_factory(
+            config,
+            Arc::new(DecompressCommitHandlerFactory::new(meta_map)),
+            conn_factory,
+            future_registry,
+        );
+        Self {
+            sender_factory,
+            senders: RwLock::new(HashMap::new()),
+            slot_cache: RwLock::new(SlotCache {
+                epoch: 0,
+                backends: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn get_cached_node(&self, epoch: u64, backend: &str, slot: usize) -> Option<String> {
+        let slot_cache = self
+            .slot_cache
+            .read()
+            .expect("BackendRedirector::get_cached_node");
+        if slot_cache.epoch != epoch {
+            return None;
+        }
+        slot_cache
+            .backends
+            .get(backend)
+            .and_then(|slots| slots.get(&slot))
+            .cloned()
+    }
+
+    pub fn set_cached_node(&self, epoch: u64, backend: String, slot: usize, address: String) {
+        let mut slot_cache = self
+            .slot_cache
+            .write()
+            .expect("BackendRedirector::set_cached_node");
+        if slot_cache.epoch != epoch {
+            slot_cache.epoch = epoch;
+            slot_cache.backends.clear();
+            self.senders
+                .write()
+                .expect("BackendRedirector::set_cached_node")
+                .clear();
+        }
+        slot_cache
+            .backends
+            .entry(backend)
+            .or_default()
+            .insert(slot, address);
+    }
+
+    pub fn send(&self, cmd_task: ReqTask<CmdCtx>, address: String) {
+        let sender = self.get_sender(address);
+        if let Err(err) = sender.send(cmd_task) {
+            warn!("failed to send redirected command: {:?}", err);
+        }
+    }
+
+    fn get_sender(&self, address: String) -> Arc<RedirectionSender<C>> {
+        if let Some(sender) = self
+            .senders
+            .read()
+            .expect("BackendRedirector::get_sender")
+            .get(&address)
+        {
+            return sender.clone();
+        }
+
+        let sender = Arc::new(self.sender_factory.create(address.clone()));
+        self.senders
+            .write()
+            .expect("BackendRedirector::get_sender")
+            .entry(address)
+            .or_insert(sender)
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::RespVec;
+
+    #[test]
+    fn test_parse_redirection() {
+        let moved: RespVec = Resp::Error(b"MOVED 3999 127.0.0.1:6381".to_vec());
+        assert_eq!(
+            parse_redirection(&moved),
+            Some(Redirection::Moved {
+                slot: 3999,
+                address: "127.0.0.1:6381".to_string()
+            })
+        );
+        let ask: RespVec = Resp::Error(b"ASK 3999 127.0.0.1:6381".to_vec());
+        assert_eq!(
+            parse_redirection(&ask),
+            Some(Redirection::Ask {
+                slot: 3999,
+                address: "127.0.0.1:6381".to_string()
+            })
+        );
+        let other: RespVec = Resp::Error(b"ERR unknown command".to_vec());
+        assert_eq!(parse_redirection(&other), None);
+        let invalid_slot: RespVec = Resp::Error(b"MOVED slot 127.0.0.1:6381".to_vec());
+        assert_eq!(parse_redirection(&invalid_slot), None);
+        let simple: RespVec = Resp::Simple(b"MOVED 3999 127.0.0.1:6381".to_vec());
+        assert_eq!(parse_redirection(&simple), None);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::protocol::{
+    BulkStr, PooledRedisClient, PooledRedisClientFactory, RedisClient, RedisClientFactory, Resp,
+};
+use futures::channel::oneshot;
+use futures::future;
+use futures_timer::Delay;
+use std::collections::{HashMap, HashSet};
+use std::str;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// The writes of the clusters with `write_ack_policy` of `replica` are replied after
+// the offset of any replica reaches the master offset retrieved after the master replies them.
+// The `INFO REPLICATION` of each master is shared by all the writes waiting for it.
+const REPLICA_ACK_CONN_POOL_SIZE: usize = 4;
+const REPLICA_ACK_CONN_TIMEOUT: Duration = Duration::from_secs(1);
+const REPLICA_ACK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Default)]
+struct AckWaiters {
+    // The writes replied by the master before the next `INFO REPLICATION` is sent.
+    pending: Vec<oneshot::Sender<()>>,
+    // The writes waiting for the replicas to reach the master offset.
+    waiting: Vec<(u64, oneshot::Sender<()>)>,
+    polling: bool,
+    // The master is removed from the metadata.
+    removed: bool,
+}
+
+impl AckWaiters {
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.waiting.is_empty()
+    }
+
+    fn remove(&mut self) {
+        self.removed = true;
+        self.pending.clear();
+        self.waiting.clear();
+    }
+
+    // `pending` is taken before sending the `INFO REPLICATION`.
+    // Dropping the senders tells the waiters that the writes can't be acknowledged.
+    fn update(&mut self, pending: Vec<oneshot::Sender<()>>, offsets: Option<(u64, Option<u64>)>) {
+        let (master_offset, replica_offset) = match offsets {
+            _ if self.removed => {
+                self.waiting.clear();
+                return;
+            }
+            Some((master_offset, Some(replica_offset))) => (master_offset, replica_offset),
+            // No replica is online or the master is not available.
+            _ => {
+                self.waiting.clear();
+                return;
+            }
+        };
+        self.waiting
+            .extend(pending.into_iter().map(|sender| (master_offset, sender)));
+        let waiting = std::mem::take(&mut self.waiting);
+        for (offset, sender) in waiting.into_iter() {
+            if sender.is_canceled() {
+                continue;
+            }
+            if offset <= replica_offset {
+                let _ = sender.send(());
+            } else {
+                self.waiting.push((offset, sender));
+            }
+        }
+    }
+}
+
+pub struct ReplicaAckTracker {
+    client_factory: Arc<PooledRedisClientFactory>,
+    masters: Mutex<HashMap<String, Arc<Mutex<AckWaiters>>>>,
+}
+
+impl Default for ReplicaAckTracker {
+    fn default() -> Self {
+        Self {
+            client_factory: Arc::new(PooledRedisClientFactory::new(
+                REPLICA_ACK_CONN_POOL_SIZE,
+                REPLICA_ACK_CONN_TIMEOUT,
+            )),
+            masters: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ReplicaAckTracker {
+    // Should be called after the masters reply the writes.
+    // Returns false if any of them is not acknowledged by the replicas within the timeout.
+    pub async fn wait_ack(&self, masters: Vec<String>, timeout: Duration) -> bool {
+        let receivers: Vec<_> = masters
+            .into_iter()
+            .map(|master| self.wait(master))
+            .collect();
+        match future::select(future::join_all(receivers), Delay::new(timeout)).await {
+            future::Either::Left((results, _)) => results.iter().all(|res| res.is_ok()),
+            future::Either::Right(((), _)) => false,
+        }
+    }
+
+    // Called with the local masters of the new metadata.
+    // The writes waiting for the removed masters are failed.
+    pub fn retain_masters(&self, masters: &HashSet<String>) {
+        self.masters
+            .lock()
+            .expect("ReplicaAckTracker::retain_masters")
+            .retain(|master, waiters| {
+                if masters.contains(master) {
+                    return true;
+                }
+                waiters
+                    .lock()
+                    .expect("ReplicaAckTracker::retain_masters")
+                    .remove();
+                false
+            });
+    }
+
+    fn wait(&self, master: String) -> oneshot::Receiver<()> {
+        let waiters = self
+            .masters
+            .lock()
+            .expect("ReplicaAckTracker::wait")
+            .entry(master.clone())
+            .or_default()
+            .clone();
+        let (sender, receiver) = oneshot::channel();
+        let mut guard = waiters.lock().expect("ReplicaAckTracker::wait");
+        guard.pending.push(sender);
+        if !guard.polling {
+            guard.polling = true;
+            let client_factory = self.client_factory.clone();
+            tokio::spawn(poll_replica_offsets(
+                client_factory,
+                master,
+                waiters.clone(),
+            ));
+        }
+        receiver
+    }
+}
+
+// Exits when there's no write waiting.
+async fn poll_replica_offsets(
+    client_factory: Arc<PooledRedisClientFactory>,
+    master: String,
+    waiters: Arc<Mutex<AckWaiters>>,
+) {
+    let mut client: Option<PooledRedisClient> = None;
+    loop {
+        let pending = {
+            let mut guard = waiters.lock().expect("poll_replica_offsets");
+            if guard.is_empty() {
+                guard.polling = false;
+                return;
+            }
+            std::mem::take(&mut guard.pending)
+        };
+
+        let offsets = match get_replication_offsets(&client_factory, &mut client, &master).await {
+            Ok(offsets) => offsets,
+            Err(()) => {
+                client = None;
+                None
+            }
+        };
+        waiters
+            .lock()
+            .expect("poll_replica_offsets")
+            .update(pending, offsets);
+
+        Delay::new(REPLICA_ACK_POLL_INTERVAL).await;
+    }
+}
+
+async fn get_replication_offsets(
+    client_factory: &PooledRedisClientFactory,
+    client: &mut Option<PooledRedisClient>,
+    master: &str,
+) -> Result<Option<(u64, Option<u64>)>, ()> {
+    if client.is_none() {
+        let c = client_factory
+            .create_client(master.to_string())
+            .await
+            .map_err(|err| warn!("failed to connect to master {}: {}", master, err))?;
+        *client = Some(c);
+    }
+    let c = client.as_mut().ok_or(())?;
+    let cmd = vec![b"INFO".to_vec(), b"REPLICATION".to_vec()];
+    let resp = c
+        .execute_single(cmd)
+        .await
+        .map_err(|err| warn!("failed to get replication offsets of {}: {}", master, err))?;
+    match resp {
+        Resp::Bulk(BulkStr::Str(s)) => {
+            Ok(str::from_utf8(&s).ok().and_then(parse_replication_offsets))
+        }
+        other => {
+            error!("invalid INFO REPLICATION reply: {:?}", other);
+            Err(())
+        }
+    }
+}
+
+// Returns the master offset and the largest offset of the online replicas.
+fn parse_replication_offsets(info: &str) -> Option<(u64, Option<u64>)> {
+    let mut master_offset = None;
+    let mut replica_offset = None;
+    for line in info.lines() {
+        let mut kv = line.trim().splitn(2, ':');
+        let (key, value) = match (kv.next(), kv.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => continue,
+        };
+        if key == "master_repl_offset" {
+            master_offset = value.parse::<u64>().ok();
+            continue;
+        }
+        // slave<n>:ip=<ip>,port=<port>,state=online,offset=<offset>,lag=<lag>
+        match key.strip_prefix("slave") {
+            Some(n) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => (),
+            _ => continue,
+        }
+        let mut online = false;
+        let mut offset = None;
+        for field in value.split(',') {
+            let mut kv = field.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some("state"), Some(state)) => online = state == "online",
+                (Some("offset"), Some(o)) => offset = o.parse::<u64>().ok(),
+                _ => (),
+            }
+        }
+        if let (true, Some(offset)) = (online, offset) {
+            replica_offset = Some(std::cmp::max(replica_offset.unwrap_or(0), offset));
+        }
+    }
+    master_offset.map(|master_offset| (master_offset, replica_offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_replication_offsets() {
+        let info = "# Replication\r\nrole:master\r\nconnected_slaves:2\r\n\
+            slave0:ip=127.0.0.1,port=6380,state=online,offset=200,lag=1\r\n\
+            slave1:ip=127.0.0.1,port=6381,state=online,offset=233,lag=0\r\n\
+            slave2:ip=127.0.0.1,port=6382,state=wait_bgsave,offset=300,lag=0\r\n\
+            master_repl_offset:240\r\n";
+        assert_eq!(parse_replication_offsets(info), Some((240, Some(233))));
+
+        let info = "role:master\r\nconnected_slaves:0\r\nmaster_repl_offset:10\r\n";
+        assert_eq!(parse_replication_offsets(info), Some((10, None)));
+        assert_eq!(parse_replication_offsets("role:master\r\n"), None);
+    }
+
+    #[test]
+    fn test_ack_waiters() {
+        let mut waiters = AckWaiters::default();
+        let (s1, mut r1) = oneshot::channel();
+        let (s2, mut r2) = oneshot::channel();
+        waiters.update(vec![s1], Some((100, Some(90))));
+        assert_eq!(r1.try_recv(), Ok(None));
+
+        // The second write is replied after the first `INFO REPLICATION`.
+        waiters.update(vec![s2], Some((120, Some(100))));
+        assert_eq!(r1.try_recv(), Ok(Some(())));
+        assert_eq!(r2.try_recv(), Ok(None));
+        assert!(!waiters.is_empty());
+
+        waiters.update(vec![], None);
+        assert!(r2.try_recv().is_err());
+        assert!(waiters.is_empty());
+    }
+
+    #[test]
+    fn test_retain_masters() {
+        let tracker = ReplicaAckTracker::default();
+        let mut receivers = vec![];
+        for master in &["127.0.0.1:6379", "127.0.0.1:6380"] {
+            let waiters = Arc::new(Mutex::new(AckWaiters::default()));
+            let (s1, r1) = oneshot::channel();
+            let (s2, r2) = oneshot::channel();
+            {
+                let mut guard = waiters.lock().unwrap();
+                guard.pending.push(s1);
+                guard.waiting.push((100, s2));
+            }
+            tracker
+                .masters
+                .lock()
+                .unwrap()
+                .insert(master.to_string(), waiters);
+            receivers.push((r1, r2));
+        }
+
+        let masters: HashSet<String> = vec!["127.0.0.1:6379".to_string()].into_iter().collect();
+        tracker.retain_masters(&masters);
+        let retained = {
+            let guard = tracker.masters.lock().unwrap();
+            assert_eq!(guard.len(), 1);
+            assert!(guard.contains_key("127.0.0.1:6379"));
+            assert!(!guard.contains_key("127.0.0.1:6380"));
+            guard.get("127.0.0.1:6379").unwrap().clone()
+        };
+        assert!(!retained.lock().unwrap().removed);
+
+        let (mut r1, mut r2) = receivers.pop().unwrap();
+        assert!(r1.try_recv().is_err());
+        assert!(r2.try_recv().is_err());
+        let (mut r1, mut r2) = receivers.pop().unwrap();
+        assert_eq!(r1.try_recv(), Ok(None));
+        assert_eq!(r2.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn test_removed_ack_waiters() {
+        let mut waiters = AckWaiters::default();
+        waiters.remove();
+        // The writes taken by the polling before the removal are failed.
+        let (s1, mut r1) = oneshot::channel();
+        waiters.update(vec![s1], Some((100, Some(100))));
+        assert!(r1.try_recv().is_err());
+        assert!(waiters.is_empty());
+    }
+}
@@ -4,6 +4,7 @@ use super::backend::{
 use super::compress::{CmdReplyDecompressor, CompressionError, CompressionStrategyMetaMapConfig};
 use super::manager::SharedMetaMap;
 use super::session::CmdCtx;
+use crate::common::response::UM_BACKEND;
 use crate::common::utils::Wrapper;
 use crate::protocol::{BulkStr, Resp, RespPacket};
 use std::marker::PhantomData;
@@ -72,7 +73,7 @@ where
             Ok(pkt) => pkt,
             Err(err) => {
                 return cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    format!("backend failed to handle task: {:?}", err).into_bytes(),
+                    format!("{} backend failed to handle task: {:?}", UM_BACKEND, err).into_bytes(),
                 )));
             }
         };
@@ -124,7 +125,7 @@ impl CmdTaskResultHandler for ReplyCommitHandler {
             Ok(pkt) => pkt,
             Err(err) => {
                 return cmd_ctx.set_resp_result(Ok(Resp::Error(
-                    format!("backend failed to handle task: {:?}", err).into_bytes(),
+                    format!("{} backend failed to handle task: {:?}", UM_BACKEND, err).into_bytes(),
                 )));
             }
         };
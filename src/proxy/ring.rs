@@ -0,0 +1,94 @@
+use crate::common::utils::get_hash_tag;
+use crc64::crc64;
+use std::cmp::max;
+
+// Each owner gets one virtual node for every this number of slots.
+const SLOTS_PER_VIRTUAL_NODE: usize = 16;
+
+// A consistent hash ring with the virtual nodes weighted by the slots of the owners.
+// The proxies of the same cluster build the same ring from the same metadata.
+pub struct HashRing {
+    // (hash, owner index) sorted by the hash
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    // `owners` are the owner identities and their slot numbers.
+    pub fn new(owners: &[(&str, usize)]) -> Self {
+        let mut points = vec![];
+        for (index, (owner, slots)) in owners.iter().enumerate() {
+            if *slots == 0 {
+                continue;
+            }
+            let virtual_nodes = max(1, slots / SLOTS_PER_VIRTUAL_NODE);
+            for i in 0..virtual_nodes {
+                let point = format!("{}#{}", owner, i);
+                points.push((crc64(0, point.as_bytes()), index));
+            }
+        }
+        // Break the ties with the owner index so that the order is stable.
+        points.sort();
+        Self { points }
+    }
+
+    // Returns the owner index. The keys with the same hash tag go to the same owner.
+    pub fn get(&self, key: &[u8]) -> Option<usize> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let hash = crc64(0, get_hash_tag(key));
+        let pos = match self.points.binary_search(&(hash, 0)) {
+            Ok(pos) | Err(pos) => pos,
+        };
+        let (_, index) = self.points.get(pos).unwrap_or(&self.points[0]);
+        Some(*index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_ring() {
+        assert_eq!(HashRing::new(&[]).get(b"key"), None);
+        assert_eq!(HashRing::new(&[("a", 0)]).get(b"key"), None);
+
+        let owners = [("a", 4096), ("b", 4096), ("c", 4096), ("d", 4096)];
+        let ring = HashRing::new(&owners);
+        let keys: Vec<String> = (0..10000).map(|i| format!("key{}", i)).collect();
+        let old: Vec<usize> = keys
+            .iter()
+            .map(|k| ring.get(k.as_bytes()).unwrap())
+            .collect();
+        for index in 0..owners.len() {
+            let count = old.iter().filter(|i| **i == index).count();
+            assert!(count > 1500 && count < 3500, "{}", count);
+        }
+        assert_eq!(ring.get(b"{user1}.a"), ring.get(b"{user1}.b"));
+
+        // Most of the moved keys go to the new owner.
+        let owners = [
+            ("a", 3277),
+            ("b", 3277),
+            ("c", 3277),
+            ("d", 3277),
+            ("e", 3276),
+        ];
+        let ring = HashRing::new(&owners);
+        let mut moved = 0;
+        let mut moved_to_new = 0;
+        for (key, old_index) in keys.iter().zip(old.iter()) {
+            let index = ring.get(key.as_bytes()).unwrap();
+            if index != *old_index {
+                moved += 1;
+                if index == 4 {
+                    moved_to_new += 1;
+                }
+            }
+        }
+        // It's about 80% for the modulo hashing.
+        assert!(moved < 4000, "{}", moved);
+        assert!(moved_to_new * 2 > moved, "{} {}", moved_to_new, moved);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::common::utils::generate_slot;
+
+// SCAN iterates the local backend nodes, sorted by address, one by one behind a single cursor.
+// The cursor is `backend_cursor << 22 | node_hash << 10 | node_index`.
+// The node hash is checked to find the node again after the nodes are changed by the metadata.
+const NODE_INDEX_BITS: u32 = 10;
+const NODE_HASH_BITS: u32 = 12;
+const BACKEND_CURSOR_SHIFT: u32 = NODE_INDEX_BITS + NODE_HASH_BITS;
+pub const MAX_SCAN_NODES: usize = 1 << NODE_INDEX_BITS;
+
+#[derive(Debug, PartialEq)]
+pub struct ScanCursor {
+    pub node_index: usize,
+    pub backend_cursor: u64,
+}
+
+fn node_hash(node: &str) -> u64 {
+    generate_slot(node.as_bytes()) as u64 & ((1 << NODE_HASH_BITS) - 1)
+}
+
+// Returns None if the backend cursor is too large.
+fn encode_cursor(nodes: &[String], node_index: usize, backend_cursor: u64) -> Option<u64> {
+    let node = nodes.get(node_index)?;
+    if backend_cursor >> (64 - BACKEND_CURSOR_SHIFT) != 0 {
+        return None;
+    }
+    Some(
+        (backend_cursor << BACKEND_CURSOR_SHIFT)
+            | (node_hash(node) << NODE_INDEX_BITS)
+            | node_index as u64,
+    )
+}
+
+// Returns None if all the nodes have been scanned.
+// When the node of the cursor is removed, the node at its index is scanned again from the start
+// so that the scan still finishes, though some keys could be returned more than once.
+pub fn decode_cursor(nodes: &[String], cursor: u64) -> Option<ScanCursor> {
+    if cursor == 0 {
+        return Some(ScanCursor {
+            node_index: 0,
+            backend_cursor: 0,
+        });
+    }
+    let node_index = (cursor & ((1 << NODE_INDEX_BITS) - 1)) as usize;
+    let hash = (cursor >> NODE_INDEX_BITS) & ((1 << NODE_HASH_BITS) - 1);
+    let backend_cursor = cursor >> BACKEND_CURSOR_SHIFT;
+    if nodes.get(node_index).map(|node| node_hash(node)) == Some(hash) {
+        return Some(ScanCursor {
+            node_index,
+            backend_cursor,
+        });
+    }
+    if let Some(node_index) = nodes.iter().position(|node| node_hash(node) == hash) {
+        return Some(ScanCursor {
+            node_index,
+            backend_cursor,
+        });
+    }
+    if node_index < nodes.len() {
+        return Some(ScanCursor {
+            node_index,
+            backend_cursor: 0,
+        });
+    }
+    None
+}
+
+// Moves to the next node after the backend cursor gets back to zero.
+pub fn next_cursor(nodes: &[String], node_index: usize, backend_cursor: u64) -> Option<u64> {
+    if backend_cursor != 0 {
+        encode_cursor(nodes, node_index, backend_cursor)
+    } else if node_index + 1 < nodes.len() {
+        encode_cursor(nodes, node_index + 1, 0)
+    } else {
+        Some(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_nodes(ports: Vec<u16>) -> Vec<String> {
+        ports
+            .into_iter()
+            .map(|port| format!("127.0.0.1:{}", port))
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_cursor() {
+        let nodes = gen_nodes(vec![6000, 6001, 6002]);
+        let start = decode_cursor(&nodes, 0).unwrap();
+        assert_eq!(start.node_index, 0);
+        assert_eq!(start.backend_cursor, 0);
+
+        let cursor = next_cursor(&nodes, 0, 233).unwrap();
+        assert_ne!(cursor, 0);
+        let decoded = decode_cursor(&nodes, cursor).unwrap();
+        assert_eq!(decoded.node_index, 0);
+        assert_eq!(decoded.backend_cursor, 233);
+
+        let cursor = next_cursor(&nodes, 1, 0).unwrap();
+        assert_ne!(cursor, 0);
+        let decoded = decode_cursor(&nodes, cursor).unwrap();
+        assert_eq!(decoded.node_index, 2);
+        assert_eq!(decoded.backend_cursor, 0);
+
+        assert_eq!(next_cursor(&nodes, 2, 0), Some(0));
+        assert_eq!(next_cursor(&nodes, 0, u64::MAX), None);
+    }
+
+    #[test]
+    fn test_scan_cursor_after_nodes_changed() {
+        let nodes = gen_nodes(vec![6000, 6001, 6002]);
+        let cursor = next_cursor(&nodes, 1, 233).unwrap();
+
+        // A node before it is removed.
+        let new_nodes = gen_nodes(vec![6001, 6002]);
+        let decoded = decode_cursor(&new_nodes, cursor).unwrap();
+        assert_eq!(decoded.node_index, 0);
+        assert_eq!(decoded.backend_cursor, 233);
+
+        // The node itself is removed.
+        let new_nodes = gen_nodes(vec![6000, 6002]);
+        let decoded = decode_cursor(&new_nodes, cursor).unwrap();
+        assert_eq!(decoded.node_index, 1);
+        assert_eq!(decoded.backend_cursor, 0);
+
+        let cursor = next_cursor(&nodes, 2, 233).unwrap();
+        assert!(decode_cursor(&gen_nodes(vec![6000]), cursor).is_none());
+    }
+}
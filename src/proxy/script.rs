@@ -0,0 +1,196 @@
+use super::command::Command;
+use crate::common::cluster::ClusterName;
+use crate::protocol::{Array, BinSafeStr, BulkStr, Resp, RespVec};
+use btoi::btou;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+// The scripts loaded by `SCRIPT LOAD` are kept so that EVALSHA could be retried
+// on the backends which don't have them, e.g. the new masters after failover.
+pub const MAX_CACHED_SCRIPTS: usize = 1024;
+
+#[derive(Default)]
+pub struct ScriptCache {
+    scripts: RwLock<HashMap<ClusterName, HashMap<String, BinSafeStr>>>,
+}
+
+impl ScriptCache {
+    // Returns false when the cache of the cluster is full.
+    pub fn add(&self, cluster_name: &ClusterName, sha: &[u8], script: BinSafeStr) -> bool {
+        let mut scripts = self.scripts.write().expect("ScriptCache::add");
+        let cluster_scripts = scripts.entry(cluster_name.clone()).or_default();
+        let sha = normalize_sha(sha);
+        if cluster_scripts.len() >= MAX_CACHED_SCRIPTS && !cluster_scripts.contains_key(&sha) {
+            return false;
+        }
+        cluster_scripts.insert(sha, script);
+        true
+    }
+
+    pub fn get(&self, cluster_name: &ClusterName, sha: &[u8]) -> Option<BinSafeStr> {
+        self.scripts
+            .read()
+            .expect("ScriptCache::get")
+            .get(cluster_name)
+            .and_then(|cluster_scripts| cluster_scripts.get(&normalize_sha(sha)))
+            .cloned()
+    }
+
+    pub fn flush(&self, cluster_name: &ClusterName) {
+        self.scripts
+            .write()
+            .expect("ScriptCache::flush")
+            .remove(cluster_name);
+    }
+}
+
+fn normalize_sha(sha: &[u8]) -> String {
+    String::from_utf8_lossy(sha).to_ascii_lowercase()
+}
+
+pub fn is_noscript_error(resp: &RespVec) -> bool {
+    match resp {
+        Resp::Error(err) => err.starts_with(b"NOSCRIPT"),
+        _ => false,
+    }
+}
+
+// EVAL script numkeys key [key ...] arg [arg ...]
+// numkeys needs to be checked before collecting the keys.
+// The other malformed arguments are left to the backends to reply.
+pub fn eval_key_num_exceeds_args(cmd: &Command) -> bool {
+    let len = match cmd.get_command_len() {
+        Some(len) => len,
+        None => return false,
+    };
+    match cmd
+        .get_command_element(2)
+        .and_then(|n| btou::<usize>(n).ok())
+    {
+        Some(key_num) => key_num > len.saturating_sub(3),
+        None => false,
+    }
+}
+
+pub fn get_loaded_sha(resp: &RespVec) -> Option<&[u8]> {
+    match resp {
+        Resp::Bulk(BulkStr::Str(sha)) => Some(sha),
+        _ => None,
+    }
+}
+
+// A script exists only when it exists in all the backends.
+pub fn merge_script_exists(replies: Vec<RespVec>) -> Option<RespVec> {
+    let mut merged: Option<Vec<RespVec>> = None;
+    for reply in replies.into_iter() {
+        let flags = match reply {
+            Resp::Arr(Array::Arr(flags)) => flags,
+            _ => return None,
+        };
+        merged = match merged {
+            None => Some(flags),
+            Some(merged) => {
+                if merged.len() != flags.len() {
+                    return None;
+                }
+                let flags = merged
+                    .into_iter()
+                    .zip(flags)
+                    .map(|(a, b)| match (a, b) {
+                        (Resp::Integer(a), Resp::Integer(b)) if a == b"1" && b == b"1" => {
+                            Resp::Integer(b"1".to_vec())
+                        }
+                        _ => Resp::Integer(b"0".to_vec()),
+                    })
+                    .collect();
+                Some(flags)
+            }
+        };
+    }
+    merged.map(|flags| Resp::Arr(Array::Arr(flags)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::RespPacket;
+    use std::convert::TryFrom;
+
+    fn gen_cmd(cmd: Vec<&str>) -> Command {
+        let arr = cmd
+            .into_iter()
+            .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+            .collect();
+        let packet = RespPacket::Data(Resp::Arr(Array::Arr(arr)));
+        Command::new(Box::new(packet))
+    }
+
+    #[test]
+    fn test_eval_key_num_exceeds_args() {
+        assert!(!eval_key_num_exceeds_args(&gen_cmd(vec!["EVAL", "", "0"])));
+        assert!(!eval_key_num_exceeds_args(&gen_cmd(vec![
+            "EVAL", "", "1", "k", "arg"
+        ])));
+        assert!(!eval_key_num_exceeds_args(&gen_cmd(vec![
+            "EVALSHA", "sha", "2", "a", "b"
+        ])));
+        assert!(eval_key_num_exceeds_args(&gen_cmd(vec![
+            "EVAL",
+            "",
+            "9999999999999"
+        ])));
+        assert!(eval_key_num_exceeds_args(&gen_cmd(vec![
+            "EVAL",
+            "",
+            "18446744073709551615",
+            "k"
+        ])));
+        assert!(eval_key_num_exceeds_args(&gen_cmd(vec![
+            "EVAL", "", "2", "k"
+        ])));
+        assert!(!eval_key_num_exceeds_args(&gen_cmd(vec!["EVAL", "", "-1"])));
+        assert!(!eval_key_num_exceeds_args(&gen_cmd(vec!["EVAL", ""])));
+    }
+
+    #[test]
+    fn test_script_cache() {
+        let cache = ScriptCache::default();
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        let other_cluster = ClusterName::try_from("othercluster").unwrap();
+        assert!(cache.add(&cluster_name, b"ABC", b"return 1".to_vec()));
+        assert_eq!(cache.get(&cluster_name, b"abc"), Some(b"return 1".to_vec()));
+        assert_eq!(cache.get(&other_cluster, b"abc"), None);
+
+        for i in 1..MAX_CACHED_SCRIPTS {
+            assert!(cache.add(&cluster_name, i.to_string().as_bytes(), vec![]));
+        }
+        assert!(!cache.add(&cluster_name, b"full", vec![]));
+        assert!(cache.add(&cluster_name, b"abc", b"return 2".to_vec()));
+
+        cache.flush(&cluster_name);
+        assert_eq!(cache.get(&cluster_name, b"abc"), None);
+    }
+
+    #[test]
+    fn test_merge_script_exists() {
+        let flags = |flags: Vec<&str>| {
+            Resp::Arr(Array::Arr(
+                flags
+                    .into_iter()
+                    .map(|f| Resp::Integer(f.as_bytes().to_vec()))
+                    .collect(),
+            ))
+        };
+        let merged =
+            merge_script_exists(vec![flags(vec!["1", "1", "0"]), flags(vec!["1", "0", "1"])]);
+        assert_eq!(merged, Some(flags(vec!["1", "0", "0"])));
+        assert_eq!(
+            merge_script_exists(vec![flags(vec!["1"]), flags(vec!["1", "0"])]),
+            None
+        );
+        assert_eq!(merge_script_exists(vec![]), None);
+        assert!(is_noscript_error(&Resp::Error(
+            b"NOSCRIPT No matching script. Please use EVAL.".to_vec()
+        )));
+    }
+}
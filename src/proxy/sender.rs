@@ -1,10 +1,11 @@
 use super::backend::{
-    BackendError, BackendNode, CmdTask, CmdTaskResultHandler, CmdTaskResultHandlerFactory,
-    ConnFactory, ReqTask,
+    BackendError, BackendNode, BackendSendError, CmdTask, CmdTaskResultHandler,
+    CmdTaskResultHandlerFactory, ConnFactory, ReqTask,
 };
 use super::service::ServerProxyConfig;
-use crate::common::response::ERR_BACKEND_CONNECTION;
+use crate::common::response::{ERR_BACKEND_CONNECTION, ERR_BACKEND_OVERLOADED};
 use crate::common::track::TrackedFutureRegistry;
+use crate::common::utils::ThreadSafe;
 use crate::protocol::Resp;
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
@@ -32,13 +33,20 @@ impl<F: CmdTaskResultHandlerFactory> CmdTaskSender for RecoverableBackendNode<F>
     type Task = <<F as CmdTaskResultHandlerFactory>::Handler as CmdTaskResultHandler>::Task;
 
     fn send(&self, cmd_task: Self::Task) -> Result<(), BackendError> {
-        self.node.send(cmd_task).map_err(|e| {
-            let cmd_task = e.into_inner();
-            cmd_task.set_resp_result(Ok(Resp::Error(
-                format!("{}: {}", ERR_BACKEND_CONNECTION, self.address).into_bytes(),
-            )));
-            error!("backend node is closed");
-            BackendError::Canceled
+        self.node.send(cmd_task).map_err(|e| match e {
+            BackendSendError::Closed(cmd_task) => {
+                cmd_task.set_resp_result(Ok(Resp::Error(
+                    format!("{}: {}", ERR_BACKEND_CONNECTION, self.address).into_bytes(),
+                )));
+                error!("backend node is closed");
+                BackendError::Canceled
+            }
+            BackendSendError::Overloaded(cmd_task) => {
+                cmd_task.set_resp_result(Ok(Resp::Error(
+                    format!("{}: {}", ERR_BACKEND_OVERLOADED, self.address).into_bytes(),
+                )));
+                BackendError::Overloaded
+            }
         })
     }
 }
@@ -254,6 +262,76 @@ impl<F: CmdTaskSenderFactory> CmdTaskSenderFactory for CachedSenderFactory<F> {
     }
 }
 
+impl<S: CmdTaskSender + ?Sized> CmdTaskSender for Box<S> {
+    type Task = S::Task;
+
+    fn send(&self, cmd_task: Self::Task) -> Result<(), BackendError> {
+        (**self).send(cmd_task)
+    }
+}
+
+// The dynamic dispatch version of the senders so that the users could plug
+// their own senders without knowing the nested generic types.
+pub type DynCmdTaskSender<T> = Box<dyn CmdTaskSender<Task = T> + Send + Sync + 'static>;
+
+// Wraps the backend senders, e.g. to shadow the traffic to another system.
+pub trait CmdTaskSenderWrapper<T>: ThreadSafe {
+    fn wrap(&self, address: &str, sender: DynCmdTaskSender<T>) -> DynCmdTaskSender<T>;
+}
+
+// Keeps the static dispatch when there're no wrappers.
+pub enum WrappedSender<S: CmdTaskSender> {
+    Inner(S),
+    Wrapped(DynCmdTaskSender<S::Task>),
+}
+
+impl<S: CmdTaskSender> CmdTaskSender for WrappedSender<S> {
+    type Task = S::Task;
+
+    fn send(&self, cmd_task: Self::Task) -> Result<(), BackendError> {
+        match self {
+            Self::Inner(sender) => sender.send(cmd_task),
+            Self::Wrapped(sender) => sender.send(cmd_task),
+        }
+    }
+}
+
+pub struct WrappedSenderFactory<'a, F: CmdTaskSenderFactory> {
+    inner_factory: &'a F,
+    wrappers: &'a [Box<dyn CmdTaskSenderWrapper<<F::Sender as CmdTaskSender>::Task>>],
+}
+
+impl<'a, F: CmdTaskSenderFactory> WrappedSenderFactory<'a, F> {
+    pub fn new(
+        inner_factory: &'a F,
+        wrappers: &'a [Box<dyn CmdTaskSenderWrapper<<F::Sender as CmdTaskSender>::Task>>],
+    ) -> Self {
+        Self {
+            inner_factory,
+            wrappers,
+        }
+    }
+}
+
+impl<'a, F: CmdTaskSenderFactory> CmdTaskSenderFactory for WrappedSenderFactory<'a, F>
+where
+    F::Sender: ThreadSafe,
+{
+    type Sender = WrappedSender<F::Sender>;
+
+    fn create(&self, address: String) -> Self::Sender {
+        let sender = self.inner_factory.create(address.clone());
+        if self.wrappers.is_empty() {
+            return WrappedSender::Inner(sender);
+        }
+        let mut sender: DynCmdTaskSender<_> = Box::new(sender);
+        for wrapper in self.wrappers.iter() {
+            sender = wrapper.wrap(address.as_str(), sender);
+        }
+        WrappedSender::Wrapped(sender)
+    }
+}
+
 pub type BackendSenderFactory<F, CF> =
     CachedSenderFactory<RRSenderGroupFactory<RecoverableBackendNodeFactory<F, CF>>>;
 
@@ -301,3 +379,107 @@ where
         )),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::ClusterName;
+    use crate::protocol::{Array, BulkStr, RespPacket};
+    use crate::proxy::command::{new_command_pair, Command};
+    use crate::proxy::session::CmdCtx;
+    use std::convert::TryFrom;
+
+    struct CountSender {
+        count: Arc<AtomicUsize>,
+    }
+
+    impl CmdTaskSender for CountSender {
+        type Task = CmdCtx;
+
+        fn send(&self, _cmd_task: Self::Task) -> Result<(), BackendError> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct CountSenderFactory {
+        count: Arc<AtomicUsize>,
+    }
+
+    impl CmdTaskSenderFactory for CountSenderFactory {
+        type Sender = CountSender;
+
+        fn create(&self, _address: String) -> Self::Sender {
+            CountSender {
+                count: self.count.clone(),
+            }
+        }
+    }
+
+    struct ShadowWrapper {
+        count: Arc<AtomicUsize>,
+    }
+
+    impl CmdTaskSenderWrapper<CmdCtx> for ShadowWrapper {
+        fn wrap(
+            &self,
+            _address: &str,
+            sender: DynCmdTaskSender<CmdCtx>,
+        ) -> DynCmdTaskSender<CmdCtx> {
+            Box::new(ShadowSender {
+                count: self.count.clone(),
+                sender,
+            })
+        }
+    }
+
+    struct ShadowSender {
+        count: Arc<AtomicUsize>,
+        sender: DynCmdTaskSender<CmdCtx>,
+    }
+
+    impl CmdTaskSender for ShadowSender {
+        type Task = CmdCtx;
+
+        fn send(&self, cmd_task: Self::Task) -> Result<(), BackendError> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            self.sender.send(cmd_task)
+        }
+    }
+
+    fn gen_test_cmd_ctx() -> CmdCtx {
+        let resp = Resp::Arr(Array::Arr(vec![
+            Resp::Bulk(BulkStr::Str(b"GET".to_vec())),
+            Resp::Bulk(BulkStr::Str(b"key".to_vec())),
+        ]));
+        let cluster = ClusterName::try_from("mycluster").unwrap();
+        let cmd = Command::new(Box::new(RespPacket::from_resp_vec(resp)));
+        let (reply_sender, _reply_receiver) = new_command_pair(&cmd);
+        CmdCtx::new(cluster, cmd, reply_sender, 0, true)
+    }
+
+    #[test]
+    fn test_wrapped_sender_factory() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let shadow_count = Arc::new(AtomicUsize::new(0));
+        let inner_factory = CountSenderFactory {
+            count: count.clone(),
+        };
+
+        let factory = WrappedSenderFactory::new(&inner_factory, &[]);
+        let sender = factory.create("127.0.0.1:6379".to_string());
+        assert!(matches!(sender, WrappedSender::Inner(_)));
+        sender.send(gen_test_cmd_ctx()).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let wrappers: Vec<Box<dyn CmdTaskSenderWrapper<CmdCtx>>> = vec![Box::new(ShadowWrapper {
+            count: shadow_count.clone(),
+        })];
+        let factory = WrappedSenderFactory::new(&inner_factory, &wrappers);
+        let sender = factory.create("127.0.0.1:6379".to_string());
+        assert!(matches!(sender, WrappedSender::Wrapped(_)));
+        sender.send(gen_test_cmd_ctx()).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(shadow_count.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,213 @@
+use super::backend::{ConnFactory, ConnSink, ConnStream, DefaultConnFactory};
+use crate::common::utils::lookup_first_address;
+use crate::protocol::{Array, BulkStr, Resp, RespPacket};
+use futures::{future, SinkExt, StreamExt};
+use futures_timer::Delay;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str;
+use std::time::Duration;
+use tokio::time;
+
+// The node address `sentinel://<master-name>` means that
+// the real address should be discovered from the sentinels.
+pub const SENTINEL_ADDRESS_PREFIX: &str = "sentinel://";
+const SWITCH_MASTER_CHANNEL: &str = "+switch-master";
+const SENTINEL_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub fn get_sentinel_master_name(address: &str) -> Option<&str> {
+    address
+        .strip_prefix(SENTINEL_ADDRESS_PREFIX)
+        .filter(|master_name| !master_name.is_empty())
+}
+
+pub async fn resolve_master_address(
+    sentinels: &[String],
+    master_name: &str,
+) -> Result<SocketAddr, SentinelError> {
+    if sentinels.is_empty() {
+        return Err(SentinelError::NoSentinel);
+    }
+
+    let futs = sentinels
+        .iter()
+        .map(|sentinel| query_master_address(sentinel, master_name));
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for res in future::join_all(futs).await.into_iter() {
+        match res {
+            Ok(address) => *counts.entry(address).or_default() += 1,
+            Err(err) => warn!("failed to query sentinel for {}: {:?}", master_name, err),
+        }
+    }
+
+    let quorum = sentinels.len() / 2 + 1;
+    let address = counts
+        .into_iter()
+        .find(|(_, count)| *count >= quorum)
+        .map(|(address, _)| address)
+        .ok_or(SentinelError::NoQuorum)?;
+    lookup_first_address(&address)
+        .await
+        .ok_or(SentinelError::InvalidAddress)
+}
+
+// Returns when the master is switched according to any of the sentinels.
+pub async fn wait_switch_master(sentinels: Vec<String>, master_name: String) {
+    if sentinels.is_empty() {
+        return future::pending().await;
+    }
+    let futs = sentinels.into_iter().map(|sentinel| {
+        let master_name = master_name.clone();
+        Box::pin(async move {
+            loop {
+                match subscribe_switch_master(&sentinel, &master_name).await {
+                    Ok(()) => return,
+                    Err(err) => {
+                        warn!("failed to subscribe sentinel {}: {:?}", sentinel, err);
+                        Delay::new(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        })
+    });
+    future::select_all(futs).await;
+}
+
+async fn connect_sentinel(
+    sentinel: &str,
+) -> Result<(ConnSink<RespPacket>, ConnStream<RespPacket>), SentinelError> {
+    let address = lookup_first_address(sentinel)
+        .await
+        .ok_or(SentinelError::InvalidAddress)?;
+    let conn_fut = DefaultConnFactory::<RespPacket>::default().create_conn(address);
+    match time::timeout(SENTINEL_TIMEOUT, conn_fut).await {
+        Err(_) => Err(SentinelError::Timeout),
+        Ok(Err(_)) => Err(SentinelError::Connection),
+        Ok(Ok(conn)) => Ok(conn),
+    }
+}
+
+fn gen_cmd(cmd: &[&str]) -> RespPacket {
+    let elements = cmd
+        .iter()
+        .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+        .collect();
+    RespPacket::from_resp_vec(Resp::Arr(Array::Arr(elements)))
+}
+
+async fn query_master_address(sentinel: &str, master_name: &str) -> Result<String, SentinelError> {
+    let (mut writer, mut reader) = connect_sentinel(sentinel).await?;
+    let cmd = gen_cmd(&["SENTINEL", "get-master-addr-by-name", master_name]);
+    writer
+        .send(cmd)
+        .await
+        .map_err(|_| SentinelError::Connection)?;
+    let reply = match time::timeout(SENTINEL_TIMEOUT, reader.next()).await {
+        Err(_) => return Err(SentinelError::Timeout),
+        Ok(None) | Ok(Some(Err(_))) => return Err(SentinelError::Connection),
+        Ok(Some(Ok(reply))) => reply,
+    };
+    parse_master_address(&reply).ok_or(SentinelError::InvalidReply)
+}
+
+fn parse_master_address(reply: &RespPacket) -> Option<String> {
+    let host = str::from_utf8(reply.get_array_element(0)?).ok()?;
+    let port = str::from_utf8(reply.get_array_element(1)?).ok()?;
+    Some(format!("{}:{}", host, port))
+}
+
+async fn subscribe_switch_master(sentinel: &str, master_name: &str) -> Result<(), SentinelError> {
+    let (mut writer, mut reader) = connect_sentinel(sentinel).await?;
+    writer
+        .send(gen_cmd(&["SUBSCRIBE", SWITCH_MASTER_CHANNEL]))
+        .await
+        .map_err(|_| SentinelError::Connection)?;
+    while let Some(msg) = reader.next().await {
+        let msg = msg.map_err(|_| SentinelError::Connection)?;
+        if is_switch_master_msg(&msg, master_name) {
+            info!("sentinel {} switched master {}", sentinel, master_name);
+            return Ok(());
+        }
+    }
+    Err(SentinelError::Connection)
+}
+
+// message +switch-master "<master-name> <old-ip> <old-port> <new-ip> <new-port>"
+fn is_switch_master_msg(msg: &RespPacket, master_name: &str) -> bool {
+    if msg.get_array_element(0) != Some(b"message")
+        || msg.get_array_element(1) != Some(SWITCH_MASTER_CHANNEL.as_bytes())
+    {
+        return false;
+    }
+    msg.get_array_element(2)
+        .and_then(|payload| str::from_utf8(payload).ok())
+        .and_then(|payload| payload.split_whitespace().next())
+        == Some(master_name)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SentinelError {
+    NoSentinel,
+    InvalidAddress,
+    Connection,
+    Timeout,
+    InvalidReply,
+    NoQuorum,
+}
+
+impl fmt::Display for SentinelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for SentinelError {
+    fn description(&self) -> &str {
+        "sentinel error"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_sentinel_master_name() {
+        assert_eq!(
+            get_sentinel_master_name("sentinel://mymaster"),
+            Some("mymaster")
+        );
+        assert_eq!(get_sentinel_master_name("sentinel://"), None);
+        assert_eq!(get_sentinel_master_name("127.0.0.1:6379"), None);
+    }
+
+    #[test]
+    fn test_parse_master_address() {
+        let reply = gen_cmd(&["127.0.0.1", "6379"]);
+        assert_eq!(
+            parse_master_address(&reply),
+            Some("127.0.0.1:6379".to_string())
+        );
+        let nil = RespPacket::from_resp_vec(Resp::Bulk(BulkStr::Nil));
+        assert_eq!(parse_master_address(&nil), None);
+    }
+
+    #[test]
+    fn test_switch_master_msg() {
+        let msg = gen_cmd(&[
+            "message",
+            "+switch-master",
+            "mymaster 127.0.0.1 6379 127.0.0.1 6380",
+        ]);
+        assert!(is_switch_master_msg(&msg, "mymaster"));
+        assert!(!is_switch_master_msg(&msg, "othermaster"));
+        let subscribe = gen_cmd(&["subscribe", "+switch-master"]);
+        assert!(!is_switch_master_msg(&subscribe, "mymaster"));
+    }
+}
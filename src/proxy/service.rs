@@ -1,16 +1,29 @@
+use super::client_list::ClientRegistry;
+use super::command::CmdTimeoutClass;
+use super::listener::{bind_listener, AcceptLimiter};
+use super::memory::MemoryTracker;
 use super::session::CmdCtxHandler;
-use super::session::{handle_session, Session};
+use super::session::{handle_session, wait_shutdown, Session, SessionControl, SessionError};
 use super::slowlog::SlowRequestLogger;
+use super::stats::ProxyStats;
+use super::tls::TlsAcceptorHolder;
 use crate::common::config::ConfigError;
 use crate::common::track::TrackedFutureRegistry;
-use crate::common::utils::{resolve_first_address, ThreadSafe};
-use futures::{FutureExt, StreamExt};
+use crate::common::utils::{get_panic_message, resolve_first_address, ThreadSafe};
+use futures::{future, FutureExt, StreamExt};
+use futures_timer::Delay;
 use std::error::Error;
 use std::num::NonZeroUsize;
+use std::panic::AssertUnwindSafe;
+use std::process;
 use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use string_error::into_err;
-use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+// Wait for the sessions to send the final errors.
+const SHUTDOWN_GRACE_TIME: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub struct ServerProxyConfig {
@@ -21,6 +34,8 @@ pub struct ServerProxyConfig {
     pub slowlog_log_slower_than: AtomicI64,
     pub slowlog_sample_rate: AtomicU64,
     pub thread_number: NonZeroUsize,
+    // Run `thread_number` single-threaded runtimes which own the sessions they accept.
+    pub thread_per_core: bool,
     pub session_channel_size: usize,
     pub backend_channel_size: usize,
     pub backend_conn_num: NonZeroUsize,
@@ -32,6 +47,43 @@ pub struct ServerProxyConfig {
     pub session_batch_buf: NonZeroUsize,
     pub active_redirection: bool,
     pub max_redirections: Option<NonZeroUsize>,
+    pub backend_redirection: bool,
+    pub sentinel_addresses: Vec<String>,
+    pub request_coalescing: bool,
+    // Max in-flight commands of each backend connection. Zero means no limitation.
+    pub backend_concurrency_limit: usize,
+    // Max in-flight commands pipelined on each backend connection.
+    // Zero means waiting for the replies of each batch before sending the next one.
+    pub backend_inflight_window: usize,
+    // Interleave the commands of different sessions sent to the same backend.
+    pub backend_session_fairness: bool,
+    // In milliseconds. The backend connections idle for this long are checked by PING before reuse.
+    // Zero means no check.
+    pub backend_idle_check_time: u64,
+    pub tls: TlsAcceptorHolder,
+    // In milliseconds. Zero means no timeout.
+    pub command_timeout_fast: AtomicU64,
+    pub command_timeout_slow: AtomicU64,
+    pub command_timeout_blocking: AtomicU64,
+    // In bytes. The largest sessions will be closed when exceeded. Zero means no limitation.
+    pub max_memory: AtomicU64,
+    pub accept_backlog: usize,
+    // Zero means no limitation.
+    pub max_accepts_per_tick: usize,
+    // In milliseconds. Zero means the storm mode is disabled.
+    pub accept_storm_pause_time: u64,
+    // Close the session on panics. Otherwise the proxy aborts so that it could be restarted.
+    pub session_panic_isolation: bool,
+    // Close the session and log the request when a reply is not in the order of the requests.
+    // Used to find the bugs mixing up the replies.
+    pub reply_order_check: bool,
+    // In milliseconds. The reads of the keys written by the same session within this window
+    // are sent to the master. Zero means disabled.
+    pub read_after_write_window: u64,
+    // In milliseconds. The max time to wait for the replicas in `write_ack_policy` of `replica`.
+    pub replica_ack_timeout: u64,
+    // The commands buffered for each MONITOR session before it's disconnected.
+    pub monitor_buffer_size: usize,
 }
 
 impl ServerProxyConfig {
@@ -51,6 +103,19 @@ impl ServerProxyConfig {
         self.slowlog_sample_rate
             .store(slowlog_sample_rate, Ordering::Relaxed)
     }
+
+    pub fn get_command_timeout(&self, timeout_class: CmdTimeoutClass) -> u64 {
+        let timeout = match timeout_class {
+            CmdTimeoutClass::Fast => &self.command_timeout_fast,
+            CmdTimeoutClass::Slow => &self.command_timeout_slow,
+            CmdTimeoutClass::Blocking => &self.command_timeout_blocking,
+        };
+        timeout.load(Ordering::Relaxed)
+    }
+
+    pub fn get_max_memory(&self) -> u64 {
+        self.max_memory.load(Ordering::Relaxed)
+    }
 }
 
 impl ServerProxyConfig {
@@ -61,6 +126,7 @@ impl ServerProxyConfig {
             "auto_select_cluster" => Ok(self.auto_select_cluster.to_string()),
             "slowlog_len" => Ok(self.slowlog_len.to_string()),
             "thread_number" => Ok(self.thread_number.to_string()),
+            "thread_per_core" => Ok(self.thread_per_core.to_string()),
             "session_channel_size" => Ok(self.session_channel_size.to_string()),
             "backend_channel_size" => Ok(self.backend_channel_size.to_string()),
             "backend_conn_num" => Ok(self.backend_conn_num.to_string()),
@@ -73,10 +139,40 @@ impl ServerProxyConfig {
             "session_batch_max_time" => Ok(self.session_batch_max_time.to_string()),
             "session_batch_buf" => Ok(self.session_batch_buf.to_string()),
             "active_redirection" => Ok(self.active_redirection.to_string()),
+            "backend_redirection" => Ok(self.backend_redirection.to_string()),
+            "sentinel_addresses" => Ok(self.sentinel_addresses.join(",")),
+            "request_coalescing" => Ok(self.request_coalescing.to_string()),
+            "backend_concurrency_limit" => Ok(self.backend_concurrency_limit.to_string()),
+            "backend_inflight_window" => Ok(self.backend_inflight_window.to_string()),
+            "backend_session_fairness" => Ok(self.backend_session_fairness.to_string()),
+            "backend_idle_check_time" => Ok(self.backend_idle_check_time.to_string()),
             "max_redirections" => Ok(self
                 .max_redirections
                 .map(|n| n.get().to_string())
                 .unwrap_or_else(|| "none".to_string())),
+            "tls_pkcs12_file" => Ok(self
+                .tls
+                .get_identity_file()
+                .map(|f| f.pkcs12_file)
+                .unwrap_or_else(|| "none".to_string())),
+            "command_timeout_fast" => {
+                Ok(self.get_command_timeout(CmdTimeoutClass::Fast).to_string())
+            }
+            "command_timeout_slow" => {
+                Ok(self.get_command_timeout(CmdTimeoutClass::Slow).to_string())
+            }
+            "command_timeout_blocking" => Ok(self
+                .get_command_timeout(CmdTimeoutClass::Blocking)
+                .to_string()),
+            "max_memory" => Ok(self.get_max_memory().to_string()),
+            "accept_backlog" => Ok(self.accept_backlog.to_string()),
+            "max_accepts_per_tick" => Ok(self.max_accepts_per_tick.to_string()),
+            "accept_storm_pause_time" => Ok(self.accept_storm_pause_time.to_string()),
+            "session_panic_isolation" => Ok(self.session_panic_isolation.to_string()),
+            "reply_order_check" => Ok(self.reply_order_check.to_string()),
+            "read_after_write_window" => Ok(self.read_after_write_window.to_string()),
+            "replica_ack_timeout" => Ok(self.replica_ack_timeout.to_string()),
+            "monitor_buffer_size" => Ok(self.monitor_buffer_size.to_string()),
             _ => Err(ConfigError::FieldNotFound),
         }
     }
@@ -88,6 +184,7 @@ impl ServerProxyConfig {
             "auto_select_cluster" => Err(ConfigError::ReadonlyField),
             "slowlog_len" => Err(ConfigError::ReadonlyField),
             "thread_number" => Err(ConfigError::ReadonlyField),
+            "thread_per_core" => Err(ConfigError::ReadonlyField),
             "session_channel_size" => Err(ConfigError::ReadonlyField),
             "backend_channel_size" => Err(ConfigError::ReadonlyField),
             "backend_conn_num" => Err(ConfigError::ReadonlyField),
@@ -112,7 +209,42 @@ impl ServerProxyConfig {
             "session_batch_max_time" => Err(ConfigError::ReadonlyField),
             "session_batch_buf" => Err(ConfigError::ReadonlyField),
             "active_redirection" => Err(ConfigError::ReadonlyField),
+            "backend_redirection" => Err(ConfigError::ReadonlyField),
+            "sentinel_addresses" => Err(ConfigError::ReadonlyField),
+            "request_coalescing" => Err(ConfigError::ReadonlyField),
+            "backend_concurrency_limit" => Err(ConfigError::ReadonlyField),
+            "backend_inflight_window" => Err(ConfigError::ReadonlyField),
+            "backend_session_fairness" => Err(ConfigError::ReadonlyField),
+            "backend_idle_check_time" => Err(ConfigError::ReadonlyField),
             "max_redirections" => Err(ConfigError::ReadonlyField),
+            "tls_pkcs12_file" => Err(ConfigError::ReadonlyField),
+            "accept_backlog" => Err(ConfigError::ReadonlyField),
+            "max_accepts_per_tick" => Err(ConfigError::ReadonlyField),
+            "accept_storm_pause_time" => Err(ConfigError::ReadonlyField),
+            "session_panic_isolation" => Err(ConfigError::ReadonlyField),
+            "reply_order_check" => Err(ConfigError::ReadonlyField),
+            "read_after_write_window" => Err(ConfigError::ReadonlyField),
+            "replica_ack_timeout" => Err(ConfigError::ReadonlyField),
+            "monitor_buffer_size" => Err(ConfigError::ReadonlyField),
+            "command_timeout_fast" | "command_timeout_slow" | "command_timeout_blocking" => {
+                let int_value = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                let timeout = match field.to_lowercase().as_ref() {
+                    "command_timeout_fast" => &self.command_timeout_fast,
+                    "command_timeout_slow" => &self.command_timeout_slow,
+                    _ => &self.command_timeout_blocking,
+                };
+                timeout.store(int_value, Ordering::Relaxed);
+                Ok(())
+            }
+            "max_memory" => {
+                let int_value = value
+                    .parse::<u64>()
+                    .map_err(|_| ConfigError::InvalidValue)?;
+                self.max_memory.store(int_value, Ordering::Relaxed);
+                Ok(())
+            }
             _ => Err(ConfigError::FieldNotFound),
         }
     }
@@ -124,6 +256,13 @@ pub struct ServerProxyService<H: CmdCtxHandler + ThreadSafe + Clone> {
     cmd_ctx_handler: H,
     slow_request_logger: Arc<SlowRequestLogger>,
     future_registry: Arc<TrackedFutureRegistry>,
+    memory_tracker: Arc<MemoryTracker>,
+    stats: Arc<ProxyStats>,
+    client_registry: Arc<ClientRegistry>,
+    // Shared by the listeners of all the cores.
+    session_id: Arc<AtomicUsize>,
+    shutdown_sender: Arc<watch::Sender<bool>>,
+    shutdown_receiver: watch::Receiver<bool>,
 }
 
 impl<H: CmdCtxHandler + ThreadSafe + Clone> ServerProxyService<H> {
@@ -132,12 +271,28 @@ impl<H: CmdCtxHandler + ThreadSafe + Clone> ServerProxyService<H> {
         cmd_ctx_handler: H,
         slow_request_logger: Arc<SlowRequestLogger>,
         future_registry: Arc<TrackedFutureRegistry>,
+        memory_tracker: Arc<MemoryTracker>,
+        stats: Arc<ProxyStats>,
     ) -> Self {
+        let (shutdown_sender, shutdown_receiver) = watch::channel(false);
         Self {
             config,
             cmd_ctx_handler,
             slow_request_logger,
             future_registry,
+            memory_tracker,
+            stats,
+            client_registry: Arc::new(ClientRegistry::default()),
+            session_id: Arc::new(AtomicUsize::new(0)),
+            shutdown_sender: Arc::new(shutdown_sender),
+            shutdown_receiver,
+        }
+    }
+
+    // Stops accepting connections and closes the sessions with a reconnect hint.
+    pub fn shutdown(&self) {
+        if self.shutdown_sender.broadcast(true).is_err() {
+            warn!("failed to shutdown: all the receivers are dropped");
         }
     }
 
@@ -149,22 +304,43 @@ impl<H: CmdCtxHandler + ThreadSafe + Clone> ServerProxyService<H> {
             into_err(err_str)
         })?;
 
-        let mut listener = TcpListener::bind(&address).await.map_err(|err| {
+        // In the thread-per-core mode, each core thread runs this with its own listener.
+        let mut listener = bind_listener(
+            address,
+            self.config.accept_backlog,
+            self.config.thread_per_core,
+        )
+        .map_err(|err| {
             error!("unable to bind address: {} {:?}", address, err);
             err
         })?;
+        let mut accept_limiter = AcceptLimiter::new(
+            self.config.max_accepts_per_tick,
+            Duration::from_millis(self.config.accept_storm_pause_time),
+            Instant::now(),
+        );
 
         let forward_handler = self.cmd_ctx_handler.clone();
         let slow_request_logger = self.slow_request_logger.clone();
 
-        let session_id = AtomicUsize::new(0);
+        let session_id = self.session_id.clone();
         let config = self.config.clone();
 
         let future_registry = self.future_registry.clone();
 
+        let mut shutdown = self.shutdown_receiver.clone();
         let mut s = listener.incoming();
-        while let Some(sock) = s.next().await {
-            let sock = sock?;
+        loop {
+            let sock = match future::select(s.next(), Box::pin(wait_shutdown(&mut shutdown))).await
+            {
+                future::Either::Left((Some(sock), _)) => sock?,
+                future::Either::Left((None, _)) => break,
+                future::Either::Right(((), _)) => {
+                    info!("server proxy is shutting down");
+                    Delay::new(SHUTDOWN_GRACE_TIME).await;
+                    break;
+                }
+            };
 
             if let Err(err) = sock.set_nodelay(true) {
                 let err_str = format!("failed to set TCP_NODELAY: {:?}", err);
@@ -181,27 +357,84 @@ impl<H: CmdCtxHandler + ThreadSafe + Clone> ServerProxyService<H> {
             let curr_session_id = session_id.fetch_add(1, Ordering::SeqCst);
 
             let handle_clone = forward_handler.clone();
-            let session_handler = handle_session(
-                Arc::new(Session::new(
-                    curr_session_id,
-                    handle_clone,
-                    slow_request_logger.clone(),
-                    config.clone(),
-                )),
-                sock,
-                config.session_channel_size,
-                config.session_batch_min_time,
-                config.session_batch_max_time,
-                config.session_batch_buf,
+            let mut session = Session::new(
+                curr_session_id,
+                handle_clone,
+                slow_request_logger.clone(),
+                config.clone(),
             );
+            let killed = session.register_client(self.client_registry.clone(), peer.clone());
+            let session = Arc::new(session);
+            let memory =
+                MemoryTracker::register(self.memory_tracker.clone(), curr_session_id, peer.clone());
+            let control = SessionControl {
+                memory: Some(memory),
+                stats: Some(self.stats.clone()),
+                shutdown: Some(self.shutdown_receiver.clone()),
+                killed: Some(killed),
+                removed_clusters: forward_handler.watch_removed_clusters(),
+                isolate_panics: config.session_panic_isolation,
+                check_reply_order: config.reply_order_check,
+            };
+            let isolate_panics = config.session_panic_isolation;
+            let tls_acceptor = config.tls.get_acceptor();
+            let config = config.clone();
+            let session_handler = async move {
+                match tls_acceptor {
+                    None => {
+                        handle_session(
+                            session,
+                            sock,
+                            config.session_channel_size,
+                            config.session_batch_min_time,
+                            config.session_batch_max_time,
+                            config.session_batch_buf,
+                            control,
+                        )
+                        .await
+                    }
+                    Some(acceptor) => {
+                        let sock = acceptor.accept(sock).await.map_err(|err| {
+                            error!("failed to do TLS handshake: {:?}", err);
+                            SessionError::Canceled
+                        })?;
+                        handle_session(
+                            session,
+                            sock,
+                            config.session_channel_size,
+                            config.session_batch_min_time,
+                            config.session_batch_max_time,
+                            config.session_batch_buf,
+                            control,
+                        )
+                        .await
+                    }
+                }
+            };
 
             let desc = format!("session: session_id={} peer={}", curr_session_id, peer);
-            let fut = session_handler.map(move |res| match res {
-                Ok(()) => info!("session IO closed {}", peer),
-                Err(err) => error!("session IO error {:?} {}", err, peer),
-            });
+            let fut = AssertUnwindSafe(session_handler)
+                .catch_unwind()
+                .map(move |res| match res {
+                    Ok(Ok(())) => info!("session IO closed {}", peer),
+                    Ok(Err(err)) => error!("session IO error {:?} {}", err, peer),
+                    Err(payload) => {
+                        error!(
+                            "session panicked {}: {}",
+                            peer,
+                            get_panic_message(payload.as_ref())
+                        );
+                        if !isolate_panics {
+                            process::abort();
+                        }
+                    }
+                });
             let fut = TrackedFutureRegistry::wrap(future_registry.clone(), fut, desc);
             tokio::spawn(fut);
+
+            if let Some(wait_time) = accept_limiter.on_accept(Instant::now()) {
+                Delay::new(wait_time).await;
+            }
         }
         Ok(())
     }
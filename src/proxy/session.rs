@@ -1,17 +1,30 @@
+use super::acl::{get_cmd_category, get_cmd_keys};
 use super::backend::{CmdTask, CmdTaskFactory, CmdTaskResult};
-use super::cluster::{ClusterTag, DEFAULT_CLUSTER};
+use super::client_list::{ClientHandle, ClientRegistry};
+use super::cluster::{ClusterTag, SlotOwner, DEFAULT_CLUSTER};
 use super::command::{
     new_command_pair, CmdReplyReceiver, CmdReplySender, CmdType, Command, CommandError,
     CommandResult, DataCmdType, TaskReply, TaskResult,
 };
+use super::last_error::LastErrors;
+use super::memory::SessionMemory;
+use super::monitor::{handle_monitor, MonitorReceiver};
+use super::pubsub::{handle_pubsub, is_pubsub_cmd, SubscriberHandle, SubscriberReceiver};
+use super::recent_writes::RecentWrites;
 use super::service::ServerProxyConfig;
 use super::slowlog::{SlowRequestLogger, Slowlog, TaskEvent};
+use super::stats::ProxyStats;
+use super::transaction::{TransactionAction, TransactionFuture, TransactionState};
 use crate::common::batch::TryChunksTimeoutStreamExt;
 use crate::common::cluster::ClusterName;
+use crate::common::config::AclCategory;
+use crate::common::response;
+use crate::common::utils::get_panic_message;
 use crate::protocol::{
     new_simple_packet_codec, BinSafeStr, DecodeError, EncodeError, Resp, RespCodec, RespPacket,
     RespVec,
 };
+use chrono::Utc;
 use futures::{future, stream, Future, TryFutureExt};
 use futures::{SinkExt, StreamExt, TryStreamExt};
 use std::boxed::Box;
@@ -22,11 +35,13 @@ use std::error::Error;
 use std::fmt;
 use std::io;
 use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
 use std::sync;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::net::TcpStream;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
 use tokio_util::codec::Decoder;
 
 // CmdReplyReceiver is the fast path without heap allocation.
@@ -36,6 +51,36 @@ pub type CmdReplyFuture<'a> =
 pub trait CmdHandler {
     fn handle_cmd(&self, cmd: Command) -> CmdReplyFuture;
     fn handle_slowlog(&self, request: Box<RespPacket>, slowlog: Slowlog);
+
+    // The error sent before closing the session so that the client could reconnect to the right place.
+    fn get_reconnect_hint(&self) -> Option<String> {
+        None
+    }
+
+    // Used by the sharded pub/sub which subscribes from the backends directly.
+    fn get_slot_owner(&self, _slot: usize) -> Result<SlotOwner, String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    fn get_local_nodes(&self) -> Result<Vec<String>, String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    // Called with the error replies for `UMCTL LASTERR`.
+    fn record_error(&self, _request: Option<&RespPacket>, _error: &RespPacket) {}
+
+    // The error sent before closing the session when its cluster is removed.
+    fn get_removed_cluster_error(&self, _removed_clusters: &[ClusterName]) -> Option<String> {
+        None
+    }
+
+    fn register_monitor(&self) -> Result<MonitorReceiver, String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    fn register_subscriber(&self) -> Result<(SubscriberHandle, SubscriberReceiver), String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
 }
 
 pub trait CmdCtxHandler {
@@ -43,8 +88,184 @@ pub trait CmdCtxHandler {
         &self,
         cmd_ctx: CmdCtx,
         result_receiver: CmdReplyReceiver,
-        session_cluster_name: &sync::RwLock<ClusterName>,
+        session_auth: &sync::RwLock<SessionAuth>,
     ) -> CmdReplyFuture;
+
+    // Returns another proxy when the cluster has been moved out of this proxy entirely.
+    fn get_moved_address(&self, _cluster_name: &ClusterName) -> Option<String> {
+        None
+    }
+
+    fn get_slot_owner(&self, _auth: &SessionAuth, _slot: usize) -> Result<SlotOwner, String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    fn get_local_nodes(&self, _auth: &SessionAuth) -> Result<Vec<String>, String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    // Used by the commands handled by the session itself such as CLIENT.
+    fn check_session_auth(&self, _auth: &SessionAuth) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Sends the clusters removed from this proxy.
+    fn watch_removed_clusters(&self) -> Option<watch::Receiver<Arc<Vec<ClusterName>>>> {
+        None
+    }
+
+    // Receives the commands of the cluster of the session for MONITOR.
+    fn register_monitor(&self, _auth: &SessionAuth) -> Result<MonitorReceiver, String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    // Subscribes from the backend connections shared by the sessions of the same cluster.
+    fn register_subscriber(
+        &self,
+        _auth: &SessionAuth,
+    ) -> Result<(SubscriberHandle, SubscriberReceiver), String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    // Checked on MULTI so that the transactions are rejected before queuing the commands.
+    fn check_multi(&self, _auth: &SessionAuth) -> Result<(), String> {
+        Err(response::CMD_NOT_SUPPORTED.to_string())
+    }
+
+    // Runs the queued commands of MULTI on the backend owning the slot and returns the reply of EXEC.
+    fn exec_transaction(
+        &self,
+        _auth: &SessionAuth,
+        _slot: usize,
+        _cmds: Vec<Command>,
+    ) -> TransactionFuture {
+        let resp = Resp::Error(response::CMD_NOT_SUPPORTED.to_string().into_bytes());
+        Box::pin(future::ready(resp))
+    }
+}
+
+// Used by the server proxy to control the sessions from outside.
+#[derive(Default)]
+pub struct SessionControl {
+    pub memory: Option<SessionMemory>,
+    pub stats: Option<Arc<ProxyStats>>,
+    pub shutdown: Option<watch::Receiver<bool>>,
+    // Set by `CLIENT KILL`.
+    pub killed: Option<watch::Receiver<bool>>,
+    // Close the sessions of the clusters removed from the metadata.
+    pub removed_clusters: Option<watch::Receiver<Arc<Vec<ClusterName>>>>,
+    // Close the session instead of the proxy on panics.
+    pub isolate_panics: bool,
+    // Close the session when the replies are not in the order of the requests.
+    pub check_reply_order: bool,
+}
+
+pub async fn wait_shutdown(shutdown: &mut watch::Receiver<bool>) {
+    loop {
+        if *shutdown.borrow() {
+            return;
+        }
+        if shutdown.recv().await.is_none() {
+            // The sender is dropped so that it will never shut down.
+            future::pending::<()>().await;
+        }
+    }
+}
+
+async fn wait_optional_shutdown(shutdown: Option<&mut watch::Receiver<bool>>) {
+    match shutdown {
+        Some(shutdown) => wait_shutdown(shutdown).await,
+        None => future::pending::<()>().await,
+    }
+}
+
+async fn wait_removed_clusters(
+    removed_clusters: Option<&mut watch::Receiver<Arc<Vec<ClusterName>>>>,
+) -> Arc<Vec<ClusterName>> {
+    match removed_clusters {
+        Some(receiver) => match receiver.recv().await {
+            Some(removed_clusters) => removed_clusters,
+            None => future::pending().await,
+        },
+        None => future::pending().await,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SessionSignal {
+    Shutdown,
+    Killed,
+    ClustersRemoved(Arc<Vec<ClusterName>>),
+}
+
+async fn wait_session_signal(
+    shutdown: Option<&mut watch::Receiver<bool>>,
+    killed: Option<&mut watch::Receiver<bool>>,
+    removed_clusters: Option<&mut watch::Receiver<Arc<Vec<ClusterName>>>>,
+) -> SessionSignal {
+    let closed = future::select(
+        Box::pin(wait_optional_shutdown(shutdown)),
+        Box::pin(wait_optional_shutdown(killed)),
+    );
+    match future::select(closed, Box::pin(wait_removed_clusters(removed_clusters))).await {
+        future::Either::Left((future::Either::Left(_), _)) => SessionSignal::Shutdown,
+        future::Either::Left((future::Either::Right(_), _)) => SessionSignal::Killed,
+        future::Either::Right((removed_clusters, _)) => {
+            SessionSignal::ClustersRemoved(removed_clusters)
+        }
+    }
+}
+
+// Set by AUTH. The password is kept so that the session
+// could be rejected after the password is removed from the cluster.
+#[derive(Debug, Clone)]
+pub struct SessionAuth {
+    default_cluster_name: ClusterName,
+    cluster_name: ClusterName,
+    // Set by `AUTH <username> <password>`.
+    user: Option<String>,
+    password: Option<String>,
+}
+
+impl SessionAuth {
+    pub fn new(cluster_name: ClusterName) -> Self {
+        Self {
+            default_cluster_name: cluster_name.clone(),
+            cluster_name,
+            user: None,
+            password: None,
+        }
+    }
+
+    pub fn get_cluster_name(&self) -> &ClusterName {
+        &self.cluster_name
+    }
+
+    pub fn get_user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn get_password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    pub fn set(
+        &mut self,
+        cluster_name: ClusterName,
+        user: Option<String>,
+        password: Option<String>,
+    ) {
+        self.cluster_name = cluster_name;
+        self.user = user;
+        self.password = password;
+    }
+
+    // Back to the state of a new connection for `RESET`.
+    pub fn reset(&mut self) {
+        self.cluster_name = self.default_cluster_name.clone();
+        self.user = None;
+        self.password = None;
+    }
 }
 
 #[derive(Debug)]
@@ -54,6 +275,10 @@ pub struct CmdCtx {
     slowlog: Slowlog,
     cluster_name: ClusterName,
     redirection_times: Option<usize>,
+    // Read from the master to read the recent writes of the session.
+    pinned_to_master: bool,
+    // Shown in MONITOR.
+    client_addr: Option<Arc<str>>,
 }
 
 impl CmdCtx {
@@ -71,6 +296,8 @@ impl CmdCtx {
             slowlog,
             cluster_name,
             redirection_times: None,
+            pinned_to_master: false,
+            client_addr: None,
         }
     }
 
@@ -86,6 +313,27 @@ impl CmdCtx {
         self.slowlog.get_session_id()
     }
 
+    #[cfg(feature = "correlation-id")]
+    pub fn get_correlation_id(&self) -> u64 {
+        self.slowlog.get_correlation_id()
+    }
+
+    pub fn set_client_addr(&mut self, client_addr: Arc<str>) {
+        self.client_addr = Some(client_addr);
+    }
+
+    pub fn get_client_addr(&self) -> Option<&str> {
+        self.client_addr.as_deref()
+    }
+
+    pub fn pin_to_master(&mut self) {
+        self.pinned_to_master = true;
+    }
+
+    pub fn is_pinned_to_master(&self) -> bool {
+        self.pinned_to_master
+    }
+
     pub fn change_cmd_element(&mut self, index: usize, data: Vec<u8>) -> bool {
         self.cmd.change_element(index, data)
     }
@@ -99,6 +347,10 @@ impl CmdCtx {
         self.cmd.wrap_cmd(preceding_element)
     }
 
+    pub fn append_cmd(&mut self, following_elements: Vec<BinSafeStr>) -> bool {
+        self.cmd.append_cmd(following_elements)
+    }
+
     pub fn get_cmd_type(&self) -> CmdType {
         self.cmd.get_type()
     }
@@ -116,6 +368,7 @@ impl CmdCtx {
     }
 }
 
+#[derive(Clone)]
 pub struct SessionContext {
     cluster_name: ClusterName,
     session_id: usize,
@@ -135,6 +388,10 @@ impl CmdTask for CmdCtx {
         self.get_cmd().get_slot()
     }
 
+    fn get_session_key(&self) -> Option<usize> {
+        Some(self.get_session_id())
+    }
+
     fn set_result(self, result: CommandResult<Self::Pkt>) {
         let Self {
             cmd,
@@ -142,8 +399,10 @@ impl CmdTask for CmdCtx {
             slowlog,
             ..
         } = self;
-        let task_result =
-            result.map(|packet| Box::new(TaskReply::new(cmd.into_packet(), packet, slowlog)));
+        let seq = cmd.get_seq();
+        let task_result = result.map(|packet| {
+            Box::new(TaskReply::new(cmd.into_packet(), packet, slowlog).with_seq(seq))
+        });
         let res = reply_sender.send(task_result);
         if let Err(e) = res {
             error!("Failed to send result: {:?}", e);
@@ -186,6 +445,10 @@ impl ClusterTag for CmdCtx {
     fn set_cluster_name(&mut self, cluster_name: ClusterName) {
         self.cluster_name = cluster_name;
     }
+
+    fn is_read_cmd(&self) -> bool {
+        !self.pinned_to_master && get_cmd_category(self.get_cmd()) == Some(AclCategory::Read)
+    }
 }
 
 pub struct CmdCtxFactory;
@@ -208,6 +471,18 @@ impl CmdTaskFactory for CmdCtxFactory {
         Pin<Box<dyn Future<Output = CmdTaskResult> + Send + 'static>>,
     ) {
         let packet = Box::new(RespPacket::from_resp_vec(resp));
+        let (cmd_ctx, reply_receiver) = self.create_with_packet(context, packet);
+        let fut = reply_receiver.map_ok(|reply| reply.into_resp_vec());
+        (cmd_ctx, Box::pin(fut))
+    }
+}
+
+impl CmdCtxFactory {
+    pub fn create_with_packet(
+        &self,
+        context: SessionContext,
+        packet: Box<RespPacket>,
+    ) -> (CmdCtx, CmdReplyReceiver) {
         let cmd = Command::new(packet);
         let (reply_sender, reply_receiver) = new_command_pair(&cmd);
         let SessionContext {
@@ -216,17 +491,21 @@ impl CmdTaskFactory for CmdCtxFactory {
             slowlog_enabled,
         } = context;
         let cmd_ctx = CmdCtx::new(cluster_name, cmd, reply_sender, session_id, slowlog_enabled);
-        let fut = reply_receiver.map_ok(|reply| reply.into_resp_vec());
-        (cmd_ctx, Box::pin(fut))
+        (cmd_ctx, reply_receiver)
     }
 }
 
 pub struct Session<H: CmdCtxHandler> {
     session_id: usize,
-    cluster_name: sync::Arc<sync::RwLock<ClusterName>>,
+    auth: sync::Arc<sync::RwLock<SessionAuth>>,
     cmd_ctx_handler: H,
     slow_request_logger: sync::Arc<SlowRequestLogger>,
     config: Arc<ServerProxyConfig>,
+    last_errors: Arc<sync::Mutex<LastErrors>>,
+    transaction: sync::Mutex<TransactionState>,
+    recent_writes: Arc<sync::Mutex<RecentWrites>>,
+    client: Option<ClientHandle>,
+    peer: Option<Arc<str>>,
 }
 
 impl<H: CmdCtxHandler> Session<H> {
@@ -237,23 +516,208 @@ impl<H: CmdCtxHandler> Session<H> {
         config: Arc<ServerProxyConfig>,
     ) -> Self {
         let cluster_name = ClusterName::try_from(DEFAULT_CLUSTER).expect("Session::new");
+        let recent_writes = RecentWrites::new(
+            Duration::from_millis(config.read_after_write_window),
+            config.request_coalescing,
+        );
         Session {
             session_id,
-            cluster_name: sync::Arc::new(sync::RwLock::new(cluster_name)),
+            auth: sync::Arc::new(sync::RwLock::new(SessionAuth::new(cluster_name))),
             cmd_ctx_handler,
             slow_request_logger,
             config,
+            last_errors: Arc::new(sync::Mutex::new(LastErrors::default())),
+            transaction: sync::Mutex::new(TransactionState::default()),
+            recent_writes: Arc::new(sync::Mutex::new(recent_writes)),
+            client: None,
+            peer: None,
+        }
+    }
+
+    // Makes the session visible to `CLIENT LIST` and `CLIENT KILL`.
+    // The returned receiver should be passed in the `SessionControl`.
+    pub fn register_client(
+        &mut self,
+        registry: Arc<ClientRegistry>,
+        peer: String,
+    ) -> watch::Receiver<bool> {
+        self.peer = Some(Arc::from(peer.as_str()));
+        let (client, killed) =
+            ClientRegistry::register(registry, self.session_id, peer, self.auth.clone());
+        self.client = Some(client);
+        killed
+    }
+
+    fn is_last_error_cmd(cmd: &Command) -> bool {
+        cmd.get_type() == CmdType::UmCtl
+            && cmd
+                .get_command_element(1)
+                .map(|sub_cmd| sub_cmd.eq_ignore_ascii_case(b"LASTERR"))
+                .unwrap_or(false)
+    }
+
+    // The errors are read after the replies of the previous commands are recorded.
+    fn handle_last_error(&self, cmd: Command) -> CmdReplyFuture<'static> {
+        let session_id = self.session_id;
+        let last_errors = self.last_errors.clone();
+        CmdReplyFuture::Right(Box::pin(async move {
+            let resp = last_errors
+                .lock()
+                .expect("Session::handle_last_error")
+                .to_resp();
+            let seq = cmd.get_seq();
+            let reply = TaskReply::new(
+                cmd.into_packet(),
+                Box::new(RespPacket::from_resp_vec(resp)),
+                Slowlog::new(session_id, false),
+            )
+            .with_seq(seq);
+            Ok(Box::new(reply))
+        }))
+    }
+
+    fn handle_client(&self, cmd: Command) -> CmdReplyFuture<'static> {
+        let auth = self.auth.read().expect("Session::handle_client").clone();
+        let resp = match (
+            self.client.as_ref(),
+            self.cmd_ctx_handler.check_session_auth(&auth),
+        ) {
+            (_, Err(err)) => Resp::Error(err.into_bytes()),
+            (None, Ok(())) => Resp::Error(response::CMD_NOT_SUPPORTED.to_string().into_bytes()),
+            (Some(client), Ok(())) => client.handle_client_cmd(&cmd),
+        };
+        let seq = cmd.get_seq();
+        let reply = TaskReply::new(
+            cmd.into_packet(),
+            Box::new(RespPacket::from_resp_vec(resp)),
+            Slowlog::new(self.session_id, false),
+        )
+        .with_seq(seq);
+        CmdReplyFuture::Right(Box::pin(future::ok(Box::new(reply))))
+    }
+
+    // The reads pinned to the master also bypass the request coalescing.
+    // Returns the pending keys of the write.
+    fn track_recent_writes(&self, cmd_ctx: &mut CmdCtx) -> Option<Vec<BinSafeStr>> {
+        let mut recent_writes = self
+            .recent_writes
+            .lock()
+            .expect("Session::track_recent_writes");
+        if !recent_writes.is_enabled() || cmd_ctx.get_cmd_type() != CmdType::Others {
+            return None;
+        }
+        let keys = get_cmd_keys(cmd_ctx.get_cmd()).ok()?;
+        let now = Instant::now();
+        match get_cmd_category(cmd_ctx.get_cmd()) {
+            Some(AclCategory::Read) if recent_writes.should_pin(keys.iter().cloned(), now) => {
+                cmd_ctx.pin_to_master();
+                None
+            }
+            Some(AclCategory::Write) => recent_writes.record(keys.into_iter(), now),
+            _ => None,
+        }
+    }
+
+    // The writes of the transactions are recorded on EXEC.
+    fn track_transaction_writes(&self, cmds: &[Command]) -> Option<Vec<BinSafeStr>> {
+        let mut recent_writes = self
+            .recent_writes
+            .lock()
+            .expect("Session::track_transaction_writes");
+        if !recent_writes.is_enabled() {
+            return None;
+        }
+        let now = Instant::now();
+        let mut pending_keys = vec![];
+        for cmd in cmds.iter() {
+            if get_cmd_category(cmd) != Some(AclCategory::Write) {
+                continue;
+            }
+            if let Ok(keys) = get_cmd_keys(cmd) {
+                if let Some(keys) = recent_writes.record(keys.into_iter(), now) {
+                    pending_keys.extend(keys);
+                }
+            }
         }
+        Some(pending_keys).filter(|keys| !keys.is_empty())
+    }
+
+    fn handle_transaction_action(
+        &self,
+        request: Box<RespPacket>,
+        action: TransactionAction,
+        seq: Option<u64>,
+    ) -> CmdReplyFuture<'static> {
+        let (fut, pending_keys) = match action {
+            TransactionAction::Reply(resp) => {
+                (Box::pin(future::ready(resp)) as TransactionFuture, None)
+            }
+            TransactionAction::Exec(slot, cmds) => {
+                let auth = self
+                    .auth
+                    .read()
+                    .expect("Session::handle_transaction_action")
+                    .clone();
+                let pending_keys = self.track_transaction_writes(&cmds);
+                let fut = self.cmd_ctx_handler.exec_transaction(&auth, slot, cmds);
+                (fut, pending_keys)
+            }
+        };
+        let session_id = self.session_id;
+        let recent_writes = self.recent_writes.clone();
+        CmdReplyFuture::Right(Box::pin(async move {
+            let resp = fut.await;
+            if let Some(keys) = pending_keys {
+                recent_writes
+                    .lock()
+                    .expect("Session::handle_transaction_action")
+                    .finish_pending(keys);
+            }
+            let reply = TaskReply::new(
+                request,
+                Box::new(RespPacket::from_resp_vec(resp)),
+                Slowlog::new(session_id, false),
+            )
+            .with_seq(seq);
+            Ok(Box::new(reply))
+        }))
     }
 }
 
 impl<H: CmdCtxHandler> CmdHandler for Session<H> {
     fn handle_cmd(&self, cmd: Command) -> CmdReplyFuture {
+        if let Some(client) = self.client.as_ref() {
+            client.record_cmd(&cmd);
+        }
+        if Self::is_last_error_cmd(&cmd) {
+            return self.handle_last_error(cmd);
+        }
+        if cmd.get_type() == CmdType::Client {
+            return self.handle_client(cmd);
+        }
+        let seq = cmd.get_seq();
+        if cmd.get_type() == CmdType::Multi {
+            let auth = self.auth.read().expect("Session::handle_cmd").clone();
+            if let Err(err) = self.cmd_ctx_handler.check_multi(&auth) {
+                let action = TransactionAction::Reply(Resp::Error(err.into_bytes()));
+                return self.handle_transaction_action(cmd.into_packet(), action, seq);
+            }
+        }
+        let res = self
+            .transaction
+            .lock()
+            .expect("Session::handle_cmd")
+            .handle_cmd(cmd);
+        let cmd = match res {
+            Ok((request, action)) => return self.handle_transaction_action(request, action, seq),
+            Err(cmd) => cmd,
+        };
         let (reply_sender, reply_receiver) = new_command_pair(&cmd);
         let cluster_name = self
-            .cluster_name
+            .auth
             .read()
             .expect("Session::handle_cmd")
+            .get_cluster_name()
             .clone();
 
         let slowlog_enabled = self
@@ -267,25 +731,110 @@ impl<H: CmdCtxHandler> CmdHandler for Session<H> {
             slowlog_enabled,
         );
         cmd_ctx.log_event(TaskEvent::Created);
-        self.cmd_ctx_handler
-            .handle_cmd_ctx(cmd_ctx, reply_receiver, &(*self.cluster_name))
+        if let Some(peer) = self.peer.as_ref() {
+            cmd_ctx.set_client_addr(peer.clone());
+        }
+        let pending_keys = self.track_recent_writes(&mut cmd_ctx);
+        let fut = self
+            .cmd_ctx_handler
+            .handle_cmd_ctx(cmd_ctx, reply_receiver, &self.auth);
+        let keys = match pending_keys {
+            Some(keys) => keys,
+            None => return fut,
+        };
+        let recent_writes = self.recent_writes.clone();
+        CmdReplyFuture::Right(Box::pin(async move {
+            let res = fut.await;
+            recent_writes
+                .lock()
+                .expect("Session::handle_cmd")
+                .finish_pending(keys);
+            res
+        }))
     }
 
     fn handle_slowlog(&self, request: Box<RespPacket>, slowlog: Slowlog) {
         self.slow_request_logger.add_slow_log(request, slowlog)
     }
+
+    fn get_reconnect_hint(&self) -> Option<String> {
+        let cluster_name = self
+            .auth
+            .read()
+            .expect("Session::get_reconnect_hint")
+            .get_cluster_name()
+            .clone();
+        self.cmd_ctx_handler
+            .get_moved_address(&cluster_name)
+            .map(|address| format!("{} {}", response::ERR_RETRY, address))
+    }
+
+    fn get_removed_cluster_error(&self, removed_clusters: &[ClusterName]) -> Option<String> {
+        let cluster_name = self
+            .auth
+            .read()
+            .expect("Session::get_removed_cluster_error")
+            .get_cluster_name()
+            .clone();
+        if !removed_clusters.contains(&cluster_name) {
+            return None;
+        }
+        let err = self
+            .get_reconnect_hint()
+            .unwrap_or_else(|| format!("{} {}", response::ERR_CLUSTER_REMOVED, cluster_name));
+        Some(err)
+    }
+
+    fn get_slot_owner(&self, slot: usize) -> Result<SlotOwner, String> {
+        let auth = self.auth.read().expect("Session::get_slot_owner").clone();
+        self.cmd_ctx_handler.get_slot_owner(&auth, slot)
+    }
+
+    fn get_local_nodes(&self) -> Result<Vec<String>, String> {
+        let auth = self.auth.read().expect("Session::get_local_nodes").clone();
+        self.cmd_ctx_handler.get_local_nodes(&auth)
+    }
+
+    fn register_monitor(&self) -> Result<MonitorReceiver, String> {
+        let auth = self.auth.read().expect("Session::register_monitor").clone();
+        self.cmd_ctx_handler.register_monitor(&auth)
+    }
+
+    fn register_subscriber(&self) -> Result<(SubscriberHandle, SubscriberReceiver), String> {
+        let auth = self
+            .auth
+            .read()
+            .expect("Session::register_subscriber")
+            .clone();
+        self.cmd_ctx_handler.register_subscriber(&auth)
+    }
+
+    fn record_error(&self, request: Option<&RespPacket>, error: &RespPacket) {
+        let backend = request
+            .and_then(|request| Command::new(Box::new(request.clone())).get_slot())
+            .and_then(|slot| self.get_slot_owner(slot).ok())
+            .map(|owner| match owner {
+                SlotOwner::Local(address) | SlotOwner::Remote(address) => address,
+            });
+        self.last_errors
+            .lock()
+            .expect("Session::record_error")
+            .record(Utc::now(), request, error, backend);
+    }
 }
 
-pub async fn handle_session<H>(
+pub async fn handle_session<H, S>(
     handler: sync::Arc<H>,
-    sock: TcpStream,
+    sock: S,
     _channel_size: usize,
     session_batch_min_time: usize,
     session_batch_max_time: usize,
     session_batch_buf: NonZeroUsize,
+    control: SessionControl,
 ) -> Result<(), SessionError>
 where
     H: CmdHandler + Send + Sync + 'static,
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
 {
     let (encoder, decoder) = new_simple_packet_codec::<Box<RespPacket>, Box<RespPacket>>();
     let (mut writer, reader) = RespCodec::new(encoder, decoder).framed(sock).split();
@@ -303,20 +852,94 @@ where
     let mut reply_receiver_list = Vec::with_capacity(session_batch_buf.get());
     let mut replies = Vec::with_capacity(session_batch_buf.get());
     let mut read_buf = VecDeque::with_capacity(session_batch_buf.get());
+    let SessionControl {
+        memory,
+        stats,
+        mut shutdown,
+        mut killed,
+        mut removed_clusters,
+        isolate_panics,
+        check_reply_order,
+    } = control;
+    // The sequence numbers of the next request and the next reply.
+    let mut request_seq: u64 = 0;
+    let mut reply_seq: u64 = 0;
+    // Approximate sizes of the requests in read_buf and the in-flight requests and replies.
+    let mut buffered_size = 0;
+    let mut in_flight_size = 0;
+    let update_memory = |used: usize| {
+        if let Some(memory) = memory.as_ref() {
+            memory.update(used);
+        }
+    };
+    let is_shed = || memory.as_ref().map(|m| m.is_shed()).unwrap_or(false);
 
     loop {
+        if is_shed() {
+            return Err(SessionError::MemoryExceeded);
+        }
+        if shutdown.as_ref().map(|s| *s.borrow()).unwrap_or(false) {
+            return send_final_error(&mut writer, get_shutdown_hint(handler.as_ref())).await;
+        }
+        // The replies of the commands received before are sent before closing.
+        if killed.as_ref().map(|k| *k.borrow()).unwrap_or(false) {
+            return Ok(());
+        }
+
         let reqs = if read_buf.is_empty() {
-            match reader.next().await {
+            let reqs = if shutdown.is_none() && killed.is_none() && removed_clusters.is_none() {
+                reader.next().await
+            } else {
+                // All the replies have been sent when waiting for the next requests here.
+                loop {
+                    let signal = Box::pin(wait_session_signal(
+                        shutdown.as_mut(),
+                        killed.as_mut(),
+                        removed_clusters.as_mut(),
+                    ));
+                    match future::select(reader.next(), signal).await {
+                        future::Either::Left((reqs, _)) => break reqs,
+                        future::Either::Right((SessionSignal::Shutdown, _)) => {
+                            let hint = get_shutdown_hint(handler.as_ref());
+                            return send_final_error(&mut writer, hint).await;
+                        }
+                        future::Either::Right((SessionSignal::Killed, _)) => return Ok(()),
+                        future::Either::Right((SessionSignal::ClustersRemoved(clusters), _)) => {
+                            if let Some(err) = handler.get_removed_cluster_error(&clusters) {
+                                return send_final_error(&mut writer, err).await;
+                            }
+                        }
+                    }
+                }
+            };
+            match reqs {
                 Some(reqs) => reqs,
                 None => return Ok(()),
             }
         } else {
-            read_buf
+            let reqs: Vec<_> = read_buf
                 .drain(..min(read_buf.len(), session_batch_buf.get()))
-                .collect()
+                .collect();
+            buffered_size -= reqs.iter().map(get_req_size).sum::<usize>();
+            reqs
         };
 
-        for req in reqs.into_iter() {
+        if let Some(hint) = handler.get_reconnect_hint() {
+            for _ in reqs.iter() {
+                let resp = Resp::Error(hint.clone().into_bytes());
+                replies.push(Box::new(RespPacket::from_resp_vec(resp)));
+            }
+            let mut batch = stream::iter(replies.drain(..)).map(Ok);
+            if let Err(err) = writer.send_all(&mut batch).await {
+                error!("writer error: {}", err);
+            }
+            return Ok(());
+        }
+
+        let mut reqs = reqs.into_iter();
+        let mut enter_pubsub = false;
+        let mut enter_monitor = false;
+        while let Some(req) = reqs.next() {
             let packet = match req {
                 Ok(packet) => packet,
                 Err(err) => {
@@ -324,11 +947,47 @@ where
                     return Err(err);
                 }
             };
-            let cmd = Command::new(packet);
+            let packet_size = packet.get_approx_size();
+            let mut cmd = Command::new(packet);
+            // This and the following requests are handled in the pub/sub or the monitor mode
+            // after the replies of the previous ones are sent.
+            let cmd_type = cmd.get_type();
+            if is_pubsub_cmd(cmd_type) || cmd_type == CmdType::Monitor {
+                let mut rest: VecDeque<_> = reqs.collect();
+                rest.push_front(Ok(cmd.into_packet()));
+                buffered_size += rest.iter().map(get_req_size).sum::<usize>();
+                rest.extend(read_buf.drain(..));
+                read_buf = rest;
+                enter_monitor = cmd_type == CmdType::Monitor;
+                enter_pubsub = !enter_monitor;
+                break;
+            }
+            in_flight_size += packet_size;
+            if check_reply_order {
+                cmd.set_seq(request_seq);
+                request_seq += 1;
+            }
 
-            let fut = handler.handle_cmd(cmd);
+            let fut = if isolate_panics {
+                let task_type = (cmd.get_type(), cmd.get_data_cmd_type());
+                match panic::catch_unwind(AssertUnwindSafe(|| handler.handle_cmd(cmd))) {
+                    Ok(fut) => fut,
+                    Err(payload) => {
+                        error!(
+                            "session panicked on command {:?}: {}",
+                            task_type,
+                            get_panic_message(payload.as_ref())
+                        );
+                        send_final_error(&mut writer, response::ERR_INTERNAL.to_string()).await?;
+                        return Err(SessionError::Panicked);
+                    }
+                }
+            } else {
+                handler.handle_cmd(cmd)
+            };
             reply_receiver_list.push(fut);
         }
+        update_memory(buffered_size + in_flight_size);
 
         for reply_receiver in reply_receiver_list.drain(..) {
             let res = {
@@ -349,7 +1008,14 @@ where
                         future::Either::Right((read_result, fut)) => {
                             reply_fut = Some(fut);
                             match read_result {
-                                Some(reqs) => read_buf.extend(reqs),
+                                Some(reqs) => {
+                                    buffered_size += reqs.iter().map(get_req_size).sum::<usize>();
+                                    read_buf.extend(reqs);
+                                    update_memory(buffered_size + in_flight_size);
+                                    if is_shed() {
+                                        return Err(SessionError::MemoryExceeded);
+                                    }
+                                }
                                 None => return Ok(()),
                             }
                             continue;
@@ -359,23 +1025,49 @@ where
                 res.map_err(SessionError::CmdErr)
             };
 
+            let expected_seq = reply_seq;
+            reply_seq += 1;
+            if let (true, Ok(task_reply)) = (check_reply_order, res.as_ref()) {
+                if let Err(err) = check_reply_seq(expected_seq, task_reply) {
+                    error!("{}", err);
+                    send_final_error(&mut writer, response::ERR_INTERNAL.to_string()).await?;
+                    return Err(SessionError::InvalidState);
+                }
+            }
+
             let packet = match res {
                 Ok(task_reply) => {
                     let (request, packet, mut slowlog) = (*task_reply).into_inner();
+                    if packet.is_error() {
+                        handler.record_error(Some(&request), &packet);
+                    }
                     slowlog.log_event(TaskEvent::WaitDone);
                     handler.handle_slowlog(request, slowlog);
                     packet
                 }
+                Err(SessionError::CmdErr(CommandError::Timeout)) => {
+                    let resp = Resp::Error(response::ERR_TIMEOUT.to_string().into_bytes());
+                    let packet = Box::new(RespPacket::from_resp_vec(resp));
+                    handler.record_error(None, &packet);
+                    packet
+                }
                 Err(e) => {
-                    let err_msg = format!("Err cmd error {:?}", e);
+                    let err_msg = format!("{} cmd error {:?}", response::UM_INTERNAL, e);
                     error!("{}", err_msg);
                     let resp = Resp::Error(err_msg.into_bytes());
-                    Box::new(RespPacket::from_resp_vec(resp))
+                    let packet = Box::new(RespPacket::from_resp_vec(resp));
+                    handler.record_error(None, &packet);
+                    packet
                 }
             };
 
+            if let Some(stats) = stats.as_ref() {
+                stats.record_reply(packet.is_error());
+            }
+            in_flight_size += packet.get_approx_size();
             replies.push(packet);
         }
+        update_memory(buffered_size + in_flight_size);
 
         let mut batch = stream::iter(replies.drain(..)).map(Ok);
         if let Err(err) = writer.send_all(&mut batch).await {
@@ -386,9 +1078,75 @@ where
             };
             return Err(err);
         }
+        in_flight_size = 0;
+        update_memory(buffered_size);
+
+        if enter_pubsub || enter_monitor {
+            let keep_session = if enter_pubsub {
+                handle_pubsub(
+                    handler.as_ref(),
+                    &mut reader,
+                    &mut writer,
+                    &mut read_buf,
+                    shutdown.as_mut(),
+                )
+                .await?
+            } else {
+                handle_monitor(
+                    handler.as_ref(),
+                    &mut reader,
+                    &mut writer,
+                    &mut read_buf,
+                    shutdown.as_mut(),
+                )
+                .await?
+            };
+            if !keep_session {
+                return Ok(());
+            }
+            buffered_size = read_buf.iter().map(get_req_size).sum::<usize>();
+            update_memory(buffered_size);
+        }
+    }
+}
+
+// The replies without the sequence numbers are not checked.
+fn check_reply_seq(expected_seq: u64, task_reply: &TaskReply) -> Result<(), String> {
+    match task_reply.get_seq() {
+        Some(seq) if seq != expected_seq => Err(format!(
+            "reply out of order: expected request {} but got the reply of request {}: {:?}",
+            expected_seq,
+            seq,
+            task_reply
+                .get_request()
+                .get_array_element(0)
+                .map(String::from_utf8_lossy),
+        )),
+        _ => Ok(()),
     }
 }
 
+fn get_shutdown_hint<H: CmdHandler>(handler: &H) -> String {
+    handler
+        .get_reconnect_hint()
+        .unwrap_or_else(|| response::ERR_SHUTTING_DOWN.to_string())
+}
+
+async fn send_final_error<W>(writer: &mut W, hint: String) -> Result<(), SessionError>
+where
+    W: futures::Sink<Box<RespPacket>, Error = EncodeError<Box<RespPacket>>> + Unpin,
+{
+    let resp = Resp::Error(hint.into_bytes());
+    if let Err(err) = writer.send(Box::new(RespPacket::from_resp_vec(resp))).await {
+        error!("failed to send the final error: {}", err);
+    }
+    Ok(())
+}
+
+fn get_req_size(req: &Result<Box<RespPacket>, SessionError>) -> usize {
+    req.as_ref().map(|p| p.get_approx_size()).unwrap_or(0)
+}
+
 #[derive(Debug)]
 pub enum SessionError {
     Io(io::Error),
@@ -396,6 +1154,8 @@ pub enum SessionError {
     InvalidProtocol,
     Canceled,
     InvalidState,
+    MemoryExceeded,
+    Panicked,
 }
 
 impl fmt::Display for SessionError {
@@ -422,6 +1182,8 @@ impl Error for SessionError {
 mod tests {
     use super::*;
     use crate::protocol::{Array, BulkStr, Resp};
+    use crate::proxy::coalescing::{CoalescingResult, GetCoalescer};
+    use crate::proxy::config_file::ServerProxyConfigFile;
     use matches::assert_matches;
     use std::convert::TryFrom;
     use tokio;
@@ -442,4 +1204,168 @@ mod tests {
         };
         assert_matches!(err, CommandError::Dropped);
     }
+
+    #[test]
+    fn test_check_reply_seq() {
+        let gen_packet = |s: &str| {
+            let resp = Resp::Arr(Array::Arr(vec![Resp::Bulk(BulkStr::Str(
+                s.as_bytes().to_vec(),
+            ))]));
+            Box::new(RespPacket::from_resp_vec(resp))
+        };
+        let gen_reply = |seq: Option<u64>| {
+            TaskReply::new(
+                gen_packet("PING"),
+                gen_packet("PONG"),
+                Slowlog::new(1, false),
+            )
+            .with_seq(seq)
+        };
+        assert!(check_reply_seq(3, &gen_reply(Some(3))).is_ok());
+        assert!(check_reply_seq(3, &gen_reply(None)).is_ok());
+        let err = check_reply_seq(3, &gen_reply(Some(4))).unwrap_err();
+        assert!(err.contains("PING"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_shutdown() {
+        let (sender, mut receiver) = watch::channel(false);
+        let mut shutdown = receiver.clone();
+        let fut = Box::pin(wait_shutdown(&mut shutdown));
+        let fut = match future::select(fut, future::ready(())).await {
+            future::Either::Left(_) => panic!(),
+            future::Either::Right(((), fut)) => fut,
+        };
+        sender.broadcast(true).unwrap();
+        fut.await;
+
+        // Returns immediately after the shutdown.
+        wait_shutdown(&mut receiver).await;
+    }
+
+    #[tokio::test]
+    async fn test_wait_session_signal() {
+        let (_shutdown_sender, mut shutdown) = watch::channel(false);
+        let (sender, mut removed_clusters) = watch::channel(Arc::new(vec![]));
+        // The initial value is returned first.
+        let signal =
+            wait_session_signal(Some(&mut shutdown), None, Some(&mut removed_clusters)).await;
+        assert_eq!(signal, SessionSignal::ClustersRemoved(Arc::new(vec![])));
+
+        let signal = Box::pin(wait_session_signal(
+            Some(&mut shutdown),
+            None,
+            Some(&mut removed_clusters),
+        ));
+        let signal = match future::select(signal, future::ready(())).await {
+            future::Either::Left(_) => panic!(),
+            future::Either::Right(((), signal)) => signal,
+        };
+        let clusters = Arc::new(vec![ClusterName::try_from("mycluster").unwrap()]);
+        sender.broadcast(clusters.clone()).unwrap();
+        assert_eq!(signal.await, SessionSignal::ClustersRemoved(clusters));
+
+        let (killed_sender, mut killed) = watch::channel(false);
+        killed_sender.broadcast(true).unwrap();
+        let signal = wait_session_signal(None, Some(&mut killed), None).await;
+        assert_eq!(signal, SessionSignal::Killed);
+    }
+
+    #[test]
+    fn test_reset_session_auth() {
+        let default_cluster = ClusterName::try_from("default").unwrap();
+        let mut auth = SessionAuth::new(default_cluster.clone());
+        auth.set(
+            ClusterName::try_from("mycluster").unwrap(),
+            Some("alice".to_string()),
+            Some("pwd".to_string()),
+        );
+        assert_eq!(auth.get_user(), Some("alice"));
+        assert_eq!(auth.get_password(), Some("pwd"));
+        auth.reset();
+        assert_eq!(auth.get_cluster_name(), &default_cluster);
+        assert_eq!(auth.get_user(), None);
+        assert_eq!(auth.get_password(), None);
+    }
+
+    // Keeps the commands without replying them.
+    #[derive(Default)]
+    struct PendingHandler {
+        cmd_ctxs: sync::Mutex<Vec<CmdCtx>>,
+    }
+
+    impl CmdCtxHandler for PendingHandler {
+        fn handle_cmd_ctx(
+            &self,
+            cmd_ctx: CmdCtx,
+            result_receiver: CmdReplyReceiver,
+            _session_auth: &sync::RwLock<SessionAuth>,
+        ) -> CmdReplyFuture<'_> {
+            self.cmd_ctxs.lock().unwrap().push(cmd_ctx);
+            CmdReplyFuture::Left(result_receiver)
+        }
+    }
+
+    fn gen_cmd(elements: Vec<&str>) -> Command {
+        let resp = Resp::Arr(Array::Arr(
+            elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect(),
+        ));
+        Command::new(Box::new(RespPacket::from_resp_vec(resp)))
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_set_then_get_bypass_coalescing() {
+        let config_file = ServerProxyConfigFile {
+            request_coalescing: true,
+            ..Default::default()
+        };
+        let (config, _) = config_file.into_config().unwrap();
+        let config = Arc::new(config);
+        let session = Session::new(
+            1,
+            PendingHandler::default(),
+            Arc::new(SlowRequestLogger::new(config.clone())),
+            config,
+        );
+        let coalescer = GetCoalescer::default();
+        let take_cmd_ctx = || session.cmd_ctx_handler.cmd_ctxs.lock().unwrap().remove(0);
+
+        // The GET of another session is in flight.
+        let (other_get, _) = CmdCtxFactory.create_with_packet(
+            SessionContext {
+                cluster_name: session.auth.read().unwrap().get_cluster_name().clone(),
+                session_id: 2,
+                slowlog_enabled: false,
+            },
+            Box::new(gen_cmd(vec!["GET", "k"]).get_packet()),
+        );
+        let _guard = match coalescer.coalesce(other_get) {
+            CoalescingResult::Leader(_, guard) => guard,
+            _ => panic!(),
+        };
+
+        // The GET following the SET not replied yet should not join the in-flight GET.
+        let set_fut = session.handle_cmd(gen_cmd(vec!["SET", "k", "v2"]));
+        let set_ctx = take_cmd_ctx();
+        drop(session.handle_cmd(gen_cmd(vec!["GET", "k"])));
+        let get_ctx = take_cmd_ctx();
+        assert!(matches!(
+            coalescer.coalesce(get_ctx),
+            CoalescingResult::Bypass(_)
+        ));
+        drop(session.handle_cmd(gen_cmd(vec!["GET", "other"])));
+        assert!(!take_cmd_ctx().is_pinned_to_master());
+
+        set_ctx.set_resp_result(Ok(Resp::Simple(b"OK".to_vec())));
+        set_fut.await.unwrap();
+        drop(session.handle_cmd(gen_cmd(vec!["GET", "k"])));
+        let get_ctx = take_cmd_ctx();
+        assert!(matches!(
+            coalescer.coalesce(get_ctx),
+            CoalescingResult::Waiting
+        ));
+    }
 }
@@ -0,0 +1,162 @@
+use super::acl::get_cmd_keys;
+use super::backend::ConnFactory;
+use super::cluster::ClusterTag;
+use super::command::{uppercase_cmd_name, Command};
+use super::manager::SharedMetaMap;
+use super::session::CmdCtx;
+use crate::common::cluster::ClusterName;
+use crate::protocol::RespPacket;
+
+#[derive(Debug, PartialEq)]
+pub enum SizeLimitError {
+    KeyTooLarge,
+    ValueTooLarge,
+}
+
+// Rejects the huge keys and values before they reach the backends
+// as they will slow down the migration and replication.
+pub struct CmdSizeChecker<C: ConnFactory<Pkt = RespPacket>> {
+    meta_map: SharedMetaMap<C>,
+}
+
+impl<C: ConnFactory<Pkt = RespPacket>> CmdSizeChecker<C> {
+    pub fn new(meta_map: SharedMetaMap<C>) -> Self {
+        Self { meta_map }
+    }
+
+    pub fn check(&self, cmd_ctx: &CmdCtx) -> Result<(), SizeLimitError> {
+        self.check_cmd(cmd_ctx.get_cluster_name(), cmd_ctx.get_cmd())
+    }
+
+    pub fn check_cmd(
+        &self,
+        cluster_name: &ClusterName,
+        cmd: &Command,
+    ) -> Result<(), SizeLimitError> {
+        let meta_map = self.meta_map.lease();
+        match meta_map.get_cluster_map().get_config(cluster_name) {
+            Some(config) => check_cmd_size(
+                config.max_key_size as usize,
+                config.max_value_size as usize,
+                cmd,
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+// Zero means no limitation.
+pub fn check_cmd_size(
+    max_key_size: usize,
+    max_value_size: usize,
+    cmd: &Command,
+) -> Result<(), SizeLimitError> {
+    if max_key_size != 0 {
+        // Invalid commands will be rejected by the backend.
+        let keys = get_cmd_keys(cmd).unwrap_or_default();
+        if keys.into_iter().any(|key| key.len() > max_key_size) {
+            return Err(SizeLimitError::KeyTooLarge);
+        }
+    }
+
+    if max_value_size != 0 {
+        let len = cmd.get_command_len().unwrap_or(0);
+        for i in get_value_indices(cmd, len).into_iter() {
+            let exceeded = cmd
+                .get_command_element(i)
+                .map(|value| value.len() > max_value_size)
+                .unwrap_or(false);
+            if exceeded {
+                return Err(SizeLimitError::ValueTooLarge);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn get_value_indices(cmd: &Command, len: usize) -> Vec<usize> {
+    let cmd_name = match cmd.get_command_element(0) {
+        Some(cmd_name) => cmd_name,
+        None => return vec![],
+    };
+    let stack_cmd_name = match uppercase_cmd_name(cmd_name) {
+        Some(stack_cmd_name) => stack_cmd_name,
+        None => return vec![],
+    };
+    let cmd_name: &[u8] = &stack_cmd_name;
+
+    match cmd_name {
+        b"SET" | b"SETNX" | b"GETSET" | b"APPEND" => vec![2],
+        b"SETEX" | b"PSETEX" | b"SETRANGE" | b"HSETNX" | b"LSET" => vec![3],
+        b"LINSERT" => vec![4],
+        b"MSET" | b"MSETNX" => (2..len).step_by(2).collect(),
+        // Both the fields and the values are checked.
+        b"HSET" | b"HMSET" | b"LPUSH" | b"RPUSH" | b"LPUSHX" | b"RPUSHX" | b"SADD" => {
+            (2..len).collect()
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Array, BulkStr, Resp};
+
+    fn gen_cmd(cmd: Vec<&str>) -> Command {
+        let arr = cmd
+            .into_iter()
+            .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+            .collect();
+        let packet = RespPacket::Data(Resp::Arr(Array::Arr(arr)));
+        Command::new(Box::new(packet))
+    }
+
+    #[test]
+    fn test_no_size_limit() {
+        let value = "v".repeat(1024);
+        let cmd = gen_cmd(vec!["SET", &value, &value]);
+        assert!(check_cmd_size(0, 0, &cmd).is_ok());
+    }
+
+    #[test]
+    fn test_key_size_limit() {
+        assert!(check_cmd_size(3, 0, &gen_cmd(vec!["GET", "key"])).is_ok());
+        assert_eq!(
+            check_cmd_size(3, 0, &gen_cmd(vec!["GET", "long_key"])),
+            Err(SizeLimitError::KeyTooLarge)
+        );
+        assert_eq!(
+            check_cmd_size(3, 0, &gen_cmd(vec!["MGET", "a", "long_key"])),
+            Err(SizeLimitError::KeyTooLarge)
+        );
+        assert!(check_cmd_size(3, 0, &gen_cmd(vec!["MSET", "a", "long_value"])).is_ok());
+    }
+
+    #[test]
+    fn test_value_size_limit() {
+        assert!(check_cmd_size(0, 5, &gen_cmd(vec!["SET", "long_key", "value"])).is_ok());
+        assert_eq!(
+            check_cmd_size(0, 5, &gen_cmd(vec!["set", "key", "long_value"])),
+            Err(SizeLimitError::ValueTooLarge)
+        );
+        assert_eq!(
+            check_cmd_size(0, 5, &gen_cmd(vec!["SETEX", "key", "10", "long_value"])),
+            Err(SizeLimitError::ValueTooLarge)
+        );
+        assert_eq!(
+            check_cmd_size(0, 5, &gen_cmd(vec!["APPEND", "key", "long_value"])),
+            Err(SizeLimitError::ValueTooLarge)
+        );
+        assert_eq!(
+            check_cmd_size(0, 5, &gen_cmd(vec!["MSET", "a", "v", "b", "long_value"])),
+            Err(SizeLimitError::ValueTooLarge)
+        );
+        assert_eq!(
+            check_cmd_size(0, 5, &gen_cmd(vec!["HSET", "key", "f", "long_value"])),
+            Err(SizeLimitError::ValueTooLarge)
+        );
+        assert!(check_cmd_size(0, 5, &gen_cmd(vec!["HSET", "key", "f", "v"])).is_ok());
+        assert!(check_cmd_size(0, 5, &gen_cmd(vec!["GET", "long_value"])).is_ok());
+    }
+}
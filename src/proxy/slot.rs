@@ -26,11 +26,26 @@ impl SlotMap {
     pub fn get(&self, slot: usize) -> Option<&str> {
         self.data.get(slot)
     }
+
+    // The index could be used to look up the data precomputed for each address
+    // in the order of `get_addresses`.
+    pub fn get_index(&self, slot: usize) -> Option<usize> {
+        self.data.get_index(slot)
+    }
+
+    pub fn get_index_by_address(&self, address: &str) -> Option<usize> {
+        self.data.addr_indexes.get(address).cloned()
+    }
+
+    pub fn get_addresses(&self) -> &[String] {
+        &self.data.addrs
+    }
 }
 
 pub struct SlotMapData {
     slot_arr: Vec<Option<usize>>,
     addrs: Vec<String>,
+    addr_indexes: HashMap<String, usize>,
 }
 
 impl SlotMapData {
@@ -57,13 +72,26 @@ impl SlotMapData {
                 }
             }
         }
-        SlotMapData { slot_arr, addrs }
+        let addr_indexes = addrs
+            .iter()
+            .enumerate()
+            .map(|(i, addr)| (addr.clone(), i))
+            .collect();
+        SlotMapData {
+            slot_arr,
+            addrs,
+            addr_indexes,
+        }
     }
 
     pub fn get(&self, slot: usize) -> Option<&str> {
-        let addr_index = self.slot_arr.get(slot).and_then(|opt| *opt)?;
+        let addr_index = self.get_index(slot)?;
         self.addrs.get(addr_index).map(|s| s.as_str())
     }
+
+    pub fn get_index(&self, slot: usize) -> Option<usize> {
+        self.slot_arr.get(slot).and_then(|opt| *opt)
+    }
 }
 
 #[cfg(test)]
@@ -71,6 +99,27 @@ mod tests {
     use super::*;
     use crate::common::cluster::{RangeList, SlotRange, SlotRangeTag};
     use std::convert::TryFrom;
+    use std::time::Instant;
+
+    fn gen_range_map(node_num: usize) -> HashMap<String, Vec<SlotRange>> {
+        let slots_per_node = SLOT_NUM / node_num;
+        (0..node_num)
+            .map(|i| {
+                let start = i * slots_per_node;
+                let end = if i + 1 == node_num {
+                    SLOT_NUM - 1
+                } else {
+                    start + slots_per_node - 1
+                };
+                let range_list = RangeList::try_from(format!("1 {}-{}", start, end).as_str());
+                let slot_range = SlotRange {
+                    range_list: range_list.unwrap(),
+                    tag: SlotRangeTag::None,
+                };
+                (format!("127.0.0.1:{}", 7000 + i), vec![slot_range])
+            })
+            .collect()
+    }
 
     #[test]
     fn test_slot_map() {
@@ -90,4 +139,64 @@ mod tests {
             assert_eq!(node, backend);
         }
     }
+
+    #[test]
+    fn test_slot_map_index() {
+        let slot_map = SlotMap::from_ranges(gen_range_map(3));
+        assert_eq!(slot_map.get_addresses().len(), 3);
+        for slot in 0..SLOT_NUM {
+            let index = slot_map.get_index(slot).unwrap();
+            let address = slot_map.get(slot).unwrap();
+            assert_eq!(slot_map.get_addresses()[index], address);
+            assert_eq!(slot_map.get_index_by_address(address), Some(index));
+        }
+        assert_eq!(slot_map.get_index(SLOT_NUM), None);
+        assert_eq!(slot_map.get_index_by_address("127.0.0.1:6379"), None);
+    }
+
+    // Run with `cargo test --release bench_slot_lookup -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_slot_lookup() {
+        const ROUNDS: usize = 1000;
+        let range_map = gen_range_map(16);
+        let nodes: HashMap<String, usize> = range_map.keys().cloned().zip(0..).collect();
+        let slot_map = SlotMap::from_ranges(range_map);
+        let senders: Vec<usize> = slot_map
+            .get_addresses()
+            .iter()
+            .map(|addr| nodes[addr])
+            .collect();
+
+        // Looks up the sender by the address of the slot.
+        let start = Instant::now();
+        let mut sum = 0;
+        for _ in 0..ROUNDS {
+            for slot in 0..SLOT_NUM {
+                sum += slot_map.get(slot).and_then(|addr| nodes.get(addr)).unwrap();
+            }
+        }
+        let by_address = start.elapsed();
+
+        // Looks up the sender by the precomputed index of the slot.
+        let start = Instant::now();
+        let mut index_sum = 0;
+        for _ in 0..ROUNDS {
+            for slot in 0..SLOT_NUM {
+                index_sum += slot_map
+                    .get_index(slot)
+                    .and_then(|i| senders.get(i))
+                    .unwrap();
+            }
+        }
+        let by_index = start.elapsed();
+
+        assert_eq!(sum, index_sum);
+        let lookups = (ROUNDS * SLOT_NUM) as u32;
+        println!(
+            "by address: {:?}/lookup, by index: {:?}/lookup",
+            by_address / lookups,
+            by_index / lookups
+        );
+    }
 }
@@ -0,0 +1,145 @@
+use crate::common::cluster::{ClusterName, Range, RangeList, SlotRange};
+use crate::common::proto::ProxyClusterMap;
+use crate::common::utils::{ThreadSafe, SLOT_NUM};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotOwnershipChange {
+    pub cluster_name: ClusterName,
+    pub epoch: u64,
+    pub gained: RangeList,
+    pub lost: RangeList,
+}
+
+// Invoked after the new metadata takes effect, for the users embedding this crate
+// to warm caches, update service discovery, or do their own cleanup.
+// It should not block since it's called inside `UMCTL SETCLUSTER`.
+pub trait SlotOwnershipHook: ThreadSafe {
+    fn on_slots_changed(&self, changes: &[SlotOwnershipChange]);
+}
+
+pub type OwnedSlots = HashMap<ClusterName, Vec<bool>>;
+
+// The importing slots are not owned by this proxy until the migration is committed.
+pub fn get_owned_slots(local: &ProxyClusterMap) -> OwnedSlots {
+    let mut owned_slots = HashMap::new();
+    for (cluster_name, node_map) in local.get_map().iter() {
+        let slots = owned_slots
+            .entry(cluster_name.clone())
+            .or_insert_with(|| vec![false; SLOT_NUM]);
+        for slot_range in node_map.values().flat_map(|ranges| ranges.iter()) {
+            mark_slots(slots, slot_range);
+        }
+    }
+    owned_slots
+}
+
+fn mark_slots(slots: &mut [bool], slot_range: &SlotRange) {
+    if slot_range.tag.is_importing() {
+        return;
+    }
+    for range in slot_range.get_range_list().get_ranges().iter() {
+        let end = range.end().min(SLOT_NUM - 1);
+        if range.start() > end {
+            continue;
+        }
+        for owned in slots[range.start()..=end].iter_mut() {
+            *owned = true;
+        }
+    }
+}
+
+pub fn diff_owned_slots(
+    old_slots: &OwnedSlots,
+    new_slots: &OwnedSlots,
+    epoch: u64,
+) -> Vec<SlotOwnershipChange> {
+    let empty = vec![false; SLOT_NUM];
+    let cluster_names: HashSet<&ClusterName> = old_slots.keys().chain(new_slots.keys()).collect();
+
+    let mut changes = vec![];
+    for cluster_name in cluster_names.into_iter() {
+        let old = old_slots.get(cluster_name).unwrap_or(&empty);
+        let new = new_slots.get(cluster_name).unwrap_or(&empty);
+        let gained = to_range_list(new, old);
+        let lost = to_range_list(old, new);
+        if gained.get_ranges().is_empty() && lost.get_ranges().is_empty() {
+            continue;
+        }
+        changes.push(SlotOwnershipChange {
+            cluster_name: cluster_name.clone(),
+            epoch,
+            gained,
+            lost,
+        });
+    }
+    changes
+}
+
+// Slots in `slots` but not in `excluded`.
+fn to_range_list(slots: &[bool], excluded: &[bool]) -> RangeList {
+    let mut ranges = vec![];
+    let mut start = None;
+    for slot in 0..SLOT_NUM {
+        let included = slots[slot] && !excluded[slot];
+        match (included, start) {
+            (true, None) => start = Some(slot),
+            (false, Some(s)) => {
+                ranges.push(Range(s, slot - 1));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        ranges.push(Range(s, SLOT_NUM - 1));
+    }
+    RangeList::new(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::SlotRangeTag;
+    use std::convert::TryFrom;
+
+    fn gen_local(cluster: &str, ranges: Vec<Range>) -> ProxyClusterMap {
+        let slot_ranges = ranges
+            .into_iter()
+            .map(|range| SlotRange {
+                range_list: RangeList::from_single_range(range),
+                tag: SlotRangeTag::None,
+            })
+            .collect();
+        let mut node_map = HashMap::new();
+        node_map.insert("127.0.0.1:6379".to_string(), slot_ranges);
+        let mut cluster_map = HashMap::new();
+        cluster_map.insert(ClusterName::try_from(cluster).unwrap(), node_map);
+        ProxyClusterMap::new(cluster_map)
+    }
+
+    #[test]
+    fn test_diff_owned_slots() {
+        let old = get_owned_slots(&gen_local("mycluster", vec![Range(0, 100)]));
+        let new = get_owned_slots(&gen_local(
+            "mycluster",
+            vec![Range(50, 100), Range(200, 300)],
+        ));
+        let changes = diff_owned_slots(&old, &new, 7);
+        assert_eq!(changes.len(), 1);
+        let change = &changes[0];
+        assert_eq!(change.epoch, 7);
+        assert_eq!(change.gained, RangeList::new(vec![Range(200, 300)]));
+        assert_eq!(change.lost, RangeList::new(vec![Range(0, 49)]));
+
+        assert!(diff_owned_slots(&new, &new, 8).is_empty());
+
+        let changes = diff_owned_slots(&new, &OwnedSlots::new(), 9);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].gained.get_ranges().is_empty());
+        assert_eq!(
+            changes[0].lost,
+            RangeList::new(vec![Range(50, 100), Range(200, 300)])
+        );
+    }
+}
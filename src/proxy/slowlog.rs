@@ -65,11 +65,17 @@ impl Default for RequestEventMap {
     }
 }
 
+#[cfg(feature = "correlation-id")]
+static NEXT_CORRELATION_ID: atomic::AtomicU64 = atomic::AtomicU64::new(1);
+
 #[derive(Debug)]
 pub struct Slowlog {
     event_map: RequestEventMap,
     session_id: usize,
     enabled: bool,
+    // Logged with all the events of the request to trace it.
+    #[cfg(feature = "correlation-id")]
+    correlation_id: u64,
 }
 
 #[derive(Debug)]
@@ -85,10 +91,17 @@ impl Slowlog {
             event_map: RequestEventMap::default(),
             session_id,
             enabled,
+            #[cfg(feature = "correlation-id")]
+            correlation_id: NEXT_CORRELATION_ID.fetch_add(1, atomic::Ordering::Relaxed),
         }
     }
 
     pub fn log_event(&mut self, event: TaskEvent) {
+        #[cfg(feature = "correlation-id")]
+        info!(
+            "correlation_id={} session_id={} event={:?}",
+            self.correlation_id, self.session_id, event
+        );
         if !self.enabled {
             return;
         }
@@ -100,6 +113,11 @@ impl Slowlog {
         self.session_id
     }
 
+    #[cfg(feature = "correlation-id")]
+    pub fn get_correlation_id(&self) -> u64 {
+        self.correlation_id
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
@@ -120,7 +138,7 @@ impl SlowlogRecord {
         }
     }
 
-    fn get_brief_command(request: &RespPacket) -> Vec<String> {
+    pub fn get_brief_command(request: &RespPacket) -> Vec<String> {
         let data_to_string = |data: &[u8]| match str::from_utf8(&data) {
             Ok(s) => s.to_string(),
             _ => format!("{:?}", data),
@@ -217,6 +235,27 @@ impl SlowRequestLogger {
         }
     }
 
+    // The `slowlog` section of `INFO`.
+    pub fn gen_info_lines(&self) -> Vec<String> {
+        let entries = self
+            .slowlogs
+            .iter()
+            .filter(|log_slot| log_slot.load().is_some())
+            .count();
+        vec![
+            format!("slowlog_len:{}", self.slowlogs.len()),
+            format!("slowlog_entries:{}", entries),
+            format!(
+                "slowlog_log_slower_than:{}",
+                self.config.get_slowlog_log_slower_than()
+            ),
+            format!(
+                "slowlog_sample_rate:{}",
+                self.config.get_slowlog_sample_rate()
+            ),
+        ]
+    }
+
     // Returns whether this current log should be enabled.
     pub fn limit_rate(&self, slowlog_sample_rate: u64) -> bool {
         self.rate_limiter.check_current_enabled(slowlog_sample_rate)
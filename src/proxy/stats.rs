@@ -0,0 +1,184 @@
+use super::memory::MemoryTracker;
+use super::meta_lock::MetaLockStats;
+use crate::common::cluster::{SLOT_LOAD_BUCKETS, SLOT_LOAD_BUCKET_SIZE};
+use crate::protocol::{Array, BulkStr, Resp, RespVec};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Counters of the replies sent to the clients.
+pub struct ProxyStats {
+    commands: AtomicU64,
+    errors: AtomicU64,
+    // The commands not in the command table forwarded by the first argument.
+    unknown_commands: AtomicU64,
+    // The writes replied before any replica receives them in `write_ack_policy` of `replica`.
+    unacked_writes: AtomicU64,
+    // The commands of each slot bucket, used by the broker to balance the load.
+    slot_reads: Vec<AtomicU64>,
+    slot_writes: Vec<AtomicU64>,
+}
+
+impl Default for ProxyStats {
+    fn default() -> Self {
+        let gen_buckets = || (0..SLOT_LOAD_BUCKETS).map(|_| AtomicU64::new(0)).collect();
+        Self {
+            commands: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            unknown_commands: AtomicU64::new(0),
+            unacked_writes: AtomicU64::new(0),
+            slot_reads: gen_buckets(),
+            slot_writes: gen_buckets(),
+        }
+    }
+}
+
+impl ProxyStats {
+    pub fn record_reply(&self, is_error: bool) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+        if is_error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_unknown_command(&self) {
+        self.unknown_commands.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get_unknown_commands(&self) -> u64 {
+        self.unknown_commands.load(Ordering::Relaxed)
+    }
+
+    pub fn record_unacked_write(&self) {
+        self.unacked_writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get_unacked_writes(&self) -> u64 {
+        self.unacked_writes.load(Ordering::Relaxed)
+    }
+
+    pub fn record_slot_command(&self, slot: usize, is_write: bool) {
+        let buckets = if is_write {
+            &self.slot_writes
+        } else {
+            &self.slot_reads
+        };
+        if let Some(counter) = buckets.get(slot / SLOT_LOAD_BUCKET_SIZE) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get_slot_commands(&self, bucket: usize) -> (u64, u64) {
+        let load = |buckets: &[AtomicU64]| {
+            buckets
+                .get(bucket)
+                .map(|counter| counter.load(Ordering::Relaxed))
+                .unwrap_or(0)
+        };
+        (load(&self.slot_reads), load(&self.slot_writes))
+    }
+
+    pub fn get_commands(&self) -> u64 {
+        self.commands.load(Ordering::Relaxed)
+    }
+
+    pub fn get_errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    // The rates are calculated by the broker from the deltas of the counters.
+    pub fn info(
+        &self,
+        memory_tracker: &MemoryTracker,
+        migration_progress: (usize, usize),
+        replication_lag: u64,
+        meta_lock: MetaLockStats,
+    ) -> RespVec {
+        let fields = self.gen_info_fields(
+            memory_tracker,
+            migration_progress,
+            replication_lag,
+            meta_lock,
+        );
+        Resp::Arr(Array::Arr(
+            fields
+                .into_iter()
+                .map(|(k, v)| Resp::Bulk(BulkStr::Str(format!("{}: {}", k, v).into_bytes())))
+                .collect(),
+        ))
+    }
+
+    // Also used by the `stats` section of `INFO`.
+    pub fn gen_info_fields(
+        &self,
+        memory_tracker: &MemoryTracker,
+        migration_progress: (usize, usize),
+        replication_lag: u64,
+        meta_lock: MetaLockStats,
+    ) -> Vec<(String, u64)> {
+        let (migrating_tasks, committed_tasks) = migration_progress;
+        let mut fields = vec![
+            ("commands_processed", self.get_commands()),
+            ("error_replies", self.get_errors()),
+            ("unknown_commands", self.get_unknown_commands()),
+            ("unacked_writes", self.get_unacked_writes()),
+            (
+                "connected_sessions",
+                memory_tracker.get_session_num() as u64,
+            ),
+            ("used_memory_sessions", memory_tracker.get_total() as u64),
+            ("migrating_tasks", migrating_tasks as u64),
+            ("committed_migrating_tasks", committed_tasks as u64),
+            ("replication_lag", replication_lag),
+            ("meta_lock_max_wait_time", meta_lock.max_wait_time),
+            ("meta_lock_max_hold_time", meta_lock.max_hold_time),
+            ("meta_lock_blocked_times", meta_lock.blocked_times),
+            ("slot_load_buckets", SLOT_LOAD_BUCKETS as u64),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect::<Vec<_>>();
+        // Only the buckets with commands are shown.
+        for bucket in 0..SLOT_LOAD_BUCKETS {
+            let (reads, writes) = self.get_slot_commands(bucket);
+            if reads != 0 {
+                fields.push((format!("slot_reads_{}", bucket), reads));
+            }
+            if writes != 0 {
+                fields.push((format!("slot_writes_{}", bucket), writes));
+            }
+        }
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_reply() {
+        let stats = ProxyStats::default();
+        stats.record_reply(false);
+        stats.record_reply(true);
+        stats.record_reply(false);
+        assert_eq!(stats.get_commands(), 3);
+        assert_eq!(stats.get_errors(), 1);
+        stats.record_unknown_command();
+        assert_eq!(stats.get_unknown_commands(), 1);
+        stats.record_unacked_write();
+        assert_eq!(stats.get_unacked_writes(), 1);
+    }
+
+    #[test]
+    fn test_record_slot_command() {
+        let stats = ProxyStats::default();
+        stats.record_slot_command(0, false);
+        stats.record_slot_command(63, true);
+        stats.record_slot_command(64, false);
+        stats.record_slot_command(16383, true);
+        stats.record_slot_command(16384, true);
+        assert_eq!(stats.get_slot_commands(0), (1, 1));
+        assert_eq!(stats.get_slot_commands(1), (1, 0));
+        assert_eq!(stats.get_slot_commands(SLOT_LOAD_BUCKETS - 1), (0, 1));
+        assert_eq!(stats.get_slot_commands(SLOT_LOAD_BUCKETS), (0, 0));
+    }
+}
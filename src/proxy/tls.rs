@@ -0,0 +1,129 @@
+use arc_swap::ArcSwapOption;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_tls::TlsAcceptor;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsIdentityFile {
+    pub pkcs12_file: String,
+    pub password: String,
+}
+
+// The acceptor could be swapped by `UMCTL RELOADTLS` without restarting the proxy.
+// Sessions already established keep using the old certificate.
+pub struct TlsAcceptorHolder {
+    acceptor: ArcSwapOption<TlsAcceptor>,
+    identity_file: Mutex<Option<TlsIdentityFile>>,
+    // The version of the broker. Zero for the files loaded manually.
+    version: AtomicU64,
+}
+
+impl Default for TlsAcceptorHolder {
+    fn default() -> Self {
+        Self {
+            acceptor: ArcSwapOption::new(None),
+            identity_file: Mutex::new(None),
+            version: AtomicU64::new(0),
+        }
+    }
+}
+
+impl fmt::Debug for TlsAcceptorHolder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = self
+            .identity_file
+            .lock()
+            .ok()
+            .and_then(|f| f.as_ref().map(|f| f.pkcs12_file.clone()));
+        write!(f, "TlsAcceptorHolder({:?})", file)
+    }
+}
+
+impl TlsAcceptorHolder {
+    pub fn get_acceptor(&self) -> Option<Arc<TlsAcceptor>> {
+        self.acceptor.load()
+    }
+
+    pub fn get_identity_file(&self) -> Option<TlsIdentityFile> {
+        self.identity_file
+            .lock()
+            .expect("TlsAcceptorHolder::get_identity_file")
+            .clone()
+    }
+
+    pub fn load(&self, identity_file: TlsIdentityFile) -> Result<(), TlsError> {
+        self.load_version(identity_file, 0).map(|_| ())
+    }
+
+    // The coordinator pushes the config on every sync,
+    // so the same version is only loaded once.
+    // Returns whether the file is loaded.
+    pub fn load_version(
+        &self,
+        identity_file: TlsIdentityFile,
+        version: u64,
+    ) -> Result<bool, TlsError> {
+        let mut file_guard = self
+            .identity_file
+            .lock()
+            .expect("TlsAcceptorHolder::load_version");
+        if version != 0
+            && self.version.load(Ordering::SeqCst) == version
+            && file_guard.as_ref() == Some(&identity_file)
+        {
+            return Ok(false);
+        }
+        let acceptor = create_acceptor(&identity_file)?;
+        self.acceptor.store(Some(Arc::new(acceptor)));
+        *file_guard = Some(identity_file);
+        self.version.store(version, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let identity_file = self.get_identity_file().ok_or(TlsError::NotConfigured)?;
+        let version = self.version.load(Ordering::SeqCst);
+        // Forces reading the file again.
+        self.version.store(0, Ordering::SeqCst);
+        self.load_version(identity_file, version).map(|_| ())
+    }
+}
+
+fn create_acceptor(identity_file: &TlsIdentityFile) -> Result<TlsAcceptor, TlsError> {
+    let der = fs::read(&identity_file.pkcs12_file).map_err(TlsError::Io)?;
+    let identity =
+        native_tls::Identity::from_pkcs12(&der, &identity_file.password).map_err(TlsError::Tls)?;
+    let acceptor = native_tls::TlsAcceptor::new(identity).map_err(TlsError::Tls)?;
+    Ok(TlsAcceptor::from(acceptor))
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    NotConfigured,
+    Io(io::Error),
+    Tls(native_tls::Error),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for TlsError {
+    fn description(&self) -> &str {
+        "tls error"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match self {
+            TlsError::Io(err) => Some(err),
+            TlsError::Tls(err) => Some(err),
+            _ => None,
+        }
+    }
+}
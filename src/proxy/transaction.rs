@@ -0,0 +1,253 @@
+use super::acl::get_cmd_keys;
+use super::command::{CmdType, Command};
+use crate::common::response;
+use crate::common::utils::generate_slot;
+use crate::protocol::{Array, BinSafeStr, Resp, RespPacket, RespVec};
+use futures::Future;
+use std::pin::Pin;
+
+// The transactions are buffered by the session and sent to the backend owning the slot
+// on a dedicated connection so that they won't be interleaved with the commands of other sessions.
+// WATCH is not supported since the connection is not kept between the transactions.
+
+pub type TransactionFuture = Pin<Box<dyn Future<Output = RespVec> + Send + 'static>>;
+
+pub enum TransactionAction {
+    Reply(RespVec),
+    Exec(usize, Vec<Command>),
+}
+
+#[derive(Default)]
+struct QueuedCommands {
+    cmds: Vec<Command>,
+    slot: Option<usize>,
+    // Set after any command failed to be queued so that EXEC will be rejected like Redis.
+    aborted: bool,
+}
+
+impl QueuedCommands {
+    fn queue(&mut self, cmd: Command) -> RespVec {
+        match self.check_slot(&cmd) {
+            Ok(slot) => {
+                self.slot = Some(slot);
+                self.cmds.push(cmd);
+                Resp::Simple(response::QUEUED_REPLY.to_string().into_bytes())
+            }
+            Err(err_msg) => {
+                self.aborted = true;
+                Resp::Error(err_msg.to_string().into_bytes())
+            }
+        }
+    }
+
+    fn check_slot(&self, cmd: &Command) -> Result<usize, &'static str> {
+        if cmd.get_type() != CmdType::Others || cmd.get_slot().is_none() {
+            return Err(response::ERR_TRANSACTION_KEY_REQUIRED);
+        }
+        let keys = get_cmd_keys(cmd).map_err(|_| response::ERR_INVALID_COMMAND)?;
+        let mut slots = keys.into_iter().map(generate_slot);
+        let slot = slots.next().ok_or(response::ERR_TRANSACTION_KEY_REQUIRED)?;
+        if slots.any(|s| s != slot) || self.slot.map(|s| s != slot).unwrap_or(false) {
+            return Err(response::ERR_CROSSSLOT);
+        }
+        Ok(slot)
+    }
+
+    fn into_action(self) -> TransactionAction {
+        if self.aborted {
+            return gen_error(response::ERR_EXECABORT);
+        }
+        match self.slot {
+            Some(slot) => TransactionAction::Exec(slot, self.cmds),
+            None => TransactionAction::Reply(Resp::Arr(Array::Arr(vec![]))),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TransactionState {
+    queued: Option<QueuedCommands>,
+}
+
+impl TransactionState {
+    // Should be called with the commands in the order they are received.
+    // Returns the command back when it's not in a transaction.
+    // Otherwise returns the request packet for the reply.
+    pub fn handle_cmd(
+        &mut self,
+        cmd: Command,
+    ) -> Result<(Box<RespPacket>, TransactionAction), Command> {
+        let cmd_type = cmd.get_type();
+        let action = match (cmd_type, self.queued.as_mut()) {
+            (CmdType::Multi, None) => {
+                self.queued = Some(QueuedCommands::default());
+                gen_ok()
+            }
+            (CmdType::Multi, Some(_)) => gen_error(response::ERR_NESTED_MULTI),
+            (CmdType::Exec, _) => match self.queued.take() {
+                Some(queued) => queued.into_action(),
+                None => gen_error(response::ERR_EXEC_WITHOUT_MULTI),
+            },
+            (CmdType::Discard, _) => match self.queued.take() {
+                Some(_) => gen_ok(),
+                None => gen_error(response::ERR_DISCARD_WITHOUT_MULTI),
+            },
+            (CmdType::Watch, None) => gen_error(response::CMD_NOT_SUPPORTED),
+            (CmdType::Watch, Some(_)) => gen_error(response::ERR_WATCH_INSIDE_MULTI),
+            // Nothing is watched.
+            (CmdType::Unwatch, None) => gen_ok(),
+            (CmdType::Reset, _) => {
+                self.queued = None;
+                return Err(cmd);
+            }
+            (_, Some(queued)) => {
+                let request = Box::new(cmd.get_packet());
+                return Ok((request, TransactionAction::Reply(queued.queue(cmd))));
+            }
+            (_, None) => return Err(cmd),
+        };
+        Ok((cmd.into_packet(), action))
+    }
+}
+
+fn gen_ok() -> TransactionAction {
+    TransactionAction::Reply(Resp::Simple(response::OK_REPLY.to_string().into_bytes()))
+}
+
+fn gen_error(err_msg: &str) -> TransactionAction {
+    TransactionAction::Reply(Resp::Error(err_msg.to_string().into_bytes()))
+}
+
+// MULTI, the queued commands and EXEC.
+pub fn gen_transaction_cmds(cmds: &[Command]) -> Vec<Vec<BinSafeStr>> {
+    let mut commands = Vec::with_capacity(cmds.len() + 2);
+    commands.push(vec![b"MULTI".to_vec()]);
+    for cmd in cmds.iter() {
+        let len = cmd.get_command_len().unwrap_or(0);
+        let elements = (0..len)
+            .filter_map(|i| cmd.get_command_element(i))
+            .map(|element| element.to_vec())
+            .collect();
+        commands.push(elements);
+    }
+    commands.push(vec![b"EXEC".to_vec()]);
+    commands
+}
+
+// Only the reply of EXEC is returned to the client.
+pub fn get_exec_reply(mut replies: Vec<RespVec>) -> RespVec {
+    replies
+        .pop()
+        .unwrap_or_else(|| Resp::Error(response::ERR_INTERNAL.to_string().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::BulkStr;
+
+    fn gen_cmd(elements: Vec<&str>) -> Command {
+        let resp = Resp::Arr(Array::Arr(
+            elements
+                .into_iter()
+                .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+                .collect(),
+        ));
+        Command::new(Box::new(RespPacket::from_resp_vec(resp)))
+    }
+
+    fn get_reply(res: Result<(Box<RespPacket>, TransactionAction), Command>) -> RespVec {
+        match res {
+            Ok((_, TransactionAction::Reply(resp))) => resp,
+            _ => panic!("unexpected action"),
+        }
+    }
+
+    fn assert_error(res: Result<(Box<RespPacket>, TransactionAction), Command>, err_msg: &str) {
+        assert_eq!(
+            get_reply(res),
+            Resp::Error(err_msg.to_string().into_bytes())
+        );
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut state = TransactionState::default();
+        assert!(state.handle_cmd(gen_cmd(vec!["GET", "a"])).is_err());
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["EXEC"])),
+            response::ERR_EXEC_WITHOUT_MULTI,
+        );
+
+        get_reply(state.handle_cmd(gen_cmd(vec!["MULTI"])));
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["MULTI"])),
+            response::ERR_NESTED_MULTI,
+        );
+        let queued = Resp::Simple(b"QUEUED".to_vec());
+        assert_eq!(
+            get_reply(state.handle_cmd(gen_cmd(vec!["SET", "{user1}:a", "1"]))),
+            queued
+        );
+        assert_eq!(
+            get_reply(state.handle_cmd(gen_cmd(vec!["MGET", "{user1}:a", "{user1}:b"]))),
+            queued
+        );
+        let (slot, cmds) = match state.handle_cmd(gen_cmd(vec!["EXEC"])) {
+            Ok((_, TransactionAction::Exec(slot, cmds))) => (slot, cmds),
+            _ => panic!("unexpected action"),
+        };
+        assert_eq!(slot, generate_slot(b"user1"));
+        let commands = gen_transaction_cmds(&cmds);
+        assert_eq!(commands.len(), 4);
+        assert_eq!(
+            commands[1],
+            vec![b"SET".to_vec(), b"{user1}:a".to_vec(), b"1".to_vec()]
+        );
+        assert!(state.handle_cmd(gen_cmd(vec!["GET", "a"])).is_err());
+
+        get_reply(state.handle_cmd(gen_cmd(vec!["MULTI"])));
+        assert_eq!(
+            get_reply(state.handle_cmd(gen_cmd(vec!["EXEC"]))),
+            Resp::Arr(Array::Arr(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_aborted_transaction() {
+        let mut state = TransactionState::default();
+        get_reply(state.handle_cmd(gen_cmd(vec!["MULTI"])));
+        get_reply(state.handle_cmd(gen_cmd(vec!["SET", "a", "1"])));
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["SET", "b", "1"])),
+            response::ERR_CROSSSLOT,
+        );
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["EXEC"])),
+            response::ERR_EXECABORT,
+        );
+
+        get_reply(state.handle_cmd(gen_cmd(vec!["MULTI"])));
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["MSET", "a", "1", "b", "2"])),
+            response::ERR_CROSSSLOT,
+        );
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["PING"])),
+            response::ERR_TRANSACTION_KEY_REQUIRED,
+        );
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["WATCH", "a"])),
+            response::ERR_WATCH_INSIDE_MULTI,
+        );
+        get_reply(state.handle_cmd(gen_cmd(vec!["DISCARD"])));
+        assert_error(
+            state.handle_cmd(gen_cmd(vec!["DISCARD"])),
+            response::ERR_DISCARD_WITHOUT_MULTI,
+        );
+
+        get_reply(state.handle_cmd(gen_cmd(vec!["MULTI"])));
+        assert!(state.handle_cmd(gen_cmd(vec!["RESET"])).is_err());
+        assert!(state.handle_cmd(gen_cmd(vec!["GET", "a"])).is_err());
+    }
+}
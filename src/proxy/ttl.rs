@@ -0,0 +1,211 @@
+use super::backend::ConnFactory;
+use super::cluster::ClusterTag;
+use super::command::DataCmdType;
+use super::manager::SharedMetaMap;
+use super::session::CmdCtx;
+use crate::common::config::TtlPolicy;
+use crate::common::utils::byte_to_uppercase;
+use crate::protocol::RespPacket;
+
+#[derive(Debug, PartialEq)]
+pub enum TtlPolicyError {
+    MissingExpiry,
+    InvalidRequest,
+}
+
+pub struct CmdTtlChecker<C: ConnFactory<Pkt = RespPacket>> {
+    meta_map: SharedMetaMap<C>,
+}
+
+impl<C: ConnFactory<Pkt = RespPacket>> CmdTtlChecker<C> {
+    pub fn new(meta_map: SharedMetaMap<C>) -> Self {
+        Self { meta_map }
+    }
+
+    // Could add the default ttl to the command.
+    pub fn check(&self, cmd_ctx: &mut CmdCtx) -> Result<(), TtlPolicyError> {
+        let (policy, default_ttl) = {
+            let meta_map = self.meta_map.lease();
+            match meta_map
+                .get_cluster_map()
+                .get_config(cmd_ctx.get_cluster_name())
+            {
+                Some(config) => (config.ttl_policy, config.default_ttl),
+                None => return Ok(()),
+            }
+        };
+        apply_ttl_policy(policy, default_ttl, cmd_ctx)
+    }
+}
+
+pub fn apply_ttl_policy(
+    policy: TtlPolicy,
+    default_ttl: u64,
+    cmd_ctx: &mut CmdCtx,
+) -> Result<(), TtlPolicyError> {
+    if policy == TtlPolicy::Disabled {
+        return Ok(());
+    }
+
+    match cmd_ctx.get_data_cmd_type() {
+        DataCmdType::SET => (),
+        // There's no way to set expiry in these commands.
+        DataCmdType::SETNX | DataCmdType::GETSET | DataCmdType::MSET | DataCmdType::MSETNX => {
+            return Err(TtlPolicyError::MissingExpiry)
+        }
+        // SETRANGE, APPEND and the INCR family mostly update the existing keys and keep their TTLs.
+        // They are not covered so that the counters could still be used.
+        _ => return Ok(()),
+    }
+
+    if set_keeps_ttl(cmd_ctx)? {
+        // The default TTL can't be added to KEEPTTL,
+        // which doesn't set any TTL when the key is created.
+        return Err(TtlPolicyError::MissingExpiry);
+    }
+    if set_has_expiry(cmd_ctx)? {
+        return Ok(());
+    }
+    if policy == TtlPolicy::Reject || default_ttl == 0 {
+        return Err(TtlPolicyError::MissingExpiry);
+    }
+    let following_elements = vec![b"EX".to_vec(), default_ttl.to_string().into_bytes()];
+    if !cmd_ctx.append_cmd(following_elements) {
+        return Err(TtlPolicyError::InvalidRequest);
+    }
+    Ok(())
+}
+
+// SET key value [EX seconds|PX milliseconds|EXAT timestamp|PXAT timestamp|KEEPTTL] [NX|XX] [GET]
+fn set_has_expiry(cmd_ctx: &CmdCtx) -> Result<bool, TtlPolicyError> {
+    set_has_option(cmd_ctx, |option| {
+        matches!(option, b"EX" | b"PX" | b"EXAT" | b"PXAT")
+    })
+}
+
+fn set_keeps_ttl(cmd_ctx: &CmdCtx) -> Result<bool, TtlPolicyError> {
+    set_has_option(cmd_ctx, |option| option == b"KEEPTTL")
+}
+
+fn set_has_option<F>(cmd_ctx: &CmdCtx, is_option: F) -> Result<bool, TtlPolicyError>
+where
+    F: Fn(&[u8]) -> bool,
+{
+    let cmd = cmd_ctx.get_cmd();
+    let len = cmd
+        .get_command_len()
+        .ok_or(TtlPolicyError::InvalidRequest)?;
+    for i in 3..len {
+        let option = cmd
+            .get_command_element(i)
+            .ok_or(TtlPolicyError::InvalidRequest)?;
+        let option: Vec<u8> = option.iter().map(|b| byte_to_uppercase(*b)).collect();
+        if is_option(&option) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::ClusterName;
+    use crate::protocol::{Array, BulkStr, Resp};
+    use crate::proxy::command::{new_command_pair, Command};
+    use std::convert::TryFrom;
+
+    fn gen_cmd_ctx(cmd: Vec<&str>) -> CmdCtx {
+        let arr = cmd
+            .into_iter()
+            .map(|s| Resp::Bulk(BulkStr::Str(s.as_bytes().to_vec())))
+            .collect();
+        let packet = RespPacket::Data(Resp::Arr(Array::Arr(arr)));
+        let cmd = Command::new(Box::new(packet));
+        let (reply_sender, _reply_receiver) = new_command_pair(&cmd);
+        let cluster_name = ClusterName::try_from("mycluster").unwrap();
+        CmdCtx::new(cluster_name, cmd, reply_sender, 0, false)
+    }
+
+    #[test]
+    fn test_ttl_policy_disabled() {
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SET", "key", "value"]);
+        assert!(apply_ttl_policy(TtlPolicy::Disabled, 0, &mut cmd_ctx).is_ok());
+        let mut cmd_ctx = gen_cmd_ctx(vec!["MSET", "key", "value"]);
+        assert!(apply_ttl_policy(TtlPolicy::Disabled, 0, &mut cmd_ctx).is_ok());
+    }
+
+    #[test]
+    fn test_ttl_policy_reject() {
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SET", "key", "value"]);
+        assert_eq!(
+            apply_ttl_policy(TtlPolicy::Reject, 60, &mut cmd_ctx),
+            Err(TtlPolicyError::MissingExpiry)
+        );
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SET", "key", "value", "nx", "ex", "10"]);
+        assert!(apply_ttl_policy(TtlPolicy::Reject, 60, &mut cmd_ctx).is_ok());
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SETEX", "key", "10", "value"]);
+        assert!(apply_ttl_policy(TtlPolicy::Reject, 60, &mut cmd_ctx).is_ok());
+        let mut cmd_ctx = gen_cmd_ctx(vec!["GET", "key"]);
+        assert!(apply_ttl_policy(TtlPolicy::Reject, 60, &mut cmd_ctx).is_ok());
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SETNX", "key", "value"]);
+        assert_eq!(
+            apply_ttl_policy(TtlPolicy::Reject, 60, &mut cmd_ctx),
+            Err(TtlPolicyError::MissingExpiry)
+        );
+    }
+
+    #[test]
+    fn test_ttl_policy_auto() {
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SET", "key", "value", "NX"]);
+        assert!(apply_ttl_policy(TtlPolicy::Auto, 60, &mut cmd_ctx).is_ok());
+        let cmd = cmd_ctx.get_cmd();
+        assert_eq!(cmd.get_command_len(), Some(6));
+        assert_eq!(cmd.get_command_element(4), Some(&b"EX"[..]));
+        assert_eq!(cmd.get_command_element(5), Some(&b"60"[..]));
+
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SET", "key", "value", "PX", "100"]);
+        assert!(apply_ttl_policy(TtlPolicy::Auto, 60, &mut cmd_ctx).is_ok());
+        assert_eq!(cmd_ctx.get_cmd().get_command_len(), Some(5));
+
+        let mut cmd_ctx = gen_cmd_ctx(vec!["SET", "key", "value"]);
+        assert_eq!(
+            apply_ttl_policy(TtlPolicy::Auto, 0, &mut cmd_ctx),
+            Err(TtlPolicyError::MissingExpiry)
+        );
+        let mut cmd_ctx = gen_cmd_ctx(vec!["MSET", "key", "value"]);
+        assert_eq!(
+            apply_ttl_policy(TtlPolicy::Auto, 60, &mut cmd_ctx),
+            Err(TtlPolicyError::MissingExpiry)
+        );
+    }
+
+    #[test]
+    fn test_ttl_policy_keepttl() {
+        for policy in vec![TtlPolicy::Reject, TtlPolicy::Auto].into_iter() {
+            let mut cmd_ctx = gen_cmd_ctx(vec!["SET", "key", "value", "keepttl"]);
+            assert_eq!(
+                apply_ttl_policy(policy, 60, &mut cmd_ctx),
+                Err(TtlPolicyError::MissingExpiry)
+            );
+            assert_eq!(cmd_ctx.get_cmd().get_command_len(), Some(4));
+        }
+    }
+
+    #[test]
+    fn test_ttl_policy_not_covered() {
+        let cmds = vec![
+            vec!["SETRANGE", "key", "0", "value"],
+            vec!["APPEND", "key", "value"],
+            vec!["INCR", "key"],
+            vec!["INCRBY", "key", "1"],
+        ];
+        for cmd in cmds.into_iter() {
+            let mut cmd_ctx = gen_cmd_ctx(cmd.clone());
+            assert!(apply_ttl_policy(TtlPolicy::Reject, 60, &mut cmd_ctx).is_ok());
+            let mut cmd_ctx = gen_cmd_ctx(cmd.clone());
+            assert!(apply_ttl_policy(TtlPolicy::Auto, 60, &mut cmd_ctx).is_ok());
+            assert_eq!(cmd_ctx.get_cmd().get_command_len(), Some(cmd.len()));
+        }
+    }
+}
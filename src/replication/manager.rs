@@ -3,12 +3,13 @@ use super::replicator::{
     MasterMeta, MasterReplicator, ReplicaMeta, ReplicaReplicator, ReplicatorMeta,
 };
 use crate::common::cluster::ClusterName;
+use crate::common::config::BackendFlavor;
 use crate::common::future_group::{new_auto_drop_future, FutureAutoStopHandle};
 use crate::common::track::TrackedFutureRegistry;
 use crate::protocol::{Array, BulkStr, RedisClientFactory, Resp, RespVec};
 use crate::proxy::cluster::ClusterMetaError;
 use itertools::Either;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{atomic, Arc, RwLock};
 
 type ReplicatorRecord = Either<Arc<dyn MasterReplicator>, Arc<dyn ReplicaReplicator>>;
@@ -31,12 +32,22 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
         }
     }
 
-    pub fn update_replicators(&self, meta: ReplicatorMeta) -> Result<(), ClusterMetaError> {
+    // The replicators are recreated after the backend flavor of their cluster is changed.
+    pub fn update_replicators<G>(
+        &self,
+        meta: ReplicatorMeta,
+        get_flavor: G,
+    ) -> Result<(), ClusterMetaError>
+    where
+        G: Fn(&ClusterName) -> BackendFlavor,
+    {
         let ReplicatorMeta {
             epoch,
             flags,
+            diff,
             masters,
             replicas,
+            removed,
         } = meta;
 
         let force = flags.force;
@@ -52,6 +63,20 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
         // After this, other threads might accidentally change `updating_epoch` to a lower epoch,
         // we will correct his later.
 
+        // The unchanged replicators will be reused below.
+        let (masters, replicas) = if diff {
+            let (current_masters, current_replicas) = self.get_metadata();
+            merge_repl_diff(
+                current_masters,
+                current_replicas,
+                masters,
+                replicas,
+                removed,
+            )
+        } else {
+            (masters, replicas)
+        };
+
         let mut master_key_set = HashMap::new();
         let mut replica_key_set = HashMap::new();
         for meta in masters.iter() {
@@ -76,18 +101,25 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
             .1
             .iter()
         {
+            let flavor = get_flavor(&key.0);
             if Some(true)
-                == master_key_set
-                    .get(key)
-                    .and_then(|meta| replicator.as_ref().left().map(|m| m.get_meta() == meta))
+                == master_key_set.get(key).and_then(|meta| {
+                    replicator
+                        .as_ref()
+                        .left()
+                        .map(|m| m.get_meta() == meta && m.get_flavor() == flavor)
+                })
             {
                 info!("reuse master replicator {} {}", key.0, key.1);
                 new_replicators.insert(key.clone(), (replicator.clone(), handle.clone()));
             }
             if Some(true)
-                == replica_key_set
-                    .get(key)
-                    .and_then(|meta| replicator.as_ref().right().map(|m| m.get_meta() == meta))
+                == replica_key_set.get(key).and_then(|meta| {
+                    replicator
+                        .as_ref()
+                        .right()
+                        .map(|m| m.get_meta() == meta && m.get_flavor() == flavor)
+                })
             {
                 info!("reuse replica replicator {} {}", key.0, key.1);
                 new_replicators.insert(key.clone(), (replicator.clone(), handle.clone()));
@@ -103,8 +135,10 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
             if new_replicators.contains_key(&key) {
                 continue;
             }
+            let flavor = get_flavor(&meta.cluster_name);
             let replicator = Arc::new(RedisMasterReplicator::new(
                 meta,
+                flavor,
                 self.client_factory.clone(),
             ));
             new_masters.insert(key.clone(), replicator.clone());
@@ -115,8 +149,10 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
             if new_replicators.contains_key(&key) {
                 continue;
             }
+            let flavor = get_flavor(&meta.cluster_name);
             let replicator = Arc::new(RedisReplicaReplicator::new(
                 meta,
+                flavor,
                 self.client_factory.clone(),
             ));
             new_replicas.insert(key.clone(), replicator.clone());
@@ -213,8 +249,45 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
         (master_metadata, replica_metadata)
     }
 
+    // master node address => replicator
+    pub fn get_master_replicators(&self) -> HashMap<String, Arc<dyn MasterReplicator>> {
+        let replicators = self
+            .replicators
+            .read()
+            .expect("ReplicatorManager::get_master_replicators");
+        replicators
+            .1
+            .iter()
+            .filter_map(|((_, address), (replicator, _handle))| {
+                replicator
+                    .as_ref()
+                    .left()
+                    .map(|master| (address.clone(), master.clone()))
+            })
+            .collect()
+    }
+
+    // replica node address => whether the initial sync has finished
+    fn get_replica_sync_states(&self) -> HashMap<String, bool> {
+        let replicators = self
+            .replicators
+            .read()
+            .expect("ReplicatorManager::get_replica_sync_states");
+        replicators
+            .1
+            .iter()
+            .filter_map(|((_, address), (replicator, _handle))| {
+                replicator
+                    .as_ref()
+                    .right()
+                    .map(|replica| (address.clone(), replica.is_synced()))
+            })
+            .collect()
+    }
+
     pub fn get_metadata_report(&self) -> RespVec {
         let (master_metadata, replica_metadata) = self.get_metadata();
+        let sync_states = self.get_replica_sync_states();
 
         let mut reports = vec![];
 
@@ -254,6 +327,11 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
             replica_meta.push(format!("cluster:{}\n", cluster_name));
             replica_meta.push("role:replica\n".to_string());
             replica_meta.push(format!("node_address:{}\n", replica_node_address));
+            let synced = sync_states
+                .get(&replica_node_address)
+                .cloned()
+                .unwrap_or(false);
+            replica_meta.push(format!("synced:{}\n", synced));
             for master in masters.into_iter() {
                 replica_meta.push(format!(
                     "master:{}@{}\n",
@@ -271,4 +349,132 @@ impl<F: RedisClientFactory> ReplicatorManager<F> {
 
         Resp::Arr(Array::Arr(reports))
     }
+
+    // The `replication` section of `INFO`.
+    pub fn gen_info_lines(&self) -> Vec<String> {
+        let (mut master_metadata, mut replica_metadata) = self.get_metadata();
+        let sync_states = self.get_replica_sync_states();
+        master_metadata.sort_by(|a, b| a.master_node_address.cmp(&b.master_node_address));
+        replica_metadata.sort_by(|a, b| a.replica_node_address.cmp(&b.replica_node_address));
+
+        let mut lines = vec![
+            format!("master_replicators:{}", master_metadata.len()),
+            format!("replica_replicators:{}", replica_metadata.len()),
+        ];
+        for (i, meta) in master_metadata.into_iter().enumerate() {
+            lines.push(format!(
+                "master_replicator_{}:cluster={},address={},replicas={}",
+                i,
+                meta.cluster_name,
+                meta.master_node_address,
+                meta.replicas.len(),
+            ));
+        }
+        for (i, meta) in replica_metadata.into_iter().enumerate() {
+            let synced = sync_states
+                .get(&meta.replica_node_address)
+                .cloned()
+                .unwrap_or(false);
+            lines.push(format!(
+                "replica_replicator_{}:cluster={},address={},masters={},synced={}",
+                i,
+                meta.cluster_name,
+                meta.replica_node_address,
+                meta.masters.len(),
+                synced,
+            ));
+        }
+        lines
+    }
+}
+
+// Applies the added, changed, and removed replicators to the current metadata.
+// A node could only be either a master or a replica in a cluster.
+fn merge_repl_diff(
+    current_masters: Vec<MasterMeta>,
+    current_replicas: Vec<ReplicaMeta>,
+    masters: Vec<MasterMeta>,
+    replicas: Vec<ReplicaMeta>,
+    removed: Vec<(ClusterName, String)>,
+) -> (Vec<MasterMeta>, Vec<ReplicaMeta>) {
+    let mut changed: HashSet<(ClusterName, String)> = removed.into_iter().collect();
+    for meta in masters.iter() {
+        changed.insert((meta.cluster_name.clone(), meta.master_node_address.clone()));
+    }
+    for meta in replicas.iter() {
+        changed.insert((meta.cluster_name.clone(), meta.replica_node_address.clone()));
+    }
+
+    let new_masters = current_masters
+        .into_iter()
+        .filter(|meta| {
+            !changed.contains(&(meta.cluster_name.clone(), meta.master_node_address.clone()))
+        })
+        .chain(masters)
+        .collect();
+    let new_replicas = current_replicas
+        .into_iter()
+        .filter(|meta| {
+            !changed.contains(&(meta.cluster_name.clone(), meta.replica_node_address.clone()))
+        })
+        .chain(replicas)
+        .collect();
+    (new_masters, new_replicas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::cluster::ReplPeer;
+    use std::convert::TryFrom;
+
+    fn gen_master(node_address: &str, replica_address: &str) -> MasterMeta {
+        MasterMeta {
+            cluster_name: ClusterName::try_from("testcluster").unwrap(),
+            master_node_address: node_address.to_string(),
+            replicas: vec![ReplPeer {
+                node_address: replica_address.to_string(),
+                proxy_address: "127.0.0.1:5299".to_string(),
+            }],
+        }
+    }
+
+    fn gen_replica(node_address: &str, master_address: &str) -> ReplicaMeta {
+        ReplicaMeta {
+            cluster_name: ClusterName::try_from("testcluster").unwrap(),
+            replica_node_address: node_address.to_string(),
+            masters: vec![ReplPeer {
+                node_address: master_address.to_string(),
+                proxy_address: "127.0.0.1:5299".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_merge_repl_diff() {
+        let current_masters = vec![gen_master("m1", "r1"), gen_master("m2", "r2")];
+        let current_replicas = vec![gen_replica("r3", "m3"), gen_replica("r4", "m4")];
+
+        let (masters, replicas) = merge_repl_diff(
+            current_masters,
+            current_replicas,
+            // Change m2 and promote r4.
+            vec![gen_master("m2", "r5"), gen_master("r4", "r6")],
+            // Add r7.
+            vec![gen_replica("r7", "m7")],
+            vec![(
+                ClusterName::try_from("testcluster").unwrap(),
+                "r3".to_string(),
+            )],
+        );
+        assert_eq!(
+            masters,
+            vec![
+                gen_master("m1", "r1"),
+                gen_master("m2", "r5"),
+                gen_master("r4", "r6")
+            ]
+        );
+        assert_eq!(replicas, vec![gen_replica("r7", "m7")]);
+    }
 }
@@ -1,42 +1,93 @@
 use super::replicator::{
     MasterMeta, MasterReplicator, ReplicaMeta, ReplicaReplicator, ReplicatorError, ReplicatorResult,
 };
+use crate::common::config::BackendFlavor;
 use crate::common::resp_execution::{retry_handle_func, I64Retriever};
 use crate::common::utils::resolve_first_address;
-use crate::protocol::{OptionalMulti, RedisClientError, RedisClientFactory, RespVec};
+use crate::protocol::{
+    BulkStr, OptionalMulti, RedisClientError, RedisClientFactory, Resp, RespVec,
+};
 use futures::{future, Future};
 use futures::{FutureExt, TryFutureExt};
 use std::pin::Pin;
+use std::str;
 use std::sync::atomic::AtomicI64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
+const UNKNOWN_LAG: i64 = -1;
+
+// Dragonfly is managed by `REPLICAOF` instead of `SLAVEOF`.
+fn gen_replicaof_cmd(flavor: BackendFlavor, host: String, port: String) -> Vec<String> {
+    let cmd_name = match flavor {
+        BackendFlavor::Redis => "SLAVEOF",
+        BackendFlavor::Dragonfly => "REPLICAOF",
+    };
+    vec![cmd_name.to_string(), host, port]
+}
+
+fn gen_no_one_cmd(flavor: BackendFlavor) -> Vec<String> {
+    gen_replicaof_cmd(flavor, "NO".to_string(), "ONE".to_string())
+}
+
 pub struct RedisMasterReplicator<F: RedisClientFactory> {
     meta: MasterMeta,
+    flavor: BackendFlavor,
     role_sync: I64Retriever<F>,
+    lag_sync: I64Retriever<F>,
 }
 
 impl<F: RedisClientFactory> RedisMasterReplicator<F> {
-    pub fn new(meta: MasterMeta, client_factory: Arc<F>) -> Self {
+    pub fn new(meta: MasterMeta, flavor: BackendFlavor, client_factory: Arc<F>) -> Self {
         let address = meta.master_node_address.clone();
         let interval = Duration::new(5, 0);
-        let cmd = vec!["SLAVEOF".to_string(), "NO".to_string(), "ONE".to_string()];
+        let cmd = gen_no_one_cmd(flavor);
+        let lag_interval = Duration::new(1, 0);
+        let lag_cmd = vec!["INFO".to_string(), "REPLICATION".to_string()];
 
         Self {
             meta,
-            role_sync: I64Retriever::new(0, client_factory, address, cmd, interval),
+            flavor,
+            role_sync: I64Retriever::new(0, client_factory.clone(), address.clone(), cmd, interval),
+            lag_sync: I64Retriever::new(
+                UNKNOWN_LAG,
+                client_factory,
+                address,
+                lag_cmd,
+                lag_interval,
+            ),
         }
     }
 
     fn send_stop_signal(&self) -> Result<(), ReplicatorError> {
-        if self.role_sync.stop() {
+        let lag_stopped = self.lag_sync.stop();
+        if self.role_sync.stop() && lag_stopped {
             Ok(())
         } else {
             Err(ReplicatorError::AlreadyEnded)
         }
     }
 
+    fn handle_lag_result(
+        flavor: BackendFlavor,
+        resp: RespVec,
+        data: &Arc<AtomicI64>,
+    ) -> Result<(), RedisClientError> {
+        let lag = match resp {
+            Resp::Bulk(BulkStr::Str(s)) => str::from_utf8(&s)
+                .ok()
+                .and_then(|info| parse_replication_lag(flavor, info))
+                .map(|lag| lag as i64),
+            other => {
+                error!("invalid INFO REPLICATION reply: {:?}", other);
+                None
+            }
+        };
+        data.store(lag.unwrap_or(UNKNOWN_LAG), Ordering::SeqCst);
+        Ok(())
+    }
+
     pub fn already_master(&self) -> bool {
         self.role_sync.get_data() != 0
     }
@@ -53,14 +104,18 @@ impl<F: RedisClientFactory> RedisMasterReplicator<F> {
 impl<F: RedisClientFactory> MasterReplicator for RedisMasterReplicator<F> {
     fn start<'s>(&'s self) -> Option<Pin<Box<dyn Future<Output = ReplicatorResult> + Send + 's>>> {
         let meta = self.meta.clone();
-        self.role_sync.start(Self::handle_result).map(|f| {
-            let fut: Pin<Box<dyn Future<Output = Result<(), ReplicatorError>> + Send + 's>> =
-                Box::pin(f.map_err(ReplicatorError::RedisError).then(move |r| {
-                    warn!("RedisMasterReplicator {:?} stopped {:?}", meta, r);
-                    future::ok(())
-                }));
-            fut
-        })
+        let flavor = self.flavor;
+        let role_fut = self.role_sync.start(Self::handle_result)?;
+        let lag_fut = self
+            .lag_sync
+            .start(move |resp, data| Self::handle_lag_result(flavor, resp, data))?;
+        let f = future::try_join(role_fut, lag_fut).map_ok(|_| ());
+        let fut: Pin<Box<dyn Future<Output = Result<(), ReplicatorError>> + Send + 's>> =
+            Box::pin(f.map_err(ReplicatorError::RedisError).then(move |r| {
+                warn!("RedisMasterReplicator {:?} stopped {:?}", meta, r);
+                future::ok(())
+            }));
+        Some(fut)
     }
 
     fn stop(&self) -> Result<(), ReplicatorError> {
@@ -70,36 +125,92 @@ impl<F: RedisClientFactory> MasterReplicator for RedisMasterReplicator<F> {
     fn get_meta(&self) -> &MasterMeta {
         &self.meta
     }
+
+    fn get_flavor(&self) -> BackendFlavor {
+        self.flavor
+    }
+
+    fn get_replication_lag(&self) -> Option<u64> {
+        let lag = self.lag_sync.get_data();
+        if lag < 0 {
+            None
+        } else {
+            Some(lag as u64)
+        }
+    }
+}
+
+// Parses the `slave<n>:ip=<ip>,port=<port>,state=online,offset=<offset>,lag=<lag>` lines.
+// The online replicas of Dragonfly are in the `stable_sync` state.
+fn parse_replication_lag(flavor: BackendFlavor, info: &str) -> Option<u64> {
+    let online_state = match flavor {
+        BackendFlavor::Redis => "online",
+        BackendFlavor::Dragonfly => "stable_sync",
+    };
+    let mut max_lag = None;
+    for line in info.lines() {
+        let mut kv = line.trim().splitn(2, ':');
+        let (key, fields) = match (kv.next(), kv.next()) {
+            (Some(key), Some(fields)) => (key, fields),
+            _ => continue,
+        };
+        // Skip the other fields such as `slave_repl_offset`.
+        match key.strip_prefix("slave") {
+            Some(n) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => (),
+            _ => continue,
+        }
+        let mut online = false;
+        let mut lag = None;
+        for field in fields.split(',') {
+            let mut kv = field.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some("state"), Some(state)) => online = state == online_state,
+                (Some("lag"), Some(l)) => lag = l.parse::<u64>().ok(),
+                _ => (),
+            }
+        }
+        if !online {
+            return None;
+        }
+        max_lag = Some(std::cmp::max(max_lag.unwrap_or(0), lag?));
+    }
+    max_lag
 }
 
 pub struct RedisReplicaReplicator<F: RedisClientFactory> {
     meta: ReplicaMeta,
+    flavor: BackendFlavor,
     role_sync: I64Retriever<F>,
+    sync_state: I64Retriever<F>,
 }
 
 impl<F: RedisClientFactory> RedisReplicaReplicator<F> {
-    pub fn new(meta: ReplicaMeta, client_factory: Arc<F>) -> Self {
+    pub fn new(meta: ReplicaMeta, flavor: BackendFlavor, client_factory: Arc<F>) -> Self {
         // Just get the first one.
-        let cmd = match Self::gen_cmd(&meta) {
+        let cmd = match Self::gen_cmd(&meta, flavor) {
             Ok(cmd) => cmd,
             Err(err) => {
                 error!(
                     "FATAL ERROR: invalid meta {:?}, will see it as master.",
                     err
                 );
-                vec!["SLAVEOF".to_string(), "NO".to_string(), "ONE".to_string()]
+                gen_no_one_cmd(flavor)
             }
         };
         let address = meta.replica_node_address.clone();
         let interval = Duration::new(5, 0);
+        let sync_interval = Duration::new(1, 0);
+        let sync_cmd = vec!["INFO".to_string(), "REPLICATION".to_string()];
 
         Self {
             meta,
-            role_sync: I64Retriever::new(0, client_factory, address, cmd, interval),
+            flavor,
+            role_sync: I64Retriever::new(0, client_factory.clone(), address.clone(), cmd, interval),
+            sync_state: I64Retriever::new(0, client_factory, address, sync_cmd, sync_interval),
         }
     }
 
-    fn gen_cmd(meta: &ReplicaMeta) -> Result<Vec<String>, ReplicatorError> {
+    fn gen_cmd(meta: &ReplicaMeta, flavor: BackendFlavor) -> Result<Vec<String>, ReplicatorError> {
         let master_node_address = match meta.masters.get(0) {
             Some(repl_meta) => &repl_meta.node_address,
             None => {
@@ -112,14 +223,15 @@ impl<F: RedisClientFactory> RedisReplicaReplicator<F> {
             Some(address) => {
                 let host = address.ip().to_string();
                 let port = address.port().to_string();
-                Ok(vec!["SLAVEOF".to_string(), host, port])
+                Ok(gen_replicaof_cmd(flavor, host, port))
             }
             None => Err(ReplicatorError::InvalidAddress),
         }
     }
 
     fn send_stop_signal(&self) -> Result<(), ReplicatorError> {
-        if self.role_sync.stop() {
+        let sync_stopped = self.sync_state.stop();
+        if self.role_sync.stop() && sync_stopped {
             Ok(())
         } else {
             Err(ReplicatorError::AlreadyEnded)
@@ -129,19 +241,57 @@ impl<F: RedisClientFactory> RedisReplicaReplicator<F> {
     fn handle_result(resp: RespVec, _data: &Arc<AtomicI64>) -> Result<(), RedisClientError> {
         retry_handle_func(OptionalMulti::Single(resp))
     }
+
+    fn handle_sync_result(
+        flavor: BackendFlavor,
+        resp: RespVec,
+        data: &Arc<AtomicI64>,
+    ) -> Result<(), RedisClientError> {
+        let synced = match resp {
+            Resp::Bulk(BulkStr::Str(s)) => str::from_utf8(&s)
+                .map(|info| parse_replica_synced(flavor, info))
+                .unwrap_or(false),
+            other => {
+                error!("invalid INFO REPLICATION reply: {:?}", other);
+                false
+            }
+        };
+        data.store(synced as i64, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+// The initial sync finishes when the link is up and no sync is in progress.
+// Dragonfly does not report `master_sync_in_progress` after the full sync.
+fn parse_replica_synced(flavor: BackendFlavor, info: &str) -> bool {
+    let mut link_up = false;
+    let mut sync_in_progress = flavor == BackendFlavor::Redis;
+    for line in info.lines() {
+        let mut kv = line.trim().splitn(2, ':');
+        match (kv.next(), kv.next()) {
+            (Some("master_link_status"), Some(status)) => link_up = status == "up",
+            (Some("master_sync_in_progress"), Some(n)) => sync_in_progress = n != "0",
+            _ => (),
+        }
+    }
+    link_up && !sync_in_progress
 }
 
 impl<F: RedisClientFactory> ReplicaReplicator for RedisReplicaReplicator<F> {
     fn start<'s>(&'s self) -> Option<Pin<Box<dyn Future<Output = ReplicatorResult> + Send + 's>>> {
         let meta = self.meta.clone();
-        self.role_sync.start(Self::handle_result).map(|f| {
-            let fut: Pin<Box<dyn Future<Output = Result<(), ReplicatorError>> + Send + 's>> =
-                Box::pin(f.map_err(ReplicatorError::RedisError).then(move |r| {
-                    warn!("RedisReplicaReplicator {:?} stopped {:?}", meta, r);
-                    future::ok(())
-                }));
-            fut
-        })
+        let flavor = self.flavor;
+        let role_fut = self.role_sync.start(Self::handle_result)?;
+        let sync_fut = self
+            .sync_state
+            .start(move |resp, data| Self::handle_sync_result(flavor, resp, data))?;
+        let f = future::try_join(role_fut, sync_fut).map_ok(|_| ());
+        let fut: Pin<Box<dyn Future<Output = Result<(), ReplicatorError>> + Send + 's>> =
+            Box::pin(f.map_err(ReplicatorError::RedisError).then(move |r| {
+                warn!("RedisReplicaReplicator {:?} stopped {:?}", meta, r);
+                future::ok(())
+            }));
+        Some(fut)
     }
 
     fn stop(&self) -> Result<(), ReplicatorError> {
@@ -151,4 +301,86 @@ impl<F: RedisClientFactory> ReplicaReplicator for RedisReplicaReplicator<F> {
     fn get_meta(&self) -> &ReplicaMeta {
         &self.meta
     }
+
+    fn get_flavor(&self) -> BackendFlavor {
+        self.flavor
+    }
+
+    fn is_synced(&self) -> bool {
+        self.sync_state.get_data() != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_replication_lag() {
+        let info = "# Replication\r\nrole:master\r\nconnected_slaves:2\r\n\
+            slave0:ip=127.0.0.1,port=6380,state=online,offset=233,lag=1\r\n\
+            slave1:ip=127.0.0.1,port=6381,state=online,offset=233,lag=3\r\n\
+            master_repl_offset:233\r\n";
+        assert_eq!(parse_replication_lag(BackendFlavor::Redis, info), Some(3));
+
+        let info = "role:master\r\nconnected_slaves:0\r\nslave_read_repl_offset:0\r\n";
+        assert_eq!(parse_replication_lag(BackendFlavor::Redis, info), None);
+        let info = "slave0:ip=127.0.0.1,port=6380,state=wait_bgsave,offset=0,lag=0\r\n";
+        assert_eq!(parse_replication_lag(BackendFlavor::Redis, info), None);
+        let info = "slave0:ip=127.0.0.1,port=6380,state=online,offset=0\r\n";
+        assert_eq!(parse_replication_lag(BackendFlavor::Redis, info), None);
+    }
+
+    #[test]
+    fn test_parse_replica_synced() {
+        let info = "# Replication\r\nrole:slave\r\nmaster_host:127.0.0.1\r\n\
+            master_link_status:up\r\nmaster_sync_in_progress:0\r\n";
+        assert!(parse_replica_synced(BackendFlavor::Redis, info));
+
+        let info = "role:slave\r\nmaster_link_status:down\r\nmaster_sync_in_progress:1\r\n";
+        assert!(!parse_replica_synced(BackendFlavor::Redis, info));
+        let info = "role:slave\r\nmaster_link_status:up\r\nmaster_sync_in_progress:1\r\n";
+        assert!(!parse_replica_synced(BackendFlavor::Redis, info));
+        let info = "role:master\r\nconnected_slaves:0\r\n";
+        assert!(!parse_replica_synced(BackendFlavor::Redis, info));
+    }
+
+    #[test]
+    fn test_dragonfly_replication_info() {
+        let info = "# Replication\r\nrole:master\r\nconnected_slaves:2\r\n\
+            slave0:ip=127.0.0.1,port=6380,state=stable_sync,lag=0\r\n\
+            slave1:ip=127.0.0.1,port=6381,state=stable_sync,lag=2\r\n";
+        assert_eq!(
+            parse_replication_lag(BackendFlavor::Dragonfly, info),
+            Some(2)
+        );
+        assert_eq!(parse_replication_lag(BackendFlavor::Redis, info), None);
+        let info = "slave0:ip=127.0.0.1,port=6380,state=full_sync,lag=0\r\n";
+        assert_eq!(parse_replication_lag(BackendFlavor::Dragonfly, info), None);
+
+        let info = "# Replication\r\nrole:replica\r\nmaster_host:127.0.0.1\r\n\
+            master_port:6379\r\nmaster_link_status:up\r\n";
+        assert!(parse_replica_synced(BackendFlavor::Dragonfly, info));
+        assert!(!parse_replica_synced(BackendFlavor::Redis, info));
+        let info = "role:replica\r\nmaster_link_status:up\r\nmaster_sync_in_progress:1\r\n";
+        assert!(!parse_replica_synced(BackendFlavor::Dragonfly, info));
+        let info = "role:replica\r\nmaster_link_status:down\r\n";
+        assert!(!parse_replica_synced(BackendFlavor::Dragonfly, info));
+    }
+
+    #[test]
+    fn test_gen_replicaof_cmd() {
+        assert_eq!(
+            gen_no_one_cmd(BackendFlavor::Redis),
+            vec!["SLAVEOF", "NO", "ONE"]
+        );
+        assert_eq!(
+            gen_replicaof_cmd(
+                BackendFlavor::Dragonfly,
+                "127.0.0.1".to_string(),
+                "6379".to_string()
+            ),
+            vec!["REPLICAOF", "127.0.0.1", "6379"]
+        );
+    }
 }
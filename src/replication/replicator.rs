@@ -1,12 +1,14 @@
 use crate::common::cluster::{ClusterName, ReplPeer};
+use crate::common::config::BackendFlavor;
 use crate::common::proto::ClusterMapFlags;
-use crate::common::utils::{CmdParseError, ThreadSafe};
-use crate::protocol::{Array, BulkStr, RedisClientError, Resp};
+use crate::common::utils::{has_flags, parse_args, resp_array_to_args, CmdParseError, ThreadSafe};
+use crate::protocol::{Array, RedisClientError, Resp};
 use futures::Future;
 use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
 use std::io;
+use std::iter::Peekable;
 use std::pin::Pin;
 use std::str;
 
@@ -18,20 +20,32 @@ pub trait MasterReplicator: ThreadSafe {
     fn start<'s>(&'s self) -> Option<Pin<Box<dyn Future<Output = ReplicatorResult> + Send + 's>>>;
     fn stop(&self) -> Result<(), ReplicatorError>;
     fn get_meta(&self) -> &MasterMeta;
+    fn get_flavor(&self) -> BackendFlavor;
+    // The largest lag of the replicas in seconds.
+    // None if it's unknown or any replica is not online.
+    fn get_replication_lag(&self) -> Option<u64>;
 }
 
 pub trait ReplicaReplicator: ThreadSafe {
     fn start<'s>(&'s self) -> Option<Pin<Box<dyn Future<Output = ReplicatorResult> + Send + 's>>>;
     fn stop(&self) -> Result<(), ReplicatorError>;
     fn get_meta(&self) -> &ReplicaMeta;
+    fn get_flavor(&self) -> BackendFlavor;
+    // Whether the initial sync from the master has finished.
+    fn is_synced(&self) -> bool;
 }
 
 #[derive(Debug, Clone)]
 pub struct ReplicatorMeta {
     pub epoch: u64,
     pub flags: ClusterMapFlags,
+    // When set, `masters` and `replicas` only include the added or changed replicators,
+    // and the ones not mentioned will be kept.
+    pub diff: bool,
     pub masters: Vec<MasterMeta>,
     pub replicas: Vec<ReplicaMeta>,
+    // The (cluster_name, node_address) of the replicators to remove in the diff mode.
+    pub removed: Vec<(ClusterName, String)>,
 }
 
 impl ReplicatorMeta {
@@ -54,46 +68,66 @@ pub struct ReplicaMeta {
     pub masters: Vec<ReplPeer>,
 }
 
+const DIFF_FLAG: &str = "DIFF";
+
 fn parse_repl_meta<T: AsRef<[u8]>>(resp: &Resp<T>) -> Result<ReplicatorMeta, CmdParseError> {
     let arr = match resp {
         Resp::Arr(Array::Arr(ref arr)) => arr,
-        _ => return Err(CmdParseError {}),
+        _ => return Err(CmdParseError::invalid("array")),
     };
 
     // Skip the "UMCTL SETREPL"
-    let it = arr.iter().skip(2).flat_map(|resp| match resp {
-        Resp::Bulk(BulkStr::Str(safe_str)) => match str::from_utf8(safe_str.as_ref()) {
-            Ok(s) => Some(s.to_string()),
-            _ => None,
-        },
-        _ => None,
-    });
-    let mut it = it.peekable();
+    let args = resp_array_to_args(arr, 2)?;
+    parse_args(args.into_iter(), 2, parse_repl_meta_args)
+}
 
-    let epoch_str = it.next().ok_or(CmdParseError {})?;
-    let epoch = epoch_str.parse::<u64>().map_err(|_e| CmdParseError {})?;
+fn parse_repl_meta_args<It>(it: &mut Peekable<It>) -> Result<ReplicatorMeta, CmdParseError>
+where
+    It: Iterator<Item = String>,
+{
+    let epoch_str = it.next().ok_or(CmdParseError::missing("epoch"))?;
+    let epoch = epoch_str
+        .parse::<u64>()
+        .map_err(|_e| CmdParseError::invalid("epoch"))?;
 
-    let flags = ClusterMapFlags::from_arg(&it.next().ok_or(CmdParseError {})?);
+    let flags_str = it.next().ok_or(CmdParseError::missing("flags"))?;
+    let flags = ClusterMapFlags::from_arg(&flags_str);
+    let diff = has_flags(&flags_str, ',', DIFF_FLAG);
 
     let mut master_meta_array = Vec::new();
     let mut replica_meta_array = Vec::new();
+    let mut removed = Vec::new();
 
     while it.peek().is_some() {
         let mut peers = Vec::new();
 
-        let role = it.next().ok_or(CmdParseError {})?;
-        let cluster_name = it.next().ok_or(CmdParseError {})?;
-        let cluster_name =
-            ClusterName::try_from(cluster_name.as_str()).map_err(|_| CmdParseError {})?;
-        let node_address = it.next().ok_or(CmdParseError {})?;
+        let role = it.next().ok_or(CmdParseError::missing("role"))?;
+        let cluster_name = it.next().ok_or(CmdParseError::missing("cluster name"))?;
+        let cluster_name = ClusterName::try_from(cluster_name.as_str())
+            .map_err(|_| CmdParseError::invalid("cluster name"))?;
+        let node_address = it.next().ok_or(CmdParseError::missing("node address"))?;
+        if role.to_uppercase() == "REMOVE" {
+            if !diff {
+                error!("remove is only allowed in the diff mode");
+                return Err(CmdParseError::invalid(
+                    "MASTER or REPLICA without DIFF flag",
+                ));
+            }
+            removed.push((cluster_name, node_address));
+            continue;
+        }
         let peer_num = it
             .next()
-            .ok_or(CmdParseError {})?
+            .ok_or(CmdParseError::missing("peer number"))?
             .parse::<usize>()
-            .map_err(|_| CmdParseError {})?;
+            .map_err(|_| CmdParseError::invalid("peer number"))?;
         for _ in 0..peer_num {
-            let node_address = it.next().ok_or(CmdParseError {})?;
-            let proxy_address = it.next().ok_or(CmdParseError {})?;
+            let node_address = it
+                .next()
+                .ok_or(CmdParseError::missing("peer node address"))?;
+            let proxy_address = it
+                .next()
+                .ok_or(CmdParseError::missing("peer proxy address"))?;
             peers.push(ReplPeer {
                 node_address,
                 proxy_address,
@@ -114,15 +148,17 @@ fn parse_repl_meta<T: AsRef<[u8]>>(resp: &Resp<T>) -> Result<ReplicatorMeta, Cmd
             })
         } else {
             error!("invalid role {}", role);
-            return Err(CmdParseError {});
+            return Err(CmdParseError::invalid("MASTER, REPLICA or REMOVE"));
         }
     }
 
     Ok(ReplicatorMeta {
         epoch,
         flags,
+        diff,
         masters: master_meta_array,
         replicas: replica_meta_array,
+        removed,
     })
 }
 
@@ -130,13 +166,20 @@ pub fn encode_repl_meta(meta: ReplicatorMeta) -> Vec<String> {
     let ReplicatorMeta {
         epoch,
         flags,
+        diff,
         masters,
         replicas,
+        removed,
     } = meta;
 
+    let flags_arg = match (diff, flags.force) {
+        (false, _) => flags.to_arg(),
+        (true, false) => DIFF_FLAG.to_string(),
+        (true, true) => format!("{},{}", flags.to_arg(), DIFF_FLAG),
+    };
     let mut args = Vec::new();
     args.push(epoch.to_string());
-    args.push(flags.to_arg());
+    args.push(flags_arg);
 
     for master in masters.iter() {
         args.push("master".to_string());
@@ -158,6 +201,11 @@ pub fn encode_repl_meta(meta: ReplicatorMeta) -> Vec<String> {
             args.push(master.proxy_address.clone());
         }
     }
+    for (cluster_name, node_address) in removed.into_iter() {
+        args.push("remove".to_string());
+        args.push(cluster_name.to_string());
+        args.push(node_address);
+    }
 
     args
 }
@@ -196,6 +244,7 @@ impl Error for ReplicatorError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::BulkStr;
 
     #[test]
     fn test_parse_and_encode_single_replicator() {
@@ -252,4 +301,33 @@ mod tests {
         let args = encode_repl_meta(meta.clone()).join(" ");
         assert_eq!(args, "233 NOFLAG master testcluster localhost:6000 1 localhost:6001 localhost:5299 replica testcluster localhost:6001 1 localhost:6000 localhost:5299")
     }
+
+    #[test]
+    fn test_parse_and_encode_diff() {
+        let arguments = "UMCTL SETREPL 233 force,diff master testcluster localhost:6000 1 localhost:6001 localhost:5299 remove testcluster localhost:7000"
+            .split(' ')
+            .map(|s| Resp::Bulk(BulkStr::Str(s.to_string().into_bytes())))
+            .collect();
+        let resp = Resp::Arr(Array::Arr(arguments));
+        let meta = parse_repl_meta(&resp).unwrap();
+        assert_eq!(meta.flags, ClusterMapFlags { force: true });
+        assert!(meta.diff);
+        assert_eq!(meta.masters.len(), 1);
+        assert_eq!(
+            meta.removed,
+            vec![(
+                ClusterName::try_from("testcluster").unwrap(),
+                "localhost:7000".to_string()
+            )]
+        );
+
+        let args = encode_repl_meta(meta).join(" ");
+        assert_eq!(args, "233 FORCE,DIFF master testcluster localhost:6000 1 localhost:6001 localhost:5299 remove testcluster localhost:7000");
+
+        let arguments = "UMCTL SETREPL 233 noflag remove testcluster localhost:7000"
+            .split(' ')
+            .map(|s| Resp::Bulk(BulkStr::Str(s.to_string().into_bytes())))
+            .collect();
+        assert!(parse_repl_meta(&Resp::Arr(Array::Arr(arguments))).is_err());
+    }
 }
@@ -19,7 +19,8 @@ mod tests {
     use std::time::Duration;
     use tokio;
     use undermoon::common::cluster::{
-        ClusterName, MigrationMeta, MigrationTaskMeta, Range, RangeList, SlotRange, SlotRangeTag,
+        ClusterName, MigrationMeta, MigrationTaskMeta, Range, RangeList, RestorePolicy, SlotRange,
+        SlotRangeTag,
     };
     use undermoon::common::config::ClusterConfig;
     use undermoon::common::proto::ProxyClusterMeta;
@@ -37,6 +38,7 @@ mod tests {
     use undermoon::proxy::manager::MetaMap;
     use undermoon::proxy::service::ServerProxyConfig;
     use undermoon::proxy::session::CmdCtx;
+    use undermoon::proxy::tls::TlsAcceptorHolder;
 
     const TEST_CLUSTER: &str = "test_cluster";
     type TestMetaManager = MetaManager<DummyClientFactory, DummyOkConnFactory>;
@@ -50,6 +52,7 @@ mod tests {
             slowlog_log_slower_than: AtomicI64::new(0),
             slowlog_sample_rate: AtomicU64::new(1),
             thread_number: NonZeroUsize::new(2).unwrap(),
+            thread_per_core: false,
             session_channel_size: 1024,
             backend_channel_size: 1024,
             // Should only be 1 so that when `wait_backend_ready` is done,
@@ -62,7 +65,27 @@ mod tests {
             session_batch_max_time: 10000,
             session_batch_buf: NonZeroUsize::new(50).unwrap(),
             active_redirection: false,
+            backend_redirection: false,
+            sentinel_addresses: vec![],
+            request_coalescing: false,
+            backend_concurrency_limit: 0,
+            backend_inflight_window: 0,
+            backend_session_fairness: true,
+            backend_idle_check_time: 0,
             max_redirections: None,
+            tls: TlsAcceptorHolder::default(),
+            command_timeout_fast: AtomicU64::new(0),
+            command_timeout_slow: AtomicU64::new(0),
+            command_timeout_blocking: AtomicU64::new(0),
+            accept_backlog: 1024,
+            max_accepts_per_tick: 0,
+            accept_storm_pause_time: 0,
+            session_panic_isolation: true,
+            reply_order_check: false,
+            read_after_write_window: 0,
+            replica_ack_timeout: 1000,
+            monitor_buffer_size: 1,
+            max_memory: AtomicU64::new(0),
         }
     }
 
@@ -216,6 +239,37 @@ mod tests {
         assert_ok_reply(reply_receiver).await;
     }
 
+    pub fn reply_key(cmd: Vec<String>) -> RespVec {
+        match cmd.get(1) {
+            Some(key) => Resp::Bulk(BulkStr::Str(key.clone().into_bytes())),
+            None => Resp::Simple(b"OK".to_vec()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_data_commands() {
+        let meta = gen_proxy_cluster_meta();
+        let mut config = gen_config();
+        config.backend_inflight_window = 8;
+        config.backend_batch_buf = NonZeroUsize::new(3).unwrap();
+        let manager = gen_testing_manager(Arc::new(reply_key), config);
+
+        manager.set_meta(meta).unwrap();
+        wait_backend_ready(&manager).await;
+
+        let mut reply_receivers = vec![];
+        for i in 0..100 {
+            let key = format!("key{}", i).into_bytes();
+            let (cmd_ctx, reply_receiver) = gen_set_command(key.clone());
+            manager.send(cmd_ctx);
+            reply_receivers.push((key, reply_receiver));
+        }
+        for (key, reply_receiver) in reply_receivers.into_iter() {
+            let (_, response, _) = reply_receiver.await.unwrap().into_inner();
+            assert_eq!(response.into_resp_vec(), Resp::Bulk(BulkStr::Str(key)));
+        }
+    }
+
     fn gen_migration_cluster_meta(is_source_proxy: bool) -> ProxyClusterMeta {
         gen_migration_cluster_meta_helper(is_source_proxy, 233, "127.0.0.1:5299", "127.0.0.1:6000")
     }
@@ -396,6 +450,7 @@ mod tests {
                         src_node_address: "127.0.0.1:6379".to_string(),
                         dst_proxy_address: dst_proxy_address.to_string(),
                         dst_node_address: "127.0.0.1:7000".to_string(),
+                        restore_policy: RestorePolicy::default(),
                     }),
                 },
             },